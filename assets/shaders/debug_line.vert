@@ -0,0 +1,17 @@
+#version 460
+
+// Debug lines (see debug_draw.rs). Positions are camera-relative, the same
+// space tri.vert's model matrices map into, so view_proj applies directly.
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_proj;
+} ubo;
+
+layout(location = 0) in vec3 in_pos;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    v_color = in_color;
+    gl_Position = ubo.view_proj * vec4(in_pos, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 460
+
+// Fullscreen pass vertex shader: no vertex buffer, draw(3). The three
+// vertices form one oversized triangle whose UVs run (0,0)..(2,2), so the
+// viewport is covered exactly once with no diagonal seam. UV (0,0) is the
+// top-left texel, matching Vulkan's +Y-down NDC with a non-flipped viewport.
+
+layout(location = 0) out vec2 v_uv;
+
+void main() {
+    v_uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 460
+
+// Object-id pass (see picking.rs): tri.vert's geometry, each fragment
+// tagged with its draw's ObjectId. The pass pushes that in PushData's
+// first padding word; 0 = no object.
+
+layout(push_constant) uniform Push {
+    mat4 model;
+    vec4 tint;
+    uint tex_index;
+    uint object_id;
+} pc;
+
+layout(location = 0) out uint out_id;
+
+void main() {
+    out_id = pc.object_id;
+}
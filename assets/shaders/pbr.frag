@@ -0,0 +1,268 @@
+#version 460
+#extension GL_EXT_nonuniform_qualifier : require
+
+// Metallic-roughness PBR (see material.rs ShadingModel): tri.frag's inputs
+// and descriptors, shaded with GGX / Smith / Schlick for the directional
+// light and the light list, and the environment's IBL maps (or the flat
+// ambient term without them) for ambient light.
+
+layout(location = 0) in vec3 v_color;
+layout(location = 1) in vec2 v_uv;
+layout(location = 2) in vec3 v_normal;
+layout(location = 3) flat in uint v_tex_index;
+layout(location = 4) in vec3 v_rel_pos;
+
+#define MAX_CASCADES 4
+
+// Mirrors resources.rs CameraUbo.
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_proj;
+    mat4 cascade_view_proj[MAX_CASCADES];
+    vec4 cascade_splits;
+    vec4 view_forward;
+    vec4 light_dir;     // towards the light; w = shadows on
+    vec4 light_color;   // rgb * intensity; w = ambient
+    vec4 shadow_params; // cascade count, normal offset, 1 / resolution
+    uvec4 mesh_pools;
+    uvec4 cluster_grid; // tiles across, down, depth slices; w = 1 to use the clusters
+    vec4 cluster_depth; // tile size in pixels, slice = log(depth) * z + w
+    vec4 ibl_params;    // x = 1 with the IBL maps bound, y = prefiltered map's last level
+} ubo;
+
+layout(set = 0, binding = 1) uniform sampler2DArrayShadow shadow_map;
+
+#define MAX_LIGHTS 1024
+#define MAX_CLUSTER_LIGHTS 64u
+
+// Mirrors lighting.rs GpuLight / LightList.
+struct Light {
+    vec4 position; // point: camera-relative, w = 1; directional: towards the light, w = 0
+    vec4 color;    // rgb * intensity; w = range
+};
+layout(std430, set = 0, binding = 2) readonly buffer Lights {
+    uvec4 count;
+    Light lights[MAX_LIGHTS];
+} light_list;
+
+// Per cluster a light count, then that many indices into light_list (see
+// light_clusters.rs).
+layout(std430, set = 0, binding = 3) readonly buffer Clusters {
+    uint cluster_lights[];
+};
+
+// The environment's diffuse irradiance, prefiltered specular (one level
+// per roughness) and the split-sum BRDF LUT (see ibl.rs).
+layout(set = 0, binding = 4) uniform samplerCube irradiance_map;
+layout(set = 0, binding = 5) uniform samplerCube prefiltered_map;
+layout(set = 0, binding = 6) uniform sampler2D brdf_lut;
+
+layout(set = 1, binding = 0) uniform sampler2D textures[];
+
+// Mirrors material.rs MaterialParams.
+layout(set = 1, binding = 1) uniform Material {
+    vec4 tint;
+    uvec4 albedo;  // x = texture index, y = 1 if it overrides v_tex_index,
+                   // z = 1 to sample layer v_tex_index of block_textures
+    vec4 surface;  // x = roughness, y = metallic, z = occlusion strength, w = normal scale
+    uvec4 maps;    // normal, metallic-roughness, occlusion, emissive texture; 0 = none
+    vec4 emissive; // rgb = emissive factor
+} material;
+
+// Block-face textures, one per layer (see block_textures.rs).
+layout(set = 1, binding = 2) uniform sampler2DArray block_textures;
+
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+
+// 1 = lit, 0 = fully shadowed. 3x3 PCF over the cascade the fragment's
+// view depth falls in; beyond the last one everything is lit.
+float shadow_factor(vec3 n) {
+    int count = int(ubo.shadow_params.x);
+    float depth = dot(v_rel_pos, ubo.view_forward.xyz);
+    int cascade = 0;
+    while (cascade < count && depth > ubo.cascade_splits[cascade]) {
+        cascade++;
+    }
+    if (cascade >= count) {
+        return 1.0;
+    }
+    vec3 pos = v_rel_pos + n * ubo.shadow_params.y;
+    vec4 clip = ubo.cascade_view_proj[cascade] * vec4(pos, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    // The light projection follows the scene's flipped viewport: +y up.
+    vec2 uv = vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    float texel = ubo.shadow_params.z;
+    float lit = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 o = vec2(x, y) * texel;
+            lit += texture(shadow_map, vec4(uv + o, float(cascade), ndc.z));
+        }
+    }
+    return lit / 9.0;
+}
+
+// upload_texture decodes every texture as sRGB; the data maps (normal,
+// metallic-roughness, occlusion) get their stored values back by encoding
+// again. Exact at texel centres, close enough where filtering blends.
+vec3 srgb_encode(vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec4 sample_map(uint index) {
+    return texture(textures[nonuniformEXT(index)], v_uv);
+}
+
+// The shading normal: the normal map through a cotangent frame built from
+// screen-space derivatives, as meshes carry no tangents.
+vec3 shading_normal() {
+    vec3 n = normalize(v_normal);
+    if (material.maps.x == 0u) {
+        return n;
+    }
+    vec3 t = srgb_encode(sample_map(material.maps.x).rgb) * 2.0 - 1.0;
+    t.xy *= material.surface.w;
+    vec3 dp1 = dFdx(v_rel_pos);
+    vec3 dp2 = dFdy(v_rel_pos);
+    vec2 duv1 = dFdx(v_uv);
+    vec2 duv2 = dFdy(v_uv);
+    vec3 dp2perp = cross(dp2, n);
+    vec3 dp1perp = cross(n, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = inversesqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+    if (isinf(scale) || isnan(scale)) {
+        return n;
+    }
+    return normalize(mat3(tangent * scale, bitangent * scale, n) * t);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Cook-Torrance for light arriving from `l` with `radiance`.
+vec3 brdf(vec3 n, vec3 v, vec3 l, vec3 radiance, vec3 albedo, float metallic, float roughness,
+          vec3 f0) {
+    float n_dot_l = max(dot(n, l), 0.0);
+    if (n_dot_l <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 h = normalize(v + l);
+    float n_dot_v = max(dot(n, v), 1e-4);
+    float n_dot_h = max(dot(n, h), 0.0);
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    float ndf = a2 / (PI * d * d);
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float g = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+    vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    vec3 specular = ndf * g * f / (4.0 * n_dot_v * n_dot_l);
+    vec3 kd = (1.0 - f) * (1.0 - metallic);
+    return (kd * albedo / PI + specular) * radiance * n_dot_l;
+}
+
+// One list light's direction and radiance at the fragment, with tri.frag's
+// falloff.
+vec3 list_light(Light l, vec3 n, vec3 v, vec3 albedo, float metallic, float roughness, vec3 f0) {
+    vec3 to_light = l.position.xyz;
+    float atten = 1.0;
+    if (l.position.w > 0.5) {
+        to_light -= v_rel_pos;
+        float d = length(to_light);
+        to_light /= max(d, 1e-4);
+        float w = clamp(1.0 - pow(d / l.color.w, 4.0), 0.0, 1.0);
+        atten = w * w / (d * d + 1.0);
+    }
+    return brdf(n, v, to_light, l.color.rgb * atten * PI, albedo, metallic, roughness, f0);
+}
+
+// As tri.frag's list_lighting: the fragment's cluster's lights when the
+// light cluster pass ran, else every light.
+vec3 list_lighting(vec3 n, vec3 v, vec3 albedo, float metallic, float roughness, vec3 f0) {
+    vec3 sum = vec3(0.0);
+    if (ubo.cluster_grid.w != 0u) {
+        uvec3 grid = ubo.cluster_grid.xyz;
+        uvec2 tile = min(uvec2(gl_FragCoord.xy / ubo.cluster_depth.xy), grid.xy - 1u);
+        float depth = max(dot(v_rel_pos, ubo.view_forward.xyz), 1e-4);
+        float slice = log(depth) * ubo.cluster_depth.z + ubo.cluster_depth.w;
+        uint z = uint(clamp(slice, 0.0, float(grid.z - 1u)));
+        uint base = ((z * grid.y + tile.y) * grid.x + tile.x) * (MAX_CLUSTER_LIGHTS + 1u);
+        uint count = min(cluster_lights[base], MAX_CLUSTER_LIGHTS);
+        for (uint i = 0u; i < count; i++) {
+            Light l = light_list.lights[cluster_lights[base + 1u + i]];
+            sum += list_light(l, n, v, albedo, metallic, roughness, f0);
+        }
+        return sum;
+    }
+    uint count = min(light_list.count.x, uint(MAX_LIGHTS));
+    for (uint i = 0u; i < count; i++) {
+        sum += list_light(light_list.lights[i], n, v, albedo, metallic, roughness, f0);
+    }
+    return sum;
+}
+
+void main() {
+    uint tex_index = material.albedo.y != 0u ? material.albedo.x : v_tex_index;
+    vec4 base = material.albedo.z != 0u
+        ? texture(block_textures, vec3(v_uv, float(v_tex_index)))
+        : texture(textures[nonuniformEXT(tex_index)], v_uv);
+    base *= material.tint;
+    vec3 albedo = base.rgb * v_color;
+
+    // glTF packs roughness in g and metalness in b.
+    float roughness = material.surface.x;
+    float metallic = material.surface.y;
+    if (material.maps.y != 0u) {
+        vec3 mr = srgb_encode(sample_map(material.maps.y).rgb);
+        roughness *= mr.g;
+        metallic *= mr.b;
+    }
+    roughness = clamp(roughness, 0.04, 1.0);
+    metallic = clamp(metallic, 0.0, 1.0);
+    float occlusion = 1.0;
+    if (material.maps.z != 0u) {
+        float ao = srgb_encode(sample_map(material.maps.z).rgb).r;
+        occlusion = mix(1.0, ao, material.surface.z);
+    }
+
+    vec3 n = shading_normal();
+    vec3 v = normalize(-v_rel_pos);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+
+    // Direct light keeps tri.frag's split (ambient takes its share of the
+    // light's intensity) and its brightness: lights' radiance is scaled by
+    // PI, so a rough white dielectric lights as tri.frag's Lambert does.
+    float ambient = ubo.light_color.w;
+    vec3 to_light = ubo.light_dir.xyz;
+    float shadow = ubo.light_dir.w > 0.5 && dot(n, to_light) > 0.0 ? shadow_factor(n) : 1.0;
+    vec3 sun = ubo.light_color.rgb * shadow * PI;
+    vec3 direct = brdf(n, v, to_light, sun, albedo, metallic, roughness, f0)
+        + list_lighting(n, v, albedo, metallic, roughness, f0);
+
+    vec3 ambient_light;
+    float n_dot_v = max(dot(n, v), 1e-4);
+    if (ubo.ibl_params.x > 0.5) {
+        vec3 f = f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+        vec3 kd = (1.0 - f) * (1.0 - metallic);
+        vec3 diffuse = texture(irradiance_map, n).rgb * albedo;
+        vec3 r = reflect(-v, n);
+        vec3 prefiltered = textureLod(prefiltered_map, r, roughness * ubo.ibl_params.y).rgb;
+        vec2 scale_bias = texture(brdf_lut, vec2(n_dot_v, roughness)).rg;
+        vec3 specular = prefiltered * (f * scale_bias.x + scale_bias.y);
+        ambient_light = ambient * (kd * diffuse + specular);
+    } else {
+        ambient_light = ambient * albedo;
+    }
+
+    vec3 emissive = material.emissive.rgb;
+    if (material.maps.w != 0u) {
+        emissive *= sample_map(material.maps.w).rgb;
+    }
+
+    vec3 color = ambient_light * occlusion + (1.0 - ambient) * direct + emissive;
+    outColor = vec4(color, base.a);
+}
@@ -0,0 +1,148 @@
+#version 460
+
+// Post-processing chain (see post.rs): one fullscreen pass per step, the
+// step picked by pc.mode. Input and output are linear FP16, 1.0 = paper
+// white, ahead of the tonemap pass.
+
+layout(location = 0) in vec2 v_uv;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+// The blurred bloom target for the composite; the source again otherwise.
+layout(set = 0, binding = 1) uniform sampler2D bloom;
+// Colour grading LUT as a strip of lut_size slices (blue), each
+// lut_size x lut_size (red across, green down); sRGB texels.
+layout(set = 0, binding = 2) uniform sampler2D lut;
+
+layout(push_constant) uniform Post {
+    vec2 texel;    // 1 / source size
+    uint mode;     // 0 = bloom bright pass, 1 = blur H, 2 = blur V,
+                   // 3 = bloom composite, 4 = vignette, 5 = FXAA, 6 = LUT,
+                   // 7 = colour filter
+    uint lut_size; // 0 = no LUT, grading passes through
+    float param;   // bloom threshold / bloom intensity / vignette strength
+    // mode 7 = accessibility colour filter: this 3x3 matrix, one row per
+    // vec4 (see color_filter.rs).
+    vec4 filter_rows[3];
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+// Luma squeezed into [0, 1), so FXAA's thresholds mean the same for HDR
+// values as for SDR ones.
+float luma(vec3 c) {
+    float l = dot(c, vec3(0.299, 0.587, 0.114));
+    return l / (1.0 + l);
+}
+
+// Four bilinear taps: a 4x4 box of the full-size source per half-size
+// texel, then a soft knee at the threshold so bloom fades in.
+vec3 bright_pass() {
+    vec3 c = vec3(0.0);
+    c += texture(source, v_uv + pc.texel * vec2(-1.0, -1.0)).rgb;
+    c += texture(source, v_uv + pc.texel * vec2(1.0, -1.0)).rgb;
+    c += texture(source, v_uv + pc.texel * vec2(-1.0, 1.0)).rgb;
+    c += texture(source, v_uv + pc.texel * vec2(1.0, 1.0)).rgb;
+    c = max(c * 0.25, vec3(0.0));
+    float peak = max(c.r, max(c.g, c.b));
+    float knee = 0.5 * pc.param;
+    float soft = clamp(peak - pc.param + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    float keep = max(soft, peak - pc.param) / max(peak, 1e-4);
+    return c * keep;
+}
+
+// 9-tap Gaussian in five bilinear taps along `dir`.
+vec3 blur(vec2 dir) {
+    const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+    const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+    vec3 c = texture(source, v_uv).rgb * weights[0];
+    for (int i = 1; i < 3; i++) {
+        vec2 o = dir * pc.texel * offsets[i];
+        c += texture(source, v_uv + o).rgb * weights[i];
+        c += texture(source, v_uv - o).rgb * weights[i];
+    }
+    return c;
+}
+
+vec3 vignette(vec3 c) {
+    // 0 at the centre, 1 in the corners.
+    float d = length(v_uv - 0.5) * 1.41421356;
+    return c * (1.0 - pc.param * d * d);
+}
+
+// FXAA in the style of Lottes' original PC version: find the edge
+// direction from the diagonal neighbours and blend along it.
+vec3 fxaa() {
+    const float REDUCE_MIN = 1.0 / 128.0;
+    const float REDUCE_MUL = 1.0 / 8.0;
+    const float SPAN_MAX = 8.0;
+    vec3 rgb_m = texture(source, v_uv).rgb;
+    float nw = luma(texture(source, v_uv + pc.texel * vec2(-1.0, -1.0)).rgb);
+    float ne = luma(texture(source, v_uv + pc.texel * vec2(1.0, -1.0)).rgb);
+    float sw = luma(texture(source, v_uv + pc.texel * vec2(-1.0, 1.0)).rgb);
+    float se = luma(texture(source, v_uv + pc.texel * vec2(1.0, 1.0)).rgb);
+    float m = luma(rgb_m);
+    float lo = min(m, min(min(nw, ne), min(sw, se)));
+    float hi = max(m, max(max(nw, ne), max(sw, se)));
+
+    vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcp_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * rcp_min, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * pc.texel;
+
+    vec3 a = 0.5 * (texture(source, v_uv + dir * (1.0 / 3.0 - 0.5)).rgb
+        + texture(source, v_uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 b = 0.5 * a + 0.25 * (texture(source, v_uv - dir * 0.5).rgb
+        + texture(source, v_uv + dir * 0.5).rgb);
+    float lb = luma(b);
+    return (lb < lo || lb > hi) ? a : b;
+}
+
+vec3 srgb_encode(vec3 linear) {
+    vec3 lo = linear * 12.92;
+    vec3 hi = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(hi, lo, lessThanEqual(linear, vec3(0.0031308)));
+}
+
+// LUTs are authored on display-referred sRGB, so look up the sRGB encoding
+// of the part up to paper white; the LUT's texels decode back to linear on
+// sampling. Whatever lies above 1.0 is added back ungraded.
+vec3 grade(vec3 c) {
+    if (pc.lut_size == 0u) {
+        return c;
+    }
+    float n = float(pc.lut_size);
+    vec3 x = srgb_encode(clamp(c, 0.0, 1.0));
+    float slice = x.b * (n - 1.0);
+    float s0 = floor(slice);
+    float s1 = min(s0 + 1.0, n - 1.0);
+    vec2 xy = (x.rg * (n - 1.0) + 0.5) / vec2(n * n, n);
+    vec3 g0 = textureLod(lut, xy + vec2(s0 / n, 0.0), 0.0).rgb;
+    vec3 g1 = textureLod(lut, xy + vec2(s1 / n, 0.0), 0.0).rgb;
+    return mix(g0, g1, slice - s0) + max(c - 1.0, vec3(0.0));
+}
+
+void main() {
+    vec3 c;
+    if (pc.mode == 0u) {
+        c = bright_pass();
+    } else if (pc.mode == 1u) {
+        c = blur(vec2(1.0, 0.0));
+    } else if (pc.mode == 2u) {
+        c = blur(vec2(0.0, 1.0));
+    } else if (pc.mode == 3u) {
+        c = texture(source, v_uv).rgb + pc.param * texture(bloom, v_uv).rgb;
+    } else if (pc.mode == 4u) {
+        c = vignette(texture(source, v_uv).rgb);
+    } else if (pc.mode == 5u) {
+        c = fxaa();
+    } else if (pc.mode == 7u) {
+        c = texture(source, v_uv).rgb;
+        // Daltonizing can push a channel below zero.
+        c = max(vec3(dot(pc.filter_rows[0].rgb, c), dot(pc.filter_rows[1].rgb, c),
+            dot(pc.filter_rows[2].rgb, c)), vec3(0.0));
+    } else {
+        c = grade(texture(source, v_uv).rgb);
+    }
+    outColor = vec4(c, 1.0);
+}
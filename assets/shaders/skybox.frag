@@ -0,0 +1,12 @@
+#version 460
+
+// Linear colour like tri.frag's; the tonemap pass (or an sRGB swapchain)
+// encodes it.
+layout(set = 0, binding = 0) uniform samplerCube sky;
+
+layout(location = 0) in vec3 v_dir;
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = vec4(texture(sky, normalize(v_dir)).rgb, 1.0);
+}
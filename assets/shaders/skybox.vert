@@ -0,0 +1,20 @@
+#version 460
+
+// Skybox (see skybox.rs): one fullscreen triangle at the far plane, which
+// is z = 0 with reverse-Z. Each corner is unprojected onto the near plane
+// through the inverse of the rotation-only view_proj; that point, relative
+// to the camera, is the view direction. It's affine across the screen, so
+// interpolating it is exact.
+
+layout(push_constant) uniform Sky {
+    mat4 inv_view_proj;
+} pc;
+
+layout(location = 0) out vec3 v_dir;
+
+void main() {
+    vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    vec4 near = pc.inv_view_proj * vec4(ndc, 1.0, 1.0);
+    v_dir = near.xyz / near.w;
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}
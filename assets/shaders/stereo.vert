@@ -0,0 +1,65 @@
+#version 460
+#extension GL_EXT_multiview : require
+
+// tri.vert for the stereo pass (see stereo.rs): one draw renders both
+// eyes, gl_ViewIndex picking the layer and the eye's camera block. The
+// stereo camera set's binding 0 covers two CameraUbo blocks back to back;
+// tri.frag, reading the first, lights both eyes as the left one.
+
+#define MAX_CASCADES 4
+
+// Mirrors resources.rs CameraUbo.
+struct Eye {
+    mat4 view_proj;
+    mat4 cascade_view_proj[MAX_CASCADES];
+    vec4 cascade_splits;
+    vec4 view_forward;
+    vec4 light_dir;
+    vec4 light_color;
+    vec4 shadow_params;
+    uvec4 mesh_pools;
+    uvec4 cluster_grid;
+    vec4 cluster_depth;
+    vec4 ibl_params;
+};
+layout(set = 0, binding = 0) uniform Camera {
+    Eye eyes[2];
+} ubo;
+
+// As in tri.vert.
+struct Candidate {
+    mat4 model;
+    vec4 tint;
+    uint first_vertex;
+    uint first_index;
+    uint index_count;
+    uint tex_index;
+};
+layout(std430, set = 2, binding = 0) readonly buffer Candidates {
+    Candidate candidates[];
+};
+
+layout(location = 0) in vec3 in_pos;
+layout(location = 1) in vec3 in_color;
+layout(location = 2) in vec2 in_uv;
+layout(location = 3) in vec3 in_normal;
+layout(location = 4) in uint in_tex_index;
+
+layout(location = 0) out vec3 v_color;
+layout(location = 1) out vec2 v_uv;
+layout(location = 2) out vec3 v_normal;
+layout(location = 3) flat out uint v_tex_index;
+layout(location = 4) out vec3 v_rel_pos;
+
+void main() {
+    Candidate c = candidates[gl_InstanceIndex];
+
+    vec4 rel_pos = c.model * vec4(in_pos, 1.0);
+    gl_Position = ubo.eyes[gl_ViewIndex].view_proj * rel_pos;
+    v_rel_pos = rel_pos.xyz;
+
+    v_color = in_color * c.tint.rgb;
+    v_uv = in_uv;
+    v_normal = mat3(c.model) * in_normal;
+    v_tex_index = in_tex_index != 0u ? in_tex_index : c.tex_index;
+}
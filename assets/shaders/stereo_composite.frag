@@ -0,0 +1,19 @@
+#version 460
+
+// Side-by-side composite of the stereo target (see stereo.rs): drawn once
+// per eye with the viewport over that eye's half of the scene target,
+// copying the eye's layer 1:1.
+
+layout(location = 0) in vec2 v_uv;
+
+layout(set = 0, binding = 0) uniform sampler2DArray eyes;
+
+layout(push_constant) uniform Composite {
+    uint layer;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(texture(eyes, vec3(v_uv, float(pc.layer))).rgb, 1.0);
+}
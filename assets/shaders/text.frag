@@ -0,0 +1,55 @@
+#version 460
+#extension GL_EXT_nonuniform_qualifier : require
+
+// Screen-space text: the font atlas's alpha is glyph coverage, the colour
+// is linear and gets whatever encoding the swapchain needs, since text is
+// drawn after the tonemap pass (see text.rs / text_encoding).
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 1) in vec4 v_color;
+
+// The bindless texture array (the default material's set 1, bound here as
+// set 0); the atlas is one of its entries.
+layout(set = 0, binding = 0) uniform sampler2D textures[];
+
+layout(push_constant) uniform Text {
+    vec2 screen_size;
+    uint tex_index;
+    uint encoding;          // 0 = scRGB, 1 = HDR10 PQ, 2 = sRGB, 3 = as is
+    float paper_white_nits; // brightness of colour 1.0 on HDR swapchains
+    mat3 gamut;             // BT.709 -> the colour space's primaries
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 pq_encode(vec3 nits) {
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+vec3 srgb_encode(vec3 linear) {
+    vec3 lo = linear * 12.92;
+    vec3 hi = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(hi, lo, lessThanEqual(linear, vec3(0.0031308)));
+}
+
+void main() {
+    float coverage = texture(textures[pc.tex_index], v_uv).a;
+    vec3 rgb = clamp(v_color.rgb, 0.0, 1.0);
+    if (pc.encoding != 3u) {
+        rgb = pc.gamut * rgb;
+    }
+    if (pc.encoding == 0u) {
+        rgb = rgb * (pc.paper_white_nits / 80.0);
+    } else if (pc.encoding == 1u) {
+        rgb = pq_encode(rgb * pc.paper_white_nits);
+    } else if (pc.encoding == 2u) {
+        rgb = srgb_encode(rgb);
+    }
+    outColor = vec4(rgb, v_color.a * coverage);
+}
@@ -0,0 +1,25 @@
+#version 460
+
+// Screen-space text quads (see text.rs). Positions arrive in pixels from
+// the top-left corner, which is Vulkan's +Y-down NDC with a non-flipped
+// viewport once scaled to -1..1.
+
+layout(location = 0) in vec2 in_pos;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec4 in_color;
+
+layout(push_constant) uniform Text {
+    vec2 screen_size;
+    uint tex_index;
+    uint encoding;
+    float paper_white_nits;
+} pc;
+
+layout(location = 0) out vec2 v_uv;
+layout(location = 1) out vec4 v_color;
+
+void main() {
+    v_uv = in_uv;
+    v_color = in_color;
+    gl_Position = vec4(in_pos / pc.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,88 @@
+#version 460
+
+// HDR tonemap + output encoding. Input is the scene's FP16 target: linear
+// BT.709 with 1.0 = paper white. Output is whatever the swapchain's colour
+// space expects: `gamut` takes the colour into its primaries and
+// `encoding` picks its transfer function (see tonemap.rs / OutputEncode).
+
+layout(location = 0) in vec2 v_uv;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+layout(push_constant) uniform Tonemap {
+    mat3 gamut;             // BT.709 -> the colour space's primaries
+    uint op;                // 0 = ACES (fitted), 1 = Reinhard, 2 = clamp
+    uint encoding;          // 0 = scRGB linear, 1 = HDR10 PQ, 2 = sRGB (SDR),
+                            // 3 = passthrough (upscale only, *_SRGB swapchain)
+    float paper_white_nits; // brightness of scene value 1.0
+    float peak_nits;        // display peak the curve's shoulder maps to
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+// Krzysztof Narkowicz's ACES filmic fit.
+vec3 aces_fitted(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+vec3 reinhard(vec3 x) {
+    return x / (1.0 + x);
+}
+
+// SMPTE ST 2084 inverse EOTF: absolute nits -> PQ code value.
+vec3 pq_encode(vec3 nits) {
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+// sRGB OETF, for UNORM swapchains the hardware doesn't encode on write.
+vec3 srgb_encode(vec3 linear) {
+    vec3 lo = linear * 12.92;
+    vec3 hi = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(hi, lo, lessThanEqual(linear, vec3(0.0031308)));
+}
+
+void main() {
+    vec3 scene_rgb = max(texture(scene, v_uv).rgb, vec3(0.0));
+
+    // SDR: no curve, just what an *_SRGB swapchain would have stored.
+    if (pc.encoding == 2u) {
+        outColor = vec4(srgb_encode(clamp(pc.gamut * scene_rgb, 0.0, 1.0)), 1.0);
+        return;
+    }
+    // Scaled scene on an *_SRGB swapchain: the hardware encodes on write.
+    if (pc.encoding == 3u) {
+        outColor = vec4(min(scene_rgb, vec3(1.0)), 1.0);
+        return;
+    }
+
+    // Run the curve in peak-relative units so its shoulder lands on the
+    // display's peak rather than on paper white.
+    vec3 x = scene_rgb * (pc.paper_white_nits / pc.peak_nits);
+    vec3 mapped;
+    if (pc.op == 0u) {
+        mapped = aces_fitted(x);
+    } else if (pc.op == 1u) {
+        mapped = reinhard(x);
+    } else {
+        mapped = clamp(x, 0.0, 1.0);
+    }
+    vec3 nits = pc.gamut * (mapped * pc.peak_nits);
+
+    if (pc.encoding == 1u) {
+        outColor = vec4(pq_encode(nits), 1.0);
+    } else {
+        // scRGB: linear, 1.0 = 80 nits, values above 1.0 are HDR.
+        outColor = vec4(nits / 80.0, 1.0);
+    }
+}
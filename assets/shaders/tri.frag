@@ -5,18 +5,151 @@ layout(location = 0) in vec3 v_color;
 layout(location = 1) in vec2 v_uv;
 layout(location = 2) in vec3 v_normal;
 layout(location = 3) flat in uint v_tex_index;
+layout(location = 4) in vec3 v_rel_pos;
+
+#define MAX_CASCADES 4
+
+// Mirrors resources.rs CameraUbo.
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_proj;
+    mat4 cascade_view_proj[MAX_CASCADES];
+    vec4 cascade_splits;
+    vec4 view_forward;
+    vec4 light_dir;     // towards the light; w = shadows on
+    vec4 light_color;   // rgb * intensity; w = ambient
+    vec4 shadow_params; // cascade count, normal offset, 1 / resolution
+    uvec4 mesh_pools;
+    uvec4 cluster_grid; // tiles across, down, depth slices; w = 1 to use the clusters
+    vec4 cluster_depth; // tile size in pixels, slice = log(depth) * z + w
+    vec4 ibl_params;    // x = 1 with the IBL maps bound, y = prefiltered map's last level
+} ubo;
+
+layout(set = 0, binding = 1) uniform sampler2DArrayShadow shadow_map;
+
+#define MAX_LIGHTS 1024
+#define MAX_CLUSTER_LIGHTS 64u
+
+// Mirrors lighting.rs GpuLight / LightList.
+struct Light {
+    vec4 position; // point: camera-relative, w = 1; directional: towards the light, w = 0
+    vec4 color;    // rgb * intensity; w = range
+};
+layout(std430, set = 0, binding = 2) readonly buffer Lights {
+    uvec4 count;
+    Light lights[MAX_LIGHTS];
+} light_list;
+
+// Per cluster a light count, then that many indices into light_list (see
+// light_clusters.rs).
+layout(std430, set = 0, binding = 3) readonly buffer Clusters {
+    uint cluster_lights[];
+};
+
+// The environment's diffuse irradiance (see ibl.rs); bindings 5 and 6 hold
+// its prefiltered specular map and the BRDF LUT.
+layout(set = 0, binding = 4) uniform samplerCube irradiance_map;
 
 layout(set = 1, binding = 0) uniform sampler2D textures[];
 
+// Mirrors material.rs MaterialParams.
+layout(set = 1, binding = 1) uniform Material {
+    vec4 tint;
+    uvec4 albedo;  // x = texture index, y = 1 if it overrides v_tex_index,
+                   // z = 1 to sample layer v_tex_index of block_textures
+    vec4 surface;  // x = roughness, y = metallic, z = occlusion strength, w = normal scale
+    uvec4 maps;    // normal, metallic-roughness, occlusion, emissive texture; 0 = none
+    vec4 emissive; // rgb = emissive factor
+} material;
+
+// Block-face textures, one per layer (see block_textures.rs).
+layout(set = 1, binding = 2) uniform sampler2DArray block_textures;
+
 layout(location = 0) out vec4 outColor;
 
+// 1 = lit, 0 = fully shadowed. 3x3 PCF over the cascade the fragment's
+// view depth falls in; beyond the last one everything is lit.
+float shadow_factor(vec3 n) {
+    int count = int(ubo.shadow_params.x);
+    float depth = dot(v_rel_pos, ubo.view_forward.xyz);
+    int cascade = 0;
+    while (cascade < count && depth > ubo.cascade_splits[cascade]) {
+        cascade++;
+    }
+    if (cascade >= count) {
+        return 1.0;
+    }
+    vec3 pos = v_rel_pos + n * ubo.shadow_params.y;
+    vec4 clip = ubo.cascade_view_proj[cascade] * vec4(pos, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    // The light projection follows the scene's flipped viewport: +y up.
+    vec2 uv = vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    float texel = ubo.shadow_params.z;
+    float lit = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 o = vec2(x, y) * texel;
+            lit += texture(shadow_map, vec4(uv + o, float(cascade), ndc.z));
+        }
+    }
+    return lit / 9.0;
+}
+
+// Lambert diffuse from one light (no shadows). Point lights fall off with
+// inverse square, windowed to reach zero at their range.
+vec3 light_contribution(Light l, vec3 n) {
+    vec3 to_light = l.position.xyz;
+    float atten = 1.0;
+    if (l.position.w > 0.5) {
+        to_light -= v_rel_pos;
+        float d = length(to_light);
+        to_light /= max(d, 1e-4);
+        float w = clamp(1.0 - pow(d / l.color.w, 4.0), 0.0, 1.0);
+        atten = w * w / (d * d + 1.0);
+    }
+    return max(dot(n, to_light), 0.0) * atten * l.color.rgb;
+}
+
+// The light list's contribution: from the fragment's cluster's lights
+// when the light cluster pass ran, else from every light.
+vec3 list_lighting(vec3 n) {
+    vec3 sum = vec3(0.0);
+    if (ubo.cluster_grid.w != 0u) {
+        uvec3 grid = ubo.cluster_grid.xyz;
+        uvec2 tile = min(uvec2(gl_FragCoord.xy / ubo.cluster_depth.xy), grid.xy - 1u);
+        float depth = max(dot(v_rel_pos, ubo.view_forward.xyz), 1e-4);
+        float slice = log(depth) * ubo.cluster_depth.z + ubo.cluster_depth.w;
+        uint z = uint(clamp(slice, 0.0, float(grid.z - 1u)));
+        uint base = ((z * grid.y + tile.y) * grid.x + tile.x) * (MAX_CLUSTER_LIGHTS + 1u);
+        uint count = min(cluster_lights[base], MAX_CLUSTER_LIGHTS);
+        for (uint i = 0u; i < count; i++) {
+            sum += light_contribution(light_list.lights[cluster_lights[base + 1u + i]], n);
+        }
+        return sum;
+    }
+    uint count = min(light_list.count.x, uint(MAX_LIGHTS));
+    for (uint i = 0u; i < count; i++) {
+        sum += light_contribution(light_list.lights[i], n);
+    }
+    return sum;
+}
+
 void main() {
-    vec4 texel = texture(textures[nonuniformEXT(v_tex_index)], v_uv);
+    uint tex_index = material.albedo.y != 0u ? material.albedo.x : v_tex_index;
+    vec4 texel = material.albedo.z != 0u
+        ? texture(block_textures, vec3(v_uv, float(v_tex_index)))
+        : texture(textures[nonuniformEXT(tex_index)], v_uv);
+    texel *= material.tint;
 
-    vec3 sun_dir = normalize(vec3(0.5, 1.0, 0.3));
-    float diffuse = max(dot(normalize(v_normal), sun_dir), 0.0);
-    float ambient = 0.4;
-    float light = ambient + (1.0 - ambient) * diffuse;
+    vec3 n = normalize(v_normal);
+    float diffuse = max(dot(n, ubo.light_dir.xyz), 0.0);
+    float shadow = ubo.light_dir.w > 0.5 && diffuse > 0.0 ? shadow_factor(n) : 1.0;
+    float ambient = ubo.light_color.w;
+    // Ambient light from the environment's irradiance where there is one.
+    vec3 ambient_light = ubo.ibl_params.x > 0.5
+        ? ambient * texture(irradiance_map, n).rgb
+        : vec3(ambient);
+    vec3 light = ambient_light
+        + (1.0 - ambient) * (diffuse * shadow * ubo.light_color.rgb + list_lighting(n));
 
     outColor = texel * vec4(v_color * light, 1.0);
 }
@@ -1,5 +1,7 @@
 #version 460
 
+// Only view_proj is read here: the shadow pass binds cascade slots of the
+// camera buffer that hold nothing else (see resources.rs CameraUbo).
 layout(set = 0, binding = 0) uniform Camera {
     mat4 view_proj;
 } ubo;
@@ -30,6 +32,8 @@ layout(location = 0) out vec3 v_color;
 layout(location = 1) out vec2 v_uv;
 layout(location = 2) out vec3 v_normal;
 layout(location = 3) flat out uint v_tex_index;
+// Camera-relative position, for the shadow lookup in tri.frag.
+layout(location = 4) out vec3 v_rel_pos;
 
 // Optional compile-time knobs:
 #ifndef UV_TILE
@@ -42,7 +46,9 @@ layout(location = 3) flat out uint v_tex_index;
 void main() {
     Candidate c = candidates[gl_InstanceIndex];
 
-    gl_Position = ubo.view_proj * c.model * vec4(in_pos, 1.0);
+    vec4 rel_pos = c.model * vec4(in_pos, 1.0);
+    gl_Position = ubo.view_proj * rel_pos;
+    v_rel_pos = rel_pos.xyz;
 
     v_color = in_color * c.tint.rgb;
 
@@ -54,7 +60,7 @@ void main() {
 
     // World-space normal. Assumes uniform scale; revisit with a proper
     // normal matrix (inverse-transpose) once non-uniform scaling or real
-    // lighting shows up. Unused downstream for now.
+    // lighting shows up.
     v_normal = mat3(c.model) * in_normal;
 
     // Per-vertex texture index (assigned per block face by the mesher) takes
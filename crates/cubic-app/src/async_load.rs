@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Background asset loading for the running world. Files are read and
+//! decoded by a cubic_assets::AssetLoader on the app's job pool; poll_async_assets, once per
+//! loop turn, uploads whatever finished (textures through
+//! upload_texture_async, meshes through the renderer's batched transfer
+//! uploads) and puts it in place once it's resident, so the frame loop
+//! never waits on disk or a decoder.
+//!
+//! Guest load-mesh calls go through here: the guest gets its id straight
+//! away and draw-mesh with it is skipped (like any unknown id) until the
+//! mesh and its textures are in. load-texture stays synchronous, since the
+//! index it returns has to be drawable the moment the guest has it.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cubic_assets::{AssetLoader, Decoded, LoadId};
+use cubic_jobs::JobPool;
+use cubic_render::MeshHandle;
+use tracing::{error, info};
+
+use crate::backend::RendererBackend;
+use crate::App;
+
+/// What to do with a load once it's decoded.
+pub(crate) enum AssetJob {
+    /// A guest load-mesh, under the id it was already handed.
+    EntityMesh(u32),
+    /// Hot-reload: new pixels for these bindless indices.
+    ReloadTexture(Vec<u32>),
+    /// Hot-reload: every entity mesh loaded from the file.
+    ReloadModel,
+}
+
+/// An uploaded entity mesh waiting on its textures to become resident.
+struct WaitingMesh {
+    id: u32,
+    handle: MeshHandle,
+    path: PathBuf,
+    textures: Vec<u32>,
+}
+
+pub(crate) struct AsyncAssets {
+    pub(crate) loader: AssetLoader,
+    jobs: HashMap<LoadId, AssetJob>,
+    // upload_texture_async indices the backend hasn't reported resident.
+    uploading: HashSet<u32>,
+    waiting: Vec<WaitingMesh>,
+}
+
+impl AsyncAssets {
+    pub(crate) fn new(jobs: Arc<JobPool>) -> Self {
+        Self {
+            loader: AssetLoader::new(jobs),
+            jobs: HashMap::new(),
+            uploading: HashSet::new(),
+            waiting: Vec::new(),
+        }
+    }
+
+    pub(crate) fn load_image(&mut self, path: &Path, job: AssetJob) {
+        let id = self.loader.load_image(path);
+        self.jobs.insert(id, job);
+    }
+
+    /// Decode an .obj/.gltf/.glb (loader::decode_mesh_file).
+    pub(crate) fn load_mesh(&mut self, path: &Path, job: AssetJob) {
+        let id = self.loader.submit(path, crate::loader::decode_mesh_file);
+        self.jobs.insert(id, job);
+    }
+
+    /// Drop everything in flight, for a new world. Loads still decoding
+    /// finish into the void.
+    pub(crate) fn clear(&mut self) {
+        self.jobs.clear();
+        self.uploading.clear();
+        self.waiting.clear();
+    }
+
+    /// The entity meshes still waiting on textures, as (id, path), for
+    /// device-lost recovery to load again: their handles died with the
+    /// device, and recovery brings every texture back resident.
+    pub(crate) fn drain_waiting(&mut self) -> Vec<(u32, PathBuf)> {
+        self.uploading.clear();
+        self.waiting.drain(..).map(|w| (w.id, w.path)).collect()
+    }
+}
+
+impl App {
+    /// Finish whatever background loads completed and publish entity
+    /// meshes whose textures have landed. Called once per loop turn from
+    /// about_to_wait.
+    pub(crate) fn poll_async_assets(&mut self) {
+        let Some(backend) = self.backend.as_mut() else {
+            return;
+        };
+        let world = &mut self.world;
+        let resident = backend.take_resident_textures();
+        if !resident.is_empty() || !world.assets.waiting.is_empty() {
+            let AsyncAssets {
+                uploading, waiting, ..
+            } = &mut world.assets;
+            for index in resident {
+                uploading.remove(&index);
+            }
+            waiting.retain(|w| {
+                if w.textures.iter().any(|t| uploading.contains(t)) {
+                    return true;
+                }
+                info!("loaded mesh {:?} -> handle {}", w.path, w.id);
+                world.entity_meshes.insert(w.id, (w.handle, w.path.clone()));
+                false
+            });
+        }
+
+        for loaded in self.world.assets.loader.poll() {
+            // Not ours (anymore): submitted before a world reload.
+            let Some(job) = self.world.assets.jobs.remove(&loaded.id) else {
+                continue;
+            };
+            let decoded = match loaded.result {
+                Ok(d) => d,
+                Err(e) => {
+                    error!("loading {:?} failed: {e:#}", loaded.path);
+                    continue;
+                }
+            };
+            match job {
+                AssetJob::EntityMesh(id) => self.finish_entity_mesh(id, loaded.path, &decoded),
+                AssetJob::ReloadTexture(indices) => {
+                    self.apply_texture_reload(&loaded.path, &indices, &decoded)
+                }
+                AssetJob::ReloadModel => self.apply_model_reload(&loaded.path, &decoded),
+            }
+        }
+    }
+
+    fn finish_entity_mesh(&mut self, id: u32, path: PathBuf, decoded: &Decoded) {
+        let Some(backend) = self.backend.as_mut() else {
+            return;
+        };
+        let world = &mut self.world;
+        let uploading = &mut world.assets.uploading;
+        let uploaded = crate::loader::upload_decoded_mesh(
+            &path,
+            decoded,
+            &mut world.model_textures,
+            |px, w, h| {
+                let index = backend.upload_texture_async(px, w, h)?;
+                // 0 is the GL backend's "no texture", resident as it is.
+                if index != 0 {
+                    uploading.insert(index);
+                }
+                Ok(index)
+            },
+        )
+        .and_then(|(verts, idxs)| Ok(backend.upload_mesh(&verts, &idxs)?));
+        let handle = match uploaded {
+            Ok(h) => h,
+            Err(e) => {
+                error!("load-mesh upload failed for {path:?}: {e:#}");
+                return;
+            }
+        };
+        // Textures uploaded for an earlier load of the same file may still
+        // be in flight too.
+        let textures: Vec<u32> = world
+            .model_textures
+            .get(&path)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|t| uploading.contains(t))
+            .collect();
+        if textures.is_empty() {
+            info!("loaded mesh {path:?} -> handle {id}");
+            world.entity_meshes.insert(id, (handle, path));
+        } else {
+            world.assets.waiting.push(WaitingMesh {
+                id,
+                handle,
+                path,
+                textures,
+            });
+        }
+    }
+}
@@ -1,27 +1,102 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-//! Renderer-backend abstraction: a small trait over the concrete GL/Vulkan
+//! Renderer-backend abstraction: a small trait over the concrete GL/Vulkan/wgpu
 //! renderers so the rest of the app doesn't match on which one is active.
 
-use crate::config::{HdrFlavorCfg, MipmapMode, RenderCfg, TextureFilter, VsyncMode};
-use anyhow::Result;
-use cubic_math::Camera;
-use cubic_render::{MeshHandle, PushData, RenderSize, Renderer, Vertex};
+use crate::config::{
+    ColorFilterCfg, DebugCfg, HdrFlavorCfg, InternalScalingCfg, LatencyModeCfg, MipmapMode,
+    PostEffectCfg, RenderCfg, TextureFilter, UpscalerCfg, VsyncMode, WindowCfg,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use cubic_math::{Camera, DVec3};
+use cubic_platform::winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use cubic_render::{
+    DepthConvention, FrameStats, LatencyMode, MeshHandle, NullRenderer, PushData, RenderResult,
+    RenderSize, Renderer, StreamCounts, SurfaceChanged, SurfaceInfo, Vertex,
+};
 use cubic_render_gl::GlRenderer;
-use cubic_render_vk::{Filter, HdrFlavor, SamplerMipmapMode, VkRenderer, VkVsyncMode};
+use cubic_render_vk::{
+    CaptureConfig, CaptureSink, ColorBlindness, ColorFilter, Filter, HdrDisplayChange, HdrFlavor,
+    InternalResolution, Letterbox, MemoryStats, PixelScaling, PostEffect, SamplerMipmapMode,
+    ShadowSettings, Upscaler, ValidationLayers, ValidationPolicy, VkRenderer, VkVsyncMode,
+};
+use cubic_render_wgpu::WgpuRenderer;
 use egui::{ClippedPrimitive, TexturesDelta};
+use std::path::Path;
+use tracing::{error, info};
 
 pub(crate) trait RendererBackend {
-    fn resize(&mut self, size: RenderSize) -> Result<()>;
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()>;
     fn set_clear_color(&mut self, rgba: [f32; 4]);
     fn set_vsync(&mut self, on: bool);
+    fn set_target_fps(&mut self, fps: Option<u32>);
+    /// Frame-time average/percentiles over the renderer's rolling window.
+    fn frame_stats(&self) -> FrameStats;
+    /// The voxel world's streaming counts, for frame_stats to report.
+    fn record_stream_counts(&mut self, counts: StreamCounts);
+    /// Whether the window is in exclusive fullscreen, for backends whose
+    /// swapchain has a say in it (Vulkan's VK_EXT_full_screen_exclusive).
+    fn set_exclusive_fullscreen(&mut self, on: bool);
+    /// The monitor's refresh rate, for GL's fallback cap when the driver
+    /// won't do vsync.
+    fn set_display_refresh_hz(&mut self, hz: f32);
+    /// The window's DPI scale, from winit (see Renderer::set_scale_factor).
+    fn set_scale_factor(&mut self, scale_factor: f32);
     fn configure_advanced(&mut self, cfg: &RenderCfg);
-    fn upload_mesh(&mut self, verts: &[Vertex], idxs: &[u32]) -> Result<MeshHandle>;
+    /// `[debug]` validation-layer policy; only Vulkan has a layer to
+    /// configure.
+    fn set_validation_policy(&mut self, cfg: &DebugCfg);
+    /// `[window]` max size and aspect lock, held by letterboxing the scene
+    /// (see cubic_render_vk::Letterbox); Vulkan only.
+    fn set_window_limits(&mut self, cfg: &WindowCfg);
+    fn upload_mesh(&mut self, verts: &[Vertex], idxs: &[u32]) -> RenderResult<MeshHandle>;
     fn set_camera(&mut self, camera: Camera);
     fn draw_mesh(&mut self, handle: MeshHandle, push: PushData);
-    fn render(&mut self) -> Result<()>;
+    /// draw_mesh for a chunk mesh: with upload_block_textures' material
+    /// where there is one, so tex_index names an array layer.
+    fn draw_chunk_mesh(&mut self, handle: MeshHandle, push: PushData);
+    /// A world-space debug line for this frame only (VkRenderer::debug_line);
+    /// the other backends don't draw them yet.
+    fn debug_line(&mut self, a: DVec3, b: DVec3, color: [f32; 4]);
+    fn render(&mut self) -> RenderResult<()>;
+    /// Release / rebuild the window surface (see Renderer::suspend).
+    fn suspend(&mut self) -> RenderResult<()>;
+    fn resume(&mut self) -> RenderResult<()>;
+    /// Human-readable present setup (swapchain format/present mode, or GL's
+    /// swap interval) for the diagnostics overlay.
+    fn present_summary(&self) -> String;
+    /// What the backend's surface actually ended up as (see
+    /// `Renderer::surface_info`).
+    fn surface_info(&self) -> SurfaceInfo;
+    /// What projections handed to the backend must be built for (see
+    /// Renderer::depth_convention).
+    fn depth_convention(&self) -> DepthConvention;
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged>;
     fn free_mesh(&mut self, _handle: MeshHandle) {} // default no-op
-    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<u32>;
+    fn draw_text(&mut self, pos: [f32; 2], text: &str, size: f32, color: [f32; 4]);
+    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> RenderResult<u32>;
+    /// upload_texture that doesn't wait on the GPU; the index can't be drawn
+    /// with until take_resident_textures reports it.
+    fn upload_texture_async(&mut self, pixels: &[u8], width: u32, height: u32)
+        -> RenderResult<u32>;
+    fn take_resident_textures(&mut self) -> Vec<u32>;
+    /// New pixels for a texture upload_texture returned (asset hot-reload).
+    fn replace_texture(
+        &mut self,
+        index: u32,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<()>;
+    /// RGBA8 LUT strip for the color_grade post effect (see
+    /// VkRenderer::set_color_grading_lut); Vulkan only, a no-op elsewhere.
+    fn set_color_grading_lut(&mut self, pixels: &[u8], width: u32, height: u32)
+        -> RenderResult<()>;
+    /// Block-face textures as one texture array (see
+    /// VkRenderer::upload_block_textures). False where the backend has
+    /// none, and block faces go through upload_texture one by one.
+    fn upload_block_textures(&mut self, layers: &[u8], size: u32, count: u32)
+        -> RenderResult<bool>;
     fn queue_egui(
         &mut self,
         textures_delta: TexturesDelta,
@@ -32,16 +107,252 @@ pub(crate) trait RendererBackend {
     );
 }
 
-pub(crate) enum Backend {
+fn vk_vsync_mode(mode: VsyncMode) -> VkVsyncMode {
+    match mode {
+        VsyncMode::Fifo => VkVsyncMode::Fifo,
+        VsyncMode::Mailbox => VkVsyncMode::Mailbox,
+        VsyncMode::FifoRelaxed => VkVsyncMode::FifoRelaxed,
+        VsyncMode::Immediate => VkVsyncMode::Immediate,
+    }
+}
+
+fn vk_color_filter(filter: ColorFilterCfg) -> ColorFilter {
+    match filter {
+        ColorFilterCfg::Off => ColorFilter::Off,
+        ColorFilterCfg::Grayscale => ColorFilter::Grayscale,
+        ColorFilterCfg::Protanopia => ColorFilter::Simulate(ColorBlindness::Protanopia),
+        ColorFilterCfg::Deuteranopia => ColorFilter::Simulate(ColorBlindness::Deuteranopia),
+        ColorFilterCfg::Tritanopia => ColorFilter::Simulate(ColorBlindness::Tritanopia),
+        ColorFilterCfg::DaltonizeProtanopia => ColorFilter::Daltonize(ColorBlindness::Protanopia),
+        ColorFilterCfg::DaltonizeDeuteranopia => {
+            ColorFilter::Daltonize(ColorBlindness::Deuteranopia)
+        }
+        ColorFilterCfg::DaltonizeTritanopia => ColorFilter::Daltonize(ColorBlindness::Tritanopia),
+    }
+}
+
+/// `--backend` and `/backend`: which renderer to bring up (see
+/// create_backend).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BackendChoice {
+    /// Vulkan where a GPU meets its requirements, else GL.
+    Auto,
+    Vk,
+    Gl,
+    Wgpu,
+    Null,
+}
+
+impl BackendChoice {
+    pub(crate) const NAMES: [&'static str; 5] = ["auto", "vk", "gl", "wgpu", "null"];
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(Self::Auto),
+            "vk" | "vulkan" => Some(Self::Vk),
+            "gl" | "opengl" => Some(Self::Gl),
+            "wgpu" => Some(Self::Wgpu),
+            "null" => Some(Self::Null),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Vk => "vk",
+            Self::Gl => "gl",
+            Self::Wgpu => "wgpu",
+            Self::Null => "null",
+        }
+    }
+}
+
+/// The Vulkan validation checks to run: `--validation`, else
+/// CUBIC_VALIDATION, else `[debug] validation`, else the build's default.
+/// One that doesn't parse is logged and skipped.
+pub(crate) fn validation_layers(cli: Option<&str>, cfg: &DebugCfg) -> ValidationLayers {
+    let parse = |source: &str, s: &str| {
+        let layers = ValidationLayers::parse(s);
+        if layers.is_none() {
+            error!("{source} {s:?} not understood (expected off, on, gpu, best or all)");
+        }
+        layers
+    };
+    cli.and_then(|s| parse("--validation", s))
+        .or_else(ValidationLayers::from_env)
+        .or_else(|| {
+            (!cfg.validation.trim().is_empty())
+                .then(|| parse("[debug] validation", &cfg.validation))
+                .flatten()
+        })
+        .unwrap_or_default()
+}
+
+/// Bring up the chosen backend on a window, falling back to the other real
+/// one if it fails: auto and vk try Vulkan first, gl tries GL first. The
+/// Vulkan side only picks GPUs that meet its minimum requirements (see
+/// cubic-render-vk's device selection), so a machine without one falls
+/// back to GL instead of failing partway. wgpu, which is opt-in, falls back
+/// to GL too. Err only if both failed.
+pub(crate) fn create_backend(
+    choice: BackendChoice,
+    window: &dyn HasWindowHandle,
+    display: &dyn HasDisplayHandle,
+    size: RenderSize,
+    gpu: Option<&str>,
+    validation: ValidationLayers,
+) -> Result<Backend> {
+    let vk = || {
+        VkRenderer::new_with_validation(window, display, size, gpu, validation)
+            .map(|r| Backend::Vk(Box::new(r)))
+    };
+    let gl = || GlRenderer::new(window, display, size).map(|r| Backend::Gl(Box::new(r)));
+    let wgpu = || WgpuRenderer::new(window, display, size).map(|r| Backend::Wgpu(Box::new(r)));
+    let (first, second): (
+        &dyn Fn() -> RenderResult<Backend>,
+        &dyn Fn() -> RenderResult<Backend>,
+    ) = match choice {
+        BackendChoice::Null => {
+            return Ok(Backend::Null(Box::new(NullRenderer::headless(size))));
+        }
+        BackendChoice::Auto | BackendChoice::Vk => (&vk, &gl),
+        BackendChoice::Gl => (&gl, &vk),
+        BackendChoice::Wgpu => (&wgpu, &gl),
+    };
+    match first() {
+        Ok(backend) => Ok(backend),
+        Err(e) => {
+            error!(
+                "{} backend init failed: {e:#}; trying the other one",
+                choice.name()
+            );
+            let backend = second().map_err(|e2| {
+                anyhow::Error::from(e2)
+                    .context(format!("no backend came up (first attempt: {e:#})"))
+            })?;
+            info!("fell back to {}", backend.name());
+            Ok(backend)
+        }
+    }
+}
+
+/// The running renderer. A plugin outside this crate reaches the
+/// renderer's own API through the variant.
+pub enum Backend {
     Gl(Box<GlRenderer>),
     Vk(Box<VkRenderer>),
+    /// `--backend wgpu`: Metal/DX12/Vulkan through wgpu (see
+    /// cubic-render-wgpu for what it draws so far).
+    Wgpu(Box<WgpuRenderer>),
+    /// `--backend null`: draws nothing, needs no GPU (see NullRenderer).
+    Null(Box<NullRenderer>),
+}
+
+impl Backend {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Backend::Gl(_) => "gl",
+            Backend::Vk(_) => "vk",
+            Backend::Wgpu(_) => "wgpu",
+            Backend::Null(_) => "null",
+        }
+    }
+
+    /// Rebuild after render() returned `RenderError::DeviceLost`. Consumes the backend
+    /// because the old device has to be torn down before a new one can take
+    /// over the window. GL never reports device loss; it passes through.
+    pub(crate) fn recover_from_device_lost(self) -> Result<Backend> {
+        match self {
+            Backend::Gl(r) => Ok(Backend::Gl(r)),
+            Backend::Vk(r) => Ok(Backend::Vk(Box::new((*r).recover_from_device_lost()?))),
+            Backend::Wgpu(r) => Ok(Backend::Wgpu(r)),
+            Backend::Null(r) => Ok(Backend::Null(r)),
+        }
+    }
+
+    /// Capture the next rendered frame for finish_screenshot to save to
+    /// `path`. Vulkan saves every presented frame over the last
+    /// (CaptureSink::Image) until then; GL reads the next one back.
+    pub(crate) fn start_screenshot(&mut self, path: &Path) -> Result<()> {
+        match self {
+            Backend::Vk(r) => Ok(r.start_capture(CaptureConfig {
+                sink: CaptureSink::Image {
+                    path: path.to_owned(),
+                },
+                every_nth: 1,
+            })?),
+            Backend::Gl(r) => {
+                r.capture_next_frame();
+                Ok(())
+            }
+            other => bail!("screenshots need Vulkan or GL, not {}", other.name()),
+        }
+    }
+
+    /// After a frame has rendered since start_screenshot: have it written
+    /// to `path`. Errs if no frame was caught.
+    pub(crate) fn finish_screenshot(&mut self, path: &Path) -> Result<()> {
+        match self {
+            Backend::Vk(r) => {
+                if r.stop_capture()?.frames_written == 0 {
+                    bail!("no frame was captured");
+                }
+                Ok(())
+            }
+            Backend::Gl(r) => {
+                let frame = r
+                    .take_captured_frame()
+                    .ok_or_else(|| anyhow!("no frame was captured"))?;
+                if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir)
+                        .with_context(|| format!("creating {}", dir.display()))?;
+                }
+                // Without alpha, as Vulkan's are: the window's is whatever
+                // the clear and overlay left.
+                let rgb: Vec<u8> = frame
+                    .rgba
+                    .chunks_exact(4)
+                    .flat_map(|p| [p[0], p[1], p[2]])
+                    .collect();
+                image::save_buffer(
+                    path,
+                    &rgb,
+                    frame.width,
+                    frame.height,
+                    image::ExtendedColorType::Rgb8,
+                )
+                .with_context(|| format!("writing {}", path.display()))
+            }
+            other => bail!("screenshots need Vulkan or GL, not {}", other.name()),
+        }
+    }
+
+    /// GPU memory use by heap and category; Vulkan only.
+    pub(crate) fn memory_stats(&self) -> Option<MemoryStats> {
+        match self {
+            Backend::Vk(r) => Some(r.memory_stats()),
+            _ => None,
+        }
+    }
+
+    /// After the window changed monitor: whether HDR output went off or
+    /// came back with it. Vulkan only; the others have no HDR output.
+    pub(crate) fn recheck_display_hdr(&mut self) -> Option<HdrDisplayChange> {
+        match self {
+            Backend::Vk(r) => r.recheck_display_hdr(),
+            _ => None,
+        }
+    }
 }
 
 impl RendererBackend for Backend {
-    fn resize(&mut self, size: RenderSize) -> Result<()> {
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()> {
         match self {
             Backend::Gl(r) => r.resize(size),
             Backend::Vk(r) => r.resize(size),
+            Backend::Wgpu(r) => r.resize(size),
+            Backend::Null(r) => r.resize(size),
         }
     }
 
@@ -49,6 +360,8 @@ impl RendererBackend for Backend {
         match self {
             Backend::Gl(r) => r.set_clear_color(rgba),
             Backend::Vk(r) => r.set_clear_color(rgba),
+            Backend::Wgpu(r) => r.set_clear_color(rgba),
+            Backend::Null(r) => r.set_clear_color(rgba),
         }
     }
 
@@ -56,23 +369,154 @@ impl RendererBackend for Backend {
         match self {
             Backend::Gl(r) => r.set_vsync(on),
             Backend::Vk(r) => r.set_vsync(on),
+            Backend::Wgpu(r) => r.set_vsync(on),
+            Backend::Null(r) => r.set_vsync(on),
+        }
+    }
+
+    fn set_target_fps(&mut self, fps: Option<u32>) {
+        match self {
+            Backend::Gl(r) => r.set_target_fps(fps),
+            Backend::Vk(r) => r.set_target_fps(fps),
+            Backend::Wgpu(r) => r.set_target_fps(fps),
+            Backend::Null(r) => r.set_target_fps(fps),
+        }
+    }
+
+    fn set_exclusive_fullscreen(&mut self, on: bool) {
+        match self {
+            // GL's swap chain is the window system's; nothing to ask for.
+            Backend::Gl(_) | Backend::Wgpu(_) | Backend::Null(_) => {}
+            Backend::Vk(r) => r.set_exclusive_fullscreen(on),
+        }
+    }
+
+    fn set_display_refresh_hz(&mut self, hz: f32) {
+        if let Backend::Gl(r) = self {
+            r.set_refresh_rate_hint(hz);
+        }
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: f32) {
+        match self {
+            Backend::Gl(r) => r.set_scale_factor(scale_factor),
+            Backend::Vk(r) => r.set_scale_factor(scale_factor),
+            Backend::Wgpu(r) => r.set_scale_factor(scale_factor),
+            Backend::Null(r) => r.set_scale_factor(scale_factor),
+        }
+    }
+
+    fn set_validation_policy(&mut self, cfg: &DebugCfg) {
+        if let Backend::Vk(r) = self {
+            r.set_validation_policy(ValidationPolicy {
+                suppress: cfg.validation_suppress.clone(),
+                panic_on_error: cfg.validation_panic,
+            });
+        }
+    }
+
+    fn set_window_limits(&mut self, cfg: &WindowCfg) {
+        if let Backend::Vk(r) = self {
+            let max_size = (cfg.max_width.is_some() || cfg.max_height.is_some()).then(|| {
+                [
+                    cfg.max_width.unwrap_or(u32::MAX),
+                    cfg.max_height.unwrap_or(u32::MAX),
+                ]
+            });
+            r.set_letterbox(Letterbox {
+                aspect: cfg.aspect_lock,
+                max_size,
+            });
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        match self {
+            Backend::Gl(r) => r.frame_stats(),
+            Backend::Vk(r) => r.frame_stats(),
+            Backend::Wgpu(r) => r.frame_stats(),
+            Backend::Null(r) => r.frame_stats(),
+        }
+    }
+
+    fn record_stream_counts(&mut self, counts: StreamCounts) {
+        match self {
+            Backend::Gl(r) => r.record_stream_counts(counts),
+            Backend::Vk(r) => r.record_stream_counts(counts),
+            Backend::Wgpu(r) => r.record_stream_counts(counts),
+            Backend::Null(r) => r.record_stream_counts(counts),
         }
     }
 
     fn configure_advanced(&mut self, cfg: &RenderCfg) {
-        // GL has no advanced knobs yet.
+        let spike = (cfg.frame_spike_ms > 0.0)
+            .then(|| std::time::Duration::from_secs_f32(cfg.frame_spike_ms / 1000.0));
+        let latency = match cfg.latency_mode {
+            LatencyModeCfg::Throughput => LatencyMode::Throughput,
+            LatencyModeCfg::Low => LatencyMode::Low,
+        };
+        match self {
+            Backend::Gl(r) => {
+                r.set_frame_spike_threshold(spike);
+                r.set_latency_mode(latency);
+                // Plain vsync on GL, with a warning (see its swap.rs).
+                r.set_adaptive_vsync(cfg.vsync_mode == VsyncMode::FifoRelaxed);
+                // No HDR output on GL: hdr means FP16 + tonemap to SDR.
+                r.set_hdr_enabled(cfg.hdr);
+                r.set_srgb_encode(cfg.srgb_encode);
+            }
+            Backend::Vk(r) => {
+                r.set_frame_spike_threshold(spike);
+                r.set_latency_mode(latency);
+            }
+            Backend::Wgpu(r) => {
+                r.set_frame_spike_threshold(spike);
+                r.set_latency_mode(latency);
+            }
+            Backend::Null(r) => {
+                r.set_frame_spike_threshold(spike);
+                r.set_latency_mode(latency);
+            }
+        }
+        // The rest is Vulkan only.
         if let Backend::Vk(r) = self {
-            let mode = match cfg.vsync_mode {
-                VsyncMode::Fifo => VkVsyncMode::Fifo,
-                VsyncMode::Mailbox => VkVsyncMode::Mailbox,
-            };
-            r.set_vsync_mode(mode);
+            r.set_vsync_mode(vk_vsync_mode(cfg.vsync_mode));
+            let priority: Vec<VkVsyncMode> = cfg
+                .present_mode_priority
+                .as_slice()
+                .iter()
+                .map(|&m| vk_vsync_mode(m))
+                .collect();
+            r.set_present_mode_priority(&priority);
             r.set_hdr_enabled(cfg.hdr);
             let flavor = match cfg.hdr_flavor {
                 HdrFlavorCfg::PreferScrgb => HdrFlavor::PreferScrgb,
                 HdrFlavorCfg::PreferHdr10 => HdrFlavor::PreferHdr10,
             };
             r.set_hdr_flavor(flavor);
+            r.set_sdr_white_nits(cfg.sdr_white_nits);
+            r.set_srgb_encode(cfg.srgb_encode);
+
+            // Calibration keys override the display-detected values one by
+            // one; with none set this re-applies the detected metadata (a
+            // no-op unless an earlier reload had overridden it).
+            let cal = &cfg.hdr_display;
+            let mut md = r.display_hdr_metadata();
+            if let Some(v) = cal.max_luminance {
+                md.max_luminance = v;
+                md.max_content_light_level = v;
+            }
+            if let Some(v) = cal.min_luminance {
+                md.min_luminance = v;
+            }
+            if let Some(v) = cal.max_frame_average_luminance {
+                md.max_frame_average_light_level = v;
+            }
+            md.red = cal.red.unwrap_or(md.red);
+            md.green = cal.green.unwrap_or(md.green);
+            md.blue = cal.blue.unwrap_or(md.blue);
+            md.white_point = cal.white_point.unwrap_or(md.white_point);
+            r.set_hdr_metadata(md);
 
             let filter = match cfg.texture_filter {
                 TextureFilter::Nearest => Filter::NEAREST,
@@ -82,16 +526,61 @@ impl RendererBackend for Backend {
                 MipmapMode::Nearest => SamplerMipmapMode::NEAREST,
                 MipmapMode::Linear => SamplerMipmapMode::LINEAR,
             };
-            r.set_sampler_config(filter, filter, mipmap_mode, cfg.anisotropy, cfg.lod_bias);
+            r.set_sampler_config(
+                filter,
+                filter,
+                mipmap_mode,
+                cfg.max_anisotropy,
+                cfg.lod_bias,
+            );
+            r.set_depth_prepass(cfg.depth_prepass);
+            let shadows = ShadowSettings {
+                enabled: cfg.shadows,
+                resolution: cfg.shadow_resolution,
+                ..r.shadow_settings()
+            };
+            if let Err(e) = r.set_shadow_settings(shadows) {
+                tracing::warn!("shadow settings rejected, keeping previous: {e:#}");
+            }
+            // Upscaler first: with the scale unchanged it's the only
+            // rebuild, and with both changed set_render_scale's covers it.
+            r.set_upscaler(match cfg.upscaler {
+                UpscalerCfg::Nearest => Upscaler::Nearest,
+                UpscalerCfg::Bilinear => Upscaler::Bilinear,
+                UpscalerCfg::Fsr1 => Upscaler::Fsr1,
+            });
+            r.set_render_scale(cfg.render_scale);
+            r.set_internal_resolution(cfg.internal_resolution.map(|size| InternalResolution {
+                size,
+                scaling: match cfg.internal_scaling {
+                    InternalScalingCfg::Integer => PixelScaling::Integer,
+                    InternalScalingCfg::Nearest => PixelScaling::Nearest,
+                },
+            }));
+            let effects: Vec<PostEffect> = cfg
+                .post_effects
+                .as_slice()
+                .iter()
+                .map(|e| match e {
+                    PostEffectCfg::Bloom => PostEffect::Bloom,
+                    PostEffectCfg::Vignette => PostEffect::Vignette,
+                    PostEffectCfg::Fxaa => PostEffect::Fxaa,
+                    PostEffectCfg::ColorGrade => PostEffect::ColorGrade,
+                })
+                .collect();
+            r.set_post_effects(&effects);
+            r.set_color_filter(vk_color_filter(cfg.color_filter));
         }
     }
 
-    fn upload_mesh(&mut self, verts: &[Vertex], idxs: &[u32]) -> Result<MeshHandle> {
+    fn upload_mesh(&mut self, verts: &[Vertex], idxs: &[u32]) -> RenderResult<MeshHandle> {
         match self {
             // GL mesh API not yet implemented; uploaded meshes are silently
             // dropped until the GL backend card is complete.
             Backend::Gl(_) => Ok(MeshHandle(u32::MAX)),
             Backend::Vk(r) => r.upload_mesh(verts, idxs),
+            Backend::Wgpu(r) => r.upload_mesh(verts, idxs),
+            Backend::Null(r) => r.upload_mesh(verts, idxs),
         }
     }
 
@@ -99,6 +588,8 @@ impl RendererBackend for Backend {
         match self {
             Backend::Gl(_) => {} // GL camera via uniforms — not yet implemented.
             Backend::Vk(r) => r.set_camera(camera),
+            Backend::Wgpu(r) => r.set_camera(camera),
+            Backend::Null(_) => {}
         }
     }
 
@@ -106,6 +597,25 @@ impl RendererBackend for Backend {
         match self {
             Backend::Gl(_) => {} // GL draw_mesh — not yet implemented.
             Backend::Vk(r) => r.draw_mesh(handle, push),
+            Backend::Wgpu(r) => r.draw_mesh(handle, push),
+            Backend::Null(r) => r.draw_mesh(handle, push),
+        }
+    }
+
+    fn draw_chunk_mesh(&mut self, handle: MeshHandle, push: PushData) {
+        match self {
+            Backend::Vk(r) => match r.block_material() {
+                Some(material) => r.draw_mesh_with_material(handle, push, material),
+                None => r.draw_mesh(handle, push),
+            },
+            Backend::Gl(_) | Backend::Wgpu(_) | Backend::Null(_) => self.draw_mesh(handle, push),
+        }
+    }
+
+    fn debug_line(&mut self, a: DVec3, b: DVec3, color: [f32; 4]) {
+        match self {
+            Backend::Vk(r) => r.debug_line(a, b, color),
+            Backend::Gl(_) | Backend::Wgpu(_) | Backend::Null(_) => {}
         }
     }
 
@@ -113,21 +623,156 @@ impl RendererBackend for Backend {
         match self {
             Backend::Gl(_) => {}
             Backend::Vk(r) => r.free_mesh(handle),
+            Backend::Wgpu(r) => r.free_mesh(handle),
+            Backend::Null(r) => r.free_mesh(handle),
+        }
+    }
+
+    fn draw_text(&mut self, pos: [f32; 2], text: &str, size: f32, color: [f32; 4]) {
+        match self {
+            Backend::Gl(r) => r.draw_text(pos, text, size, color),
+            Backend::Vk(r) => r.draw_text(pos, text, size, color),
+            Backend::Wgpu(r) => r.draw_text(pos, text, size, color),
+            Backend::Null(r) => r.draw_text(pos, text, size, color),
         }
     }
 
-    fn render(&mut self) -> Result<()> {
+    fn render(&mut self) -> RenderResult<()> {
         match self {
             Backend::Gl(r) => r.render(),
             Backend::Vk(r) => r.render(),
+            Backend::Wgpu(r) => r.render(),
+            Backend::Null(r) => r.render(),
+        }
+    }
+
+    fn suspend(&mut self) -> RenderResult<()> {
+        match self {
+            Backend::Gl(r) => r.suspend(),
+            Backend::Vk(r) => r.suspend(),
+            Backend::Wgpu(r) => r.suspend(),
+            Backend::Null(r) => r.suspend(),
         }
     }
 
-    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<u32> {
+    fn resume(&mut self) -> RenderResult<()> {
+        match self {
+            Backend::Gl(r) => r.resume(),
+            Backend::Vk(r) => r.resume(),
+            Backend::Wgpu(r) => r.resume(),
+            Backend::Null(r) => r.resume(),
+        }
+    }
+
+    fn present_summary(&self) -> String {
+        match self {
+            Backend::Gl(r) => r.present_summary(),
+            Backend::Vk(r) => r.present_summary(),
+            Backend::Wgpu(r) => r.present_summary(),
+            Backend::Null(r) => r.present_summary(),
+        }
+    }
+
+    fn surface_info(&self) -> SurfaceInfo {
+        match self {
+            Backend::Gl(r) => r.surface_info(),
+            Backend::Vk(r) => r.surface_info(),
+            Backend::Wgpu(r) => r.surface_info(),
+            Backend::Null(r) => r.surface_info(),
+        }
+    }
+
+    fn depth_convention(&self) -> DepthConvention {
+        match self {
+            Backend::Gl(r) => r.depth_convention(),
+            Backend::Vk(r) => r.depth_convention(),
+            Backend::Wgpu(r) => r.depth_convention(),
+            Backend::Null(r) => r.depth_convention(),
+        }
+    }
+
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged> {
+        match self {
+            Backend::Gl(r) => r.take_surface_change(),
+            Backend::Vk(r) => r.take_surface_change(),
+            Backend::Wgpu(r) => r.take_surface_change(),
+            Backend::Null(r) => r.take_surface_change(),
+        }
+    }
+
+    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> RenderResult<u32> {
         match self {
             // GL texture API not yet implemented.
             Backend::Gl(_) => Ok(0),
             Backend::Vk(r) => r.upload_texture(pixels, width, height),
+            Backend::Wgpu(r) => r.upload_texture(pixels, width, height),
+            Backend::Null(r) => r.upload_texture(pixels, width, height),
+        }
+    }
+
+    fn upload_texture_async(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<u32> {
+        match self {
+            // Same as upload_texture: only ever the dummy, resident already.
+            Backend::Gl(_) => Ok(0),
+            Backend::Vk(r) => r.upload_texture_async(pixels, width, height),
+            Backend::Wgpu(r) => r.upload_texture_async(pixels, width, height),
+            Backend::Null(r) => r.upload_texture_async(pixels, width, height),
+        }
+    }
+
+    fn take_resident_textures(&mut self) -> Vec<u32> {
+        match self {
+            Backend::Gl(_) | Backend::Wgpu(_) => Vec::new(),
+            Backend::Vk(r) => r.take_resident_textures(),
+            Backend::Null(r) => r.take_resident_textures(),
+        }
+    }
+
+    fn replace_texture(
+        &mut self,
+        index: u32,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<()> {
+        match self {
+            // Nothing to replace: GL's and wgpu's upload_texture hand out
+            // only 0.
+            Backend::Gl(_) | Backend::Wgpu(_) => Ok(()),
+            Backend::Vk(r) => r.replace_texture(index, pixels, width, height),
+            Backend::Null(r) => r.replace_texture(index, pixels, width, height),
+        }
+    }
+
+    fn set_color_grading_lut(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<()> {
+        match self {
+            Backend::Vk(r) => {
+                let index = r.upload_texture(pixels, width, height)?;
+                r.set_color_grading_lut(Some(index))
+            }
+            Backend::Gl(_) | Backend::Wgpu(_) | Backend::Null(_) => Ok(()),
+        }
+    }
+
+    fn upload_block_textures(
+        &mut self,
+        layers: &[u8],
+        size: u32,
+        count: u32,
+    ) -> RenderResult<bool> {
+        match self {
+            Backend::Vk(r) => r.upload_block_textures(layers, size, count).map(|_| true),
+            Backend::Gl(_) | Backend::Wgpu(_) | Backend::Null(_) => Ok(false),
         }
     }
 
@@ -140,8 +785,10 @@ impl RendererBackend for Backend {
         ppp: f32,
     ) {
         match self {
-            Backend::Gl(_) => {}
+            Backend::Gl(r) => r.queue_egui(textures_delta, paint_jobs, w, h, ppp),
             Backend::Vk(r) => r.queue_egui(textures_delta, paint_jobs, w, h, ppp),
+            Backend::Wgpu(r) => r.queue_egui(textures_delta, paint_jobs, w, h, ppp),
+            Backend::Null(r) => r.queue_egui(textures_delta, paint_jobs, w, h, ppp),
         }
     }
 }
@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Bringing a backend up on the app's window: at startup (resumed) and at
+//! runtime through `/backend`, which tears the current one down and brings
+//! the requested one up on the same window. A loaded world goes back up
+//! through WorldRenderer's re-upload path; egui's textures are resent from
+//! the app's own mirror of them, since egui itself only ever sends deltas.
+
+use cubic_platform::winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use tracing::{error, info, warn};
+
+use crate::backend::{create_backend, Backend, BackendChoice, RendererBackend};
+use crate::{App, AppState};
+
+impl App {
+    /// Bring up `choice` (or its fallback, see create_backend) on the
+    /// window. None until there is a window, or if no backend would start.
+    pub(crate) fn bring_up_backend(&mut self, choice: BackendChoice) -> Option<Backend> {
+        let window = self.window.as_ref()?;
+        let (wh, dh) = match (window.window_handle(), window.display_handle()) {
+            (Ok(wh), Ok(dh)) => (wh, dh),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("window handles unavailable: {e}");
+                return None;
+            }
+        };
+        let mut backend = match create_backend(
+            choice,
+            &wh,
+            &dh,
+            self.render_size,
+            self.gpu_choice.as_deref(),
+            self.validation_choice,
+        ) {
+            Ok(backend) => backend,
+            Err(e) => {
+                error!("{e:#}");
+                return None;
+            }
+        };
+        // Agnostic settings, then the backend-specific ones.
+        backend.set_clear_color(self.cfg.render.clear_color);
+        backend.set_display_refresh_hz(self.detected_refresh_hz);
+        backend.set_scale_factor(self.scale_factor);
+        backend.set_vsync(self.cfg.render.vsync);
+        backend.configure_advanced(&self.cfg.render);
+        backend.set_validation_policy(&self.cfg.debug);
+        backend.set_window_limits(&self.startup_window);
+        self.load_color_grading_lut(&mut backend);
+        info!("backend = {}", backend.name());
+        Some(backend)
+    }
+
+    /// `/backend <choice>`: swap the running backend for another on the
+    /// same window. The old one goes first, as a Vulkan swapchain or GL
+    /// surface has to let go of the window before another can take it. If
+    /// neither the new one nor its fallback starts, the old kind is brought
+    /// back up.
+    pub(crate) fn switch_backend(&mut self, choice: BackendChoice) -> Result<String, String> {
+        let Some(old) = self.backend.take() else {
+            return Err("no backend is running".to_string());
+        };
+        let old_name = old.name();
+        let old_choice = BackendChoice::parse(old_name).unwrap_or(BackendChoice::Auto);
+        drop(old);
+
+        let (mut backend, result) = match self.bring_up_backend(choice) {
+            Some(backend) => {
+                let msg = format!("backend: {old_name} -> {}", backend.name());
+                (backend, Ok(msg))
+            }
+            None => match self.bring_up_backend(old_choice) {
+                Some(backend) => (
+                    backend,
+                    Err(format!(
+                        "couldn't start {}; back on {old_name}",
+                        choice.name()
+                    )),
+                ),
+                None => return Err("no backend would start; see the log".to_string()),
+            },
+        };
+        self.backend_choice = choice;
+        self.egui_resend = true;
+        if self.state != AppState::Launcher {
+            self.reupload_after_backend_switch(&mut backend);
+        }
+        self.backend = Some(backend);
+        result
+    }
+
+    /// Hand `[post] lut` to a freshly started backend. A missing or
+    /// wrongly sized image is logged, and color_grade then passes colours
+    /// through.
+    fn load_color_grading_lut(&self, backend: &mut Backend) {
+        let path = &self.cfg.post.lut;
+        if path.is_empty() {
+            return;
+        }
+        let loaded = image::open(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|img| {
+                let rgba = img.to_rgba8();
+                let (w, h) = rgba.dimensions();
+                Ok(backend.set_color_grading_lut(&rgba, w, h)?)
+            });
+        if let Err(e) = loaded {
+            warn!("color grading LUT {path} not loaded: {e:#}");
+        }
+    }
+
+    /// Record this frame's egui texture updates in the mirror, and after a
+    /// backend switch widen them to every texture egui has, for the new
+    /// backend's first egui frame.
+    pub(crate) fn track_egui_textures(&mut self, delta: &mut egui::TexturesDelta) {
+        self.egui_textures.apply(delta);
+        if std::mem::take(&mut self.egui_resend) {
+            delta.set = self.egui_textures.full_set();
+        }
+    }
+}
@@ -0,0 +1,465 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! `cubic bench`: a fixed number of frames over a test scene along a
+//! scripted camera path, timed frame by frame and reported as JSON or CSV
+//! for comparing builds across commits.
+//!
+//! The scene is the current game's `bench` world, deleted and generated
+//! afresh from `--seed` every run, so two runs differ only in the build.
+//! The camera follows `--path` by frame number rather than time, so a
+//! slower build sees the same views, just later. The first `--warmup`
+//! frames hold the path's first pose and aren't measured, for the chunks
+//! around it to stream in.
+//!
+//! JSON is one object: the run, frame/CPU/GPU time stats, streaming and
+//! GPU memory at the end, and every measured frame's times. CSV is one
+//! summary row under a header; with `--output` onto an existing file only
+//! the row is appended, so one file can collect a row per commit.
+
+use crate::backend::{Backend, RendererBackend};
+use crate::profile;
+use crate::App;
+use anyhow::{Context, Result};
+use cubic_math::{Camera, DVec3};
+use cubic_render::{FrameStats, FrameTimeStats, StreamCounts};
+use cubic_render_vk::{MemoryCategory, MemoryStats};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// The world `cubic bench` renders; see the module docs.
+const BENCH_WORLD: &str = "bench";
+
+/// Orbit radius and height above the start, in metres.
+const ORBIT_RADIUS: f64 = 48.0;
+const ORBIT_HEIGHT: f64 = 24.0;
+/// Flyover height above the start, and distance covered per frame, in
+/// metres: 15 m/s at 60 fps, fast enough to keep the stream busy.
+const FLYOVER_HEIGHT: f64 = 16.0;
+const FLYOVER_STEP: f64 = 0.25;
+const FLYOVER_PITCH: f32 = -0.3;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct BenchArgs {
+    /// Frames to measure.
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+    /// Frames rendered first and not measured.
+    #[arg(long, default_value_t = 120)]
+    warmup: u32,
+    /// Camera path.
+    #[arg(long, value_enum, default_value_t = CameraPath::Orbit)]
+    path: CameraPath,
+    /// World seed of the test scene.
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+    /// Report format.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+    format: ReportFormat,
+    /// Write the report here instead of stdout (CSV appends).
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Tag for the run in the report, e.g. the commit hash.
+    #[arg(long, default_value = "")]
+    label: String,
+    /// Hide the window.
+    #[arg(long)]
+    pub(crate) offscreen: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CameraPath {
+    /// Stay at the start.
+    Static,
+    /// Circle the start once over the run, looking at it.
+    Orbit,
+    /// Fly straight ahead (-Z), looking slightly down.
+    Flyover,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl CameraPath {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Static => "static",
+            Self::Orbit => "orbit",
+            Self::Flyover => "flyover",
+        }
+    }
+
+    /// Set `camera` to the pose for measured frame `frame` of `frames`,
+    /// relative to `origin`.
+    pub(crate) fn apply(self, camera: &mut Camera, origin: DVec3, frame: u32, frames: u32) {
+        match self {
+            Self::Static => camera.position = origin,
+            Self::Orbit => {
+                let angle = std::f64::consts::TAU * f64::from(frame) / f64::from(frames.max(1));
+                let (sin, cos) = angle.sin_cos();
+                camera.position =
+                    origin + DVec3::new(ORBIT_RADIUS * cos, ORBIT_HEIGHT, ORBIT_RADIUS * sin);
+                // Facing back at the origin (see Camera::forward).
+                camera.yaw = cos.atan2(sin) as f32;
+                camera.pitch = -(ORBIT_HEIGHT.atan2(ORBIT_RADIUS) as f32);
+            }
+            Self::Flyover => {
+                camera.position =
+                    origin + DVec3::new(0.0, FLYOVER_HEIGHT, -FLYOVER_STEP * f64::from(frame));
+                camera.yaw = 0.0;
+                camera.pitch = FLYOVER_PITCH;
+            }
+        }
+    }
+}
+
+/// One measured frame.
+struct Sample {
+    frame_ms: f32,
+    cpu_ms: f32,
+}
+
+/// A `cubic bench` in progress.
+pub(crate) struct BenchRun {
+    args: BenchArgs,
+    /// Where the camera started; paths are relative to it.
+    origin: Option<DVec3>,
+    samples: Vec<Sample>,
+    /// When the first measured frame started.
+    measuring_since: Option<Instant>,
+}
+
+impl BenchRun {
+    pub(crate) fn new(mut args: BenchArgs) -> Self {
+        args.frames = args.frames.max(1);
+        Self {
+            samples: Vec::with_capacity(args.frames as usize),
+            args,
+            origin: None,
+            measuring_since: None,
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.args.seed
+    }
+
+    /// Place the camera for the frame about to be drawn, `rendered` frames
+    /// in.
+    pub(crate) fn apply_camera(&mut self, camera: &mut Camera, rendered: u32) {
+        let origin = *self.origin.get_or_insert(camera.position);
+        let frame = rendered.saturating_sub(self.args.warmup);
+        self.args
+            .path
+            .apply(camera, origin, frame, self.args.frames);
+        if rendered == self.args.warmup {
+            self.measuring_since = Some(Instant::now());
+        }
+    }
+
+    /// Count a rendered frame, `rendered` frames in, that took `frame_ms`
+    /// since the last and `cpu_ms` in render(). True once the run is
+    /// done.
+    pub(crate) fn frame_rendered(&mut self, rendered: u32, frame_ms: f32, cpu_ms: f32) -> bool {
+        if rendered <= self.args.warmup {
+            return false;
+        }
+        self.samples.push(Sample { frame_ms, cpu_ms });
+        self.samples.len() >= self.args.frames as usize
+    }
+
+    /// Write the report (see the module docs).
+    pub(crate) fn report(
+        &self,
+        backend: &Backend,
+        size: [u32; 2],
+        stream: StreamCounts,
+    ) -> Result<()> {
+        let report = Report {
+            args: &self.args,
+            backend: backend.name(),
+            size,
+            seconds: self
+                .measuring_since
+                .map_or(0.0, |t| t.elapsed().as_secs_f64()),
+            frame: FrameTimeStats::of(self.samples.iter().map(|s| s.frame_ms)),
+            cpu: FrameTimeStats::of(self.samples.iter().map(|s| s.cpu_ms)),
+            backend_stats: backend.frame_stats(),
+            stream,
+            memory: backend.memory_stats(),
+            samples: &self.samples,
+        };
+        let Some(path) = &self.args.output else {
+            match self.args.format {
+                ReportFormat::Json => println!("{}", report.json()),
+                ReportFormat::Csv => print!("{}{}", Report::CSV_HEADER, report.csv_row()),
+            }
+            return Ok(());
+        };
+        let text = match self.args.format {
+            ReportFormat::Json => format!("{}\n", report.json()),
+            ReportFormat::Csv if path.exists() => report.csv_row(),
+            ReportFormat::Csv => format!("{}{}", Report::CSV_HEADER, report.csv_row()),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(self.args.format == ReportFormat::Csv)
+            .write(true)
+            .truncate(self.args.format == ReportFormat::Json)
+            .open(path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        file.write_all(text.as_bytes())
+            .with_context(|| format!("writing {}", path.display()))?;
+        tracing::info!("bench report written to {}", path.display());
+        Ok(())
+    }
+}
+
+impl App {
+    /// Switch to the bench world, deleted first so it's generated afresh
+    /// from `seed` (see the module docs).
+    pub(crate) fn use_bench_world(&mut self, seed: u64) {
+        let dir = profile::world_dir(
+            &self.current_game_name,
+            &self.current_profile_name,
+            BENCH_WORLD,
+        );
+        if dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                tracing::warn!("bench: couldn't clear {}: {e}", dir.display());
+            }
+        }
+        self.current_world_name = BENCH_WORLD.to_owned();
+        // Zero would be a random seed (see load_world).
+        self.cfg.world.seed = seed.max(1);
+    }
+}
+
+struct Report<'a> {
+    args: &'a BenchArgs,
+    backend: &'static str,
+    size: [u32; 2],
+    seconds: f64,
+    frame: FrameTimeStats,
+    cpu: FrameTimeStats,
+    /// For the GPU times and validation counts, which only the backend has.
+    backend_stats: FrameStats,
+    stream: StreamCounts,
+    memory: Option<MemoryStats>,
+    samples: &'a [Sample],
+}
+
+impl Report<'_> {
+    const CSV_HEADER: &'static str = "label,version,backend,path,seed,width,height,frames,\
+        seconds,fps,frame_avg_ms,frame_p95_ms,frame_p99_ms,frame_max_ms,cpu_avg_ms,cpu_p95_ms,\
+        cpu_p99_ms,cpu_max_ms,gpu_avg_ms,gpu_p95_ms,gpu_p99_ms,gpu_max_ms,resident_chunks,\
+        gpu_allocated_bytes,gpu_reserved_bytes,validation_errors\n";
+
+    fn fps(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.samples.len() as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn json(&self) -> String {
+        let times = |t: &FrameTimeStats| {
+            format!(
+                "{{\"avg\": {:.3}, \"p95\": {:.3}, \"p99\": {:.3}, \"max\": {:.3}}}",
+                t.avg_ms, t.p95_ms, t.p99_ms, t.max_ms
+            )
+        };
+        let memory = match &self.memory {
+            Some(m) => {
+                let categories: Vec<String> = MemoryCategory::ALL
+                    .iter()
+                    .map(|&c| format!("{}: {}", json_string(c.name()), m.category(c)))
+                    .collect();
+                let device_local_usage: Option<u64> = m
+                    .heaps
+                    .iter()
+                    .filter(|h| h.device_local)
+                    .map(|h| h.usage)
+                    .sum();
+                format!(
+                    "{{\"allocated\": {}, \"reserved\": {}, \"device_local_usage\": {}, \
+                     \"categories\": {{{}}}}}",
+                    m.allocated,
+                    m.reserved,
+                    device_local_usage.map_or("null".to_owned(), |u| u.to_string()),
+                    categories.join(", ")
+                )
+            }
+            None => "null".to_owned(),
+        };
+        let samples: Vec<String> = self
+            .samples
+            .iter()
+            .map(|s| format!("[{:.3}, {:.3}]", s.frame_ms, s.cpu_ms))
+            .collect();
+        let v = &self.backend_stats.validation;
+        format!(
+            "{{\"label\": {}, \"version\": {}, \"backend\": {}, \"path\": {}, \"seed\": {}, \
+             \"size\": [{}, {}], \"frames\": {}, \"warmup\": {}, \"seconds\": {:.3}, \
+             \"fps\": {:.1}, \"frame_ms\": {}, \"cpu_ms\": {}, \"gpu_ms\": {}, \
+             \"streaming\": {{\"resident\": {}, \"meshing\": {}, \"uploads_pending\": {}}}, \
+             \"memory\": {}, \
+             \"validation\": {{\"errors\": {}, \"warnings\": {}, \"suppressed\": {}}}, \
+             \"samples\": {{\"columns\": [\"frame_ms\", \"cpu_ms\"], \"rows\": [{}]}}}}",
+            json_string(&self.args.label),
+            json_string(env!("CARGO_PKG_VERSION")),
+            json_string(self.backend),
+            json_string(self.args.path.name()),
+            self.args.seed,
+            self.size[0],
+            self.size[1],
+            self.samples.len(),
+            self.args.warmup,
+            self.seconds,
+            self.fps(),
+            times(&self.frame),
+            times(&self.cpu),
+            self.backend_stats
+                .gpu
+                .as_ref()
+                .map_or("null".to_owned(), times),
+            self.stream.resident,
+            self.stream.meshing,
+            self.stream.uploads_pending,
+            memory,
+            v.errors,
+            v.warnings,
+            v.suppressed,
+            samples.join(", "),
+        )
+    }
+
+    fn csv_row(&self) -> String {
+        let times = |t: Option<&FrameTimeStats>| match t {
+            Some(t) => format!(
+                "{:.3},{:.3},{:.3},{:.3}",
+                t.avg_ms, t.p95_ms, t.p99_ms, t.max_ms
+            ),
+            None => ",,,".to_owned(),
+        };
+        let (allocated, reserved) = self
+            .memory
+            .as_ref()
+            .map_or((String::new(), String::new()), |m| {
+                (m.allocated.to_string(), m.reserved.to_string())
+            });
+        format!(
+            "{},{},{},{},{},{},{},{},{:.3},{:.1},{},{},{},{},{},{},{}\n",
+            csv_field(&self.args.label),
+            env!("CARGO_PKG_VERSION"),
+            self.backend,
+            self.args.path.name(),
+            self.args.seed,
+            self.size[0],
+            self.size[1],
+            self.samples.len(),
+            self.seconds,
+            self.fps(),
+            times(Some(&self.frame)),
+            times(Some(&self.cpu)),
+            times(self.backend_stats.gpu.as_ref()),
+            self.stream.resident,
+            allocated,
+            reserved,
+            self.backend_stats.validation.errors,
+        )
+    }
+}
+
+/// `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `s` as a CSV field, quoted if it needs to be.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbit_faces_the_origin() {
+        let origin = DVec3::new(10.0, 50.0, -4.0);
+        let mut camera = Camera::default();
+        for frame in [0, 17, 50, 99] {
+            CameraPath::Orbit.apply(&mut camera, origin, frame, 100);
+            let to_origin = (origin - camera.position).normalize().as_vec3();
+            assert!(camera.forward().dot(to_origin) > 0.999, "frame {frame}");
+        }
+    }
+
+    #[test]
+    fn csv_row_matches_header() {
+        let args = BenchArgs {
+            frames: 2,
+            warmup: 0,
+            path: CameraPath::Static,
+            seed: 1,
+            format: ReportFormat::Csv,
+            output: None,
+            label: "a,\"b\"".to_owned(),
+            offscreen: false,
+        };
+        let samples = [
+            Sample {
+                frame_ms: 16.0,
+                cpu_ms: 2.0,
+            },
+            Sample {
+                frame_ms: 17.0,
+                cpu_ms: 3.0,
+            },
+        ];
+        let report = Report {
+            args: &args,
+            backend: "null",
+            size: [1280, 720],
+            seconds: 0.033,
+            frame: FrameTimeStats::of(samples.iter().map(|s| s.frame_ms)),
+            cpu: FrameTimeStats::of(samples.iter().map(|s| s.cpu_ms)),
+            backend_stats: FrameStats::default(),
+            stream: StreamCounts::default(),
+            memory: None,
+            samples: &samples,
+        };
+        let row = report.csv_row();
+        assert!(row.starts_with("\"a,\"\"b\"\"\","));
+        // The label's quoted comma aside, a field per column.
+        let columns = Report::CSV_HEADER.split(',').count();
+        assert_eq!(row.split(',').count() - 1, columns);
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("a\"b\\c\n"), r#""a\"b\\c\n""#);
+        assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+    }
+}
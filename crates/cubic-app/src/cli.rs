@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Subcommands: `cubic info`, `cubic bench`, `cubic capture` and `cubic
+//! golden`. Without one, cubic opens the launcher as usual.
+//!
+//! `info` only needs a window to ask about, so it runs an event loop of its
+//! own (InfoApp). The others are the normal app with an
+//! `AutoRun`: a world is loaded straight away, with no launcher, and
+//! rendered with no input until the run is done, then the app quits.
+//! `--offscreen` hides the window; the frames still go through its
+//! swapchain, so a platform that won't hide windows shows it anyway.
+
+use crate::backend::Backend;
+use crate::bench::{BenchArgs, BenchRun};
+use crate::config::UnfocusedPolicy;
+use crate::golden::{GoldenArgs, GoldenRun};
+use crate::App;
+use anyhow::{anyhow, Result};
+use clap::Subcommand;
+use cubic_platform::winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+use cubic_render_vk::{describe_gpus, GpuInfo};
+use std::path::PathBuf;
+use tracing::info;
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// List the GPUs Vulkan sees: requirements met, surface formats,
+    /// present modes and optional extensions.
+    Info,
+    /// Render a test scene along a scripted camera path and report the
+    /// frame times as JSON or CSV (see bench.rs).
+    Bench(BenchArgs),
+    /// Render the last world and save one frame to a PNG (Vulkan or GL).
+    Capture {
+        /// Image to write.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// Frames rendered first, for chunks around the camera to stream
+        /// in.
+        #[arg(long, default_value_t = 120)]
+        warmup: u32,
+        /// Hide the window.
+        #[arg(long)]
+        offscreen: bool,
+    },
+    /// Render known views and compare them with stored golden images
+    /// (see golden.rs).
+    Golden(GoldenArgs),
+}
+
+// ---------------------------------------------------------------------------
+// info
+// ---------------------------------------------------------------------------
+
+/// `cubic info`: print describe_gpus for a hidden window.
+pub(crate) fn run_info(event_loop: EventLoop<()>, gpu: Option<String>) -> Result<()> {
+    let mut app = InfoApp { gpu, result: None };
+    event_loop.run_app(&mut app)?;
+    let gpus = app
+        .result
+        .unwrap_or_else(|| Err(anyhow!("the event loop ended before a window was created")))?;
+    print!("{}", format_gpus(&gpus));
+    Ok(())
+}
+
+struct InfoApp {
+    gpu: Option<String>,
+    result: Option<Result<Vec<GpuInfo>>>,
+}
+
+impl ApplicationHandler for InfoApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.result.is_none() {
+            let attrs = Window::default_attributes()
+                .with_title("cubic info")
+                .with_visible(false);
+            self.result = Some(
+                event_loop
+                    .create_window(attrs)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|w| describe_gpus(&w, &w, self.gpu.as_deref())),
+            );
+        }
+        event_loop.exit();
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, _: WindowEvent) {}
+}
+
+fn format_gpus(gpus: &[GpuInfo]) -> String {
+    let mut out = String::new();
+    if gpus.is_empty() {
+        out.push_str("No Vulkan GPUs found.\n");
+    }
+    for gpu in gpus {
+        let (major, minor, patch) = gpu.api_version;
+        out.push_str(&format!(
+            "GPU {}: {} ({}, Vulkan {major}.{minor}.{patch}){}\n",
+            gpu.index,
+            gpu.name,
+            gpu.device_type,
+            if gpu.selected { " — selected" } else { "" }
+        ));
+        if gpu.missing.is_empty() {
+            out.push_str("  requirements: met\n");
+        } else {
+            out.push_str(&format!(
+                "  requirements: lacks {}\n",
+                gpu.missing.join(", ")
+            ));
+        }
+        if gpu.can_present {
+            out.push_str("  surface formats:\n");
+            for f in &gpu.surface_formats {
+                out.push_str(&format!("    {f}\n"));
+            }
+            out.push_str(&format!(
+                "  present modes: {}\n",
+                gpu.present_modes.join(", ")
+            ));
+        } else {
+            out.push_str("  can't present to a window\n");
+        }
+        out.push_str("  optional extensions:\n");
+        for (name, has) in &gpu.extensions {
+            out.push_str(&format!("    {} {name}\n", if *has { "+" } else { "-" }));
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// bench / capture / golden
+// ---------------------------------------------------------------------------
+
+enum Task {
+    Bench(BenchRun),
+    Capture { output: PathBuf, warmup: u32 },
+    Golden(GoldenRun),
+}
+
+/// A `bench`, `capture` or `golden` in progress; see the module docs.
+pub(crate) struct AutoRun {
+    task: Task,
+    pub(crate) offscreen: bool,
+    /// Frames rendered since the world was loaded.
+    rendered: u32,
+    /// Set when the run ends; main returns it.
+    outcome: Option<Result<()>>,
+}
+
+impl AutoRun {
+    /// The run for `command`; None for `info`, which isn't one.
+    pub(crate) fn new(command: Command) -> Option<Self> {
+        let (task, offscreen) = match command {
+            Command::Info => return None,
+            Command::Bench(args) => {
+                let offscreen = args.offscreen;
+                (Task::Bench(BenchRun::new(args)), offscreen)
+            }
+            Command::Capture {
+                output,
+                warmup,
+                offscreen,
+            } => (Task::Capture { output, warmup }, offscreen),
+            Command::Golden(args) => {
+                let offscreen = args.offscreen;
+                (Task::Golden(GoldenRun::new(args)), offscreen)
+            }
+        };
+        Some(Self {
+            task,
+            offscreen,
+            rendered: 0,
+            outcome: None,
+        })
+    }
+
+    /// What main returns once the event loop is done.
+    pub(crate) fn finish(self) -> Result<()> {
+        self.outcome
+            .unwrap_or_else(|| Err(anyhow!("closed before the run finished")))
+    }
+}
+
+impl App {
+    /// Skip the launcher: load the world (the bench world for a bench or
+    /// golden, else the last one) and go straight in game.
+    pub(crate) fn start_auto_run(&mut self) {
+        // A hidden or backgrounded window mustn't slow the run down.
+        self.cfg.render.unfocused = UnfocusedPolicy::None;
+        let bench_seed = match self.auto_run.as_ref().map(|run| &run.task) {
+            Some(Task::Bench(bench)) => Some(bench.seed()),
+            Some(Task::Golden(golden)) => {
+                // A frame counter would never match its golden.
+                self.cfg.ui.show_fps = false;
+                Some(golden.seed())
+            }
+            _ => None,
+        };
+        if let Some(seed) = bench_seed {
+            self.use_bench_world(seed);
+        }
+        self.load_world();
+        self.state = crate::AppState::InGame;
+    }
+
+    /// Move the camera along a bench's path or to a golden's scene, after
+    /// the game has placed it for this frame.
+    pub(crate) fn auto_run_camera(&mut self) {
+        let Some(run) = &mut self.auto_run else {
+            return;
+        };
+        match &mut run.task {
+            Task::Bench(bench) => bench.apply_camera(&mut self.camera, run.rendered),
+            Task::Golden(golden) => golden.apply_camera(&mut self.camera),
+            Task::Capture { .. } => {}
+        }
+    }
+
+    /// After a frame rendered in game: advance the run, and quit once it's
+    /// done.
+    pub(crate) fn auto_run_frame(&mut self, backend: &mut Backend) {
+        let Some(run) = &mut self.auto_run else {
+            return;
+        };
+        if run.outcome.is_some() {
+            return;
+        }
+        run.rendered += 1;
+        let outcome = match &mut run.task {
+            Task::Bench(bench) => {
+                let done = bench.frame_rendered(
+                    run.rendered,
+                    self.last_frame_dt * 1000.0,
+                    self.last_render_cpu_ms,
+                );
+                done.then(|| {
+                    let size = [self.render_size.width, self.render_size.height];
+                    bench.report(backend, size, self.world.stream.stream_counts())
+                })
+            }
+            // Capture starts after the warm-up and catches the next frame.
+            Task::Capture { output, warmup } if run.rendered == *warmup + 1 => {
+                match backend.start_screenshot(output) {
+                    Ok(()) => None,
+                    Err(e) => Some(Err(e)),
+                }
+            }
+            Task::Capture { output, warmup } if run.rendered > *warmup + 1 => {
+                Some(backend.finish_screenshot(output).map(|()| {
+                    info!("saved {}", output.display());
+                }))
+            }
+            Task::Capture { .. } => None,
+            Task::Golden(golden) => {
+                golden.frame_rendered(backend, self.world.stream.stream_counts())
+            }
+        };
+        if outcome.is_some() {
+            run.outcome = outcome;
+            self.quit_requested = true;
+        }
+    }
+}
@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-//! Command dispatcher. Host-side built-ins + WASM game command delegation.
+//! Command dispatcher. Host-side built-ins, then engine plugins' commands
+//! (see plugin.rs), then WASM game command delegation.
 
+use crate::backend::{BackendChoice, RendererBackend};
+use crate::config::{save_global_cfg, InternalScalingCfg, RenderCfg, WindowMode};
 use crate::ui::ChatMessageKind;
 use crate::App;
 
@@ -15,10 +18,17 @@ pub(crate) fn dispatch(app: &mut App, input: &str) {
         "tp" => cmd_tp(app, &args),
         "set" => cmd_set(app, &args),
         "help" => cmd_help(app, &args),
+        "reload" => Ok(app.reload_settings()),
+        "window" => cmd_window(app, &args),
+        "backend" => cmd_backend(app, &args),
+        "resolution" => cmd_resolution(app, &args),
         "locate" => Ok("Biome location not yet implemented.".to_string()),
         other => {
-            // Check game-registered commands
-            if let Some(cmd) = app
+            if let Some(result) =
+                app.with_plugins(None, |plugins, ctx| plugins.command(ctx, other, &args))
+            {
+                result
+            } else if let Some(cmd) = app
                 .guest
                 .registered_commands
                 .iter()
@@ -35,8 +45,9 @@ pub(crate) fn dispatch(app: &mut App, input: &str) {
                     app.push_chat_message(result, ChatMessageKind::CommandOutput);
                 }
                 return;
+            } else {
+                Err(format!("Unknown command: /{other}. Type /help for a list."))
             }
-            Err(format!("Unknown command: /{other}. Type /help for a list."))
         }
     };
 
@@ -62,12 +73,17 @@ pub(crate) fn completions(app: &App, input: &str, cursor_pos: usize) -> Vec<Stri
     // Completing the command name itself
     if tokens.is_empty() || (tokens.len() == 1 && !ends_with_space) {
         let partial = tokens.first().copied().unwrap_or("");
-        let mut matches: Vec<String> = ["tp", "set", "help", "locate"]
+        let mut matches: Vec<String> = ["tp", "set", "help", "locate", "reload", "backend"]
             .iter()
             .filter(|c| c.starts_with(partial))
             .map(|c| format!("/{c}"))
             .collect();
-        // Add game-registered commands
+        // Add plugin and game-registered commands
+        for cmd in app.plugins.commands() {
+            if cmd.name.starts_with(partial) {
+                matches.push(format!("/{}", cmd.name));
+            }
+        }
         for cmd in &app.guest.registered_commands {
             if cmd.name.starts_with(partial) {
                 matches.push(format!("/{}", cmd.name));
@@ -123,8 +139,53 @@ pub(crate) fn completions(app: &App, input: &str, cursor_pos: usize) -> Vec<Stri
                 vec![]
             }
         }
+        "window" => {
+            if arg_index == 0 {
+                WINDOW_MODES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .filter(|m| m.starts_with(partial))
+                    .map(String::from)
+                    .collect()
+            } else {
+                vec![]
+            }
+        }
+        "backend" => {
+            if arg_index == 0 {
+                BackendChoice::NAMES
+                    .iter()
+                    .filter(|b| b.starts_with(partial))
+                    .map(|b| b.to_string())
+                    .collect()
+            } else {
+                vec![]
+            }
+        }
+        "resolution" => match arg_index {
+            0 => ["off", "640x360", "320x180"]
+                .iter()
+                .filter(|r| r.starts_with(partial))
+                .map(|r| r.to_string())
+                .collect(),
+            1 => ["integer", "nearest"]
+                .iter()
+                .filter(|s| s.starts_with(partial))
+                .map(|s| s.to_string())
+                .collect(),
+            _ => vec![],
+        },
         "help" => {
-            let builtins = ["tp", "set", "help", "locate"];
+            let builtins = [
+                "tp",
+                "set",
+                "help",
+                "locate",
+                "reload",
+                "window",
+                "backend",
+                "resolution",
+            ];
             builtins
                 .iter()
                 .filter(|c| c.starts_with(partial))
@@ -132,6 +193,17 @@ pub(crate) fn completions(app: &App, input: &str, cursor_pos: usize) -> Vec<Stri
                 .collect()
         }
         _ => {
+            if let Some(cmd) = app.plugins.commands().find(|c| c.name == cmd) {
+                if arg_index > 0 {
+                    return vec![];
+                }
+                return cmd
+                    .args
+                    .iter()
+                    .filter(|a| a.starts_with(partial))
+                    .map(|a| a.to_string())
+                    .collect();
+            }
             // Game-registered command completions
             if let Some(cmd) = app.guest.registered_commands.iter().find(|c| c.name == cmd) {
                 if let Some(values) = cmd.completions.get(&(arg_index as u32)) {
@@ -265,6 +337,107 @@ fn cmd_set(app: &mut App, args: &[&str]) -> Result<String, String> {
     Ok(format!("{key} = {val}"))
 }
 
+// ---------------------------------------------------------------------------
+// /window
+// ---------------------------------------------------------------------------
+
+const WINDOW_MODES: &[(&str, WindowMode)] = &[
+    ("windowed", WindowMode::Windowed),
+    ("maximized", WindowMode::Maximized),
+    ("borderless", WindowMode::BorderlessFullscreen),
+    ("exclusive", WindowMode::ExclusiveFullscreen),
+];
+
+fn cmd_window(app: &mut App, args: &[&str]) -> Result<String, String> {
+    let Some(&arg) = args.first() else {
+        let current = app.launcher.window_mode;
+        let name = WINDOW_MODES
+            .iter()
+            .find(|(_, m)| *m == current)
+            .map_or("?", |(name, _)| name);
+        return Ok(format!("window mode: {name}"));
+    };
+    let Some(&(name, mode)) = WINDOW_MODES.iter().find(|(name, _)| *name == arg) else {
+        return Err(format!(
+            "Unknown window mode: {arg}. Use windowed, maximized, borderless or exclusive."
+        ));
+    };
+    app.set_window_mode(mode);
+    Ok(format!("window mode: {name}"))
+}
+
+// ---------------------------------------------------------------------------
+// /backend
+// ---------------------------------------------------------------------------
+
+fn cmd_backend(app: &mut App, args: &[&str]) -> Result<String, String> {
+    let Some(&arg) = args.first() else {
+        let running = app.backend.as_ref().map_or("none", |b| b.name());
+        return Ok(format!(
+            "backend: {running} (requested {})",
+            app.backend_choice.name()
+        ));
+    };
+    let Some(choice) = BackendChoice::parse(arg) else {
+        return Err(format!(
+            "Unknown backend: {arg}. Use {}.",
+            BackendChoice::NAMES.join(", ")
+        ));
+    };
+    app.switch_backend(choice)
+}
+
+// ---------------------------------------------------------------------------
+// /resolution
+// ---------------------------------------------------------------------------
+
+fn describe_resolution(render: &RenderCfg) -> String {
+    match render.internal_resolution {
+        Some([w, h]) => {
+            let scaling = match render.internal_scaling {
+                InternalScalingCfg::Integer => "integer",
+                InternalScalingCfg::Nearest => "nearest",
+            };
+            format!("internal resolution: {w}x{h} ({scaling} scaling)")
+        }
+        None => "internal resolution: off (the window's)".to_string(),
+    }
+}
+
+fn cmd_resolution(app: &mut App, args: &[&str]) -> Result<String, String> {
+    let render = &mut app.cfg.render;
+    match args {
+        [] => return Ok(describe_resolution(render)),
+        ["off"] => render.internal_resolution = None,
+        [size, rest @ ..] if rest.len() <= 1 => {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(w, h)| Some([w.parse::<u32>().ok()?, h.parse::<u32>().ok()?]))
+                .filter(|&[w, h]| w > 0 && h > 0);
+            let Some(size) = parsed else {
+                return Err(format!("Expected <width>x<height>, got '{size}'"));
+            };
+            render.internal_scaling = match rest.first() {
+                None => render.internal_scaling,
+                Some(&"integer") => InternalScalingCfg::Integer,
+                Some(&"nearest") => InternalScalingCfg::Nearest,
+                Some(other) => {
+                    return Err(format!("Unknown scaling: {other}. Use integer or nearest."))
+                }
+            };
+            render.internal_resolution = Some(size);
+        }
+        _ => {
+            return Err("Usage: /resolution [<width>x<height> [integer|nearest] | off]".to_string())
+        }
+    }
+    if let Some(backend) = &mut app.backend {
+        backend.configure_advanced(&app.cfg.render);
+    }
+    save_global_cfg(&app.cfg);
+    Ok(describe_resolution(&app.cfg.render))
+}
+
 // ---------------------------------------------------------------------------
 // /help
 // ---------------------------------------------------------------------------
@@ -274,8 +447,16 @@ fn cmd_help(app: &App, args: &[&str]) -> Result<String, String> {
         let mut out = "/tp [@p|@c] <x> <y> <z> — teleport (~ for relative)\n\
               /set [<key> <value>] — view/change hot config\n\
               /locate biome <name> — find biome (not yet implemented)\n\
+              /reload — re-read cubic.toml and apply [render] changes\n\
+              /window [mode] — show/switch window mode\n\
+              /backend [auto|vk|gl|wgpu|null] — show/switch renderer backend\n\
+              /resolution [WxH [integer|nearest] | off] — fixed internal resolution\n\
               /help [command] — show help"
             .to_string();
+        for cmd in app.plugins.commands() {
+            out.push('\n');
+            out.push_str(cmd.usage);
+        }
         if !app.guest.registered_commands.is_empty() {
             out.push_str("\nGame commands:");
             for cmd in &app.guest.registered_commands {
@@ -296,8 +477,33 @@ fn cmd_help(app: &App, args: &[&str]) -> Result<String, String> {
             "locate" => {
                 Ok("/locate biome <name> — find nearest biome (not yet implemented)".to_string())
             }
+            "reload" => Ok("/reload — re-read cubic.toml (plus game overrides and \
+                            profile) and apply [render] changes live; also \
+                            happens automatically when the file is saved"
+                .to_string()),
+            "window" => Ok("/window — show the current window mode\n\
+                            /window <mode> — switch and remember it (cubic.toml \
+                            and profile). Modes: windowed maximized borderless \
+                            exclusive"
+                .to_string()),
+            "backend" => Ok("/backend — show the running renderer backend\n\
+                             /backend <auto|vk|gl|wgpu|null> — tear it down and bring \
+                             that one up on the same window (this session only; \
+                             --backend sets the startup one). The world's \
+                             meshes and textures are uploaded again"
+                .to_string()),
+            "resolution" => Ok("/resolution — show the internal resolution\n\
+                                /resolution <width>x<height> [integer|nearest] — render \
+                                the scene at that size, scaled up to the window with \
+                                letterbox bars (Vulkan only), and remember it in \
+                                cubic.toml\n\
+                                /resolution off — back to the window's resolution"
+                .to_string()),
             "help" => Ok("/help [command] — list commands or show usage for one".to_string()),
             other => {
+                if let Some(cmd) = app.plugins.commands().find(|c| c.name == other) {
+                    return Ok(cmd.help.to_string());
+                }
                 if let Some(cmd) = app
                     .guest
                     .registered_commands
@@ -8,8 +8,10 @@ use std::fs;
 
 use crate::{game_override, profile};
 
+/// cubic.toml, resolved through game_overrides.toml and the profile. Its
+/// sections are the app's own; a plugin outside this crate gets it opaque.
 #[derive(Debug, Deserialize, Serialize, Default)]
-pub(crate) struct AppCfg {
+pub struct AppCfg {
     #[serde(default)]
     pub(crate) render: RenderCfg,
     #[serde(default)]
@@ -21,22 +23,160 @@ pub(crate) struct AppCfg {
     #[serde(default)]
     pub(crate) game: GameCfg,
     #[serde(default)]
+    pub(crate) physics: PhysicsCfg,
+    #[serde(default)]
     pub(crate) controls: ControlsCfg,
     #[serde(default)]
     pub(crate) launcher: LauncherCfg,
     #[serde(default)]
     pub(crate) ui: UiCfg,
+    #[serde(default)]
+    pub(crate) window: WindowCfg,
+    #[serde(default, skip_serializing_if = "DebugCfg::is_default")]
+    pub(crate) debug: DebugCfg,
+    #[serde(default, skip_serializing_if = "PostCfg::is_default")]
+    pub(crate) post: PostCfg,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum VsyncMode {
     Fifo,
     #[default]
     Mailbox,
+    /// Adaptive vsync: FIFO that tears rather than waits on a late frame.
+    FifoRelaxed,
+    Immediate,
+}
+
+/// `[render] present_mode_priority`: vsync modes to try in order (first the
+/// surface supports wins) before `vsync_mode` and its fallbacks, e.g.
+/// `["mailbox", "fifo_relaxed", "fifo"]`. A list in cubic.toml; held as a
+/// fixed array (each mode at most once, repeats dropped) so RenderCfg
+/// stays Copy.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(from = "Vec<VsyncMode>", into = "Vec<VsyncMode>")]
+pub(crate) struct PresentModePriority {
+    modes: [VsyncMode; 4],
+    len: usize,
+}
+
+impl PresentModePriority {
+    pub(crate) fn as_slice(&self) -> &[VsyncMode] {
+        &self.modes[..self.len]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl From<Vec<VsyncMode>> for PresentModePriority {
+    fn from(modes: Vec<VsyncMode>) -> Self {
+        let mut out = Self::default();
+        for m in modes {
+            if !out.as_slice().contains(&m) {
+                out.modes[out.len] = m;
+                out.len += 1;
+            }
+        }
+        out
+    }
+}
+
+impl From<PresentModePriority> for Vec<VsyncMode> {
+    fn from(p: PresentModePriority) -> Self {
+        p.as_slice().to_vec()
+    }
+}
+
+/// One `[render] post_effects` entry: see cubic_render_vk::PostEffect.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PostEffectCfg {
+    #[default]
+    Bloom,
+    Vignette,
+    Fxaa,
+    #[serde(alias = "lut")]
+    ColorGrade,
+}
+
+/// `[render] post_effects`: the post-processing chain in the order it
+/// runs, e.g. `["bloom", "color_grade", "fxaa"]`; leave an effect out to
+/// turn it off. A fixed array like PresentModePriority, for the same
+/// reason.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(from = "Vec<PostEffectCfg>", into = "Vec<PostEffectCfg>")]
+pub(crate) struct PostEffects {
+    effects: [PostEffectCfg; 4],
+    len: usize,
+}
+
+impl PostEffects {
+    pub(crate) fn as_slice(&self) -> &[PostEffectCfg] {
+        &self.effects[..self.len]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl From<Vec<PostEffectCfg>> for PostEffects {
+    fn from(effects: Vec<PostEffectCfg>) -> Self {
+        let mut out = Self::default();
+        for e in effects {
+            if !out.as_slice().contains(&e) {
+                out.effects[out.len] = e;
+                out.len += 1;
+            }
+        }
+        out
+    }
+}
+
+impl From<PostEffects> for Vec<PostEffectCfg> {
+    fn from(p: PostEffects) -> Self {
+        p.as_slice().to_vec()
+    }
+}
+
+/// `[render] color_filter`: see cubic_render_vk::ColorFilter.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ColorFilterCfg {
+    #[default]
+    Off,
+    #[serde(alias = "greyscale")]
+    Grayscale,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    DaltonizeProtanopia,
+    DaltonizeDeuteranopia,
+    DaltonizeTritanopia,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default)]
+/// How the game window is shown (App::set_window_mode). The profile's
+/// remembered `[window] mode` keeps its own spellings (see
+/// ui::window_mode_to_str); this is cubic.toml's.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WindowMode {
+    #[default]
+    Windowed,
+    Maximized,
+    /// A monitor-sized undecorated window, still composited.
+    #[serde(alias = "fullscreen")]
+    BorderlessFullscreen,
+    /// The monitor switched to a video mode and handed to the game alone;
+    /// on Vulkan with VK_EXT_full_screen_exclusive the swapchain is allowed
+    /// to bypass the compositor too.
+    ExclusiveFullscreen,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum UnfocusedPolicy {
     None,
@@ -45,7 +185,36 @@ pub(crate) enum UnfocusedPolicy {
     Throttle,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default)]
+/// `[render] latency_mode`: see cubic_render::LatencyMode.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LatencyModeCfg {
+    #[default]
+    Throughput,
+    Low,
+}
+
+/// `[render] upscaler`: see cubic_render_vk::Upscaler.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UpscalerCfg {
+    Nearest,
+    #[default]
+    #[serde(alias = "linear")]
+    Bilinear,
+    Fsr1,
+}
+
+/// `[render] internal_scaling`: see cubic_render_vk::PixelScaling.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InternalScalingCfg {
+    #[default]
+    Integer,
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum HdrFlavorCfg {
     #[default]
@@ -69,32 +238,121 @@ pub(crate) enum MipmapMode {
     Linear,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+/// `[render.hdr_display]`: manual HDR10 calibration. Every key is optional
+/// and overrides just that value on top of what the renderer detected from
+/// the display's EDID (or its generic 1000-nit BT.2020 fallback). Chromaticities
+/// are CIE 1931 `[x, y]`; luminances are nits.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub(crate) struct HdrDisplayCfg {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_luminance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min_luminance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_frame_average_luminance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) red: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) green: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) blue: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) white_point: Option<[f32; 2]>,
+}
+
+impl HdrDisplayCfg {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 pub(crate) struct RenderCfg {
     #[serde(default = "default_clear")]
     pub(crate) clear_color: [f32; 4],
     #[serde(default = "default_vsync")]
     pub(crate) vsync: bool,
+    // GL has none of these: it's vsync on/off, and fifo_relaxed logs that
+    // it falls back to plain vsync.
     #[serde(default)]
     pub(crate) vsync_mode: VsyncMode,
+    // Vulkan only, like vsync_mode. Skipped when empty, like hdr_display.
+    #[serde(default, skip_serializing_if = "PresentModePriority::is_empty")]
+    pub(crate) present_mode_priority: PresentModePriority,
     #[serde(default)]
     pub(crate) unfocused: UnfocusedPolicy,
     #[serde(default)]
     pub(crate) unfocused_fps: u32,
     #[serde(default)]
     pub(crate) fps_when_vsync_off: u32,
+    // Log a "frame time spike" warning for frames slower than this many
+    // ms (the renderer's frame_stats tracker); 0 = off.
+    #[serde(default)]
+    pub(crate) frame_spike_ms: f32,
+    // "low": render() waits for each frame to reach the display before
+    // the next one's input/simulation starts; less latency, lower fps.
+    #[serde(default)]
+    pub(crate) latency_mode: LatencyModeCfg,
     #[serde(default)]
     pub(crate) hdr: bool,
     #[serde(default)]
     pub(crate) hdr_flavor: HdrFlavorCfg,
+    // Brightness of SDR white on an HDR swapchain, in nits (Vulkan only;
+    // the renderer clamps it to 80..=1000).
+    #[serde(default = "default_sdr_white_nits")]
+    pub(crate) sdr_white_nits: f32,
+    // sRGB-encode the output when the only SDR swapchain format on offer
+    // is UNORM (or GL's default framebuffer isn't sRGB); off leaves it
+    // looking too dark.
+    #[serde(default = "default_srgb_encode")]
+    pub(crate) srgb_encode: bool,
     #[serde(default)]
     pub(crate) texture_filter: TextureFilter,
     #[serde(default)]
     pub(crate) mipmap_mode: MipmapMode,
-    #[serde(default = "default_anisotropy")]
-    pub(crate) anisotropy: f32,
+    // Upper bound for anisotropic filtering; the renderer clamps it to the
+    // device limit, and forces 0.0 (off) where samplerAnisotropy isn't
+    // supported. Was `anisotropy` before — still accepted on load, and
+    // save_global_cfg renames it in place (see LEGACY_KEY_RENAMES).
+    #[serde(default = "default_anisotropy", alias = "anisotropy")]
+    pub(crate) max_anisotropy: f32,
     #[serde(default)]
     pub(crate) lod_bias: f32,
+    // Scene resolution as a fraction of the window's (0.25..=1.0), brought
+    // back up to full size by `upscaler` (Vulkan only).
+    #[serde(default = "default_render_scale")]
+    pub(crate) render_scale: f32,
+    #[serde(default)]
+    pub(crate) upscaler: UpscalerCfg,
+    // Fixed scene resolution, e.g. [640, 360] for pixel art, scaled up to
+    // the window by `internal_scaling` with letterbox bars; replaces
+    // render_scale/upscaler while set (Vulkan only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) internal_resolution: Option<[u32; 2]>,
+    #[serde(default)]
+    pub(crate) internal_scaling: InternalScalingCfg,
+    // Depth-only pass before the colour pass so overdrawn fragments are
+    // rejected by early-Z instead of shaded (Vulkan only).
+    #[serde(default)]
+    pub(crate) depth_prepass: bool,
+    // Cascaded shadow maps for the sun (Vulkan only); shadow_resolution is
+    // each cascade's width/height in texels.
+    #[serde(default)]
+    pub(crate) shadows: bool,
+    #[serde(default = "default_shadow_resolution")]
+    pub(crate) shadow_resolution: u32,
+    // Post-processing between the scene and the tonemap (Vulkan only);
+    // color_grade's LUT is `[post] lut`.
+    #[serde(default, skip_serializing_if = "PostEffects::is_empty")]
+    pub(crate) post_effects: PostEffects,
+    // Accessibility filter after the post effects: grayscale, or
+    // simulating / daltonizing for a colour-blindness (Vulkan only).
+    #[serde(default)]
+    pub(crate) color_filter: ColorFilterCfg,
+    // Skipped when empty so save_global_cfg doesn't add a bare
+    // `hdr_display = {}` to every cubic.toml.
+    #[serde(default, skip_serializing_if = "HdrDisplayCfg::is_unset")]
+    pub(crate) hdr_display: HdrDisplayCfg,
 }
 
 impl Default for RenderCfg {
@@ -103,15 +361,30 @@ impl Default for RenderCfg {
             clear_color: default_clear(),
             vsync: true,
             vsync_mode: VsyncMode::Mailbox,
+            present_mode_priority: PresentModePriority::default(),
             unfocused: UnfocusedPolicy::Throttle,
             unfocused_fps: 30,
             fps_when_vsync_off: 0,
+            frame_spike_ms: 0.0,
+            latency_mode: LatencyModeCfg::Throughput,
             hdr: false,
             hdr_flavor: HdrFlavorCfg::PreferScrgb,
+            sdr_white_nits: default_sdr_white_nits(),
+            srgb_encode: true,
             texture_filter: TextureFilter::Linear,
             mipmap_mode: MipmapMode::Linear,
-            anisotropy: default_anisotropy(),
+            max_anisotropy: default_anisotropy(),
             lod_bias: 0.0,
+            render_scale: default_render_scale(),
+            upscaler: UpscalerCfg::Bilinear,
+            internal_resolution: None,
+            internal_scaling: InternalScalingCfg::Integer,
+            depth_prepass: false,
+            shadows: false,
+            shadow_resolution: default_shadow_resolution(),
+            post_effects: PostEffects::default(),
+            color_filter: ColorFilterCfg::Off,
+            hdr_display: HdrDisplayCfg::default(),
         }
     }
 }
@@ -122,9 +395,21 @@ fn default_clear() -> [f32; 4] {
 fn default_vsync() -> bool {
     true
 }
+fn default_sdr_white_nits() -> f32 {
+    200.0
+}
+fn default_srgb_encode() -> bool {
+    true
+}
 fn default_anisotropy() -> f32 {
     0.0
 }
+fn default_render_scale() -> f32 {
+    1.0
+}
+fn default_shadow_resolution() -> u32 {
+    2048
+}
 pub(crate) fn load_cfg() -> AppCfg {
     match fs::read_to_string("cubic.toml") {
         Ok(s) => toml::from_str::<AppCfg>(&s).unwrap_or_default(),
@@ -175,8 +460,8 @@ pub(crate) fn apply_game_override(
         if let Some(v) = r.vsync {
             cfg.render.vsync = v;
         }
-        if let Some(v) = r.anisotropy {
-            cfg.render.anisotropy = v;
+        if let Some(v) = r.max_anisotropy {
+            cfg.render.max_anisotropy = v;
         }
         if let Some(v) = r.lod_bias {
             cfg.render.lod_bias = v;
@@ -241,12 +526,21 @@ pub(crate) fn apply_profile(mut cfg: AppCfg, profile: &profile::ProfileCfg) -> A
         if let Some(v) = r.vsync {
             cfg.render.vsync = v;
         }
-        if let Some(v) = r.anisotropy {
-            cfg.render.anisotropy = v;
+        if let Some(v) = r.max_anisotropy {
+            cfg.render.max_anisotropy = v;
         }
         if let Some(v) = r.lod_bias {
             cfg.render.lod_bias = v;
         }
+        if let Some(v) = r.depth_prepass {
+            cfg.render.depth_prepass = v;
+        }
+        if let Some(v) = r.shadows {
+            cfg.render.shadows = v;
+        }
+        if let Some(v) = r.shadow_resolution {
+            cfg.render.shadow_resolution = v;
+        }
         if let Some(v) = &r.texture_filter {
             match parse_cfg_str::<TextureFilter>(v) {
                 Some(tf) => cfg.render.texture_filter = tf,
@@ -281,6 +575,9 @@ pub(crate) fn apply_profile(mut cfg: AppCfg, profile: &profile::ProfileCfg) -> A
         if let Some(v) = c.mouse_sensitivity {
             cfg.camera.mouse_sensitivity = v;
         }
+        if let Some(v) = c.invert_y {
+            cfg.camera.invert_y = v;
+        }
     }
     if let Some(p) = &profile.player {
         if let Some(v) = p.walk_speed {
@@ -404,6 +701,18 @@ fn default_stream_radius() -> i32 {
     8
 }
 
+/// `[world] save_compression`: how edited chunks saved whole are
+/// compressed in region files; see cubic_world::ChunkCompression. "zstd"
+/// needs the "zstd" build feature and saves as LZ4 without it.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SaveCompressionCfg {
+    None,
+    #[default]
+    Lz4,
+    Zstd,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
 pub(crate) struct WorldCfg {
     #[serde(default = "default_stream_radius")]
@@ -414,12 +723,20 @@ pub(crate) struct WorldCfg {
     pub(crate) upload_budget_ms: f32,
     #[serde(default = "default_upload_budget_min_ms")]
     pub(crate) upload_budget_min_ms: f32,
+    #[serde(default)] // 0 = no cap, only the time budget
+    pub(crate) max_chunk_uploads: u32,
+    #[serde(default = "default_lod_distance")] // 0 = full detail everywhere
+    pub(crate) lod_distance: i32,
     #[serde(default = "default_stream_radius_y")]
     pub(crate) stream_radius_y: i32,
     #[serde(default = "default_diff_threshold")]
     pub diff_threshold: usize,
     #[serde(default = "default_autosave_interval_s")]
     pub autosave_interval_s: u64,
+    #[serde(default)] // 0 = on the interval only
+    pub(crate) autosave_dirty_chunks: usize,
+    #[serde(default)]
+    pub(crate) save_compression: SaveCompressionCfg,
 }
 
 impl Default for WorldCfg {
@@ -429,9 +746,13 @@ impl Default for WorldCfg {
             seed: 0,
             upload_budget_ms: 0.0,
             upload_budget_min_ms: default_upload_budget_min_ms(),
+            max_chunk_uploads: 0,
+            lod_distance: default_lod_distance(),
             stream_radius_y: default_stream_radius_y(),
             autosave_interval_s: default_autosave_interval_s(),
             diff_threshold: default_diff_threshold(),
+            autosave_dirty_chunks: 0,
+            save_compression: SaveCompressionCfg::default(),
         }
     }
 }
@@ -450,6 +771,11 @@ pub(crate) struct CameraCfg {
     pub(crate) move_speed: f32,
     #[serde(default = "default_mouse_sensitivity")]
     pub(crate) mouse_sensitivity: f32,
+    // Flips vertical mouse look. Applied where raw motion is accumulated
+    // (see device_event), so it covers both the free-fly camera and the
+    // look_dy a loaded game receives.
+    #[serde(default)]
+    pub(crate) invert_y: bool,
 }
 
 impl Default for CameraCfg {
@@ -457,6 +783,7 @@ impl Default for CameraCfg {
         CameraCfg {
             move_speed: default_move_speed(),
             mouse_sensitivity: default_mouse_sensitivity(),
+            invert_y: false,
         }
     }
 }
@@ -530,6 +857,10 @@ pub(crate) struct UiCfg {
     pub(crate) crosshair_path: String,
     #[serde(default = "default_crosshair_size")]
     pub(crate) crosshair_size: f32,
+    /// FPS counter drawn in-game by the renderer's text overlay (Vulkan
+    /// only for now).
+    #[serde(default)]
+    pub(crate) show_fps: bool,
 }
 
 impl Default for UiCfg {
@@ -537,6 +868,7 @@ impl Default for UiCfg {
         UiCfg {
             crosshair_path: default_crosshair_path(),
             crosshair_size: default_crosshair_size(),
+            show_fps: false,
         }
     }
 }
@@ -555,6 +887,10 @@ pub(crate) struct GameCfg {
     pub(crate) path: String,
     #[serde(default = "default_wasm_memory_mb")]
     pub(crate) wasm_memory_mb: usize,
+    /// Fixed simulation rate for the game's on_tick, in Hz; 0 ticks once
+    /// per frame with the frame's delta (see game_loop.rs).
+    #[serde(default)]
+    pub(crate) tick_hz: u32,
 }
 
 fn default_game_path() -> String {
@@ -570,10 +906,57 @@ impl Default for GameCfg {
         GameCfg {
             path: default_game_path(),
             wasm_memory_mb: default_wasm_memory_mb(),
+            tick_hz: 0,
         }
     }
 }
 
+/// `[physics]`: the rigid-body simulation (cubic-physics), stepped with the
+/// game tick. The player isn't a rigid body; these only affect bodies
+/// spawned into the scene (e.g. with /physics).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub(crate) struct PhysicsCfg {
+    /// m/s², along Y.
+    #[serde(default = "default_physics_gravity")]
+    pub(crate) gravity: f32,
+    /// Chunks (Chebyshev distance from the camera's) that get colliders.
+    /// Bodies further out than this fall through the world.
+    #[serde(default = "default_collider_radius")]
+    pub(crate) collider_radius: i32,
+    #[serde(default)]
+    pub(crate) chunk_colliders: ChunkCollidersCfg,
+}
+
+fn default_physics_gravity() -> f32 {
+    -cubic_physics::GRAVITY
+}
+
+fn default_collider_radius() -> i32 {
+    2
+}
+
+impl Default for PhysicsCfg {
+    fn default() -> Self {
+        PhysicsCfg {
+            gravity: default_physics_gravity(),
+            collider_radius: default_collider_radius(),
+            chunk_colliders: ChunkCollidersCfg::default(),
+        }
+    }
+}
+
+/// `[physics] chunk_colliders`: what chunk colliders are built from (see
+/// cubic_physics::ChunkColliderSource).
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ChunkCollidersCfg {
+    /// Solid voxels merged into boxes.
+    #[default]
+    Voxels,
+    /// The chunk's render mesh as a triangle mesh.
+    Mesh,
+}
+
 fn default_launcher_width() -> u32 {
     800
 }
@@ -603,6 +986,173 @@ impl Default for LauncherCfg {
     }
 }
 
+/// `[window]`: how the window is created (title, icon, size, placement,
+/// decorations; read once, with command-line overrides on top, see
+/// App::startup_window; the size limits are re-read on reload, see
+/// App::apply_window_limits), and the window mode a profile starts in when it
+/// hasn't remembered one of its own, saved by App::set_window_mode.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub(crate) struct WindowCfg {
+    #[serde(default = "default_window_title")]
+    pub(crate) title: String,
+    /// Image file for the window/taskbar icon; empty for the platform's
+    /// default. A square PNG, 256x256 or smaller, works everywhere.
+    #[serde(default)]
+    pub(crate) icon: String,
+    /// Initial inner size in physical pixels; either one unset falls back
+    /// to `[launcher]`'s. Also the windowed-launch size for profiles that
+    /// haven't remembered one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) height: Option<u32>,
+    /// Size limits on the window's inner size in physical pixels, handed
+    /// to the platform; each unset one is unlimited. Re-applied on reload.
+    /// Where the window ends up bigger than the max anyway (fullscreen, a
+    /// tiling compositor), the Vulkan backend letterboxes the scene down to
+    /// it; GL and wgpu fill the window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min_height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_height: Option<u32>,
+    /// Width:height the scene is held to, e.g. `[16, 9]`, letterboxed
+    /// inside whatever shape the window has. Vulkan only: winit has no
+    /// aspect constraint to put on the window itself, so under GL and wgpu
+    /// the setting is ignored and the scene fills the window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) aspect_lock: Option<[u32; 2]>,
+    #[serde(default = "default_true")]
+    pub(crate) resizable: bool,
+    #[serde(default = "default_true")]
+    pub(crate) decorations: bool,
+    #[serde(default)]
+    pub(crate) always_on_top: bool,
+    /// Monitor to open on: index from the startup log, or a name
+    /// substring. Unset leaves placement to the platform.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) monitor: Option<String>,
+    #[serde(default)]
+    pub(crate) start_maximized: bool,
+    #[serde(default)]
+    pub(crate) mode: WindowMode,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_window_title() -> String {
+    "cubic".to_string()
+}
+
+impl Default for WindowCfg {
+    fn default() -> Self {
+        WindowCfg {
+            title: default_window_title(),
+            icon: String::new(),
+            width: None,
+            height: None,
+            min_width: None,
+            min_height: None,
+            max_width: None,
+            max_height: None,
+            aspect_lock: None,
+            resizable: true,
+            decorations: true,
+            always_on_top: false,
+            monitor: None,
+            start_maximized: false,
+            mode: WindowMode::default(),
+        }
+    }
+}
+
+/// `[debug]`: the Vulkan validation layer (which checks run, see
+/// cubic_render_vk::ValidationLayers; what happens to their messages, see
+/// cubic_render_vk::ValidationPolicy) and CPU profiler export. Read at
+/// startup. Skipped on save when untouched so it doesn't show up in every
+/// cubic.toml.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub(crate) struct DebugCfg {
+    /// Validation checks to run: off, on, gpu, best or all, comma-separated
+    /// (see ValidationLayers::parse). "" = the build's default, on in
+    /// debug builds only. CUBIC_VALIDATION and `--validation` override it.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) validation: String,
+    /// Panic on the first validation error (for CI runs).
+    #[serde(default)]
+    pub(crate) validation_panic: bool,
+    /// Validation message ids to drop: VUID names or message id numbers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) validation_suppress: Vec<String>,
+    /// Span export for CPU profiling (see cubic_core::Profiler).
+    #[serde(default)]
+    pub(crate) profiler: ProfilerKind,
+    /// Where `profiler = "chrome"` writes its JSON; "" = a timestamped
+    /// cubic-trace-*.json in the working directory.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) trace_path: String,
+    /// Outline physics colliders near the camera (Vulkan's debug lines;
+    /// also toggled by `/physics colliders`).
+    #[serde(default)]
+    pub(crate) physics_colliders: bool,
+}
+
+/// `[post]`: assets for `[render] post_effects` that can't live in
+/// `[render]`, which stays Copy. Read when a backend starts.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub(crate) struct PostCfg {
+    /// Colour grading LUT image for the color_grade effect: a strip of N
+    /// slices of N×N, N² wide and N high (the common 256x16 or 1024x32
+    /// layout). "" = no grading.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) lut: String,
+}
+
+impl PostCfg {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ProfilerKind {
+    #[default]
+    None,
+    Chrome,
+    Tracy,
+}
+
+impl DebugCfg {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn profiler(&self) -> cubic_core::Profiler {
+        match self.profiler {
+            ProfilerKind::None => cubic_core::Profiler::None,
+            ProfilerKind::Chrome => {
+                let path = if self.trace_path.is_empty() {
+                    let secs = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    format!("cubic-trace-{secs}.json")
+                } else {
+                    self.trace_path.clone()
+                };
+                cubic_core::Profiler::Chrome(path.into())
+            }
+            ProfilerKind::Tracy => cubic_core::Profiler::Tracy,
+        }
+    }
+}
+
 /// Optional modifier layered on top of a control's base key (e.g. "F6" +
 /// Shift). Deliberately side-agnostic (not ShiftLeft-vs-ShiftRight) — unlike
 /// a control's own base key, which can legitimately be bound to a specific
@@ -909,6 +1459,7 @@ pub(crate) fn save_global_cfg(cfg: &AppCfg) {
     let mut doc = existing
         .parse::<toml_edit::DocumentMut>()
         .unwrap_or_default();
+    migrate_legacy_keys(&mut doc);
 
     match toml_edit::ser::to_document(cfg) {
         Ok(new_doc) => {
@@ -927,6 +1478,31 @@ pub(crate) fn save_global_cfg(cfg: &AppCfg) {
     }
 }
 
+/// (section, old key, new key) for renamed cubic.toml fields whose serde
+/// `alias` still accepts the old spelling. Without renaming in place, the
+/// merge below would add the new key next to the old one, and serde
+/// rejects a struct that sets a field under both names — the next load
+/// would then silently fall back to AppCfg::default().
+const LEGACY_KEY_RENAMES: &[(&str, &str, &str)] = &[("render", "anisotropy", "max_anisotropy")];
+
+fn migrate_legacy_keys(doc: &mut toml_edit::DocumentMut) {
+    for &(section, old, new) in LEGACY_KEY_RENAMES {
+        let Some(table) = doc.get_mut(section).and_then(|t| t.as_table_like_mut()) else {
+            continue;
+        };
+        if table.contains_key(new) {
+            continue;
+        }
+        if let Some(item) = table.remove(old) {
+            table.insert(new, item);
+        }
+    }
+}
+
+fn default_lod_distance() -> i32 {
+    4
+}
+
 fn default_diff_threshold() -> usize {
     512
 }
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Main-loop timing: the per-frame delta RedrawRequested measures, and an
+//! optional fixed timestep for simulation that shouldn't depend on the
+//! frame rate (the guest's on_tick today; physics and voxel sim later).
+//!
+//! With `[game] tick_hz` set, each frame's delta feeds FixedStep's
+//! accumulator and App::fixed_update runs once per whole step (none on a
+//! fast frame, several on a slow one, at most MAX_STEPS_PER_FRAME). What
+//! the simulation hands the renderer is kept as the last two steps' values
+//! in an Interpolated and drawn blended by FixedStep::alpha, so motion is
+//! smooth at any frame rate instead of stepping at the tick rate. With
+//! `tick_hz = 0` the simulation runs once per frame with the frame's own
+//! delta, as before.
+
+use std::time::{Duration, Instant};
+
+use cubic_math::{Camera, DVec3};
+
+/// Steps run in one frame at most. A frame later than this many steps
+/// drops the excess rather than trying to catch up, which would make the
+/// next frame slower still (the "spiral of death").
+pub(crate) const MAX_STEPS_PER_FRAME: u32 = 8;
+
+/// Frame delta longer than this is treated as this long: a stall (window
+/// drag, breakpoint, load) shouldn't become one huge simulation step.
+const MAX_FRAME_DELTA: Duration = Duration::from_millis(250);
+
+/// Measures the time between frames.
+pub(crate) struct FrameClock {
+    last: Instant,
+}
+
+impl FrameClock {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self { last: now }
+    }
+
+    /// Time since the previous tick() (or new()), clamped to
+    /// MAX_FRAME_DELTA.
+    pub(crate) fn tick(&mut self, now: Instant) -> Duration {
+        let dt = now.saturating_duration_since(self.last);
+        self.last = now;
+        dt.min(MAX_FRAME_DELTA)
+    }
+
+    /// Forget the time spent away (pause, suspend, a world load), so the
+    /// next frame doesn't see it as one long delta.
+    pub(crate) fn reset(&mut self, now: Instant) {
+        self.last = now;
+    }
+}
+
+/// Fixed-timestep accumulator.
+pub(crate) struct FixedStep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl FixedStep {
+    /// `hz` steps per second; None for 0 (variable timestep).
+    pub(crate) fn new(hz: u32) -> Option<Self> {
+        (hz > 0).then(|| Self {
+            step: Duration::from_secs_f64(1.0 / f64::from(hz)),
+            accumulator: Duration::ZERO,
+        })
+    }
+
+    pub(crate) fn step_secs(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    /// Add a frame's delta; returns how many steps to run now.
+    pub(crate) fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if steps > MAX_STEPS_PER_FRAME {
+            tracing::debug!(
+                "fixed step: {steps} steps due, running {MAX_STEPS_PER_FRAME}; simulation slowed"
+            );
+            steps = MAX_STEPS_PER_FRAME;
+        }
+        steps
+    }
+
+    /// How far the current time is past the last step, as a fraction of a
+    /// step: the blend factor between the last two steps' states.
+    pub(crate) fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+    }
+}
+
+/// Blendable render state.
+pub(crate) trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+/// The camera pose the simulation sets (see cubic_wasm::CameraUpdate).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct CameraPose {
+    pub(crate) position: DVec3,
+    pub(crate) yaw: f32,
+    pub(crate) pitch: f32,
+}
+
+impl CameraPose {
+    pub(crate) fn apply(self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+    }
+}
+
+impl Lerp for CameraPose {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        // Yaw along the short way round, so crossing ±PI doesn't spin.
+        let mut dyaw = other.yaw - self.yaw;
+        dyaw -= std::f32::consts::TAU * (dyaw / std::f32::consts::TAU).round();
+        Self {
+            position: self.position.lerp(other.position, f64::from(t)),
+            yaw: self.yaw + dyaw * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+        }
+    }
+}
+
+/// A value's state at the last two fixed steps.
+pub(crate) struct Interpolated<T> {
+    previous: T,
+    current: T,
+}
+
+impl<T: Lerp> Interpolated<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            previous: value,
+            current: value,
+        }
+    }
+
+    /// A step produced `value`.
+    pub(crate) fn push(&mut self, value: T) {
+        self.previous = self.current;
+        self.current = value;
+    }
+
+    pub(crate) fn current(&self) -> T {
+        self.current
+    }
+
+    pub(crate) fn at(&self, alpha: f32) -> T {
+        self.previous.lerp(self.current, alpha.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_follow_accumulated_time() {
+        let mut fixed = FixedStep::new(50).unwrap();
+        assert_eq!(fixed.advance(Duration::from_millis(10)), 0);
+        assert_eq!(fixed.advance(Duration::from_millis(15)), 1);
+        assert!((fixed.alpha() - 0.25).abs() < 1e-4);
+        assert_eq!(fixed.advance(Duration::from_millis(45)), 2);
+    }
+
+    #[test]
+    fn steps_per_frame_are_capped() {
+        let mut fixed = FixedStep::new(100).unwrap();
+        assert_eq!(fixed.advance(Duration::from_secs(1)), MAX_STEPS_PER_FRAME);
+        assert!(fixed.alpha() < 1.0);
+    }
+
+    #[test]
+    fn zero_hz_is_variable_step() {
+        assert!(FixedStep::new(0).is_none());
+    }
+
+    #[test]
+    fn yaw_blends_the_short_way() {
+        let a = CameraPose {
+            position: DVec3::ZERO,
+            yaw: 3.0,
+            pitch: 0.0,
+        };
+        let b = CameraPose { yaw: -3.0, ..a };
+        let mid = a.lerp(b, 0.5).yaw;
+        assert!(mid.abs() > 3.1, "blended through zero: {mid}");
+    }
+
+    #[test]
+    fn frame_delta_is_clamped() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        assert_eq!(clock.tick(start + Duration::from_secs(5)), MAX_FRAME_DELTA);
+    }
+}
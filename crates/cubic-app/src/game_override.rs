@@ -58,8 +58,8 @@ pub struct GameRenderOverride {
     pub texture_filter: Option<String>,
     #[serde(default)]
     pub mipmap_mode: Option<String>,
-    #[serde(default)]
-    pub anisotropy: Option<f32>,
+    #[serde(default, alias = "anisotropy")]
+    pub max_anisotropy: Option<f32>,
     #[serde(default)]
     pub lod_bias: Option<f32>,
     #[serde(default)]
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! `cubic golden`: golden-image rendering tests. A handful of known views
+//! of the bench world (see bench.rs), each rendered once streaming around
+//! it has settled, saved, and compared with the stored golden for the
+//! backend: `<goldens>/<scene>.<backend>.png`. Every backend that can take
+//! a screenshot (Vulkan, GL) has goldens of its own, since they don't draw
+//! the same thing.
+//!
+//! The frames go to `--out`; a scene that fails also gets a
+//! `<scene>.<backend>.diff.png` there with what differed in red. The
+//! comparison is cubic_render::compare_rgba8 against GoldenTolerance's
+//! defaults, which allow for driver differences but not for a missing
+//! chunk or a change of shading. `--update` writes the frames over the
+//! goldens instead, for a change that is meant to alter the image.
+//!
+//! Goldens are only comparable at the size they were made at: use the
+//! same `--width`/`--height` every run. tools/golden.sh pins 640x360 and
+//! runs both backends; CI's golden job runs it on Mesa's software
+//! rasterizers, which is what the goldens in tests/golden are made on.
+
+use crate::backend::Backend;
+use crate::bench::CameraPath;
+use anyhow::{anyhow, bail, Context, Result};
+use cubic_math::{Camera, DVec3};
+use cubic_render::{compare_rgba8, diff_image_rgba8, GoldenTolerance, StreamCounts};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Frames a scene holds its pose with nothing meshing or uploading before
+/// it's captured, for the last uploads to show.
+const SETTLE_FRAMES: u32 = 10;
+/// Frames a scene waits for streaming to settle before it's captured
+/// anyway.
+const MAX_SCENE_FRAMES: u32 = 1200;
+
+/// One view of the bench world: a bench camera path's pose.
+struct Scene {
+    name: &'static str,
+    path: CameraPath,
+    /// The pose for this frame of a run of `frames` (see
+    /// CameraPath::apply).
+    frame: u32,
+    frames: u32,
+}
+
+const SCENES: &[Scene] = &[
+    Scene {
+        name: "start",
+        path: CameraPath::Static,
+        frame: 0,
+        frames: 1,
+    },
+    Scene {
+        name: "orbit-east",
+        path: CameraPath::Orbit,
+        frame: 0,
+        frames: 4,
+    },
+    Scene {
+        name: "orbit-south",
+        path: CameraPath::Orbit,
+        frame: 1,
+        frames: 4,
+    },
+    Scene {
+        name: "flyover",
+        path: CameraPath::Flyover,
+        frame: 40,
+        frames: 1,
+    },
+];
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct GoldenArgs {
+    /// Where the goldens are kept.
+    #[arg(long, default_value = "tests/golden")]
+    goldens: PathBuf,
+    /// Where this run's frames (and diffs) go.
+    #[arg(long, default_value = "target/golden")]
+    out: PathBuf,
+    /// Write the frames over the goldens instead of comparing.
+    #[arg(long)]
+    update: bool,
+    /// World seed of the scenes; goldens only hold for the seed they were
+    /// made with.
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+    /// Hide the window.
+    #[arg(long)]
+    pub(crate) offscreen: bool,
+}
+
+/// A `cubic golden` in progress.
+pub(crate) struct GoldenRun {
+    args: GoldenArgs,
+    /// Where the camera started; scenes are relative to it.
+    origin: Option<DVec3>,
+    /// Index into SCENES.
+    scene: usize,
+    /// Frames rendered in the current scene, and of those the last with
+    /// streaming idle.
+    frames: u32,
+    idle: u32,
+    /// The current scene's screenshot was asked for.
+    capturing: bool,
+    failed: Vec<&'static str>,
+}
+
+impl GoldenRun {
+    pub(crate) fn new(args: GoldenArgs) -> Self {
+        Self {
+            args,
+            origin: None,
+            scene: 0,
+            frames: 0,
+            idle: 0,
+            capturing: false,
+            failed: Vec::new(),
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.args.seed
+    }
+
+    /// Place the camera at the current scene's pose.
+    pub(crate) fn apply_camera(&mut self, camera: &mut Camera) {
+        let origin = *self.origin.get_or_insert(camera.position);
+        if let Some(scene) = SCENES.get(self.scene) {
+            scene.path.apply(camera, origin, scene.frame, scene.frames);
+        }
+    }
+
+    /// Count a rendered frame: once the scene has settled, screenshot the
+    /// next one, then check it and move on. The run's outcome once every
+    /// scene is done.
+    pub(crate) fn frame_rendered(
+        &mut self,
+        backend: &mut Backend,
+        stream: StreamCounts,
+    ) -> Option<Result<()>> {
+        let scene = &SCENES[self.scene];
+        let actual = self
+            .args
+            .out
+            .join(format!("{}.{}.png", scene.name, backend.name()));
+        if self.capturing {
+            let checked = backend
+                .finish_screenshot(&actual)
+                .and_then(|()| self.check(scene, &actual));
+            match checked {
+                Ok(true) => println!("{}: ok", scene.name),
+                Ok(false) => self.failed.push(scene.name),
+                Err(e) => return Some(Err(e.context(format!("scene {}", scene.name)))),
+            }
+            self.scene += 1;
+            self.frames = 0;
+            self.idle = 0;
+            self.capturing = false;
+            return (self.scene == SCENES.len()).then(|| self.outcome());
+        }
+
+        self.frames += 1;
+        // Nothing resident yet is a world still loading, not a settled one.
+        if stream.resident > 0 && stream.meshing == 0 && stream.uploads_pending == 0 {
+            self.idle += 1;
+        } else {
+            self.idle = 0;
+        }
+        if self.idle < SETTLE_FRAMES && self.frames < MAX_SCENE_FRAMES {
+            return None;
+        }
+        if self.idle < SETTLE_FRAMES {
+            warn!(
+                "golden: {} still streaming after {MAX_SCENE_FRAMES} frames; capturing anyway",
+                scene.name
+            );
+        }
+        self.capturing = true;
+        backend.start_screenshot(&actual).err().map(Err)
+    }
+
+    /// Compare the frame at `actual` with the scene's golden, or make it
+    /// the golden with `--update`. False if it differs.
+    fn check(&self, scene: &Scene, actual: &Path) -> Result<bool> {
+        let golden = self
+            .args
+            .goldens
+            .join(actual.file_name().unwrap_or_default());
+        if self.args.update {
+            std::fs::create_dir_all(&self.args.goldens)
+                .with_context(|| format!("creating {}", self.args.goldens.display()))?;
+            std::fs::copy(actual, &golden)
+                .with_context(|| format!("writing {}", golden.display()))?;
+            println!("{}: updated {}", scene.name, golden.display());
+            return Ok(true);
+        }
+        if !golden.exists() {
+            bail!("no golden at {}; make one with --update", golden.display());
+        }
+
+        let frame = load_rgba8(actual)?;
+        let expected = load_rgba8(&golden)?;
+        if frame.dimensions() != expected.dimensions() {
+            bail!(
+                "frame is {}x{} but the golden is {}x{}; render at the golden's size \
+                 (--width/--height)",
+                frame.width(),
+                frame.height(),
+                expected.width(),
+                expected.height()
+            );
+        }
+        let tolerance = GoldenTolerance::default();
+        let diff = compare_rgba8(
+            &frame,
+            &expected,
+            frame.width(),
+            frame.height(),
+            tolerance.channel_delta,
+        )?;
+        if diff.passes(&tolerance) {
+            return Ok(true);
+        }
+
+        let diff_path = actual.with_extension("diff.png");
+        image::save_buffer(
+            &diff_path,
+            &diff_image_rgba8(&frame, &expected, tolerance.channel_delta),
+            frame.width(),
+            frame.height(),
+            image::ExtendedColorType::Rgba8,
+        )
+        .with_context(|| format!("writing {}", diff_path.display()))?;
+        println!(
+            "{}: FAILED — {:.2}% of pixels off (max channel delta {}), SSIM {:.4}; see {}",
+            scene.name,
+            diff.mismatched_fraction() * 100.0,
+            diff.max_channel_delta,
+            diff.ssim,
+            diff_path.display()
+        );
+        Ok(false)
+    }
+
+    fn outcome(&self) -> Result<()> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} of {} scenes differ from their goldens: {}",
+            self.failed.len(),
+            SCENES.len(),
+            self.failed.join(", ")
+        ))
+    }
+}
+
+fn load_rgba8(path: &Path) -> Result<image::RgbaImage> {
+    Ok(image::open(path)
+        .with_context(|| format!("reading {}", path.display()))?
+        .to_rgba8())
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Asset hot-reload: the running game's assets/ directory is watched
+//! (cubic_assets::AssetWatcher) and edited files are pushed back into the
+//! renderer in place, so a repainted texture or re-exported model shows up
+//! without relaunching the world. Files are decoded in the background
+//! (async_load.rs) and applied when they're ready.
+//!
+//! - Textures (block faces from tex_map, load-texture's guest_textures) are
+//!   decoded again and swapped in under their existing bindless index, so
+//!   baked chunk meshes and guest draws pick them up with no remesh. Block
+//!   faces in a texture array rebuild the whole array instead, every face
+//!   keeping its layer.
+//! - Models (entity_meshes) are loaded and uploaded again, keeping their
+//!   guest-visible ids; a glTF's own textures are replaced in place.
+//! - .toml files re-run reload_settings, the same as an edited cubic.toml.
+//! - Shaders are left to the renderer's own debug shader reload.
+//!
+//! Images a glTF only references by URI aren't tracked separately: touch
+//! the .gltf to pick up a changed one.
+
+use std::path::{Path, PathBuf};
+
+use cubic_assets::{AssetChange, AssetKind, AssetWatcher, Decoded};
+use tracing::{error, info, warn};
+
+use crate::async_load::AssetJob;
+use crate::backend::RendererBackend;
+use crate::App;
+
+/// Watch `<game dir>/assets` for the world about to load, game_path being
+/// cfg.game.path (the .wasm). None, with a log line, if the game has no
+/// assets/ directory or the watch can't be set up.
+pub(crate) fn watch_game_assets(game_path: &str) -> Option<AssetWatcher> {
+    let root = game_dir(game_path).join("assets");
+    if !root.is_dir() {
+        info!("asset hot-reload off: no {root:?}");
+        return None;
+    }
+    match AssetWatcher::new(&root) {
+        Ok(w) => Some(w),
+        Err(e) => {
+            warn!("asset hot-reload off: {e:#}");
+            None
+        }
+    }
+}
+
+fn game_dir(game_path: &str) -> PathBuf {
+    Path::new(game_path)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf()
+}
+
+/// Whether `a` and `b` name the same file. The watcher reports paths under
+/// its root as given, the world's maps hold the game dir joined onto a
+/// guest's relative path; both usually match textually, but `./` or a
+/// symlinked game dir would throw a plain comparison off.
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+impl App {
+    /// Apply whatever the asset watcher has settled on since the last call.
+    /// Called once per loop turn from about_to_wait; a no-op without a
+    /// loaded world.
+    pub(crate) fn poll_asset_changes(&mut self) {
+        let Some(watcher) = &mut self.asset_watcher else {
+            return;
+        };
+        let changes = watcher.poll();
+        if changes.is_empty() {
+            return;
+        }
+        let mut reload_config = false;
+        for change in changes {
+            match change.kind {
+                AssetKind::Texture => self.reload_texture(&change),
+                AssetKind::Model => self.reload_model(&change),
+                AssetKind::Config => reload_config = true,
+                AssetKind::Shader | AssetKind::Other => {}
+            }
+        }
+        if reload_config {
+            info!("{}", self.reload_settings());
+        }
+    }
+
+    fn reload_texture(&mut self, change: &AssetChange) {
+        let game_dir = game_dir(&self.cfg.game.path);
+        let changed = |rel: &String| same_file(&game_dir.join(rel), &change.path);
+        // Array layers aren't bindless indices; those faces rebuild the
+        // array instead.
+        let mut indices = Vec::new();
+        if !self.world.block_array {
+            indices.extend(
+                self.world
+                    .tex_map
+                    .iter()
+                    .filter(|(rel, _)| changed(rel))
+                    .map(|(_, &index)| index),
+            );
+        } else if self.world.tex_map.keys().any(changed) {
+            self.reload_block_textures();
+        }
+        indices.extend(
+            self.world
+                .guest_textures
+                .iter()
+                .filter(|(full, _)| same_file(full, &change.path))
+                .map(|(_, &index)| index),
+        );
+        if indices.is_empty() {
+            return;
+        }
+        self.world
+            .assets
+            .load_image(&change.path, AssetJob::ReloadTexture(indices));
+    }
+
+    /// Upload the block texture array again from disk. Layers follow the
+    /// sorted paths, so every face keeps its layer and no chunk remeshes.
+    fn reload_block_textures(&mut self) {
+        let Some(plugin) = self.guest.plugin.clone() else {
+            return;
+        };
+        let Some(mut backend) = self.backend.take() else {
+            return;
+        };
+        self.upload_block_textures(&mut backend, &plugin);
+        self.backend = Some(backend);
+        info!("hot-reloaded block texture array");
+    }
+
+    /// The decoded image for a reload_texture, in place under `indices`.
+    pub(crate) fn apply_texture_reload(&mut self, path: &Path, indices: &[u32], decoded: &Decoded) {
+        let Some(backend) = self.backend.as_mut() else {
+            return;
+        };
+        let Decoded::Image(img) = decoded else {
+            return;
+        };
+        for &index in indices {
+            match backend.replace_texture(index, &img.pixels, img.width, img.height) {
+                Ok(()) => info!("hot-reloaded texture {path:?} (index {index})"),
+                Err(e) => error!("hot-reload {path:?}: {e:#}"),
+            }
+        }
+    }
+
+    fn reload_model(&mut self, change: &AssetChange) {
+        let used = self
+            .world
+            .entity_meshes
+            .values()
+            .any(|(_, path)| same_file(path, &change.path));
+        if used {
+            self.world
+                .assets
+                .load_mesh(&change.path, AssetJob::ReloadModel);
+        }
+    }
+
+    /// The decoded file for a reload_model, uploaded again for every
+    /// entity mesh loaded from it.
+    pub(crate) fn apply_model_reload(&mut self, changed: &Path, decoded: &Decoded) {
+        let Some(backend) = self.backend.as_mut() else {
+            return;
+        };
+        let world = &mut self.world;
+        let mut reused_textures = false;
+        for (id, (handle, path)) in world.entity_meshes.iter_mut() {
+            if !same_file(path, changed) {
+                continue;
+            }
+            // The first reload of a glTF overwrites its old textures'
+            // indices in order (uploading any extra), so re-exporting a
+            // model doesn't leak bindless slots; later entities loaded from
+            // the same file then reuse the result through model_textures.
+            let mut old_textures = if reused_textures {
+                Vec::new().into_iter()
+            } else {
+                world
+                    .model_textures
+                    .remove(path.as_path())
+                    .unwrap_or_default()
+                    .into_iter()
+            };
+            reused_textures = true;
+            let uploaded = crate::loader::upload_decoded_mesh(
+                path,
+                decoded,
+                &mut world.model_textures,
+                |px, w, h| match old_textures.next() {
+                    Some(index) => Ok(backend.replace_texture(index, px, w, h).map(|()| index)?),
+                    None => Ok(backend.upload_texture(px, w, h)?),
+                },
+            )
+            .and_then(|(verts, idxs)| Ok(backend.upload_mesh(&verts, &idxs)?));
+            match uploaded {
+                Ok(h) => {
+                    backend.free_mesh(std::mem::replace(handle, h));
+                    info!("hot-reloaded mesh {path:?} (id {id})");
+                }
+                Err(e) => error!("hot-reload {path:?}: {e:#}"),
+            }
+        }
+    }
+}
@@ -1,8 +1,63 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use cubic_assets::Decoded;
 use cubic_render_vk::Vertex;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Load a guest mesh file by extension: glTF 2.0 (.gltf/.glb) through
+/// cubic-assets, everything else as OBJ (load_obj_mesh). A glTF scene is
+/// merged into one mesh with its node transforms and base colours baked in.
+/// Its textures go up through `upload_texture` the first time `path` is
+/// loaded and are remembered in `textures`; a later load of the same path
+/// (e.g. after device loss, which keeps bindless textures) reuses them.
+pub fn load_mesh_file(
+    path: &Path,
+    textures: &mut HashMap<PathBuf, Vec<u32>>,
+    upload_texture: impl FnMut(&[u8], u32, u32) -> Result<u32>,
+) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let decoded = decode_mesh_file(path)?;
+    upload_decoded_mesh(path, &decoded, textures, upload_texture)
+}
+
+/// load_mesh_file's file reading and decoding half, which touches no
+/// renderer and so can run on an AssetLoader worker.
+pub fn decode_mesh_file(path: &Path) -> Result<Decoded> {
+    let is_gltf = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"));
+    if is_gltf {
+        cubic_assets::load_gltf(path).map(Decoded::Model)
+    } else {
+        load_obj_mesh(path).map(|(verts, idxs)| Decoded::Mesh(verts, idxs))
+    }
+}
+
+/// load_mesh_file's other half: a decode_mesh_file result made into one
+/// mesh, its textures (if any) uploaded once per `path` as described there.
+pub fn upload_decoded_mesh(
+    path: &Path,
+    decoded: &Decoded,
+    textures: &mut HashMap<PathBuf, Vec<u32>>,
+    upload_texture: impl FnMut(&[u8], u32, u32) -> Result<u32>,
+) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let model = match decoded {
+        Decoded::Mesh(verts, idxs) => return Ok((verts.clone(), idxs.clone())),
+        Decoded::Model(model) => model,
+        Decoded::Image(_) => bail!("{path:?} is an image, not a mesh"),
+    };
+    let indices = match textures.get(path) {
+        Some(indices) => indices.clone(),
+        None => {
+            let indices = model.upload_textures(upload_texture)?;
+            textures.insert(path.to_path_buf(), indices.clone());
+            indices
+        }
+    };
+    Ok(model.merged(&indices))
+}
 
 /// Load an OBJ file and return (vertices, indices) ready to pass directly to
 /// `VkRenderer::upload_mesh`. All sub-meshes in the file are merged into one
@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! The `[physics]` plugin: steps cubic-physics with the game tick over the
+//! scene's RigidBody entities, keeps chunk colliders on the loaded chunks
+//! around the camera, outlines colliders when `[debug] physics_colliders`
+//! is on, and answers /physics.
+
+use crate::backend::RendererBackend;
+use crate::config::ChunkCollidersCfg;
+use crate::plugin::{Plugin, PluginCommand, PluginContext, PluginEvent};
+use cubic_ecs::Transform;
+use cubic_math::Vec3;
+use cubic_physics::{ChunkColliderSource, ColliderShape, PhysicsWorld, RigidBody};
+use cubic_world::{world_pos_to_chunk, ChunkPos, CHUNK_SIZE, VOXEL_SIZE};
+
+/// Chunk colliders built per frame at most, nearest the camera first.
+const MAX_COLLIDER_BUILDS: usize = 4;
+
+/// How far the camera strays from the physics origin before it's moved
+/// (see PhysicsWorld::set_origin), in metres.
+const PHYSICS_REBASE_DISTANCE: f64 = 1024.0;
+
+const PHYSICS_ARGS: &[&str] = &["box", "ball", "colliders", "clear"];
+
+const COMMANDS: &[PluginCommand] = &[PluginCommand {
+    name: "physics",
+    usage: "/physics [box|ball|colliders|clear] — rigid-body sandbox",
+    help: "/physics — body and chunk collider counts\n\
+           /physics box|ball — drop a 1 m dynamic body 3 m in front of the \
+           camera\n\
+           /physics colliders — toggle collider outlines ([debug] \
+           physics_colliders; Vulkan only)\n\
+           /physics clear — remove every body",
+    args: PHYSICS_ARGS,
+}];
+
+/// Rigid bodies for the scene's cubic_physics::RigidBody entities, and
+/// colliders for the loaded chunks around the camera.
+#[derive(Default)]
+pub(crate) struct PhysicsPlugin {
+    world: PhysicsWorld,
+}
+
+impl Plugin for PhysicsPlugin {
+    fn name(&self) -> &'static str {
+        "physics"
+    }
+
+    fn fixed_update(&mut self, ctx: &mut PluginContext, step: f32) {
+        let gravity = Vec3::new(0.0, ctx.cfg.physics.gravity, 0.0);
+        self.world.set_gravity(gravity);
+        self.world.step(ctx.scene, step);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext, _dt: f32) {
+        self.update_chunk_colliders(ctx);
+        if !ctx.cfg.debug.physics_colliders {
+            return;
+        }
+        if let Some(backend) = ctx.backend.as_deref_mut() {
+            // Out to a chunk's width: further, voxel chunk colliders are
+            // enough lines to crowd out everything else.
+            let chunk_world_size = CHUNK_SIZE as f64 * VOXEL_SIZE as f64;
+            self.world
+                .debug_lines(ctx.camera.position, chunk_world_size, |a, b, color| {
+                    backend.debug_line(a, b, color)
+                });
+        }
+    }
+
+    fn on_event(&mut self, ctx: &mut PluginContext, event: PluginEvent) {
+        match event {
+            PluginEvent::WorldLoaded => self.world.clear(),
+            // Rebuilt now, so the next step's bodies see the edit.
+            PluginEvent::ChunkEdited(pos) => self.rebuild_chunk_colliders(ctx, pos),
+            PluginEvent::ChunkUnloaded(pos) => self.world.remove_chunk_collider(pos),
+            PluginEvent::Suspended => {}
+        }
+    }
+
+    fn commands(&self) -> &'static [PluginCommand] {
+        COMMANDS
+    }
+
+    fn command(
+        &mut self,
+        ctx: &mut PluginContext,
+        _name: &str,
+        args: &[&str],
+    ) -> Result<String, String> {
+        let Some(&arg) = args.first() else {
+            return Ok(format!(
+                "physics: {} bodies, colliders set for {} chunks",
+                self.world.body_count(),
+                self.world.chunk_colliders().count()
+            ));
+        };
+        let shape = match arg {
+            "box" => ColliderShape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            },
+            "ball" => ColliderShape::Ball { radius: 0.5 },
+            "colliders" => {
+                let on = !ctx.cfg.debug.physics_colliders;
+                ctx.cfg.debug.physics_colliders = on;
+                return Ok(format!(
+                    "collider outlines {}",
+                    if on { "on" } else { "off" }
+                ));
+            }
+            "clear" => {
+                let bodies: Vec<_> = ctx.scene.query::<RigidBody>().map(|(e, _)| e).collect();
+                for &e in &bodies {
+                    ctx.scene.despawn(e);
+                }
+                return Ok(format!("removed {} bodies", bodies.len()));
+            }
+            other => {
+                return Err(format!(
+                    "Unknown /physics argument: {other}. Use {}.",
+                    PHYSICS_ARGS.join(", ")
+                ))
+            }
+        };
+        // A metre cube or ball 3 m in front of the camera. Bodies have no
+        // mesh of their own, so they only show with /physics colliders.
+        let at = ctx.camera.position + (ctx.camera.forward() * 3.0).as_dvec3();
+        let e = ctx.scene.spawn();
+        ctx.scene.insert(e, Transform::from_translation(at));
+        ctx.scene.insert(e, RigidBody::dynamic(shape));
+        Ok(format!(
+            "spawned a {arg} at {:.1} {:.1} {:.1}",
+            at.x, at.y, at.z
+        ))
+    }
+}
+
+impl PhysicsPlugin {
+    /// Rebuild the colliders an edit in the chunk at `pos` touched: its
+    /// own, plus its neighbours' when colliders are meshes, which are
+    /// culled against it. Only ones already set; update_chunk_colliders
+    /// builds the rest.
+    fn rebuild_chunk_colliders(&mut self, ctx: &PluginContext, pos: ChunkPos) {
+        let source = chunk_collider_source(ctx.cfg.physics.chunk_colliders);
+        let mut touched = vec![pos];
+        if source == ChunkColliderSource::Mesh {
+            for (dx, dy, dz) in [
+                (-1, 0, 0),
+                (1, 0, 0),
+                (0, -1, 0),
+                (0, 1, 0),
+                (0, 0, -1),
+                (0, 0, 1),
+            ] {
+                touched.push(ChunkPos {
+                    x: pos.x + dx,
+                    y: pos.y + dy,
+                    z: pos.z + dz,
+                });
+            }
+        }
+        for pos in touched {
+            if self.world.has_chunk_collider(pos) {
+                self.build_chunk_collider(ctx, pos, source);
+            }
+        }
+    }
+
+    /// Set the collider of the chunk at `pos` from its current voxels, or
+    /// drop it if the chunk isn't loaded.
+    fn build_chunk_collider(
+        &mut self,
+        ctx: &PluginContext,
+        pos: ChunkPos,
+        source: ChunkColliderSource,
+    ) {
+        match ctx.stream.chunks().get(&pos) {
+            Some(chunk) => {
+                self.world
+                    .set_chunk_collider(pos, chunk, ctx.stream.neighbors(pos), source);
+            }
+            None => self.world.remove_chunk_collider(pos),
+        }
+    }
+
+    /// Keep colliders on the loaded chunks within `[physics]
+    /// collider_radius` of the camera's chunk and drop the rest, building
+    /// at most MAX_COLLIDER_BUILDS a frame, nearest first. Also moves the
+    /// physics origin along with the camera.
+    fn update_chunk_colliders(&mut self, ctx: &PluginContext) {
+        let _span = tracing::debug_span!("chunk_colliders").entered();
+        let center = world_pos_to_chunk(ctx.camera.position);
+        if ctx.camera.position.distance(self.world.origin()) > PHYSICS_REBASE_DISTANCE {
+            self.world.set_origin(center.to_world_origin());
+        }
+
+        let r = ctx.cfg.physics.collider_radius.max(0);
+        let within = |p: ChunkPos| {
+            (p.x - center.x).abs() <= r
+                && (p.y - center.y).abs() <= r
+                && (p.z - center.z).abs() <= r
+        };
+        let far: Vec<ChunkPos> = self
+            .world
+            .chunk_colliders()
+            .filter(|&p| !within(p))
+            .collect();
+        for pos in far {
+            self.world.remove_chunk_collider(pos);
+        }
+
+        let mut missing = Vec::new();
+        for x in -r..=r {
+            for y in -r..=r {
+                for z in -r..=r {
+                    let pos = ChunkPos {
+                        x: center.x + x,
+                        y: center.y + y,
+                        z: center.z + z,
+                    };
+                    if !self.world.has_chunk_collider(pos) && ctx.stream.chunks().contains_key(&pos)
+                    {
+                        missing.push((x * x + y * y + z * z, pos));
+                    }
+                }
+            }
+        }
+        missing.sort_unstable_by_key(|&(d2, _)| d2);
+        let source = chunk_collider_source(ctx.cfg.physics.chunk_colliders);
+        for (_, pos) in missing.into_iter().take(MAX_COLLIDER_BUILDS) {
+            self.build_chunk_collider(ctx, pos, source);
+        }
+    }
+}
+
+fn chunk_collider_source(cfg: ChunkCollidersCfg) -> ChunkColliderSource {
+    match cfg {
+        ChunkCollidersCfg::Voxels => ChunkColliderSource::Voxels,
+        ChunkCollidersCfg::Mesh => ChunkColliderSource::Mesh,
+    }
+}
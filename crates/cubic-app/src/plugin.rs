@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Engine plugins: host-side subsystems compiled into the app (physics
+//! today; audio, scripting, extra renderers later) that hook the app's
+//! lifecycle without lib.rs or world.rs knowing about them. Not to be
+//! confused with the WASM game (guest.rs), which is loaded at runtime and
+//! talks to the engine through WIT.
+//!
+//! A plugin implements `Plugin` and is added to a `PluginRegistry` handed
+//! to `run`. The stock binary runs `default_plugins`; another build links
+//! cubic-app and composes its own from its main without editing the app:
+//!
+//! ```ignore
+//! let mut plugins = cubic_app::default_plugins();
+//! plugins.add(MyPlugin::default());
+//! cubic_app::run(plugins)
+//! ```
+//!
+//! Hooks run in registration order (shutdown in reverse):
+//!
+//! - `init` once at startup, before the window exists. An error drops
+//!   the plugin, logged, rather than the app.
+//! - `fixed_update` every simulation step, after the game's on_tick and
+//!   its block edits (see App::fixed_update).
+//! - `update` once a frame in game, after chunk streaming and before the
+//!   world is drawn, with the backend for any drawing of its own.
+//! - `on_event` for the `PluginEvent`s below, as they happen.
+//! - `command` for a chat command the built-ins don't know, before the
+//!   game's own; see `commands`.
+//! - `shutdown` once on exit, after the world's last save is queued.
+//!
+//! Hooks get a `PluginContext`: the config, the scene, the loaded chunks
+//! and the camera, borrowed from the App for that call only.
+
+use crate::backend::Backend;
+use crate::config::AppCfg;
+use crate::physics::PhysicsPlugin;
+use crate::App;
+use anyhow::Result;
+use cubic_math::Camera;
+use cubic_world::{AsyncWorldStream, ChunkPos};
+use tracing::{error, info};
+
+/// What a plugin sees of the app during a hook.
+pub struct PluginContext<'a> {
+    /// Mutable so a plugin's commands can flip its own settings for the
+    /// session, as /set does for the built-in ones.
+    pub cfg: &'a mut AppCfg,
+    /// The drawable entities (see WorldRenderer::scene).
+    pub scene: &'a mut cubic_ecs::World,
+    /// The loaded chunks; read-only, edits go through the game.
+    pub stream: &'a AsyncWorldStream,
+    pub camera: &'a Camera,
+    /// Only during `update`.
+    pub backend: Option<&'a mut Backend>,
+}
+
+/// Something that happened that plugins may care about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PluginEvent {
+    /// load_world started a new world; the scene is empty again.
+    WorldLoaded,
+    /// Blocks changed in this chunk this step.
+    ChunkEdited(ChunkPos),
+    /// The stream dropped this chunk.
+    ChunkUnloaded(ChunkPos),
+    /// The platform took the surface away (see App::suspended).
+    Suspended,
+}
+
+/// A chat command a plugin answers, for /help and tab-completion.
+pub struct PluginCommand {
+    /// Without the leading `/`.
+    pub name: &'static str,
+    /// One line for the /help listing.
+    pub usage: &'static str,
+    /// The longer text for `/help <name>`.
+    pub help: &'static str,
+    /// First-argument completions.
+    pub args: &'static [&'static str],
+}
+
+/// An engine plugin; every hook but `name` has a do-nothing default.
+pub trait Plugin {
+    /// For logs.
+    fn name(&self) -> &'static str;
+
+    fn init(&mut self, _ctx: &mut PluginContext) -> Result<()> {
+        Ok(())
+    }
+
+    fn fixed_update(&mut self, _ctx: &mut PluginContext, _step: f32) {}
+
+    fn update(&mut self, _ctx: &mut PluginContext, _dt: f32) {}
+
+    fn on_event(&mut self, _ctx: &mut PluginContext, _event: PluginEvent) {}
+
+    /// The chat commands `command` answers.
+    fn commands(&self) -> &'static [PluginCommand] {
+        &[]
+    }
+
+    /// Run `/name args`, one of `commands`: chat output, or an error
+    /// shown as one.
+    fn command(
+        &mut self,
+        _ctx: &mut PluginContext,
+        name: &str,
+        _args: &[&str],
+    ) -> Result<String, String> {
+        Err(format!("Unknown command: /{name}"))
+    }
+
+    fn shutdown(&mut self, _ctx: &mut PluginContext) {}
+}
+
+/// The app's plugins, in registration order.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+/// The engine's own plugins, what the stock binary runs.
+pub fn default_plugins() -> PluginRegistry {
+    let mut plugins = PluginRegistry::default();
+    plugins.add(PhysicsPlugin::default());
+    plugins
+}
+
+impl PluginRegistry {
+    /// Register `plugin` after those already added.
+    pub fn add(&mut self, plugin: impl Plugin + 'static) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    pub(crate) fn init(&mut self, ctx: &mut PluginContext) {
+        self.plugins.retain_mut(|p| match p.init(ctx) {
+            Ok(()) => {
+                info!("plugin {} initialized", p.name());
+                true
+            }
+            Err(e) => {
+                error!("plugin {} failed to initialize, disabled: {e:#}", p.name());
+                false
+            }
+        });
+    }
+
+    pub(crate) fn fixed_update(&mut self, ctx: &mut PluginContext, step: f32) {
+        for p in &mut self.plugins {
+            p.fixed_update(ctx, step);
+        }
+    }
+
+    pub(crate) fn update(&mut self, ctx: &mut PluginContext, dt: f32) {
+        for p in &mut self.plugins {
+            p.update(ctx, dt);
+        }
+    }
+
+    pub(crate) fn on_event(&mut self, ctx: &mut PluginContext, event: PluginEvent) {
+        for p in &mut self.plugins {
+            p.on_event(ctx, event);
+        }
+    }
+
+    /// Every plugin's commands.
+    pub(crate) fn commands(&self) -> impl Iterator<Item = &'static PluginCommand> + '_ {
+        self.plugins.iter().flat_map(|p| p.commands())
+    }
+
+    /// Run `/name args` on the plugin that has it; None if none does.
+    pub(crate) fn command(
+        &mut self,
+        ctx: &mut PluginContext,
+        name: &str,
+        args: &[&str],
+    ) -> Option<Result<String, String>> {
+        let p = self
+            .plugins
+            .iter_mut()
+            .find(|p| p.commands().iter().any(|c| c.name == name))?;
+        Some(p.command(ctx, name, args))
+    }
+
+    /// Shut every plugin down and drop it.
+    pub(crate) fn shutdown(&mut self, ctx: &mut PluginContext) {
+        for mut p in self.plugins.drain(..).rev() {
+            p.shutdown(ctx);
+        }
+    }
+}
+
+impl App {
+    /// Call `f` with the plugins and a context borrowed from the rest of
+    /// the app; `backend` is what the context's backend will be.
+    pub(crate) fn with_plugins<R>(
+        &mut self,
+        backend: Option<&mut Backend>,
+        f: impl FnOnce(&mut PluginRegistry, &mut PluginContext) -> R,
+    ) -> R {
+        let mut ctx = PluginContext {
+            cfg: &mut self.cfg,
+            scene: &mut self.world.scene,
+            stream: &self.world.stream,
+            camera: &self.camera,
+            backend,
+        };
+        f(&mut self.plugins, &mut ctx)
+    }
+
+    /// Tell every plugin about `event`.
+    pub(crate) fn plugin_event(&mut self, event: PluginEvent) {
+        self.with_plugins(None, |plugins, ctx| plugins.on_event(ctx, event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    const ALPHA: &[PluginCommand] = &[PluginCommand {
+        name: "alpha",
+        usage: "/alpha",
+        help: "",
+        args: &[],
+    }];
+    const BETA: &[PluginCommand] = &[PluginCommand {
+        name: "beta",
+        usage: "/beta <x>",
+        help: "",
+        args: &[],
+    }];
+
+    /// Logs the hooks it gets; fails `init` if told to.
+    struct Probe {
+        name: &'static str,
+        commands: &'static [PluginCommand],
+        fail_init: bool,
+        log: Log,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Log) -> Self {
+            Probe {
+                name,
+                commands: &[],
+                fail_init: false,
+                log: Rc::clone(log),
+            }
+        }
+    }
+
+    impl Plugin for Probe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn init(&mut self, _ctx: &mut PluginContext) -> Result<()> {
+            self.log.borrow_mut().push(format!("init {}", self.name));
+            if self.fail_init {
+                bail!("no device");
+            }
+            Ok(())
+        }
+
+        fn fixed_update(&mut self, _ctx: &mut PluginContext, _step: f32) {
+            self.log.borrow_mut().push(format!("step {}", self.name));
+        }
+
+        fn commands(&self) -> &'static [PluginCommand] {
+            self.commands
+        }
+
+        fn command(
+            &mut self,
+            _ctx: &mut PluginContext,
+            name: &str,
+            args: &[&str],
+        ) -> Result<String, String> {
+            Ok(format!("{} ran /{name} {}", self.name, args.join(" ")))
+        }
+
+        fn shutdown(&mut self, _ctx: &mut PluginContext) {
+            self.log
+                .borrow_mut()
+                .push(format!("shutdown {}", self.name));
+        }
+    }
+
+    /// Run `f` with a context over an empty scene and world.
+    fn with_ctx(f: impl FnOnce(&mut PluginContext)) {
+        let mut cfg = AppCfg::default();
+        let mut scene = cubic_ecs::World::new();
+        let jobs = Arc::new(cubic_jobs::JobPool::new("plugin-test", 1, None));
+        let stream = AsyncWorldStream::new(1, 1, jobs);
+        let camera = Camera::default();
+        f(&mut PluginContext {
+            cfg: &mut cfg,
+            scene: &mut scene,
+            stream: &stream,
+            camera: &camera,
+            backend: None,
+        });
+    }
+
+    #[test]
+    fn a_failed_init_drops_only_that_plugin() {
+        let log = Log::default();
+        let mut plugins = PluginRegistry::default();
+        plugins.add(Probe::new("a", &log));
+        plugins.add(Probe {
+            commands: ALPHA,
+            fail_init: true,
+            ..Probe::new("b", &log)
+        });
+        plugins.add(Probe::new("c", &log));
+        with_ctx(|ctx| {
+            plugins.init(ctx);
+            plugins.fixed_update(ctx, 0.05);
+            assert!(plugins.command(ctx, "alpha", &[]).is_none());
+        });
+        assert_eq!(
+            *log.borrow(),
+            ["init a", "init b", "init c", "step a", "step c"]
+        );
+        assert_eq!(plugins.commands().count(), 0);
+    }
+
+    #[test]
+    fn commands_go_to_the_plugin_that_lists_them() {
+        let log = Log::default();
+        let mut plugins = PluginRegistry::default();
+        plugins.add(Probe {
+            commands: ALPHA,
+            ..Probe::new("a", &log)
+        });
+        plugins.add(Probe {
+            commands: BETA,
+            ..Probe::new("b", &log)
+        });
+        let names: Vec<_> = plugins.commands().map(|c| c.name).collect();
+        assert_eq!(names, ["alpha", "beta"]);
+        with_ctx(|ctx| {
+            assert_eq!(
+                plugins.command(ctx, "beta", &["x"]),
+                Some(Ok("b ran /beta x".to_string()))
+            );
+            assert_eq!(
+                plugins.command(ctx, "alpha", &[]),
+                Some(Ok("a ran /alpha ".to_string()))
+            );
+            assert_eq!(plugins.command(ctx, "gamma", &[]), None);
+        });
+    }
+
+    #[test]
+    fn shutdown_runs_in_reverse_and_empties_the_registry() {
+        let log = Log::default();
+        let mut plugins = PluginRegistry::default();
+        for name in ["a", "b", "c"] {
+            plugins.add(Probe::new(name, &log));
+        }
+        with_ctx(|ctx| {
+            plugins.shutdown(ctx);
+            plugins.fixed_update(ctx, 0.05);
+        });
+        assert_eq!(*log.borrow(), ["shutdown c", "shutdown b", "shutdown a"]);
+    }
+}
@@ -93,10 +93,16 @@ pub struct RenderOverride {
     pub texture_filter: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub mipmap_mode: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub anisotropy: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "anisotropy")]
+    pub max_anisotropy: Option<f32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub lod_bias: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth_prepass: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadows: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_resolution: Option<u32>,
     // add other RenderCfg fields as Option here
 }
 
@@ -120,6 +126,8 @@ pub struct CameraOverride {
     pub move_speed: Option<f32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub mouse_sensitivity: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invert_y: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Live cubic.toml reload: a cheap mtime watch polled from about_to_wait
+//! (plus the /reload command), re-resolving the full cubic.toml ->
+//! game_overrides.toml -> profile.toml chain and routing whatever changed in
+//! `[render]` to the backend setters — no restart needed to try a different
+//! clear colour, vsync mode, HDR flavor or FPS cap. `[window]`'s size
+//! limits and aspect lock are re-applied too; the rest of it is read once,
+//! at window creation.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing::info;
+
+use crate::backend::RendererBackend;
+use crate::config::{apply_game_override, apply_profile, load_cfg, RenderCfg, UnfocusedPolicy};
+use crate::App;
+
+/// How often about_to_wait stats cubic.toml. A metadata() call is cheap,
+/// but not free enough to do every frame with vsync off.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches cubic.toml's mtime. Polling rather than an inotify-style
+/// watcher: one file, checked twice a second, doesn't justify a new
+/// dependency and a background thread.
+pub(crate) struct SettingsWatcher {
+    path: PathBuf,
+    last_mtime: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl SettingsWatcher {
+    pub(crate) fn new() -> Self {
+        let path = PathBuf::from("cubic.toml");
+        let last_mtime = mtime(&path);
+        Self {
+            path,
+            last_mtime,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// True once per change of cubic.toml's mtime (including the file
+    /// appearing or disappearing). Rate-limited to POLL_INTERVAL. The app's
+    /// own save_global_cfg writes trip this too; that's harmless, since
+    /// re-resolving what was just saved yields an empty RenderCfgDiff.
+    pub(crate) fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let now = mtime(&self.path);
+        if now == self.last_mtime {
+            return false;
+        }
+        self.last_mtime = now;
+        true
+    }
+}
+
+fn mtime(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Which groups of `[render]` knobs differ between two configs — each group
+/// maps onto one backend setter (or, for pacing, onto about_to_wait).
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RenderCfgDiff {
+    pub(crate) clear_color: bool,
+    /// vsync on/off or anything configure_advanced consumes (vsync mode,
+    /// HDR, sampler settings, depth prepass, post effects, colour filter).
+    pub(crate) present: bool,
+    /// FPS caps and the unfocused policy; about_to_wait re-reads them every
+    /// loop turn and hands the resulting cap to the backend's pacer, so
+    /// there's nothing to apply here beyond reporting the change.
+    pub(crate) pacing: bool,
+}
+
+impl RenderCfgDiff {
+    pub(crate) fn between(old: &RenderCfg, new: &RenderCfg) -> Self {
+        Self {
+            clear_color: old.clear_color != new.clear_color,
+            present: old.vsync != new.vsync
+                || old.vsync_mode != new.vsync_mode
+                || old.present_mode_priority != new.present_mode_priority
+                || old.hdr != new.hdr
+                || old.hdr_flavor != new.hdr_flavor
+                || old.sdr_white_nits != new.sdr_white_nits
+                || old.srgb_encode != new.srgb_encode
+                || old.hdr_display != new.hdr_display
+                || old.texture_filter != new.texture_filter
+                || old.mipmap_mode != new.mipmap_mode
+                || old.max_anisotropy != new.max_anisotropy
+                || old.lod_bias != new.lod_bias
+                || old.render_scale != new.render_scale
+                || old.upscaler != new.upscaler
+                || old.internal_resolution != new.internal_resolution
+                || old.internal_scaling != new.internal_scaling
+                || old.depth_prepass != new.depth_prepass
+                || old.frame_spike_ms != new.frame_spike_ms
+                || old.latency_mode != new.latency_mode
+                || old.shadows != new.shadows
+                || old.shadow_resolution != new.shadow_resolution
+                || old.post_effects != new.post_effects
+                || old.color_filter != new.color_filter,
+            pacing: old.vsync != new.vsync
+                || old.unfocused != new.unfocused
+                || old.unfocused_fps != new.unfocused_fps
+                || old.fps_when_vsync_off != new.fps_when_vsync_off,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl App {
+    /// Re-read cubic.toml through the same resolution chain as startup and
+    /// apply the `[render]` section live. Only `[render]` is swapped in:
+    /// world/controls/game settings are baked into state built at launch
+    /// (stream radius, resolved bindings, the loaded guest), and changing
+    /// them under a running world would need more than a setter call.
+    /// Returns a short summary for the /reload command.
+    pub(crate) fn reload_settings(&mut self) -> String {
+        let resolved = apply_profile(
+            apply_game_override(load_cfg(), &self.game_overrides),
+            &self.current_profile,
+        );
+        let old = self.cfg.render;
+        let new = resolved.render;
+        let diff = RenderCfgDiff::between(&old, &new);
+        let limits = self.apply_window_limits(&resolved.window);
+        if diff.is_empty() && !limits {
+            return "cubic.toml reloaded: no render changes".to_string();
+        }
+        self.cfg.render = new;
+        info!("cubic.toml reloaded: {:?}, window limits: {}", diff, limits);
+
+        if let Some(backend) = &mut self.backend {
+            if diff.clear_color {
+                backend.set_clear_color(new.clear_color);
+            }
+            // While unfocused under VsyncOn the Focused handler owns the
+            // present mode (forced Fifo); it re-applies self.cfg.render on
+            // refocus, which now already holds the new values.
+            let unfocused_override = !self.focused && new.unfocused == UnfocusedPolicy::VsyncOn;
+            if diff.present && !unfocused_override {
+                backend.set_vsync(new.vsync);
+                backend.configure_advanced(&new);
+            }
+        }
+        let mut changed = Vec::new();
+        if diff.clear_color {
+            changed.push("clear color");
+        }
+        if diff.present {
+            changed.push("present/sampling");
+        }
+        if diff.pacing {
+            changed.push("frame pacing");
+        }
+        if limits {
+            changed.push("window limits");
+        }
+        format!("cubic.toml reloaded: {}", changed.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_configs_differ_in_nothing() {
+        let cfg = RenderCfg::default();
+        assert!(RenderCfgDiff::between(&cfg, &cfg).is_empty());
+    }
+
+    #[test]
+    fn each_knob_lands_in_its_group() {
+        let old = RenderCfg::default();
+        let diff = |new: RenderCfg| RenderCfgDiff::between(&old, &new);
+
+        let clear = diff(RenderCfg {
+            clear_color: [1.0, 0.0, 0.0, 1.0],
+            ..old
+        });
+        assert_eq!(
+            clear,
+            RenderCfgDiff {
+                clear_color: true,
+                ..Default::default()
+            }
+        );
+
+        let present = RenderCfgDiff {
+            present: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            diff(RenderCfg {
+                hdr: !old.hdr,
+                ..old
+            }),
+            present
+        );
+        assert_eq!(
+            diff(RenderCfg {
+                render_scale: old.render_scale * 0.5,
+                ..old
+            }),
+            present
+        );
+        assert_eq!(
+            diff(RenderCfg {
+                internal_resolution: Some([640, 360]),
+                ..old
+            }),
+            present
+        );
+        assert_eq!(
+            diff(RenderCfg {
+                shadows: !old.shadows,
+                ..old
+            }),
+            present
+        );
+
+        let pacing = RenderCfgDiff {
+            pacing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            diff(RenderCfg {
+                unfocused: UnfocusedPolicy::None,
+                ..old
+            }),
+            pacing
+        );
+        assert_eq!(
+            diff(RenderCfg {
+                unfocused_fps: old.unfocused_fps + 1,
+                ..old
+            }),
+            pacing
+        );
+        assert_eq!(
+            diff(RenderCfg {
+                fps_when_vsync_off: old.fps_when_vsync_off + 1,
+                ..old
+            }),
+            pacing
+        );
+    }
+
+    #[test]
+    fn vsync_is_both_present_and_pacing() {
+        let old = RenderCfg::default();
+        let new = RenderCfg {
+            vsync: !old.vsync,
+            ..old
+        };
+        assert_eq!(
+            RenderCfgDiff::between(&old, &new),
+            RenderCfgDiff {
+                clear_color: false,
+                present: true,
+                pacing: true,
+            }
+        );
+    }
+}
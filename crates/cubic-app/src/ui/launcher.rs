@@ -8,9 +8,8 @@ use crate::config::{save_global_cfg, KeyBinding, ModifierKey, TextureFilter, Tri
 use crate::input::{input_source_to_string, resolve_controls, InputSource, InputTracker};
 use crate::profile;
 use crate::{App, AppState};
-use cubic_platform::winit::window::Fullscreen;
 
-use super::{GameEntry, LauncherTab, PendingWindowedResize, WindowMode, WorldEntry};
+use super::{GameEntry, LauncherTab, WindowMode, WorldEntry};
 
 pub(crate) fn scan_games() -> Vec<GameEntry> {
     let mut games = vec![];
@@ -69,34 +68,7 @@ impl App {
             self.cfg.world.seed = meta.seed;
         }
 
-        // Apply window mode
-        if let Some(window) = &self.window {
-            match self.launcher.window_mode {
-                WindowMode::Windowed => {
-                    window.set_fullscreen(None);
-                    if let (Ok(width), Ok(height)) = (
-                        self.launcher.window_width_str.parse::<u32>(),
-                        self.launcher.window_height_str.parse::<u32>(),
-                    ) {
-                        // Don't request the size directly — see
-                        // PendingWindowedResize. Kick off the maximize
-                        // dance instead; the actual request_inner_size
-                        // happens once both steps are confirmed, in the
-                        // WindowEvent::Resized handler.
-                        window.set_maximized(true);
-                        self.pending_windowed_resize =
-                            Some(PendingWindowedResize::AwaitingMaximizeConfirm { width, height });
-                    }
-                }
-                WindowMode::Maximized => {
-                    window.set_fullscreen(None);
-                    window.set_maximized(true);
-                }
-                WindowMode::Fullscreen => {
-                    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
-                }
-            }
-        }
+        self.apply_window_mode();
 
         // Remember this launch's window choice for next time. Tied to the
         // Launch click (not each widget edit) so browsing the window-mode
@@ -115,7 +87,7 @@ impl App {
 
     /// Save the launcher's current window mode/size into the active
     /// profile so it's remembered next time this profile is used.
-    fn persist_window_prefs(&mut self) {
+    pub(crate) fn persist_window_prefs(&mut self) {
         let prefs = self
             .current_profile
             .window
@@ -207,9 +179,14 @@ impl App {
                     );
                     ui.selectable_value(
                         &mut self.launcher.window_mode,
-                        WindowMode::Fullscreen,
+                        WindowMode::BorderlessFullscreen,
                         "Fullscreen",
                     );
+                    ui.selectable_value(
+                        &mut self.launcher.window_mode,
+                        WindowMode::ExclusiveFullscreen,
+                        "Exclusive",
+                    );
                 });
             });
         });
@@ -290,10 +267,10 @@ impl App {
                 });
 
                 ui.horizontal(|ui| {
-                    ui.label("Anisotropy");
+                    ui.label("Max anisotropy");
                     changed |= ui
                         .add(egui::Slider::new(
-                            &mut self.cfg.render.anisotropy,
+                            &mut self.cfg.render.max_anisotropy,
                             0.0..=16.0,
                         ))
                         .changed();
@@ -306,6 +283,16 @@ impl App {
                         .changed();
                 });
 
+                changed |= ui
+                    .checkbox(&mut self.cfg.render.depth_prepass, "Depth prepass")
+                    .on_hover_text("Vulkan only. Helps with heavy overdraw, costs vertex work.")
+                    .changed();
+
+                changed |= ui
+                    .checkbox(&mut self.cfg.render.shadows, "Shadows")
+                    .on_hover_text("Vulkan only. Cascaded shadow maps for the sun.")
+                    .changed();
+
                 // Apply live (not just on next restart) and persist,
                 // mirroring the same set_vsync + configure_advanced pair
                 // the Focused-event handler already uses.
@@ -372,6 +359,9 @@ impl App {
                         )
                         .changed();
                 });
+                changed |= ui
+                    .checkbox(&mut self.cfg.camera.invert_y, "Invert mouse Y")
+                    .changed();
                 if changed {
                     save_global_cfg(&self.cfg);
                 }
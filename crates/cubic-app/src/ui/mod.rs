@@ -11,8 +11,15 @@ pub(crate) mod chat;
 pub(crate) use chat::{ChatMessage, ChatMessageKind};
 pub(crate) mod input_bar;
 
+use crate::backend::RendererBackend;
+use crate::config::save_global_cfg;
+pub(crate) use crate::config::WindowMode;
 use crate::{profile, App};
 
+/// Frames of history kept for the diagnostics overlay's frame-time graph
+/// (see App::frame_times_ms) — about two seconds at 120 fps.
+pub(crate) const FRAME_HISTORY: usize = 240;
+
 /// Transient launcher UI state — not persisted directly; committed to
 /// cfg/profile.toml as the user interacts (see handle_launch,
 /// apply_control_remap).
@@ -64,13 +71,6 @@ pub(crate) struct WorldEntry {
     pub(crate) meta: Option<profile::WorldToml>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) enum WindowMode {
-    Windowed,
-    Maximized,
-    Fullscreen,
-}
-
 /// Tracks an in-flight maximize→unmaximize dance used to unstick
 /// `request_inner_size()` on Wayland, where a plain custom-size request is
 /// silently ignored once the compositor's last reported configure marks
@@ -104,7 +104,8 @@ pub(crate) fn window_mode_to_str(mode: WindowMode) -> &'static str {
     match mode {
         WindowMode::Windowed => "windowed",
         WindowMode::Maximized => "maximized",
-        WindowMode::Fullscreen => "fullscreen",
+        WindowMode::BorderlessFullscreen => "fullscreen",
+        WindowMode::ExclusiveFullscreen => "exclusive_fullscreen",
     }
 }
 
@@ -112,7 +113,8 @@ pub(crate) fn str_to_window_mode(s: &str) -> Option<WindowMode> {
     match s {
         "windowed" => Some(WindowMode::Windowed),
         "maximized" => Some(WindowMode::Maximized),
-        "fullscreen" => Some(WindowMode::Fullscreen),
+        "fullscreen" => Some(WindowMode::BorderlessFullscreen),
+        "exclusive_fullscreen" => Some(WindowMode::ExclusiveFullscreen),
         _ => None,
     }
 }
@@ -222,6 +224,36 @@ impl App {
                 let fps = self.last_fps;
                 let frame_ms = self.last_frame_dt * 1000.0;
                 ui.label(format!("{fps} fps  {frame_ms:.2}ms"));
+                if !self.frame_times_ms.is_empty() {
+                    let n = self.frame_times_ms.len() as f32;
+                    let avg = self.frame_times_ms.iter().sum::<f32>() / n;
+                    let max = self.frame_times_ms.iter().copied().fold(0.0, f32::max);
+                    ui.label(format!(
+                        "frame avg {avg:.2}ms  max {max:.2}ms  render cpu {:.2}ms",
+                        self.last_render_cpu_ms
+                    ));
+                    if let Some(stats) = self.backend.as_ref().map(|b| b.frame_stats()) {
+                        ui.label(format!(
+                            "p95 {:.2}ms  p99 {:.2}ms  (renderer, last {} frames)",
+                            stats.frame.p95_ms, stats.frame.p99_ms, stats.frames
+                        ));
+                        let c = stats.culling;
+                        if c.tested > 0 {
+                            ui.label(format!(
+                                "cpu culling: {} of {} draws culled",
+                                c.culled, c.tested
+                            ));
+                        }
+                        let v = stats.validation;
+                        if v != Default::default() {
+                            ui.label(format!(
+                                "validation: {} errors  {} warnings  {} suppressed",
+                                v.errors, v.warnings, v.suppressed
+                            ));
+                        }
+                    }
+                    frame_time_graph(ui, &self.frame_times_ms);
+                }
 
                 // Position — feet, not the camera, when a WASM game is
                 // driving: third-person orbit moves the camera away from
@@ -252,8 +284,11 @@ impl App {
 
                 // Chunk stats
                 let loaded = self.world.chunk_meshes.len();
-                let pending = self.world.stream.ready_meshes.len();
-                ui.label(format!("Chunks: {loaded} loaded  {pending} pending"));
+                let counts = self.world.stream.stream_counts();
+                ui.label(format!(
+                    "Chunks: {loaded} loaded  {} pending  {} meshing",
+                    counts.uploads_pending, counts.meshing
+                ));
 
                 // Block position (which voxel the camera is in)
                 let voxel_x = (p.x / cubic_world::VOXEL_SIZE as f64).floor() as i32;
@@ -261,6 +296,81 @@ impl App {
                 let voxel_z = (p.z / cubic_world::VOXEL_SIZE as f64).floor() as i32;
                 ui.label(format!("Block: {voxel_x} {voxel_y} {voxel_z}"));
                 ui.label(format!("Seed: {}", self.world.seed));
+
+                ui.separator();
+                self.build_diagnostics_render_section(ui);
             });
     }
+
+    /// Backend/swapchain readout plus vsync/HDR toggles. The toggles only
+    /// take clicks while paused (in-game the cursor is grabbed), and apply
+    /// + persist exactly like the Settings tab's own Render section.
+    fn build_diagnostics_render_section(&mut self, ui: &mut egui::Ui) {
+        let info = self.window_info();
+        let Some(backend) = &mut self.backend else {
+            // Taken out of `self` for the duration of RedrawRequested's
+            // render block, but build_ui runs before that, so in practice
+            // always present here.
+            return;
+        };
+        ui.label(backend.present_summary());
+        let [logical_w, logical_h] = info.logical_size();
+        ui.label(format!(
+            "Window: {}x{} px, {logical_w:.0}x{logical_h:.0} logical at {:.2}x",
+            info.size.width, info.size.height, info.scale_factor
+        ));
+
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= ui.checkbox(&mut self.cfg.render.vsync, "VSync").changed();
+            changed |= ui.checkbox(&mut self.cfg.render.hdr, "HDR").changed();
+            // Asking for HDR doesn't guarantee it (no HDR display, no
+            // matching surface format), so show what's actually live.
+            if backend.surface_info().hdr {
+                ui.label("HDR active");
+            }
+        });
+        if changed {
+            backend.set_vsync(self.cfg.render.vsync);
+            backend.configure_advanced(&self.cfg.render);
+            save_global_cfg(&self.cfg);
+        }
+    }
+}
+
+/// Minimal frame-time bar graph: one column per frame, scaled so 33ms
+/// (30 fps) fills the height, with a reference line at 16.7ms. Drawn with
+/// the plain painter to avoid pulling in egui_plot for a debug readout.
+fn frame_time_graph(ui: &mut egui::Ui, times_ms: &std::collections::VecDeque<f32>) {
+    const FULL_SCALE_MS: f32 = 33.3;
+    let size = egui::vec2(FRAME_HISTORY as f32, 40.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(96));
+
+    let col_w = rect.width() / FRAME_HISTORY as f32;
+    let x0 = rect.right() - col_w * times_ms.len() as f32;
+    for (i, &ms) in times_ms.iter().enumerate() {
+        let h = (ms / FULL_SCALE_MS).min(1.0) * rect.height();
+        let x = x0 + col_w * i as f32;
+        let color = if ms > 16.7 {
+            egui::Color32::from_rgb(230, 120, 60)
+        } else {
+            egui::Color32::from_rgb(90, 200, 120)
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - h),
+                egui::pos2(x + col_w, rect.bottom()),
+            ),
+            0.0,
+            color,
+        );
+    }
+    let y_60 = rect.bottom() - (16.7 / FULL_SCALE_MS) * rect.height();
+    painter.hline(
+        rect.x_range(),
+        y_60,
+        egui::Stroke::new(1.0, egui::Color32::from_white_alpha(96)),
+    );
 }
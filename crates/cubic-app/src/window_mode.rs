@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Window/display mode: windowed, maximized, borderless or exclusive
+//! fullscreen, applied to the winit window and the renderer together.
+//!
+//! The launcher applies the profile's choice at Launch (handle_launch);
+//! set_window_mode switches at any time after that (/window) and saves the
+//! choice to both cubic.toml's `[window] mode` and the profile, so it's
+//! what the next launch starts in.
+
+use cubic_platform::winit::dpi::{PhysicalPosition, PhysicalSize};
+use cubic_platform::winit::event_loop::ActiveEventLoop;
+use cubic_platform::winit::monitor::{MonitorHandle, VideoModeHandle};
+use cubic_platform::winit::window::{Fullscreen, Icon, Window, WindowAttributes, WindowLevel};
+
+use crate::backend::RendererBackend;
+use crate::config::{save_global_cfg, LauncherCfg, WindowCfg, WindowMode};
+use crate::ui::PendingWindowedResize;
+use crate::App;
+
+/// The video mode exclusive fullscreen switches to: the window's monitor
+/// at its largest resolution, fastest refresh rate at that size. None if
+/// winit can't tell which monitor the window is on or lists no modes.
+fn exclusive_video_mode(window: &Window) -> Option<VideoModeHandle> {
+    window.current_monitor()?.video_modes().max_by_key(|m| {
+        let size = m.size();
+        (
+            size.width as u64 * size.height as u64,
+            m.refresh_rate_millihertz(),
+            m.bit_depth(),
+        )
+    })
+}
+
+/// `[window] icon` as a winit icon. None (platform default icon) if the
+/// path is empty or the image won't load, the latter with a warning.
+pub(crate) fn load_window_icon(path: &str) -> Option<Icon> {
+    if path.is_empty() {
+        return None;
+    }
+    let rgba = match image::open(path) {
+        Ok(img) => img.to_rgba8(),
+        Err(e) => {
+            tracing::warn!("failed to load window icon {path}: {e}");
+            return None;
+        }
+    };
+    let (w, h) = rgba.dimensions();
+    Icon::from_rgba(rgba.into_raw(), w, h)
+        .map_err(|e| tracing::warn!("unusable window icon {path}: {e}"))
+        .ok()
+}
+
+/// `[window] monitor`: an index into winit's monitor list or a name
+/// substring (case-insensitive). Logs the list when nothing matches.
+fn find_monitor(event_loop: &ActiveEventLoop, spec: &str) -> Option<MonitorHandle> {
+    let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+    let found = match spec.parse::<usize>() {
+        Ok(index) => monitors.get(index).cloned(),
+        Err(_) => {
+            let needle = spec.to_lowercase();
+            monitors
+                .iter()
+                .find(|m| m.name().is_some_and(|n| n.to_lowercase().contains(&needle)))
+                .cloned()
+        }
+    };
+    if found.is_none() {
+        tracing::warn!("no monitor matches {spec:?}; opening wherever the platform puts it");
+        for (i, m) in monitors.iter().enumerate() {
+            let size = m.size();
+            tracing::info!(
+                "monitor {i}: {} ({}x{})",
+                m.name().unwrap_or_else(|| "unnamed".to_string()),
+                size.width,
+                size.height
+            );
+        }
+    }
+    found
+}
+
+/// `cfg`'s min and max inner size for winit, None where neither axis is
+/// set. An unset bound is 1 or unlimited, so one key works on its own.
+fn inner_size_limits(cfg: &WindowCfg) -> (Option<PhysicalSize<u32>>, Option<PhysicalSize<u32>>) {
+    let min_size = (cfg.min_width.is_some() || cfg.min_height.is_some())
+        .then(|| PhysicalSize::new(cfg.min_width.unwrap_or(1), cfg.min_height.unwrap_or(1)));
+    let max_size = (cfg.max_width.is_some() || cfg.max_height.is_some()).then(|| {
+        PhysicalSize::new(
+            cfg.max_width.unwrap_or(i32::MAX as u32),
+            cfg.max_height.unwrap_or(i32::MAX as u32),
+        )
+    });
+    (min_size, max_size)
+}
+
+/// The attributes resumed() creates the window with: `cfg` (cubic.toml's
+/// `[window]` plus command-line overrides), falling back to `[launcher]`'s
+/// size, within `cfg`'s size limits. A chosen monitor gets the window
+/// centred on it; Wayland ignores positions, so there it's up to the
+/// compositor.
+pub(crate) fn startup_window_attributes(
+    event_loop: &ActiveEventLoop,
+    cfg: &WindowCfg,
+    launcher: &LauncherCfg,
+) -> WindowAttributes {
+    let size = PhysicalSize::new(
+        cfg.width.unwrap_or(launcher.width).max(1),
+        cfg.height.unwrap_or(launcher.height).max(1),
+    );
+    let mut attrs = Window::default_attributes()
+        .with_title(cfg.title.clone())
+        .with_window_icon(load_window_icon(&cfg.icon))
+        .with_inner_size(size)
+        .with_resizable(cfg.resizable)
+        .with_decorations(cfg.decorations)
+        .with_maximized(cfg.start_maximized)
+        .with_window_level(if cfg.always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        });
+    let (min_size, max_size) = inner_size_limits(cfg);
+    if let Some(min_size) = min_size {
+        attrs = attrs.with_min_inner_size(min_size);
+    }
+    if let Some(max_size) = max_size {
+        attrs = attrs.with_max_inner_size(max_size);
+    }
+    if let Some(monitor) = cfg
+        .monitor
+        .as_deref()
+        .and_then(|spec| find_monitor(event_loop, spec))
+    {
+        let origin = monitor.position();
+        let area = monitor.size();
+        let x = origin.x + (area.width.saturating_sub(size.width) / 2) as i32;
+        let y = origin.y + (area.height.saturating_sub(size.height) / 2) as i32;
+        attrs = attrs.with_position(PhysicalPosition::new(x, y));
+    }
+    attrs
+}
+
+impl App {
+    /// Take `cfg`'s size limits and aspect lock (a reloaded cubic.toml's)
+    /// on the running window and renderer. Returns whether they differed
+    /// from the ones in effect.
+    pub(crate) fn apply_window_limits(&mut self, cfg: &WindowCfg) -> bool {
+        let limits = |w: &WindowCfg| {
+            (
+                w.min_width,
+                w.min_height,
+                w.max_width,
+                w.max_height,
+                w.aspect_lock,
+            )
+        };
+        if limits(cfg) == limits(&self.startup_window) {
+            return false;
+        }
+        let window = &mut self.startup_window;
+        window.min_width = cfg.min_width;
+        window.min_height = cfg.min_height;
+        window.max_width = cfg.max_width;
+        window.max_height = cfg.max_height;
+        window.aspect_lock = cfg.aspect_lock;
+        if let Some(window) = &self.window {
+            let (min_size, max_size) = inner_size_limits(&self.startup_window);
+            window.set_min_inner_size(min_size);
+            window.set_max_inner_size(max_size);
+        }
+        if let Some(backend) = &mut self.backend {
+            backend.set_window_limits(&self.startup_window);
+        }
+        true
+    }
+
+    /// Switch the window to `mode` now and remember it (cubic.toml and the
+    /// current profile).
+    pub(crate) fn set_window_mode(&mut self, mode: WindowMode) {
+        self.launcher.window_mode = mode;
+        self.apply_window_mode();
+        self.cfg.window.mode = mode;
+        save_global_cfg(&self.cfg);
+        self.persist_window_prefs();
+    }
+
+    /// Put the window (and the renderer's swapchain) into the launcher's
+    /// current window mode.
+    pub(crate) fn apply_window_mode(&mut self) {
+        let mode = self.launcher.window_mode;
+        if let Some(window) = &self.window {
+            match mode {
+                WindowMode::Windowed => {
+                    window.set_fullscreen(None);
+                    if let (Ok(width), Ok(height)) = (
+                        self.launcher.window_width_str.parse::<u32>(),
+                        self.launcher.window_height_str.parse::<u32>(),
+                    ) {
+                        // Don't request the size directly — see
+                        // PendingWindowedResize. Kick off the maximize
+                        // dance instead; the actual request_inner_size
+                        // happens once both steps are confirmed, in the
+                        // WindowEvent::Resized handler.
+                        window.set_maximized(true);
+                        self.pending_windowed_resize =
+                            Some(PendingWindowedResize::AwaitingMaximizeConfirm { width, height });
+                    }
+                }
+                WindowMode::Maximized => {
+                    window.set_fullscreen(None);
+                    window.set_maximized(true);
+                }
+                WindowMode::BorderlessFullscreen => {
+                    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                }
+                WindowMode::ExclusiveFullscreen => match exclusive_video_mode(window) {
+                    Some(video_mode) => {
+                        tracing::info!("exclusive fullscreen: {video_mode}");
+                        window.set_fullscreen(Some(Fullscreen::Exclusive(video_mode)));
+                    }
+                    None => {
+                        // Wayland has no mode switching at all; the closest
+                        // thing is a borderless window on the same output.
+                        tracing::warn!(
+                            "exclusive fullscreen: no video modes for this monitor, \
+                             using borderless"
+                        );
+                        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                    }
+                },
+            }
+        }
+        if let Some(backend) = &mut self.backend {
+            backend.set_exclusive_fullscreen(mode == WindowMode::ExclusiveFullscreen);
+        }
+    }
+}
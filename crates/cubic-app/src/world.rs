@@ -1,22 +1,29 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
 //! World (re)loading and the per-frame guest tick / chunk streaming /
-//! upload / remesh / draw pipeline driven from RedrawRequested.
+//! upload / remesh / draw pipeline driven from RedrawRequested. The guest
+//! tick itself is fixed_update, run per fixed step or once per frame (see
+//! game_loop.rs).
 
+use crate::async_load::{AssetJob, AsyncAssets};
 use crate::backend::{Backend, RendererBackend};
-use crate::frustum::Frustum;
+use crate::config::SaveCompressionCfg;
+use crate::game_loop::{CameraPose, Interpolated};
+use crate::plugin::PluginEvent;
 use crate::profile;
 use crate::App;
-use cubic_math::{DVec3, Vec3};
-use cubic_render::{MeshHandle, PushData};
+use cubic_ecs::{GlobalTransform, Transform};
+use cubic_math::{DVec3, Quat, Vec3};
+use cubic_render::clip::{self, Frustum};
+use cubic_render::{MeshHandle, PushData, RenderError};
 use cubic_wasm::{
     clear_tick_query, set_tick_input, set_tick_query, take_camera_update, InputSnapshot,
     WasmPlugin, WasmWorldGenerator,
 };
 use cubic_world::ChunkPos;
 use cubic_world::{
-    mesh_chunk, world_pos_to_chunk, AsyncWorldStream, BlockFaceTextures, RegionCache,
-    WorldGenerator, CHUNK_SIZE, VOXEL_SIZE,
+    world_pos_to_chunk, AsyncWorldStream, BlockFaceTextures, BlockTextureArray, ChunkCompression,
+    RegionCache, WorldGenerator, CHUNK_SIZE, VOXEL_SIZE,
 };
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
@@ -32,32 +39,65 @@ pub(crate) struct WorldRenderer {
     pub(crate) stream: AsyncWorldStream,
     pub(crate) chunk_meshes: HashMap<ChunkPos, MeshHandle>,
     // Path (relative to the game's data dir) -> bindless texture index,
+    // or layer of the block texture array when block_array is set,
     // populated by load_world() from the WASM plugin's block registry.
     // Consumed by the mesher to assign tex_index per face.
     pub(crate) tex_map: HashMap<String, u32>,
-    // Per-block-per-face bindless texture index lookup built from tex_map
-    // in load_world(); Arc'd so streaming worker threads can share it.
+    pub(crate) block_array: bool,
+    // Per-block-per-face texture index lookup built from tex_map in
+    // load_world(); Arc'd so streaming worker threads can share it.
     pub(crate) face_textures: Arc<BlockFaceTextures>,
-    pub(crate) entity_meshes: HashMap<u32, MeshHandle>,
+    // Guest-visible mesh id -> (handle, .obj/.gltf it was loaded from); the
+    // path lets reupload_after_device_loss load it again.
+    pub(crate) entity_meshes: HashMap<u32, (MeshHandle, std::path::PathBuf)>,
     pub(crate) next_entity_mesh_id: u32,
+    // glTF mesh path -> bindless indices of its textures (see
+    // loader::load_mesh_file), so reloading a model doesn't upload them twice.
+    pub(crate) model_textures: HashMap<std::path::PathBuf, Vec<u32>>,
+    // load-texture path (joined onto the game dir) -> bindless index, so
+    // asset hot-reload can find the guest's textures (see hot_reload.rs).
+    pub(crate) guest_textures: HashMap<std::path::PathBuf, u32>,
+    // Background decoding and the entity meshes still loading (see
+    // async_load.rs).
+    pub(crate) assets: AsyncAssets,
     pub(crate) remesh_scratch: HashSet<ChunkPos>,
+    // Drawable entities: Transform (+ optional cubic_ecs::Parent) and an
+    // EntityMesh each, resolved to world space and drawn every frame.
+    pub(crate) scene: cubic_ecs::World,
+    // The scene entities spawned from the guest's draw-mesh calls in the
+    // last simulation step; replaced by the next step's.
+    pub(crate) guest_entities: Vec<cubic_ecs::Entity>,
     pub(crate) seed: u64,
 }
 
+/// Scene component: draw this guest-visible entity mesh (an
+/// `entity_meshes` key) with this bindless texture.
+#[derive(Clone, Copy)]
+pub(crate) struct EntityMesh {
+    pub(crate) mesh_id: u32,
+    pub(crate) tex_index: u32,
+}
+
 impl WorldRenderer {
-    pub(crate) fn new(stream_radius: i32, stream_radius_y: i32) -> Self {
+    pub(crate) fn new(
+        stream_radius: i32,
+        stream_radius_y: i32,
+        jobs: Arc<cubic_jobs::JobPool>,
+    ) -> Self {
         Self {
-            stream: AsyncWorldStream::new(
-                stream_radius,
-                stream_radius_y,
-                Some(Arc::new(cubic_wasm::set_worker_id as fn(usize))),
-            ),
+            stream: AsyncWorldStream::new(stream_radius, stream_radius_y, Arc::clone(&jobs)),
             chunk_meshes: HashMap::new(),
             tex_map: HashMap::new(),
+            block_array: false,
             face_textures: Arc::new(BlockFaceTextures::new()),
             entity_meshes: HashMap::new(),
             next_entity_mesh_id: 1,
+            model_textures: HashMap::new(),
+            guest_textures: HashMap::new(),
+            assets: AsyncAssets::new(jobs),
             remesh_scratch: HashSet::new(),
+            scene: cubic_ecs::World::new(),
+            guest_entities: Vec::new(),
             seed: 0,
         }
     }
@@ -75,6 +115,11 @@ impl App {
         self.world.chunk_meshes.clear();
         self.world.face_textures = Arc::new(BlockFaceTextures::new());
         self.world.tex_map = HashMap::new();
+        self.world.guest_textures.clear();
+        self.world.assets.clear();
+        self.world.scene.clear();
+        self.world.guest_entities.clear();
+        self.sim_camera = None;
 
         // Derive world directory from profile — not from cubic.toml. The path is
         // always: $XDG_DATA_HOME/CubicEngine/profiles/<game>/<profile>/worlds/<world>/
@@ -140,10 +185,9 @@ impl App {
         // launcher's seed field actually affect generated terrain means
         // rebuilding the plugin (and the generator, which just wraps it)
         // on every launch rather than reusing one built once in main().
-        let worker_count = std::thread::available_parallelism()
-            .map_or(4, |n| n.get())
-            .saturating_sub(1)
-            .max(1);
+        // One output buffer per job-pool worker, each of which generates
+        // through its own instance (see cubic_wasm::set_worker_id).
+        let worker_count = self.jobs.threads();
         let plugin = Arc::new(
             WasmPlugin::load(
                 &self.cfg.game.path,
@@ -155,13 +199,16 @@ impl App {
         );
 
         // Set up asset loading callbacks before warm_up so on_load can call
-        // load-mesh/load-texture synchronously during the guest's on_load.
+        // load-mesh/load-texture during the guest's on_load (load-mesh only
+        // queues the file; see async_load.rs).
         // Safety: warm_up() is synchronous and returns before these closures
         // go out of scope. The pointers are valid for the duration of the call.
         {
             let backend_ptr = self.backend.as_mut().unwrap() as *mut Backend;
-            let entity_meshes_ptr = &mut self.world.entity_meshes as *mut HashMap<u32, MeshHandle>;
+            let assets_ptr = &mut self.world.assets as *mut AsyncAssets;
             let next_id_ptr = &mut self.world.next_entity_mesh_id as *mut u32;
+            let guest_textures_ptr =
+                &mut self.world.guest_textures as *mut HashMap<std::path::PathBuf, u32>;
             let game_dir = std::path::Path::new(&self.cfg.game.path)
                 .parent()
                 .unwrap_or(std::path::Path::new("."))
@@ -171,32 +218,24 @@ impl App {
             cubic_wasm::set_load_fns(
                 move |path: &str| {
                     let full = game_dir.join(path);
-                    let backend = unsafe { &mut *backend_ptr };
-                    let entity_meshes = unsafe { &mut *entity_meshes_ptr };
+                    let assets = unsafe { &mut *assets_ptr };
                     let next_id = unsafe { &mut *next_id_ptr };
-                    match crate::loader::load_obj_mesh(&full) {
-                        Ok((verts, idxs)) => match backend.upload_mesh(&verts, &idxs) {
-                            Ok(handle) => {
-                                let id = *next_id;
-                                *next_id += 1;
-                                entity_meshes.insert(id, handle);
-                                tracing::info!("loaded mesh: {path} -> handle {id}");
-                                id
-                            }
-                            Err(e) => {
-                                tracing::error!("load-mesh upload failed: {e}");
-                                0
-                            }
-                        },
-                        Err(e) => {
-                            tracing::error!("load-mesh failed for {path}: {e}");
-                            0
-                        }
+                    // Only a file that isn't there fails up front; decode
+                    // and upload errors are logged when they happen, and
+                    // the id just never becomes drawable.
+                    if !full.is_file() {
+                        tracing::error!("load-mesh failed for {path}: no such file");
+                        return 0;
                     }
+                    let id = *next_id;
+                    *next_id += 1;
+                    assets.load_mesh(&full, AssetJob::EntityMesh(id));
+                    id
                 },
                 move |path: &str| {
                     let full = game_dir2.join(path);
                     let backend = unsafe { &mut *backend_ptr };
+                    let guest_textures = unsafe { &mut *guest_textures_ptr };
                     match image::open(&full) {
                         Ok(img) => {
                             let rgba = img.to_rgba8();
@@ -204,6 +243,7 @@ impl App {
                             match backend.upload_texture(rgba.as_raw(), w, h) {
                                 Ok(idx) => {
                                     tracing::info!("loaded texture: {path} -> index {idx}");
+                                    guest_textures.insert(full, idx);
                                     idx
                                 }
                                 Err(e) => {
@@ -254,78 +294,23 @@ impl App {
         self.guest.registered_commands = commands;
 
         // Load textures
-        if let Some(backend) = &mut self.backend {
-            let unique_paths: HashSet<String> = {
-                let registry_arc = plugin.block_registry();
-                let registry = registry_arc.lock().unwrap();
-                registry
-                    .all_defs()
-                    .flat_map(|def| {
-                        [
-                            def.faces.top.clone(),
-                            def.faces.bottom.clone(),
-                            def.faces.front.clone(),
-                            def.faces.back.clone(),
-                            def.faces.left.clone(),
-                            def.faces.right.clone(),
-                        ]
-                    })
-                    .filter(|p| !p.is_empty())
-                    .collect()
-            };
-
-            let game_dir = std::path::Path::new(&self.cfg.game.path)
-                .parent()
-                .unwrap_or(std::path::Path::new("."));
-
-            let mut tex_map: HashMap<String, u32> = HashMap::new();
-            for path in unique_paths {
-                let full = game_dir.join(&path);
-                match image::open(&full) {
-                    Ok(img) => {
-                        let rgba = img.to_rgba8();
-                        let (w, h) = rgba.dimensions();
-                        match backend.upload_texture(rgba.as_raw(), w, h) {
-                            Ok(index) => {
-                                tex_map.insert(path, index);
-                            }
-                            Err(e) => error!("texture upload failed {full:?}: {e}"),
-                        }
-                    }
-                    Err(e) => error!("failed to load texture {full:?}: {e}"),
-                }
-            }
-            self.world.tex_map = tex_map;
-
-            // Build the per-block-per-face texture lookup the mesher
-            // indexes by BlockTypeId, now that tex_map has the path ->
-            // bindless index mapping.
-            let registry_arc = plugin.block_registry();
-            let registry = registry_arc.lock().unwrap();
-            let mut face_textures = BlockFaceTextures::new();
-            for def in registry.all_defs() {
-                // dir order: -X, +X, -Y, +Y, -Z, +Z
-                // face mapping: left/right=sides, bottom=-Y, top=+Y, front/back=sides
-                let get = |path: &str| self.world.tex_map.get(path).copied().unwrap_or(0);
-                face_textures.push([
-                    get(&def.faces.left),   // -X
-                    get(&def.faces.right),  // +X
-                    get(&def.faces.bottom), // -Y
-                    get(&def.faces.top),    // +Y
-                    get(&def.faces.front),  // -Z
-                    get(&def.faces.back),   // +Z
-                ]);
-            }
-            self.world.face_textures = Arc::new(face_textures);
+        if let Some(mut backend) = self.backend.take() {
+            self.upload_block_textures(&mut backend, &plugin);
+            self.backend = Some(backend);
         }
 
         // Initialize streaming using the current (possibly launcher-edited)
-        // radius settings, not whatever main() built App with.
+        // radius settings, not whatever main() built App with. Saves the old
+        // stream queued finish first, as the new one can't see them.
+        self.world.stream.wait_for_saves();
         self.world.stream = AsyncWorldStream::new(
             self.cfg.world.stream_radius,
             self.cfg.world.stream_radius_y,
-            Some(Arc::new(cubic_wasm::set_worker_id as fn(usize))),
+            Arc::clone(&self.jobs),
         );
+        self.world
+            .stream
+            .set_lod_distance(self.cfg.world.lod_distance);
 
         if let Some(generator) = self.guest.generator.clone() {
             self.world.stream.set_persistence(
@@ -335,10 +320,287 @@ impl App {
                 self.cfg.world.diff_threshold,
             );
         }
+        self.world
+            .stream
+            .set_save_compression(chunk_compression(self.cfg.world.save_compression));
+
+        self.asset_watcher = crate::hot_reload::watch_game_assets(&self.cfg.game.path);
 
         self.region_cache = Some(region_cache);
         self.load_chat_log(&world_dir);
         self.load_input_history(&world_dir);
+        self.plugin_event(PluginEvent::WorldLoaded);
+    }
+
+    /// Decode and upload every block face texture the guest's registry
+    /// names, then rebuild tex_map and face_textures from the layers (or
+    /// indices) the backend handed out. Faces go up as one texture array
+    /// (BlockTextureArray, at the largest face's size) where the backend
+    /// has one, so chunks draw with a single material; otherwise each is
+    /// its own bindless texture. Used by load_world, again after a backend
+    /// switch, and by hot-reload of an array face.
+    pub(crate) fn upload_block_textures(&mut self, backend: &mut Backend, plugin: &WasmPlugin) {
+        let unique_paths: HashSet<String> = {
+            let registry_arc = plugin.block_registry();
+            let registry = registry_arc.lock().unwrap();
+            registry
+                .all_defs()
+                .flat_map(|def| {
+                    [
+                        def.faces.top.clone(),
+                        def.faces.bottom.clone(),
+                        def.faces.front.clone(),
+                        def.faces.back.clone(),
+                        def.faces.left.clone(),
+                        def.faces.right.clone(),
+                    ]
+                })
+                .filter(|p| !p.is_empty())
+                .collect()
+        };
+
+        let game_dir = std::path::Path::new(&self.cfg.game.path)
+            .parent()
+            .unwrap_or(std::path::Path::new("."));
+
+        // Decoded in parallel on the asset loader, but waited for:
+        // streaming can't start until every face has its index.
+        let loader = &mut self.world.assets.loader;
+        let mut pending: HashMap<cubic_assets::LoadId, String> = unique_paths
+            .into_iter()
+            .map(|path| (loader.load_image(&game_dir.join(&path)), path))
+            .collect();
+        let ids: Vec<_> = pending.keys().copied().collect();
+        let mut images = Vec::new();
+        for loaded in loader.wait(&ids) {
+            let Some(path) = pending.remove(&loaded.id) else {
+                continue;
+            };
+            let full = loaded.path;
+            match loaded.result {
+                Ok(cubic_assets::Decoded::Image(img)) => images.push((path, full, img)),
+                Ok(_) => {}
+                Err(e) => error!("failed to load texture {full:?}: {e:#}"),
+            }
+        }
+        // Sorted so a face keeps its layer across uploads of the same set.
+        images.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let size = images
+            .iter()
+            .map(|(_, _, img)| img.width.max(img.height))
+            .max()
+            .unwrap_or(1);
+        let mut array = BlockTextureArray::new(size);
+        for (path, _, img) in &images {
+            array.insert(path, &img.pixels, img.width, img.height);
+        }
+        let mut tex_map: HashMap<String, u32> = HashMap::new();
+        self.world.block_array =
+            match backend.upload_block_textures(array.pixels(), size, array.layer_count()) {
+                Ok(uploaded) => uploaded,
+                Err(e) => {
+                    error!("block texture array upload failed, using single textures: {e:#}");
+                    false
+                }
+            };
+        if self.world.block_array {
+            for (path, _, _) in &images {
+                tex_map.insert(path.clone(), array.layer(path));
+            }
+        } else {
+            for (path, full, img) in images {
+                match backend.upload_texture(&img.pixels, img.width, img.height) {
+                    Ok(index) => {
+                        tex_map.insert(path, index);
+                    }
+                    Err(e) => error!("texture upload failed {full:?}: {e}"),
+                }
+            }
+        }
+        self.world.tex_map = tex_map;
+
+        // Build the per-block-per-face texture lookup the mesher
+        // indexes by BlockTypeId, now that tex_map has the path ->
+        // layer or bindless index mapping.
+        let registry_arc = plugin.block_registry();
+        let registry = registry_arc.lock().unwrap();
+        let mut face_textures = BlockFaceTextures::new();
+        for def in registry.all_defs() {
+            // dir order: -X, +X, -Y, +Y, -Z, +Z
+            // face mapping: left/right=sides, bottom=-Y, top=+Y, front/back=sides
+            let get = |path: &str| self.world.tex_map.get(path).copied().unwrap_or(0);
+            face_textures.push([
+                get(&def.faces.left),   // -X
+                get(&def.faces.right),  // +X
+                get(&def.faces.bottom), // -Y
+                get(&def.faces.top),    // +Y
+                get(&def.faces.front),  // -Z
+                get(&def.faces.back),   // +Z
+            ]);
+        }
+        self.world.face_textures = Arc::new(face_textures);
+    }
+
+    /// Put the world's geometry back after the renderer was rebuilt for a
+    /// lost device (textures and pipelines survive that; meshes don't).
+    /// Chunk meshes go back through the boundary-remesh queue, so they
+    /// reappear over the next few frames within the usual upload budget;
+    /// the handful of guest entity meshes are reloaded from disk right away
+    /// under their old ids. The stale handles are simply forgotten — they
+    /// belonged to the dead device, so there's nothing to free_mesh.
+    pub(crate) fn reupload_after_device_loss(&mut self, backend: &mut Backend) {
+        let chunks: Vec<ChunkPos> = self
+            .world
+            .chunk_meshes
+            .drain()
+            .map(|(pos, _)| pos)
+            .collect();
+        self.world.stream.remesh_queue.extend(chunks);
+
+        // Ones that were still waiting on textures just load again.
+        for (id, path) in self.world.assets.drain_waiting() {
+            self.world.assets.load_mesh(&path, AssetJob::EntityMesh(id));
+        }
+
+        for (id, (handle, path)) in self.world.entity_meshes.iter_mut() {
+            let uploaded =
+                crate::loader::load_mesh_file(path, &mut self.world.model_textures, |px, w, h| {
+                    Ok(backend.upload_texture(px, w, h)?)
+                })
+                .and_then(|(verts, idxs)| Ok(backend.upload_mesh(&verts, &idxs)?));
+            match uploaded {
+                Ok(h) => *handle = h,
+                Err(e) => error!("entity mesh {id} ({path:?}) not restored: {e}"),
+            }
+        }
+    }
+
+    /// Put the world back on a backend that replaced the old one (see
+    /// App::switch_backend). Unlike a device loss nothing survives the
+    /// switch, so textures go up again first: block textures get fresh
+    /// indices (chunks remesh with them), and guest textures are uploaded
+    /// in their old index order, which keeps the indices the guest holds
+    /// wherever the new backend allocates like the old one (Vulkan to
+    /// Vulkan). Anything that moves is logged.
+    pub(crate) fn reupload_after_backend_switch(&mut self, backend: &mut Backend) {
+        if let Some(plugin) = self.guest.plugin.clone() {
+            self.upload_block_textures(backend, &plugin);
+        }
+        let mut guest: Vec<_> = self.world.guest_textures.drain().collect();
+        guest.sort_by_key(|&(_, index)| index);
+        for (path, old) in guest {
+            let uploaded = image::open(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|img| {
+                    let rgba = img.to_rgba8();
+                    let (w, h) = rgba.dimensions();
+                    Ok(backend.upload_texture(rgba.as_raw(), w, h)?)
+                });
+            match uploaded {
+                Ok(index) => {
+                    if index != old {
+                        tracing::warn!(
+                            "guest texture {path:?} is index {index} on the new backend, \
+                             was {old}; the game still draws it with {old}"
+                        );
+                    }
+                    self.world.guest_textures.insert(path, index);
+                }
+                Err(e) => error!("guest texture {path:?} not restored: {e}"),
+            }
+        }
+        // Entity meshes reload their textures through this cache.
+        self.world.model_textures.clear();
+        self.reupload_after_device_loss(backend);
+    }
+
+    /// One simulation step of `step` seconds: the guest's on_tick with
+    /// `input`, then the camera and block edits it requested. This is the
+    /// hook game logic runs in; with `[game] tick_hz` set it runs at that
+    /// fixed rate whatever the frame rate, otherwise once per frame.
+    pub(crate) fn fixed_update(&mut self, input: InputSnapshot, step: f32) {
+        let _span = tracing::debug_span!("fixed_update").entered();
+        // Bracket on_tick with a chunk-query view borrowed from
+        // self.world.stream: queries happen on the main thread, sequentially,
+        // before the streaming update mutates chunks, so no locking or
+        // copying is needed — just a borrow scoped to this call.
+        let view = self.world.stream.query_view();
+        set_tick_query(&view);
+        set_tick_input(input);
+
+        if let Some(game) = &self.guest.wasm_game {
+            game.tick(step);
+        }
+
+        if let Some(cam) = take_camera_update() {
+            self.player_spectating = cam.spectating;
+            let pose = CameraPose {
+                position: DVec3::new(cam.x, cam.y, cam.z),
+                yaw: cam.yaw,
+                pitch: cam.pitch,
+            };
+            match &mut self.sim_camera {
+                Some(sim) => sim.push(pose),
+                None => self.sim_camera = Some(Interpolated::new(pose)),
+            }
+        } else if let Some(sim) = &mut self.sim_camera {
+            // No update means the camera held still this step.
+            let pose = sim.current();
+            sim.push(pose);
+        }
+
+        clear_tick_query();
+
+        // Apply any block edits (break/place) the guest requested this
+        // tick — deferred until after the chunk-query borrow above ends,
+        // since it aliases the same chunk data (see BlockEditRequest's doc
+        // comment). set_block_at pushes into self.world.stream.remesh_queue,
+        // which world_tick_and_draw's boundary remesh pass already drains —
+        // no separate "upload this edit's mesh" step needed. Plugins hear
+        // of the edited chunks now, though, within the step.
+        let mut edited = HashSet::new();
+        for edit in cubic_wasm::take_block_edits() {
+            let changed = self.world.stream.set_block_at(
+                edit.x,
+                edit.y,
+                edit.z,
+                cubic_world::BlockTypeId(edit.block_id),
+            );
+            if changed {
+                edited.insert(world_pos_to_chunk(DVec3::new(edit.x, edit.y, edit.z)));
+            }
+        }
+        for pos in edited {
+            self.plugin_event(PluginEvent::ChunkEdited(pos));
+        }
+
+        let scene = &mut self.world.scene;
+        for e in self.world.guest_entities.drain(..) {
+            scene.despawn(e);
+        }
+        for req in cubic_wasm::take_draw_queue() {
+            let e = scene.spawn();
+            // from_rotation_y(yaw) turns the model's -Z front (see
+            // player.obj) to (-sin(yaw), 0, -cos(yaw)) — the direction
+            // cubic_math::Camera::forward() and player.rs use for the same
+            // yaw, so models face where the guest says they do.
+            scene.insert(
+                e,
+                Transform::from_translation(DVec3::new(req.x, req.y, req.z))
+                    .with_rotation(Quat::from_rotation_y(req.yaw)),
+            );
+            scene.insert(
+                e,
+                EntityMesh {
+                    mesh_id: req.mesh_handle,
+                    tex_index: req.tex_index,
+                },
+            );
+            self.world.guest_entities.push(e);
+        }
+
+        self.with_plugins(None, |plugins, ctx| plugins.fixed_update(ctx, step));
     }
 
     /// Advance the guest tick, chunk streaming, mesh upload/remesh, and
@@ -352,14 +614,8 @@ impl App {
         now: std::time::Instant,
         dt: f32,
     ) {
+        let _span = tracing::debug_span!("world").entered();
         // --- Physics tick ---
-        // Bracket on_tick with a chunk-query view borrowed from
-        // self.world.stream: queries happen on the main thread, sequentially,
-        // before the streaming update below mutates chunks, so no locking
-        // or copying is needed — just a borrow scoped to this call.
-        let view = self.world.stream.query_view();
-        set_tick_query(&view);
-
         // take_mouse_delta() is consumed here for the game tick —
         // apply_input() skips its own yaw/pitch update whenever wasm_game
         // is active (see its doc comment) so the delta isn't
@@ -393,69 +649,57 @@ impl App {
         {
             self.show_diagnostics = !self.show_diagnostics;
         }
-        set_tick_input(snap);
-
-        if let Some(game) = &self.guest.wasm_game {
-            game.tick(dt);
-        }
-
-        if let Some(cam) = take_camera_update() {
-            self.camera.position = DVec3::new(cam.x, cam.y, cam.z);
-            self.camera.yaw = cam.yaw;
-            self.camera.pitch = cam.pitch;
-            self.player_spectating = cam.spectating;
-        }
-
-        clear_tick_query();
 
-        // Apply any block edits (break/place) the guest requested this
-        // tick — deferred until after the chunk-query borrow above ends,
-        // since it aliases the same chunk data (see BlockEditRequest's doc
-        // comment). set_block_at pushes into self.world.stream.remesh_queue,
-        // which the boundary remesh pass below already drains — no
-        // separate "upload this edit's mesh" step needed.
-        for edit in cubic_wasm::take_block_edits() {
-            self.world.stream.set_block_at(
-                edit.x,
-                edit.y,
-                edit.z,
-                cubic_world::BlockTypeId(edit.block_id),
-            );
+        let alpha = match self.fixed_step.as_mut() {
+            Some(fixed) => {
+                let steps = fixed.advance(std::time::Duration::from_secs_f32(dt));
+                let step = fixed.step_secs();
+                let alpha = fixed.alpha();
+                for i in 0..steps {
+                    // The frame's mouse movement is turned once, by the
+                    // first step, not once per step.
+                    let input = if i == 0 {
+                        snap
+                    } else {
+                        InputSnapshot {
+                            look_dx: 0.0,
+                            look_dy: 0.0,
+                            ..snap
+                        }
+                    };
+                    self.fixed_update(input, step);
+                }
+                alpha
+            }
+            None => {
+                self.fixed_update(snap, dt);
+                1.0
+            }
+        };
+        if let Some(sim) = &self.sim_camera {
+            sim.at(alpha).apply(&mut self.camera);
         }
+        self.auto_run_camera();
 
-        // Flush entity draw queue from game tick
+        // --- Scene ---
+        // Resolve the scene's transform hierarchy and draw every entity
+        // mesh at its world-space transform, camera-relative.
+        cubic_ecs::propagate_transforms(&mut self.world.scene);
         let cam_pos = self.camera.position;
-        for req in cubic_wasm::take_draw_queue() {
-            if let Some(&handle) = self.world.entity_meshes.get(&req.mesh_handle) {
-                let relative = (DVec3::new(req.x, req.y, req.z) - cam_pos).as_vec3();
-                let cos_y = req.yaw.cos();
-                // Negated (not req.yaw + PI): at yaw=0 this matrix already
-                // maps the model's -Z front (see player.obj) to world -Z
-                // correctly, so adding PI would just turn the model to
-                // face backward. The bug is a mirroring, not a 180 offset
-                // — cubic_math::Camera::forward() (and player.rs's own
-                // forward/right vectors) define yaw such that local -Z
-                // rotates to world (-sin(yaw), 0, -cos(yaw)), but this
-                // matrix without the negation rotates it to (sin(yaw), 0,
-                // -cos(yaw)) instead — correct only at yaw = 0 or PI,
-                // mirrored everywhere else. Negating sin_y (equivalent to
-                // using -yaw) matches the engine's convention for every
-                // yaw, not just the yaw=0 case a single visual check at
-                // spawn would catch.
-                let sin_y = -req.yaw.sin();
-                let push = PushData {
-                    model: [
-                        [cos_y, 0.0, sin_y, 0.0],
-                        [0.0, 1.0, 0.0, 0.0],
-                        [-sin_y, 0.0, cos_y, 0.0],
-                        [relative.x, relative.y, relative.z, 1.0],
-                    ],
-                    tint: [1.0, 1.0, 1.0, 1.0],
-                    tex_index: req.tex_index,
-                    _pad: [0; 3],
-                };
-                backend.draw_mesh(handle, push);
-            }
+        for (e, mesh) in self.world.scene.query::<EntityMesh>() {
+            let Some(&(handle, _)) = self.world.entity_meshes.get(&mesh.mesh_id) else {
+                continue;
+            };
+            let Some(global) = self.world.scene.get::<GlobalTransform>(e) else {
+                continue;
+            };
+            let push = PushData {
+                model: global.relative_matrix(cam_pos).to_cols_array_2d(),
+                tint: [1.0, 1.0, 1.0, 1.0],
+                tex_index: mesh.tex_index,
+                _pad: [0; 3],
+            };
+            backend.draw_mesh(handle, push);
         }
 
         // --- Stream update ---
@@ -471,6 +715,7 @@ impl App {
             if let Some(handle) = self.world.chunk_meshes.remove(&pos) {
                 backend.free_mesh(handle);
             }
+            self.plugin_event(PluginEvent::ChunkUnloaded(pos));
         }
 
         // Compute this frame's mesh budget
@@ -481,62 +726,82 @@ impl App {
             self.cfg.world.upload_budget_ms
         };
         let budget_deadline = now + std::time::Duration::from_secs_f32(upload_ms / 1000.0);
+        // Optional cap on the count too, shared with the remesh below.
+        let mut uploads_left = match self.cfg.world.max_chunk_uploads {
+            0 => usize::MAX,
+            n => n as usize,
+        };
 
-        // Upload new chunks
-        while std::time::Instant::now() < budget_deadline {
+        // Upload new chunks, nearest first (see AsyncWorldStream)
+        while uploads_left > 0 && std::time::Instant::now() < budget_deadline {
             let Some((pos, verts, idxs)) = self.world.stream.ready_meshes.pop() else {
                 break;
             };
+            uploads_left -= 1;
             match backend.upload_mesh(&verts, &idxs) {
                 Ok(handle) => {
-                    self.world.chunk_meshes.insert(pos, handle);
+                    if let Some(old) = self.world.chunk_meshes.insert(pos, handle) {
+                        backend.free_mesh(old);
+                    }
+                }
+                // Streaming frees the chunks that fall out of range; keep
+                // this one for a later frame rather than leave a hole.
+                Err(e @ RenderError::OutOfMemory { .. }) => {
+                    tracing::warn!("chunk {pos:?} upload deferred: {e}");
+                    self.world.stream.ready_meshes.push((pos, verts, idxs));
+                    break;
                 }
                 Err(e) => error!("chunk {pos:?} upload failed: {e}"),
             }
         }
 
-        // Boundary remesh — shares the same deadline
+        // Boundary remesh — shares the same deadline. Meshed across the job
+        // pool a batch at a time, the deadline checked between batches.
         self.world.remesh_scratch.clear();
         self.world
             .remesh_scratch
             .extend(self.world.stream.remesh_queue.drain(..));
+        let pending: Vec<ChunkPos> = self.world.remesh_scratch.iter().copied().collect();
+        let batch = self.jobs.threads() * 2;
         let mut deferred = Vec::new();
-        for &pos in &self.world.remesh_scratch {
-            if std::time::Instant::now() >= budget_deadline {
-                deferred.push(pos);
-                continue;
-            }
-            let neighbors = self.world.stream.neighbors(pos);
-            if neighbors.iter().all(Option::is_none) {
+        for positions in pending.chunks(batch) {
+            if uploads_left == 0 || std::time::Instant::now() >= budget_deadline {
+                deferred.extend_from_slice(positions);
                 continue;
             }
-            let chunk = match self.world.stream.chunks().get(&pos) {
-                Some(c) => c,
-                None => continue,
-            };
-            let (verts, idxs) = mesh_chunk(chunk, neighbors, &self.world.face_textures);
-            if let Some(old) = self.world.chunk_meshes.remove(&pos) {
-                backend.free_mesh(old);
-            }
-            if !verts.is_empty() {
-                match backend.upload_mesh(&verts, &idxs) {
-                    Ok(handle) => {
-                        self.world.chunk_meshes.insert(pos, handle);
-                        self.world.stream.mark_remeshed(pos);
+            uploads_left = uploads_left.saturating_sub(positions.len());
+            let meshes = self
+                .world
+                .stream
+                .remesh(positions, &self.world.face_textures);
+            for (pos, verts, idxs) in meshes {
+                if let Some(old) = self.world.chunk_meshes.remove(&pos) {
+                    backend.free_mesh(old);
+                }
+                if !verts.is_empty() {
+                    match backend.upload_mesh(&verts, &idxs) {
+                        Ok(handle) => {
+                            self.world.chunk_meshes.insert(pos, handle);
+                            self.world.stream.mark_remeshed(pos);
+                        }
+                        Err(e) => error!("remesh {pos:?} failed: {e}"),
                     }
-                    Err(e) => error!("remesh {pos:?} failed: {e}"),
                 }
             }
         }
         self.world.stream.remesh_queue.extend(deferred);
+        backend.record_stream_counts(self.world.stream.stream_counts());
+
+        // --- Plugins ---
+        self.with_plugins(Some(&mut *backend), |plugins, ctx| plugins.update(ctx, dt));
 
         // --- Draw ---
         backend.set_camera(self.camera);
 
         let aspect = self.render_size.width as f32 / self.render_size.height as f32;
-        let view_proj =
-            self.camera.projection_matrix(aspect) * self.camera.view_matrix_no_translation();
-        let frustum = Frustum::from_view_proj(&view_proj);
+        let convention = backend.depth_convention();
+        let view_proj = clip::camera_view_proj(&self.camera, aspect, convention);
+        let frustum = Frustum::from_view_proj(&view_proj, convention);
         let chunk_world_size = CHUNK_SIZE as f32 * VOXEL_SIZE;
         let cam_pos = self.camera.position; // snapshot once
 
@@ -557,16 +822,31 @@ impl App {
                     tex_index: 0,
                     _pad: [0; 3],
                 };
-                backend.draw_mesh(handle, push);
+                backend.draw_chunk_mesh(handle, push);
             }
         }
 
-        // Autosave
+        // Autosave: every autosave_interval_s, or sooner once
+        // autosave_dirty_chunks chunks are edited. The chunks are written on
+        // the job pool; this only snapshots them.
         let interval = self.cfg.world.autosave_interval_s;
-        if interval > 0 && self.autosave_timer.elapsed().as_secs() >= interval {
-            self.world.stream.flush_dirty();
+        let dirty_limit = self.cfg.world.autosave_dirty_chunks;
+        let dirty = self.world.stream.dirty_count();
+        let due = interval > 0 && self.autosave_timer.elapsed().as_secs() >= interval;
+        if due || (dirty_limit > 0 && dirty >= dirty_limit) {
+            if dirty > 0 {
+                self.world.stream.flush_dirty();
+                tracing::info!("autosave queued {dirty} chunks");
+            }
             self.autosave_timer = std::time::Instant::now();
-            tracing::info!("autosave complete");
         }
     }
 }
+
+fn chunk_compression(cfg: SaveCompressionCfg) -> ChunkCompression {
+    match cfg {
+        SaveCompressionCfg::None => ChunkCompression::None,
+        SaveCompressionCfg::Lz4 => ChunkCompression::Lz4,
+        SaveCompressionCfg::Zstd => ChunkCompression::Zstd,
+    }
+}
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! glTF 2.0 (.gltf with its buffers/images, or .glb) into a Model.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use cubic_math::{Mat4, Vec3};
+use cubic_render::Vertex;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use tracing::warn;
+
+use crate::{Model, ModelImage, ModelInstance, ModelMaterial, ModelMesh};
+
+/// Load a glTF 2.0 file's default scene (or its first, without one).
+/// Only triangle-list primitives are read; others are skipped with a
+/// warning.
+pub fn load_gltf(path: &Path) -> Result<Model> {
+    let (doc, buffers, images) =
+        gltf::import(path).with_context(|| format!("load_gltf {:?}", path))?;
+
+    let images = images
+        .into_iter()
+        .enumerate()
+        .map(|(i, data)| convert_image(data).with_context(|| format!("{path:?}: image {i}")))
+        .collect::<Result<Vec<_>>>()?;
+
+    let materials = doc
+        .materials()
+        .map(|m| {
+            let pbr = m.pbr_metallic_roughness();
+            let normal = m.normal_texture();
+            let occlusion = m.occlusion_texture();
+            ModelMaterial {
+                base_color: pbr.base_color_factor(),
+                base_color_image: pbr
+                    .base_color_texture()
+                    .map(|t| t.texture().source().index()),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                metallic_roughness_image: pbr
+                    .metallic_roughness_texture()
+                    .map(|t| t.texture().source().index()),
+                normal_image: normal.as_ref().map(|t| t.texture().source().index()),
+                normal_scale: normal.as_ref().map_or(1.0, |t| t.scale()),
+                occlusion_image: occlusion.as_ref().map(|t| t.texture().source().index()),
+                occlusion_strength: occlusion.as_ref().map_or(1.0, |t| t.strength()),
+                emissive: m.emissive_factor(),
+                emissive_image: m.emissive_texture().map(|t| t.texture().source().index()),
+            }
+        })
+        .collect();
+
+    // glTF mesh index -> this model's meshes (one per primitive).
+    let mut meshes = Vec::new();
+    let mut primitives: Vec<Vec<usize>> = Vec::new();
+    for mesh in doc.meshes() {
+        let mut ids = Vec::new();
+        for prim in mesh.primitives() {
+            if prim.mode() != Mode::Triangles {
+                warn!(
+                    "{path:?}: mesh {} primitive {} is {:?}, not triangles; skipped",
+                    mesh.index(),
+                    prim.index(),
+                    prim.mode()
+                );
+                continue;
+            }
+            let reader = prim.reader(|b| buffers.get(b.index()).map(|d| &d.0[..]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<[f32; 3]> = positions.collect();
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(i) => i.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if let Some(&bad) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+                bail!("{path:?}: mesh {} index {bad} out of range", mesh.index());
+            }
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(n) => n.collect(),
+                None => smooth_normals(&positions, &indices),
+            };
+            let uvs: Vec<[f32; 2]> = reader
+                .read_tex_coords(0)
+                .map(|t| t.into_f32().collect())
+                .unwrap_or_default();
+            let colors: Vec<[f32; 3]> = reader
+                .read_colors(0)
+                .map(|c| c.into_rgb_f32().collect())
+                .unwrap_or_default();
+
+            let vertices = positions
+                .iter()
+                .enumerate()
+                .map(|(i, &pos)| Vertex {
+                    pos,
+                    color: colors.get(i).copied().unwrap_or([1.0; 3]),
+                    uv: uvs.get(i).copied().unwrap_or([0.0; 2]),
+                    normal: normals.get(i).copied().unwrap_or([0.0, 1.0, 0.0]),
+                    tex_index: 0,
+                })
+                .collect();
+            ids.push(meshes.len());
+            meshes.push(ModelMesh {
+                vertices,
+                indices,
+                material: prim.material().index(),
+            });
+        }
+        primitives.push(ids);
+    }
+
+    let mut instances = Vec::new();
+    if let Some(scene) = doc.default_scene().or_else(|| doc.scenes().next()) {
+        for node in scene.nodes() {
+            collect_instances(&node, Mat4::IDENTITY, &primitives, &mut instances);
+        }
+    }
+
+    Ok(Model {
+        meshes,
+        materials,
+        images,
+        instances,
+    })
+}
+
+fn collect_instances(
+    node: &gltf::Node,
+    parent: Mat4,
+    primitives: &[Vec<usize>],
+    out: &mut Vec<ModelInstance>,
+) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        out.extend(
+            primitives[mesh.index()]
+                .iter()
+                .map(|&mesh| ModelInstance { mesh, transform }),
+        );
+    }
+    for child in node.children() {
+        collect_instances(&child, transform, primitives, out);
+    }
+}
+
+/// Area-weighted vertex normals, for primitives that ship without any.
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vec3::ZERO; positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(positions[tri[k] as usize]));
+        let n = (b - a).cross(c - a);
+        for &i in tri {
+            sums[i as usize] += n;
+        }
+    }
+    sums.into_iter()
+        .map(|n| n.try_normalize().unwrap_or(Vec3::Y).to_array())
+        .collect()
+}
+
+/// Decoded glTF image to RGBA8. 16-bit channels keep their high byte;
+/// float images aren't base colour material and are refused.
+fn convert_image(data: gltf::image::Data) -> Result<ModelImage> {
+    let (channels, bytes_per_channel) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        other => bail!("unsupported image format {other:?}"),
+    };
+    let texel_bytes = channels * bytes_per_channel;
+    let texels = data.width as usize * data.height as usize;
+    if data.pixels.len() < texels * texel_bytes {
+        bail!("image data shorter than {}x{}", data.width, data.height);
+    }
+    let mut pixels = Vec::with_capacity(texels * 4);
+    for texel in data.pixels.chunks_exact(texel_bytes).take(texels) {
+        // gltf hands 16-bit channels over as native-endian u16s; on the
+        // little-endian targets cubic builds for, the high byte is second.
+        let channel = |c: usize| texel[c * bytes_per_channel + bytes_per_channel - 1];
+        let rgba = match channels {
+            1 => [channel(0), channel(0), channel(0), 255],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), 255],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        };
+        pixels.extend_from_slice(&rgba);
+    }
+    Ok(ModelImage {
+        pixels,
+        width: data.width,
+        height: data.height,
+    })
+}
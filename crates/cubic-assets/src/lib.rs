@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Model loading: glTF 2.0 files decoded into CPU-side meshes, materials,
+//! images and node transforms built from the renderer's own types
+//! (cubic_render::Vertex, PushData), ready to upload. Plus AssetLoader, which
+//! does that decoding (and image decoding) as jobs on a cubic_jobs::JobPool
+//! rather than on the caller's thread, and AssetWatcher, the filesystem
+//! watch behind asset hot-reload.
+//!
+//! Nothing here talks to a GPU. Uploading goes through callbacks with the
+//! shape of the renderers' upload_texture/upload_mesh, so the same Model
+//! works with either backend:
+//!
+//! ```ignore
+//! let model = cubic_assets::load_gltf(path)?;
+//! let textures = model.upload_textures(|px, w, h| renderer.upload_texture(px, w, h))?;
+//! let uploaded = model.upload_meshes(&textures, |v, i| renderer.upload_mesh(v, i))?;
+//! for (mesh, push) in uploaded.draws(model_matrix) {
+//!     renderer.draw_mesh(mesh, push);
+//! }
+//! ```
+//!
+//! glTF materials map onto what a draw can express: the base colour factor
+//! becomes the draw's tint and the base colour texture its bindless
+//! tex_index. The rest of a metallic-roughness material (factors, normal,
+//! metallic-roughness, occlusion and emissive textures) is read into
+//! ModelMaterial too, for a renderer with PBR materials to build them from
+//! (ModelMaterial::textures resolves the images to bindless indices, and
+//! each UploadedPart names its material). Skins and animations are not
+//! read.
+
+mod import;
+mod load;
+mod watch;
+
+use anyhow::Result;
+use cubic_math::{Mat3, Mat4, Vec3};
+use cubic_render::{MeshHandle, PushData, Vertex};
+
+pub use import::load_gltf;
+pub use load::{load_image, AssetLoader, Decoded, LoadId, Loaded};
+pub use watch::{AssetChange, AssetKind, AssetWatcher};
+
+/// A loaded model: everything a glTF scene's meshes need, in CPU memory.
+#[derive(Clone, Default)]
+pub struct Model {
+    /// One per glTF primitive (a glTF mesh with several materials is
+    /// several of these).
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    pub images: Vec<ModelImage>,
+    /// The scene flattened: each node's meshes with the node's transform
+    /// (parents' included), in model space.
+    pub instances: Vec<ModelInstance>,
+}
+
+#[derive(Clone)]
+pub struct ModelMesh {
+    /// `tex_index` is 0 until upload_meshes/merged fill in the material's
+    /// texture.
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into `Model::materials`; None is glTF's default material.
+    pub material: Option<usize>,
+}
+
+/// A glTF metallic-roughness material. Image fields index
+/// `Model::images`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelMaterial {
+    /// Linear RGBA base colour factor.
+    pub base_color: [f32; 4],
+    pub base_color_image: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in g, metalness in b, scaling the factors.
+    pub metallic_roughness_image: Option<usize>,
+    /// Tangent-space normals.
+    pub normal_image: Option<usize>,
+    pub normal_scale: f32,
+    /// Occlusion in r.
+    pub occlusion_image: Option<usize>,
+    pub occlusion_strength: f32,
+    /// Linear RGB emissive factor.
+    pub emissive: [f32; 3],
+    pub emissive_image: Option<usize>,
+}
+
+impl Default for ModelMaterial {
+    /// glTF's default material.
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            base_color_image: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_image: None,
+            normal_image: None,
+            normal_scale: 1.0,
+            occlusion_image: None,
+            occlusion_strength: 1.0,
+            emissive: [0.0; 3],
+            emissive_image: None,
+        }
+    }
+}
+
+/// A ModelMaterial's images as bindless indices (see
+/// `ModelMaterial::textures`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaterialTextures {
+    pub base_color: Option<u32>,
+    pub metallic_roughness: Option<u32>,
+    pub normal: Option<u32>,
+    pub occlusion: Option<u32>,
+    pub emissive: Option<u32>,
+}
+
+impl ModelMaterial {
+    /// This material's images as bindless indices, given upload_textures'
+    /// result; None where it has no image.
+    pub fn textures(&self, textures: &[u32]) -> MaterialTextures {
+        let index = |image: Option<usize>| image.and_then(|i| textures.get(i)).copied();
+        MaterialTextures {
+            base_color: index(self.base_color_image),
+            metallic_roughness: index(self.metallic_roughness_image),
+            normal: index(self.normal_image),
+            occlusion: index(self.occlusion_image),
+            emissive: index(self.emissive_image),
+        }
+    }
+}
+
+/// RGBA8 pixels, sRGB like every upload_texture input.
+#[derive(Clone)]
+pub struct ModelImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ModelInstance {
+    /// Index into `Model::meshes`.
+    pub mesh: usize,
+    pub transform: Mat4,
+}
+
+/// A model's meshes on the GPU, ready to draw.
+#[derive(Clone, Debug, Default)]
+pub struct UploadedModel {
+    pub parts: Vec<UploadedPart>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UploadedPart {
+    pub mesh: MeshHandle,
+    pub transform: Mat4,
+    pub tint: [f32; 4],
+    pub tex_index: u32,
+    /// Index into `Model::materials`; None is glTF's default material.
+    pub material: Option<usize>,
+}
+
+impl UploadedModel {
+    /// Every part's draw for a model placed at `model` (camera-relative,
+    /// like any other draw's model matrix).
+    pub fn draws(&self, model: Mat4) -> impl Iterator<Item = (MeshHandle, PushData)> + '_ {
+        self.parts.iter().map(move |p| {
+            (
+                p.mesh,
+                PushData {
+                    model: (model * p.transform).to_cols_array_2d(),
+                    tint: p.tint,
+                    tex_index: p.tex_index,
+                    _pad: [0; 3],
+                },
+            )
+        })
+    }
+}
+
+impl Model {
+    /// Upload every image with `upload_texture` (an upload_texture of
+    /// either renderer), returning bindless indices in `images` order.
+    pub fn upload_textures(
+        &self,
+        mut upload_texture: impl FnMut(&[u8], u32, u32) -> Result<u32>,
+    ) -> Result<Vec<u32>> {
+        self.images
+            .iter()
+            .map(|img| upload_texture(&img.pixels, img.width, img.height))
+            .collect()
+    }
+
+    fn material(&self, mesh: &ModelMesh) -> ModelMaterial {
+        mesh.material
+            .and_then(|m| self.materials.get(m))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Bindless index of `material`'s texture given upload_textures'
+    /// result; 0 (the renderer's white dummy) without one.
+    fn tex_index(material: &ModelMaterial, textures: &[u32]) -> u32 {
+        material.textures(textures).base_color.unwrap_or(0)
+    }
+
+    /// Upload each mesh once with `upload_mesh`, texture indices from
+    /// upload_textures baked into its vertices; instances sharing a mesh
+    /// share the handle.
+    pub fn upload_meshes(
+        &self,
+        textures: &[u32],
+        mut upload_mesh: impl FnMut(&[Vertex], &[u32]) -> Result<MeshHandle>,
+    ) -> Result<UploadedModel> {
+        let mut handles = Vec::with_capacity(self.meshes.len());
+        for mesh in &self.meshes {
+            let tex_index = Self::tex_index(&self.material(mesh), textures);
+            let vertices: Vec<Vertex> = mesh
+                .vertices
+                .iter()
+                .map(|v| Vertex { tex_index, ..*v })
+                .collect();
+            handles.push(upload_mesh(&vertices, &mesh.indices)?);
+        }
+        let parts = self
+            .instances
+            .iter()
+            .map(|inst| {
+                let mesh = &self.meshes[inst.mesh];
+                let material = self.material(mesh);
+                UploadedPart {
+                    mesh: handles[inst.mesh],
+                    transform: inst.transform,
+                    tint: material.base_color,
+                    tex_index: Self::tex_index(&material, textures),
+                    material: mesh.material,
+                }
+            })
+            .collect();
+        Ok(UploadedModel { parts })
+    }
+
+    /// The whole scene as one mesh, node transforms applied and base
+    /// colours folded into vertex colours: for callers that draw a model
+    /// as a single handle (alpha is dropped, there's no per-vertex alpha).
+    pub fn merged(&self, textures: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for inst in &self.instances {
+            let mesh = &self.meshes[inst.mesh];
+            let material = self.material(mesh);
+            let tex_index = Self::tex_index(&material, textures);
+            let [r, g, b, _] = material.base_color;
+            let normal_matrix = Mat3::from_mat4(inst.transform).inverse().transpose();
+            let base = vertices.len() as u32;
+            vertices.extend(mesh.vertices.iter().map(|v| {
+                Vertex {
+                    pos: inst
+                        .transform
+                        .transform_point3(Vec3::from(v.pos))
+                        .to_array(),
+                    color: [v.color[0] * r, v.color[1] * g, v.color[2] * b],
+                    uv: v.uv,
+                    normal: (normal_matrix * Vec3::from(v.normal))
+                        .normalize_or_zero()
+                        .to_array(),
+                    tex_index,
+                }
+            }));
+            indices.extend(mesh.indices.iter().map(|&i| base + i));
+        }
+        (vertices, indices)
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Background asset decoding: jobs on the engine's JobPool that read and
+//! decode files (images, glTF models, or whatever a caller's decode
+//! function understands) so the thread running the frame loop only ever
+//! sees finished CPU-side data. Uploading stays with the caller, who owns
+//! the renderer; see upload_texture_async for an upload that doesn't wait
+//! either.
+
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use cubic_jobs::{JobPool, Priority};
+use cubic_render::Vertex;
+use tracing::error;
+
+use crate::{load_gltf, Model, ModelImage};
+
+/// Identifies one submitted load; handed back with its result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LoadId(pub u64);
+
+/// What a worker produced.
+pub enum Decoded {
+    Image(ModelImage),
+    Model(Model),
+    /// A single ready-to-upload mesh, e.g. from a format decoded outside
+    /// this crate.
+    Mesh(Vec<Vertex>, Vec<u32>),
+}
+
+pub struct Loaded {
+    pub id: LoadId,
+    pub path: PathBuf,
+    pub result: Result<Decoded>,
+}
+
+/// Decode any image format the `image` crate was built with into RGBA8.
+pub fn load_image(path: &Path) -> Result<ModelImage> {
+    let rgba = image::open(path)
+        .with_context(|| format!("load_image {path:?}"))?
+        .to_rgba8();
+    let (width, height) = rgba.dimensions();
+    Ok(ModelImage {
+        pixels: rgba.into_raw(),
+        width,
+        height,
+    })
+}
+
+/// Decode jobs plus the queue of finished loads. Jobs start in submission
+/// order across the pool's workers, so completions can arrive out of
+/// order. Loads still running when the loader is dropped finish into the
+/// void.
+pub struct AssetLoader {
+    jobs: Arc<JobPool>,
+    done_tx: Sender<Loaded>,
+    done: Receiver<Loaded>,
+    next_id: u64,
+    in_flight: usize,
+    // Completions wait() received on the way to the ones it wanted.
+    early: Vec<Loaded>,
+}
+
+impl AssetLoader {
+    /// Decode on `jobs`.
+    pub fn new(jobs: Arc<JobPool>) -> Self {
+        let (done_tx, done) = channel();
+        Self {
+            jobs,
+            done_tx,
+            done,
+            next_id: 1,
+            in_flight: 0,
+            early: Vec::new(),
+        }
+    }
+
+    /// Queue `decode(path)` on the pool. High priority: a decode is
+    /// usually something already on screen waiting for its data, and
+    /// shouldn't queue behind a whole world's chunk generation.
+    pub fn submit(
+        &mut self,
+        path: &Path,
+        decode: impl FnOnce(&Path) -> Result<Decoded> + Send + 'static,
+    ) -> LoadId {
+        let id = LoadId(self.next_id);
+        self.next_id += 1;
+        let path = path.to_owned();
+        let done_tx = self.done_tx.clone();
+        self.jobs.spawn(Priority::High, "asset decode", move || {
+            // A panicking decoder (a malformed file tripping a decoder
+            // bug) fails its load rather than never completing it.
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| decode(&path)))
+                .unwrap_or_else(|_| Err(anyhow!("decoder panicked")));
+            // The loader may be gone (world reload) by now.
+            let _ = done_tx.send(Loaded { id, path, result });
+        });
+        self.in_flight += 1;
+        id
+    }
+
+    /// Queue an image decode (load_image).
+    pub fn load_image(&mut self, path: &Path) -> LoadId {
+        self.submit(path, |p| load_image(p).map(Decoded::Image))
+    }
+
+    /// Queue a glTF import (load_gltf).
+    pub fn load_model(&mut self, path: &Path) -> LoadId {
+        self.submit(path, |p| load_gltf(p).map(Decoded::Model))
+    }
+
+    /// Loads submitted and not yet returned by poll or wait.
+    pub fn pending(&self) -> usize {
+        self.in_flight + self.early.len()
+    }
+
+    /// Every load finished since the last call. Never blocks.
+    pub fn poll(&mut self) -> Vec<Loaded> {
+        let mut out = std::mem::take(&mut self.early);
+        for loaded in self.done.try_iter() {
+            self.in_flight -= 1;
+            out.push(loaded);
+        }
+        out
+    }
+
+    /// Block until every load in `ids` has finished and return those, in
+    /// `ids` order; others finishing meanwhile are kept for poll. For
+    /// loading screens, where waiting is the point but decoding in
+    /// parallel still pays.
+    pub fn wait(&mut self, ids: &[LoadId]) -> Vec<Loaded> {
+        let mut found: HashMap<LoadId, Loaded> = HashMap::new();
+        let mut i = 0;
+        while i < self.early.len() {
+            if ids.contains(&self.early[i].id) {
+                let loaded = self.early.swap_remove(i);
+                found.insert(loaded.id, loaded);
+            } else {
+                i += 1;
+            }
+        }
+        while found.len() < ids.len() {
+            let Ok(loaded) = self.done.recv() else {
+                error!(
+                    "asset loader: workers gone with {} loads waited on",
+                    ids.len()
+                );
+                break;
+            };
+            self.in_flight -= 1;
+            if ids.contains(&loaded.id) {
+                found.insert(loaded.id, loaded);
+            } else {
+                self.early.push(loaded);
+            }
+        }
+        ids.iter().filter_map(|id| found.remove(id)).collect()
+    }
+}
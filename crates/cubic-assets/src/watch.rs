@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Asset hot-reload's half that knows nothing about GPUs: a recursive
+//! filesystem watch over a content directory, turning notify's event stream
+//! into "these files changed" once the writes settle. What a change means
+//! (re-decode and replace a texture, re-upload a mesh, re-read a config) is
+//! up to whoever owns those resources.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::warn;
+
+/// How long a file has to go without further events before it's reported.
+/// Editors and exporters write in several steps (truncate, write, rename
+/// over); reporting the first of them would reload a half-written file.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// What a changed file is, going by its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    /// .png, .jpg, .jpeg
+    Texture,
+    /// .obj, .gltf, .glb
+    Model,
+    /// .toml
+    Config,
+    /// GLSL sources and .spv (the renderer has its own shader reload).
+    Shader,
+    Other,
+}
+
+impl AssetKind {
+    pub fn of(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("png" | "jpg" | "jpeg") => Self::Texture,
+            Some("obj" | "gltf" | "glb") => Self::Model,
+            Some("toml") => Self::Config,
+            Some("vert" | "frag" | "comp" | "glsl" | "spv") => Self::Shader,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetChange {
+    /// As notify reports it: under the watched root as given to new().
+    pub path: PathBuf,
+    pub kind: AssetKind,
+}
+
+/// Recursive watch over a content directory. notify delivers events on its
+/// own thread; they queue up until poll(), so nothing here runs unless the
+/// owner asks.
+pub struct AssetWatcher {
+    root: PathBuf,
+    // Kept alive for the watch; dropping it stops the events.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    // Changed paths waiting out SETTLE, with their latest event's time.
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl AssetWatcher {
+    /// Watch everything under `root`. Fails if it doesn't exist or the
+    /// platform watch can't be set up (e.g. out of inotify watches).
+    pub fn new(root: &Path) -> Result<Self> {
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away with the AssetWatcher itself.
+            let _ = tx.send(event);
+        })
+        .context("create asset watcher")?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("watch {root:?}"))?;
+        Ok(Self {
+            root: root.to_owned(),
+            _watcher: watcher,
+            events,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Files that were created, modified or renamed into place and have
+    /// since been quiet for SETTLE, each once however many events it got.
+    /// Removals aren't reported: whatever was loaded from a deleted file
+    /// stays as it was. Cheap when nothing happened; call once a frame.
+    pub fn poll(&mut self) -> Vec<AssetChange> {
+        let now = Instant::now();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(e) => e,
+                Err(e) => {
+                    warn!("asset watcher {:?}: {e}", self.root);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                self.pending.insert(path, now);
+            }
+        }
+
+        let mut settled = Vec::new();
+        self.pending.retain(|path, last| {
+            if now.duration_since(*last) < SETTLE {
+                return true;
+            }
+            // A rename's source half, or a file gone again since.
+            if path.is_file() {
+                settled.push(AssetChange {
+                    kind: AssetKind::of(path),
+                    path: path.clone(),
+                });
+            }
+            false
+        });
+        settled
+    }
+}
@@ -1,10 +1,88 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-pub fn init_tracing() {
+use std::path::PathBuf;
+
+/// CPU profiler export, alongside the log output. Both consume the same
+/// spans: the renderers' per-frame stages (frame, acquire, record, submit,
+/// present, pace, recreate), cubic-jobs' jobs, and anything else at debug
+/// level or above.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Profiler {
+    #[default]
+    None,
+    /// Chrome trace JSON, for chrome://tracing or ui.perfetto.dev. Written
+    /// as the app runs, complete once the TracingGuard drops.
+    Chrome(PathBuf),
+    /// Streamed live to a connected Tracy profiler.
+    Tracy,
+}
+
+/// Keeps the profiler export alive; hold it for the life of the app.
+#[must_use = "dropping the guard stops the profiler export"]
+pub struct TracingGuard {
+    #[cfg(feature = "chrome")]
+    _chrome: Option<tracing_chrome::FlushGuard>,
+}
+
+pub fn init_tracing(profiler: &Profiler) -> TracingGuard {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::prelude::*;
     use tracing_subscriber::{fmt, EnvFilter};
-    let _ = fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_target(false)
-        .compact()
-        .try_init();
+
+    let unavailable = match profiler {
+        Profiler::Chrome(_) if !cfg!(feature = "chrome") => Some("chrome"),
+        Profiler::Tracy if !cfg!(feature = "tracy") => Some("tracy"),
+        _ => None,
+    };
+
+    // Profiler layers get every debug span whatever RUST_LOG says; only
+    // the log output follows it.
+    #[cfg(feature = "chrome")]
+    let (chrome, chrome_flush) = match profiler {
+        Profiler::Chrome(path) => {
+            let (layer, flush) = tracing_chrome::ChromeLayerBuilder::new()
+                .file(path)
+                .include_args(true)
+                .build();
+            (Some(layer.with_filter(LevelFilter::DEBUG)), Some(flush))
+        }
+        _ => (None, None),
+    };
+    #[cfg(not(feature = "chrome"))]
+    let chrome: Option<tracing_subscriber::layer::Identity> = None;
+
+    #[cfg(feature = "tracy")]
+    let tracy = (*profiler == Profiler::Tracy)
+        .then(|| tracing_tracy::TracyLayer::default().with_filter(LevelFilter::DEBUG));
+    #[cfg(not(feature = "tracy"))]
+    let tracy: Option<tracing_subscriber::layer::Identity> = None;
+
+    let installed = tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_target(false)
+                .compact()
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .with(chrome)
+        .with(tracy)
+        .try_init()
+        .is_ok();
+    if installed {
+        match (unavailable, profiler) {
+            (Some(name), _) => tracing::warn!(
+                "profiler {name:?} requested, but this build doesn't include it (cubic-core feature \"{name}\")"
+            ),
+            (None, Profiler::Chrome(path)) => {
+                tracing::info!("profiler: writing Chrome trace to {}", path.display())
+            }
+            (None, Profiler::Tracy) => tracing::info!("profiler: Tracy enabled"),
+            (None, Profiler::None) => {}
+        }
+    }
+
+    TracingGuard {
+        #[cfg(feature = "chrome")]
+        _chrome: chrome_flush,
+    }
 }
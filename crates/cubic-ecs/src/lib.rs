@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! A small entity-component store: generational entity ids, one sparse set
+//! per component type, and the transform hierarchy (transform.rs) that
+//! turns local Transform + Parent into the world-space matrices the
+//! renderer draws with.
+//!
+//! Deliberately minimal — no archetypes, no scheduler, no multi-component
+//! query joins. Systems are plain functions over `&mut World` that iterate
+//! one component with `query` and look the rest up with `get`, the way
+//! propagate_transforms does. That's enough for a voxel engine's handful of
+//! entities per frame, and keeps the facade small enough to move onto
+//! hecs/bevy_ecs later without touching callers.
+
+mod transform;
+
+pub use transform::{propagate_transforms, GlobalTransform, Parent, Transform};
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// An entity id. The generation makes ids of despawned entities stale
+/// rather than silently aliasing whatever reuses their slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Dense component storage for one type, indexed by entity slot.
+struct Store<T> {
+    dense: Vec<T>,
+    owners: Vec<Entity>,
+    // Entity index -> position in `dense`.
+    sparse: Vec<Option<u32>>,
+}
+
+impl<T> Store<T> {
+    fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            owners: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    fn slot(&self, e: Entity) -> Option<usize> {
+        let slot = (*self.sparse.get(e.index as usize)?)? as usize;
+        (self.owners[slot] == e).then_some(slot)
+    }
+
+    fn insert(&mut self, e: Entity, value: T) -> Option<T> {
+        if let Some(slot) = self.slot(e) {
+            return Some(std::mem::replace(&mut self.dense[slot], value));
+        }
+        let i = e.index as usize;
+        if self.sparse.len() <= i {
+            self.sparse.resize(i + 1, None);
+        }
+        self.sparse[i] = Some(self.dense.len() as u32);
+        self.dense.push(value);
+        self.owners.push(e);
+        None
+    }
+
+    fn remove(&mut self, e: Entity) -> Option<T> {
+        let slot = self.slot(e)?;
+        self.sparse[e.index as usize] = None;
+        self.owners.swap_remove(slot);
+        let value = self.dense.swap_remove(slot);
+        if let Some(moved) = self.owners.get(slot) {
+            self.sparse[moved.index as usize] = Some(slot as u32);
+        }
+        Some(value)
+    }
+}
+
+/// Type-erased Store, so World can drop an entity's components without
+/// knowing their types.
+trait AnyStore {
+    fn remove_entity(&mut self, e: Entity);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyStore for Store<T> {
+    fn remove_entity(&mut self, e: Entity) {
+        self.remove(e);
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+        self.owners.clear();
+        self.sparse.clear();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Entities and their components.
+#[derive(Default)]
+pub struct World {
+    // Current generation of each slot; odd = alive, even = free. Starting
+    // at 0 (free) means a default Entity never matches a live one.
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    stores: HashMap<TypeId, Box<dyn AnyStore>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                (self.generations.len() - 1) as u32
+            }
+        };
+        let generation = &mut self.generations[index as usize];
+        *generation = generation.wrapping_add(1);
+        Entity {
+            index,
+            generation: *generation,
+        }
+    }
+
+    /// Remove `e` and all its components. False if it was already gone.
+    /// Children parented to it are left in place and become roots (see
+    /// propagate_transforms).
+    pub fn despawn(&mut self, e: Entity) -> bool {
+        if !self.is_alive(e) {
+            return false;
+        }
+        for store in self.stores.values_mut() {
+            store.remove_entity(e);
+        }
+        let generation = &mut self.generations[e.index as usize];
+        *generation = generation.wrapping_add(1);
+        self.free.push(e.index);
+        true
+    }
+
+    pub fn is_alive(&self, e: Entity) -> bool {
+        self.generations.get(e.index as usize) == Some(&e.generation) && e.generation % 2 == 1
+    }
+
+    /// Number of live entities.
+    pub fn len(&self) -> usize {
+        self.generations.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Despawn everything. Ids handed out before stay stale.
+    pub fn clear(&mut self) {
+        for store in self.stores.values_mut() {
+            store.clear();
+        }
+        for (index, generation) in self.generations.iter_mut().enumerate() {
+            if *generation % 2 == 1 {
+                *generation = generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+    }
+
+    /// Attach `value` to `e`, returning the component it replaced. Does
+    /// nothing (and hands `value` back) if `e` isn't alive.
+    pub fn insert<T: 'static>(&mut self, e: Entity, value: T) -> Option<T> {
+        if !self.is_alive(e) {
+            return Some(value);
+        }
+        self.store_mut::<T>().insert(e, value)
+    }
+
+    pub fn remove<T: 'static>(&mut self, e: Entity) -> Option<T> {
+        self.stores
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Store<T>>()?
+            .remove(e)
+    }
+
+    pub fn get<T: 'static>(&self, e: Entity) -> Option<&T> {
+        let store = self.store::<T>()?;
+        store.slot(e).map(|slot| &store.dense[slot])
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, e: Entity) -> Option<&mut T> {
+        let store = self
+            .stores
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Store<T>>()?;
+        store.slot(e).map(|slot| &mut store.dense[slot])
+    }
+
+    /// Every entity with a `T`, in storage order (not spawn order).
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.store::<T>()
+            .into_iter()
+            .flat_map(|s| s.owners.iter().copied().zip(s.dense.iter()))
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.stores
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut::<Store<T>>())
+            .into_iter()
+            .flat_map(|s| s.owners.iter().copied().zip(s.dense.iter_mut()))
+    }
+
+    fn store<T: 'static>(&self) -> Option<&Store<T>> {
+        self.stores
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<Store<T>>()
+    }
+
+    fn store_mut<T: 'static>(&mut self) -> &mut Store<T> {
+        self.stores
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Store::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<Store<T>>()
+            .expect("store keyed by its own TypeId")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despawned_ids_go_stale() {
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, 1u32);
+        assert!(world.despawn(a));
+        let b = world.spawn();
+        assert_ne!(a, b);
+        assert!(!world.is_alive(a));
+        assert_eq!(world.get::<u32>(a), None);
+        assert_eq!(world.insert(a, 2u32), Some(2));
+        assert_eq!(world.get::<u32>(b), None);
+    }
+
+    #[test]
+    fn remove_keeps_other_components_reachable() {
+        let mut world = World::new();
+        let ids: Vec<_> = (0..4u32)
+            .map(|i| {
+                let e = world.spawn();
+                world.insert(e, i);
+                e
+            })
+            .collect();
+        assert_eq!(world.remove::<u32>(ids[1]), Some(1));
+        for (i, &e) in ids.iter().enumerate().filter(|&(i, _)| i != 1) {
+            assert_eq!(world.get::<u32>(e), Some(&(i as u32)));
+        }
+        assert_eq!(world.query::<u32>().count(), 3);
+    }
+
+    #[test]
+    fn clear_despawns_everything() {
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, "a");
+        world.clear();
+        assert!(world.is_empty());
+        assert!(!world.is_alive(a));
+        assert_eq!(world.query::<&str>().count(), 0);
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Transform hierarchy: each entity's local Transform, relative to its
+//! Parent if it has one, resolved once per frame into a GlobalTransform.
+//!
+//! Positions are f64 all the way down to the render list, the same as the
+//! camera's (see cubic_math::Camera::position): GlobalTransform keeps its
+//! translation in world space and relative_matrix subtracts the camera
+//! position before anything is cast to f32.
+
+use std::collections::HashMap;
+
+use cubic_math::{DVec3, Mat3, Mat4, Quat, Vec3};
+
+use crate::{Entity, World};
+
+/// Parent chains deeper than this are cut (and logged) — it only happens
+/// with a Parent cycle, which would otherwise recurse forever.
+const MAX_DEPTH: u32 = 64;
+
+/// Position, rotation and scale relative to the Parent (or the world, for
+/// a root).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: DVec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: DVec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: DVec3) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vec3) -> Self {
+        Self { scale, ..self }
+    }
+}
+
+/// Makes the entity's Transform relative to another entity's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+/// World-space result of propagate_transforms. Written by it; anything
+/// else writing one gets overwritten next frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalTransform {
+    pub translation: DVec3,
+    /// Rotation and scale combined; a general 3x3 since a rotated child of
+    /// a non-uniformly scaled parent is sheared.
+    pub linear: Mat3,
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self {
+            translation: DVec3::ZERO,
+            linear: Mat3::IDENTITY,
+        }
+    }
+}
+
+impl GlobalTransform {
+    /// `local`'s world-space transform with this one as its parent.
+    pub fn mul_transform(&self, local: &Transform) -> Self {
+        Self {
+            translation: self.translation + self.linear.as_dmat3() * local.translation,
+            linear: self.linear
+                * Mat3::from_quat(local.rotation)
+                * Mat3::from_diagonal(local.scale),
+        }
+    }
+
+    /// Model matrix with the translation taken relative to `origin` (the
+    /// camera position), for camera-relative rendering.
+    pub fn relative_matrix(&self, origin: DVec3) -> Mat4 {
+        let t = (self.translation - origin).as_vec3();
+        Mat4::from_cols(
+            self.linear.x_axis.extend(0.0),
+            self.linear.y_axis.extend(0.0),
+            self.linear.z_axis.extend(0.0),
+            t.extend(1.0),
+        )
+    }
+}
+
+/// Compute every Transform's GlobalTransform, parents before children.
+/// A Parent that's been despawned is ignored, so its orphans become roots
+/// at their local transform. Entities that have lost their Transform keep
+/// their last GlobalTransform; remove that too if it matters.
+pub fn propagate_transforms(world: &mut World) {
+    let entities: Vec<Entity> = world.query::<Transform>().map(|(e, _)| e).collect();
+    let mut resolved = HashMap::with_capacity(entities.len());
+    for e in entities {
+        resolve(world, e, &mut resolved, 0);
+    }
+    for (e, global) in resolved {
+        world.insert(e, global);
+    }
+}
+
+fn resolve(
+    world: &World,
+    e: Entity,
+    resolved: &mut HashMap<Entity, GlobalTransform>,
+    depth: u32,
+) -> GlobalTransform {
+    if let Some(global) = resolved.get(&e) {
+        return *global;
+    }
+    let parent = match world.get::<Parent>(e) {
+        Some(&Parent(p)) if world.is_alive(p) => {
+            if depth < MAX_DEPTH {
+                resolve(world, p, resolved, depth + 1)
+            } else {
+                tracing::warn!("ecs: parent chain of {e:?} deeper than {MAX_DEPTH}; cycle?");
+                GlobalTransform::default()
+            }
+        }
+        _ => GlobalTransform::default(),
+    };
+    let local = world.get::<Transform>(e).copied().unwrap_or_default();
+    let global = parent.mul_transform(&local);
+    resolved.insert(e, global);
+    global
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_follows_parent() {
+        let mut world = World::new();
+        let parent = world.spawn();
+        world.insert(
+            parent,
+            Transform::from_translation(DVec3::new(10.0, 0.0, 0.0))
+                .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        );
+        let child = world.spawn();
+        world.insert(
+            child,
+            Transform::from_translation(DVec3::new(0.0, 0.0, -1.0)),
+        );
+        world.insert(child, Parent(parent));
+
+        propagate_transforms(&mut world);
+        let global = world.get::<GlobalTransform>(child).unwrap();
+        // A quarter turn about +Y takes -Z to -X.
+        assert!((global.translation - DVec3::new(9.0, 0.0, 0.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn relative_matrix_subtracts_origin() {
+        let global = GlobalTransform {
+            translation: DVec3::new(1.0e9 + 2.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        let m = global.relative_matrix(DVec3::new(1.0e9, 0.0, 0.0));
+        assert_eq!(m.w_axis.x, 2.0);
+    }
+
+    #[test]
+    fn orphans_and_cycles_become_roots() {
+        let mut world = World::new();
+        let gone = world.spawn();
+        let orphan = world.spawn();
+        world.insert(orphan, Transform::from_translation(DVec3::ONE));
+        world.insert(orphan, Parent(gone));
+        world.despawn(gone);
+
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Transform::default());
+        world.insert(b, Transform::default());
+        world.insert(a, Parent(b));
+        world.insert(b, Parent(a));
+
+        propagate_transforms(&mut world);
+        assert_eq!(
+            world.get::<GlobalTransform>(orphan).unwrap().translation,
+            DVec3::ONE
+        );
+        assert!(world.get::<GlobalTransform>(a).is_some());
+    }
+}
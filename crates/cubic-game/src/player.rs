@@ -20,9 +20,6 @@ const DEFAULT_SPAWN_Y: f32 = 20.0;
 /// Max block-interaction distance, in metres (~12 voxels) — same ballpark
 /// as vanilla Minecraft's reach.
 const REACH: f32 = 6.0;
-/// Ray march step size, in metres — well under VOXEL_SIZE so the march
-/// can't skip over a thin voxel boundary and tunnel through a face.
-const RAY_STEP: f32 = 0.05;
 
 /// Mirrors `cubic_wasm::InputSnapshot` field-for-field — see on_tick in
 /// lib.rs, which decodes the host's get-input out-ptr buffer into this.
@@ -136,11 +133,11 @@ impl Player {
         [self.pos[0], self.pos[1] + EYE_HEIGHT as f64, self.pos[2]]
     }
 
-    /// March a ray from the eye along the look direction (yaw/pitch —
-    /// full 3D, unlike movement's horizontal-only forward vector) up to
-    /// REACH, sampling `physics::is-solid` every RAY_STEP. Always aims from
-    /// the eye regardless of `third_person`, so aiming stays consistent
-    /// with where the player is actually looking rather than the orbited
+    /// Cast a ray from the eye along the look direction (yaw/pitch — full
+    /// 3D, unlike movement's horizontal-only forward vector) up to REACH,
+    /// via the host's voxel-exact `physics::raycast`. Always aims from the
+    /// eye regardless of `third_person`, so aiming stays consistent with
+    /// where the player is actually looking rather than the orbited
     /// third-person camera's angle.
     fn raycast_target(&self) -> Option<RayHit> {
         let origin = self.eye_pos();
@@ -150,45 +147,35 @@ impl Player {
             -self.yaw.cos() * self.pitch.cos(),
         ];
 
-        let mut prev_voxel: Option<[i32; 3]> = None;
-        let mut t = 0.0f32;
-        let voxel_size = crate::VOXEL_SIZE as f64;
-        while t < REACH {
-            let p = [
-                origin[0] + dir[0] as f64 * t as f64,
-                origin[1] + dir[1] as f64 * t as f64,
-                origin[2] + dir[2] as f64 * t as f64,
-            ];
-            if physics::is_solid(p[0], p[1], p[2]) {
-                let voxel = [
-                    (p[0] / voxel_size).floor() as i32,
-                    (p[1] / voxel_size).floor() as i32,
-                    (p[2] / voxel_size).floor() as i32,
-                ];
-                let block = [
-                    voxel[0] as f64 * voxel_size,
-                    voxel[1] as f64 * voxel_size,
-                    voxel[2] as f64 * voxel_size,
-                ];
-                let place = prev_voxel
-                    .map(|pv| {
-                        [
-                            pv[0] as f64 * voxel_size,
-                            pv[1] as f64 * voxel_size,
-                            pv[2] as f64 * voxel_size,
-                        ]
-                    })
-                    .unwrap_or(block);
-                return Some(RayHit { block, place });
-            }
-            prev_voxel = Some([
-                (p[0] / voxel_size).floor() as i32,
-                (p[1] / voxel_size).floor() as i32,
-                (p[2] / voxel_size).floor() as i32,
-            ]);
-            t += RAY_STEP;
+        // Result buffer for raycast's out-ptr: [x, y, z (f64 each), nx, ny,
+        // nz (i32 each), block_id (u32)] = 3*8 + 4*4 = 40 bytes — stack
+        // memory, as for sweep-aabb in tick.
+        let mut result_buf = [0u8; 40];
+        let written = physics::raycast(
+            origin[0],
+            origin[1],
+            origin[2],
+            dir[0],
+            dir[1],
+            dir[2],
+            REACH,
+            result_buf.as_mut_ptr() as u32,
+        );
+        if written == 0 {
+            return None;
         }
-        None
+        let f = |i: usize| f64::from_le_bytes(result_buf[i..i + 8].try_into().unwrap());
+        let n = |i: usize| i32::from_le_bytes(result_buf[i..i + 4].try_into().unwrap());
+        let block = [f(0), f(8), f(16)];
+        // The voxel in front of the entered face; the hit voxel itself if
+        // the eye is already inside it.
+        let voxel_size = crate::VOXEL_SIZE as f64;
+        let place = [
+            block[0] + n(24) as f64 * voxel_size,
+            block[1] + n(28) as f64 * voxel_size,
+            block[2] + n(32) as f64 * voxel_size,
+        ];
+        Some(RayHit { block, place })
     }
 
     pub fn tick(&mut self, dt: f32, input: &InputState) {
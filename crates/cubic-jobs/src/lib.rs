@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! The engine's shared worker pool: chunk generation and boundary remesh
+//! (cubic-world), asset decoding (cubic-assets), and whatever else wants to
+//! be off the frame loop's thread.
+//!
+//! Work stealing, kept simple: each worker has its own deque that jobs
+//! spawned *from* that worker go on (popped newest-first, so a job's
+//! follow-up work runs while its data is still in cache), plus one global
+//! queue per Priority for jobs spawned from anywhere else. An idle worker
+//! looks at its own deque, then the global queues highest priority first,
+//! then steals the oldest job from another worker's deque.
+//!
+//! `scope` runs tasks that borrow the caller's stack and waits for them,
+//! like std::thread::scope but on the pool's threads. A worker waiting on
+//! a scope keeps running other jobs meanwhile, so nested scopes can't
+//! deadlock the pool; any other thread just blocks.
+//!
+//! Every job runs inside a `job` tracing span (name, priority) and ends
+//! with a trace event on the `cubic_jobs` target carrying its queue wait
+//! and run time, so `RUST_LOG=cubic_jobs=trace` shows where pool time goes.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tracing::error;
+
+/// Which global queue a job goes on. Only orders jobs waiting for a free
+/// worker; a running job is never preempted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Something is waiting on it: a scope the frame loop blocks on, an
+    /// asset the player can see is missing.
+    High,
+    Normal,
+    /// Background work nothing is waiting for.
+    Low,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+}
+
+type JobFn = Box<dyn FnOnce() + Send + 'static>;
+
+struct Job {
+    name: &'static str,
+    priority: Priority,
+    queued: Instant,
+    run: JobFn,
+}
+
+struct Shared {
+    injector: [Mutex<VecDeque<Job>>; 3],
+    locals: Vec<Mutex<VecDeque<Job>>>,
+    // Bumped under its lock on every push; a worker only sleeps if it
+    // hasn't changed since before it last looked for work, so a push
+    // between looking and sleeping can't be missed.
+    epoch: Mutex<u64>,
+    wake: Condvar,
+    shutdown: AtomicBool,
+}
+
+thread_local! {
+    // (pool, worker index) if this thread is a pool worker; the pool is
+    // identified by its Shared's address.
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+impl Shared {
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// This thread's worker index in this pool, if it is one of its workers.
+    fn current_worker(&self) -> Option<usize> {
+        WORKER
+            .get()
+            .filter(|&(pool, _)| pool == self.id())
+            .map(|(_, i)| i)
+    }
+
+    fn push(&self, job: Job) {
+        match self.current_worker() {
+            Some(i) => self.locals[i].lock().unwrap().push_back(job),
+            None => self.injector[job.priority as usize]
+                .lock()
+                .unwrap()
+                .push_back(job),
+        }
+        *self.epoch.lock().unwrap() += 1;
+        self.wake.notify_one();
+    }
+
+    fn find(&self, me: Option<usize>) -> Option<Job> {
+        if let Some(i) = me {
+            if let Some(job) = self.locals[i].lock().unwrap().pop_back() {
+                return Some(job);
+            }
+        }
+        for priority in Priority::ALL {
+            if let Some(job) = self.injector[priority as usize].lock().unwrap().pop_front() {
+                return Some(job);
+            }
+        }
+        let n = self.locals.len();
+        let start = me.map_or(0, |i| i + 1);
+        (0..n)
+            .map(|k| (start + k) % n)
+            .filter(|&victim| Some(victim) != me)
+            .find_map(|victim| self.locals[victim].lock().unwrap().pop_front())
+    }
+}
+
+fn run(job: Job) {
+    let Job {
+        name,
+        priority,
+        queued,
+        run,
+    } = job;
+    let wait = queued.elapsed();
+    let span = tracing::debug_span!("job", name, ?priority);
+    let _enter = span.enter();
+    let start = Instant::now();
+    // A panicking job fails itself, not the worker. Scoped jobs catch
+    // their own panics first to hand them back to the scope.
+    if panic::catch_unwind(AssertUnwindSafe(run)).is_err() {
+        error!("job {name:?} panicked");
+    }
+    tracing::trace!(
+        target: "cubic_jobs",
+        job = name,
+        wait_us = wait.as_micros() as u64,
+        run_us = start.elapsed().as_micros() as u64,
+        "job done"
+    );
+}
+
+fn worker_loop(shared: &Shared, index: usize) {
+    loop {
+        let seen = *shared.epoch.lock().unwrap();
+        if let Some(job) = shared.find(Some(index)) {
+            run(job);
+            continue;
+        }
+        // Queues are drained before exiting, so dropping the pool still
+        // finishes everything already spawned.
+        if shared.shutdown.load(Ordering::Acquire) {
+            return;
+        }
+        let epoch = shared.epoch.lock().unwrap();
+        let _epoch = shared
+            .wake
+            .wait_while(epoch, |e| {
+                *e == seen && !shared.shutdown.load(Ordering::Acquire)
+            })
+            .unwrap();
+    }
+}
+
+/// A fixed set of worker threads. Dropping it lets the workers finish
+/// every queued job and joins them.
+pub struct JobPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobPool {
+    /// Start `threads` workers (at least one), named `{name}-{index}`.
+    /// `on_worker_start` runs first thing on each with its index, for
+    /// per-thread setup (e.g. cubic_wasm::set_worker_id).
+    pub fn new(
+        name: &str,
+        threads: usize,
+        on_worker_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
+    ) -> Self {
+        let threads = threads.max(1);
+        let shared = Arc::new(Shared {
+            injector: Default::default(),
+            locals: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            epoch: Mutex::new(0),
+            wake: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (0..threads)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                let on_start = on_worker_start.clone();
+                std::thread::Builder::new()
+                    .name(format!("{name}-{i}"))
+                    .spawn(move || {
+                        WORKER.set(Some((shared.id(), i)));
+                        if let Some(f) = on_start {
+                            f(i);
+                        }
+                        worker_loop(&shared, i);
+                    })
+                    .expect("spawn job pool thread")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.shared.locals.len()
+    }
+
+    /// Queue `f`. `name` labels it in tracing.
+    pub fn spawn(&self, priority: Priority, name: &'static str, f: impl FnOnce() + Send + 'static) {
+        self.shared.push(Job {
+            name,
+            priority,
+            queued: Instant::now(),
+            run: Box::new(f),
+        });
+    }
+
+    /// Run `f`, which can spawn tasks borrowing anything that outlives
+    /// this call, and wait for all of them. A panic in `f` or any task is
+    /// resumed here once every task has finished.
+    pub fn scope<'env, R>(&self, f: impl FnOnce(&Scope<'_, 'env>) -> R) -> R {
+        let scope = Scope {
+            pool: self,
+            state: Arc::new(ScopeState::default()),
+            _env: PhantomData,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+        if let Some(payload) = scope.state.panic.lock().unwrap().take() {
+            panic::resume_unwind(payload);
+        }
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+}
+
+impl Drop for JobPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        *self.shared.epoch.lock().unwrap() += 1;
+        self.shared.wake.notify_all();
+        let me = std::thread::current().id();
+        for worker in self.workers.drain(..) {
+            // The last owner going away inside a job would otherwise join
+            // its own thread.
+            if worker.thread().id() != me {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    done: Condvar,
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+/// Handle for spawning borrowing tasks inside JobPool::scope.
+pub struct Scope<'pool, 'env> {
+    pool: &'pool JobPool,
+    state: Arc<ScopeState>,
+    // Invariant in 'env, like std::thread::Scope.
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'_, 'env> {
+    pub fn spawn(&self, priority: Priority, name: &'static str, f: impl FnOnce() + Send + 'env) {
+        *self.state.pending.lock().unwrap() += 1;
+        let state = Arc::clone(&self.state);
+        let task: Box<dyn FnOnce() + Send + 'env> = Box::new(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                state.panic.lock().unwrap().get_or_insert(payload);
+            }
+            let mut pending = state.pending.lock().unwrap();
+            *pending -= 1;
+            if *pending == 0 {
+                state.done.notify_all();
+            }
+        });
+        // SAFETY: only the lifetime changes. JobPool::scope doesn't return
+        // until `pending` is back to zero, which this task only does after
+        // `f` has run and been dropped, so nothing borrowed for 'env is
+        // touched after 'env ends.
+        let run = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'env>, JobFn>(task) };
+        self.pool.shared.push(Job {
+            name,
+            priority,
+            queued: Instant::now(),
+            run,
+        });
+    }
+
+    fn wait(&self) {
+        let shared = &self.pool.shared;
+        let Some(me) = shared.current_worker() else {
+            let pending = self.state.pending.lock().unwrap();
+            let _pending = self.state.done.wait_while(pending, |p| *p > 0).unwrap();
+            return;
+        };
+        // On a worker: help rather than block, or a pool whose every
+        // worker waits on a scope would have nobody left to run its tasks.
+        loop {
+            if *self.state.pending.lock().unwrap() == 0 {
+                return;
+            }
+            if let Some(job) = shared.find(Some(me)) {
+                run(job);
+                continue;
+            }
+            // Our tasks are running elsewhere; nap until one finishes or a
+            // new job might have come in.
+            let pending = self.state.pending.lock().unwrap();
+            if *pending > 0 {
+                let _ = self
+                    .state
+                    .done
+                    .wait_timeout(pending, Duration::from_millis(1))
+                    .unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+
+    #[test]
+    fn spawned_jobs_run() {
+        let pool = JobPool::new("test", 2, None);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..16 {
+            let tx = tx.clone();
+            pool.spawn(Priority::Normal, "test", move || tx.send(i).unwrap());
+        }
+        let mut got: Vec<i32> = rx.iter().take(16).collect();
+        got.sort_unstable();
+        assert_eq!(got, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scope_borrows_and_waits() {
+        let pool = JobPool::new("test", 3, None);
+        let mut out = vec![0u32; 64];
+        pool.scope(|s| {
+            for (i, slot) in out.iter_mut().enumerate() {
+                s.spawn(Priority::High, "square", move || *slot = (i * i) as u32);
+            }
+        });
+        assert!(out.iter().enumerate().all(|(i, &v)| v == (i * i) as u32));
+    }
+
+    #[test]
+    fn nested_scope_on_a_single_worker() {
+        let pool = Arc::new(JobPool::new("test", 1, None));
+        let (tx, rx) = mpsc::channel();
+        let inner = Arc::clone(&pool);
+        pool.spawn(Priority::Normal, "outer", move || {
+            let count = AtomicUsize::new(0);
+            inner.scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(Priority::Normal, "inner", || {
+                        count.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            });
+            tx.send(count.load(Ordering::Relaxed)).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(4));
+    }
+
+    #[test]
+    fn scope_resumes_task_panics() {
+        let pool = JobPool::new("test", 2, None);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| s.spawn(Priority::Normal, "boom", || panic!("boom")));
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -17,7 +17,8 @@ pub struct Camera {
     pub pitch: f32,
     /// Vertical field of view, in radians.
     pub fovy: f32,
-    /// Near clip distance. The far plane is infinite (reverse-Z).
+    /// Near clip distance. There's no far one: cubic-render's projections
+    /// (see its clip module) put the far plane at infinity.
     pub near: f32,
 }
 
@@ -52,20 +53,6 @@ impl Camera {
         camera::rh::view::look_to_mat4(self.position.as_vec3(), self.forward(), Vec3::Y)
     }
 
-    /// Right-handed, Vulkan depth range [0, 1], reverse-Z, infinite far
-    /// plane projection matrix. Must stay numerically identical to what
-    /// the renderer's pipeline expects (reverse-Z: clear depth = 0.0,
-    /// depth_compare_op = GREATER_OR_EQUAL).
-    pub fn projection_matrix(&self, aspect: f32) -> Mat4 {
-        let f = 1.0 / (0.5 * self.fovy).tan();
-        Mat4::from_cols(
-            Vec4::new(f / aspect, 0.0, 0.0, 0.0),
-            Vec4::new(0.0, f, 0.0, 0.0),
-            Vec4::new(0.0, 0.0, 0.0, -1.0),
-            Vec4::new(0.0, 0.0, self.near, 0.0),
-        )
-    }
-
     /// View matrix with translation zeroed — for camera-relative rendering.
     /// Use this instead of view_matrix() when model matrices are built
     /// relative to the camera position.
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Collider outlines for the renderer's debug lines (VkRenderer::debug_line
+//! and friends): boxes as their 12 edges, balls and capsules as circles,
+//! triangle meshes as their triangles. Emitted through a callback so this
+//! crate needn't know about any renderer.
+
+use cubic_math::DVec3;
+use rapier3d::na::{Point3, Vector3};
+use rapier3d::parry::query::PointQuery;
+use rapier3d::prelude::{Isometry, Real, Shape};
+
+use crate::PhysicsWorld;
+
+/// Segments per circle.
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// Awake dynamic and kinematic bodies.
+const AWAKE: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+/// Sleeping bodies.
+const ASLEEP: [f32; 4] = [0.4, 0.5, 0.9, 1.0];
+/// Fixed bodies and chunk colliders.
+const STATIC: [f32; 4] = [0.2, 0.9, 0.3, 1.0];
+
+impl PhysicsWorld {
+    /// Outline every collider within `radius` metres of `center`, calling
+    /// `line(a, b, color)` for each segment in world space.
+    pub fn debug_lines(
+        &self,
+        center: DVec3,
+        radius: f64,
+        mut line: impl FnMut(DVec3, DVec3, [f32; 4]),
+    ) {
+        let local = (center - self.origin).as_vec3();
+        let local = Point3::new(local.x, local.y, local.z);
+        for (_, collider) in self.colliders.iter() {
+            let aabb = collider.compute_aabb();
+            if f64::from(aabb.distance_to_local_point(&local, true)) > radius {
+                continue;
+            }
+            let color = match collider.parent().and_then(|h| self.bodies.get(h)) {
+                Some(body) if body.is_fixed() => STATIC,
+                Some(body) if body.is_sleeping() => ASLEEP,
+                Some(_) => AWAKE,
+                None => STATIC,
+            };
+            let mut emit = |a: Point3<Real>, b: Point3<Real>| {
+                line(self.to_world(a), self.to_world(b), color);
+            };
+            outline(collider.shape(), collider.position(), &mut emit);
+        }
+    }
+
+    fn to_world(&self, p: Point3<Real>) -> DVec3 {
+        self.origin + DVec3::new(f64::from(p.x), f64::from(p.y), f64::from(p.z))
+    }
+}
+
+fn outline(
+    shape: &dyn Shape,
+    pos: &Isometry<Real>,
+    emit: &mut impl FnMut(Point3<Real>, Point3<Real>),
+) {
+    if let Some(cuboid) = shape.as_cuboid() {
+        let h = cuboid.half_extents;
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            pos * Point3::new(sign(1) * h.x, sign(2) * h.y, sign(4) * h.z)
+        };
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    emit(corner(i), corner(i | bit));
+                }
+            }
+        }
+    } else if let Some(ball) = shape.as_ball() {
+        for (u, v) in [
+            (Vector3::x(), Vector3::y()),
+            (Vector3::y(), Vector3::z()),
+            (Vector3::z(), Vector3::x()),
+        ] {
+            circle(pos, Point3::origin(), u, v, ball.radius, emit);
+        }
+    } else if let Some(capsule) = shape.as_capsule() {
+        let (a, b, r) = (capsule.segment.a, capsule.segment.b, capsule.radius);
+        for end in [a, b] {
+            circle(pos, end, Vector3::x(), Vector3::z(), r, emit);
+        }
+        for side in [Vector3::x(), -Vector3::x(), Vector3::z(), -Vector3::z()] {
+            emit(pos * (a + side * r), pos * (b + side * r));
+        }
+    } else if let Some(compound) = shape.as_compound() {
+        for (part_pos, part) in compound.shapes() {
+            outline(part.as_ref(), &(pos * part_pos), emit);
+        }
+    } else if let Some(mesh) = shape.as_trimesh() {
+        let vertices = mesh.vertices();
+        for tri in mesh.indices() {
+            let p = tri.map(|i| pos * vertices[i as usize]);
+            emit(p[0], p[1]);
+            emit(p[1], p[2]);
+            emit(p[2], p[0]);
+        }
+    }
+}
+
+fn circle(
+    pos: &Isometry<Real>,
+    center: Point3<Real>,
+    u: Vector3<Real>,
+    v: Vector3<Real>,
+    radius: Real,
+    emit: &mut impl FnMut(Point3<Real>, Point3<Real>),
+) {
+    let point = |i: usize| {
+        let t = i as Real / CIRCLE_SEGMENTS as Real * std::f32::consts::TAU;
+        pos * (center + (u * t.cos() + v * t.sin()) * radius)
+    };
+    for i in 0..CIRCLE_SEGMENTS {
+        emit(point(i), point(i + 1));
+    }
+}
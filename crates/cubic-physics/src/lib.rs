@@ -0,0 +1,499 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Rigid-body physics on rapier3d, driven from the ECS.
+//!
+//! Give an entity a Transform and a RigidBody and PhysicsWorld::step
+//! simulates it: a body is created from the Transform the first time the
+//! entity is seen, and after each step a dynamic body's position and
+//! rotation are written back into the Transform (kinematic bodies go the
+//! other way, following their Transform). Only root entities are synced;
+//! a body under a Parent would fight propagate_transforms. Scale is
+//! ignored, the collider is whatever RigidBody::shape says.
+//!
+//! The static world is chunk colliders (voxel.rs), set per chunk as the
+//! streamer loads and edits them. Nothing collides with chunks that have
+//! no collider, so callers keep them set around wherever bodies are.
+//!
+//! rapier is f32. Positions inside it are relative to `origin`, which
+//! set_origin moves (rebasing every body and collider) so the simulated
+//! area never strays far enough out for f32 to get coarse — the same idea
+//! as camera-relative rendering.
+//!
+//! Step it from the fixed-timestep loop: rapier's solver is tuned for a
+//! constant dt, and a varying one makes stacks jitter.
+
+mod debug;
+mod voxel;
+
+pub use voxel::{voxel_boxes, ChunkColliderSource, VoxelBox};
+
+use cubic_ecs::{Entity, Parent, Transform, World};
+use cubic_math::{DVec3, Quat, Vec3};
+use cubic_world::{Chunk, ChunkPos};
+use rapier3d::na::{Quaternion, Translation3, UnitQuaternion};
+use rapier3d::prelude::{
+    CCDSolver, ColliderBuilder, ColliderHandle, ColliderSet, DefaultBroadPhase, ImpulseJointSet,
+    IntegrationParameters, IslandManager, Isometry, MultibodyJointSet, NarrowPhase,
+    PhysicsPipeline, QueryPipeline, Real, RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
+    SharedShape, Vector,
+};
+use std::collections::HashMap;
+
+/// Standard gravity, m/s², pulling down -Y.
+pub const GRAVITY: f32 = 9.81;
+
+/// Friction of chunk colliders, rapier's default.
+const CHUNK_FRICTION: f32 = 0.5;
+
+/// How the body moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyKind {
+    /// Moved by forces, gravity and contacts.
+    #[default]
+    Dynamic,
+    /// Moved by its Transform each step; pushes dynamic bodies, isn't
+    /// pushed back.
+    Kinematic,
+    /// Never moves.
+    Fixed,
+}
+
+/// Collision shape, centred on the entity's translation, in metres.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColliderShape {
+    Cuboid {
+        half_extents: Vec3,
+    },
+    Ball {
+        radius: f32,
+    },
+    /// Upright: a cylinder `2 * half_height` tall capped by hemispheres.
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+}
+
+impl ColliderShape {
+    fn shared(self) -> SharedShape {
+        match self {
+            ColliderShape::Cuboid { half_extents: h } => SharedShape::cuboid(h.x, h.y, h.z),
+            ColliderShape::Ball { radius } => SharedShape::ball(radius),
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => SharedShape::capsule_y(half_height, radius),
+        }
+    }
+}
+
+/// Component: simulate this entity as a rigid body. Changing it (or
+/// removing and re-adding it) rebuilds the body at the entity's current
+/// Transform on the next step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RigidBody {
+    pub kind: BodyKind,
+    pub shape: ColliderShape,
+    /// kg/m³; mass follows from the shape's volume.
+    pub density: f32,
+    pub friction: f32,
+    /// Bounciness, 0 (none) to 1.
+    pub restitution: f32,
+}
+
+impl RigidBody {
+    pub fn dynamic(shape: ColliderShape) -> Self {
+        Self {
+            kind: BodyKind::Dynamic,
+            shape,
+            density: 1000.0,
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+
+    pub fn kinematic(shape: ColliderShape) -> Self {
+        Self {
+            kind: BodyKind::Kinematic,
+            ..Self::dynamic(shape)
+        }
+    }
+
+    pub fn fixed(shape: ColliderShape) -> Self {
+        Self {
+            kind: BodyKind::Fixed,
+            ..Self::dynamic(shape)
+        }
+    }
+}
+
+/// The rapier world and which entity and chunk owns what in it.
+pub struct PhysicsWorld {
+    gravity: Vector<Real>,
+    params: IntegrationParameters,
+    pipeline: PhysicsPipeline,
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd: CCDSolver,
+    query: QueryPipeline,
+    origin: DVec3,
+    // Each simulated entity's body and the RigidBody it was built from,
+    // to notice the component changing.
+    entities: HashMap<Entity, (RigidBodyHandle, RigidBody)>,
+    // None for chunks that were set but had nothing solid.
+    chunks: HashMap<ChunkPos, Option<ColliderHandle>>,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhysicsWorld {
+    /// An empty world with standard gravity and its origin at the world's.
+    pub fn new() -> Self {
+        Self {
+            gravity: Vector::new(0.0, -GRAVITY, 0.0),
+            params: IntegrationParameters::default(),
+            pipeline: PhysicsPipeline::new(),
+            islands: IslandManager::new(),
+            broad_phase: DefaultBroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            impulse_joints: ImpulseJointSet::new(),
+            multibody_joints: MultibodyJointSet::new(),
+            ccd: CCDSolver::new(),
+            query: QueryPipeline::new(),
+            origin: DVec3::ZERO,
+            entities: HashMap::new(),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) {
+        self.gravity = to_na(gravity);
+    }
+
+    /// Drop every body and chunk collider, e.g. for a new world.
+    pub fn clear(&mut self) {
+        *self = Self {
+            gravity: self.gravity,
+            ..Self::new()
+        };
+    }
+
+    /// World position rapier's coordinates are relative to.
+    pub fn origin(&self) -> DVec3 {
+        self.origin
+    }
+
+    /// Move the origin to `origin`, shifting everything in the simulation
+    /// so nothing moves in world space. Call when the area being simulated
+    /// (the camera, the player) has wandered a few hundred metres off it.
+    pub fn set_origin(&mut self, origin: DVec3) {
+        let shift = to_na((self.origin - origin).as_vec3());
+        self.origin = origin;
+        for (_, body) in self.bodies.iter_mut() {
+            let mut pos = *body.position();
+            pos.translation.vector += shift;
+            body.set_position(pos, false);
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(pos);
+            }
+        }
+        for handle in self.chunks.values().flatten() {
+            if let Some(collider) = self.colliders.get_mut(*handle) {
+                let mut pos = *collider.position();
+                pos.translation.vector += shift;
+                collider.set_position(pos);
+            }
+        }
+    }
+
+    /// Advance the simulation by `dt` seconds, syncing `world`'s RigidBody
+    /// entities in before and their Transforms out after.
+    pub fn step(&mut self, world: &mut World, dt: f32) {
+        let _span = tracing::debug_span!("physics_step").entered();
+        self.sync_bodies(world);
+        self.params.dt = dt;
+        self.pipeline.step(
+            &self.gravity,
+            &self.params,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd,
+            Some(&mut self.query),
+            &(),
+            &(),
+        );
+        for (&e, &(handle, desc)) in &self.entities {
+            if desc.kind != BodyKind::Dynamic {
+                continue;
+            }
+            let (Some(body), Some(transform)) =
+                (self.bodies.get(handle), world.get_mut::<Transform>(e))
+            else {
+                continue;
+            };
+            let pos = body.position();
+            transform.translation = self.origin + from_na(pos.translation.vector).as_dvec3();
+            transform.rotation = from_na_quat(&pos.rotation);
+        }
+    }
+
+    /// Create, rebuild and remove bodies to match `world`'s RigidBody
+    /// components, and move kinematic bodies to their Transforms.
+    fn sync_bodies(&mut self, world: &World) {
+        let stale: Vec<Entity> = self
+            .entities
+            .iter()
+            .filter(|(&e, (_, desc))| world.get::<RigidBody>(e) != Some(desc))
+            .map(|(&e, _)| e)
+            .collect();
+        for e in stale {
+            self.remove_body(e);
+        }
+
+        for (e, &desc) in world.query::<RigidBody>() {
+            if world.get::<Parent>(e).is_some() {
+                continue;
+            }
+            let transform = world.get::<Transform>(e).copied().unwrap_or_default();
+            let pos = self.isometry(&transform);
+            if let Some(&(handle, _)) = self.entities.get(&e) {
+                if desc.kind == BodyKind::Kinematic {
+                    if let Some(body) = self.bodies.get_mut(handle) {
+                        body.set_next_kinematic_position(pos);
+                    }
+                }
+                continue;
+            }
+            let builder = match desc.kind {
+                BodyKind::Dynamic => RigidBodyBuilder::dynamic(),
+                BodyKind::Kinematic => RigidBodyBuilder::kinematic_position_based(),
+                BodyKind::Fixed => RigidBodyBuilder::fixed(),
+            };
+            let handle = self.bodies.insert(builder.position(pos).build());
+            let collider = ColliderBuilder::new(desc.shape.shared())
+                .density(desc.density)
+                .friction(desc.friction)
+                .restitution(desc.restitution)
+                .build();
+            self.colliders
+                .insert_with_parent(collider, handle, &mut self.bodies);
+            self.entities.insert(e, (handle, desc));
+        }
+    }
+
+    fn remove_body(&mut self, e: Entity) {
+        if let Some((handle, _)) = self.entities.remove(&e) {
+            self.bodies.remove(
+                handle,
+                &mut self.islands,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        }
+    }
+
+    /// Bodies being simulated.
+    pub fn body_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// `e`'s velocity in m/s, once a step has created its body.
+    pub fn linvel(&self, e: Entity) -> Option<Vec3> {
+        let (handle, _) = self.entities.get(&e)?;
+        Some(from_na(*self.bodies.get(*handle)?.linvel()))
+    }
+
+    /// Set `e`'s velocity, waking it. Nothing until a step has created its
+    /// body.
+    pub fn set_linvel(&mut self, e: Entity, linvel: Vec3) {
+        if let Some(body) = self.body_mut(e) {
+            body.set_linvel(to_na(linvel), true);
+        }
+    }
+
+    /// Push `e` with an impulse in N·s, waking it.
+    pub fn apply_impulse(&mut self, e: Entity, impulse: Vec3) {
+        if let Some(body) = self.body_mut(e) {
+            body.apply_impulse(to_na(impulse), true);
+        }
+    }
+
+    fn body_mut(&mut self, e: Entity) -> Option<&mut rapier3d::prelude::RigidBody> {
+        let (handle, _) = self.entities.get(&e)?;
+        self.bodies.get_mut(*handle)
+    }
+
+    /// Set (or replace) the collider of the chunk at `pos` from its voxels;
+    /// see ChunkColliderSource. False if the chunk has nothing solid, in
+    /// which case it has no collider now (but still counts as set).
+    pub fn set_chunk_collider(
+        &mut self,
+        pos: ChunkPos,
+        chunk: &Chunk,
+        neighbors: [Option<&Chunk>; 6],
+        source: ChunkColliderSource,
+    ) -> bool {
+        self.remove_chunk_collider(pos);
+        let Some(shape) = voxel::chunk_shape(chunk, neighbors, source) else {
+            self.chunks.insert(pos, None);
+            return false;
+        };
+        let corner = (pos.to_world_origin() - self.origin).as_vec3();
+        let collider = ColliderBuilder::new(shape)
+            .translation(to_na(corner))
+            .friction(CHUNK_FRICTION)
+            .build();
+        self.chunks
+            .insert(pos, Some(self.colliders.insert(collider)));
+        true
+    }
+
+    /// Drop the chunk at `pos`'s collider, waking anything resting on it.
+    pub fn remove_chunk_collider(&mut self, pos: ChunkPos) {
+        if let Some(Some(handle)) = self.chunks.remove(&pos) {
+            self.colliders
+                .remove(handle, &mut self.islands, &mut self.bodies, true);
+        }
+    }
+
+    /// Whether the chunk at `pos` has been set, solid or not.
+    pub fn has_chunk_collider(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Every chunk that has been set, solid or not.
+    pub fn chunk_colliders(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    fn isometry(&self, transform: &Transform) -> Isometry<Real> {
+        let t = (transform.translation - self.origin).as_vec3();
+        Isometry::from_parts(
+            Translation3::new(t.x, t.y, t.z),
+            to_na_quat(transform.rotation),
+        )
+    }
+}
+
+fn to_na(v: Vec3) -> Vector<Real> {
+    Vector::new(v.x, v.y, v.z)
+}
+
+fn from_na(v: Vector<Real>) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
+
+fn to_na_quat(q: Quat) -> UnitQuaternion<Real> {
+    UnitQuaternion::new_normalize(Quaternion::new(q.w, q.x, q.y, q.z))
+}
+
+fn from_na_quat(q: &UnitQuaternion<Real>) -> Quat {
+    Quat::from_xyzw(q.i, q.j, q.k, q.w)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cubic_world::{BlockTypeId, ChunkLocalPos};
+
+    /// A chunk with a floor of solid voxels 2 m thick (y 0..4 at 0.5 m).
+    fn floor_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                for y in 0..4 {
+                    chunk.set(ChunkLocalPos::new(x, y, z), BlockTypeId(1));
+                }
+            }
+        }
+        chunk
+    }
+
+    fn drop_ball(source: ChunkColliderSource) -> f64 {
+        let mut physics = PhysicsWorld::new();
+        let pos = ChunkPos { x: 0, y: 0, z: 0 };
+        assert!(physics.set_chunk_collider(pos, &floor_chunk(), [None; 6], source));
+
+        let mut world = World::new();
+        let ball = world.spawn();
+        world.insert(ball, Transform::from_translation(DVec3::new(8.0, 6.0, 8.0)));
+        world.insert(
+            ball,
+            RigidBody::dynamic(ColliderShape::Ball { radius: 0.5 }),
+        );
+        for _ in 0..240 {
+            physics.step(&mut world, 1.0 / 60.0);
+        }
+        assert_eq!(physics.body_count(), 1);
+        world.get::<Transform>(ball).unwrap().translation.y
+    }
+
+    #[test]
+    fn ball_comes_to_rest_on_a_chunk_floor() {
+        for source in [ChunkColliderSource::Voxels, ChunkColliderSource::Mesh] {
+            // Floor top at 2 m, ball radius 0.5 m.
+            let y = drop_ball(source);
+            assert!((y - 2.5).abs() < 0.05, "{source:?}: ball at y = {y}");
+        }
+    }
+
+    #[test]
+    fn bodies_follow_their_components() {
+        let mut physics = PhysicsWorld::new();
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, Transform::default());
+        world.insert(a, RigidBody::fixed(ColliderShape::Ball { radius: 1.0 }));
+        physics.step(&mut world, 1.0 / 60.0);
+        assert_eq!(physics.body_count(), 1);
+
+        // A changed component rebuilds the body: now it falls.
+        world.insert(a, RigidBody::dynamic(ColliderShape::Ball { radius: 1.0 }));
+        physics.step(&mut world, 1.0 / 60.0);
+        physics.step(&mut world, 1.0 / 60.0);
+        assert!(physics.linvel(a).unwrap().y < 0.0);
+
+        world.despawn(a);
+        physics.step(&mut world, 1.0 / 60.0);
+        assert_eq!(physics.body_count(), 0);
+    }
+
+    #[test]
+    fn rebasing_keeps_world_positions() {
+        let mut physics = PhysicsWorld::new();
+        physics.set_gravity(Vec3::ZERO);
+        let mut world = World::new();
+        let e = world.spawn();
+        let start = DVec3::new(1000.25, 20.0, -3000.5);
+        world.insert(e, Transform::from_translation(start));
+        world.insert(e, RigidBody::dynamic(ColliderShape::Ball { radius: 0.5 }));
+        physics.step(&mut world, 1.0 / 60.0);
+
+        physics.set_origin(DVec3::new(1000.0, 0.0, -3000.0));
+        physics.step(&mut world, 1.0 / 60.0);
+        let at = world.get::<Transform>(e).unwrap().translation;
+        assert!(at.distance(start) < 1e-3, "moved to {at}");
+    }
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Chunk colliders: the static geometry bodies land on, built from a
+//! chunk's voxels one of two ways (ChunkColliderSource).
+//!
+//! - `Voxels`: the solid voxels merged greedily into boxes (runs along X,
+//!   grown along Z, then Y), one compound of cuboids per chunk. Exact
+//!   block edges, usually a few dozen boxes for natural terrain, and no
+//!   internal edges for a body to catch on sliding along a floor.
+//! - `Mesh`: the chunk's render mesh (mesh_chunk, greedy, culled against
+//!   the neighbours) as a triangle mesh. Only the surface, so thin, but
+//!   the same faces the player sees.
+//!
+//! Either way the shape is in metres relative to the chunk's origin corner.
+
+use cubic_world::{mesh_chunk, BlockFaceTextures, BlockTypeId, Chunk, CHUNK_SIZE, VOXEL_SIZE};
+use rapier3d::na::{Isometry3, Point3, Vector3};
+use rapier3d::prelude::{SharedShape, TriMeshFlags};
+
+/// How set_chunk_collider turns a chunk into a collider.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkColliderSource {
+    /// Greedy-merged voxel boxes.
+    #[default]
+    Voxels,
+    /// The chunk's greedy render mesh as a triangle mesh.
+    Mesh,
+}
+
+/// An axis-aligned box of solid voxels, in voxel coordinates within the
+/// chunk; `max` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelBox {
+    pub min: [u8; 3],
+    pub max: [u8; 3],
+}
+
+const CS: usize = CHUNK_SIZE;
+
+fn index(x: usize, y: usize, z: usize) -> usize {
+    x + z * CS + y * CS * CS
+}
+
+/// The solid (non-air) voxels of `chunk` as few boxes as a greedy pass
+/// finds: every solid voxel in exactly one box.
+pub fn voxel_boxes(chunk: &Chunk) -> Vec<VoxelBox> {
+    let solid: Vec<bool> = chunk
+        .data
+        .iter()
+        .map(|&i| chunk.palette[i as usize] != BlockTypeId(0))
+        .collect();
+    let mut taken = vec![false; solid.len()];
+    let free = |taken: &[bool], x, y, z| {
+        let i = index(x, y, z);
+        solid[i] && !taken[i]
+    };
+
+    let mut boxes = Vec::new();
+    for y in 0..CS {
+        for z in 0..CS {
+            for x in 0..CS {
+                if !free(&taken, x, y, z) {
+                    continue;
+                }
+                let mut x1 = x + 1;
+                while x1 < CS && free(&taken, x1, y, z) {
+                    x1 += 1;
+                }
+                let mut z1 = z + 1;
+                while z1 < CS && (x..x1).all(|xx| free(&taken, xx, y, z1)) {
+                    z1 += 1;
+                }
+                let mut y1 = y + 1;
+                while y1 < CS && (z..z1).all(|zz| (x..x1).all(|xx| free(&taken, xx, y1, zz))) {
+                    y1 += 1;
+                }
+                for yy in y..y1 {
+                    for zz in z..z1 {
+                        for xx in x..x1 {
+                            taken[index(xx, yy, zz)] = true;
+                        }
+                    }
+                }
+                boxes.push(VoxelBox {
+                    min: [x as u8, y as u8, z as u8],
+                    max: [x1 as u8, y1 as u8, z1 as u8],
+                });
+            }
+        }
+    }
+    boxes
+}
+
+/// `chunk`'s collider shape, or None if nothing in it is solid.
+/// `neighbors` (see mesh_chunk) only matter for `Mesh`.
+pub(crate) fn chunk_shape(
+    chunk: &Chunk,
+    neighbors: [Option<&Chunk>; 6],
+    source: ChunkColliderSource,
+) -> Option<SharedShape> {
+    match source {
+        ChunkColliderSource::Voxels => {
+            let shapes: Vec<_> = voxel_boxes(chunk)
+                .into_iter()
+                .map(|b| {
+                    let size = |i: usize| f32::from(b.max[i] - b.min[i]) * VOXEL_SIZE;
+                    let centre = |i: usize| f32::from(b.min[i]) * VOXEL_SIZE + 0.5 * size(i);
+                    (
+                        Isometry3::translation(centre(0), centre(1), centre(2)),
+                        SharedShape::cuboid(0.5 * size(0), 0.5 * size(1), 0.5 * size(2)),
+                    )
+                })
+                .collect();
+            (!shapes.is_empty()).then(|| SharedShape::compound(shapes))
+        }
+        ChunkColliderSource::Mesh => {
+            let (vertices, indices) = mesh_chunk(chunk, neighbors, &BlockFaceTextures::new());
+            if indices.is_empty() {
+                return None;
+            }
+            let points = vertices
+                .iter()
+                .map(|v| Point3::from(Vector3::from(v.pos)))
+                .collect();
+            let triangles = indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect();
+            match SharedShape::trimesh_with_flags(
+                points,
+                triangles,
+                TriMeshFlags::FIX_INTERNAL_EDGES,
+            ) {
+                Ok(shape) => Some(shape),
+                Err(e) => {
+                    tracing::warn!("chunk collider mesh rejected: {e}");
+                    None
+                }
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cubic_world::ChunkLocalPos;
+
+    fn volume(boxes: &[VoxelBox]) -> usize {
+        boxes
+            .iter()
+            .map(|b| {
+                (0..3)
+                    .map(|i| (b.max[i] - b.min[i]) as usize)
+                    .product::<usize>()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn air_has_no_boxes() {
+        assert!(voxel_boxes(&Chunk::new()).is_empty());
+        assert!(chunk_shape(&Chunk::new(), [None; 6], ChunkColliderSource::Voxels).is_none());
+        assert!(chunk_shape(&Chunk::new(), [None; 6], ChunkColliderSource::Mesh).is_none());
+    }
+
+    #[test]
+    fn solid_slab_is_one_box() {
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                for y in 0..4 {
+                    chunk.set(ChunkLocalPos::new(x, y, z), BlockTypeId(1));
+                }
+            }
+        }
+        let boxes = voxel_boxes(&chunk);
+        assert_eq!(
+            boxes,
+            vec![VoxelBox {
+                min: [0, 0, 0],
+                max: [32, 4, 32]
+            }]
+        );
+    }
+
+    #[test]
+    fn every_solid_voxel_is_covered_once() {
+        let mut chunk = Chunk::new();
+        // An L on the floor and a lone block above it.
+        for x in 0..6 {
+            chunk.set(ChunkLocalPos::new(x, 0, 0), BlockTypeId(1));
+        }
+        for z in 1..4 {
+            chunk.set(ChunkLocalPos::new(0, 0, z), BlockTypeId(2));
+        }
+        chunk.set(ChunkLocalPos::new(9, 7, 3), BlockTypeId(1));
+        let boxes = voxel_boxes(&chunk);
+        assert_eq!(boxes.len(), 3);
+        assert_eq!(volume(&boxes), 6 + 3 + 1);
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Depth state for cubic-render's DepthConvention, so matrices built for
+//! the Vulkan backend draw the same here.
+//!
+//! Those matrices put depth in [0, 1], Vulkan's clip range. GL clips depth
+//! to [-1, 1] unless glClipControl (GL 4.5 or ARB_clip_control) switches
+//! it to ZERO_TO_ONE. The context is only asked for 3.3, so it's loaded by
+//! hand where the driver has it. Without it everything still draws and
+//! sorts correctly, just mapped into [0.5, 1] of the depth buffer, which
+//! throws away reverse-Z's precision advantage.
+
+use std::ffi::CString;
+
+use cubic_render::DepthConvention;
+use glow::HasContext as _;
+use glutin::display::{Display, GlDisplay as _};
+
+type ClipControlFn = unsafe extern "system" fn(origin: u32, depth: u32);
+
+pub(crate) struct DepthState {
+    clip_control: Option<ClipControlFn>,
+    convention: DepthConvention,
+}
+
+impl DepthState {
+    pub(crate) fn new(gl: &glow::Context, display: &Display) -> Self {
+        let version = gl.version();
+        let supported = (!version.is_embedded && (version.major, version.minor) >= (4, 5))
+            || gl.supported_extensions().contains("GL_ARB_clip_control");
+        // get_proc_address can hand back a pointer for functions the driver
+        // doesn't implement, hence the version/extension check first.
+        let clip_control = supported
+            .then(|| {
+                let name = CString::new("glClipControl").unwrap();
+                let ptr = display.get_proc_address(&name);
+                // SAFETY: a non-null glClipControl has this signature.
+                (!ptr.is_null())
+                    .then(|| unsafe { std::mem::transmute::<*const _, ClipControlFn>(ptr) })
+            })
+            .flatten();
+        if clip_control.is_none() {
+            tracing::info!("gl: no glClipControl, depth keeps GL's [-1, 1] clip range");
+        }
+        Self {
+            clip_control,
+            convention: DepthConvention::default(),
+        }
+    }
+
+    pub(crate) fn convention(&self) -> DepthConvention {
+        self.convention
+    }
+
+    pub(crate) fn set_convention(&mut self, convention: DepthConvention) {
+        self.convention = convention;
+    }
+
+    pub(crate) fn zero_to_one(&self) -> bool {
+        self.clip_control.is_some()
+    }
+
+    /// Set the clip range, depth test and clear value. Once per frame,
+    /// before the scene: egui_glow leaves depth testing off.
+    pub(crate) fn apply(&self, gl: &glow::Context) {
+        unsafe {
+            if let Some(clip_control) = self.clip_control {
+                clip_control(glow::LOWER_LEFT, glow::ZERO_TO_ONE);
+            }
+            gl.enable(glow::DEPTH_TEST);
+            gl.depth_mask(true);
+            gl.depth_func(match self.convention {
+                DepthConvention::ReverseZ => glow::GEQUAL,
+                DepthConvention::Standard => glow::LEQUAL,
+            });
+            gl.clear_depth_f32(self.convention.clear_depth());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Egui overlay for the GL backend — the counterpart of cubic-render-vk's
+//! egui_overlay.rs, painting with egui_glow instead of egui-ash-renderer.
+//! Same contract: cubic-app hands over a tessellated frame via queue_egui
+//! and it's drawn over the scene by the next render().
+
+use anyhow::{anyhow, Result};
+use glow::HasContext as _;
+use std::sync::Arc;
+
+use crate::GlRenderer;
+
+pub(crate) struct EguiFrame {
+    pub(crate) textures_delta: egui::TexturesDelta,
+    pub(crate) paint_jobs: Vec<egui::ClippedPrimitive>,
+    pub(crate) screen_width: u32,
+    pub(crate) screen_height: u32,
+    pub(crate) pixels_per_point: f32,
+}
+
+pub(crate) fn build_egui_painter(gl: &Arc<glow::Context>) -> Result<egui_glow::Painter> {
+    // Default shader prefix/version: egui_glow picks one matching the
+    // context, which is always desktop GL 3.3 core here (see make_current).
+    egui_glow::Painter::new(gl.clone(), "", None, false).map_err(|e| anyhow!("egui_glow: {e}"))
+}
+
+impl GlRenderer {
+    /// Paint a staged egui frame (see Renderer::queue_egui) into the
+    /// default framebuffer. Called from render() after the scene, before
+    /// swap_buffers. No-op if nothing is staged.
+    pub(crate) fn paint_egui(&mut self) {
+        let Some(frame) = self.egui_pending.take() else {
+            return;
+        };
+        self.egui_painter.paint_and_update_textures(
+            [frame.screen_width, frame.screen_height],
+            frame.pixels_per_point,
+            &frame.paint_jobs,
+            &frame.textures_delta,
+        );
+        // egui_glow leaves blending/scissor on and turns FRAMEBUFFER_SRGB
+        // and culling off (it outputs gamma-space colour); restore the
+        // scene state new() set up so the next frame starts from it.
+        unsafe {
+            self.gl.disable(glow::SCISSOR_TEST);
+            self.gl.disable(glow::BLEND);
+            if self.fb_srgb {
+                self.gl.enable(glow::FRAMEBUFFER_SRGB);
+            }
+            self.gl.enable(glow::CULL_FACE);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Offscreen scene target and output pass, GL's counterpart of
+//! cubic-render-vk's tonemap.rs, so GL output looks like Vulkan's.
+//!
+//! The default framebuffer is asked for sRGB (see make_current) and
+//! FRAMEBUFFER_SRGB is only turned on when the driver confirms it, via the
+//! back buffer's colour encoding. Scenes render straight to it unless:
+//!
+//! - `hdr` is on: GL has no HDR output, so the scene renders to an FP16
+//!   target and the selected tonemap curve brings it into SDR, where the
+//!   Vulkan backend would hand it to an HDR display instead;
+//! - the default framebuffer turned out linear and `srgb_encode` is on:
+//!   same target, no curve, and the pass sRGB-encodes in the shader (as
+//!   Vulkan's UNORM swapchain fallback does).
+//!
+//! The target has a depth buffer of its own, for the scene's depth test
+//! (see depth.rs). egui is drawn after the pass, onto the default
+//! framebuffer.
+
+use anyhow::{anyhow, Result};
+use cubic_render::{RenderSize, TonemapOperator};
+use glow::HasContext as _;
+
+const VERT: &str = r#"#version 330 core
+out vec2 v_uv;
+void main() {
+    // One triangle covering the screen.
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    v_uv = pos;
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}"#;
+
+// Curves as in tonemap.frag.
+const FRAG: &str = r#"#version 330 core
+in vec2 v_uv;
+out vec4 outColor;
+uniform sampler2D scene;
+uniform uint op;     // 0 = ACES, 1 = Reinhard, 2 = clamp
+uniform bool encode; // sRGB-encode here: the framebuffer won't
+
+vec3 aces_fitted(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+vec3 srgb_encode(vec3 linear) {
+    vec3 lo = linear * 12.92;
+    vec3 hi = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(hi, lo, lessThanEqual(linear, vec3(0.0031308)));
+}
+
+void main() {
+    vec3 x = max(texture(scene, v_uv).rgb, vec3(0.0));
+    vec3 mapped;
+    if (op == 0u) {
+        mapped = aces_fitted(x);
+    } else if (op == 1u) {
+        mapped = x / (1.0 + x);
+    } else {
+        mapped = min(x, vec3(1.0));
+    }
+    outColor = vec4(encode ? srgb_encode(mapped) : mapped, 1.0);
+}"#;
+
+pub(crate) struct ScenePass {
+    fbo: glow::Framebuffer,
+    color: glow::Texture,
+    depth: glow::Renderbuffer,
+    program: glow::Program,
+    vao: glow::VertexArray,
+    size: RenderSize,
+}
+
+impl ScenePass {
+    pub(crate) fn new(gl: &glow::Context, size: RenderSize) -> Result<Self> {
+        let program = crate::link_program(gl, VERT, FRAG)?;
+        unsafe {
+            let vao = gl.create_vertex_array().map_err(anyhow::Error::msg)?;
+            let fbo = gl.create_framebuffer().map_err(anyhow::Error::msg)?;
+            let color = gl.create_texture().map_err(anyhow::Error::msg)?;
+            let depth = gl.create_renderbuffer().map_err(anyhow::Error::msg)?;
+            let mut pass = Self {
+                fbo,
+                color,
+                depth,
+                program,
+                vao,
+                size,
+            };
+            pass.allocate(gl, size)?;
+            gl.use_program(Some(program));
+            if let Some(loc) = gl.get_uniform_location(program, "scene") {
+                gl.uniform_1_i32(Some(&loc), 0);
+            }
+            gl.use_program(None);
+            Ok(pass)
+        }
+    }
+
+    /// (Re)allocate the FP16 colour and 32-bit float depth targets at
+    /// `size`.
+    unsafe fn allocate(&mut self, gl: &glow::Context, size: RenderSize) -> Result<()> {
+        self.size = size;
+        // SAFETY: the caller's context is current; the objects are ours.
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.color));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA16F as i32,
+                size.width.max(1) as i32,
+                size.height.max(1) as i32,
+                0,
+                glow::RGBA,
+                glow::HALF_FLOAT,
+                glow::PixelUnpackData::Slice(None),
+            );
+            for (pname, value) in [
+                (glow::TEXTURE_MIN_FILTER, glow::NEAREST),
+                (glow::TEXTURE_MAG_FILTER, glow::NEAREST),
+                (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+                (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+            ] {
+                gl.tex_parameter_i32(glow::TEXTURE_2D, pname, value as i32);
+            }
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(self.depth));
+            gl.renderbuffer_storage(
+                glow::RENDERBUFFER,
+                glow::DEPTH_COMPONENT32F,
+                size.width.max(1) as i32,
+                size.height.max(1) as i32,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbo));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(self.color),
+                0,
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(self.depth),
+            );
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                return Err(anyhow!("GL scene target incomplete: 0x{status:x}"));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn resize(&mut self, gl: &glow::Context, size: RenderSize) -> Result<()> {
+        if size == self.size {
+            return Ok(());
+        }
+        // SAFETY: called with the renderer's context current.
+        unsafe { self.allocate(gl, size) }
+    }
+
+    /// Direct scene drawing into the target.
+    pub(crate) fn bind(&self, gl: &glow::Context) {
+        unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbo)) };
+    }
+
+    /// Draw the target onto the default framebuffer through `op`,
+    /// sRGB-encoding in the shader if `encode`.
+    pub(crate) fn resolve(&self, gl: &glow::Context, op: TonemapOperator, encode: bool) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.viewport(0, 0, self.size.width as i32, self.size.height as i32);
+            // The window's depth buffer wasn't cleared this frame; the scene
+            // was tested against ours.
+            gl.disable(glow::DEPTH_TEST);
+            gl.use_program(Some(self.program));
+            if let Some(loc) = gl.get_uniform_location(self.program, "op") {
+                gl.uniform_1_u32(Some(&loc), op as u32);
+            }
+            if let Some(loc) = gl.get_uniform_location(self.program, "encode") {
+                gl.uniform_1_i32(Some(&loc), i32::from(encode));
+            }
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.color));
+            gl.bind_vertex_array(Some(self.vao));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.use_program(None);
+        }
+    }
+
+    /// Free the GL objects; the context must be current.
+    pub(crate) fn destroy(self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.fbo);
+            gl.delete_texture(self.color);
+            gl.delete_renderbuffer(self.depth);
+            gl.delete_vertex_array(self.vao);
+            gl.delete_program(self.program);
+        }
+    }
+}
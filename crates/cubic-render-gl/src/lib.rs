@@ -1,44 +1,87 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-use anyhow::{anyhow, Context, Result};
-use cubic_render::{RenderSize, Renderer};
+mod depth;
+mod egui_overlay;
+mod hdr;
+mod swap;
+
+use anyhow::{Context, Result};
+use cubic_render::{
+    DepthConvention, FramePacer, FrameStats, FrameStatsTracker, LatencyMode, PresentMode,
+    RenderError, RenderResult, RenderSize, Renderer, StreamCounts, SurfaceChanged, SurfaceInfo,
+    TonemapOperator,
+};
 use glow::HasContext as _;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
 use glutin::{
-    config::ConfigTemplateBuilder,
+    config::{Config, ConfigTemplateBuilder},
     context::{
         ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext, Version,
     },
     display::{Display, DisplayApiPreference},
+    error::ErrorKind,
     prelude::*,
     surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
 };
 
 use std::num::NonZeroU32;
+use std::sync::Arc;
 
 pub struct GlRenderer {
-    //display: Display,
     context: PossiblyCurrentContext,
-    surface: Surface<WindowSurface>,
-    gl: glow::Context,
+    // None between suspend() and resume(); the context stays, not current.
+    surface: Option<Surface<WindowSurface>>,
+    display: Display,
+    // What resume() builds a new window surface from.
+    config: Config,
+    window_raw: RawWindowHandle,
+    // Arc because egui_glow's Painter keeps its own handle to the context.
+    gl: Arc<glow::Context>,
     size: RenderSize,
     clear: [f32; 4],
     program: glow::Program,
     vao: glow::VertexArray,
     vsync: bool,
+    swap: swap::SwapControl,
+    // The app's set_target_fps; the pacer runs at the lower of this and
+    // swap's fallback cap.
+    target_fps: Option<u32>,
+    pacer: FramePacer,
+    frame_stats: FrameStatsTracker,
+    latency_mode: LatencyMode,
+    egui_painter: egui_glow::Painter,
+    egui_pending: Option<egui_overlay::EguiFrame>,
+    // Whether the default framebuffer really is sRGB (make_current asks
+    // for it; drivers may still hand back a linear one).
+    fb_srgb: bool,
+    hdr: bool,
+    srgb_encode: bool,
+    tonemap: TonemapOperator,
+    // Depth convention and clip range (see depth.rs).
+    depth: depth::DepthState,
+    // The FP16 target and output pass, while hdr or a linear framebuffer
+    // needs it (see hdr.rs and sync_scene_pass).
+    scene_pass: Option<hdr::ScenePass>,
+    // Resizes not yet picked up by take_surface_change, coalesced.
+    surface_change: Option<SurfaceChanged>,
+    // capture_next_frame was called; the next render reads back into
+    // `captured`.
+    capture_next: bool,
+    captured: Option<CapturedFrame>,
 }
 
-fn compile_program(gl: &glow::Context) -> Result<glow::Program> {
-    unsafe {
-        let vs = gl
-            .create_shader(glow::VERTEX_SHADER)
-            .map_err(anyhow::Error::msg)?;
-        let fs = gl
-            .create_shader(glow::FRAGMENT_SHADER)
-            .map_err(anyhow::Error::msg)?;
+/// A frame read back from the window by capture_next_frame.
+#[derive(Clone, Debug)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8, top row first, as the window shows it (sRGB encoded).
+    pub rgba: Vec<u8>,
+}
 
-        let vert_src = r#"#version 330 core
+fn compile_program(gl: &glow::Context) -> Result<glow::Program> {
+    let vert_src = r#"#version 330 core
         out vec3 vColor;
         void main() {
           vec2 pos[3] = vec2[3](
@@ -55,23 +98,47 @@ fn compile_program(gl: &glow::Context) -> Result<glow::Program> {
           vColor = col[gl_VertexID];
         }"#;
 
-        let frag_src = r#"#version 330 core
+    let frag_src = r#"#version 330 core
         in vec3 vColor;
         out vec4 outColor;
         void main(){ outColor = vec4(vColor, 1.0); }"#;
 
+    link_program(gl, vert_src, frag_src)
+}
+
+pub(crate) fn link_program(
+    gl: &glow::Context,
+    vert_src: &str,
+    frag_src: &str,
+) -> Result<glow::Program> {
+    unsafe {
+        let vs = gl
+            .create_shader(glow::VERTEX_SHADER)
+            .map_err(anyhow::Error::msg)?;
+        let fs = gl
+            .create_shader(glow::FRAGMENT_SHADER)
+            .map_err(anyhow::Error::msg)?;
+
         gl.shader_source(vs, vert_src);
         gl.compile_shader(vs);
 
         if !gl.get_shader_compile_status(vs) {
-            return Err(anyhow!("GL vert compile: {}", gl.get_shader_info_log(vs)));
+            return Err(RenderError::ShaderCompile {
+                shader: "GL vertex shader".into(),
+                log: gl.get_shader_info_log(vs),
+            }
+            .into());
         }
 
         gl.shader_source(fs, frag_src);
         gl.compile_shader(fs);
 
         if !gl.get_shader_compile_status(fs) {
-            return Err(anyhow!("GL frag compile: {}", gl.get_shader_info_log(fs)));
+            return Err(RenderError::ShaderCompile {
+                shader: "GL fragment shader".into(),
+                log: gl.get_shader_info_log(fs),
+            }
+            .into());
         }
 
         let program = gl.create_program().map_err(anyhow::Error::msg)?;
@@ -81,10 +148,11 @@ fn compile_program(gl: &glow::Context) -> Result<glow::Program> {
         gl.link_program(program);
 
         if !gl.get_program_link_status(program) {
-            return Err(anyhow::anyhow!(
-                "GL link: {}",
-                gl.get_program_info_log(program)
-            ));
+            return Err(RenderError::ShaderCompile {
+                shader: "GL program link".into(),
+                log: gl.get_program_info_log(program),
+            }
+            .into());
         }
 
         gl.detach_shader(program, vs);
@@ -104,17 +172,23 @@ impl GlRenderer {
     ) -> Result<(
         PossiblyCurrentContext,
         Surface<WindowSurface>,
+        Config,
         glow::Context,
+        bool,
     )> {
-        let template = ConfigTemplateBuilder::new().build();
-        let mut configs = unsafe { display.find_configs(template) }.context("find_configs")?;
-        let config = configs.next().ok_or_else(|| anyhow!("no GL configs"))?;
-        let w = NonZeroU32::new(size.width.max(1)).unwrap();
-        let h = NonZeroU32::new(size.height.max(1)).unwrap();
-
-        let sattrs = SurfaceAttributesBuilder::<WindowSurface>::new().build(window_handle, w, h);
-        let surface = unsafe { display.create_window_surface(&config, &sattrs) }
-            .context("create_window_surface")?;
+        let template = ConfigTemplateBuilder::new().with_depth_size(24).build();
+        // Prefer an sRGB-capable config so FRAMEBUFFER_SRGB can do the
+        // encoding, as the Vulkan backend's *_SRGB swapchain formats do.
+        let configs: Vec<_> = unsafe { display.find_configs(template) }
+            .context("find_configs")?
+            .collect();
+        let config = configs
+            .iter()
+            .find(|c| c.srgb_capable())
+            .or_else(|| configs.first())
+            .cloned()
+            .ok_or_else(|| RenderError::unsupported("no GL configs for the window"))?;
+        let surface = Self::create_surface(display, &config, window_handle, size)?;
         let ctx_attrs = ContextAttributesBuilder::new()
             .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
             .build(Some(window_handle));
@@ -132,7 +206,35 @@ impl GlRenderer {
         let _ =
             surface.set_swap_interval(&context, SwapInterval::Wait(NonZeroU32::new(1).unwrap()));
 
-        Ok((context, surface, gl))
+        // The config flag is a request; the back buffer's encoding is what
+        // we got.
+        let fb_srgb = unsafe {
+            gl.get_framebuffer_attachment_parameter_i32(
+                glow::FRAMEBUFFER,
+                glow::BACK_LEFT,
+                glow::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            )
+        } == glow::SRGB as i32;
+        tracing::info!(
+            "gl: default framebuffer is {}",
+            if fb_srgb { "sRGB" } else { "linear" }
+        );
+
+        Ok((context, surface, config, gl, fb_srgb))
+    }
+
+    fn create_surface(
+        display: &Display,
+        config: &Config,
+        window_handle: RawWindowHandle,
+        size: RenderSize,
+    ) -> Result<Surface<WindowSurface>> {
+        let w = NonZeroU32::new(size.width.max(1)).unwrap();
+        let h = NonZeroU32::new(size.height.max(1)).unwrap();
+        let sattrs = SurfaceAttributesBuilder::<WindowSurface>::new()
+            .with_srgb(config.srgb_capable().then_some(true))
+            .build(window_handle, w, h);
+        unsafe { display.create_window_surface(config, &sattrs) }.context("create_window_surface")
     }
 }
 
@@ -141,7 +243,7 @@ impl Renderer for GlRenderer {
         window: &dyn HasWindowHandle,
         display_handle: &dyn HasDisplayHandle,
         size: RenderSize,
-    ) -> Result<Self> {
+    ) -> RenderResult<Self> {
         let wh = window
             .window_handle()
             .map_err(|e| anyhow::anyhow!("{e}"))?
@@ -157,82 +259,438 @@ impl Renderer for GlRenderer {
         let api_pref = DisplayApiPreference::Egl;
         let display = unsafe { Display::new(dh, api_pref) }.context("Display::new")?;
 
-        let (context, surface, gl) = Self::make_current(&display, wh, size)?;
+        let (context, surface, config, gl, fb_srgb) = Self::make_current(&display, wh, size)?;
+        let gl = Arc::new(gl);
+        let egui_painter = egui_overlay::build_egui_painter(&gl)?;
         let program = compile_program(&gl)?;
         let vao = unsafe { gl.create_vertex_array().map_err(anyhow::Error::msg)? };
+        let mut depth = depth::DepthState::new(&gl, &display);
+        if let Some(convention) = std::env::var("CUBIC_DEPTH")
+            .ok()
+            .and_then(|s| DepthConvention::from_name(&s))
+        {
+            depth.set_convention(convention);
+        }
 
         unsafe {
             gl.bind_vertex_array(Some(vao));
             gl.bind_vertex_array(None);
-            gl.enable(glow::FRAMEBUFFER_SRGB);
+            if fb_srgb {
+                gl.enable(glow::FRAMEBUFFER_SRGB);
+            }
             gl.enable(glow::CULL_FACE);
             gl.front_face(glow::CCW);
             gl.cull_face(glow::BACK);
             gl.bind_vertex_array(None);
-            gl.disable(glow::DEPTH_TEST);
         }
 
         let initial_vsync = true;
+        let mut swap = swap::SwapControl::new(&display);
+        swap.apply(&surface, &context, initial_vsync);
 
-        let _ = surface.set_swap_interval(
-            &context,
-            if initial_vsync {
-                SwapInterval::Wait(NonZeroU32::new(1).unwrap())
-            } else {
-                SwapInterval::DontWait
-            },
-        );
-
-        Ok(Self {
-            //display,
+        let mut renderer = Self {
             context,
-            surface,
+            surface: Some(surface),
+            display,
+            config,
+            window_raw: wh,
             gl,
             size,
             clear: [0.02, 0.02, 0.04, 1.0],
             program,
             vao,
             vsync: initial_vsync,
-        })
+            swap,
+            target_fps: None,
+            pacer: FramePacer::new(),
+            frame_stats: FrameStatsTracker::default(),
+            latency_mode: LatencyMode::Throughput,
+            egui_painter,
+            egui_pending: None,
+            fb_srgb,
+            hdr: std::env::var("CUBIC_HDR").ok().as_deref() == Some("1"),
+            srgb_encode: std::env::var("CUBIC_SRGB_ENCODE").ok().as_deref() != Some("0"),
+            tonemap: std::env::var("CUBIC_TONEMAP")
+                .ok()
+                .and_then(|s| TonemapOperator::from_name(&s))
+                .unwrap_or_default(),
+            depth,
+            scene_pass: None,
+            surface_change: None,
+            capture_next: false,
+            captured: None,
+        };
+        // The initial interval may already have needed the fallback cap.
+        renderer.update_pacer();
+        renderer.sync_scene_pass()?;
+        Ok(renderer)
     }
 
-    fn resize(&mut self, size: RenderSize) -> Result<()> {
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()> {
+        let previous = self
+            .surface_change
+            .map_or(self.surface_info(), |c| c.previous);
         self.size = size;
+        // Suspended: resume() creates the surface at this size.
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+        let _span =
+            tracing::debug_span!("recreate", width = size.width, height = size.height).entered();
 
         let w = NonZeroU32::new(size.width).unwrap();
         let h = NonZeroU32::new(size.height).unwrap();
 
-        self.surface.resize(&self.context, w, h);
+        surface.resize(&self.context, w, h);
+        if let Some(pass) = &mut self.scene_pass {
+            pass.resize(&self.gl, size)?;
+        }
         self.set_vsync(self.vsync);
+        self.surface_change = Some(SurfaceChanged {
+            previous,
+            current: self.surface_info(),
+        });
 
         Ok(())
     }
+    fn suspend(&mut self) -> RenderResult<()> {
+        if self.surface.is_none() {
+            return Ok(());
+        }
+        // Everything but the window surface lives in the context, which
+        // keeps it while not current.
+        unsafe { self.gl.finish() };
+        self.context
+            .make_not_current_in_place()
+            .context("suspend: make_not_current")?;
+        self.surface = None;
+        tracing::info!("gl: suspended, window surface released");
+        Ok(())
+    }
+
+    fn resume(&mut self) -> RenderResult<()> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+        let surface =
+            Self::create_surface(&self.display, &self.config, self.window_raw, self.size)?;
+        self.context
+            .make_current(&surface)
+            .context("resume: make_current")?;
+        self.surface = Some(surface);
+        // A new surface starts at the driver's default interval.
+        self.set_vsync(self.vsync);
+        if let Some(pass) = &mut self.scene_pass {
+            pass.resize(&self.gl, self.size)?;
+        }
+        self.sync_scene_pass()?;
+        tracing::info!("gl: resumed at {}x{}", self.size.width, self.size.height);
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+
     fn set_clear_color(&mut self, rgba: [f32; 4]) {
         self.clear = rgba;
     }
-    fn render(&mut self) -> Result<()> {
-        if self.size.width == 0 || self.size.height == 0 {
+    fn surface_info(&self) -> SurfaceInfo {
+        // A double-buffered 8-bit RGBA window surface, sRGB-encoded by
+        // FRAMEBUFFER_SRGB when make_current got an sRGB one.
+        SurfaceInfo {
+            format: if self.fb_srgb {
+                "RGBA8_SRGB"
+            } else {
+                "RGBA8_UNORM"
+            },
+            color_space: "SRGB_NONLINEAR",
+            extent: self.size,
+            present_mode: match self.swap.applied() {
+                swap::Interval::Immediate => PresentMode::Immediate,
+                swap::Interval::Vsync => PresentMode::Fifo,
+            },
+            hdr: false,
+            image_count: 2,
+        }
+    }
+    fn render(&mut self) -> RenderResult<()> {
+        if self.surface.is_none() || self.size.width == 0 || self.size.height == 0 {
             return Ok(());
         }
-
+        let cpu_start = std::time::Instant::now();
+        // No acquire or submit stage of its own on GL: the driver does
+        // both inside swap_buffers, which the present span covers.
+        let _frame = tracing::debug_span!("frame", backend = "gl").entered();
+
+        let record = tracing::debug_span!("record").entered();
+        if let Some(pass) = &self.scene_pass {
+            pass.bind(&self.gl);
+        }
         unsafe {
             self.gl
                 .viewport(0, 0, self.size.width as i32, self.size.height as i32);
             self.gl
                 .clear_color(self.clear[0], self.clear[1], self.clear[2], self.clear[3]);
+            self.depth.apply(&self.gl);
 
-            self.gl.clear(glow::COLOR_BUFFER_BIT);
+            self.gl
+                .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
             self.gl.use_program(Some(self.program));
             self.gl.bind_vertex_array(Some(self.vao));
             self.gl.draw_arrays(glow::TRIANGLES, 0, 3);
             self.gl.bind_vertex_array(None);
             self.gl.use_program(None);
         }
+        if let Some(pass) = &self.scene_pass {
+            let op = if self.hdr {
+                self.tonemap
+            } else {
+                TonemapOperator::Clamp
+            };
+            pass.resolve(&self.gl, op, !self.fb_srgb && self.srgb_encode);
+        }
+
+        self.paint_egui();
+        if std::mem::take(&mut self.capture_next) {
+            self.captured = Some(self.read_back());
+        }
+        drop(record);
+
+        if let Some(surface) = &self.surface {
+            let _present = tracing::debug_span!("present").entered();
+            surface
+                .swap_buffers(&self.context)
+                .map_err(|e| match e.error_kind() {
+                    ErrorKind::ContextLost => RenderError::DeviceLost { stage: "present" },
+                    ErrorKind::BadSurface | ErrorKind::BadNativeWindow => RenderError::SurfaceLost,
+                    _ => anyhow::Error::new(e).context("swap_buffers").into(),
+                })?;
+        }
+        if self.swap.observe_swap(std::time::Instant::now()) {
+            self.update_pacer();
+        }
+        self.frame_stats.record(cpu_start.elapsed());
+        let _pace = tracing::debug_span!("pace").entered();
+        if self.latency_mode == LatencyMode::Low {
+            // GL can't see presents; finishing the frame's commands keeps
+            // the driver from buffering the next ones behind it.
+            unsafe { self.gl.finish() };
+        }
+        self.pacer.wait();
+
+        Ok(())
+    }
+
+    fn set_vsync(&mut self, on: bool) {
+        self.vsync = on;
+        // Suspended: resume() applies it to the new surface.
+        if let Some(surface) = &self.surface {
+            self.swap.apply(surface, &self.context, on);
+        }
+        self.update_pacer();
+    }
+
+    fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.target_fps = fps.filter(|&f| f > 0);
+        self.update_pacer();
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        self.frame_stats.stats()
+    }
+
+    fn set_latency_mode(&mut self, mode: LatencyMode) {
+        self.latency_mode = mode;
+    }
+
+    fn set_frame_spike_threshold(&mut self, threshold: Option<std::time::Duration>) {
+        self.frame_stats.set_spike_threshold(threshold);
+    }
+
+    fn record_stream_counts(&mut self, counts: StreamCounts) {
+        self.frame_stats.record_streaming(counts);
+    }
+
+    fn depth_convention(&self) -> DepthConvention {
+        self.depth.convention()
+    }
 
-        self.surface
-            .swap_buffers(&self.context)
-            .context("swap_buffers")?;
+    /// Either convention; the next render() applies it.
+    fn set_depth_convention(&mut self, convention: DepthConvention) -> bool {
+        self.depth.set_convention(convention);
+        true
+    }
+
+    fn queue_egui(
+        &mut self,
+        textures_delta: egui::TexturesDelta,
+        paint_jobs: Vec<egui::ClippedPrimitive>,
+        screen_width: u32,
+        screen_height: u32,
+        pixels_per_point: f32,
+    ) {
+        self.egui_pending = Some(egui_overlay::EguiFrame {
+            textures_delta,
+            paint_jobs,
+            screen_width,
+            screen_height,
+            pixels_per_point,
+        });
+    }
+}
+
+impl GlRenderer {
+    /// One-line description of the presentation setup, for the debug
+    /// overlay. GL exposes far less than a Vulkan swapchain: the swap
+    /// interval in effect and what the display offers around it.
+    pub fn present_summary(&self) -> String {
+        let mut s = format!(
+            "GL 3.3 default framebuffer, swap interval {}",
+            self.swap.applied().as_i32()
+        );
+        if let Some(cap) = self.swap.forced_cap() {
+            s.push_str(&format!(" (driver ignores vsync, capped at {cap} fps)"));
+        }
+        if self.swap.damage_supported() {
+            s.push_str(", swap with damage available");
+        }
+        s.push_str(if self.fb_srgb {
+            ", sRGB framebuffer"
+        } else {
+            ", linear framebuffer"
+        });
+        s.push_str(&format!(
+            ", {} depth{}",
+            self.depth.convention().name(),
+            if self.depth.zero_to_one() {
+                ""
+            } else {
+                " (no clip control)"
+            }
+        ));
+        if self.hdr {
+            s.push_str(&format!(", FP16 scene, {} tonemap", self.tonemap.name()));
+        } else if self.scene_pass.is_some() {
+            s.push_str(", FP16 scene, sRGB encoded in shader");
+        }
+        s
+    }
 
+    /// Read the next rendered frame back from the window, overlay and
+    /// all; take_captured_frame then has it. For screenshots and golden
+    /// images.
+    pub fn capture_next_frame(&mut self) {
+        self.capture_next = true;
+        self.captured = None;
+    }
+
+    /// The frame capture_next_frame asked for, once it has rendered.
+    pub fn take_captured_frame(&mut self) -> Option<CapturedFrame> {
+        self.captured.take()
+    }
+
+    /// The back buffer as it stands, before the swap. GL's rows run
+    /// bottom up; flipped here.
+    fn read_back(&self) -> CapturedFrame {
+        let (width, height) = (self.size.width, self.size.height);
+        let row = width as usize * 4;
+        let mut pixels = vec![0u8; row * height as usize];
+        unsafe {
+            self.gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            self.gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            self.gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(Some(&mut pixels)),
+            );
+        }
+        let rgba = pixels.chunks_exact(row).rev().flatten().copied().collect();
+        CapturedFrame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// Render the scene to an FP16 target and tonemap it down, the GL
+    /// stand-in for the Vulkan backend's HDR output (GL itself only
+    /// presents SDR).
+    pub fn set_hdr_enabled(&mut self, on: bool) {
+        self.hdr = on;
+        self.sync_scene_pass_or_warn();
+    }
+
+    /// Encode to sRGB in the output pass when the default framebuffer is
+    /// linear. No effect on an sRGB framebuffer, which always encodes.
+    pub fn set_srgb_encode(&mut self, on: bool) {
+        self.srgb_encode = on;
+        self.sync_scene_pass_or_warn();
+    }
+
+    pub fn set_tonemap_operator(&mut self, op: TonemapOperator) {
+        self.tonemap = op;
+    }
+
+    fn sync_scene_pass_or_warn(&mut self) {
+        if let Err(e) = self.sync_scene_pass() {
+            tracing::warn!("gl: scene pass unavailable ({e:#}); drawing straight to the window");
+        }
+    }
+
+    /// Create or drop the scene pass to match hdr / srgb_encode / fb_srgb.
+    fn sync_scene_pass(&mut self) -> Result<()> {
+        // No current context while suspended; resume() syncs.
+        if self.surface.is_none() {
+            return Ok(());
+        }
+        let wanted = self.hdr || (!self.fb_srgb && self.srgb_encode);
+        match (wanted, self.scene_pass.take()) {
+            (true, None) => self.scene_pass = Some(hdr::ScenePass::new(&self.gl, self.size)?),
+            (true, Some(pass)) => self.scene_pass = Some(pass),
+            (false, Some(pass)) => pass.destroy(&self.gl),
+            (false, None) => {}
+        }
         Ok(())
     }
+
+    /// `vsync_mode = "fifo_relaxed"`: adaptive vsync, which GL can't do
+    /// (see swap.rs). Logs that vsync stays plain; nothing else changes.
+    pub fn set_adaptive_vsync(&mut self, on: bool) {
+        self.swap.set_adaptive(on);
+    }
+
+    /// The display's refresh rate, for the fallback cap used when the
+    /// driver won't do vsync (see swap.rs). 60 until told otherwise.
+    pub fn set_refresh_rate_hint(&mut self, hz: f32) {
+        self.swap.set_refresh_hz(hz);
+    }
+
+    fn update_pacer(&mut self) {
+        let cap = match (self.target_fps, self.swap.forced_cap()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.pacer.set_target_fps(cap);
+    }
+}
+
+impl Drop for GlRenderer {
+    fn drop(&mut self) {
+        // Painter::destroy frees its GL objects; it must run while the
+        // context is still current, i.e. before `context` drops below.
+        // Same for the scene pass. While suspended nothing is current;
+        // the objects go with the context instead.
+        if self.surface.is_none() {
+            return;
+        }
+        self.egui_painter.destroy();
+        if let Some(pass) = self.scene_pass.take() {
+            pass.destroy(&self.gl);
+        }
+    }
 }
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Swap interval control behind GlRenderer::set_vsync. glutin only knows
+//! "wait N vblanks" and "don't wait"; on top of that:
+//!
+//! - no adaptive vsync. GlRenderer always presents through EGL (CGL on
+//!   macOS), which has no negative interval, so `vsync_mode =
+//!   "fifo_relaxed"` falls back to plain vsync, with a warning the first
+//!   time it's asked for (see set_adaptive);
+//! - EGL_KHR/EXT_swap_buffers_with_damage detection, logged and reported
+//!   in the present summary. Every frame redraws the whole window, so
+//!   there's no damage smaller than the surface to pass yet;
+//! - a fallback FPS cap at the display's refresh rate when the driver won't
+//!   honour vsync: either set_swap_interval fails outright, or swaps keep
+//!   coming back far faster than a vblank (a driver panel forcing vsync
+//!   off). The cap combines with the app's own target_fps, lower wins.
+
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+use glutin::{
+    context::PossiblyCurrentContext,
+    display::{Display, GetDisplayExtensions as _},
+    prelude::*,
+    surface::{Surface, SwapInterval, WindowSurface},
+};
+
+/// Swaps watched before deciding whether vsync is being honoured.
+const PROBE_FRAMES: u32 = 120;
+
+/// The swap interval actually in effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Interval {
+    Immediate,
+    Vsync,
+}
+
+impl Interval {
+    pub(crate) fn as_i32(self) -> i32 {
+        match self {
+            Self::Immediate => 0,
+            Self::Vsync => 1,
+        }
+    }
+}
+
+pub(crate) struct SwapControl {
+    damage_supported: bool,
+    // fifo_relaxed was asked for and told it's plain vsync here; said once.
+    adaptive_warned: bool,
+    applied: Interval,
+    // vsync of the last apply; re-applying the same request (GlRenderer
+    // does on every resize) keeps what the check found.
+    requested: Option<bool>,
+    refresh_hz: f32,
+    // The refresh-rate cap while the driver isn't doing vsync for us.
+    forced_cap: Option<u32>,
+    last_swap: Option<Instant>,
+    probed: u32,
+    fast: u32,
+}
+
+/// The display's extensions. Only the platform displays list them, not
+/// glutin's Display wrapper; GlRenderer always asks for EGL outside macOS.
+fn display_extensions(display: &Display) -> HashSet<&'static str> {
+    match display {
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        Display::Egl(egl) => egl.extensions().clone(),
+        #[allow(unreachable_patterns)]
+        _ => HashSet::new(),
+    }
+}
+
+impl SwapControl {
+    pub(crate) fn new(display: &Display) -> Self {
+        let ext = display_extensions(display);
+        let damage_supported = ext.contains("EGL_KHR_swap_buffers_with_damage")
+            || ext.contains("EGL_EXT_swap_buffers_with_damage");
+        tracing::info!(
+            "gl: swap with damage {}",
+            if damage_supported {
+                "available"
+            } else {
+                "unavailable"
+            }
+        );
+        Self {
+            damage_supported,
+            adaptive_warned: false,
+            applied: Interval::Vsync,
+            requested: None,
+            refresh_hz: 60.0,
+            forced_cap: None,
+            last_swap: None,
+            probed: 0,
+            fast: 0,
+        }
+    }
+
+    pub(crate) fn applied(&self) -> Interval {
+        self.applied
+    }
+
+    pub(crate) fn damage_supported(&self) -> bool {
+        self.damage_supported
+    }
+
+    pub(crate) fn forced_cap(&self) -> Option<u32> {
+        self.forced_cap
+    }
+
+    /// Adaptive vsync was asked for (`fifo_relaxed`). EGL can't do it, so
+    /// this only warns, once, that vsync stays plain.
+    pub(crate) fn set_adaptive(&mut self, on: bool) {
+        if on && !self.adaptive_warned {
+            self.adaptive_warned = true;
+            tracing::warn!(
+                "gl: vsync_mode fifo_relaxed needs a negative swap interval, which EGL \
+                 doesn't have; using plain vsync"
+            );
+        }
+    }
+
+    pub(crate) fn set_refresh_hz(&mut self, hz: f32) {
+        if hz > 0.0 {
+            self.refresh_hz = hz;
+        }
+    }
+
+    /// Set the interval for `vsync` (see the module docs), returning what
+    /// ended up in effect. A different request than last time restarts
+    /// the vsync check.
+    pub(crate) fn apply(
+        &mut self,
+        surface: &Surface<WindowSurface>,
+        context: &PossiblyCurrentContext,
+        vsync: bool,
+    ) -> Interval {
+        if self.requested != Some(vsync) {
+            self.requested = Some(vsync);
+            self.forced_cap = None;
+            self.last_swap = None;
+            self.probed = 0;
+            self.fast = 0;
+        }
+
+        let interval = if vsync {
+            SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+        } else {
+            SwapInterval::DontWait
+        };
+        self.applied = match (surface.set_swap_interval(context, interval), vsync) {
+            (Ok(()), true) => Interval::Vsync,
+            (Ok(()), false) => Interval::Immediate,
+            (Err(e), true) => {
+                let cap = self.refresh_cap();
+                tracing::warn!("gl: driver refused swap interval 1 ({e}); capping at {cap} fps");
+                self.forced_cap = Some(cap);
+                Interval::Immediate
+            }
+            (Err(e), false) => {
+                tracing::warn!("gl: driver refused swap interval 0 ({e}); vsync stays on");
+                Interval::Vsync
+            }
+        };
+        self.applied
+    }
+
+    /// Call after every swap. While vsync is meant to be on, watches for
+    /// swaps returning much faster than a refresh; if nearly all of the
+    /// first PROBE_FRAMES do, the driver is ignoring the interval and the
+    /// fallback cap goes on. Returns true when that happens.
+    pub(crate) fn observe_swap(&mut self, now: Instant) -> bool {
+        let last = self.last_swap.replace(now);
+        if self.applied == Interval::Immediate
+            || self.forced_cap.is_some()
+            || self.probed >= PROBE_FRAMES
+        {
+            return false;
+        }
+        let Some(last) = last else {
+            return false;
+        };
+        let period = Duration::from_secs_f32(1.0 / self.refresh_hz);
+        self.probed += 1;
+        if now - last < period / 2 {
+            self.fast += 1;
+        }
+        if self.probed == PROBE_FRAMES && self.fast * 10 >= PROBE_FRAMES * 9 {
+            let cap = self.refresh_cap();
+            tracing::warn!("gl: driver is ignoring vsync; capping at {cap} fps instead");
+            self.forced_cap = Some(cap);
+            return true;
+        }
+        false
+    }
+
+    fn refresh_cap(&self) -> u32 {
+        self.refresh_hz.round().max(1.0) as u32
+    }
+}
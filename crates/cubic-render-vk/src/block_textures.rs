@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Block-face textures as one texture array (set 1, binding 2), so a whole
+//! chunk mesh draws with one material however many block types it holds.
+//!
+//! An array rather than an atlas: greedy-merged quads tile a texture
+//! across several blocks with UVs past 1.0, which a layer repeats like any
+//! 2D texture and an atlas tile can't without wrapping in the shader, and
+//! layers can't bleed into each other as they're minified.
+//!
+//! upload_block_textures takes equally sized square layers (cubic_world's
+//! BlockTextureArray packs them) and returns the material that samples
+//! them, albedo from the layer each vertex's tex_index names. Uploading
+//! again replaces the array and keeps the material. The layers are kept
+//! for device-lost recovery, which uploads them again under the same
+//! material handle.
+
+use anyhow::{bail, Result};
+use ash::vk;
+use cubic_render::RenderResult;
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::material::{MaterialDesc, MaterialHandle};
+use crate::resources::create_texture_array;
+use crate::VkRenderer;
+
+/// The uploaded array and the material drawing with it.
+pub(crate) struct BlockTextures {
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+    // Source layers, for device-lost recovery.
+    layers: Vec<u8>,
+    size: u32,
+    count: u32,
+    pub(crate) material: MaterialHandle,
+}
+
+impl BlockTextures {
+    /// The uploaded layers, size and count, for device-lost recovery to
+    /// upload again.
+    pub(crate) fn take_source(&mut self) -> (Vec<u8>, u32, u32) {
+        (std::mem::take(&mut self.layers), self.size, self.count)
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(self.alloc);
+    }
+}
+
+impl VkRenderer {
+    /// Upload the block texture array: `count` layers of `size` x `size`
+    /// RGBA8 sRGB texels, back to back. Returns the material to draw chunk
+    /// meshes with (draw_mesh_with_material), whose vertices' tex_index is
+    /// then a layer. Sampled with cubic.toml's settings, repeating.
+    pub fn upload_block_textures(
+        &mut self,
+        layers: &[u8],
+        size: u32,
+        count: u32,
+    ) -> RenderResult<MaterialHandle> {
+        self.upload_block_array(layers, size, count)?;
+        if let Some(material) = self.block_material() {
+            return Ok(material);
+        }
+        let material = self.create_material(MaterialDesc {
+            block_array: true,
+            ..Default::default()
+        })?;
+        if let Some(block) = self.block_textures.as_mut() {
+            block.material = material;
+        }
+        Ok(material)
+    }
+
+    /// The material upload_block_textures returned, while it's alive.
+    pub fn block_material(&self) -> Option<MaterialHandle> {
+        let material = self.block_textures.as_ref()?.material;
+        (material != MaterialHandle::DEFAULT && self.material_desc(material).is_some())
+            .then_some(material)
+    }
+
+    /// The array half of upload_block_textures: replace the image and point
+    /// every material set at it. Device-lost recovery calls this directly,
+    /// then puts the old material handle back itself.
+    pub(crate) fn upload_block_array(
+        &mut self,
+        layers: &[u8],
+        size: u32,
+        count: u32,
+    ) -> Result<()> {
+        let max_layers = unsafe {
+            self.instance
+                .get_physical_device_properties(self.phys)
+                .limits
+                .max_image_array_layers
+        };
+        if size == 0 || count == 0 || count > max_layers {
+            bail!("upload_block_textures: {count} layers of {size}x{size} (at most {max_layers})");
+        }
+        if layers.len() != size as usize * size as usize * 4 * count as usize {
+            bail!("upload_block_textures: expected {count} layers of {size}x{size} RGBA8");
+        }
+        let sampler = self.samplers.get(&self.device, &self.sampler_config)?;
+        // Binding 2 is rewritten in place in sets frames in flight bind;
+        // the old array itself is retired through the trash queue.
+        if self.block_textures.is_some() {
+            self.wait_frames_in_flight()?;
+        }
+        let (image, alloc, view) = create_texture_array(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.queue,
+            self.cmd_pool,
+            layers,
+            size,
+            count,
+        )?;
+
+        let material = match self.block_textures.take() {
+            Some(old) => {
+                self.retire_image(old.image, old.alloc, old.view);
+                old.material
+            }
+            None => MaterialHandle::DEFAULT,
+        };
+        self.material_pool
+            .write_block_array(&self.device, view, sampler);
+        self.block_textures = Some(BlockTextures {
+            image,
+            alloc,
+            view,
+            layers: layers.to_vec(),
+            size,
+            count,
+            material,
+        });
+        Ok(())
+    }
+}
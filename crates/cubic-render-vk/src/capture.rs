@@ -0,0 +1,579 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Video capture: while start_capture is on, every presented frame (or
+//! every Nth) is copied out of the swapchain image into a small ring of
+//! host-visible buffers, and a writer thread turns those into a PNG
+//! sequence, a raw stream, a video encoded by ffmpeg, or (stopped after
+//! a frame) a single screenshot.
+//!
+//! The copy is a "capture" pass at the very end of the frame graph, so the
+//! file shows what was presented, text and egui included. A ring slot is
+//! read back at the start of a later frame, once the timeline says the GPU
+//! is past it, so capture never waits on the GPU. When every slot is still
+//! in flight, or the writer is WRITER_QUEUE frames behind (PNG encoding at
+//! 4K isn't quick), that frame is skipped; stop_capture reports how many.
+//!
+//! Only 8-bit swapchain formats can be captured: an HDR swapchain's
+//! 10-bit or FP16 pixels would need a conversion pass first. A resize
+//! carries an image sequence on at the new size but ends a raw or ffmpeg
+//! stream, whose frame size is fixed.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, bail, Context, Result};
+use ash::vk;
+use cubic_render::RenderResult;
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+use tracing::{info, warn};
+
+use crate::error::vk_error;
+use crate::frame_graph::{Access, FrameGraph, ResourceId};
+use crate::resources::create_buffer_and_memory;
+use crate::VkRenderer;
+
+/// Read-back buffers; one more than frames usually in flight.
+const RING_LEN: usize = 3;
+/// Frames handed to the writer thread and not yet written.
+const WRITER_QUEUE: usize = 8;
+
+/// Where captured frames go.
+#[derive(Clone, Debug)]
+pub enum CaptureSink {
+    /// frame_000000.png, frame_000001.png, ... in `dir` (created if
+    /// missing).
+    ImageSequence { dir: PathBuf },
+    /// One image at `path`, format from the extension (.png, .jpg, ...);
+    /// each captured frame overwrites the last, so a capture stopped after
+    /// one frame is a screenshot.
+    Image { path: PathBuf },
+    /// Every frame's pixels back to back in one file, 4 bytes each in the
+    /// swapchain's channel order (BGRA or RGBA, logged at start).
+    Raw { path: PathBuf },
+    /// Piped into `ffmpeg` (from PATH) as raw video at `fps` and encoded
+    /// to `path`; container and codec follow the extension (.mp4: H.264).
+    Ffmpeg { path: PathBuf, fps: u32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct CaptureConfig {
+    pub sink: CaptureSink,
+    /// Capture one presented frame in this many; 0 counts as 1.
+    pub every_nth: u32,
+}
+
+/// What a capture produced, from stop_capture.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    pub frames_written: u64,
+    /// Due frames dropped because the ring or the writer was full.
+    pub frames_skipped: u64,
+}
+
+/// BGRA (true) or RGBA (false) bytes, for the formats capture handles.
+fn channel_order(format: vk::Format) -> Option<bool> {
+    match format {
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => Some(true),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::A8B8G8R8_UNORM_PACK32
+        | vk::Format::A8B8G8R8_SRGB_PACK32 => Some(false),
+        _ => None,
+    }
+}
+
+struct Frame {
+    width: u32,
+    height: u32,
+    bgra: bool,
+    pixels: Vec<u8>,
+}
+
+/// The writer thread's end of a CaptureSink.
+enum Sink {
+    Images {
+        dir: PathBuf,
+    },
+    Image(PathBuf),
+    Raw(BufWriter<File>),
+    Ffmpeg {
+        child: Child,
+        stdin: BufWriter<ChildStdin>,
+    },
+}
+
+impl Sink {
+    fn open(sink: &CaptureSink, extent: vk::Extent2D, bgra: bool) -> Result<Self> {
+        Ok(match sink {
+            CaptureSink::ImageSequence { dir } => {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("creating {}", dir.display()))?;
+                Self::Images { dir: dir.clone() }
+            }
+            CaptureSink::Image { path } => {
+                if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir)
+                        .with_context(|| format!("creating {}", dir.display()))?;
+                }
+                Self::Image(path.clone())
+            }
+            CaptureSink::Raw { path } => Self::Raw(BufWriter::new(
+                File::create(path).with_context(|| format!("creating {}", path.display()))?,
+            )),
+            CaptureSink::Ffmpeg { path, fps } => {
+                let size = format!("{}x{}", extent.width, extent.height);
+                let fps = (*fps).max(1).to_string();
+                let mut child = Command::new("ffmpeg")
+                    .args(["-loglevel", "error", "-y", "-f", "rawvideo"])
+                    .args(["-pixel_format", if bgra { "bgra" } else { "rgba" }])
+                    .args(["-video_size", &size, "-framerate", &fps, "-i", "-"])
+                    // yuv420p (what players expect) needs even dimensions.
+                    .args([
+                        "-vf",
+                        "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                        "-pix_fmt",
+                        "yuv420p",
+                    ])
+                    .arg(path)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .context("starting ffmpeg (is it on PATH?)")?;
+                let stdin = child.stdin.take().expect("ffmpeg stdin is piped");
+                Self::Ffmpeg {
+                    child,
+                    stdin: BufWriter::new(stdin),
+                }
+            }
+        })
+    }
+
+    fn write(&mut self, frame: &Frame, index: u64) -> Result<()> {
+        match self {
+            Self::Images { dir } => {
+                save_image(frame, &dir.join(format!("frame_{index:06}.png")))?;
+            }
+            Self::Image(path) => save_image(frame, path)?,
+            Self::Raw(file) => file.write_all(&frame.pixels)?,
+            Self::Ffmpeg { stdin, .. } => stdin
+                .write_all(&frame.pixels)
+                .context("writing to ffmpeg")?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Images { .. } | Self::Image(_) => {}
+            Self::Raw(mut file) => file.flush()?,
+            Self::Ffmpeg { mut child, stdin } => {
+                // Closing stdin is ffmpeg's end of input.
+                stdin.into_inner().map_err(|e| e.into_error())?;
+                let status = child.wait()?;
+                if !status.success() {
+                    bail!("ffmpeg exited with {status}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `frame` as an RGB image file, format from `path`'s extension.
+fn save_image(frame: &Frame, path: &Path) -> Result<()> {
+    let rgb: Vec<u8> = frame
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|p| {
+            if frame.bgra {
+                [p[2], p[1], p[0]]
+            } else {
+                [p[0], p[1], p[2]]
+            }
+        })
+        .collect();
+    image::save_buffer(
+        path,
+        &rgb,
+        frame.width,
+        frame.height,
+        image::ExtendedColorType::Rgb8,
+    )
+    .with_context(|| format!("writing {}", path.display()))
+}
+
+fn run_writer(mut sink: Sink, frames: Receiver<Frame>) -> Result<u64> {
+    let mut written = 0;
+    for frame in frames {
+        sink.write(&frame, written)?;
+        written += 1;
+    }
+    sink.finish()?;
+    Ok(written)
+}
+
+fn create_ring(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    extent: vk::Extent2D,
+) -> Result<Vec<(vk::Buffer, Allocation)>> {
+    let size = extent.width as u64 * extent.height as u64 * 4;
+    let mut ring = Vec::with_capacity(RING_LEN);
+    for _ in 0..RING_LEN {
+        match create_buffer_and_memory(
+            device,
+            allocator,
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+            "capture readback",
+        ) {
+            Ok(slot) => ring.push(slot),
+            Err(e) => {
+                destroy_ring(device, allocator, ring);
+                return Err(e);
+            }
+        }
+    }
+    Ok(ring)
+}
+
+fn destroy_ring(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    ring: Vec<(vk::Buffer, Allocation)>,
+) {
+    for (buffer, alloc) in ring {
+        unsafe { device.destroy_buffer(buffer, None) };
+        let _ = allocator.free(alloc);
+    }
+}
+
+pub(crate) struct Capture {
+    ring: Vec<(vk::Buffer, Allocation)>,
+    extent: vk::Extent2D,
+    bgra: bool,
+    // A raw or ffmpeg stream: can't change size.
+    fixed_size: bool,
+    every_nth: u64,
+    presented: u64,
+    // Ring slot this frame copies into: it's a captured frame and one was
+    // free.
+    next: Option<usize>,
+    // Copied slots, oldest first, with the timeline value retiring each.
+    in_flight: VecDeque<(usize, u64)>,
+    skipped: u64,
+    // None once the writer has hung up (it failed; finish says why).
+    frames: Option<SyncSender<Frame>>,
+    writer: JoinHandle<Result<u64>>,
+}
+
+impl Capture {
+    /// Read back every slot the GPU is done with (timeline at `retired`)
+    /// and queue it for the writer, oldest first.
+    fn collect(&mut self, retired: u64) {
+        while let Some(&(slot, value)) = self.in_flight.front() {
+            if value > retired {
+                break;
+            }
+            self.in_flight.pop_front();
+            let Some(frames) = self.frames.as_ref() else {
+                continue;
+            };
+            let Some(pixels) = self.ring[slot].1.mapped_slice() else {
+                continue;
+            };
+            let len = self.extent.width as usize * self.extent.height as usize * 4;
+            let frame = Frame {
+                width: self.extent.width,
+                height: self.extent.height,
+                bgra: self.bgra,
+                pixels: pixels[..len].to_vec(),
+            };
+            match frames.try_send(frame) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => self.skipped += 1,
+                Err(TrySendError::Disconnected(_)) => self.frames = None,
+            }
+        }
+    }
+
+    /// Count a presented frame and, if it's due, claim a free slot for it.
+    fn begin_frame(&mut self) {
+        let due = self.presented.is_multiple_of(self.every_nth);
+        self.presented += 1;
+        self.next = None;
+        if !due {
+            return;
+        }
+        self.next = (0..RING_LEN).find(|s| !self.in_flight.iter().any(|&(i, _)| i == *s));
+        if self.next.is_none() {
+            self.skipped += 1;
+        }
+    }
+
+    /// Free the ring and wait for the writer to drain; anything still in
+    /// flight is dropped, so collect first.
+    fn finish(mut self, device: &ash::Device, allocator: &mut Allocator) -> Result<CaptureStats> {
+        destroy_ring(device, allocator, std::mem::take(&mut self.ring));
+        drop(self.frames.take());
+        let frames_written = self
+            .writer
+            .join()
+            .map_err(|_| anyhow!("capture writer panicked"))??;
+        Ok(CaptureStats {
+            frames_written,
+            frames_skipped: self.skipped,
+        })
+    }
+}
+
+impl VkRenderer {
+    /// Start copying presented frames to `cfg.sink` (see module docs).
+    /// Fails if a capture is already running, the swapchain isn't 8-bit or
+    /// copyable, or the sink can't be opened.
+    pub fn start_capture(&mut self, cfg: CaptureConfig) -> RenderResult<()> {
+        if self.capture.is_some() {
+            return Err(anyhow!("start_capture: already capturing (see stop_capture)").into());
+        }
+        if !self
+            .swapchain_usage
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            return Err(anyhow!("start_capture: swapchain images aren't TRANSFER_SRC").into());
+        }
+        let bgra = channel_order(self.format)
+            .ok_or_else(|| anyhow!("start_capture: can't capture {:?}", self.format))?;
+        let sink = Sink::open(&cfg.sink, self.extent, bgra)?;
+        let ring = create_ring(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.extent,
+        )?;
+        let (frames, rx) = mpsc::sync_channel(WRITER_QUEUE);
+        let writer = match std::thread::Builder::new()
+            .name("capture writer".into())
+            .spawn(move || run_writer(sink, rx))
+        {
+            Ok(writer) => writer,
+            Err(e) => {
+                let allocator = self.allocator.as_mut().expect("allocator missing");
+                destroy_ring(&self.device, allocator, ring);
+                return Err(anyhow::Error::from(e)
+                    .context("capture writer thread")
+                    .into());
+            }
+        };
+        info!(
+            "vk: capturing {}x{} {} frames to {:?}",
+            self.extent.width,
+            self.extent.height,
+            if bgra { "BGRA" } else { "RGBA" },
+            cfg.sink
+        );
+        self.capture = Some(Capture {
+            ring,
+            extent: self.extent,
+            bgra,
+            fixed_size: !matches!(
+                cfg.sink,
+                CaptureSink::ImageSequence { .. } | CaptureSink::Image { .. }
+            ),
+            every_nth: cfg.every_nth.max(1) as u64,
+            presented: 0,
+            next: None,
+            in_flight: VecDeque::new(),
+            skipped: 0,
+            frames: Some(frames),
+            writer,
+        });
+        Ok(())
+    }
+
+    /// End the capture, once the frames already copied are written out.
+    /// Returns the writer's error if it failed (a full disk, ffmpeg
+    /// exiting); all zeros if nothing was capturing.
+    pub fn stop_capture(&mut self) -> RenderResult<CaptureStats> {
+        if self.capture.is_none() {
+            return Ok(CaptureStats::default());
+        }
+        if self.timeline_value > 0 {
+            let wait_info = vk::SemaphoreWaitInfo {
+                s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+                semaphore_count: 1,
+                p_semaphores: &self.timeline,
+                p_values: &self.timeline_value,
+                ..Default::default()
+            };
+            unsafe {
+                self.device
+                    .wait_semaphores(&wait_info, u64::MAX)
+                    .map_err(|e| vk_error(e, "wait_semaphores"))?
+            };
+        }
+        Ok(self.end_capture(self.timeline_value)?)
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Collect whatever `retired` covers, then stop.
+    pub(crate) fn end_capture(&mut self, retired: u64) -> Result<CaptureStats> {
+        let Some(mut capture) = self.capture.take() else {
+            return Ok(CaptureStats::default());
+        };
+        capture.collect(retired);
+        let stats = capture.finish(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        )?;
+        info!(
+            "vk: capture stopped: {} frames written, {} skipped",
+            stats.frames_written, stats.frames_skipped
+        );
+        Ok(stats)
+    }
+
+    /// Per frame, before recording: hand retired copies to the writer and
+    /// pick this frame's slot. Ends the capture if the writer has failed.
+    pub(crate) fn capture_begin_frame(&mut self, retired: u64) {
+        let Some(capture) = self.capture.as_mut() else {
+            return;
+        };
+        capture.collect(retired);
+        if capture.frames.is_some() {
+            capture.begin_frame();
+            return;
+        }
+        if let Err(e) = self.end_capture(retired) {
+            warn!("vk: capture stopped: {e:#}");
+        }
+    }
+
+    /// This frame's commands were submitted, to retire at `value`.
+    pub(crate) fn capture_submitted(&mut self, value: u64) {
+        if let Some(capture) = self.capture.as_mut() {
+            if let Some(slot) = capture.next.take() {
+                capture.in_flight.push_back((slot, value));
+            }
+        }
+    }
+
+    /// Follow a swapchain rebuild: an image sequence gets a ring at the
+    /// new size; a fixed-size stream, or a format capture can't read, ends
+    /// the capture. Either way the frames still copying into the old ring
+    /// are waited for and read back first.
+    pub(crate) fn sync_capture(&mut self) {
+        let Some(capture) = self.capture.as_mut() else {
+            return;
+        };
+        let bgra = channel_order(self.format);
+        if capture.extent == self.extent && bgra == Some(capture.bgra) {
+            return;
+        }
+        if let Some(&(_, last)) = capture.in_flight.back() {
+            let wait_info = vk::SemaphoreWaitInfo {
+                s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+                semaphore_count: 1,
+                p_semaphores: &self.timeline,
+                p_values: &last,
+                ..Default::default()
+            };
+            if let Err(e) = unsafe { self.device.wait_semaphores(&wait_info, u64::MAX) } {
+                warn!("vk: capture: waiting for copies in flight: {e:?}");
+            }
+        }
+        capture.collect(self.timeline_value);
+        let reason = if bgra.is_none() {
+            format!("swapchain format is now {:?}", self.format)
+        } else if capture.fixed_size {
+            "the stream's frame size is fixed".to_owned()
+        } else {
+            let allocator = self.allocator.as_mut().expect("allocator missing");
+            match create_ring(&self.device, allocator, self.extent) {
+                Ok(ring) => {
+                    destroy_ring(
+                        &self.device,
+                        allocator,
+                        std::mem::replace(&mut capture.ring, ring),
+                    );
+                    capture.extent = self.extent;
+                    capture.bgra = bgra.unwrap_or(capture.bgra);
+                    return;
+                }
+                Err(e) => format!("no read-back ring at the new size ({e:#})"),
+            }
+        };
+        warn!("vk: capture ended by swapchain rebuild: {reason}");
+        if let Err(e) = self.end_capture(self.timeline_value) {
+            warn!("vk: capture stopped: {e:#}");
+        }
+    }
+
+    /// The "capture" pass: this frame's swapchain image into its ring slot,
+    /// after everything else has drawn. Nothing when this frame isn't
+    /// captured.
+    pub(crate) fn add_capture_pass(
+        &self,
+        g: &mut FrameGraph,
+        swapchain: ResourceId,
+        image: vk::Image,
+    ) {
+        let Some(slot) = self.capture.as_ref().and_then(|c| c.next) else {
+            return;
+        };
+        // Read on the host after an earlier frame retired; the export's
+        // final barrier makes this frame's copy visible to that read.
+        let readback = g.import_buffer("capture readback", Access::host_read());
+        g.export(readback, Some(Access::host_read()));
+        g.add_pass(
+            "capture",
+            &[
+                (swapchain, Access::transfer_read()),
+                (readback, Access::transfer_write()),
+            ],
+            move |r, cmd| {
+                r.record_capture(cmd, image, slot);
+                Ok(())
+            },
+        );
+    }
+
+    fn record_capture(&self, cmd: vk::CommandBuffer, image: vk::Image, slot: usize) {
+        let Some(capture) = self.capture.as_ref() else {
+            return;
+        };
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D::default(),
+            image_extent: vk::Extent3D {
+                width: capture.extent.width,
+                height: capture.extent.height,
+                depth: 1,
+            },
+        };
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                cmd,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                capture.ring[slot].0,
+                std::slice::from_ref(&region),
+            );
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Accessibility colour filters: grayscale, colour-blindness simulation,
+//! and daltonization (shifting the colours a viewer can't tell apart into
+//! ones they can).
+//!
+//! Every filter is one 3x3 matrix on linear RGB, so it runs as the last
+//! step of the post-processing chain (see post.rs), after grading and
+//! ahead of the tonemap, and forces that chain on like any effect does.
+//! Simulation uses Machado et al.'s 2009 matrices at full severity;
+//! daltonization adds the simulation's error back, redistributed onto the
+//! channels the viewer still sees (Fidaner et al.).
+
+use cubic_render::RenderSize;
+
+use crate::VkRenderer;
+
+/// The kind of dichromacy a ColorFilter simulates or corrects for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorBlindness {
+    /// No long-wavelength (red) cones.
+    Protanopia,
+    /// No medium-wavelength (green) cones.
+    Deuteranopia,
+    /// No short-wavelength (blue) cones.
+    Tritanopia,
+}
+
+/// set_color_filter's choices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorFilter {
+    #[default]
+    Off,
+    Grayscale,
+    /// Show the image as a viewer with this deficiency sees it.
+    Simulate(ColorBlindness),
+    /// Adjust the image so a viewer with this deficiency can tell apart
+    /// more of what they'd otherwise confuse.
+    Daltonize(ColorBlindness),
+}
+
+type Mat3 = [[f32; 3]; 3];
+
+const IDENTITY: Mat3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Rec. 709 luminance in every channel.
+const GRAYSCALE: Mat3 = [
+    [0.2126, 0.7152, 0.0722],
+    [0.2126, 0.7152, 0.0722],
+    [0.2126, 0.7152, 0.0722],
+];
+
+impl ColorBlindness {
+    /// Machado, Oliveira and Fernandes (2009), severity 1.0, linear RGB.
+    fn simulation(self) -> Mat3 {
+        match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// Where daltonization moves the error: red-green losses into green
+    /// and blue, blue-yellow losses into red and green.
+    fn error_shift(self) -> Mat3 {
+        match self {
+            Self::Protanopia | Self::Deuteranopia => {
+                [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]
+            }
+            Self::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+        }
+    }
+}
+
+fn mul(a: Mat3, b: Mat3) -> Mat3 {
+    std::array::from_fn(|r| std::array::from_fn(|c| (0..3).map(|k| a[r][k] * b[k][c]).sum()))
+}
+
+impl ColorFilter {
+    /// Names for cubic.toml and CUBIC_COLOR_FILTER: off, grayscale,
+    /// protanopia, deuteranopia, tritanopia, and daltonize_ plus one of
+    /// the last three.
+    pub(crate) fn from_name(s: &str) -> Option<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let blindness = |s: &str| match s {
+            "protanopia" => Some(ColorBlindness::Protanopia),
+            "deuteranopia" => Some(ColorBlindness::Deuteranopia),
+            "tritanopia" => Some(ColorBlindness::Tritanopia),
+            _ => None,
+        };
+        match s.as_str() {
+            "off" | "none" => Some(Self::Off),
+            "grayscale" | "greyscale" => Some(Self::Grayscale),
+            _ => match s.strip_prefix("daltonize_") {
+                Some(rest) => blindness(rest).map(Self::Daltonize),
+                None => blindness(&s).map(Self::Simulate),
+            },
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Grayscale => "grayscale",
+            Self::Simulate(ColorBlindness::Protanopia) => "protanopia",
+            Self::Simulate(ColorBlindness::Deuteranopia) => "deuteranopia",
+            Self::Simulate(ColorBlindness::Tritanopia) => "tritanopia",
+            Self::Daltonize(ColorBlindness::Protanopia) => "daltonize protanopia",
+            Self::Daltonize(ColorBlindness::Deuteranopia) => "daltonize deuteranopia",
+            Self::Daltonize(ColorBlindness::Tritanopia) => "daltonize tritanopia",
+        }
+    }
+
+    /// The filter's matrix, row by row, each padded to a vec4 for
+    /// post.frag's push constants.
+    pub(crate) fn rows(self) -> [[f32; 4]; 3] {
+        let m = match self {
+            Self::Off => IDENTITY,
+            Self::Grayscale => GRAYSCALE,
+            Self::Simulate(b) => b.simulation(),
+            // rgb + shift * (rgb - sim * rgb) = (I + shift * (I - sim)) rgb
+            Self::Daltonize(b) => {
+                let sim = b.simulation();
+                let error: Mat3 =
+                    std::array::from_fn(|r| std::array::from_fn(|c| IDENTITY[r][c] - sim[r][c]));
+                let shift = mul(b.error_shift(), error);
+                std::array::from_fn(|r| std::array::from_fn(|c| IDENTITY[r][c] + shift[r][c]))
+            }
+        };
+        m.map(|[x, y, z]| [x, y, z, 0.0])
+    }
+}
+
+impl VkRenderer {
+    /// Apply `filter` to the final image (see module docs). Recreates the
+    /// swapchain if it changes, as set_post_effects does.
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        if self.cfg.color_filter == filter {
+            return;
+        }
+        self.cfg.color_filter = filter;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    pub fn color_filter(&self) -> ColorFilter {
+        self.cfg.color_filter
+    }
+}
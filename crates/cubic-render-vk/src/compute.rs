@@ -0,0 +1,584 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! User compute work: pipelines built from a SPIR-V module plus a list of
+//! storage-buffer bindings, renderer-owned GPU buffers for them to work on,
+//! and a per-frame queue of dispatches.
+//!
+//! Queued work (`dispatch`, `copy_buffer_to_mesh`) is recorded as the
+//! first pass of the next frame's graph (see frame_graph.rs), ahead of the
+//! indirect-cull prepass and the scene, in call order. Barriers are the
+//! renderer's job: each op is fenced off from the next, and the graph
+//! fences the whole batch from the graphics work around it, so a dispatch sees everything earlier ops wrote and the frame's
+//! draws see everything the batch wrote. Compute results reach graphics
+//! either as a mesh's vertices (`copy_buffer_to_mesh`, then draw the mesh as
+//! usual) or as vertex/index/indirect data read straight from a GpuBuffer.
+//!
+//! Storage buffers only for now; storage images arrive with render targets.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use ash::vk;
+use cubic_render::{MeshHandle, RenderResult};
+use gpu_allocator::vulkan::Allocation;
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::PooledSet;
+use crate::error::vk_error;
+use crate::pipeline::{create_compute_pipeline_specialized, load_spv_file, shader_dir, SpecValue};
+use crate::reflect::{check_specialization, reflect};
+use crate::resources::create_buffer_and_memory_shared;
+use crate::sync::memory_barrier2;
+use crate::{DeferredDrop, GpuResource, VkRenderer};
+
+/// How a compute shader uses one of its storage buffers. Only used to
+/// decide barriers (a dispatch that writes nothing needs none after it);
+/// the shader's own `readonly`/`writeonly` qualifiers still apply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// A compute pipeline (see `VkRenderer::register_compute_pipeline`).
+/// `bindings[n]` is `layout(set = 0, binding = n) buffer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputeDesc {
+    /// Lookup key for `compute_pipeline_by_name`; must be unique.
+    pub name: String,
+    /// SPIR-V file name, resolved against the shader directory like
+    /// `PipelineDesc`'s shaders.
+    pub shader: String,
+    pub bindings: Vec<BufferAccess>,
+    /// Bytes of push constants `dispatch` takes (multiple of 4, 0 = none).
+    pub push_constant_size: u32,
+    /// Specialization constants by constant_id, as `PipelineDesc`'s.
+    pub specialization: BTreeMap<u32, SpecValue>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputePipelineHandle(pub u32);
+
+/// A renderer-owned device-local buffer (`create_gpu_buffer`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GpuBufferHandle(pub u32);
+
+/// A set of buffers bound to one compute pipeline's bindings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputeBindingsHandle(pub u32);
+
+pub(crate) struct ComputePipeline {
+    pub(crate) desc: ComputeDesc,
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+pub(crate) struct GpuBuffer {
+    buffer: vk::Buffer,
+    alloc: Allocation,
+    size: u64,
+}
+
+pub(crate) struct ComputeBindings {
+    pipeline: u32,
+    set: PooledSet,
+}
+
+/// One queued piece of compute-phase work, recorded by `record_compute`.
+pub(crate) enum ComputeOp {
+    Dispatch {
+        pipeline: u32,
+        set: vk::DescriptorSet,
+        push: Vec<u8>,
+        groups: [u32; 3],
+        // Any binding other than BufferAccess::Read.
+        writes: bool,
+    },
+    CopyToMesh {
+        src: vk::Buffer,
+        src_offset: u64,
+        dst_offset: u64,
+        size: u64,
+    },
+}
+
+impl ComputePipeline {
+    pub(crate) fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+impl GpuBuffer {
+    pub(crate) fn destroy(
+        self,
+        device: &ash::Device,
+        allocator: &mut gpu_allocator::vulkan::Allocator,
+    ) {
+        unsafe { device.destroy_buffer(self.buffer, None) };
+        let _ = allocator.free(self.alloc);
+    }
+}
+
+impl VkRenderer {
+    /// Build a compute pipeline from `desc` (through the shared pipeline
+    /// cache). Fails on a missing/invalid shader or push constants the
+    /// device can't hold.
+    pub fn register_compute_pipeline(
+        &mut self,
+        desc: ComputeDesc,
+    ) -> RenderResult<ComputePipelineHandle> {
+        if self.compute_pipeline_by_name(&desc.name).is_some() {
+            return Err(anyhow!(
+                "register_compute_pipeline: a pipeline named {:?} already exists",
+                desc.name
+            )
+            .into());
+        }
+        let limits = unsafe { self.instance.get_physical_device_properties(self.phys) }.limits;
+        if !desc.push_constant_size.is_multiple_of(4)
+            || desc.push_constant_size > limits.max_push_constants_size
+        {
+            return Err(anyhow!(
+                "register_compute_pipeline: {:?}: push constant size {} must be a multiple of 4 and at most {}",
+                desc.name,
+                desc.push_constant_size,
+                limits.max_push_constants_size
+            )
+            .into());
+        }
+        let words = load_spv_file(&shader_dir().join(&desc.shader))?;
+        reflect(&words)
+            .and_then(|shader| check_specialization(&[&shader], &desc.specialization))
+            .with_context(|| format!("register_compute_pipeline: {}", desc.shader))?;
+
+        let bindings: Vec<_> = (0..desc.bindings.len() as u32)
+            .map(|binding| vk::DescriptorSetLayoutBinding {
+                binding,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                ..Default::default()
+            })
+            .collect();
+        let set_ci = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            binding_count: bindings.len() as u32,
+            p_bindings: bindings.as_ptr(),
+            ..Default::default()
+        };
+        let set_layout = unsafe {
+            self.device
+                .create_descriptor_set_layout(&set_ci, None)
+                .map_err(|e| vk_error(e, "create_descriptor_set_layout"))?
+        };
+
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: desc.push_constant_size,
+        };
+        let layout_ci = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &set_layout,
+            push_constant_range_count: (desc.push_constant_size > 0) as u32,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = match unsafe { self.device.create_pipeline_layout(&layout_ci, None) } {
+            Ok(l) => l,
+            Err(e) => {
+                unsafe { self.device.destroy_descriptor_set_layout(set_layout, None) };
+                return Err(vk_error(e, "create_pipeline_layout").into());
+            }
+        };
+        let pipeline = match create_compute_pipeline_specialized(
+            &self.device,
+            self.pipeline_cache,
+            layout,
+            &words,
+            &desc.specialization,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                unsafe {
+                    self.device.destroy_pipeline_layout(layout, None);
+                    self.device.destroy_descriptor_set_layout(set_layout, None);
+                }
+                return Err(e
+                    .context(format!("compute pipeline {:?}", desc.name))
+                    .into());
+            }
+        };
+        self.compute_pipelines.push(ComputePipeline {
+            desc,
+            set_layout,
+            layout,
+            pipeline,
+        });
+        Ok(ComputePipelineHandle(
+            self.compute_pipelines.len() as u32 - 1,
+        ))
+    }
+
+    pub fn compute_pipeline_by_name(&self, name: &str) -> Option<ComputePipelineHandle> {
+        self.compute_pipelines
+            .iter()
+            .position(|cp| cp.desc.name == name)
+            .map(|i| ComputePipelineHandle(i as u32))
+    }
+
+    /// Allocate an uninitialised device-local buffer of `size` bytes,
+    /// usable as a compute storage buffer and, for graphics, as a vertex/
+    /// index/indirect source or copy source.
+    pub fn create_gpu_buffer(&mut self, size: u64) -> RenderResult<GpuBufferHandle> {
+        if size == 0 {
+            return Err(anyhow!("create_gpu_buffer: size is 0").into());
+        }
+        let (buffer, alloc) = create_buffer_and_memory_shared(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            size,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::INDEX_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            "compute buffer",
+            &self.mesh_families,
+        )?;
+        let buf = GpuBuffer {
+            buffer,
+            alloc,
+            size,
+        };
+        // Reuse a freed slot so handles stay dense.
+        if let Some(i) = self.gpu_buffers.iter().position(Option::is_none) {
+            self.gpu_buffers[i] = Some(buf);
+            return Ok(GpuBufferHandle(i as u32));
+        }
+        self.gpu_buffers.push(Some(buf));
+        Ok(GpuBufferHandle(self.gpu_buffers.len() as u32 - 1))
+    }
+
+    /// Upload `data` into a GpuBuffer at `offset`, through the same
+    /// batched transfer path as `upload_mesh`; visible to the next frame's
+    /// compute and draws.
+    pub fn write_gpu_buffer(
+        &mut self,
+        handle: GpuBufferHandle,
+        offset: u64,
+        data: &[u8],
+    ) -> RenderResult<()> {
+        let buf = self.gpu_buffer(handle)?;
+        if offset + data.len() as u64 > buf.size {
+            return Err(anyhow!(
+                "write_gpu_buffer: {} bytes at {offset} overrun the {}-byte buffer",
+                data.len(),
+                buf.size
+            )
+            .into());
+        }
+        let dst = buf.buffer;
+        Ok(self.uploader.queue_buffer_copy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            dst,
+            offset,
+            data,
+        )?)
+    }
+
+    /// Release a GpuBuffer once the GPU is done with it. Bindings that
+    /// reference it must not be dispatched afterwards.
+    pub fn free_gpu_buffer(&mut self, handle: GpuBufferHandle) {
+        if let Some(buf) = self
+            .gpu_buffers
+            .get_mut(handle.0 as usize)
+            .and_then(Option::take)
+        {
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::Buffer {
+                    buffer: buf.buffer,
+                    alloc: buf.alloc,
+                },
+            });
+        }
+    }
+
+    /// Bind `buffers` (one per `ComputeDesc::bindings` entry, in order,
+    /// each bound whole) for dispatches of `pipeline`. Bindings live until
+    /// `free_compute_bindings`, so build them once, not per frame.
+    pub fn create_compute_bindings(
+        &mut self,
+        pipeline: ComputePipelineHandle,
+        buffers: &[GpuBufferHandle],
+    ) -> RenderResult<ComputeBindingsHandle> {
+        let cp = self
+            .compute_pipelines
+            .get(pipeline.0 as usize)
+            .ok_or_else(|| anyhow!("create_compute_bindings: unknown pipeline {pipeline:?}"))?;
+        if buffers.len() != cp.desc.bindings.len() {
+            return Err(anyhow!(
+                "create_compute_bindings: {:?} takes {} buffers, got {}",
+                cp.desc.name,
+                cp.desc.bindings.len(),
+                buffers.len()
+            )
+            .into());
+        }
+        let infos = buffers
+            .iter()
+            .map(|&h| {
+                self.gpu_buffer(h).map(|b| vk::DescriptorBufferInfo {
+                    buffer: b.buffer,
+                    offset: 0,
+                    range: vk::WHOLE_SIZE,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let set = self
+            .descriptors
+            .persistent
+            .allocate(&self.device, cp.set_layout)
+            .context("create_compute_bindings")?;
+        let writes: Vec<_> = infos
+            .iter()
+            .enumerate()
+            .map(|(i, info)| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set.set,
+                dst_binding: i as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                p_buffer_info: info,
+                ..Default::default()
+            })
+            .collect();
+        unsafe { self.device.update_descriptor_sets(&writes, &[]) };
+
+        let bindings = ComputeBindings {
+            pipeline: pipeline.0,
+            set,
+        };
+        if let Some(i) = self.compute_bindings.iter().position(Option::is_none) {
+            self.compute_bindings[i] = Some(bindings);
+            return Ok(ComputeBindingsHandle(i as u32));
+        }
+        self.compute_bindings.push(Some(bindings));
+        Ok(ComputeBindingsHandle(
+            self.compute_bindings.len() as u32 - 1,
+        ))
+    }
+
+    pub fn free_compute_bindings(&mut self, handle: ComputeBindingsHandle) {
+        if let Some(b) = self
+            .compute_bindings
+            .get_mut(handle.0 as usize)
+            .and_then(Option::take)
+        {
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::DescriptorSet(b.set),
+            });
+        }
+    }
+
+    /// Queue `groups` workgroups of `pipeline` for the next frame, reading
+    /// and writing the buffers in `bindings`. `push` must be exactly the
+    /// pipeline's `push_constant_size` bytes.
+    pub fn dispatch(
+        &mut self,
+        pipeline: ComputePipelineHandle,
+        bindings: ComputeBindingsHandle,
+        push: &[u8],
+        groups: [u32; 3],
+    ) -> RenderResult<()> {
+        let cp = self
+            .compute_pipelines
+            .get(pipeline.0 as usize)
+            .ok_or_else(|| anyhow!("dispatch: unknown pipeline {pipeline:?}"))?;
+        let b = self
+            .compute_bindings
+            .get(bindings.0 as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("dispatch: unknown or freed bindings {bindings:?}"))?;
+        if b.pipeline != pipeline.0 {
+            return Err(anyhow!(
+                "dispatch: bindings {bindings:?} were made for another pipeline than {:?}",
+                cp.desc.name
+            )
+            .into());
+        }
+        if push.len() != cp.desc.push_constant_size as usize {
+            return Err(anyhow!(
+                "dispatch: {:?} takes {} bytes of push constants, got {}",
+                cp.desc.name,
+                cp.desc.push_constant_size,
+                push.len()
+            )
+            .into());
+        }
+        let limits = unsafe { self.instance.get_physical_device_properties(self.phys) }.limits;
+        if groups
+            .iter()
+            .zip(limits.max_compute_work_group_count)
+            .any(|(&g, max)| g > max)
+        {
+            return Err(anyhow!(
+                "dispatch: {groups:?} workgroups over the device's {:?}",
+                limits.max_compute_work_group_count
+            )
+            .into());
+        }
+        if groups.contains(&0) {
+            return Ok(());
+        }
+        let set = b.set.set;
+        let writes = cp.desc.bindings.iter().any(|&a| a != BufferAccess::Read);
+        self.pending_compute.push(ComputeOp::Dispatch {
+            pipeline: pipeline.0,
+            set,
+            push: push.to_vec(),
+            groups,
+            writes,
+        });
+        Ok(())
+    }
+
+    /// Queue a copy of `mesh`'s whole vertex data from `src` (starting at
+    /// `src_offset`, in the mesh's vertex layout) into the mesh, after any
+    /// dispatches queued before it. The way for a compute pass to generate
+    /// or deform geometry that's then drawn with draw_mesh as usual.
+    pub fn copy_buffer_to_mesh(
+        &mut self,
+        src: GpuBufferHandle,
+        src_offset: u64,
+        mesh: MeshHandle,
+    ) -> RenderResult<()> {
+        let (dst_offset, size) = self
+            .mesh_vertex_bytes(mesh)
+            .ok_or_else(|| anyhow!("copy_buffer_to_mesh: unknown or freed mesh {mesh:?}"))?;
+        let buf = self.gpu_buffer(src)?;
+        if src_offset + size > buf.size {
+            return Err(anyhow!(
+                "copy_buffer_to_mesh: mesh needs {size} bytes from offset {src_offset}, buffer holds {}",
+                buf.size
+            )
+            .into());
+        }
+        let src = buf.buffer;
+        self.pending_compute.push(ComputeOp::CopyToMesh {
+            src,
+            src_offset,
+            dst_offset,
+            size,
+        });
+        Ok(())
+    }
+
+    fn gpu_buffer(&self, handle: GpuBufferHandle) -> Result<&GpuBuffer> {
+        self.gpu_buffers
+            .get(handle.0 as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("unknown or freed GPU buffer {handle:?}"))
+    }
+
+    /// Record this frame's queued compute ops, as the frame graph's
+    /// "compute" pass. Ops are fenced off from each other here; ordering
+    /// against earlier frames' draws and this frame's is the graph's job.
+    pub(crate) fn record_compute(&self, cmd: vk::CommandBuffer) {
+        for op in &self.pending_compute {
+            match op {
+                ComputeOp::Dispatch {
+                    pipeline,
+                    set,
+                    push,
+                    groups,
+                    writes,
+                } => {
+                    let cp = &self.compute_pipelines[*pipeline as usize];
+                    unsafe {
+                        self.device.cmd_bind_pipeline(
+                            cmd,
+                            vk::PipelineBindPoint::COMPUTE,
+                            cp.pipeline,
+                        );
+                        self.device.cmd_bind_descriptor_sets(
+                            cmd,
+                            vk::PipelineBindPoint::COMPUTE,
+                            cp.layout,
+                            0,
+                            std::slice::from_ref(set),
+                            &[],
+                        );
+                        if !push.is_empty() {
+                            self.device.cmd_push_constants(
+                                cmd,
+                                cp.layout,
+                                vk::ShaderStageFlags::COMPUTE,
+                                0,
+                                push,
+                            );
+                        }
+                        self.device
+                            .cmd_dispatch(cmd, groups[0], groups[1], groups[2]);
+                    }
+                    // Later dispatches and copies see this one's writes.
+                    if !writes {
+                        continue;
+                    }
+                    memory_barrier2(
+                        &self.device,
+                        cmd,
+                        vk::PipelineStageFlags2::COMPUTE_SHADER,
+                        vk::AccessFlags2::SHADER_WRITE,
+                        vk::PipelineStageFlags2::COMPUTE_SHADER | vk::PipelineStageFlags2::COPY,
+                        vk::AccessFlags2::SHADER_READ
+                            | vk::AccessFlags2::SHADER_WRITE
+                            | vk::AccessFlags2::TRANSFER_READ,
+                    );
+                }
+                ComputeOp::CopyToMesh {
+                    src,
+                    src_offset,
+                    dst_offset,
+                    size,
+                } => {
+                    let region = vk::BufferCopy {
+                        src_offset: *src_offset,
+                        dst_offset: *dst_offset,
+                        size: *size,
+                    };
+                    unsafe {
+                        self.device.cmd_copy_buffer(
+                            cmd,
+                            *src,
+                            self.shared_vbuf,
+                            std::slice::from_ref(&region),
+                        )
+                    };
+                    memory_barrier2(
+                        &self.device,
+                        cmd,
+                        vk::PipelineStageFlags2::COPY,
+                        vk::AccessFlags2::TRANSFER_WRITE,
+                        vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT
+                            | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                        vk::AccessFlags2::VERTEX_ATTRIBUTE_READ | vk::AccessFlags2::SHADER_READ,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Crash reports for VK_ERROR_DEVICE_LOST. When render() sees the device
+//! go, it writes `crash_reports/vk-<unix time>.txt` (next to cubic.toml)
+//! with what the driver can still tell us, for users to attach to issues:
+//!
+//! - VK_EXT_device_fault: the driver's description of the fault, the GPU
+//!   addresses involved (page faults, bad instruction pointers) and
+//!   vendor-specific fault codes. A vendor binary dump, where the driver
+//!   offers one, goes next to the report as `.bin` for the vendor's tools.
+//! - VK_NV_device_diagnostic_checkpoints: every frame graph pass drops a
+//!   checkpoint as it starts; after the loss the queue reports the last
+//!   one its top and bottom of pipe got to, i.e. which pass the GPU was
+//!   in when it hung.
+//!
+//! Both are optional; without either the report still records the device,
+//! driver, stage and present setup. Neither costs anything until a device
+//! is lost, beyond one vkCmdSetCheckpointNV per pass.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use ash::vk;
+
+use crate::VkRenderer;
+
+const REPORT_DIR: &str = "crash_reports";
+
+/// Fault query and checkpoint loaders for whichever of the two extensions
+/// the device enabled, and the pass names checkpoints refer to.
+pub(crate) struct CrashDiagnostics {
+    fault: Option<ash::ext::device_fault::DeviceFn>,
+    checkpoints: Option<ash::nv::device_diagnostic_checkpoints::Device>,
+    // Checkpoint markers are opaque pointers; ours are index + 1 into this.
+    names: Vec<&'static str>,
+}
+
+impl CrashDiagnostics {
+    pub(crate) fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        has_device_fault: bool,
+        has_checkpoints: bool,
+    ) -> Self {
+        let fault = has_device_fault.then(|| {
+            ash::ext::device_fault::DeviceFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+            })
+        });
+        Self {
+            fault,
+            checkpoints: has_checkpoints
+                .then(|| ash::nv::device_diagnostic_checkpoints::Device::new(instance, device)),
+            names: Vec::new(),
+        }
+    }
+
+    /// Mark the start of pass `name` in `cmd` (see the module docs).
+    pub(crate) fn checkpoint(&mut self, cmd: vk::CommandBuffer, name: &'static str) {
+        let Some(loader) = &self.checkpoints else {
+            return;
+        };
+        let index = match self.names.iter().position(|&n| n == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+        };
+        let marker = (index + 1) as *const std::ffi::c_void;
+        unsafe { loader.cmd_set_checkpoint(cmd, marker) };
+    }
+
+    /// Fault description, addresses and vendor codes, plus the vendor
+    /// binary if there is one.
+    fn fault_section(&self, device: vk::Device, out: &mut String) -> Option<Vec<u8>> {
+        let Some(fp) = &self.fault else {
+            out.push_str("fault: VK_EXT_device_fault not supported\n");
+            return None;
+        };
+        let mut counts = vk::DeviceFaultCountsEXT {
+            s_type: vk::StructureType::DEVICE_FAULT_COUNTS_EXT,
+            ..Default::default()
+        };
+        let r =
+            unsafe { (fp.get_device_fault_info_ext)(device, &mut counts, std::ptr::null_mut()) };
+        if r != vk::Result::SUCCESS {
+            let _ = writeln!(out, "fault: vkGetDeviceFaultInfoEXT (counts) failed: {r:?}");
+            return None;
+        }
+        let mut addresses =
+            vec![vk::DeviceFaultAddressInfoEXT::default(); counts.address_info_count as usize];
+        let mut vendor =
+            vec![vk::DeviceFaultVendorInfoEXT::default(); counts.vendor_info_count as usize];
+        let mut binary = vec![0u8; counts.vendor_binary_size as usize];
+        let mut info = vk::DeviceFaultInfoEXT {
+            s_type: vk::StructureType::DEVICE_FAULT_INFO_EXT,
+            p_address_infos: addresses.as_mut_ptr(),
+            p_vendor_infos: vendor.as_mut_ptr(),
+            p_vendor_binary_data: if binary.is_empty() {
+                std::ptr::null_mut()
+            } else {
+                binary.as_mut_ptr().cast()
+            },
+            ..Default::default()
+        };
+        // INCOMPLETE just means the driver trimmed something; report what
+        // did come back.
+        let r = unsafe { (fp.get_device_fault_info_ext)(device, &mut counts, &mut info) };
+        if r != vk::Result::SUCCESS && r != vk::Result::INCOMPLETE {
+            let _ = writeln!(out, "fault: vkGetDeviceFaultInfoEXT failed: {r:?}");
+            return None;
+        }
+        addresses.truncate(counts.address_info_count as usize);
+        vendor.truncate(counts.vendor_info_count as usize);
+        binary.truncate(counts.vendor_binary_size as usize);
+
+        let description = info
+            .description_as_c_str()
+            .map(|d| d.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let _ = writeln!(out, "fault: {description}");
+        for a in &addresses {
+            let _ = writeln!(
+                out,
+                "  address {:?}: 0x{:016x} (+/- 0x{:x})",
+                a.address_type, a.reported_address, a.address_precision
+            );
+        }
+        for v in &vendor {
+            let description = v
+                .description_as_c_str()
+                .map(|d| d.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "  vendor: {description} (code 0x{:x}, data 0x{:x})",
+                v.vendor_fault_code, v.vendor_fault_data
+            );
+        }
+        (!binary.is_empty()).then_some(binary)
+    }
+
+    /// The last checkpoint each pipe stage of `queue` reached.
+    fn checkpoint_section(&self, queue: vk::Queue, out: &mut String) {
+        let Some(loader) = &self.checkpoints else {
+            out.push_str("checkpoints: VK_NV_device_diagnostic_checkpoints not supported\n");
+            return;
+        };
+        let len = unsafe { loader.get_queue_checkpoint_data_len(queue) };
+        let mut data = vec![
+            vk::CheckpointDataNV {
+                s_type: vk::StructureType::CHECKPOINT_DATA_NV,
+                ..Default::default()
+            };
+            len
+        ];
+        unsafe { loader.get_queue_checkpoint_data(queue, &mut data) };
+        if data.is_empty() {
+            out.push_str("checkpoints: none reached\n");
+            return;
+        }
+        out.push_str("checkpoints (last pass each stage reached):\n");
+        for c in &data {
+            let index = c.p_checkpoint_marker as usize;
+            let name = index
+                .checked_sub(1)
+                .and_then(|i| self.names.get(i))
+                .copied()
+                .unwrap_or("<unknown>");
+            let _ = writeln!(out, "  {:?}: {name}", c.stage);
+        }
+    }
+}
+
+impl VkRenderer {
+    /// Write a crash report for a device lost during `stage` (see the
+    /// module docs). Returns where it went; failures are only logged, the
+    /// caller has a device to recover either way.
+    pub(crate) fn write_crash_report(&self, stage: &str) -> Option<PathBuf> {
+        let props = unsafe { self.instance.get_physical_device_properties(self.phys) };
+        let mut out = String::new();
+        let _ = writeln!(out, "cubic Vulkan crash report");
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let _ = writeln!(out, "time: {time} (unix)");
+        let _ = writeln!(out, "stage: {stage}");
+        let _ = writeln!(
+            out,
+            "device: {} (vendor 0x{:04x}, device 0x{:04x}, {:?})",
+            props
+                .device_name_as_c_str()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            props.vendor_id,
+            props.device_id,
+            props.device_type
+        );
+        let _ = writeln!(
+            out,
+            "driver: 0x{:08x}, Vulkan {}.{}.{}",
+            props.driver_version,
+            vk::api_version_major(props.api_version),
+            vk::api_version_minor(props.api_version),
+            vk::api_version_patch(props.api_version)
+        );
+        let _ = writeln!(out, "render path: {:?}", self.path);
+        let _ = writeln!(out, "present: {}", self.present_summary());
+        let v = self.validation.counts();
+        let _ = writeln!(
+            out,
+            "validation: {} errors, {} warnings, {} suppressed",
+            v.errors, v.warnings, v.suppressed
+        );
+        out.push('\n');
+        let binary = self
+            .crash_diag
+            .fault_section(self.device.handle(), &mut out);
+        self.crash_diag.checkpoint_section(self.queue, &mut out);
+
+        let dir = PathBuf::from(REPORT_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::error!("vk: couldn't create {}: {e}", dir.display());
+            return None;
+        }
+        let path = dir.join(format!("vk-{time}.txt"));
+        if let Some(binary) = binary {
+            let bin = path.with_extension("bin");
+            if let Err(e) = std::fs::write(&bin, binary) {
+                tracing::warn!("vk: couldn't write {}: {e}", bin.display());
+            }
+        }
+        match std::fs::write(&path, out) {
+            Ok(()) => {
+                tracing::error!(
+                    "vk: device lost; crash report written to {}",
+                    path.display()
+                );
+                Some(path)
+            }
+            Err(e) => {
+                tracing::error!("vk: couldn't write {}: {e}", path.display());
+                None
+            }
+        }
+    }
+}
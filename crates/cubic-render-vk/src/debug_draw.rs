@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Immediate-mode debug lines: chunk bounds, physics shapes, camera
+//! frusta. Every `debug_*` call adds world-space line segments for the
+//! next frame only; render() draws and forgets them.
+//!
+//! Points are f64 world positions like Camera::position. They're made
+//! camera-relative (and f32) at record time, the same space model matrices
+//! map into, so lines stay put far from the origin and line up with the
+//! meshes they outline. The segments go up through the staging belt and are
+//! drawn at the end of the scene pass with a LINE_LIST pipeline
+//! (debug_line.vert/.frag): depth-tested against the scene but not writing
+//! depth, alpha-blended, tonemapped with everything else. Without those
+//! shaders built, the debug_* calls do nothing.
+
+use anyhow::Result;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_math::{Camera, DVec3, Vec3};
+use cubic_render::{VertexAttribute, VertexFormat, VertexLayout};
+
+use crate::pipeline::{create_pipeline, BlendMode, PipelineDesc};
+use crate::{DeferredDrop, GpuResource, VkRenderer};
+
+/// Segments per debug_sphere circle.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// Lines past this many in one frame are dropped (and counted in the
+/// warning record_debug_lines logs), so a runaway loop can't eat the whole
+/// staging belt.
+const MAX_DEBUG_LINES: usize = 65536;
+
+// Vertex layout of debug_line.vert.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct LineVertex {
+    pos: [f32; 3],
+    color: [f32; 4],
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct DebugLine {
+    a: DVec3,
+    b: DVec3,
+    color: [f32; 4],
+}
+
+/// The debug line pipeline: a LINE_LIST variant of the scene pipeline with
+/// its own position + colour vertex layout.
+fn debug_line_pipeline_desc() -> PipelineDesc {
+    PipelineDesc {
+        vertex_shader: "debug_line.vert.spv".to_owned(),
+        fragment_shader: "debug_line.frag.spv".to_owned(),
+        blend: BlendMode::Alpha,
+        depth_write: false,
+        vertex_layout: VertexLayout {
+            stride: std::mem::size_of::<LineVertex>() as u32,
+            attributes: vec![
+                VertexAttribute {
+                    location: 0,
+                    offset: 0,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    location: 1,
+                    offset: 12,
+                    format: VertexFormat::Float32x4,
+                },
+            ],
+        },
+        ..PipelineDesc::lines("debug lines")
+    }
+}
+
+impl VkRenderer {
+    /// (Re)build the debug line pipeline against the current scene target,
+    /// retiring the old one. Called at startup and with the scene pipelines
+    /// (see rebuild_scene_pipelines); on failure, debug lines are off.
+    pub(crate) fn sync_debug_line_pipeline(&mut self) {
+        let built = create_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &self.pipeline_config(),
+            &debug_line_pipeline_desc(),
+        );
+        let new = match built {
+            Ok((layout, pipeline, _)) => Some((layout, pipeline)),
+            Err(e) => {
+                tracing::warn!("debug lines disabled: {e:#}");
+                None
+            }
+        };
+        if let Some((layout, pipeline)) = std::mem::replace(&mut self.debug_line_pipeline, new) {
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::Pipeline(pipeline),
+            });
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::PipelineLayout(layout),
+            });
+        }
+    }
+
+    /// A line from `a` to `b` in world space, `color` linear RGBA.
+    pub fn debug_line(&mut self, a: DVec3, b: DVec3, color: [f32; 4]) {
+        if self.debug_line_pipeline.is_none() {
+            return;
+        }
+        if self.debug_lines.len() >= MAX_DEBUG_LINES {
+            self.debug_lines_dropped += 1;
+            return;
+        }
+        self.debug_lines.push(DebugLine { a, b, color });
+    }
+
+    /// The 12 edges of an axis-aligned box, e.g. a chunk's bounds.
+    pub fn debug_aabb(&mut self, min: DVec3, max: DVec3, color: [f32; 4]) {
+        let corner = |i: usize| {
+            DVec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        for i in 0..8 {
+            // Each edge once: from every corner along the axes it's at min on.
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.debug_line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Three axis-aligned circles outlining a sphere.
+    pub fn debug_sphere(&mut self, center: DVec3, radius: f64, color: [f32; 4]) {
+        let axes = [
+            (DVec3::X, DVec3::Y),
+            (DVec3::Y, DVec3::Z),
+            (DVec3::Z, DVec3::X),
+        ];
+        for (u, v) in axes {
+            let point = |i: usize| {
+                let t = i as f64 / CIRCLE_SEGMENTS as f64 * std::f64::consts::TAU;
+                center + (u * t.cos() + v * t.sin()) * radius
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                self.debug_line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    /// World axes at `origin`, `size` long: X red, Y green, Z blue.
+    pub fn debug_axes(&mut self, origin: DVec3, size: f64) {
+        self.debug_line(origin, origin + DVec3::X * size, [1.0, 0.0, 0.0, 1.0]);
+        self.debug_line(origin, origin + DVec3::Y * size, [0.0, 1.0, 0.0, 1.0]);
+        self.debug_line(origin, origin + DVec3::Z * size, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    /// `camera`'s view frustum from its near plane out to `far` (the real
+    /// one has no far plane), for a viewport of `aspect` width/height.
+    pub fn debug_frustum(&mut self, camera: &Camera, aspect: f32, far: f32, color: [f32; 4]) {
+        let forward = camera.forward();
+        let right = forward.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let up = right.cross(forward);
+        let tan = (0.5 * camera.fovy).tan();
+        let rect = |d: f32| {
+            let (h, w) = (d * tan, d * tan * aspect);
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| {
+                camera.position + (forward * d + right * (x * w) + up * (y * h)).as_dvec3()
+            })
+        };
+        let (near, far) = (rect(camera.near), rect(far));
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+            self.debug_line(near[i], near[j], color);
+            self.debug_line(far[i], far[j], color);
+            self.debug_line(near[i], far[i], color);
+        }
+    }
+
+    /// Draw this frame's debug lines, at the end of the scene pass (after
+    /// record_pipeline_draws, with the scene's viewport still set). No-op
+    /// without lines or a pipeline.
+    pub(crate) fn record_debug_lines(&mut self, cmd: vk::CommandBuffer) -> Result<()> {
+        let Some((layout, pipeline)) = self.debug_line_pipeline else {
+            return Ok(());
+        };
+        if self.debug_lines.is_empty() {
+            return Ok(());
+        }
+        if self.debug_lines_dropped > 0 {
+            tracing::warn!(
+                "debug lines: {} over the {MAX_DEBUG_LINES} per-frame limit dropped",
+                self.debug_lines_dropped
+            );
+        }
+        let eye = self.camera.position;
+        let vertices: Vec<LineVertex> = self
+            .debug_lines
+            .iter()
+            .flat_map(|l| {
+                [l.a, l.b].map(|p| LineVertex {
+                    pos: (p - eye).as_vec3().to_array(),
+                    color: l.color,
+                })
+            })
+            .collect();
+        let slice = self.upload_transient(bytemuck::cast_slice(&vertices))?;
+        self.debug_labels.begin(cmd, "debug lines");
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            self.device
+                .cmd_bind_vertex_buffers(cmd, 0, &[slice.buffer()], &[slice.offset()]);
+        }
+        // Into every view, like the rest of the scene.
+        for view in self.scene_views() {
+            self.bind_scene_view(cmd, layout, &[self.camera_set.set], &view);
+            unsafe {
+                self.device.cmd_draw(cmd, vertices.len() as u32, 1, 0, 0);
+            }
+        }
+        self.debug_labels.end(cmd);
+        Ok(())
+    }
+}
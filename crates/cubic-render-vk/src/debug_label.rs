@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! VK_EXT_debug_utils object names and command buffer labels, so RenderDoc
+//! and Nsight captures show "frame_cmd[2]" and a "shadows" region rather
+//! than raw handles and one flat list of commands.
+//!
+//! Only when the instance has VK_EXT_debug_utils: always in debug builds,
+//! and in release builds while the validation layer is on (see
+//! instance.rs). Without it every call here is a no-op.
+
+use std::ffi::CString;
+
+use ash::ext::debug_utils;
+use ash::vk::{self, Handle};
+
+use crate::VkRenderer;
+
+/// Label colour of frame graph passes in capture tools.
+const PASS_COLOR: [f32; 4] = [0.3, 0.6, 0.9, 1.0];
+/// Label colour of `VkRenderer::debug_marker`s.
+const MARKER_COLOR: [f32; 4] = [0.9, 0.7, 0.2, 1.0];
+
+pub(crate) struct DebugLabels {
+    loader: Option<debug_utils::Device>,
+}
+
+impl DebugLabels {
+    /// `enabled`: the instance has VK_EXT_debug_utils.
+    pub(crate) fn new(instance: &ash::Instance, device: &ash::Device, enabled: bool) -> Self {
+        Self {
+            loader: enabled.then(|| debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    pub(crate) fn name<H: Handle>(&self, handle: H, name: &str) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
+        let raw = handle.as_raw();
+        if raw == 0 {
+            return;
+        }
+        let Ok(name) = CString::new(name) else {
+            return;
+        };
+        let info = vk::DebugUtilsObjectNameInfoEXT {
+            s_type: vk::StructureType::DEBUG_UTILS_OBJECT_NAME_INFO_EXT,
+            object_type: H::TYPE,
+            object_handle: raw,
+            p_object_name: name.as_ptr(),
+            ..Default::default()
+        };
+        // Names are a capture nicety; a failure isn't worth surfacing.
+        let _ = unsafe { loader.set_debug_utils_object_name(&info) };
+    }
+
+    /// Open a labelled region; must be closed with `end` in the same
+    /// command buffer.
+    pub(crate) fn begin(&self, cmd: vk::CommandBuffer, label: &str) {
+        self.with_label(label, PASS_COLOR, |loader, info| unsafe {
+            loader.cmd_begin_debug_utils_label(cmd, info)
+        });
+    }
+
+    pub(crate) fn end(&self, cmd: vk::CommandBuffer) {
+        if let Some(loader) = &self.loader {
+            unsafe { loader.cmd_end_debug_utils_label(cmd) };
+        }
+    }
+
+    /// A single point label rather than a region.
+    pub(crate) fn insert(&self, cmd: vk::CommandBuffer, label: &str) {
+        self.with_label(label, MARKER_COLOR, |loader, info| unsafe {
+            loader.cmd_insert_debug_utils_label(cmd, info)
+        });
+    }
+
+    fn with_label(
+        &self,
+        label: &str,
+        color: [f32; 4],
+        f: impl FnOnce(&debug_utils::Device, &vk::DebugUtilsLabelEXT),
+    ) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
+        let Ok(name) = CString::new(label) else {
+            return;
+        };
+        let info = vk::DebugUtilsLabelEXT {
+            s_type: vk::StructureType::DEBUG_UTILS_LABEL_EXT,
+            p_label_name: name.as_ptr(),
+            color,
+            ..Default::default()
+        };
+        f(loader, &info);
+    }
+}
+
+impl VkRenderer {
+    /// Insert a labelled marker at the start of the next frame's commands,
+    /// e.g. "chunk remesh burst" or "level loaded", to find the frame in a
+    /// RenderDoc/Nsight capture. Debug builds only; a no-op in release.
+    pub fn debug_marker(&mut self, label: &str) {
+        if self.debug_labels.loader.is_some() {
+            self.pending_markers.push(label.to_owned());
+        }
+    }
+
+    /// Record (and forget) the markers queued by debug_marker.
+    pub(crate) fn record_debug_markers(&mut self, cmd: vk::CommandBuffer) {
+        for label in self.pending_markers.drain(..) {
+            self.debug_labels.insert(cmd, &label);
+        }
+    }
+
+    /// (Re)name every long-lived object for capture tools. Called after
+    /// startup, swapchain recreation and pipeline registration, which are
+    /// what create them; names are per handle, so renaming is harmless.
+    pub(crate) fn name_objects(&self) {
+        let l = &self.debug_labels;
+        if l.loader.is_none() {
+            return;
+        }
+        l.name(self.shared_vbuf, "mesh_vertex_pool");
+        l.name(self.shared_ibuf, "mesh_index_pool");
+        l.name(self.depth_image, "depth");
+        l.name(self.depth_view, "depth_view");
+        l.name(self.pipeline, "opaque");
+        l.name(self.pipeline_layout, "scene_layout");
+        for (i, np) in self.named_pipelines.iter().enumerate() {
+            l.name(np.pipeline, &np.desc.name);
+            l.name(np.layout, &format!("{}_layout[{}]", np.desc.name, i + 1));
+        }
+        for (i, &image) in self.images.iter().enumerate() {
+            l.name(image, &format!("swapchain[{i}]"));
+        }
+        for (i, &view) in self.image_views.iter().enumerate() {
+            l.name(view, &format!("swapchain_view[{i}]"));
+        }
+        for (i, &cmd) in self.cmd_bufs.iter().enumerate() {
+            l.name(cmd, &format!("frame_cmd[{i}]"));
+        }
+        l.name(self.staging_belt.buffer(), "staging_belt");
+        l.name(self.camera_set.set, "camera_set");
+        l.name(self.shadow_camera_set, "shadow_camera_set");
+        for (i, &buffer) in self.indirect_bufs.iter().enumerate() {
+            l.name(buffer, &format!("indirect_draws[{i}]"));
+        }
+        for (i, &buffer) in self.draw_count_bufs.iter().enumerate() {
+            l.name(buffer, &format!("draw_count[{i}]"));
+        }
+        for (i, &buffer) in self.candidate_bufs.iter().enumerate() {
+            l.name(buffer, &format!("draw_candidates[{i}]"));
+        }
+        for (i, &buffer) in self.bounds_bufs.iter().enumerate() {
+            l.name(buffer, &format!("draw_bounds[{i}]"));
+        }
+        l.name(self.tex_image, "texture[0]");
+        for (i, (image, _, view, _)) in self.tex_store.iter().enumerate() {
+            l.name(*image, &format!("texture[{}]", i + 1));
+            l.name(*view, &format!("texture_view[{}]", i + 1));
+        }
+        l.name(self.shadow.map.image, "shadow_map");
+        if let Some(tm) = &self.tonemap {
+            l.name(tm.image, "hdr_scene_target");
+        }
+        if let Some(text) = &self.text_pass {
+            l.name(text.pipeline, "text");
+        }
+        if let Some(pipeline) = self.skybox.pipeline {
+            l.name(pipeline, "skybox");
+        }
+        if let Some((layout, pipeline)) = self.debug_line_pipeline {
+            l.name(pipeline, "debug_lines");
+            l.name(layout, "debug_lines_layout");
+        }
+    }
+}
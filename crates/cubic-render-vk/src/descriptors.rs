@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Descriptor set allocation shared by every pass.
+//!
+//! A DescriptorAllocator hands sets out of a list of pools and creates
+//! another when the current one runs out, each twice the size of the last
+//! up to a cap, so nothing has to guess its set count up front. Pools are
+//! sized from a per-set ratio table, so one allocator serves any layout
+//! built from those descriptor types.
+//!
+//! The renderer keeps one per lifetime (see Descriptors):
+//! - persistent: sets whose owner frees them (compute bindings, skybox
+//!   cubemaps, the tonemap and FSR1 passes) or that live as long as the
+//!   renderer (the camera sets, the per-frame indirect-draw sets). A freed
+//!   set's space goes back to its pool.
+//! - transient: alloc_transient_set, for the next rendered frame only.
+//!   Like the staging belt, each submit retires the pools used since the
+//!   last one with its timeline value, and they're reset for reuse once
+//!   the timeline has passed it.
+//!
+//! Material sets have an allocator of their own in MaterialPool: they need
+//! UPDATE_AFTER_BIND pools sized for the bindless texture array.
+
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use cubic_render::RenderResult;
+
+use crate::VkRenderer;
+
+/// Descriptors of each type per set in the shared pools. Averages, not
+/// limits: one set can use more so long as its pool still has them.
+const GENERAL_POOL_RATIOS: &[(vk::DescriptorType, u32)] = &[
+    (vk::DescriptorType::UNIFORM_BUFFER, 2),
+    (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 2),
+    (vk::DescriptorType::STORAGE_BUFFER, 8),
+    (vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, 1),
+    (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+    (vk::DescriptorType::STORAGE_IMAGE, 1),
+];
+/// Sets in the shared allocators' first pool; each new pool doubles it.
+const GENERAL_FIRST_POOL_SETS: u32 = 16;
+const GENERAL_MAX_POOL_SETS: u32 = 1024;
+
+/// One allocated set and the pool it came from, to free it to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PooledSet {
+    pub(crate) pool: vk::DescriptorPool,
+    pub(crate) set: vk::DescriptorSet,
+}
+
+pub(crate) struct DescriptorAllocator {
+    ratios: Vec<(vk::DescriptorType, u32)>,
+    flags: vk::DescriptorPoolCreateFlags,
+    next_sets: u32,
+    max_sets: u32,
+    // Where allocations go until it runs out.
+    current: Option<vk::DescriptorPool>,
+    // Pools with room: reset, or with sets freed back into them.
+    ready: Vec<vk::DescriptorPool>,
+    // Pools that ran out.
+    full: Vec<vk::DescriptorPool>,
+    // Transient use only: (timeline value, pools) per submitted frame,
+    // oldest first.
+    in_flight: VecDeque<(u64, Vec<vk::DescriptorPool>)>,
+}
+
+impl DescriptorAllocator {
+    /// Pools hold `ratios` descriptors of each type per set, `first_sets`
+    /// sets in the first pool and twice the previous in each after that,
+    /// up to `max_sets`.
+    pub(crate) fn new(
+        ratios: &[(vk::DescriptorType, u32)],
+        flags: vk::DescriptorPoolCreateFlags,
+        first_sets: u32,
+        max_sets: u32,
+    ) -> Self {
+        Self {
+            ratios: ratios.to_vec(),
+            flags,
+            next_sets: first_sets,
+            max_sets: max_sets.max(first_sets),
+            current: None,
+            ready: Vec::new(),
+            full: Vec::new(),
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// A set of `layout`, from a new pool if the ones so far are full.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &ash::Device,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<PooledSet> {
+        loop {
+            let (pool, fresh) = match self.current {
+                Some(pool) => (pool, false),
+                None => self.next_pool(device)?,
+            };
+            self.current = Some(pool);
+            let info = vk::DescriptorSetAllocateInfo {
+                s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+                descriptor_pool: pool,
+                descriptor_set_count: 1,
+                p_set_layouts: &layout,
+                ..Default::default()
+            };
+            match unsafe { device.allocate_descriptor_sets(&info) } {
+                Ok(sets) => return Ok(PooledSet { pool, set: sets[0] }),
+                // Out of room: retire it and try the next. A pool just
+                // created failing means the set is bigger than a pool.
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL)
+                    if !fresh =>
+                {
+                    self.full.push(pool);
+                    self.current = None;
+                }
+                Err(e) => return Err(anyhow!("descriptor set allocation: {e:?}")),
+            }
+        }
+    }
+
+    /// `count` sets of `layout`, for owners that only ever drop them with
+    /// a reset or destroy.
+    pub(crate) fn allocate_many(
+        &mut self,
+        device: &ash::Device,
+        layout: vk::DescriptorSetLayout,
+        count: usize,
+    ) -> Result<Vec<vk::DescriptorSet>> {
+        (0..count)
+            .map(|_| self.allocate(device, layout).map(|p| p.set))
+            .collect()
+    }
+
+    /// Give a set back to its pool. Only for allocators created with
+    /// FREE_DESCRIPTOR_SET, once the GPU is done with the set.
+    pub(crate) fn free(&mut self, device: &ash::Device, set: PooledSet) {
+        let _ = unsafe { device.free_descriptor_sets(set.pool, &[set.set]) };
+        if let Some(i) = self.full.iter().position(|&p| p == set.pool) {
+            self.ready.push(self.full.swap_remove(i));
+        }
+    }
+
+    /// Retire every pool allocated from since the last call with the frame
+    /// submit that signals `value`.
+    pub(crate) fn finish_frame(&mut self, value: u64) {
+        let mut pools = std::mem::take(&mut self.full);
+        pools.extend(self.current.take());
+        if !pools.is_empty() {
+            self.in_flight.push_back((value, pools));
+        }
+    }
+
+    /// Reset and reuse every retired pool the timeline has passed.
+    pub(crate) fn reclaim(&mut self, device: &ash::Device, signaled: u64) {
+        while let Some((value, _)) = self.in_flight.front() {
+            if *value > signaled {
+                break;
+            }
+            let (_, pools) = self.in_flight.pop_front().expect("front checked");
+            for pool in pools {
+                self.reset_pool(device, pool);
+            }
+        }
+    }
+
+    /// Caller must have idled the device. Sets go with their pools.
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        let pools = self
+            .current
+            .take()
+            .into_iter()
+            .chain(self.ready.drain(..))
+            .chain(self.full.drain(..))
+            .chain(self.in_flight.drain(..).flat_map(|(_, p)| p));
+        for pool in pools {
+            unsafe { device.destroy_descriptor_pool(pool, None) };
+        }
+    }
+
+    fn reset_pool(&mut self, device: &ash::Device, pool: vk::DescriptorPool) {
+        let reset =
+            unsafe { device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty()) };
+        // Only fails on out-of-memory; better a lost pool than a half-reset one.
+        match reset {
+            Ok(()) => self.ready.push(pool),
+            Err(e) => {
+                tracing::warn!("vk: descriptor pool reset failed ({e:?}); dropping the pool");
+                unsafe { device.destroy_descriptor_pool(pool, None) };
+            }
+        }
+    }
+
+    /// A pool to allocate from, and whether it was created just now.
+    fn next_pool(&mut self, device: &ash::Device) -> Result<(vk::DescriptorPool, bool)> {
+        if let Some(pool) = self.ready.pop() {
+            return Ok((pool, false));
+        }
+        let sets = self.next_sets;
+        self.next_sets = (sets * 2).min(self.max_sets);
+        let pool_sizes: Vec<_> = self
+            .ratios
+            .iter()
+            .map(|&(ty, n)| vk::DescriptorPoolSize {
+                ty,
+                descriptor_count: n * sets,
+            })
+            .collect();
+        let pool_ci = vk::DescriptorPoolCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            flags: self.flags,
+            max_sets: sets,
+            pool_size_count: pool_sizes.len() as u32,
+            p_pool_sizes: pool_sizes.as_ptr(),
+            ..Default::default()
+        };
+        let pool = unsafe { device.create_descriptor_pool(&pool_ci, None)? };
+        Ok((pool, true))
+    }
+}
+
+/// The renderer's shared allocators; see the module docs.
+pub(crate) struct Descriptors {
+    pub(crate) persistent: DescriptorAllocator,
+    pub(crate) transient: DescriptorAllocator,
+}
+
+impl Descriptors {
+    pub(crate) fn new() -> Self {
+        let general = |flags| {
+            DescriptorAllocator::new(
+                GENERAL_POOL_RATIOS,
+                flags,
+                GENERAL_FIRST_POOL_SETS,
+                GENERAL_MAX_POOL_SETS,
+            )
+        };
+        Self {
+            persistent: general(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET),
+            transient: general(vk::DescriptorPoolCreateFlags::empty()),
+        }
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        self.persistent.destroy(device);
+        self.transient.destroy(device);
+    }
+}
+
+impl VkRenderer {
+    /// A descriptor set of `layout` for this frame's commands to bind, e.g.
+    /// over a BufferSlice from upload_transient. Valid for commands
+    /// recorded for the next rendered frame only; it's reclaimed without
+    /// being freed.
+    pub fn alloc_transient_set(
+        &mut self,
+        layout: vk::DescriptorSetLayout,
+    ) -> RenderResult<vk::DescriptorSet> {
+        Ok(self
+            .descriptors
+            .transient
+            .allocate(&self.device, layout)?
+            .set)
+    }
+}
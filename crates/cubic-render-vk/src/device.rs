@@ -5,6 +5,8 @@ use ash::khr::{surface, swapchain};
 use ash::{vk, Instance};
 use std::ffi::c_char;
 
+use crate::resources::query_texture_array_caps;
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum RenderPath {
     Core13, // Vulkan 1.3 core dynamic rendering + sync2
@@ -12,50 +14,319 @@ pub(crate) enum RenderPath {
     Legacy, // No dynamic rendering: would need render pass/framebuffer path
 }
 
+/// The queue families the renderer submits to. Graphics and present are
+/// the same family on nearly every driver; when they aren't, swapchain
+/// images are shared CONCURRENT between the two (see
+/// create_swapchain_bundle) and presents go to a queue of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct QueueFamilies {
+    pub(crate) graphics: u32,
+    pub(crate) present: u32,
+}
+
+impl QueueFamilies {
+    pub(crate) fn separate_present(self) -> bool {
+        self.graphics != self.present
+    }
+}
+
+/// An explicit GPU request: `--gpu` in cubic-app, or CUBIC_GPU. An index
+/// refers to the enumeration order printed in the startup log.
+#[derive(Clone, Debug, PartialEq)]
+enum GpuSelector {
+    Index(usize),
+    /// Case-insensitive substring of the device name ("4070", "radeon").
+    Name(String),
+    Type(vk::PhysicalDeviceType),
+}
+
+impl GpuSelector {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return None;
+        }
+        if let Ok(i) = s.parse::<usize>() {
+            return Some(Self::Index(i));
+        }
+        Some(match s.to_ascii_lowercase().as_str() {
+            "discrete" | "dgpu" => Self::Type(vk::PhysicalDeviceType::DISCRETE_GPU),
+            "integrated" | "igpu" => Self::Type(vk::PhysicalDeviceType::INTEGRATED_GPU),
+            "cpu" => Self::Type(vk::PhysicalDeviceType::CPU),
+            lower => Self::Name(lower.to_owned()),
+        })
+    }
+
+    fn matches(&self, index: usize, name: &str, ty: vk::PhysicalDeviceType) -> bool {
+        match self {
+            Self::Index(i) => *i == index,
+            Self::Name(n) => name.to_ascii_lowercase().contains(n.as_str()),
+            Self::Type(t) => *t == ty,
+        }
+    }
+}
+
+/// Preference between otherwise-suitable devices: discrete first (hybrid
+/// laptops list the iGPU first more often than not), then integrated, then
+/// virtual and software rasterizers. Device-local memory breaks ties
+/// between devices of the same type.
+fn device_score(instance: &Instance, phys: vk::PhysicalDevice, ty: vk::PhysicalDeviceType) -> u64 {
+    let type_score: u64 = match ty {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    };
+    let mem = unsafe { instance.get_physical_device_memory_properties(phys) };
+    let local_mib: u64 = mem.memory_heaps[..mem.memory_heap_count as usize]
+        .iter()
+        .filter(|h| h.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|h| h.size >> 20)
+        .max()
+        .unwrap_or(0);
+    // Type dominates: no heap is anywhere near 2^40 MiB.
+    (type_score << 40) | local_mib
+}
+
+/// What `phys` lacks of the renderer's minimum requirements (the features
+/// and extensions decide_path_and_create_device can't do without); empty if
+/// it can run cubic. Checked before a device is picked, so a machine whose
+/// only GPU falls short fails build_renderer with the reason instead of
+/// partway through device creation, and cubic-app can fall back to GL.
+pub(crate) fn missing_requirements(
+    instance: &Instance,
+    phys: vk::PhysicalDevice,
+) -> Vec<&'static str> {
+    let mut missing = Vec::new();
+    let api = unsafe { instance.get_physical_device_properties(phys).api_version };
+    let (maj, min) = (vk::api_version_major(api), vk::api_version_minor(api));
+    if maj == 1 && min < 2 {
+        missing.push("Vulkan 1.2");
+        return missing;
+    }
+    let exts = unsafe { instance.enumerate_device_extension_properties(phys) }.unwrap_or_default();
+    let has = |name: &std::ffi::CStr| {
+        exts.iter()
+            .any(|e| unsafe { std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) } == name)
+    };
+    if !has(swapchain::NAME) {
+        missing.push("VK_KHR_swapchain");
+    }
+    let core13 = maj > 1 || min >= 3;
+    let khr13 = has(ash::khr::synchronization2::NAME) && has(ash::khr::dynamic_rendering::NAME);
+    if !core13 && !khr13 {
+        missing.push("dynamic rendering and synchronization2 (Vulkan 1.3 or KHR extensions)");
+    }
+    let mut f12 = vk::PhysicalDeviceVulkan12Features {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_2_FEATURES,
+        ..Default::default()
+    };
+    let mut query = vk::PhysicalDeviceFeatures2 {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+        p_next: (&mut f12) as *mut _ as *mut _,
+        ..Default::default()
+    };
+    unsafe { instance.get_physical_device_features2(phys, &mut query) };
+    for (on, name) in [
+        (f12.timeline_semaphore, "timeline semaphores"),
+        (f12.buffer_device_address, "buffer device address"),
+        (
+            f12.descriptor_binding_partially_bound,
+            "partially bound descriptors",
+        ),
+        (f12.runtime_descriptor_array, "runtime descriptor arrays"),
+        (
+            f12.shader_sampled_image_array_non_uniform_indexing,
+            "non-uniform sampled image indexing",
+        ),
+        (f12.draw_indirect_count, "draw indirect count"),
+    ] {
+        if on != vk::TRUE {
+            missing.push(name);
+        }
+    }
+    missing
+}
+
+/// Graphics and present families for `phys`: one family doing both if
+/// there is one, else the first graphics family plus a family that can
+/// present. CUBIC_SEPARATE_PRESENT_QUEUE=1 prefers a present family other
+/// than the graphics one where the device has it, to exercise that path
+/// on hardware that doesn't need it.
+fn queue_families(
+    instance: &Instance,
+    surf_i: &surface::Instance,
+    surface: vk::SurfaceKHR,
+    phys: vk::PhysicalDevice,
+) -> Option<QueueFamilies> {
+    let qprops = unsafe { instance.get_physical_device_queue_family_properties(phys) };
+    let presents: Vec<bool> = (0..qprops.len() as u32)
+        .map(|i| {
+            unsafe { surf_i.get_physical_device_surface_support(phys, i, surface) }.unwrap_or(false)
+        })
+        .collect();
+    let is_graphics = |i: usize| qprops[i].queue_flags.contains(vk::QueueFlags::GRAPHICS);
+    let combined = (0..qprops.len()).find(|&i| is_graphics(i) && presents[i]);
+    let graphics = combined.or_else(|| (0..qprops.len()).find(|&i| is_graphics(i)))?;
+    let other_present = (0..qprops.len()).find(|&i| i != graphics && presents[i]);
+    let force_separate = std::env::var("CUBIC_SEPARATE_PRESENT_QUEUE")
+        .ok()
+        .as_deref()
+        == Some("1");
+    let present = match (combined, other_present) {
+        (Some(_), Some(other)) if force_separate => other,
+        (Some(both), _) => both,
+        (None, other) => other?,
+    };
+    Some(QueueFamilies {
+        graphics: graphics as u32,
+        present: present as u32,
+    })
+}
+
+/// Pick the physical device and its graphics and present queue families.
+/// `requested` (cubic-app's `--gpu`) wins over CUBIC_GPU; either accepts an
+/// index, a name substring, or discrete/integrated/cpu. A request that
+/// matches nothing usable is logged and ignored in favour of the
+/// highest-scoring device (see device_score), rather than failing startup.
 pub(crate) fn select_device_and_queue(
     instance: &ash::Instance,
     surf_i: &surface::Instance,
     surface: vk::SurfaceKHR,
-) -> Result<(vk::PhysicalDevice, u32)> {
-    pick_device_and_queue(instance, surf_i, surface)
+    requested: Option<&str>,
+) -> Result<(vk::PhysicalDevice, QueueFamilies)> {
+    let env = std::env::var("CUBIC_GPU").ok();
+    let request = requested.or(env.as_deref());
+    let selector = request.and_then(GpuSelector::parse);
+    pick_device_and_queue(instance, surf_i, surface, selector.as_ref())
 }
 
 fn pick_device_and_queue(
     instance: &Instance,
     surf_i: &surface::Instance,
     surface: vk::SurfaceKHR,
-) -> Result<(vk::PhysicalDevice, u32)> {
+    selector: Option<&GpuSelector>,
+) -> Result<(vk::PhysicalDevice, QueueFamilies)> {
     let phys_devs = unsafe { instance.enumerate_physical_devices()? };
 
-    for phys in phys_devs {
-        let qprops = unsafe { instance.get_physical_device_queue_family_properties(phys) };
-
-        for (i, q) in qprops.iter().enumerate() {
-            if q.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                let supports_surface =
-                    unsafe { surf_i.get_physical_device_surface_support(phys, i as u32, surface) }
-                        .unwrap_or(false);
-
-                if supports_surface {
-                    return Ok((phys, i as u32));
-                }
+    struct Usable {
+        index: usize,
+        phys: vk::PhysicalDevice,
+        families: QueueFamilies,
+        score: u64,
+        requested: bool,
+        name: String,
+    }
+    let mut usable = Vec::new();
+    for (index, &phys) in phys_devs.iter().enumerate() {
+        let props = unsafe { instance.get_physical_device_properties(phys) };
+        let name = props
+            .device_name_as_c_str()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "<unnamed>".to_owned());
+        let ty = props.device_type;
+        let missing = missing_requirements(instance, phys);
+        let families = queue_families(instance, surf_i, surface, phys);
+        tracing::info!(
+            "GPU {}: {} ({:?}, Vulkan {}.{}){}",
+            index,
+            name,
+            ty,
+            vk::api_version_major(props.api_version),
+            vk::api_version_minor(props.api_version),
+            if !missing.is_empty() {
+                format!(" — lacks {}, skipped", missing.join(", "))
+            } else if families.is_none() {
+                " — can't present to this window, skipped".to_owned()
+            } else {
+                String::new()
             }
+        );
+        if !missing.is_empty() {
+            continue;
+        }
+        if let Some(families) = families {
+            usable.push(Usable {
+                index,
+                phys,
+                families,
+                score: device_score(instance, phys, ty),
+                requested: selector.is_some_and(|s| s.matches(index, &name, ty)),
+                name,
+            });
         }
     }
 
-    Err(anyhow!("no suitable physical device/queue family"))
+    let requested = usable
+        .iter()
+        .filter(|d| d.requested)
+        .max_by_key(|d| d.score);
+    if let (Some(sel), None) = (selector, requested) {
+        tracing::warn!(
+            "requested GPU {:?} not found among usable devices; using the best available",
+            sel
+        );
+    }
+    let chosen = requested
+        .or_else(|| usable.iter().max_by_key(|d| d.score))
+        .ok_or_else(|| {
+            anyhow!(
+                "no GPU meets the minimum requirements and can present (see the GPU list above)"
+            )
+        })?;
+    tracing::info!("using GPU {}: {}", chosen.index, chosen.name);
+    Ok((chosen.phys, chosen.families))
 }
 
+/// A queue family for background uploads (see upload::TransferUploader)
+/// distinct from the graphics one: transfer-only families first (usually a
+/// dedicated DMA engine), then any non-graphics family that can transfer.
+/// None means uploads share the graphics queue. CUBIC_NO_TRANSFER_QUEUE=1
+/// forces that, for ruling the transfer path out when debugging.
+pub(crate) fn find_transfer_queue_family(
+    instance: &ash::Instance,
+    phys: vk::PhysicalDevice,
+    graphics_family: u32,
+) -> Option<u32> {
+    if std::env::var("CUBIC_NO_TRANSFER_QUEUE").ok().as_deref() == Some("1") {
+        return None;
+    }
+    let qprops = unsafe { instance.get_physical_device_queue_family_properties(phys) };
+    let candidates = || {
+        qprops.iter().enumerate().filter(|&(i, q)| {
+            i as u32 != graphics_family
+                && q.queue_count > 0
+                && q.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !q.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+        })
+    };
+    candidates()
+        .find(|(_, q)| !q.queue_flags.contains(vk::QueueFlags::COMPUTE))
+        .or_else(|| candidates().next())
+        .map(|(i, _)| i as u32)
+}
+
+#[allow(clippy::type_complexity)]
 pub(crate) fn decide_path_and_create_device(
-    _entry: &ash::Entry,
+    entry: &ash::Entry,
     instance: &ash::Instance,
     phys: vk::PhysicalDevice,
-    queue_family: u32,
+    families: QueueFamilies,
+    transfer_family: Option<u32>,
 ) -> Result<(
     ash::Device,
     vk::Queue,
+    vk::Queue,         /*present; the graphics queue unless separate*/
+    Option<vk::Queue>, /*transfer*/
     RenderPath,
     bool, /*has_hdr_metadata*/
+    bool, /*has_present_wait*/
+    bool, /*has_full_screen_exclusive*/
+    bool, /*has_swapchain_maintenance1*/
+    bool, /*has_device_fault*/
+    bool, /*has_diagnostic_checkpoints*/
+    bool, /*has_multiview*/
 )> {
     // STRICT ORDER (feature pNext chain):
     // Core 1.3 path: feats13 -> chained after feats12 -> chained after feats2
@@ -63,15 +334,27 @@ pub(crate) fn decide_path_and_create_device(
     // DO NOT MIX core 1.3 structs with KHR equivalents in the same chain.
     // Wrong chain = undefined features; validation won't always catch it.
 
-    // --- Queue we want on this device ---
+    // --- Queues we want on this device (graphics, present if separate,
+    // optional transfer) ---
+    // One queue per distinct family: a present-only family can also be the
+    // transfer one, and then both use its queue 0.
     let priorities = [1.0_f32];
-    let qinfo = vk::DeviceQueueCreateInfo {
-        s_type: vk::StructureType::DEVICE_QUEUE_CREATE_INFO,
-        queue_family_index: queue_family,
-        queue_count: 1,
-        p_queue_priorities: priorities.as_ptr(),
-        ..Default::default()
-    };
+    let mut wanted = vec![families.graphics];
+    for family in std::iter::once(families.present).chain(transfer_family) {
+        if !wanted.contains(&family) {
+            wanted.push(family);
+        }
+    }
+    let qinfos: Vec<vk::DeviceQueueCreateInfo> = wanted
+        .iter()
+        .map(|&family| vk::DeviceQueueCreateInfo {
+            s_type: vk::StructureType::DEVICE_QUEUE_CREATE_INFO,
+            queue_family_index: family,
+            queue_count: 1,
+            p_queue_priorities: priorities.as_ptr(),
+            ..Default::default()
+        })
+        .collect();
 
     // --- One shot device extension query ---
     let ext_props = unsafe {
@@ -94,6 +377,112 @@ pub(crate) fn decide_path_and_create_device(
     if has_hdr_meta {
         device_exts.push(ash::ext::hdr_metadata::NAME.as_ptr());
     }
+    // VK_KHR_present_wait (needs VK_KHR_present_id) for set_target_fps's
+    // pacing: lets the CPU wait until a given present has actually reached
+    // the display. Optional; the pacer falls back to sleep+spin alone.
+    let mut feats_present_id = vk::PhysicalDevicePresentIdFeaturesKHR {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_PRESENT_ID_FEATURES_KHR,
+        ..Default::default()
+    };
+    let mut feats_present_wait = vk::PhysicalDevicePresentWaitFeaturesKHR {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_PRESENT_WAIT_FEATURES_KHR,
+        ..Default::default()
+    };
+    let has_present_wait =
+        has(ash::khr::present_id::NAME) && has(ash::khr::present_wait::NAME) && {
+            feats_present_id.p_next = (&mut feats_present_wait) as *mut _ as *mut _;
+            let mut query = vk::PhysicalDeviceFeatures2 {
+                s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+                p_next: (&mut feats_present_id) as *mut _ as *mut _,
+                ..Default::default()
+            };
+            unsafe { instance.get_physical_device_features2(phys, &mut query) };
+            feats_present_id.present_id == vk::TRUE && feats_present_wait.present_wait == vk::TRUE
+        };
+    if has_present_wait {
+        device_exts.push(ash::khr::present_id::NAME.as_ptr());
+        device_exts.push(ash::khr::present_wait::NAME.as_ptr());
+    }
+    // The surface extensions below depend on instance extensions that
+    // create_instance enables whenever they're there; check that they were.
+    let inst_exts =
+        unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+    let instance_has = |name: &std::ffi::CStr| {
+        inst_exts
+            .iter()
+            .any(|e| unsafe { std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) } == name)
+    };
+    let has_surface_caps2 = instance_has(ash::khr::get_surface_capabilities2::NAME);
+    // VK_EXT_full_screen_exclusive (Windows drivers) for
+    // set_exclusive_fullscreen.
+    let has_fse = has_surface_caps2 && has(ash::ext::full_screen_exclusive::NAME);
+    if has_fse {
+        device_exts.push(ash::ext::full_screen_exclusive::NAME.as_ptr());
+    }
+    // VK_EXT_swapchain_maintenance1 (needs the instance's
+    // VK_EXT_surface_maintenance1): present-mode switches without
+    // swapchain recreation, and present fences. Optional, like present
+    // wait; without it every vsync change recreates the swapchain.
+    let mut feats_sm1 = vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_SWAPCHAIN_MAINTENANCE_1_FEATURES_EXT,
+        ..Default::default()
+    };
+    let has_sm1 = has_surface_caps2
+        && instance_has(ash::ext::surface_maintenance1::NAME)
+        && has(ash::ext::swapchain_maintenance1::NAME)
+        && {
+            let mut query = vk::PhysicalDeviceFeatures2 {
+                s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+                p_next: (&mut feats_sm1) as *mut _ as *mut _,
+                ..Default::default()
+            };
+            unsafe { instance.get_physical_device_features2(phys, &mut query) };
+            feats_sm1.swapchain_maintenance1 == vk::TRUE
+        };
+    if has_sm1 {
+        device_exts.push(ash::ext::swapchain_maintenance1::NAME.as_ptr());
+    }
+    // Crash diagnostics for device loss (see crash_report.rs):
+    // VK_EXT_device_fault (with the vendor binary dump where offered) and
+    // NVIDIA's VK_NV_device_diagnostic_checkpoints. Both optional.
+    let mut feats_fault = vk::PhysicalDeviceFaultFeaturesEXT {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_FAULT_FEATURES_EXT,
+        ..Default::default()
+    };
+    let has_device_fault = has(ash::ext::device_fault::NAME) && {
+        let mut query = vk::PhysicalDeviceFeatures2 {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+            p_next: (&mut feats_fault) as *mut _ as *mut _,
+            ..Default::default()
+        };
+        unsafe { instance.get_physical_device_features2(phys, &mut query) };
+        feats_fault.device_fault == vk::TRUE
+    };
+    if has_device_fault {
+        device_exts.push(ash::ext::device_fault::NAME.as_ptr());
+    }
+    let has_checkpoints = has(ash::nv::device_diagnostic_checkpoints::NAME);
+    if has_checkpoints {
+        device_exts.push(ash::nv::device_diagnostic_checkpoints::NAME.as_ptr());
+    }
+    // Multiview (VK_KHR_multiview, core in 1.1) for set_stereo's layered
+    // pass (see stereo.rs). Optional; only the base feature is asked for,
+    // not multiview in geometry or tessellation shaders.
+    let mut feats_multiview = vk::PhysicalDeviceMultiviewFeatures {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_MULTIVIEW_FEATURES,
+        ..Default::default()
+    };
+    let has_multiview = {
+        let mut query = vk::PhysicalDeviceFeatures2 {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+            p_next: (&mut feats_multiview) as *mut _ as *mut _,
+            ..Default::default()
+        };
+        unsafe { instance.get_physical_device_features2(phys, &mut query) };
+        feats_multiview.multiview == vk::TRUE
+    };
+    feats_multiview.multiview_geometry_shader = vk::FALSE;
+    feats_multiview.multiview_tessellation_shader = vk::FALSE;
 
     // --- Feature structs (must outlive create_device); build the correct pNext chain ---
     let force_khr = std::env::var("CUBIC_FORCE_KHR").ok().as_deref() == Some("1");
@@ -122,9 +511,8 @@ pub(crate) fn decide_path_and_create_device(
     // Enable timeline semaphore
     feats12.timeline_semaphore = vk::TRUE;
     // Enable buffer device address (VK_KHR_buffer_device_address, core in
-    // 1.2). Not used yet, but must be enabled at device creation time so
-    // ray tracing / bindless buffer patterns don't require a device
-    // recreation later.
+    // 1.2): the shared mesh pools are read by address from vertex-pulling
+    // pipelines (PipelineDesc::vertex_pulling).
     feats12.buffer_device_address = vk::TRUE;
     // Enable descriptor indexing (VK_EXT_descriptor_indexing, core in 1.2)
     // for the bindless texture array: PARTIALLY_BOUND lets the array have
@@ -134,16 +522,29 @@ pub(crate) fn decide_path_and_create_device(
     feats12.descriptor_binding_partially_bound = vk::TRUE;
     feats12.runtime_descriptor_array = vk::TRUE;
     feats12.shader_sampled_image_array_non_uniform_indexing = vk::TRUE;
+    // Optional: update-after-bind for that array, so new textures can be
+    // registered while frames in flight still use the set. Requested only
+    // where supported; the array's layout follows the same query.
+    if query_texture_array_caps(instance, phys).update_after_bind {
+        feats12.descriptor_binding_sampled_image_update_after_bind = vk::TRUE;
+        feats12.descriptor_binding_update_unused_while_pending = vk::TRUE;
+    }
     // Required by cmd_draw_indexed_indirect_count (GPU-driven indirect draw).
     feats12.draw_indirect_count = vk::TRUE;
-    // Anisotropic texture filtering (see cubic-app's texture_filter/anisotropy
-    // config). Enabled unconditionally, matching the other features above —
-    // actual max_anisotropy is capped per-sampler against
-    // physical_device_properties.limits.max_sampler_anisotropy, and a config
-    // of 0.0 disables it regardless (anisotropy_enable = FALSE on the sampler).
-    feats2.features.sampler_anisotropy = vk::TRUE;
-
-    let (path, pnext): (RenderPath, *const std::ffi::c_void) = if !force_khr {
+    let supported = unsafe { instance.get_physical_device_features(phys) };
+    // Anisotropic texture filtering (cubic-app's render.max_anisotropy).
+    // Optional, unlike the features above: requesting an unsupported
+    // feature fails create_device outright, and plenty of mobile/embedded
+    // GPUs lack it. set_sampler_config re-checks and forces 0.0 (off) where
+    // it's missing; otherwise it caps the config against
+    // limits.max_sampler_anisotropy.
+    feats2.features.sampler_anisotropy = supported.sampler_anisotropy;
+    // Wireframe pipelines (PolygonMode::LINE, see PipelineDesc::wireframe).
+    // Optional: only requested where supported; register_pipeline re-checks
+    // it and rejects LINE descriptions on devices without it.
+    feats2.features.fill_mode_non_solid = supported.fill_mode_non_solid;
+
+    let path = if !force_khr {
         let dev_api = unsafe { instance.get_physical_device_properties(phys).api_version };
         let maj = vk::api_version_major(dev_api);
         let min = vk::api_version_minor(dev_api);
@@ -155,7 +556,7 @@ pub(crate) fn decide_path_and_create_device(
 
             feats12.p_next = (&mut feats13) as *mut _ as *mut _;
             feats2.p_next = (&mut feats12) as *mut _ as *mut _;
-            (RenderPath::Core13, (&mut feats2) as *mut _ as *const _)
+            RenderPath::Core13
         } else if has_sync2_khr && has_dynren_khr {
             // Vulkan 1.2 + KHR
             device_exts.push(ash::khr::synchronization2::NAME.as_ptr());
@@ -167,9 +568,9 @@ pub(crate) fn decide_path_and_create_device(
             feats_sync2_khr.p_next = (&mut feats_dr_khr) as *mut _ as *mut _;
             feats12.p_next = (&mut feats_sync2_khr) as *mut _ as *mut _;
             feats2.p_next = (&mut feats12) as *mut _ as *mut _;
-            (RenderPath::KhrExt, (&mut feats2) as *mut _ as *const _)
+            RenderPath::KhrExt
         } else {
-            (RenderPath::Legacy, std::ptr::null())
+            RenderPath::Legacy
         }
     } else {
         // Forced KHR path on 1.3 hardware (for testing)
@@ -182,7 +583,7 @@ pub(crate) fn decide_path_and_create_device(
         feats_sync2_khr.p_next = (&mut feats_dr_khr) as *mut _ as *mut _;
         feats12.p_next = (&mut feats_sync2_khr) as *mut _ as *mut _;
         feats2.p_next = (&mut feats12) as *mut _ as *mut _;
-        (RenderPath::KhrExt, (&mut feats2) as *mut _ as *const _)
+        RenderPath::KhrExt
     };
 
     // IMPORTANT: if we're on Legacy path, bail out BEFORE creating the device
@@ -192,12 +593,38 @@ pub(crate) fn decide_path_and_create_device(
         ));
     }
 
+    // Present id/wait go at the head of whichever chain was picked above,
+    // linked present_id -> present_wait; the query left their supported
+    // bits set, which is exactly what we want to enable.
+    if has_present_wait {
+        feats_present_wait.p_next = feats2.p_next;
+        feats_present_id.p_next = (&mut feats_present_wait) as *mut _ as *mut _;
+        feats2.p_next = (&mut feats_present_id) as *mut _ as *mut _;
+    }
+    // Same for swapchain maintenance1 (its query left the bit set).
+    if has_sm1 {
+        feats_sm1.p_next = feats2.p_next;
+        feats2.p_next = (&mut feats_sm1) as *mut _ as *mut _;
+    }
+    // And device fault (device_fault plus whatever vendor binary support
+    // the query reported).
+    if has_device_fault {
+        feats_fault.p_next = feats2.p_next;
+        feats2.p_next = (&mut feats_fault) as *mut _ as *mut _;
+    }
+    // And multiview.
+    if has_multiview {
+        feats_multiview.p_next = feats2.p_next;
+        feats2.p_next = (&mut feats_multiview) as *mut _ as *mut _;
+    }
+
     // --- Create device with our queue and the chosen feature chain ---
+    // The chain's head is only taken now, after the links added above.
     let dinfo = vk::DeviceCreateInfo {
         s_type: vk::StructureType::DEVICE_CREATE_INFO,
-        p_next: pnext,
-        queue_create_info_count: 1,
-        p_queue_create_infos: &qinfo,
+        p_next: (&mut feats2) as *mut _ as *const _,
+        queue_create_info_count: qinfos.len() as u32,
+        p_queue_create_infos: qinfos.as_ptr(),
         enabled_extension_count: device_exts.len() as u32,
         pp_enabled_extension_names: device_exts.as_ptr(),
         ..Default::default()
@@ -209,6 +636,21 @@ pub(crate) fn decide_path_and_create_device(
             .context("create_device")?
     };
 
-    let queue = unsafe { device.get_device_queue(queue_family, 0) };
-    Ok((device, queue, path, has_hdr_meta))
+    let queue = unsafe { device.get_device_queue(families.graphics, 0) };
+    let present_queue = unsafe { device.get_device_queue(families.present, 0) };
+    let transfer_queue = transfer_family.map(|f| unsafe { device.get_device_queue(f, 0) });
+    Ok((
+        device,
+        queue,
+        present_queue,
+        transfer_queue,
+        path,
+        has_hdr_meta,
+        has_present_wait,
+        has_fse,
+        has_sm1,
+        has_device_fault,
+        has_checkpoints,
+        has_multiview,
+    ))
 }
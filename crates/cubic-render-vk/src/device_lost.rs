@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! VK_ERROR_DEVICE_LOST recovery. A driver reset or GPU hang invalidates the
+//! logical device and everything created from it, so there is nothing to
+//! patch up in place: `VkRenderer::recover_from_device_lost` tears the old
+//! renderer down and builds a new one against the same window from the raw
+//! handles and runtime config it kept, retrying a few times with a backoff
+//! (a resetting driver can refuse new devices for a moment). render() has
+//! already written a crash report by then (see crash_report.rs).
+//!
+//! Carried over: runtime config (vsync/HDR/tonemap/swapchain usage), HDR
+//! metadata, sampler settings, FPS cap, clear colour, camera and viewports,
+//! the GPU culling and depth prepass toggles, the directional light, shadow
+//! settings and light list (same handles), registered pipelines and compute
+//! pipelines (same handles), bindless textures (re-uploaded in order from
+//! retained pixels, so indices stay valid), materials (same handles),
+//! cubemaps and the environment (same handles), the block texture array
+//! (under the same material), the post-processing chain
+//! and its grading LUT, picking, stereo, and egui's textures. NOT carried
+//! over: meshes, GPU buffers, compute bindings, render targets (whose
+//! texture indices come back as 1x1 black) and a running capture (its file
+//! ends at the loss). Every MeshHandle/GpuBufferHandle/ComputeBindingsHandle/
+//! TargetHandle from before the loss is dead; the caller has to upload its
+//! data, or create its targets, again.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use cubic_render::RenderResult;
+use raw_window_handle::{
+    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+    RawWindowHandle, WindowHandle,
+};
+use tracing::{error, info, warn};
+
+use crate::error::classify;
+use crate::validation::ValidationState;
+use crate::{build_renderer, VkRenderer};
+
+/// Rebuild attempts before giving up; each waits twice as long as the last.
+const MAX_ATTEMPTS: u32 = 3;
+const FIRST_BACKOFF: Duration = Duration::from_millis(250);
+
+/// The window/display handles the lost renderer was created with, wrapped
+/// back up so build_renderer can take them. Same lifetime contract as
+/// recreate_surface: the window must outlive the renderer, which cubic's
+/// callers already guarantee by dropping the renderer first.
+struct StoredHandles {
+    display: RawDisplayHandle,
+    window: RawWindowHandle,
+}
+
+impl HasDisplayHandle for StoredHandles {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        // SAFETY: see the struct docs; the display outlives the renderer.
+        Ok(unsafe { DisplayHandle::borrow_raw(self.display) })
+    }
+}
+
+impl HasWindowHandle for StoredHandles {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        // SAFETY: see the struct docs; the window outlives the renderer.
+        Ok(unsafe { WindowHandle::borrow_raw(self.window) })
+    }
+}
+
+impl VkRenderer {
+    /// Replace a renderer whose device was lost with a freshly built one
+    /// (see the module docs for what carries over). The old renderer is
+    /// dropped first: its swapchain has to let go of the window before a
+    /// new one can be created on it. Err after MAX_ATTEMPTS failed rebuilds;
+    /// there's no renderer left at that point.
+    pub fn recover_from_device_lost(mut self) -> RenderResult<VkRenderer> {
+        let handles = StoredHandles {
+            display: self.display_raw,
+            window: self.window_raw,
+        };
+        let size = cubic_render::RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let gpu = self.gpu_request.clone();
+        let validation_layers = self.validation_layers;
+        let cfg = self.cfg;
+        let hdr_metadata = self.hdr_metadata;
+        let sampler_config = self.sampler_config;
+        let clear = self.clear;
+        let camera = self.camera;
+        let viewports = std::mem::take(&mut self.viewports);
+        let gpu_culling = self.gpu_culling;
+        let depth_prepass = self.depth_prepass;
+        let picking = self.picking.is_some();
+        let stereo = self.stereo();
+        let light = self.light;
+        let shadow_settings = self.shadow_settings;
+        let lights = std::mem::take(&mut self.lights);
+        let pipelines: Vec<_> = self
+            .named_pipelines
+            .iter()
+            .map(|np| np.desc.clone())
+            .collect();
+        let compute_pipelines: Vec<_> = self
+            .compute_pipelines
+            .iter()
+            .map(|cp| cp.desc.clone())
+            .collect();
+        let materials: Vec<_> = self
+            .materials
+            .iter()
+            .map(|m| m.as_ref().map(|m| m.desc))
+            .collect();
+        let textures = std::mem::take(&mut self.tex_sources);
+        // Replayed synchronously below, so async uploads still in flight
+        // come back resident; their owners still need to hear about it.
+        let resident: Vec<u32> = self
+            .pending_textures
+            .iter()
+            .map(|&(_, index)| index)
+            .chain(self.resident_textures.iter().copied())
+            .collect();
+        let cubemaps = self.skybox.take_sources();
+        let environment = self.skybox.environment;
+        let block_textures = self
+            .block_textures
+            .as_mut()
+            .map(|b| (b.take_source(), b.material));
+        let egui_textures = std::mem::take(&mut self.egui_textures);
+        let pacer = std::mem::take(&mut self.pacer);
+        let latency_mode = self.latency_mode;
+        // Keeps its spike threshold; the rebuild's stall isn't a frame.
+        let mut frame_stats = std::mem::take(&mut self.frame_stats);
+        frame_stats.reset();
+        // Its atlas comes back at the same bindless index with the textures.
+        let text_font = self.text_font.take();
+        // Kept alive past drop(self): the old messenger still points at it.
+        let validation =
+            std::mem::replace(&mut self.validation, Box::new(ValidationState::from_env()));
+        drop(self);
+
+        let mut backoff = FIRST_BACKOFF;
+        let mut last_err = anyhow!("no rebuild attempted");
+        for attempt in 1..=MAX_ATTEMPTS {
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            match build_renderer(
+                &handles,
+                &handles,
+                size,
+                gpu.as_deref(),
+                validation_layers,
+                Some(cfg),
+            ) {
+                Ok(mut r) => {
+                    info!("vk: device rebuilt after device loss (attempt {attempt})");
+                    r.set_hdr_metadata(hdr_metadata);
+                    r.sampler_config = sampler_config;
+                    r.clear = clear;
+                    r.camera = camera;
+                    r.viewports = viewports;
+                    r.gpu_culling = gpu_culling;
+                    r.depth_prepass = depth_prepass;
+                    r.light = light;
+                    r.lights = lights;
+                    if let Err(e) = r.set_shadow_settings(shadow_settings) {
+                        error!("vk: shadow settings not restored after device loss: {e:#}");
+                    }
+                    r.pacer = pacer;
+                    r.latency_mode = latency_mode;
+                    r.frame_stats = frame_stats;
+                    r.validation.inherit(&validation);
+                    r.text_font = text_font;
+                    for desc in pipelines {
+                        let name = desc.name.clone();
+                        if let Err(e) = r.register_pipeline(desc) {
+                            // Later handles shift down by one; say so loudly.
+                            error!("vk: pipeline {name:?} not restored after device loss: {e:#}");
+                        }
+                    }
+                    for desc in compute_pipelines {
+                        let name = desc.name.clone();
+                        if let Err(e) = r.register_compute_pipeline(desc) {
+                            error!("vk: compute pipeline {name:?} not restored after device loss: {e:#}");
+                        }
+                    }
+                    for tex in &textures {
+                        if let Err(e) = r.upload_texture_with_sampler(
+                            &tex.pixels,
+                            tex.width,
+                            tex.height,
+                            tex.sampler,
+                        ) {
+                            error!("vk: texture not restored after device loss: {e:#}");
+                        }
+                    }
+                    for (faces, size) in cubemaps {
+                        if let Err(e) = r.upload_cubemap(faces, size) {
+                            error!("vk: cubemap not restored after device loss: {e:#}");
+                        }
+                    }
+                    if let Some(env) = environment {
+                        r.set_environment(env);
+                    }
+                    if let Some(((layers, size, count), material)) = &block_textures {
+                        match r.upload_block_array(layers, *size, *count) {
+                            // Recreated with the others below.
+                            Ok(()) => {
+                                if let Some(b) = r.block_textures.as_mut() {
+                                    b.material = *material;
+                                }
+                            }
+                            Err(e) => {
+                                error!("vk: block textures not restored after device loss: {e:#}")
+                            }
+                        }
+                    }
+                    r.resident_textures = resident;
+                    if let Err(e) = r.set_picking(picking) {
+                        error!("vk: picking not restored after device loss: {e:#}");
+                    }
+                    if let Err(e) = r.set_stereo(stereo) {
+                        error!("vk: stereo not restored after device loss: {e:#}");
+                    }
+                    // The first build's chain ran before the LUT's texture
+                    // was back; nothing has been submitted since.
+                    if r.cfg.post_lut.is_some() {
+                        r.sync_post_stack();
+                    }
+                    // After pipelines and textures, which materials refer to.
+                    r.restore_materials(materials);
+                    r.restore_egui_textures(egui_textures);
+                    return Ok(r);
+                }
+                Err(e) => {
+                    warn!("vk: rebuild after device loss failed (attempt {attempt}/{MAX_ATTEMPTS}): {e:#}");
+                    last_err = e;
+                }
+            }
+        }
+        Err(classify(last_err.context(format!(
+            "vk: giving up on device-lost recovery after {MAX_ATTEMPTS} attempts"
+        ))))
+    }
+}
@@ -5,6 +5,7 @@
 
 use anyhow::Result;
 use ash::vk;
+use cubic_render::EguiTextureMirror;
 use gpu_allocator::vulkan::{Allocator, AllocatorCreateDesc};
 use std::sync::{Arc, Mutex};
 
@@ -57,10 +58,9 @@ pub(crate) fn build_egui_renderer(
         device.clone(),
         egui_ash_renderer::DynamicRendering {
             color_attachment_format: color_format,
-            // egui draws inside the same cmd_begin_rendering scope as the
-            // scene (see record_egui), which always binds the real depth
-            // attachment — Vulkan requires the bound pipeline's declared
-            // depthAttachmentFormat to match whenever one is bound, even if
+            // egui's frame graph pass (see build_frame_graph) binds the
+            // real depth attachment — Vulkan requires the bound pipeline's
+            // declared depthAttachmentFormat to match whenever one is bound, even if
             // this pipeline doesn't test/write depth (both disabled via
             // Options below).
             depth_attachment_format: Some(depth_format),
@@ -95,11 +95,10 @@ impl VkRenderer {
         });
     }
 
-    /// Draw a staged egui frame (see queue_egui) into `cmd`. Must be called
-    /// while `cmd` is inside an active dynamic-rendering scope (between
-    /// begin_rendering and cmd_end_rendering) targeting the same color
-    /// attachment format the egui renderer was built with, and before that
-    /// image transitions to PRESENT_SRC_KHR. No-op if nothing is staged.
+    /// Draw a staged egui frame (see queue_egui) into `cmd`, as the frame
+    /// graph's "egui" pass: inside a dynamic-rendering scope on the
+    /// swapchain image (loaded, so the scene stays underneath) with the
+    /// depth attachment bound. No-op if nothing is staged.
     pub(crate) fn record_egui(&mut self, cmd: vk::CommandBuffer) -> Result<()> {
         let Some(frame) = self.egui_pending.take() else {
             return Ok(());
@@ -126,6 +125,19 @@ impl VkRenderer {
         // doesn't submit), and set_textures's uploads are already
         // synchronously complete (queue_wait_idle) by the time it returns.
         renderer.free_textures(frame.textures_delta.free.as_slice())?;
+        self.egui_textures.apply(&frame.textures_delta);
         Ok(())
     }
+
+    /// Re-upload a mirror taken from a renderer that lost its device (see
+    /// device_lost.rs). Synchronous, like every egui set_textures call.
+    pub(crate) fn restore_egui_textures(&mut self, mirror: EguiTextureMirror) {
+        if let Some(renderer) = self.egui_renderer.as_mut() {
+            let set = mirror.full_set();
+            if let Err(e) = renderer.set_textures(self.queue, self.cmd_pool, &set) {
+                tracing::error!("vk: egui textures not restored after device loss: {e:#}");
+            }
+        }
+        self.egui_textures = mirror;
+    }
 }
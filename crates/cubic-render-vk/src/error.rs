@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Turning Vulkan and allocator failures into RenderError. Internals stay
+//! on anyhow. The calls that see a failure callers can act on raise the
+//! typed error there (vk_error, alloc_error), and any `?` up to a public
+//! method finds it again through the context. The lifecycle calls (new,
+//! resize, render, resume, recover_from_device_lost) also classify
+//! whatever else comes up through them, so a `?` on a raw vk::Result deep
+//! in swapchain setup still reaches the caller as, say, SurfaceLost.
+
+use anyhow::anyhow;
+use ash::vk;
+use cubic_render::{MemoryKind, RenderError};
+use gpu_allocator::AllocationError;
+
+/// The typed error for `e`, if it's one a caller can act on.
+fn typed(e: vk::Result, stage: &'static str) -> Option<RenderError> {
+    Some(match e {
+        vk::Result::ERROR_DEVICE_LOST => RenderError::DeviceLost { stage },
+        vk::Result::ERROR_SURFACE_LOST_KHR => RenderError::SurfaceLost,
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY => RenderError::OutOfMemory {
+            kind: MemoryKind::Host,
+        },
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY | vk::Result::ERROR_OUT_OF_POOL_MEMORY => {
+            RenderError::OutOfMemory {
+                kind: MemoryKind::Device,
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// A failed vk call at `stage`, typed where RenderError has a variant for
+/// it.
+pub(crate) fn vk_error(e: vk::Result, stage: &'static str) -> anyhow::Error {
+    match typed(e, stage) {
+        Some(typed) => typed.into(),
+        None => anyhow!("{stage}: {e:?}"),
+    }
+}
+
+/// An allocator failure, typed when it's out of memory.
+pub(crate) fn alloc_error(e: AllocationError) -> anyhow::Error {
+    match e {
+        AllocationError::OutOfMemory => RenderError::OutOfMemory {
+            kind: MemoryKind::Device,
+        }
+        .into(),
+        e => e.into(),
+    }
+}
+
+/// The RenderError for an internal failure: one raised as such anywhere in
+/// the chain, else a vk::Result or allocator error in it that has a
+/// variant, else Other.
+pub(crate) fn classify(e: anyhow::Error) -> RenderError {
+    if e.downcast_ref::<RenderError>().is_some() {
+        return e.into();
+    }
+    let found = e.chain().find_map(|cause| {
+        if let Some(&vk_err) = cause.downcast_ref::<vk::Result>() {
+            return typed(vk_err, "a vk call");
+        }
+        match cause.downcast_ref::<AllocationError>() {
+            Some(AllocationError::OutOfMemory) => Some(RenderError::OutOfMemory {
+                kind: MemoryKind::Device,
+            }),
+            _ => None,
+        }
+    });
+    found.unwrap_or(RenderError::Other(e))
+}
@@ -6,20 +6,29 @@
 use anyhow::{anyhow, Result};
 use ash::vk;
 use ash::Entry;
-use cubic_render::RenderSize;
+use cubic_math::Mat4;
+use cubic_render::clip::{self, Frustum};
+use cubic_render::{
+    draw_depth, CullCounts, DepthConvention, DrawSortKey, LatencyMode, PipelineHandle, RenderError,
+    RenderSize,
+};
+use gpu_allocator::vulkan::Allocation;
 
+use crate::error::vk_error;
+use crate::frame_graph::{Access, FrameGraph, LoadOp};
 use crate::instance::recreate_surface;
+use crate::material::MaterialHandle;
 #[cfg(debug_assertions)]
-use crate::pipeline::{create_pipeline, PipelineConfig};
+use crate::pipeline::{create_depth_prepass_pipeline, create_pipeline, BlendMode, PipelineDesc};
 use crate::resources::{
-    depth_aspect_mask, depth_attachment_layout, DrawCandidate, MAX_INDIRECT_DRAWS,
+    depth_aspect_mask, depth_attachment_layout, CullPush, DrawCandidate, MAX_INDIRECT_DRAWS,
 };
-#[cfg(debug_assertions)]
-use crate::DeferredDrop;
 use crate::{
-    is_device_lost, is_surface_lost, is_swapchain_out_of_date, semaphore_submit_info_signal,
-    semaphore_submit_info_wait, stage_flags2_from_legacy, GpuResource, VkRenderer,
+    is_surface_lost, is_swapchain_out_of_date, semaphore_submit_info_signal,
+    semaphore_submit_info_wait, stage_flags2_from_legacy, GpuResource, QueuedDraw, SceneView,
+    VkRenderer,
 };
+use crate::{DeferredDrop, RetiredPass, UNBOUNDED};
 
 impl VkRenderer {
     #[inline]
@@ -43,10 +52,64 @@ impl VkRenderer {
         // rather than risk destroying a resource still in use.
         let signaled =
             unsafe { self.device.get_semaphore_counter_value(self.timeline) }.unwrap_or(0);
+        self.destroy_retired(signaled);
+    }
+
+    /// Retire a pass's objects until the frames submitted so far are done
+    /// with them.
+    pub(crate) fn retire_pass(&mut self, pass: impl RetiredPass + 'static) {
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::Pass(Box::new(pass)),
+        });
+    }
+
+    /// Retire an image and its view like retire_pass does a pass.
+    pub(crate) fn retire_image(
+        &mut self,
+        image: vk::Image,
+        alloc: Allocation,
+        view: vk::ImageView,
+    ) {
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::ImageView(view),
+        });
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::Image { image, alloc },
+        });
+    }
 
+    /// Wait for the frames submitted so far: for the descriptor writes
+    /// that can only go into sets those frames still bind (the material
+    /// sets' bindless and block array entries, which have no fresh set to
+    /// move to). Narrower than device_wait_idle — the transfer queue's
+    /// uploads carry on — and whatever the write replaces is still retired
+    /// through the trash queue.
+    pub(crate) fn wait_frames_in_flight(&self) -> Result<()> {
+        if self.timeline_value == 0 {
+            return Ok(());
+        }
+        let wait_info = vk::SemaphoreWaitInfo {
+            s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+            semaphore_count: 1,
+            p_semaphores: &self.timeline,
+            p_values: &self.timeline_value,
+            ..Default::default()
+        };
+        unsafe { self.device.wait_semaphores(&wait_info, u64::MAX) }
+            .map_err(|e| vk_error(e, "wait_semaphores"))?;
+        Ok(())
+    }
+
+    /// Destroy every trashed resource retired at or before `value`;
+    /// u64::MAX with the device idle destroys them all, including ones
+    /// retired for a frame that was never submitted.
+    pub(crate) fn destroy_retired(&mut self, value: u64) {
         let mut i = 0;
         while i < self.trash.len() {
-            if self.trash[i].value > signaled {
+            if self.trash[i].value > value {
                 i += 1;
                 continue;
             }
@@ -74,6 +137,9 @@ impl VkRenderer {
                 GpuResource::Pipeline(p) => unsafe {
                     self.device.destroy_pipeline(p, None);
                 },
+                GpuResource::DescriptorSet(set) => {
+                    self.descriptors.persistent.free(&self.device, set);
+                }
                 GpuResource::PipelineLayout(l) => unsafe {
                     self.device.destroy_pipeline_layout(l, None);
                 },
@@ -86,6 +152,25 @@ impl VkRenderer {
                     self.vert_alloc.free(first_vertex, vertex_count);
                     self.idx_alloc.free(first_index, index_count);
                 }
+                GpuResource::MaterialSlot(slot) => self.material_pool.free(slot),
+                GpuResource::Pass(pass) => pass.destroy_retired(
+                    &self.device,
+                    self.allocator.as_mut().expect("allocator missing"),
+                    &mut self.descriptors.persistent,
+                ),
+                GpuResource::Swapchain {
+                    swapchain,
+                    views,
+                    frames,
+                } => unsafe {
+                    for view in views {
+                        self.device.destroy_image_view(view, None);
+                    }
+                    for f in frames {
+                        f.destroy(&self.device);
+                    }
+                    self.swapchain_loader.destroy_swapchain(swapchain, None);
+                },
             }
         }
     }
@@ -96,6 +181,11 @@ impl VkRenderer {
             return Ok(());
         };
 
+        // Writes fresh .spv files for any edited GLSL, which the mtime
+        // check below then treats like any other .spv change.
+        #[cfg(feature = "runtime-shader-compile")]
+        crate::shader_compile::recompile_changed(&mut dev.glsl_sources);
+
         let vm = std::fs::metadata(&dev.vert_spv)
             .and_then(|m| m.modified())
             .ok();
@@ -120,23 +210,13 @@ impl VkRenderer {
             dev.frag_mtime = t;
         }
 
-        // Ensure no in-flight use of old pipeline while swapping.
-        unsafe {
-            self.device.device_wait_idle().ok();
-        }
-
         // Rebuild using the same loader (reads from shader_dir(), i.e.
         // CUBIC_SHADER_DIR if set, else assets/shaders/)
-        let (new_layout, new_pipeline) = create_pipeline(
+        let (new_layout, new_pipeline, reflected) = create_pipeline(
             &self.device,
             self.pipeline_cache,
-            &PipelineConfig {
-                color_format: self.format,
-                depth_format: self.depth_format,
-                set_layout_camera: self.desc_set_layout_camera,
-                set_layout_material: self.desc_set_layout_material,
-                set_layout_indirect_graphics: self.desc_set_layout_indirect_graphics,
-            },
+            &self.pipeline_config(),
+            &PipelineDesc::default(),
         )?;
 
         self.trash.push(DeferredDrop {
@@ -149,134 +229,56 @@ impl VkRenderer {
         });
         self.pipeline_layout = new_layout;
         self.pipeline = new_pipeline;
+        self.pipeline_reflected = reflected;
+        // The prepass and shadow pipelines run tri.vert too; the prepass
+        // has to stay bit-identical to the scene pipeline's vertex stage.
+        let (new_layout, new_pipeline) = create_depth_prepass_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &self.pipeline_config(),
+        )?;
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::Pipeline(self.prepass_pipeline),
+        });
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::PipelineLayout(self.prepass_pipeline_layout),
+        });
+        self.prepass_pipeline_layout = new_layout;
+        self.prepass_pipeline = new_pipeline;
+        self.rebuild_shadow_pipeline()?;
+        // Registered pipelines may share the edited shaders.
+        self.rebuild_named_pipelines()?;
 
         // No re-record needed here: render() records each frame's command
         // buffer fresh against whatever self.pipeline currently is.
         Ok(())
     }
 
-    #[inline]
-    fn transition_to_color(&self, cmd: vk::CommandBuffer, image: vk::Image) {
-        let subrange = vk::ImageSubresourceRange {
-            aspect_mask: vk::ImageAspectFlags::COLOR,
-            base_mip_level: 0,
-            level_count: 1,
-            base_array_layer: 0,
-            layer_count: 1,
-        };
-
-        let pre_barrier = vk::ImageMemoryBarrier2 {
-            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER_2,
-            src_stage_mask: vk::PipelineStageFlags2::TOP_OF_PIPE,
-            src_access_mask: vk::AccessFlags2::empty(),
-            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
-            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags2::COLOR_ATTACHMENT_READ,
-            old_layout: vk::ImageLayout::UNDEFINED,
-            new_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-            image,
-            subresource_range: subrange,
-            ..Default::default()
-        };
-
-        let dep_pre = vk::DependencyInfo {
-            s_type: vk::StructureType::DEPENDENCY_INFO,
-            image_memory_barrier_count: 1,
-            p_image_memory_barriers: &pre_barrier,
-            ..Default::default()
-        };
-        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dep_pre) };
-    }
-
-    #[inline]
-    fn transition_depth_to_attachment(&self, cmd: vk::CommandBuffer, image: vk::Image) {
-        let subrange = vk::ImageSubresourceRange {
-            aspect_mask: depth_aspect_mask(self.depth_format),
-            base_mip_level: 0,
-            level_count: 1,
-            base_array_layer: 0,
-            layer_count: 1,
-        };
-
-        let pre = vk::ImageMemoryBarrier2 {
-            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER_2,
-            src_stage_mask: vk::PipelineStageFlags2::TOP_OF_PIPE,
-            src_access_mask: vk::AccessFlags2::empty(),
-            dst_stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
-                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
-            dst_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
-                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
-            old_layout: vk::ImageLayout::UNDEFINED,
-            new_layout: depth_attachment_layout(self.depth_format),
-            image,
-            subresource_range: subrange,
-            ..Default::default()
-        };
-        let dep = vk::DependencyInfo {
-            s_type: vk::StructureType::DEPENDENCY_INFO,
-            image_memory_barrier_count: 1,
-            p_image_memory_barriers: &pre,
-            ..Default::default()
-        };
-        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dep) };
-    }
-
-    #[inline]
-    fn begin_rendering(&self, cmd: vk::CommandBuffer, image_view: vk::ImageView) {
-        let color_att = vk::RenderingAttachmentInfo {
-            s_type: vk::StructureType::RENDERING_ATTACHMENT_INFO,
-            image_view,
-            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            clear_value: self.clear,
-            ..Default::default()
-        };
-
-        let depth_att = vk::RenderingAttachmentInfo {
-            s_type: vk::StructureType::RENDERING_ATTACHMENT_INFO,
-            image_view: self.depth_view,
-            image_layout: depth_attachment_layout(self.depth_format),
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::DONT_CARE,
-            clear_value: vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 0.0,
-                    stencil: 0,
-                },
-            },
-            ..Default::default()
-        };
-
-        let render_area = vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: self.extent,
-        };
-
-        let rendering_info = vk::RenderingInfo {
-            s_type: vk::StructureType::RENDERING_INFO,
-            render_area,
-            layer_count: 1,
-            color_attachment_count: 1,
-            p_color_attachments: &color_att,
-            p_depth_attachment: &depth_att,
-            ..Default::default()
-        };
-
-        unsafe { self.device.cmd_begin_rendering(cmd, &rendering_info) };
-    }
+    /// Phase 1 of the GPU-driven draw: write candidates and their bounding
+    /// spheres, dispatch indirect-cull compute (frustum test + compaction),
+    /// and leave the indirect/count buffers ready for the draw call. Runs
+    /// as the graph's "indirect cull" pass, outside any rendering scope;
+    /// the graph fences the results off from the scene pass.
+    fn cull_compute_prepass(&self, cmd: vk::CommandBuffer, frame: usize) {
+        // Every queued draw gets a candidate slot (the vertex shader reads
+        // per-object data from it either way), but only the default
+        // pipeline + material draws — sorted to the front by render_frame — are
+        // expanded into indirect commands; the rest are recorded directly
+        // by record_pipeline_draws.
+        let written = self.pending_draws.len().min(MAX_INDIRECT_DRAWS as usize);
+        let candidate_count = self.default_draw_count() as u32;
 
-    /// Phase 1 of the GPU-driven draw: write candidates, dispatch indirect-cull
-    /// compute, and leave the indirect/count buffers ready for the draw call.
-    /// Must run OUTSIDE the render pass (before vkCmdBeginRendering).
-    fn cull_compute_prepass(&self, cmd: vk::CommandBuffer, image_index: usize) {
-        let candidate_count = self.pending_draws.len() as u32;
-
-        // Write this frame's DrawCandidate array to the host-mapped buffer.
-        if candidate_count > 0 {
-            let ptr = self.candidate_ptrs[image_index] as *mut DrawCandidate;
-            for (i, (handle, push)) in self.pending_draws.iter().enumerate() {
-                let mesh = match self.meshes.get(handle.0 as usize) {
+        // Write this frame's DrawCandidate array (and the matching bounds
+        // array) to the host-mapped buffers.
+        if written > 0 {
+            let ptr = self.candidate_ptrs[frame] as *mut DrawCandidate;
+            let bounds_ptr = self.bounds_ptrs[frame] as *mut [f32; 4];
+            for (i, QueuedDraw { mesh, push, .. }) in
+                self.pending_draws[..written].iter().enumerate()
+            {
+                let mesh = match self.meshes.get(mesh.0 as usize) {
                     Some(m) => m,
                     None => continue,
                 };
@@ -292,11 +294,22 @@ impl VkRenderer {
                             tex_index: push.tex_index,
                         },
                     );
+                    std::ptr::write(bounds_ptr.add(i), mesh.bounds);
                 }
             }
         }
 
-        // --- Compute dispatch: expand candidates → indirect commands ---
+        // One frustum is all the shader tests, so with several viewports
+        // every candidate is kept.
+        let view_projs = self.cull_view_projs(self.view_aspect());
+        let push = CullPush {
+            candidate_count,
+            cull_enabled: (self.gpu_culling && view_projs.len() == 1) as u32,
+            _pad: [0; 2],
+            planes: frustum_planes(&view_projs[0]),
+        };
+
+        // --- Compute dispatch: cull candidates → compacted indirect commands ---
         // Zero the draw-count atomics before the compute shader writes them.
         // TRANSFER_DST ensures vkCmdFillBuffer completes before COMPUTE reads.
         let fill_to_compute = vk::MemoryBarrier2 {
@@ -307,19 +320,9 @@ impl VkRenderer {
             dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
             ..Default::default()
         };
-        let compute_to_indirect = vk::MemoryBarrier2 {
-            s_type: vk::StructureType::MEMORY_BARRIER_2,
-            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
-            src_access_mask: vk::AccessFlags2::SHADER_WRITE,
-            dst_stage_mask: vk::PipelineStageFlags2::DRAW_INDIRECT
-                | vk::PipelineStageFlags2::VERTEX_SHADER,
-            dst_access_mask: vk::AccessFlags2::INDIRECT_COMMAND_READ
-                | vk::AccessFlags2::SHADER_READ,
-            ..Default::default()
-        };
         unsafe {
             self.device
-                .cmd_fill_buffer(cmd, self.draw_count_bufs[image_index], 0, 4, 0);
+                .cmd_fill_buffer(cmd, self.draw_count_bufs[frame], 0, 4, 0);
             let dep = vk::DependencyInfo {
                 s_type: vk::StructureType::DEPENDENCY_INFO,
                 memory_barrier_count: 1,
@@ -338,7 +341,7 @@ impl VkRenderer {
                 vk::PipelineBindPoint::COMPUTE,
                 self.indirect_cull_pipeline_layout,
                 0,
-                std::slice::from_ref(&self.indirect_compute_desc_sets[image_index]),
+                std::slice::from_ref(&self.indirect_compute_desc_sets[frame]),
                 &[],
             );
             self.device.cmd_push_constants(
@@ -346,60 +349,248 @@ impl VkRenderer {
                 self.indirect_cull_pipeline_layout,
                 vk::ShaderStageFlags::COMPUTE,
                 0,
-                bytemuck::bytes_of(&candidate_count),
+                bytemuck::bytes_of(&push),
             );
             let groups = candidate_count.div_ceil(64).max(1);
             self.device.cmd_dispatch(cmd, groups, 1, 1);
+        }
+    }
 
-            let dep2 = vk::DependencyInfo {
-                s_type: vk::StructureType::DEPENDENCY_INFO,
-                memory_barrier_count: 1,
-                p_memory_barriers: &compute_to_indirect,
-                ..Default::default()
+    /// Number of leading `pending_draws` entries using the default pipeline
+    /// and material (see the sort in render_frame), capped at the candidate
+    /// buffer size.
+    fn default_draw_count(&self) -> usize {
+        self.pending_draws
+            .iter()
+            .take(MAX_INDIRECT_DRAWS as usize)
+            .take_while(|d| {
+                d.pipeline == PipelineHandle::DEFAULT && d.material == MaterialHandle::DEFAULT
+            })
+            .count()
+    }
+
+    /// Order pending_draws for recording (see DrawSortKey): opaque draws
+    /// by pipeline in handle order, then material, front to back within
+    /// each, so default-material draws on the default pipeline lead (the
+    /// indirect path's share, and the shadow pass's casters follow); then
+    /// draws on blending pipelines, back to front.
+    fn sort_draws(&mut self) {
+        let forward = self.camera.forward();
+        let meshes = &self.meshes;
+        let pipelines = &self.named_pipelines;
+        self.pending_draws.sort_by_cached_key(|d| {
+            let bounds = meshes
+                .get(d.mesh.0 as usize)
+                .map_or(UNBOUNDED, |m| m.bounds);
+            let model = Mat4::from_cols_array_2d(&d.push.model);
+            let depth = draw_depth(forward, &model, bounds);
+            let blended = d.pipeline != PipelineHandle::DEFAULT
+                && pipelines
+                    .get(d.pipeline.0 as usize - 1)
+                    .is_some_and(|np| np.desc.blend != BlendMode::Opaque);
+            if blended {
+                DrawSortKey::transparent(depth)
+            } else {
+                DrawSortKey::opaque(d.pipeline.0, d.material.0, depth)
+            }
+        });
+    }
+
+    /// What the scene is culled against: the main camera's view_proj, or
+    /// both eyes' (see stereo.rs), or every viewport's (see viewports.rs).
+    /// Pre-rotation only turns clip space about Z, which maps the side
+    /// planes onto each other; the unrotated set culls the same.
+    fn cull_view_projs(&self, aspect: f32) -> Vec<Mat4> {
+        if let Some(eyes) = self.stereo_view_projs() {
+            return eyes.to_vec();
+        }
+        if self.viewport_views.is_empty() {
+            let convention = DepthConvention::ReverseZ;
+            return vec![clip::camera_view_proj(&self.camera, aspect, convention)];
+        }
+        self.viewport_views.iter().map(|v| v.view_proj).collect()
+    }
+
+    /// CPU frustum test of the draws record_pipeline_draws records, which
+    /// the cull pass never sees; a draw stays if any view sees it. Culled
+    /// ones are only marked, since default-pipeline draws with a material
+    /// still cast shadows.
+    fn cull_direct_draws(&mut self, aspect: f32) {
+        let start = self.default_draw_count();
+        let end = self.pending_draws.len().min(MAX_INDIRECT_DRAWS as usize);
+        let mut counts = CullCounts::default();
+        if self.gpu_culling && start < end {
+            let frustums: Vec<Frustum> = self
+                .cull_view_projs(aspect)
+                .iter()
+                .map(|m| Frustum::from_view_proj(m, DepthConvention::ReverseZ))
+                .collect();
+            for draw in &mut self.pending_draws[start..end] {
+                let Some(mesh) = self.meshes.get(draw.mesh.0 as usize) else {
+                    continue;
+                };
+                if mesh.bounds[3] < 0.0 {
+                    continue;
+                }
+                let model = Mat4::from_cols_array_2d(&draw.push.model);
+                draw.culled = !frustums
+                    .iter()
+                    .any(|f| f.contains_bounds(&model, mesh.bounds));
+                counts.tested += 1;
+                counts.culled += draw.culled as u32;
+            }
+        }
+        self.frame_stats.record_culling(counts);
+    }
+
+    /// The scene's descriptor sets for frame slot `frame`, in set order:
+    /// camera, default material, candidates.
+    pub(crate) fn scene_sets(&self, frame: usize) -> [vk::DescriptorSet; 3] {
+        [
+            self.camera_set.set,
+            self.material_desc_set,
+            self.indirect_graphics_desc_sets[frame],
+        ]
+    }
+
+    /// Phase 2b: draws queued on registered pipelines or with a material.
+    /// Recorded directly (not via the indirect buffer) right after the
+    /// indirect call, reusing its vertex/index buffers and, until the
+    /// pipeline changes, its descriptor sets; set 1 is rebound whenever
+    /// the material changes. first_instance is the candidate slot, exactly
+    /// as the cull shader would have set it, and the part of the draw's
+    /// PushData the pipeline's shaders read is pushed too.
+    fn record_pipeline_draws(&self, cmd: vk::CommandBuffer, frame: usize, view: &SceneView) {
+        self.record_direct_draws(
+            cmd,
+            self.default_draw_count(),
+            true,
+            &self.scene_sets(frame),
+            &view.offsets,
+        );
+    }
+
+    /// record_pipeline_draws over pending_draws[start..], with the default
+    /// pipeline and material already bound, and `sets` with the camera
+    /// set's dynamic `offsets`; `skip_culled` leaves out what
+    /// cull_direct_draws marked (only right for the scene's views). Each
+    /// pipeline has its own layout, reflected from its shaders, so the
+    /// sets are bound again with it whenever the pipeline changes.
+    pub(crate) fn record_direct_draws(
+        &self,
+        cmd: vk::CommandBuffer,
+        start: usize,
+        skip_culled: bool,
+        sets: &[vk::DescriptorSet; 3],
+        offsets: &[u32; 2],
+    ) {
+        let end = self.pending_draws.len().min(MAX_INDIRECT_DRAWS as usize);
+        let mut bound = PipelineHandle::DEFAULT;
+        let mut bound_material = MaterialHandle::DEFAULT;
+        for i in start..end {
+            let draw = self.pending_draws[i];
+            if skip_culled && draw.culled {
+                continue;
+            }
+            let (pipeline, layout, reflected) = if draw.pipeline == PipelineHandle::DEFAULT {
+                (
+                    self.pipeline,
+                    self.pipeline_layout,
+                    &self.pipeline_reflected,
+                )
+            } else {
+                let Some(np) = self.named_pipelines.get(draw.pipeline.0 as usize - 1) else {
+                    continue;
+                };
+                (np.pipeline, np.layout, &np.reflected)
+            };
+            let set_count = reflected.sets.len();
+            let Some(mesh) = self.meshes.get(draw.mesh.0 as usize) else {
+                continue;
             };
-            self.device.cmd_pipeline_barrier2(cmd, &dep2);
+            if mesh.index_count == 0 {
+                continue;
+            }
+            unsafe {
+                if draw.pipeline != bound {
+                    self.device
+                        .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                    let mut rebound = *sets;
+                    rebound[1] = self.material_set(draw.material);
+                    if set_count > 0 {
+                        self.device.cmd_bind_descriptor_sets(
+                            cmd,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            layout,
+                            0,
+                            &rebound[..set_count],
+                            offsets,
+                        );
+                    }
+                    bound = draw.pipeline;
+                    bound_material = draw.material;
+                } else if draw.material != bound_material && set_count > 1 {
+                    self.device.cmd_bind_descriptor_sets(
+                        cmd,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        layout,
+                        1,
+                        &[self.material_set(draw.material)],
+                        &[],
+                    );
+                    bound_material = draw.material;
+                }
+                if let Some(range) = reflected.push_constants {
+                    let bytes = bytemuck::bytes_of(&draw.push);
+                    self.device.cmd_push_constants(
+                        cmd,
+                        layout,
+                        range.stage_flags,
+                        range.offset,
+                        &bytes[range.offset as usize..(range.offset + range.size) as usize],
+                    );
+                }
+                self.device.cmd_draw_indexed(
+                    cmd,
+                    mesh.index_count,
+                    1,
+                    mesh.first_index,
+                    mesh.first_vertex,
+                    i as u32,
+                );
+            }
         }
     }
 
     /// Phase 2: the actual indirect draw call. Must run INSIDE the render pass
-    /// (between vkCmdBeginRendering and vkCmdEndRendering).
-    fn record_indirect_draws(&self, cmd: vk::CommandBuffer, image_index: usize) -> Result<()> {
-        if self.pipeline == vk::Pipeline::null() {
+    /// (the graph's "scene" pass opens it). `depth_only` draws the same
+    /// commands with the prepass pipeline instead, for the "depth prepass"
+    /// pass; both pipelines share one layout shape, so the sets bind alike.
+    /// Draws through `view`, over its part of the scene target. `frame` is
+    /// the frame slot, as for the graph.
+    fn record_indirect_draws(
+        &self,
+        cmd: vk::CommandBuffer,
+        frame: usize,
+        depth_only: bool,
+        view: &SceneView,
+    ) -> Result<()> {
+        let (pipeline, layout) = if depth_only {
+            (self.prepass_pipeline, self.prepass_pipeline_layout)
+        } else {
+            (self.pipeline, self.pipeline_layout)
+        };
+        if pipeline == vk::Pipeline::null() {
             return Err(anyhow!("pipeline is VK_NULL_HANDLE at record time"));
         }
-        let vp = vk::Viewport {
-            x: 0.0,
-            y: self.extent.height as f32,
-            width: self.extent.width as f32,
-            height: -(self.extent.height as f32),
-            min_depth: 0.0,
-            max_depth: 1.0,
-        };
-        let sc = vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: self.extent,
-        };
-        let sets = [
-            self.desc_sets[image_index],                   // set 0: camera
-            self.material_desc_set,                        // set 1: bindless textures
-            self.indirect_graphics_desc_sets[image_index], // set 2: candidates
-        ];
+        let sets = self.scene_sets(frame);
         let offsets = [0_u64];
         unsafe {
             self.device
-                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
-            self.device
-                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
-            self.device
-                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&sc));
-            self.device.cmd_bind_descriptor_sets(
-                cmd,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &sets,
-                &[],
-            );
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        }
+        self.bind_scene_view(cmd, layout, &sets, view);
+        unsafe {
             // One shared vertex/index buffer pair for all meshes.
             self.device.cmd_bind_vertex_buffers(
                 cmd,
@@ -413,9 +604,9 @@ impl VkRenderer {
             // involvement beyond writing the candidate array above.
             self.device.cmd_draw_indexed_indirect_count(
                 cmd,
-                self.indirect_bufs[image_index],
+                self.indirect_bufs[frame],
                 0,
-                self.draw_count_bufs[image_index],
+                self.draw_count_bufs[frame],
                 0,
                 MAX_INDIRECT_DRAWS,
                 std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
@@ -424,48 +615,307 @@ impl VkRenderer {
         Ok(())
     }
 
-    #[inline]
-    fn transition_to_present(&self, cmd: vk::CommandBuffer, image: vk::Image) {
-        let subrange = vk::ImageSubresourceRange {
-            aspect_mask: vk::ImageAspectFlags::COLOR,
-            base_mip_level: 0,
-            level_count: 1,
-            base_array_layer: 0,
-            layer_count: 1,
-        };
-
-        let post_barrier = vk::ImageMemoryBarrier2 {
-            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER_2,
-            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
-            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
-            dst_stage_mask: vk::PipelineStageFlags2::NONE,
-            dst_access_mask: vk::AccessFlags2::empty(),
-            old_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-            new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+    /// This frame's passes: user compute, indirect cull, light clusters
+    /// (with enough lights; see light_clusters.rs), depth prepass (if
+    /// enabled), render targets (those with a camera), scene (once per
+    /// viewport, as is the prepass; see viewports.rs), object ids
+    /// (with picking on); or with stereo on, stereo scene and composite in
+    /// place of the prepass, scene and object ids (see stereo.rs); then
+    /// FSR1 (if upscaling with it), post-processing, tonemap (HDR or
+    /// offscreen scene only), text and egui (if queued), capture (on
+    /// captured frames). Barriers, layouts and rendering
+    /// scopes are the graph's business (see frame_graph.rs); each pass
+    /// only declares what it touches. `frame` is the frame slot (see
+    /// FRAMES_IN_FLIGHT) whose indirect-draw buffers and sets it uses.
+    fn build_frame_graph(
+        &self,
+        image: vk::Image,
+        image_view: vk::ImageView,
+        frame: usize,
+    ) -> FrameGraph {
+        let mut g = FrameGraph::new(self.extent);
+        let depth_layout = depth_attachment_layout(self.depth_format);
+        let swapchain = g.import_image(
+            "swapchain",
             image,
-            subresource_range: subrange,
-            ..Default::default()
-        };
+            image_view,
+            vk::ImageAspectFlags::COLOR,
+            Access::acquired(),
+        );
+        g.export(swapchain, Some(Access::present()));
+        // Shared by every frame in flight: the previous frame's depth
+        // writes have to land before this frame's clear.
+        let depth = g.import_image(
+            "depth",
+            self.depth_image,
+            self.depth_view,
+            depth_aspect_mask(self.depth_format),
+            Access::depth_attachment(LoadOp::DontCare, false, depth_layout),
+        );
+        // GpuBuffers and mesh vertices compute may write; read by last
+        // frame's draws, read again by this one's, kept for the next.
+        let user_buffers = g.import_buffer("compute buffers", Access::graphics_read());
+        g.export(user_buffers, None);
+        let indirect = g.import_buffer("indirect commands", Access::graphics_read());
+        // Rewritten (or, with shadows off, left as the placeholder) every
+        // frame, so last frame's contents never matter; only its reads
+        // have to finish first.
+        let shadow_map = g.import_image(
+            "shadow map",
+            self.shadow.map.image,
+            self.shadow.map.array_view,
+            vk::ImageAspectFlags::DEPTH,
+            Access::stale(vk::PipelineStageFlags2::FRAGMENT_SHADER),
+        );
 
-        let dep_post = vk::DependencyInfo {
-            s_type: vk::StructureType::DEPENDENCY_INFO,
-            image_memory_barrier_count: 1,
-            p_image_memory_barriers: &post_barrier,
-            ..Default::default()
+        if !self.pending_compute.is_empty() {
+            g.add_pass(
+                "compute",
+                &[(user_buffers, Access::compute_write())],
+                |r, cmd| {
+                    r.record_compute(cmd);
+                    Ok(())
+                },
+            );
+        }
+        g.add_pass(
+            "indirect cull",
+            &[(indirect, Access::compute_write())],
+            move |r, cmd| {
+                r.cull_compute_prepass(cmd, frame);
+                Ok(())
+            },
+        );
+        // Shared by every frame in flight, like depth; only the scene
+        // pass reads it (see light_clusters.rs).
+        let light_clusters = self.light_clusters_active().then(|| {
+            let clusters = g.import_buffer("light clusters", Access::graphics_read());
+            g.add_pass(
+                "light clusters",
+                &[(clusters, Access::compute_write())],
+                |r, cmd| r.record_light_clusters(cmd),
+            );
+            clusters
+        });
+
+        // With the HDR tonemap pass active the scene renders into its FP16
+        // target, and the swapchain image is only touched by the tonemap.
+        // Last frame read it in FSR1's first pass instead, if that's on.
+        let scene_target = match self.tonemap.as_ref() {
+            Some(tm) => g.import_image(
+                "hdr target",
+                tm.image,
+                tm.view,
+                vk::ImageAspectFlags::COLOR,
+                if self.fsr1.is_some() {
+                    Access::sampled_compute()
+                } else {
+                    Access::sampled_fragment()
+                },
+            ),
+            None => swapchain,
+        };
+        // Below 1.0 render scale, the passes drawing the scene only cover
+        // the scaled part of depth (see upscale.rs).
+        let scene_extent = self.scene_extent();
+        let clear = self.clear;
+        let depth_clear = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 0.0,
+                stencil: 0,
+            },
         };
-        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dep_post) };
+        // With the prepass on, it clears and fills depth and the scene
+        // loads it: the scene pipeline's GREATER_OR_EQUAL test then passes
+        // only the visible surface, and early-Z rejects the rest before
+        // the fragment shader runs. The scene still writes depth (the same
+        // values), which registered pipelines' draws rely on.
+        if self.shadows_active() {
+            g.add_pass(
+                "shadows",
+                &[
+                    (
+                        shadow_map,
+                        Access::depth_target(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL),
+                    ),
+                    (user_buffers, Access::graphics_read()),
+                ],
+                move |r, cmd| {
+                    r.record_shadow_pass(cmd, frame);
+                    Ok(())
+                },
+            );
+        }
+        let stereo = self.stereo_view_projs().is_some();
+        let scene_depth = if self.depth_prepass && !stereo {
+            g.add_pass_at(
+                "depth prepass",
+                scene_extent,
+                &[
+                    (
+                        depth,
+                        Access::depth_attachment(LoadOp::Clear(depth_clear), true, depth_layout),
+                    ),
+                    (indirect, Access::graphics_read()),
+                    (user_buffers, Access::graphics_read()),
+                ],
+                move |r, cmd| {
+                    for view in r.scene_views() {
+                        r.record_indirect_draws(cmd, frame, true, &view)?;
+                    }
+                    Ok(())
+                },
+            );
+            LoadOp::Load
+        } else {
+            LoadOp::Clear(depth_clear)
+        };
+        // Render targets with a camera draw the scene ahead of it (see
+        // render_target.rs); the scene then reads every target, since any
+        // material may show one.
+        let shared = [
+            (indirect, Access::graphics_read()),
+            (user_buffers, Access::graphics_read()),
+            (shadow_map, Access::sampled_fragment()),
+        ];
+        let targets = self.add_render_target_passes(&mut g, frame, &shared);
+        if stereo {
+            self.add_stereo_passes(&mut g, frame, scene_target, depth, &shared);
+        } else {
+            let mut scene_uses = vec![
+                (scene_target, Access::color_attachment(LoadOp::Clear(clear))),
+                (
+                    depth,
+                    Access::depth_attachment(scene_depth, false, depth_layout),
+                ),
+            ];
+            scene_uses.extend_from_slice(&shared);
+            scene_uses.extend(targets.iter().map(|&t| (t, Access::sampled_fragment())));
+            scene_uses.extend(light_clusters.map(|c| (c, Access::graphics_read())));
+            g.add_pass_at("scene", scene_extent, &scene_uses, move |r, cmd| {
+                // Once per view; with no viewports set, one over the whole
+                // target through the main camera.
+                for view in r.scene_views() {
+                    r.record_sky(cmd, view.sky_view_proj, view.area);
+                    r.record_indirect_draws(cmd, frame, false, &view)?;
+                    r.record_pipeline_draws(cmd, frame, &view);
+                }
+                r.record_debug_lines(cmd)
+            });
+            // With picking on, the draws again as object ids (see picking.rs).
+            self.add_object_id_pass(&mut g, frame, depth, &shared);
+        }
+
+        // The passes below bind depth too: their pipelines were built
+        // against the depth format and Vulkan wants it bound to match.
+        let overlay_depth = Access::depth_attachment(LoadOp::DontCare, false, depth_layout);
+        // FSR1 upscales the scene target in two compute passes; the
+        // tonemap then reads the second one's output. Both targets are
+        // rewritten whole every frame.
+        let mut tonemap_source = scene_target;
+        if let Some(fsr) = self.fsr1.as_ref() {
+            let (image, view) = fsr.easu_target();
+            let easu = g.import_image(
+                "fsr1 easu target",
+                image,
+                view,
+                vk::ImageAspectFlags::COLOR,
+                Access::stale(vk::PipelineStageFlags2::COMPUTE_SHADER),
+            );
+            let (image, view) = fsr.output_target();
+            let output = g.import_image(
+                "fsr1 output",
+                image,
+                view,
+                vk::ImageAspectFlags::COLOR,
+                Access::stale(vk::PipelineStageFlags2::FRAGMENT_SHADER),
+            );
+            g.add_pass(
+                "fsr1 easu",
+                &[
+                    (scene_target, Access::sampled_compute()),
+                    (easu, Access::storage_image_write()),
+                ],
+                |r, cmd| {
+                    r.record_fsr1_easu(cmd);
+                    Ok(())
+                },
+            );
+            g.add_pass(
+                "fsr1 rcas",
+                &[
+                    (easu, Access::sampled_compute()),
+                    (output, Access::storage_image_write()),
+                ],
+                |r, cmd| {
+                    r.record_fsr1_rcas(cmd);
+                    Ok(())
+                },
+            );
+            tonemap_source = output;
+        }
+        // Then the post-processing chain, if any, over whichever that was.
+        if let Some(post) = self.post.as_ref() {
+            tonemap_source = post.add_passes(&mut g, tonemap_source);
+        }
+        if scene_target != swapchain {
+            // Letterbox bars are the only part it doesn't draw over.
+            let bars = if self.output_area().extent != self.extent {
+                LoadOp::Clear(vk::ClearValue {
+                    color: vk::ClearColorValue {
+                        float32: [0.0, 0.0, 0.0, 1.0],
+                    },
+                })
+            } else {
+                LoadOp::DontCare
+            };
+            g.add_pass(
+                "tonemap",
+                &[
+                    (tonemap_source, Access::sampled_fragment()),
+                    (swapchain, Access::color_attachment(bars)),
+                    (depth, overlay_depth),
+                ],
+                |r, cmd| {
+                    r.record_tonemap(cmd);
+                    Ok(())
+                },
+            );
+        }
+        if self.text_pending() {
+            g.add_pass(
+                "text",
+                &[
+                    (swapchain, Access::color_attachment(LoadOp::Load)),
+                    (depth, overlay_depth),
+                ],
+                |r, cmd| r.record_text(cmd),
+            );
+        }
+        if self.egui_pending.is_some() {
+            g.add_pass(
+                "egui",
+                &[
+                    (swapchain, Access::color_attachment(LoadOp::Load)),
+                    (depth, overlay_depth),
+                ],
+                |r, cmd| r.record_egui(cmd),
+            );
+        }
+        self.add_capture_pass(&mut g, swapchain, image);
+        g
     }
 
-    // Records draws queued via draw_mesh() into the given image's command
-    // buffer. Called fresh every frame for the just-acquired image (see
-    // render()) — safe to reset because acquire_next_image only returns an
-    // image index once the GPU is done with its previous use.
+    // Records draws queued via draw_mesh() into the frame slot's command
+    // buffer, for the just-acquired image. Called fresh every frame (see
+    // render()) — safe to reset because render_frame has waited for the
+    // slot's previous submit.
     fn record_one_command(
         &mut self,
         cmd: vk::CommandBuffer,
         image: vk::Image,
         image_view: vk::ImageView,
-        image_index: usize,
+        frame: usize,
     ) -> Result<()> {
         // reset + begin
         unsafe {
@@ -477,20 +927,10 @@ impl VkRenderer {
             ..Default::default()
         };
         unsafe { self.device.begin_command_buffer(cmd, &begin)? };
+        self.record_debug_markers(cmd);
 
-        // body
-        // Phase 1: compute cull — MUST happen outside the render pass.
-        self.cull_compute_prepass(cmd, image_index);
-        self.transition_to_color(cmd, image);
-        self.transition_depth_to_attachment(cmd, self.depth_image);
-        self.begin_rendering(cmd, image_view);
-        // Phase 2: indirect draw — inside the render pass.
-        self.record_indirect_draws(cmd, image_index)?;
-        // Egui overlay, if queued — still inside the render pass, on top of
-        // the scene, before the image transitions to present.
-        self.record_egui(cmd)?;
-        unsafe { self.device.cmd_end_rendering(cmd) };
-        self.transition_to_present(cmd, image);
+        self.build_frame_graph(image, image_view, frame)
+            .execute(self, cmd)?;
 
         // end
         unsafe { self.device.end_command_buffer(cmd)? };
@@ -499,12 +939,57 @@ impl VkRenderer {
 
     // STRICT PER-FRAME ORDER:
     // 1) acquire_next_image (waits on acquire semaphore)
-    // 2) record this frame's draws into the acquired image's command buffer
-    //    (acquire_next_image only returns an image once the GPU is done
-    //    with its previous use, so resetting its command buffer here is safe)
+    // 2) record this frame's draws into the frame slot's command buffer
+    //    (the slot's previous submit was waited for before acquiring, so
+    //    resetting it and rewriting its indirect buffers here is safe)
     // 3) queue_submit (signals render-finished for THIS image)
-    // 4) queue_present (waits on render-finished)
+    // 4) queue_present on the present queue (waits on render-finished)
     // Each swapchain image has its own FrameSync; do not cross-use semaphores.
+    /// set_target_fps's limiter, run after every render_frame. With
+    /// VK_KHR_present_wait, first wait (at most one frame slot) for the
+    /// previous frame to actually reach the display: that keeps a mailbox
+    /// swapchain from racing ahead of the cap and lines the CPU deadline up
+    /// with scanout. Then the sleep+spin pacer. Errors here (timeout, out of
+    /// date, even device loss) are left for the next frame to report.
+    pub(crate) fn pace_frame(&mut self) {
+        let Some(frame) = self.pacer.frame_time() else {
+            return;
+        };
+        if let Some(pw) = &self.present_wait {
+            if self.present_id > 1 {
+                let timeout = frame.as_nanos() as u64;
+                let _ =
+                    unsafe { pw.wait_for_present(self.swapchain, self.present_id - 1, timeout) };
+            }
+        }
+        self.pacer.wait();
+    }
+
+    /// LatencyMode::Low's wait, after a frame was presented: until that
+    /// present reaches the display with VK_KHR_present_wait, otherwise
+    /// until the GPU has finished the frame (the timeline), the nearest
+    /// observable point before it. Bounded, so a present the engine holds
+    /// back (minimized, occluded) can't hang the app; errors are left for
+    /// the next frame, as in pace_frame.
+    pub(crate) fn wait_for_latency(&mut self) {
+        if self.latency_mode != LatencyMode::Low {
+            return;
+        }
+        const TIMEOUT_NS: u64 = 100_000_000;
+        if let Some(pw) = &self.present_wait {
+            let _ = unsafe { pw.wait_for_present(self.swapchain, self.present_id, TIMEOUT_NS) };
+            return;
+        }
+        let wait_info = vk::SemaphoreWaitInfo {
+            s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+            semaphore_count: 1,
+            p_semaphores: &self.timeline,
+            p_values: &self.timeline_value,
+            ..Default::default()
+        };
+        let _ = unsafe { self.device.wait_semaphores(&wait_info, TIMEOUT_NS) };
+    }
+
     pub(crate) fn render_frame(&mut self) -> Result<()> {
         // Guard on pause
         if self.paused {
@@ -514,10 +999,19 @@ impl VkRenderer {
         if self.should_skip_for_backoff() {
             return Ok(());
         }
+        // A bad edit (e.g. mismatched stage interfaces) keeps the previous
+        // pipeline running rather than failing the frame.
         #[cfg(debug_assertions)]
-        self.hot_reload_shaders_if_changed()?;
+        if let Err(e) = self.hot_reload_shaders_if_changed() {
+            tracing::error!("vk: shader hot-reload failed: {e:#}");
+        }
+
+        if let Err(e) = self.apply_pending_resize(false) {
+            tracing::warn!("vk: resize: {e:#}");
+        }
 
         // 1) Acquire
+        let acquire = tracing::debug_span!("acquire").entered();
         let acq_sem = self.acq_slots[self.acq_index].sem;
         let acq_last_signal_value = self.acq_slots[self.acq_index].last_signal_value;
         if acq_last_signal_value > 0 {
@@ -530,11 +1024,23 @@ impl VkRenderer {
                 ..Default::default()
             };
             unsafe {
-                self.device.wait_semaphores(&wait_info, u64::MAX)?;
+                self.device
+                    .wait_semaphores(&wait_info, u64::MAX)
+                    .map_err(|e| vk_error(e, "wait"))?;
             }
         }
 
         self.drain_trash();
+        // Same fallback as drain_trash: reclaim nothing if the query fails.
+        let signaled =
+            unsafe { self.device.get_semaphore_counter_value(self.timeline) }.unwrap_or(0);
+        self.descriptors.transient.reclaim(&self.device, signaled);
+        self.uploader.reclaim(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        );
+        self.promote_resident_textures();
+        self.capture_begin_frame(signaled);
 
         let (image_index, _) = match unsafe {
             self.swapchain_loader.acquire_next_image(
@@ -546,12 +1052,12 @@ impl VkRenderer {
         } {
             Ok(pair) => pair,
             Err(e) if is_swapchain_out_of_date(e) => {
-                self.backoff_frames = 2;
-                let want = RenderSize {
-                    width: self.extent.width,
-                    height: self.extent.height,
-                };
-                let _ = self.recreate_swapchain(want);
+                // Straight to the latest size, skipping no frames unless
+                // that fails: a drag over a fixed-extent surface lands here
+                // every time the size moves on.
+                if self.apply_pending_resize(true).is_err() {
+                    self.backoff_frames = 2;
+                }
                 return Ok(());
             }
             Err(e) if is_surface_lost(e) => {
@@ -573,26 +1079,54 @@ impl VkRenderer {
                     };
                     let _ = self.recreate_swapchain(want);
                 } else {
+                    // Paused until a resize; the caller can try
+                    // suspend/resume meanwhile.
                     self.paused = true;
+                    return Err(RenderError::SurfaceLost.into());
                 }
                 return Ok(());
             }
-            Err(e) if is_device_lost(e) => return Err(anyhow!("vk: device lost during acquire")),
-            Err(e) => return Err(anyhow!("acquire_next_image: {e:?}")),
+            Err(e) => return Err(vk_error(e, "acquire")),
         };
+        drop(acquire);
 
+        let record = tracing::debug_span!("record").entered();
         let img = image_index as usize;
         let render_finished = self.frames[img].render_finished;
-        let cmd = self.cmd_bufs[img];
-        let aspect = self.extent.width as f32 / self.extent.height as f32;
-        self.update_camera_ubo_for_image(img, &self.camera, aspect)?;
+        // Per frame slot, not per image: how many images the swapchain has
+        // doesn't matter to them, so recreating it leaves them be.
+        let slot = self.acq_index;
+        let cmd = self.cmd_bufs[slot];
+        let aspect = self.view_aspect();
+        let camera = self.camera;
+        self.write_frame_uniforms(&camera, aspect)?;
+
+        self.sort_draws();
+        self.cull_direct_draws(aspect);
 
         // Record this frame's draws (queued via draw_mesh()) into the
         // image we just acquired, then clear the queue for the next frame.
-        self.record_one_command(cmd, self.images[img], self.image_views[img], img)?;
+        self.record_one_command(cmd, self.images[img], self.image_views[img], slot)?;
         self.pending_draws.clear();
+        // User compute may read uploaded GpuBuffers, or copy into the
+        // vertex buffer uploads also write; then it has to wait too.
+        let upload_wait_stage = if self.pending_compute.is_empty() {
+            vk::PipelineStageFlags2::VERTEX_INPUT
+        } else {
+            vk::PipelineStageFlags2::VERTEX_INPUT
+                | vk::PipelineStageFlags2::COMPUTE_SHADER
+                | vk::PipelineStageFlags2::COPY
+        };
+        self.pending_compute.clear();
+
+        // Kick this frame's mesh uploads (upload_mesh since last frame) so
+        // the submit below can wait for them.
+        self.uploader.flush(&self.device)?;
+        drop(record);
 
-        // 2) Submit (wait on acquire sem; signal render-finished; bump timeline)
+        // 2) Submit (wait on acquire sem + uploads; signal render-finished;
+        // bump timeline)
+        let submit = tracing::debug_span!("submit").entered();
         let next_value = self.timeline_value.wrapping_add(1);
 
         let stage_color = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
@@ -600,11 +1134,18 @@ impl VkRenderer {
 
         // Build the semaphore infos
         let wait_acquire = semaphore_submit_info_wait(acq_sem, 0, stage2_color);
+        // Waiting on an already-reached value (or 0, before any upload) is
+        // a no-op, so this is unconditional.
+        let wait_uploads = semaphore_submit_info_wait(
+            self.uploader.timeline(),
+            self.uploader.submitted_value(),
+            upload_wait_stage,
+        );
         let signal_present = semaphore_submit_info_signal(render_finished, 0, stage2_color);
         let signal_timeline = semaphore_submit_info_signal(self.timeline, next_value, stage2_color);
 
         // IMPORTANT: store in locals so the pointers in SubmitInfo2 stay valid
-        let waits = [wait_acquire];
+        let waits = [wait_acquire, wait_uploads];
         let signals = [signal_present, signal_timeline];
 
         let cmd_info = vk::CommandBufferSubmitInfo {
@@ -638,18 +1179,61 @@ impl VkRenderer {
             Ok(()) => {
                 self.timeline_value = next_value;
                 self.acq_slots[self.acq_index].last_signal_value = next_value;
+                self.staging_belt.finish_frame(next_value);
+                self.descriptors.transient.finish_frame(next_value);
+                self.capture_submitted(next_value);
             }
-            Err(vk::Result::ERROR_DEVICE_LOST) => {
-                return Err(anyhow!("vk: device lost during submit"));
-            }
-            Err(e) => {
-                return Err(anyhow!("queue_submit2: {e:?}"));
-            }
+            Err(e) => return Err(vk_error(e, "submit")),
         }
+        drop(submit);
 
-        // 3) Present (wait on render-finished)
+        // 3) Present (wait on render-finished), tagged with a present id
+        // when pace_frame can wait on it (VK_KHR_present_wait). With
+        // VK_EXT_swapchain_maintenance1 it also names the present mode (so
+        // apply_present_mode can switch without recreating) and signals
+        // this image's present fence, which recreate_swapchain waits on
+        // before tearing the old swapchain down.
+        let _present = tracing::debug_span!("present").entered();
+        let present_fence = self.frames[img].present_fence;
+        let mut p_next: *const std::ffi::c_void = std::ptr::null();
+        let present_mode_info = vk::SwapchainPresentModeInfoEXT {
+            s_type: vk::StructureType::SWAPCHAIN_PRESENT_MODE_INFO_EXT,
+            swapchain_count: 1,
+            p_present_modes: &self.present_mode,
+            ..Default::default()
+        };
+        let present_fence_info = vk::SwapchainPresentFenceInfoEXT {
+            s_type: vk::StructureType::SWAPCHAIN_PRESENT_FENCE_INFO_EXT,
+            p_next: &present_mode_info as *const _ as *const std::ffi::c_void,
+            swapchain_count: 1,
+            p_fences: &present_fence,
+            ..Default::default()
+        };
+        if present_fence != vk::Fence::null() {
+            // Signaled by this image's previous present, which acquiring
+            // it again means is (all but) done.
+            unsafe {
+                self.device
+                    .wait_for_fences(&[present_fence], true, u64::MAX)
+                    .map_err(|e| vk_error(e, "present fence"))?;
+                self.device.reset_fences(&[present_fence])?;
+            }
+            p_next = &present_fence_info as *const _ as *const std::ffi::c_void;
+        }
+        let present_id = self.present_id + 1;
+        let present_id_info = vk::PresentIdKHR {
+            s_type: vk::StructureType::PRESENT_ID_KHR,
+            p_next,
+            swapchain_count: 1,
+            p_present_ids: &present_id,
+            ..Default::default()
+        };
+        if self.present_wait.is_some() {
+            p_next = &present_id_info as *const _ as *const std::ffi::c_void;
+        }
         let present = vk::PresentInfoKHR {
             s_type: vk::StructureType::PRESENT_INFO_KHR,
+            p_next,
             wait_semaphore_count: 1,
             p_wait_semaphores: &render_finished,
             swapchain_count: 1,
@@ -658,15 +1242,20 @@ impl VkRenderer {
             ..Default::default()
         };
 
-        match unsafe { self.swapchain_loader.queue_present(self.queue, &present) } {
+        let present_res = unsafe {
+            self.swapchain_loader
+                .queue_present(self.present_queue, &present)
+        };
+        // Ids only have to increase per swapchain; whether or not this one
+        // got presented, it's been used.
+        self.present_id = present_id;
+        match present_res {
             Ok(_) => {}
             Err(e) if is_swapchain_out_of_date(e) => {
-                self.backoff_frames = 2;
-                let want = RenderSize {
-                    width: self.extent.width,
-                    height: self.extent.height,
-                };
-                let _ = self.recreate_swapchain(want);
+                // As for acquire.
+                if self.apply_pending_resize(true).is_err() {
+                    self.backoff_frames = 2;
+                }
                 return Ok(());
             }
             Err(e) if is_surface_lost(e) => {
@@ -688,12 +1277,14 @@ impl VkRenderer {
                     };
                     let _ = self.recreate_swapchain(want);
                 } else {
+                    // Paused until a resize; the caller can try
+                    // suspend/resume meanwhile.
                     self.paused = true;
+                    return Err(RenderError::SurfaceLost.into());
                 }
                 return Ok(());
             }
-            Err(e) if is_device_lost(e) => return Err(anyhow!("vk: device lost during present")),
-            Err(e) => return Err(anyhow!("queue_present: {e:?}")),
+            Err(e) => return Err(vk_error(e, "present")),
         }
 
         // Rotate acquire slot
@@ -702,3 +1293,13 @@ impl VkRenderer {
         Ok(())
     }
 }
+
+/// Inward-facing, normalized frustum planes (left, right, bottom, top,
+/// near) of `view_proj`, in the camera-relative world space model
+/// matrices map into, for the cull shader. The infinite far plane has
+/// nothing to contribute.
+fn frustum_planes(view_proj: &Mat4) -> [[f32; 4]; 5] {
+    let frustum = Frustum::from_view_proj(view_proj, DepthConvention::ReverseZ);
+    let p = frustum.planes;
+    [p[0], p[1], p[2], p[3], p[4]].map(|p| p.to_array())
+}
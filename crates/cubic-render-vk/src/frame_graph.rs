@@ -0,0 +1,716 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Per-frame render graph. Each frame's command buffer is described as a
+//! list of passes, each declaring how it touches a set of named resources
+//! (swapchain image, depth, HDR target, indirect buffers, ...); the graph
+//! then works out everything record_one_command used to spell out by hand:
+//!
+//! - dependencies: a pass depends on the last earlier pass writing a
+//!   resource it uses (read-after-write / write-after-write) and on every
+//!   earlier pass reading a resource it writes (write-after-read). The
+//!   declaration order is the order accesses logically happen in, exactly
+//!   like a command stream; nothing else constrains the schedule.
+//! - culling: passes nothing exported depends on are dropped (a resource is
+//!   exported when something outside the frame consumes it: the presented
+//!   image, buffers the app reads back or draws from next frame).
+//! - execution order: a topological sort of what's left, ties broken by
+//!   declaration order.
+//! - barriers: the graph tracks each resource's layout and last write/reads
+//!   and emits one sync2 barrier batch per pass, covering only the hazards
+//!   and layout changes that pass actually has; buffers share one global
+//!   memory barrier. Exported images get their final transition (e.g. to
+//!   PRESENT_SRC_KHR) after the last pass.
+//! - attachments: a pass that uses resources as colour/depth attachments
+//!   gets a dynamic-rendering scope over them (load/store ops from the
+//!   declaration) around its record callback.
+//!
+//! Built fresh every frame; it's a few small Vecs, not worth caching.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use anyhow::{Context, Result};
+use ash::vk;
+use tracing::trace;
+
+use crate::VkRenderer;
+
+/// Handle to a resource imported into one FrameGraph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ResourceId(usize);
+
+/// What happens to an attachment's previous contents when a pass begins.
+#[derive(Clone, Copy)]
+pub(crate) enum LoadOp {
+    Clear(vk::ClearValue),
+    /// Keep them; the only op that needs the old layout preserved.
+    Load,
+    DontCare,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AttachmentSlot {
+    Color,
+    Depth,
+}
+
+#[derive(Clone, Copy)]
+struct Attachment {
+    slot: AttachmentSlot,
+    load: LoadOp,
+    store: bool,
+}
+
+/// One way of touching a resource: pipeline stages, access mask, the image
+/// layout it needs (UNDEFINED for buffers), and whether it writes.
+#[derive(Clone, Copy)]
+pub(crate) struct Access {
+    stage: vk::PipelineStageFlags2,
+    access: vk::AccessFlags2,
+    layout: vk::ImageLayout,
+    write: bool,
+    attachment: Option<Attachment>,
+}
+
+const WRITE_ACCESS: vk::AccessFlags2 = vk::AccessFlags2::from_raw(
+    vk::AccessFlags2::SHADER_WRITE.as_raw()
+        | vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+        | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags2::HOST_WRITE.as_raw()
+        | vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+);
+
+impl Access {
+    /// The pass's colour attachment.
+    pub(crate) fn color_attachment(load: LoadOp) -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            access: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags2::COLOR_ATTACHMENT_READ,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            write: true,
+            attachment: Some(Attachment {
+                slot: AttachmentSlot::Color,
+                load,
+                store: true,
+            }),
+        }
+    }
+
+    /// The pass's depth attachment, in `layout` (see
+    /// depth_attachment_layout). `store` false = contents die with the pass.
+    pub(crate) fn depth_attachment(load: LoadOp, store: bool, layout: vk::ImageLayout) -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            access: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            layout,
+            write: true,
+            attachment: Some(Attachment {
+                slot: AttachmentSlot::Depth,
+                load,
+                store,
+            }),
+        }
+    }
+
+    /// Depth written by a pass that opens its own rendering scopes (one
+    /// per layer of an array image, say) instead of the graph's single
+    /// scope; in `layout`, like depth_attachment.
+    pub(crate) fn depth_target(layout: vk::ImageLayout) -> Self {
+        Self {
+            attachment: None,
+            ..Self::depth_attachment(LoadOp::DontCare, true, layout)
+        }
+    }
+
+    /// Last touched at `stage` by an earlier frame, contents no longer
+    /// wanted: layout UNDEFINED, so the first use this frame transitions
+    /// from scratch but still waits for that earlier use.
+    pub(crate) fn stale(stage: vk::PipelineStageFlags2) -> Self {
+        Self {
+            stage,
+            access: vk::AccessFlags2::empty(),
+            layout: vk::ImageLayout::UNDEFINED,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Copied out of, e.g. into a read-back buffer.
+    pub(crate) fn transfer_read() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COPY,
+            access: vk::AccessFlags2::TRANSFER_READ,
+            layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Copied into, e.g. a read-back buffer.
+    pub(crate) fn transfer_write() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COPY,
+            access: vk::AccessFlags2::TRANSFER_WRITE,
+            layout: vk::ImageLayout::UNDEFINED,
+            write: true,
+            attachment: None,
+        }
+    }
+
+    /// Read by the CPU through a mapping, once the frame has retired.
+    pub(crate) fn host_read() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::HOST,
+            access: vk::AccessFlags2::HOST_READ,
+            layout: vk::ImageLayout::UNDEFINED,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Sampled in a fragment shader.
+    pub(crate) fn sampled_fragment() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Sampled in a compute shader.
+    pub(crate) fn sampled_compute() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            ..Self::sampled_fragment()
+        }
+    }
+
+    /// Image written as a storage image by a compute shader, in GENERAL.
+    pub(crate) fn storage_image_write() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            layout: vk::ImageLayout::GENERAL,
+            write: true,
+            attachment: None,
+        }
+    }
+
+    /// Buffer written by compute shaders and/or transfers (copies, fills),
+    /// and read by them: the commands inside one pass order themselves.
+    pub(crate) fn compute_write() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COMPUTE_SHADER | vk::PipelineStageFlags2::TRANSFER,
+            access: vk::AccessFlags2::SHADER_READ
+                | vk::AccessFlags2::SHADER_WRITE
+                | vk::AccessFlags2::TRANSFER_READ
+                | vk::AccessFlags2::TRANSFER_WRITE,
+            layout: vk::ImageLayout::UNDEFINED,
+            write: true,
+            attachment: None,
+        }
+    }
+
+    /// Buffer read by any part of a draw: vertex/index input, indirect
+    /// arguments, vertex/fragment shader storage reads.
+    pub(crate) fn graphics_read() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::VERTEX_INPUT
+                | vk::PipelineStageFlags2::DRAW_INDIRECT
+                | vk::PipelineStageFlags2::VERTEX_SHADER
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access: vk::AccessFlags2::VERTEX_ATTRIBUTE_READ
+                | vk::AccessFlags2::INDEX_READ
+                | vk::AccessFlags2::INDIRECT_COMMAND_READ
+                | vk::AccessFlags2::SHADER_READ,
+            layout: vk::ImageLayout::UNDEFINED,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Swapchain image handed to the presentation engine (final access
+    /// only; presentation itself is ordered by the render-finished
+    /// semaphore, hence no stage).
+    pub(crate) fn present() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::NONE,
+            access: vk::AccessFlags2::empty(),
+            layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            write: false,
+            attachment: None,
+        }
+    }
+
+    /// Swapchain image straight out of acquire: contents undefined, and
+    /// the acquire semaphore's wait (at COLOR_ATTACHMENT_OUTPUT) is what
+    /// the first transition has to chain onto.
+    pub(crate) fn acquired() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            access: vk::AccessFlags2::empty(),
+            layout: vk::ImageLayout::UNDEFINED,
+            write: false,
+            attachment: None,
+        }
+    }
+}
+
+enum Kind {
+    Image {
+        image: vk::Image,
+        view: vk::ImageView,
+        aspect: vk::ImageAspectFlags,
+    },
+    Buffer,
+}
+
+/// Where a resource stands between passes.
+#[derive(Clone, Copy)]
+struct State {
+    layout: vk::ImageLayout,
+    // Last write (stage + write access bits); NONE once nothing's pending.
+    write_stage: vk::PipelineStageFlags2,
+    write_access: vk::AccessFlags2,
+    // Stages that already waited on that write.
+    synced: vk::PipelineStageFlags2,
+    // Stages reading since the last write (WAR hazards for the next one).
+    reads: vk::PipelineStageFlags2,
+}
+
+struct Resource {
+    name: &'static str,
+    kind: Kind,
+    state: State,
+    exported: bool,
+    final_access: Option<Access>,
+}
+
+type RecordFn = Box<dyn FnOnce(&mut VkRenderer, vk::CommandBuffer) -> Result<()>>;
+
+struct Pass {
+    name: &'static str,
+    extent: vk::Extent2D,
+    // Multiview: the views the scope renders, one attachment layer each;
+    // 0 for an ordinary single-layer scope.
+    view_mask: u32,
+    uses: Vec<(ResourceId, Access)>,
+    record: RecordFn,
+}
+
+pub(crate) struct FrameGraph {
+    resources: Vec<Resource>,
+    passes: Vec<Pass>,
+    extent: vk::Extent2D,
+}
+
+impl FrameGraph {
+    /// `extent` is the render area of every pass with attachments, unless
+    /// the pass was declared with add_pass_at.
+    pub(crate) fn new(extent: vk::Extent2D) -> Self {
+        Self {
+            resources: Vec::new(),
+            passes: Vec::new(),
+            extent,
+        }
+    }
+
+    /// Bring an existing image into the graph. `prev` is how it was last
+    /// touched (by an earlier frame, or acquire), which the first barrier
+    /// has to order against.
+    pub(crate) fn import_image(
+        &mut self,
+        name: &'static str,
+        image: vk::Image,
+        view: vk::ImageView,
+        aspect: vk::ImageAspectFlags,
+        prev: Access,
+    ) -> ResourceId {
+        self.import(
+            name,
+            Kind::Image {
+                image,
+                view,
+                aspect,
+            },
+            prev,
+        )
+    }
+
+    /// Bring a buffer (or a group of buffers always used together) into
+    /// the graph. Buffers only drive execution/memory barriers, so there's
+    /// no handle to pass.
+    pub(crate) fn import_buffer(&mut self, name: &'static str, prev: Access) -> ResourceId {
+        self.import(name, Kind::Buffer, prev)
+    }
+
+    fn import(&mut self, name: &'static str, kind: Kind, prev: Access) -> ResourceId {
+        let state = if prev.write {
+            State {
+                layout: prev.layout,
+                write_stage: prev.stage,
+                write_access: prev.access & WRITE_ACCESS,
+                synced: vk::PipelineStageFlags2::NONE,
+                reads: vk::PipelineStageFlags2::NONE,
+            }
+        } else {
+            State {
+                layout: prev.layout,
+                write_stage: vk::PipelineStageFlags2::NONE,
+                write_access: vk::AccessFlags2::empty(),
+                synced: vk::PipelineStageFlags2::NONE,
+                reads: prev.stage,
+            }
+        };
+        self.resources.push(Resource {
+            name,
+            kind,
+            state,
+            exported: false,
+            final_access: None,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Mark a resource as consumed outside this frame's graph, so passes
+    /// writing it are never culled; `final_access` (if any) is transitioned
+    /// to after the last pass.
+    pub(crate) fn export(&mut self, id: ResourceId, final_access: Option<Access>) {
+        let r = &mut self.resources[id.0];
+        r.exported = true;
+        r.final_access = final_access;
+    }
+
+    /// Declare a pass. At most one colour and one depth attachment.
+    pub(crate) fn add_pass(
+        &mut self,
+        name: &'static str,
+        uses: &[(ResourceId, Access)],
+        record: impl FnOnce(&mut VkRenderer, vk::CommandBuffer) -> Result<()> + 'static,
+    ) {
+        self.add_pass_at(name, self.extent, uses, record);
+    }
+
+    /// add_pass with its own render area, e.g. a scene rendered below
+    /// output resolution. Attachments may be larger than `extent`.
+    pub(crate) fn add_pass_at(
+        &mut self,
+        name: &'static str,
+        extent: vk::Extent2D,
+        uses: &[(ResourceId, Access)],
+        record: impl FnOnce(&mut VkRenderer, vk::CommandBuffer) -> Result<()> + 'static,
+    ) {
+        self.add_multiview_pass(name, extent, 0, uses, record);
+    }
+
+    /// add_pass_at with a multiview rendering scope over `view_mask`'s
+    /// layers of its (array) attachments; pipelines drawn in it must have
+    /// been built with the same mask.
+    pub(crate) fn add_multiview_pass(
+        &mut self,
+        name: &'static str,
+        extent: vk::Extent2D,
+        view_mask: u32,
+        uses: &[(ResourceId, Access)],
+        record: impl FnOnce(&mut VkRenderer, vk::CommandBuffer) -> Result<()> + 'static,
+    ) {
+        debug_assert!(
+            [AttachmentSlot::Color, AttachmentSlot::Depth]
+                .iter()
+                .all(|&slot| uses
+                    .iter()
+                    .filter(|(_, a)| a.attachment.map(|t| t.slot) == Some(slot))
+                    .count()
+                    <= 1),
+            "pass {name}: more than one attachment in a slot"
+        );
+        self.passes.push(Pass {
+            name,
+            extent,
+            view_mask,
+            uses: uses.to_vec(),
+            record: Box::new(record),
+        });
+    }
+
+    /// Dependency edges (from -> to), in terms of declaration indices.
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        let mut deps = vec![Vec::new(); self.passes.len()];
+        let mut last_write: Vec<Option<usize>> = vec![None; self.resources.len()];
+        let mut reads_since: Vec<Vec<usize>> = vec![Vec::new(); self.resources.len()];
+        for (i, pass) in self.passes.iter().enumerate() {
+            for &(id, access) in &pass.uses {
+                if let Some(w) = last_write[id.0] {
+                    if w != i {
+                        deps[i].push(w);
+                    }
+                }
+                if access.write {
+                    deps[i].extend(reads_since[id.0].drain(..).filter(|&r| r != i));
+                }
+            }
+            for &(id, access) in &pass.uses {
+                if access.write {
+                    last_write[id.0] = Some(i);
+                } else {
+                    reads_since[id.0].push(i);
+                }
+            }
+        }
+        deps
+    }
+
+    /// Declaration indices of the passes to run, in execution order.
+    fn schedule(&self) -> Vec<usize> {
+        let deps = self.dependencies();
+        let n = self.passes.len();
+
+        // Cull: live = writes an exported resource, or a live pass needs it.
+        let mut live = vec![false; n];
+        let mut stack: Vec<usize> = (0..n)
+            .filter(|&i| {
+                self.passes[i]
+                    .uses
+                    .iter()
+                    .any(|(id, a)| a.write && self.resources[id.0].exported)
+            })
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !std::mem::replace(&mut live[i], true) {
+                stack.extend(deps[i].iter().copied());
+            }
+        }
+
+        // Kahn over the live passes; the min-heap makes declaration order
+        // the tiebreak.
+        let mut pending = vec![0usize; n];
+        let mut dependents = vec![Vec::new(); n];
+        for i in (0..n).filter(|&i| live[i]) {
+            for &d in &deps[i] {
+                pending[i] += 1;
+                dependents[d].push(i);
+            }
+        }
+        let mut ready: BinaryHeap<Reverse<usize>> = (0..n)
+            .filter(|&i| live[i] && pending[i] == 0)
+            .map(Reverse)
+            .collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(Reverse(i)) = ready.pop() {
+            order.push(i);
+            for &j in &dependents[i] {
+                pending[j] -= 1;
+                if pending[j] == 0 {
+                    ready.push(Reverse(j));
+                }
+            }
+        }
+        order
+    }
+
+    /// Record every live pass into `cmd` (already begun), with barriers
+    /// and rendering scopes, then the exported images' final transitions.
+    pub(crate) fn execute(mut self, r: &mut VkRenderer, cmd: vk::CommandBuffer) -> Result<()> {
+        let order = self.schedule();
+        let mut passes: Vec<Option<Pass>> = self.passes.drain(..).map(Some).collect();
+        for i in order {
+            let pass = passes[i].take().expect("pass scheduled twice");
+            let name = pass.name;
+            // Label spans the pass's barriers too, so captures show what
+            // each transition was for.
+            r.debug_labels.begin(cmd, name);
+            r.crash_diag.checkpoint(cmd, name);
+            self.barriers(r, cmd, &pass.uses);
+            let rendering = self.begin_rendering(r, cmd, &pass);
+            (pass.record)(r, cmd).with_context(|| format!("frame graph pass {name:?}"))?;
+            if rendering {
+                unsafe { r.device.cmd_end_rendering(cmd) };
+            }
+            r.debug_labels.end(cmd);
+        }
+        for pass in passes.into_iter().flatten() {
+            trace!("frame graph: culled pass {:?}", pass.name);
+        }
+        let finals: Vec<_> = (0..self.resources.len())
+            .filter_map(|i| self.resources[i].final_access.map(|a| (ResourceId(i), a)))
+            .collect();
+        self.barriers(r, cmd, &finals);
+        Ok(())
+    }
+
+    /// Emit (at most) one barrier batch bringing every resource in `uses`
+    /// from its current state to the access it's about to get.
+    fn barriers(&mut self, r: &VkRenderer, cmd: vk::CommandBuffer, uses: &[(ResourceId, Access)]) {
+        let mut image_barriers = Vec::new();
+        let mut memory = vk::MemoryBarrier2 {
+            s_type: vk::StructureType::MEMORY_BARRIER_2,
+            ..Default::default()
+        };
+        for &(id, access) in uses {
+            let res = &mut self.resources[id.0];
+            let s = res.state;
+            let is_image = matches!(res.kind, Kind::Image { .. });
+            let layout_change = is_image && access.layout != s.layout;
+            let raw = !s.write_stage.is_empty() && !s.synced.contains(access.stage);
+            let war = access.write && !s.reads.is_empty();
+            if layout_change || raw || war {
+                trace!(
+                    "frame graph: barrier on {} ({:?} -> {:?})",
+                    res.name,
+                    s.layout,
+                    access.layout
+                );
+                let src_stage = s.write_stage
+                    | if access.write || layout_change {
+                        s.reads
+                    } else {
+                        vk::PipelineStageFlags2::NONE
+                    };
+                // Anything but Load on an attachment discards, so the old
+                // layout can be UNDEFINED (no transition of stale data).
+                let discard = matches!(
+                    access.attachment,
+                    Some(Attachment {
+                        load: LoadOp::Clear(_) | LoadOp::DontCare,
+                        ..
+                    })
+                );
+                match res.kind {
+                    Kind::Image { image, aspect, .. } => {
+                        image_barriers.push(vk::ImageMemoryBarrier2 {
+                            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER_2,
+                            src_stage_mask: src_stage,
+                            src_access_mask: s.write_access,
+                            dst_stage_mask: access.stage,
+                            dst_access_mask: access.access,
+                            old_layout: if discard {
+                                vk::ImageLayout::UNDEFINED
+                            } else {
+                                s.layout
+                            },
+                            new_layout: access.layout,
+                            image,
+                            subresource_range: vk::ImageSubresourceRange {
+                                aspect_mask: aspect,
+                                base_mip_level: 0,
+                                level_count: vk::REMAINING_MIP_LEVELS,
+                                base_array_layer: 0,
+                                layer_count: vk::REMAINING_ARRAY_LAYERS,
+                            },
+                            ..Default::default()
+                        })
+                    }
+                    Kind::Buffer => {
+                        memory.src_stage_mask |= src_stage;
+                        memory.src_access_mask |= s.write_access;
+                        memory.dst_stage_mask |= access.stage;
+                        memory.dst_access_mask |= access.access;
+                    }
+                }
+            }
+            res.state = if access.write {
+                State {
+                    layout: access.layout,
+                    write_stage: access.stage,
+                    write_access: access.access & WRITE_ACCESS,
+                    synced: vk::PipelineStageFlags2::NONE,
+                    reads: vk::PipelineStageFlags2::NONE,
+                }
+            } else if layout_change {
+                // A layout transition is a write of its own: later readers
+                // in other stages must chain onto this one.
+                State {
+                    layout: access.layout,
+                    write_stage: access.stage,
+                    write_access: vk::AccessFlags2::empty(),
+                    synced: access.stage,
+                    reads: access.stage,
+                }
+            } else {
+                State {
+                    synced: if raw {
+                        s.synced | access.stage
+                    } else {
+                        s.synced
+                    },
+                    reads: s.reads | access.stage,
+                    ..s
+                }
+            };
+        }
+        let has_memory = !memory.src_stage_mask.is_empty() || !memory.dst_stage_mask.is_empty();
+        if image_barriers.is_empty() && !has_memory {
+            return;
+        }
+        let dep = vk::DependencyInfo {
+            s_type: vk::StructureType::DEPENDENCY_INFO,
+            memory_barrier_count: has_memory as u32,
+            p_memory_barriers: &memory,
+            image_memory_barrier_count: image_barriers.len() as u32,
+            p_image_memory_barriers: image_barriers.as_ptr(),
+            ..Default::default()
+        };
+        unsafe { r.device.cmd_pipeline_barrier2(cmd, &dep) };
+    }
+
+    /// Open a dynamic-rendering scope over the pass's attachments, if it
+    /// has any. True if one was opened.
+    fn begin_rendering(&self, r: &VkRenderer, cmd: vk::CommandBuffer, pass: &Pass) -> bool {
+        let info = |slot| {
+            pass.uses.iter().find_map(|&(id, a)| {
+                let t = a.attachment.filter(|t| t.slot == slot)?;
+                let Kind::Image { view, .. } = self.resources[id.0].kind else {
+                    return None;
+                };
+                let (load_op, clear_value) = match t.load {
+                    LoadOp::Clear(v) => (vk::AttachmentLoadOp::CLEAR, v),
+                    LoadOp::Load => (vk::AttachmentLoadOp::LOAD, vk::ClearValue::default()),
+                    LoadOp::DontCare => {
+                        (vk::AttachmentLoadOp::DONT_CARE, vk::ClearValue::default())
+                    }
+                };
+                Some(vk::RenderingAttachmentInfo {
+                    s_type: vk::StructureType::RENDERING_ATTACHMENT_INFO,
+                    image_view: view,
+                    image_layout: a.layout,
+                    load_op,
+                    store_op: if t.store {
+                        vk::AttachmentStoreOp::STORE
+                    } else {
+                        vk::AttachmentStoreOp::DONT_CARE
+                    },
+                    clear_value,
+                    ..Default::default()
+                })
+            })
+        };
+        let color = info(AttachmentSlot::Color);
+        let depth = info(AttachmentSlot::Depth);
+        if color.is_none() && depth.is_none() {
+            return false;
+        }
+        let rendering_info = vk::RenderingInfo {
+            s_type: vk::StructureType::RENDERING_INFO,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: pass.extent,
+            },
+            // Ignored with a view mask.
+            layer_count: 1,
+            view_mask: pass.view_mask,
+            color_attachment_count: color.is_some() as u32,
+            p_color_attachments: color.as_ref().map_or(std::ptr::null(), |c| c as *const _),
+            p_depth_attachment: depth.as_ref().map_or(std::ptr::null(), |d| d as *const _),
+            ..Default::default()
+        };
+        unsafe { r.device.cmd_begin_rendering(cmd, &rendering_info) };
+        true
+    }
+}
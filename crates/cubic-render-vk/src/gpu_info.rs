@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! What the machine offers the renderer, without building one: every GPU
+//! the Vulkan loader lists, whether it meets the minimum requirements,
+//! what it can present to a given window, and which of the optional
+//! extensions the renderer makes use of it has. For `cubic info`.
+
+use std::ffi::CStr;
+
+use anyhow::{Context, Result};
+use ash::khr::surface;
+use ash::{vk, Entry};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+
+use crate::device::{missing_requirements, select_device_and_queue};
+use crate::instance::create_instance;
+use crate::validation::ValidationLayers;
+
+/// Device extensions the renderer turns on when present; none required.
+const OPTIONAL_EXTENSIONS: &[&CStr] = &[
+    ash::khr::synchronization2::NAME,
+    ash::khr::dynamic_rendering::NAME,
+    ash::khr::present_id::NAME,
+    ash::khr::present_wait::NAME,
+    ash::ext::hdr_metadata::NAME,
+    ash::ext::full_screen_exclusive::NAME,
+    ash::ext::swapchain_maintenance1::NAME,
+    ash::ext::memory_budget::NAME,
+    ash::ext::device_fault::NAME,
+    ash::nv::device_diagnostic_checkpoints::NAME,
+];
+
+/// One GPU as describe_gpus found it.
+#[derive(Clone, Debug)]
+pub struct GpuInfo {
+    /// Enumeration order, as `--gpu` and CUBIC_GPU take it.
+    pub index: usize,
+    pub name: String,
+    /// "DISCRETE_GPU", "INTEGRATED_GPU", ...
+    pub device_type: String,
+    /// Major, minor, patch.
+    pub api_version: (u32, u32, u32),
+    /// What it lacks of the minimum requirements; empty if it can run the
+    /// renderer.
+    pub missing: Vec<&'static str>,
+    /// Has a queue family that can present to the window.
+    pub can_present: bool,
+    /// The device the renderer would pick for this window.
+    pub selected: bool,
+    /// "FORMAT / COLOR_SPACE" pairs the surface offers; empty if it can't
+    /// present.
+    pub surface_formats: Vec<String>,
+    /// "FIFO", "MAILBOX", ...
+    pub present_modes: Vec<String>,
+    /// Each optional device extension and whether it has it.
+    pub extensions: Vec<(&'static str, bool)>,
+}
+
+/// Describe every GPU and what it can present to `window`. `gpu` is a
+/// request as `VkRenderer::new_with_gpu` takes it, for `selected`.
+pub fn describe_gpus(
+    window: &dyn HasWindowHandle,
+    display: &dyn HasDisplayHandle,
+    gpu: Option<&str>,
+) -> Result<Vec<GpuInfo>> {
+    let dh = display
+        .display_handle()
+        .map_err(|e| anyhow::anyhow!("{e}"))?
+        .as_raw();
+    let wh = window
+        .window_handle()
+        .map_err(|e| anyhow::anyhow!("{e}"))?
+        .as_raw();
+
+    let entry = Entry::linked();
+    let (instance, _) = create_instance(&entry, dh, ValidationLayers::OFF)?;
+    let surf_i = surface::Instance::new(&entry, &instance);
+    let surface = match unsafe { ash_window::create_surface(&entry, &instance, dh, wh, None) } {
+        Ok(surface) => surface,
+        Err(e) => {
+            unsafe { instance.destroy_instance(None) };
+            return Err(e).context("ash_window::create_surface");
+        }
+    };
+
+    let gpus = describe(&instance, &surf_i, surface, gpu);
+
+    unsafe {
+        surf_i.destroy_surface(surface, None);
+        instance.destroy_instance(None);
+    }
+    gpus
+}
+
+fn describe(
+    instance: &ash::Instance,
+    surf_i: &surface::Instance,
+    surface: vk::SurfaceKHR,
+    gpu: Option<&str>,
+) -> Result<Vec<GpuInfo>> {
+    let phys_devs = unsafe { instance.enumerate_physical_devices()? };
+    // Err when nothing's usable; every entry then says why.
+    let selected = select_device_and_queue(instance, surf_i, surface, gpu)
+        .ok()
+        .map(|(phys, _)| phys);
+
+    let mut gpus = Vec::with_capacity(phys_devs.len());
+    for (index, &phys) in phys_devs.iter().enumerate() {
+        let props = unsafe { instance.get_physical_device_properties(phys) };
+        let name = props
+            .device_name_as_c_str()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "<unnamed>".to_owned());
+        let qfamilies =
+            unsafe { instance.get_physical_device_queue_family_properties(phys) }.len() as u32;
+        let can_present = (0..qfamilies).any(|i| {
+            unsafe { surf_i.get_physical_device_surface_support(phys, i, surface) }.unwrap_or(false)
+        });
+        let (surface_formats, present_modes) = if can_present {
+            let formats = unsafe { surf_i.get_physical_device_surface_formats(phys, surface) }
+                .unwrap_or_default();
+            let modes = unsafe { surf_i.get_physical_device_surface_present_modes(phys, surface) }
+                .unwrap_or_default();
+            (
+                formats
+                    .iter()
+                    .map(|f| format!("{:?} / {:?}", f.format, f.color_space))
+                    .collect(),
+                modes.iter().map(|m| format!("{m:?}")).collect(),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let exts =
+            unsafe { instance.enumerate_device_extension_properties(phys) }.unwrap_or_default();
+        let extensions = OPTIONAL_EXTENSIONS
+            .iter()
+            .map(|&want| {
+                let has = exts
+                    .iter()
+                    .any(|e| unsafe { CStr::from_ptr(e.extension_name.as_ptr()) } == want);
+                (want.to_str().unwrap_or("?"), has)
+            })
+            .collect();
+        gpus.push(GpuInfo {
+            index,
+            name,
+            device_type: format!("{:?}", props.device_type),
+            api_version: (
+                vk::api_version_major(props.api_version),
+                vk::api_version_minor(props.api_version),
+                vk::api_version_patch(props.api_version),
+            ),
+            missing: missing_requirements(instance, phys),
+            can_present,
+            selected: selected == Some(phys),
+            surface_formats,
+            present_modes,
+            extensions,
+        });
+    }
+    Ok(gpus)
+}
@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! HDR10 mastering metadata (VK_EXT_hdr_metadata): what we tell the
+//! compositor/display about the content's colour volume, so its own tone
+//! mapping doesn't crush or clip what we send.
+//!
+//! Sources, in priority order: an explicit `set_hdr_metadata` call (cubic-app
+//! feeds its `[render.hdr_display]` calibration through that), then the
+//! connected display's EDID (Linux DRM sysfs; works the same under Wayland
+//! and X11), then the generic 1000-nit BT.2020 fallback.
+
+use ash::vk;
+use std::path::Path;
+
+/// Display colour volume in CIE 1931 xy + nits. Mirrors VkHdrMetadataEXT
+/// without the ash types, so cubic-app can build one from its config.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HdrMetadata {
+    pub red: [f32; 2],
+    pub green: [f32; 2],
+    pub blue: [f32; 2],
+    pub white_point: [f32; 2],
+    pub max_luminance: f32,
+    pub min_luminance: f32,
+    pub max_content_light_level: f32,
+    pub max_frame_average_light_level: f32,
+}
+
+impl Default for HdrMetadata {
+    /// BT.2020 primaries, D65 white, a typical 1000-nit HDR10 display.
+    fn default() -> Self {
+        Self {
+            red: [0.708, 0.292],
+            green: [0.170, 0.797],
+            blue: [0.131, 0.046],
+            white_point: [0.3127, 0.3290],
+            max_luminance: 1000.0,
+            min_luminance: 0.001,
+            max_content_light_level: 1000.0,
+            max_frame_average_light_level: 400.0,
+        }
+    }
+}
+
+impl HdrMetadata {
+    pub(crate) fn to_vk(self) -> vk::HdrMetadataEXT<'static> {
+        let xy = |c: [f32; 2]| vk::XYColorEXT { x: c[0], y: c[1] };
+        vk::HdrMetadataEXT {
+            s_type: vk::StructureType::HDR_METADATA_EXT,
+            display_primary_red: xy(self.red),
+            display_primary_green: xy(self.green),
+            display_primary_blue: xy(self.blue),
+            white_point: xy(self.white_point),
+            max_luminance: self.max_luminance,
+            min_luminance: self.min_luminance,
+            max_content_light_level: self.max_content_light_level,
+            max_frame_average_light_level: self.max_frame_average_light_level,
+            ..Default::default()
+        }
+    }
+
+    /// Parse an EDID blob: primaries/white point from the base block, and
+    /// luminance from the CTA-861 HDR Static Metadata data block. None if
+    /// the blob is malformed or the display doesn't advertise ST 2084 (PQ)
+    /// support, i.e. isn't an HDR10 display. Luminance values the block
+    /// leaves out keep their defaults.
+    pub(crate) fn from_edid(edid: &[u8]) -> Option<Self> {
+        const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+        if edid.len() < 128 || edid[..8] != HEADER {
+            return None;
+        }
+
+        // Chromaticity: 10-bit values, high 8 bits in bytes 27..35, low 2
+        // bits packed into bytes 25 (red/green) and 26 (blue/white).
+        let coord = |hi: usize, lo_byte: usize, shift: u32| -> f32 {
+            let lo = (edid[lo_byte] >> shift) & 0x3;
+            (((edid[hi] as u32) << 2) | lo as u32) as f32 / 1024.0
+        };
+        let mut md = Self {
+            red: [coord(27, 25, 6), coord(28, 25, 4)],
+            green: [coord(29, 25, 2), coord(30, 25, 0)],
+            blue: [coord(31, 26, 6), coord(32, 26, 4)],
+            white_point: [coord(33, 26, 2), coord(34, 26, 0)],
+            ..Self::default()
+        };
+
+        let static_block = edid[128..]
+            .chunks_exact(128)
+            .take(edid[126] as usize)
+            .filter(|ext| ext[0] == 0x02) // CTA-861 extension
+            .find_map(cta_hdr_static_metadata)?;
+        const EOTF_ST2084: u8 = 1 << 2;
+        if static_block.first().copied().unwrap_or(0) & EOTF_ST2084 == 0 {
+            return None;
+        }
+        // Payload: EOTFs, descriptor types, then optional max / max
+        // frame-average / min luminance code values (CTA-861-G 7.5.13).
+        let cv_nits = |cv: u8| 50.0 * 2f32.powf(cv as f32 / 32.0);
+        if let Some(&cv) = static_block.get(2) {
+            md.max_luminance = cv_nits(cv);
+            md.max_content_light_level = md.max_luminance;
+        }
+        if let Some(&cv) = static_block.get(3) {
+            md.max_frame_average_light_level = cv_nits(cv);
+        }
+        if let Some(&cv) = static_block.get(4) {
+            md.min_luminance = md.max_luminance * (cv as f32 / 255.0).powi(2) / 100.0;
+        }
+        Some(md)
+    }
+}
+
+/// The HDR Static Metadata data block's payload (after the extended tag)
+/// in one CTA-861 extension block, if present.
+fn cta_hdr_static_metadata(ext: &[u8]) -> Option<&[u8]> {
+    const TAG_EXTENDED: u8 = 7;
+    const EXT_TAG_HDR_STATIC: u8 = 6;
+    // Data blocks run from byte 4 up to the detailed-timing offset in byte 2.
+    let end = (ext[2] as usize).min(ext.len());
+    let mut i = 4;
+    while i < end {
+        let tag = ext[i] >> 5;
+        let len = (ext[i] & 0x1F) as usize;
+        let payload = ext.get(i + 1..i + 1 + len)?;
+        if tag == TAG_EXTENDED && payload.first() == Some(&EXT_TAG_HDR_STATIC) {
+            return Some(&payload[1..]);
+        }
+        i += 1 + len;
+    }
+    None
+}
+
+/// Metadata of the first connected HDR-capable display found under
+/// /sys/class/drm. There's no reliable way to map a DRM connector back to
+/// the monitor our window is on from here, so with several HDR displays
+/// attached this is a best guess (logged); `[render.hdr_display]` overrides
+/// it. None off Linux, or when no connected display advertises HDR10.
+pub(crate) fn detect_display_hdr_metadata() -> Option<HdrMetadata> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else {
+        return None;
+    };
+    let mut found: Vec<(String, HdrMetadata)> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let dir = e.path();
+            let name = e.file_name().to_string_lossy().into_owned();
+            connector_hdr_metadata(&dir).map(|md| (name, md))
+        })
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    let (name, md) = found.first()?;
+    if found.len() > 1 {
+        tracing::info!(
+            "{} HDR displays connected; using {}'s EDID for HDR metadata",
+            found.len(),
+            name
+        );
+    } else {
+        tracing::info!("HDR metadata from {} EDID: {:?}", name, md);
+    }
+    Some(*md)
+}
+
+fn connector_hdr_metadata(dir: &Path) -> Option<HdrMetadata> {
+    let status = std::fs::read_to_string(dir.join("status")).ok()?;
+    if status.trim() != "connected" {
+        return None;
+    }
+    let edid = std::fs::read(dir.join("edid")).ok()?;
+    HdrMetadata::from_edid(&edid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DisplayHDR 400-class panel: roughly DCI-P3 primaries, D65 white.
+    const PRIMARIES: [[f32; 2]; 4] = [
+        [0.680, 0.320],
+        [0.265, 0.690],
+        [0.150, 0.060],
+        [0.3127, 0.3291],
+    ];
+
+    /// An EDID base block carrying PRIMARIES, followed by one CTA-861
+    /// extension per entry of `cta_blocks` (each that extension's raw data
+    /// blocks). Checksums are left at zero; from_edid doesn't read them.
+    fn edid(cta_blocks: &[&[u8]]) -> Vec<u8> {
+        let mut out = vec![0u8; 128];
+        out[..8].copy_from_slice(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        for (i, &v) in PRIMARIES.as_flattened().iter().enumerate() {
+            let v = (v * 1024.0).round() as u16;
+            out[27 + i] = (v >> 2) as u8;
+            out[25 + i / 4] |= ((v & 0x3) as u8) << (6 - 2 * (i % 4));
+        }
+        out[126] = cta_blocks.len() as u8;
+        for blocks in cta_blocks {
+            let mut ext = vec![0u8; 128];
+            ext[0] = 0x02;
+            ext[1] = 3;
+            ext[2] = 4 + blocks.len() as u8;
+            ext[4..4 + blocks.len()].copy_from_slice(blocks);
+            out.extend_from_slice(&ext);
+        }
+        out
+    }
+
+    /// A video data block (two VICs), then an HDR static metadata block
+    /// with `payload` after its extended tag.
+    fn cta_with_static(payload: &[u8]) -> Vec<u8> {
+        let mut blocks = vec![0x2 << 5 | 2, 16, 4];
+        blocks.push(0x7 << 5 | (1 + payload.len() as u8));
+        blocks.push(6);
+        blocks.extend_from_slice(payload);
+        blocks
+    }
+
+    fn assert_near(got: f32, want: f32, tol: f32) {
+        assert!((got - want).abs() <= tol, "got {got}, want {want}");
+    }
+
+    #[test]
+    fn hdr10_edid_gives_primaries_and_luminance() {
+        // EOTFs: SDR + ST 2084; static metadata type 1; max code value 96
+        // (400 nits), frame average 80 (~283 nits), min 32.
+        let cta = cta_with_static(&[0b101, 0x01, 96, 80, 32]);
+        let md = HdrMetadata::from_edid(&edid(&[&cta])).expect("HDR10 display");
+
+        let got = [md.red, md.green, md.blue, md.white_point];
+        for (got, want) in got.iter().zip(PRIMARIES) {
+            assert_near(got[0], want[0], 1.0 / 1024.0);
+            assert_near(got[1], want[1], 1.0 / 1024.0);
+        }
+        assert_near(md.max_luminance, 400.0, 1e-3);
+        assert_near(md.max_content_light_level, 400.0, 1e-3);
+        assert_near(md.max_frame_average_light_level, 282.84, 1e-2);
+        // 400 × (32/255)² / 100
+        assert_near(md.min_luminance, 0.062_99, 1e-4);
+    }
+
+    #[test]
+    fn luminance_the_block_omits_keeps_its_default() {
+        let cta = cta_with_static(&[0b100, 0x01]);
+        let md = HdrMetadata::from_edid(&edid(&[&cta])).expect("HDR10 display");
+        let default = HdrMetadata::default();
+        assert_eq!(md.max_luminance, default.max_luminance);
+        assert_eq!(md.min_luminance, default.min_luminance);
+        assert_eq!(
+            md.max_frame_average_light_level,
+            default.max_frame_average_light_level
+        );
+    }
+
+    #[test]
+    fn static_block_is_found_in_a_later_extension() {
+        let sdr_only = [0x2 << 5 | 1, 16];
+        let cta = cta_with_static(&[0b100, 0x01, 96]);
+        let md = HdrMetadata::from_edid(&edid(&[&sdr_only, &cta])).expect("HDR10 display");
+        assert_near(md.max_luminance, 400.0, 1e-3);
+    }
+
+    #[test]
+    fn non_hdr_edids_give_none() {
+        // No extension at all: a plain SDR monitor.
+        assert_eq!(HdrMetadata::from_edid(&edid(&[])), None);
+        // A CTA extension without an HDR static metadata block.
+        assert_eq!(HdrMetadata::from_edid(&edid(&[&[0x2 << 5 | 1, 16]])), None);
+        // A static metadata block advertising only SDR and HLG, no PQ.
+        let hlg = cta_with_static(&[0b1001, 0x01, 96, 80, 32]);
+        assert_eq!(HdrMetadata::from_edid(&edid(&[&hlg])), None);
+    }
+
+    #[test]
+    fn malformed_edids_give_none() {
+        let cta = cta_with_static(&[0b100, 0x01, 96, 80, 32]);
+        let good = edid(&[&cta]);
+        assert_eq!(HdrMetadata::from_edid(&good[..100]), None);
+        let mut bad_header = good;
+        bad_header[0] = 0xFF;
+        assert_eq!(HdrMetadata::from_edid(&bad_header), None);
+        // Full-length data blocks whose last one runs past the extension.
+        let mut overrun = vec![0u8; 120];
+        for i in [0, 32, 64, 96] {
+            overrun[i] = 0x2 << 5 | 31;
+        }
+        assert_eq!(HdrMetadata::from_edid(&edid(&[&overrun])), None);
+    }
+}
@@ -0,0 +1,570 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Image-based lighting: every environment cubemap gets an irradiance map
+//! (the diffuse light arriving from each direction, cosine-convolved) and
+//! a prefiltered specular map (GGX-convolved, one roughness per mip), and
+//! the renderer one split-sum BRDF lookup table (Karis 2013): what a PBR
+//! shader needs to light a surface with its surroundings.
+//!
+//! All three are compute passes (ibl_irradiance.comp, ibl_prefilter.comp,
+//! ibl_brdf.comp) run once, on the graphics queue with the CPU waiting:
+//! the LUT at startup, a cubemap's maps right after upload_cubemap uploads
+//! it. Both convolutions read the source's one level with fixed sample
+//! counts, which is plenty for skies and smooth panoramas; very sharp,
+//! bright sources can speckle the rougher mips.
+//!
+//! The environment set_environment picks has its maps bound to the camera
+//! set (binding 4 irradiance, 5 prefiltered, 6 the LUT) and the CameraUbo's
+//! ibl_params says so; tri.frag then takes its ambient light from the
+//! irradiance map rather than a flat colour. The prefiltered map's level
+//! is roughness * ibl_params.y; the LUT is indexed by (N·V, roughness) and
+//! holds the scale and bias to F0 in r and g. Without the shaders built,
+//! cubemaps get no maps and ambient light stays flat.
+
+use anyhow::Result;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::pipeline::{create_compute_pipeline, load_spv_file, shader_dir};
+use crate::resources::{
+    create_storage_image, submit_one_time, transition_image_layout2, LayoutTransition,
+};
+use crate::VkRenderer;
+
+/// Format of every IBL map: HDR, and a storage format every device has.
+const IBL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Texels on a side of an irradiance map's faces; irradiance has no
+/// detail to speak of.
+const IRRADIANCE_SIZE: u32 = 32;
+
+/// Texels on a side of the prefiltered map's sharpest level.
+const PREFILTER_SIZE: u32 = 128;
+
+/// Prefiltered levels: roughness 0 at level 0 up to 1 at the last (8x8).
+pub(crate) const PREFILTER_MIPS: u32 = 5;
+
+const BRDF_LUT_SIZE: u32 = 256;
+
+/// Push constants of the IBL shaders; layout must match their `Push`
+/// block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct IblPush {
+    /// Texels on a side of the level being written.
+    size: u32,
+    /// GGX roughness of the level (ibl_prefilter.comp only).
+    roughness: f32,
+}
+
+fn subresource(levels: u32, layers: u32) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: levels,
+        base_array_layer: 0,
+        layer_count: layers,
+    }
+}
+
+/// One generated map, and the view the scene samples it through: a cube
+/// over every level, or 2D for the LUT.
+pub(crate) struct IblImage {
+    image: vk::Image,
+    alloc: Allocation,
+    pub(crate) view: vk::ImageView,
+    size: u32,
+    levels: u32,
+    cube: bool,
+}
+
+impl IblImage {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        size: u32,
+        levels: u32,
+        cube: bool,
+        name: &str,
+    ) -> Result<Self> {
+        let (image, alloc) =
+            create_storage_image(device, allocator, size, levels, IBL_FORMAT, cube, name)?;
+        let ci = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            image,
+            view_type: if cube {
+                vk::ImageViewType::CUBE
+            } else {
+                vk::ImageViewType::TYPE_2D
+            },
+            format: IBL_FORMAT,
+            subresource_range: subresource(levels, if cube { 6 } else { 1 }),
+            ..Default::default()
+        };
+        match unsafe { device.create_image_view(&ci, None) } {
+            Ok(view) => Ok(Self {
+                image,
+                alloc,
+                view,
+                size,
+                levels,
+                cube,
+            }),
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                let _ = allocator.free(alloc);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn layers(&self) -> u32 {
+        if self.cube {
+            6
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(self.alloc);
+    }
+}
+
+/// An environment cubemap's maps (see module docs).
+pub(crate) struct IblMaps {
+    pub(crate) irradiance: IblImage,
+    pub(crate) prefiltered: IblImage,
+}
+
+impl IblMaps {
+    pub(crate) fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        self.irradiance.destroy(device, allocator);
+        self.prefiltered.destroy(device, allocator);
+    }
+}
+
+/// The IBL pipelines, the sampler the scene reads every map through, and
+/// the BRDF LUT.
+pub(crate) struct IblPass {
+    // Binding 0 the source cubemap, 1 the level being written.
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    irradiance_pipeline: vk::Pipeline,
+    prefilter_pipeline: vk::Pipeline,
+    brdf_pipeline: vk::Pipeline,
+    /// Trilinear and clamped; also reads the sources.
+    pub(crate) sampler: vk::Sampler,
+    pub(crate) brdf_lut: Option<IblImage>,
+}
+
+impl IblPass {
+    pub(crate) fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        cache: vk::PipelineCache,
+        queue: vk::Queue,
+        cmd_pool: vk::CommandPool,
+    ) -> Result<Self> {
+        // Shaders first: missing .spv files are the usual failure, and
+        // nothing needs cleaning up yet.
+        let irradiance_words = load_spv_file(&shader_dir().join("ibl_irradiance.comp.spv"))?;
+        let prefilter_words = load_spv_file(&shader_dir().join("ibl_prefilter.comp.spv"))?;
+        let brdf_words = load_spv_file(&shader_dir().join("ibl_brdf.comp.spv"))?;
+
+        let mut pass = Self {
+            set_layout: vk::DescriptorSetLayout::null(),
+            layout: vk::PipelineLayout::null(),
+            irradiance_pipeline: vk::Pipeline::null(),
+            prefilter_pipeline: vk::Pipeline::null(),
+            brdf_pipeline: vk::Pipeline::null(),
+            sampler: vk::Sampler::null(),
+            brdf_lut: None,
+        };
+        let result = (|| -> Result<()> {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding {
+                    binding: 0,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: 1,
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    ..Default::default()
+                },
+                vk::DescriptorSetLayoutBinding {
+                    binding: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    descriptor_count: 1,
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    ..Default::default()
+                },
+            ];
+            let set_layout_ci = vk::DescriptorSetLayoutCreateInfo {
+                s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+                binding_count: bindings.len() as u32,
+                p_bindings: bindings.as_ptr(),
+                ..Default::default()
+            };
+            pass.set_layout = unsafe { device.create_descriptor_set_layout(&set_layout_ci, None)? };
+            let push_range = vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<IblPush>() as u32,
+            };
+            let layout_info = vk::PipelineLayoutCreateInfo {
+                s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+                set_layout_count: 1,
+                p_set_layouts: &pass.set_layout,
+                push_constant_range_count: 1,
+                p_push_constant_ranges: &push_range,
+                ..Default::default()
+            };
+            pass.layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+            pass.irradiance_pipeline =
+                create_compute_pipeline(device, cache, pass.layout, &irradiance_words)?;
+            pass.prefilter_pipeline =
+                create_compute_pipeline(device, cache, pass.layout, &prefilter_words)?;
+            pass.brdf_pipeline = create_compute_pipeline(device, cache, pass.layout, &brdf_words)?;
+
+            let sampler_ci = vk::SamplerCreateInfo {
+                s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+                mag_filter: vk::Filter::LINEAR,
+                min_filter: vk::Filter::LINEAR,
+                mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+                address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                max_lod: vk::LOD_CLAMP_NONE,
+                ..Default::default()
+            };
+            pass.sampler = unsafe { device.create_sampler(&sampler_ci, None)? };
+
+            let lut = pass.brdf_lut.insert(IblImage::new(
+                device,
+                allocator,
+                BRDF_LUT_SIZE,
+                1,
+                false,
+                "brdf lut",
+            )?);
+            let lut = (lut.image, BRDF_LUT_SIZE, 1, 1);
+            pass.generate(
+                device,
+                descriptors,
+                queue,
+                cmd_pool,
+                pass.brdf_pipeline,
+                None,
+                lut,
+            )
+        })();
+        match result {
+            Ok(()) => Ok(pass),
+            Err(e) => {
+                pass.destroy(device, allocator);
+                Err(e)
+            }
+        }
+    }
+
+    /// Fill every level of `target` (image, size, levels, layers) with
+    /// `pipeline`, reading `source`, and leave it ready to sample.
+    #[allow(clippy::too_many_arguments)]
+    fn generate(
+        &self,
+        device: &ash::Device,
+        descriptors: &mut DescriptorAllocator,
+        queue: vk::Queue,
+        cmd_pool: vk::CommandPool,
+        pipeline: vk::Pipeline,
+        source: Option<vk::ImageView>,
+        (image, size, levels, layers): (vk::Image, u32, u32, u32),
+    ) -> Result<()> {
+        let mut views: Vec<vk::ImageView> = Vec::new();
+        let mut sets: Vec<PooledSet> = Vec::new();
+        let result = (|| -> Result<()> {
+            // A view and a set per level, each writing just that level.
+            for level in 0..levels {
+                let ci = vk::ImageViewCreateInfo {
+                    s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+                    image,
+                    view_type: if layers == 6 {
+                        vk::ImageViewType::TYPE_2D_ARRAY
+                    } else {
+                        vk::ImageViewType::TYPE_2D
+                    },
+                    format: IBL_FORMAT,
+                    subresource_range: vk::ImageSubresourceRange {
+                        base_mip_level: level,
+                        level_count: 1,
+                        ..subresource(1, layers)
+                    },
+                    ..Default::default()
+                };
+                let view = unsafe { device.create_image_view(&ci, None)? };
+                views.push(view);
+                let set = descriptors.allocate(device, self.set_layout)?;
+                sets.push(set);
+
+                let source_info = source.map(|view| vk::DescriptorImageInfo {
+                    sampler: self.sampler,
+                    image_view: view,
+                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                });
+                let target_info = vk::DescriptorImageInfo {
+                    sampler: vk::Sampler::null(),
+                    image_view: view,
+                    image_layout: vk::ImageLayout::GENERAL,
+                };
+                let mut writes = vec![vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: set.set,
+                    dst_binding: 1,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &target_info,
+                    ..Default::default()
+                }];
+                if let Some(info) = source_info.as_ref() {
+                    writes.push(vk::WriteDescriptorSet {
+                        s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                        dst_set: set.set,
+                        dst_binding: 0,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        p_image_info: info,
+                        ..Default::default()
+                    });
+                }
+                unsafe { device.update_descriptor_sets(&writes, &[]) };
+            }
+
+            let sub = subresource(levels, layers);
+            submit_one_time(device, queue, cmd_pool, |cmd| {
+                transition_image_layout2(
+                    device,
+                    cmd,
+                    &LayoutTransition {
+                        image,
+                        sub,
+                        src_stage: vk::PipelineStageFlags2::TOP_OF_PIPE,
+                        src_access: vk::AccessFlags2::empty(),
+                        old_layout: vk::ImageLayout::UNDEFINED,
+                        dst_stage: vk::PipelineStageFlags2::COMPUTE_SHADER,
+                        dst_access: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                        new_layout: vk::ImageLayout::GENERAL,
+                    },
+                );
+                unsafe {
+                    device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, pipeline);
+                }
+                for (level, set) in sets.iter().enumerate() {
+                    let push = IblPush {
+                        size: (size >> level).max(1),
+                        roughness: if levels > 1 {
+                            level as f32 / (levels - 1) as f32
+                        } else {
+                            0.0
+                        },
+                    };
+                    unsafe {
+                        device.cmd_bind_descriptor_sets(
+                            cmd,
+                            vk::PipelineBindPoint::COMPUTE,
+                            self.layout,
+                            0,
+                            std::slice::from_ref(&set.set),
+                            &[],
+                        );
+                        device.cmd_push_constants(
+                            cmd,
+                            self.layout,
+                            vk::ShaderStageFlags::COMPUTE,
+                            0,
+                            bytemuck::bytes_of(&push),
+                        );
+                        // 8x8 workgroups, one layer (cube face) per z.
+                        let groups = push.size.div_ceil(8);
+                        device.cmd_dispatch(cmd, groups, groups, layers);
+                    }
+                }
+                transition_image_layout2(
+                    device,
+                    cmd,
+                    &LayoutTransition {
+                        image,
+                        sub,
+                        src_stage: vk::PipelineStageFlags2::COMPUTE_SHADER,
+                        src_access: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                        old_layout: vk::ImageLayout::GENERAL,
+                        dst_stage: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                        dst_access: vk::AccessFlags2::SHADER_SAMPLED_READ,
+                        new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    },
+                );
+            })
+        })();
+        // submit_one_time waited (or never submitted), so both can go.
+        for set in sets {
+            descriptors.free(device, set);
+        }
+        for view in views {
+            unsafe { device.destroy_image_view(view, None) };
+        }
+        result
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_pipeline(self.irradiance_pipeline, None);
+            device.destroy_pipeline(self.prefilter_pipeline, None);
+            device.destroy_pipeline(self.brdf_pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        if let Some(lut) = self.brdf_lut.take() {
+            lut.destroy(device, allocator);
+        }
+    }
+}
+
+impl VkRenderer {
+    /// Generate `source`'s irradiance and prefiltered maps (see module
+    /// docs); None without the IBL pass.
+    pub(crate) fn build_ibl_maps(&mut self, source: vk::ImageView) -> Result<Option<IblMaps>> {
+        let Some(ibl) = self.ibl.as_ref() else {
+            return Ok(None);
+        };
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        let irradiance = IblImage::new(
+            &self.device,
+            allocator,
+            IRRADIANCE_SIZE,
+            1,
+            true,
+            "irradiance map",
+        )?;
+        let prefiltered = match IblImage::new(
+            &self.device,
+            allocator,
+            PREFILTER_SIZE,
+            PREFILTER_MIPS,
+            true,
+            "prefiltered environment",
+        ) {
+            Ok(image) => image,
+            Err(e) => {
+                irradiance.destroy(&self.device, allocator);
+                return Err(e);
+            }
+        };
+        let maps = IblMaps {
+            irradiance,
+            prefiltered,
+        };
+        let descriptors = &mut self.descriptors.persistent;
+        let result = [
+            (ibl.irradiance_pipeline, &maps.irradiance),
+            (ibl.prefilter_pipeline, &maps.prefiltered),
+        ]
+        .into_iter()
+        .try_for_each(|(pipeline, target)| {
+            ibl.generate(
+                &self.device,
+                descriptors,
+                self.queue,
+                self.cmd_pool,
+                pipeline,
+                Some(source),
+                (target.image, target.size, target.levels, target.layers()),
+            )
+        });
+        match result {
+            Ok(()) => Ok(Some(maps)),
+            Err(e) => {
+                maps.destroy(&self.device, allocator);
+                Err(e)
+            }
+        }
+    }
+
+    /// Point bindings 4 to 6 of `sets` (as write_shadow_descriptors) at
+    /// the bound environment's maps and the LUT. Only call while the sets
+    /// aren't in use, as write_shadow_descriptors.
+    pub(crate) fn write_environment_descriptors(
+        &self,
+        sets: impl IntoIterator<Item = vk::DescriptorSet>,
+    ) {
+        let (Some(ibl), Some(env)) = (self.ibl.as_ref(), self.skybox.ibl_bound) else {
+            return;
+        };
+        let (Some(maps), Some(lut)) = (self.skybox.ibl_maps(env), ibl.brdf_lut.as_ref()) else {
+            return;
+        };
+        let infos = [
+            (4, maps.irradiance.view),
+            (5, maps.prefiltered.view),
+            (6, lut.view),
+        ]
+        .map(|(binding, image_view)| {
+            let info = vk::DescriptorImageInfo {
+                sampler: ibl.sampler,
+                image_view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            };
+            (binding, info)
+        });
+        let writes: Vec<vk::WriteDescriptorSet> = sets
+            .into_iter()
+            .flat_map(|set| {
+                infos
+                    .iter()
+                    .map(move |(binding, info)| vk::WriteDescriptorSet {
+                        s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                        dst_set: set,
+                        dst_binding: *binding,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        p_image_info: info,
+                        ..Default::default()
+                    })
+            })
+            .collect();
+        unsafe { self.device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    /// Bind the environment's maps to the camera sets, if it has them and
+    /// they aren't bound already. Frames in flight may have the sets
+    /// bound, so this goes through renew_camera_sets.
+    pub(crate) fn sync_environment_lighting(&mut self) {
+        let Some(env) = self.skybox.environment else {
+            return;
+        };
+        if self.skybox.ibl_bound == Some(env) || self.skybox.ibl_maps(env).is_none() {
+            return;
+        }
+        let bound = self.skybox.ibl_bound.replace(env);
+        if let Err(e) = self.renew_camera_sets() {
+            tracing::warn!("environment lighting not bound: {e:#}");
+            self.skybox.ibl_bound = bound;
+        }
+    }
+
+    /// CameraUbo's ibl_params: x = 1 with the environment's maps bound,
+    /// y = the prefiltered map's last level.
+    pub(crate) fn ibl_params(&self) -> [f32; 4] {
+        match self.skybox.environment {
+            Some(env) if self.skybox.ibl_bound == Some(env) => {
+                [1.0, (PREFILTER_MIPS - 1) as f32, 0.0, 0.0]
+            }
+            _ => [0.0; 4],
+        }
+    }
+}
@@ -1,46 +1,41 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
 use anyhow::{Context, Result};
-#[cfg(debug_assertions)]
 use ash::ext::debug_utils as ext_debug;
 use ash::khr::surface;
 use ash::{vk, Entry, Instance};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
-use std::ffi::c_char;
+use std::ffi::{c_char, CStr};
 
-#[cfg(debug_assertions)]
-pub(crate) type DebugState = vk::DebugUtilsMessengerEXT;
-#[cfg(not(debug_assertions))]
-pub(crate) type DebugState = ();
+use crate::validation::{debug_callback, ValidationLayers, ValidationState};
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// What create_instance enabled beyond the window's own extensions.
+pub(crate) struct InstanceFeatures {
+    pub(crate) swapchain_colorspace: bool,
+    /// VK_EXT_debug_utils: the validation messenger and debug_label.rs.
+    pub(crate) debug_utils: bool,
+    /// The validation layer parts actually running.
+    pub(crate) validation: ValidationLayers,
+}
 
 type InitRet = (
     ash::Entry,
     ash::Instance,
     surface::Instance,
     vk::SurfaceKHR,
-    Option<DebugState>,
-    bool,
+    Option<vk::DebugUtilsMessengerEXT>,
+    InstanceFeatures,
 );
 
-#[cfg(debug_assertions)]
-unsafe extern "system" fn debug_callback(
-    _severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    _types: vk::DebugUtilsMessageTypeFlagsEXT,
-    data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _user: *mut std::ffi::c_void,
-) -> vk::Bool32 {
-    if !data.is_null() {
-        let msg = unsafe { std::ffi::CStr::from_ptr((*data).p_message) };
-        eprintln!("[Vulkan] {:?}", msg);
-    }
-    vk::FALSE
-}
-
-#[cfg(debug_assertions)]
+/// `validation` receives every message (see validation.rs) and must
+/// outlive the messenger.
 pub(crate) fn create_debug_messenger(
     entry: &ash::Entry,
     instance: &ash::Instance,
-) -> Result<DebugState> {
+    validation: &ValidationState,
+) -> Result<vk::DebugUtilsMessengerEXT> {
     let debug_loader = ext_debug::Instance::new(entry, instance);
     let ci = vk::DebugUtilsMessengerCreateInfoEXT {
         s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
@@ -51,30 +46,78 @@ pub(crate) fn create_debug_messenger(
             | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
             | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
         pfn_user_callback: Some(debug_callback),
+        p_user_data: validation as *const ValidationState as *mut std::ffi::c_void,
         ..Default::default()
     };
     Ok(unsafe { debug_loader.create_debug_utils_messenger(&ci, None)? })
 }
 
-#[cfg(not(debug_assertions))]
-pub(crate) fn create_debug_messenger(
-    _entry: &ash::Entry,
-    _instance: &ash::Instance,
-) -> Result<DebugState> {
-    Ok(())
-}
-
-#[cfg(debug_assertions)]
 pub(crate) fn destroy_debug_messenger(
     entry: &ash::Entry,
     instance: &ash::Instance,
-    dbg: DebugState,
+    dbg: vk::DebugUtilsMessengerEXT,
 ) {
     let loader = ext_debug::Instance::new(entry, instance);
     unsafe { loader.destroy_debug_utils_messenger(dbg, None) };
 }
 
-fn create_instance(entry: &Entry, display_raw: RawDisplayHandle) -> Result<(Instance, bool)> {
+fn has_extension(exts: &[vk::ExtensionProperties], name: &CStr) -> bool {
+    exts.iter()
+        .any(|e| unsafe { CStr::from_ptr(e.extension_name.as_ptr()) } == name)
+}
+
+/// `requested`, less what this system can't provide: the layer itself
+/// (it ships with the Vulkan SDK or a distribution's validation-layers
+/// package, not with drivers), and VK_EXT_validation_features for the
+/// GPU-assisted and best-practices parts. Each missing piece is a warning,
+/// never a failure.
+fn supported_validation(
+    entry: &Entry,
+    requested: ValidationLayers,
+    has_debug_utils: bool,
+) -> ValidationLayers {
+    if !requested.enabled {
+        return ValidationLayers::OFF;
+    }
+    let layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap_or_default();
+    let installed = layers
+        .iter()
+        .any(|l| unsafe { CStr::from_ptr(l.layer_name.as_ptr()) } == VALIDATION_LAYER);
+    if !installed {
+        tracing::warn!(
+            "validation requested, but {VALIDATION_LAYER:?} isn't installed (Vulkan SDK or \
+             the distribution's vulkan-validation-layers); running without it"
+        );
+        return ValidationLayers::OFF;
+    }
+    if !has_debug_utils {
+        tracing::warn!(
+            "validation requested, but VK_EXT_debug_utils is missing; running without it"
+        );
+        return ValidationLayers::OFF;
+    }
+    let mut supported = requested;
+    if requested.gpu_assisted || requested.best_practices {
+        let layer_exts =
+            unsafe { entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER)) }
+                .unwrap_or_default();
+        if !has_extension(&layer_exts, ash::ext::validation_features::NAME) {
+            tracing::warn!(
+                "the validation layer has no VK_EXT_validation_features; GPU-assisted and \
+                 best-practices checks stay off"
+            );
+            supported.gpu_assisted = false;
+            supported.best_practices = false;
+        }
+    }
+    supported
+}
+
+pub(crate) fn create_instance(
+    entry: &Entry,
+    display_raw: RawDisplayHandle,
+    validation: ValidationLayers,
+) -> Result<(Instance, InstanceFeatures)> {
     let app = std::ffi::CString::new("CubicEngine").unwrap();
 
     let app_info = vk::ApplicationInfo {
@@ -95,60 +138,91 @@ fn create_instance(entry: &Entry, display_raw: RawDisplayHandle) -> Result<(Inst
             .enumerate_instance_extension_properties(None)
             .context("enumerate_instance_extension_properties(instance)")?
     };
-    let has_swapchain_cs = inst_exts.iter().any(|e| unsafe {
-        std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) == ash::ext::swapchain_colorspace::NAME
-    });
-
-    #[cfg(debug_assertions)]
-    let ext_vec = {
-        let mut v = ext_slice.to_vec();
-        if has_swapchain_cs {
-            v.push(ash::ext::swapchain_colorspace::NAME.as_ptr());
-        }
-        v.push(ash::ext::debug_utils::NAME.as_ptr());
-        v
-    };
-    #[cfg(not(debug_assertions))]
-    let ext_vec = {
-        let mut v = ext_slice.to_vec();
-        if has_swapchain_cs {
-            v.push(ash::ext::swapchain_colorspace::NAME.as_ptr());
-        }
-        v
-    };
+    let has_swapchain_cs = has_extension(&inst_exts, ash::ext::swapchain_colorspace::NAME);
+    // Needed by the device's VK_EXT_full_screen_exclusive (see
+    // decide_path_and_create_device); harmless without it.
+    let has_surface_caps2 = has_extension(&inst_exts, ash::khr::get_surface_capabilities2::NAME);
+    // Needed (with the above) by the device's VK_EXT_swapchain_maintenance1.
+    let has_surface_maint1 =
+        has_surface_caps2 && has_extension(&inst_exts, ash::ext::surface_maintenance1::NAME);
+    let has_debug_utils = has_extension(&inst_exts, ext_debug::NAME);
+    let validation = supported_validation(entry, validation, has_debug_utils);
+    // Debug builds name objects for capture tools (debug_label.rs) even
+    // with the layer off.
+    let debug_utils = has_debug_utils && (validation.enabled || cfg!(debug_assertions));
 
-    #[cfg(debug_assertions)]
-    let layers = [std::ffi::CString::new("VK_LAYER_KHRONOS_validation").unwrap()];
-    #[cfg(debug_assertions)]
-    let layer_ptrs: [*const c_char; 1] = [layers[0].as_ptr()];
-    let (enabled_layer_count, pp_enabled_layer_names) = {
-        #[cfg(debug_assertions)]
-        {
-            (layer_ptrs.len() as u32, layer_ptrs.as_ptr())
-        }
-        #[cfg(not(debug_assertions))]
-        {
-            (0u32, std::ptr::null::<*const c_char>())
-        }
+    let mut ext_vec = ext_slice.to_vec();
+    if has_swapchain_cs {
+        ext_vec.push(ash::ext::swapchain_colorspace::NAME.as_ptr());
+    }
+    if has_surface_caps2 {
+        ext_vec.push(ash::khr::get_surface_capabilities2::NAME.as_ptr());
+    }
+    if has_surface_maint1 {
+        ext_vec.push(ash::ext::surface_maintenance1::NAME.as_ptr());
+    }
+    if debug_utils {
+        ext_vec.push(ext_debug::NAME.as_ptr());
+    }
+
+    let mut enables = Vec::new();
+    if validation.gpu_assisted {
+        enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+        enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+    }
+    if validation.best_practices {
+        enables.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+    }
+    if !enables.is_empty() {
+        ext_vec.push(ash::ext::validation_features::NAME.as_ptr());
+    }
+    let features = vk::ValidationFeaturesEXT {
+        s_type: vk::StructureType::VALIDATION_FEATURES_EXT,
+        enabled_validation_feature_count: enables.len() as u32,
+        p_enabled_validation_features: enables.as_ptr(),
+        ..Default::default()
     };
+    let layer_ptrs: [*const c_char; 1] = [VALIDATION_LAYER.as_ptr()];
+    let enabled_layers: &[*const c_char] = if validation.enabled { &layer_ptrs } else { &[] };
 
     let create_info = vk::InstanceCreateInfo {
         s_type: vk::StructureType::INSTANCE_CREATE_INFO,
+        p_next: if enables.is_empty() {
+            std::ptr::null()
+        } else {
+            &features as *const _ as *const std::ffi::c_void
+        },
         p_application_info: &app_info,
         enabled_extension_count: ext_vec.len() as u32,
         pp_enabled_extension_names: ext_vec.as_ptr(),
-        enabled_layer_count,
-        pp_enabled_layer_names,
+        enabled_layer_count: enabled_layers.len() as u32,
+        pp_enabled_layer_names: enabled_layers.as_ptr(),
         ..Default::default()
     };
 
     let instance = unsafe { entry.create_instance(&create_info, None)? };
-    Ok((instance, has_swapchain_cs))
+    if validation.enabled {
+        tracing::info!(
+            "vk: validation layer on (GPU-assisted: {}, best practices: {})",
+            validation.gpu_assisted,
+            validation.best_practices
+        );
+    }
+    Ok((
+        instance,
+        InstanceFeatures {
+            swapchain_colorspace: has_swapchain_cs,
+            debug_utils,
+            validation,
+        },
+    ))
 }
 
 pub(crate) fn init_instance_and_surface(
     window: &dyn HasWindowHandle,
     display: &dyn HasDisplayHandle,
+    layers: ValidationLayers,
+    validation: &ValidationState,
 ) -> anyhow::Result<InitRet> {
     let dh = display
         .display_handle()
@@ -161,7 +235,7 @@ pub(crate) fn init_instance_and_surface(
 
     let entry = Entry::linked();
 
-    let (instance, have_swapchain_colorspace_ext) = create_instance(&entry, dh)?;
+    let (instance, features) = create_instance(&entry, dh, layers)?;
 
     let surface_loader = surface::Instance::new(&entry, &instance);
 
@@ -170,8 +244,8 @@ pub(crate) fn init_instance_and_surface(
             .context("ash_window::create_surface")?
     };
 
-    let debug_state = if cfg!(debug_assertions) {
-        Some(create_debug_messenger(&entry, &instance)?)
+    let debug_messenger = if features.validation.enabled {
+        Some(create_debug_messenger(&entry, &instance, validation)?)
     } else {
         None
     };
@@ -181,8 +255,8 @@ pub(crate) fn init_instance_and_surface(
         instance,
         surface_loader,
         surface,
-        debug_state,
-        have_swapchain_colorspace_ext,
+        debug_messenger,
+        features,
     ))
 }
 
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Letterboxing: the scene held to a fixed aspect ratio and/or under a
+//! maximum size, centred in the window with black bars around it, for
+//! games whose layout assumes one shape of screen (`[window] aspect_lock`
+//! and `max_width`/`max_height`, which the window itself may not honour:
+//! fullscreen, a tiling compositor).
+//!
+//! A fixed internal resolution (set_internal_resolution, `[render]
+//! internal_resolution`) letterboxes too: the scene renders at exactly that
+//! size, say 640x360 for pixel art, whatever the window's, and is scaled up
+//! with NEAREST sampling by the largest whole factor that fits (every
+//! texel the same number of pixels) or, with PixelScaling::Nearest, to
+//! fill as much as its aspect ratio allows. It replaces the render scale
+//! and upscaler while set.
+//!
+//! A letterboxed scene always goes through the tonemap pass's
+//! intermediate target, sized to the letterboxed area (times the render
+//! scale) or to the internal resolution, and the tonemap pass draws it
+//! into that area of the swapchain and clears the rest. Text and egui still
+//! cover the whole window, and pick() takes window pixels as before.
+
+use ash::vk;
+use cubic_render::RenderSize;
+
+use crate::swapchain::transform_swaps_axes;
+use crate::VkRenderer;
+
+/// set_letterbox's constraints; the default has none, and the scene fills
+/// the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Letterbox {
+    /// Width:height the scene keeps, e.g. [16, 9].
+    pub aspect: Option<[u32; 2]>,
+    /// Largest scene size in physical pixels.
+    pub max_size: Option<[u32; 2]>,
+}
+
+/// How a fixed internal resolution is scaled up to the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelScaling {
+    /// By the largest whole factor that fits, so every texel is the same
+    /// size on screen; falls back to Nearest in a window smaller than the
+    /// internal resolution.
+    #[default]
+    Integer,
+    /// As large as fits at the internal resolution's aspect ratio; texels
+    /// differ by a pixel here and there.
+    Nearest,
+}
+
+impl PixelScaling {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Integer => "integer",
+            Self::Nearest => "nearest",
+        }
+    }
+}
+
+/// set_internal_resolution's mode: the scene at `size` physical pixels
+/// (width, height), scaled up to the window by `scaling`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InternalResolution {
+    pub size: [u32; 2],
+    pub scaling: PixelScaling,
+}
+
+/// The largest `w`:`h` rectangle inside `avail_w` x `avail_h`.
+fn fit_aspect(avail_w: u32, avail_h: u32, w: u32, h: u32) -> (u32, u32) {
+    let full_height_w = (avail_h as u64 * w as u64 / h as u64) as u32;
+    if full_height_w <= avail_w {
+        (full_height_w.max(1), avail_h)
+    } else {
+        (
+            avail_w,
+            ((avail_w as u64 * h as u64 / w as u64) as u32).max(1),
+        )
+    }
+}
+
+/// A `size` rectangle centred in `area`.
+fn centred(area: vk::Rect2D, size: vk::Extent2D) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: area.offset.x + ((area.extent.width - size.width) / 2) as i32,
+            y: area.offset.y + ((area.extent.height - size.height) / 2) as i32,
+        },
+        extent: size,
+    }
+}
+
+/// The part of a swapchain of `extent` the scene shows in under
+/// `letterbox`, centred. Constraints apply to the window's upright size,
+/// so a quarter-turn `pre_transform` swaps the result's axes back.
+fn letterbox_area(
+    extent: vk::Extent2D,
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    letterbox: Letterbox,
+) -> vk::Rect2D {
+    let swaps = transform_swaps_axes(pre_transform);
+    let (mut w, mut h) = if swaps {
+        (extent.height, extent.width)
+    } else {
+        (extent.width, extent.height)
+    };
+    if let Some([max_w, max_h]) = letterbox.max_size {
+        w = w.min(max_w.max(1));
+        h = h.min(max_h.max(1));
+    }
+    if let Some([aw, ah]) = letterbox.aspect.filter(|&[aw, ah]| aw > 0 && ah > 0) {
+        (w, h) = fit_aspect(w, h, aw, ah);
+    }
+    if swaps {
+        std::mem::swap(&mut w, &mut h);
+    }
+    let whole = vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent,
+    };
+    centred(
+        whole,
+        vk::Extent2D {
+            width: w,
+            height: h,
+        },
+    )
+}
+
+/// Where a scene of `scene` (the internal resolution, in the swapchain's
+/// orientation) goes inside `area`, scaled by `scaling`.
+fn scaled_area(area: vk::Rect2D, scene: vk::Extent2D, scaling: PixelScaling) -> vk::Rect2D {
+    let factor = (area.extent.width / scene.width).min(area.extent.height / scene.height);
+    let (w, h) = if scaling == PixelScaling::Integer && factor >= 1 {
+        (scene.width * factor, scene.height * factor)
+    } else {
+        fit_aspect(
+            area.extent.width,
+            area.extent.height,
+            scene.width,
+            scene.height,
+        )
+    };
+    centred(
+        area,
+        vk::Extent2D {
+            width: w,
+            height: h,
+        },
+    )
+}
+
+impl VkRenderer {
+    /// Keep the scene to `letterbox`'s aspect ratio and maximum size,
+    /// centred with black bars (see module docs). Recreates the swapchain
+    /// if it changes.
+    pub fn set_letterbox(&mut self, letterbox: Letterbox) {
+        if self.cfg.letterbox == letterbox {
+            return;
+        }
+        self.cfg.letterbox = letterbox;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    pub fn letterbox(&self) -> Letterbox {
+        self.cfg.letterbox
+    }
+
+    /// Render the scene at a fixed resolution and scale it up to the
+    /// window (see module docs), or back at the window's with None.
+    /// Recreates the swapchain if it changes.
+    pub fn set_internal_resolution(&mut self, internal: Option<InternalResolution>) {
+        let internal = internal.map(|r| InternalResolution {
+            size: r.size.map(|d| d.max(1)),
+            ..r
+        });
+        if self.cfg.internal_resolution == internal {
+            return;
+        }
+        self.cfg.internal_resolution = internal;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    pub fn internal_resolution(&self) -> Option<InternalResolution> {
+        self.cfg.internal_resolution
+    }
+
+    /// The internal resolution in the swapchain's orientation, if one is
+    /// set.
+    pub(crate) fn internal_extent(&self) -> Option<vk::Extent2D> {
+        let [w, h] = self.cfg.internal_resolution?.size;
+        Some(if transform_swaps_axes(self.pre_transform) {
+            vk::Extent2D {
+                width: h,
+                height: w,
+            }
+        } else {
+            vk::Extent2D {
+                width: w,
+                height: h,
+            }
+        })
+    }
+
+    /// Where in the swapchain image the scene goes: all of it unless
+    /// letterboxed.
+    pub(crate) fn output_area(&self) -> vk::Rect2D {
+        let area = letterbox_area(self.extent, self.pre_transform, self.cfg.letterbox);
+        match (self.cfg.internal_resolution, self.internal_extent()) {
+            (Some(internal), Some(scene)) => scaled_area(area, scene, internal.scaling),
+            _ => area,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: extent(width, height),
+        }
+    }
+
+    const UPRIGHT: vk::SurfaceTransformFlagsKHR = vk::SurfaceTransformFlagsKHR::IDENTITY;
+
+    #[test]
+    fn fit_aspect_fills_the_tighter_axis() {
+        // Pillarboxed: the height limits.
+        assert_eq!(fit_aspect(1920, 1080, 4, 3), (1440, 1080));
+        // Letterboxed: the width limits, height rounds down.
+        assert_eq!(fit_aspect(1000, 1000, 16, 9), (1000, 562));
+        // Never collapses to zero.
+        assert_eq!(fit_aspect(1, 1000, 1000, 1), (1, 1));
+    }
+
+    #[test]
+    fn no_constraints_fill_the_window() {
+        let area = letterbox_area(extent(1920, 1080), UPRIGHT, Letterbox::default());
+        assert_eq!(area, rect(0, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn aspect_lock_centres_the_scene() {
+        let letterbox = Letterbox {
+            aspect: Some([4, 3]),
+            max_size: None,
+        };
+        let area = letterbox_area(extent(1920, 1080), UPRIGHT, letterbox);
+        assert_eq!(area, rect(240, 0, 1440, 1080));
+    }
+
+    #[test]
+    fn odd_leftover_pixel_goes_right_and_down() {
+        let letterbox = Letterbox {
+            aspect: Some([16, 9]),
+            max_size: None,
+        };
+        // 3 spare columns: 1 left, 2 right.
+        let area = letterbox_area(extent(1923, 1080), UPRIGHT, letterbox);
+        assert_eq!(area, rect(1, 0, 1920, 1080));
+        // 1 spare row: all of it below.
+        let area = letterbox_area(extent(1920, 1081), UPRIGHT, letterbox);
+        assert_eq!(area, rect(0, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn max_size_applies_before_aspect() {
+        let letterbox = Letterbox {
+            aspect: Some([16, 9]),
+            max_size: Some([800, 600]),
+        };
+        let area = letterbox_area(extent(1920, 1080), UPRIGHT, letterbox);
+        assert_eq!(area, rect(560, 315, 800, 450));
+
+        // A zero maximum is clamped to a pixel, and a zero aspect ignored.
+        let letterbox = Letterbox {
+            aspect: Some([0, 9]),
+            max_size: Some([0, 0]),
+        };
+        let area = letterbox_area(extent(1920, 1080), UPRIGHT, letterbox);
+        assert_eq!(area, rect(959, 539, 1, 1));
+    }
+
+    #[test]
+    fn quarter_turn_constrains_the_upright_window() {
+        let letterbox = Letterbox {
+            aspect: Some([4, 3]),
+            max_size: None,
+        };
+        // A landscape window on a portrait swapchain: 4:3 across the
+        // window's width is 3:4 across the swapchain's.
+        let area = letterbox_area(
+            extent(1080, 1920),
+            vk::SurfaceTransformFlagsKHR::ROTATE_90,
+            letterbox,
+        );
+        assert_eq!(area, rect(0, 240, 1080, 1440));
+        let area = letterbox_area(
+            extent(1920, 1080),
+            vk::SurfaceTransformFlagsKHR::ROTATE_180,
+            letterbox,
+        );
+        assert_eq!(area, rect(240, 0, 1440, 1080));
+    }
+
+    #[test]
+    fn integer_scaling_takes_the_largest_whole_factor() {
+        let scene = extent(640, 360);
+        // 2.5x across, 2.5x down: 2x, centred.
+        let area = scaled_area(rect(0, 0, 1600, 900), scene, PixelScaling::Integer);
+        assert_eq!(area, rect(160, 90, 1280, 720));
+        // The smaller axis decides: 3x across fits, only 2x down.
+        let area = scaled_area(rect(0, 0, 1920, 800), scene, PixelScaling::Integer);
+        assert_eq!(area, rect(320, 40, 1280, 720));
+        // Inside a letterboxed area, offset by it.
+        let area = scaled_area(rect(100, 20, 1600, 900), scene, PixelScaling::Integer);
+        assert_eq!(area, rect(260, 110, 1280, 720));
+    }
+
+    #[test]
+    fn nearest_scaling_fills_at_the_scene_aspect() {
+        let area = scaled_area(
+            rect(0, 0, 1600, 1000),
+            extent(640, 360),
+            PixelScaling::Nearest,
+        );
+        assert_eq!(area, rect(0, 50, 1600, 900));
+    }
+
+    #[test]
+    fn integer_scaling_falls_back_to_nearest_below_1x() {
+        let scene = extent(640, 360);
+        let integer = scaled_area(rect(0, 0, 600, 300), scene, PixelScaling::Integer);
+        let nearest = scaled_area(rect(0, 0, 600, 300), scene, PixelScaling::Nearest);
+        assert_eq!(integer, nearest);
+        assert_eq!(integer, rect(33, 0, 533, 300));
+    }
+}
@@ -1,73 +1,189 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
 
+mod block_textures;
+mod capture;
+mod color_filter;
+mod compute;
+mod crash_report;
+mod debug_draw;
+mod debug_label;
+mod descriptors;
 mod device;
+mod device_lost;
 mod egui_overlay;
+mod error;
 mod frame;
+mod frame_graph;
+mod gpu_info;
+mod hdr_metadata;
+mod ibl;
 mod instance;
+mod letterbox;
+mod light_clusters;
+mod lighting;
+mod material;
+mod memory;
+mod picking;
 mod pipeline;
+mod post;
+mod reflect;
+mod render_target;
 mod resources;
+mod sampler;
+#[cfg(all(debug_assertions, feature = "runtime-shader-compile"))]
+mod shader_compile;
+mod shadow;
+mod skybox;
+mod staging_belt;
+mod stereo;
+mod suspend;
 mod swapchain;
 mod sync;
+mod text;
+mod tonemap;
+mod upload;
+mod upscale;
+mod validation;
+mod viewports;
 
 use anyhow::{anyhow, Result};
 use ash::khr::surface;
 use ash::{vk, Entry};
-use cubic_math::Camera;
-use cubic_render::{RenderSize, Renderer};
-use device::{decide_path_and_create_device, select_device_and_queue, RenderPath};
+use crash_report::CrashDiagnostics;
+use cubic_math::{Camera, Mat4};
+use cubic_render::{
+    clip, DepthConvention, FramePacer, FrameStats, FrameStatsTracker, LatencyMode, PresentMode,
+    RenderSize, Renderer, StreamCounts, SurfaceChanged, SurfaceInfo,
+};
+use debug_draw::DebugLine;
+use debug_label::DebugLabels;
+use descriptors::{DescriptorAllocator, Descriptors, PooledSet};
+use device::{
+    decide_path_and_create_device, find_transfer_queue_family, select_device_and_queue,
+    QueueFamilies, RenderPath,
+};
+use error::{classify, vk_error};
 use gpu_allocator::vulkan::{Allocation, Allocator, AllocatorCreateDesc};
 use gpu_allocator::MemoryLocation;
-#[cfg(debug_assertions)]
-use instance::destroy_debug_messenger;
-use instance::{init_instance_and_surface, recreate_surface};
+use instance::{destroy_debug_messenger, init_instance_and_surface};
 #[cfg(debug_assertions)]
 use pipeline::ShaderDev;
 use pipeline::{
-    create_compute_pipeline, create_or_load_pipeline_cache, create_pipeline, load_spv_file,
-    pipeline_cache_path, save_pipeline_cache, shader_dir, PipelineConfig,
+    create_compute_pipeline, create_depth_prepass_pipeline, create_or_load_pipeline_cache,
+    create_pipeline, load_spv_file, pipeline_cache_path, save_pipeline_cache, shader_dir,
+    vk_vertex_format, PipelineConfig,
 };
+pub use pipeline::{BlendMode, PipelineDesc, SpecValue};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use reflect::ReflectedLayout;
 use resources::{
-    create_buffer_and_memory, create_camera_desc_set_layout, create_depth_resources,
-    create_dummy_texture_and_sampler, create_frame_uniforms_and_sets,
-    create_indirect_compute_desc_set_layout, create_indirect_draw_resources,
-    create_indirect_graphics_desc_set_layout, create_material_desc_pool_and_set,
-    create_material_desc_set_layout, pick_depth_format, upload_via_staging,
-    write_material_descriptors, RangeAlloc, SamplerConfig, MAX_SHARED_INDICES, MAX_SHARED_VERTICES,
+    create_buffer_and_memory_shared, create_camera_desc_set_layout, create_camera_sets,
+    create_depth_resources, create_dummy_texture, create_indirect_compute_desc_set_layout,
+    create_indirect_draw_resources, create_indirect_graphics_desc_set_layout,
+    create_material_desc_set_layout, pick_depth_format, pick_mip_gen, query_texture_array_caps,
+    CullPush, FrameUniformOffsets, MipGen, RangeAlloc, RetainedTexture, TextureArrayCaps,
+    MAX_SHARED_INDICES, MAX_SHARED_VERTICES,
 };
 use tracing::info;
 // Vertex, PushData, and MeshHandle are now defined in cubic-render so that
 // cubic-world can use them without depending on Vulkan. Re-export them from
 // here so existing callers (cubic-app etc.) import from cubic-render-vk
 // without any changes.
-pub use cubic_render::{MeshHandle, PushData, Vertex};
+use block_textures::BlockTextures;
+use capture::Capture;
+pub use capture::{CaptureConfig, CaptureSink, CaptureStats};
+pub use color_filter::{ColorBlindness, ColorFilter};
+pub use compute::{
+    BufferAccess, ComputeBindingsHandle, ComputeDesc, ComputePipelineHandle, GpuBufferHandle,
+};
+pub use cubic_render::TonemapOperator;
+pub use cubic_render::{
+    MemoryKind, MeshHandle, PipelineHandle, PushData, RenderError, RenderResult, Vertex,
+    VertexAttribute, VertexFormat, VertexLayout,
+};
+pub use gpu_info::{describe_gpus, GpuInfo};
+use hdr_metadata::detect_display_hdr_metadata;
+pub use hdr_metadata::HdrMetadata;
+use ibl::IblPass;
+pub use letterbox::{InternalResolution, Letterbox, PixelScaling};
+use light_clusters::LightClusters;
+pub use lighting::{
+    DirectionalLight, Light, LightHandle, ShadowSettings, MAX_LIGHTS, MAX_SHADOW_CASCADES,
+};
+use material::{Material, MaterialPool};
+pub use material::{MaterialDesc, MaterialHandle, ShadingModel};
+pub use memory::{HeapStats, MemoryCategory, MemoryStats};
+pub use picking::ObjectId;
+use picking::Picking;
+pub use post::PostEffect;
+use post::{PostChain, PostStack};
+use render_target::RenderTarget;
+pub use render_target::{RenderTargetDesc, TargetHandle};
+use sampler::SamplerCache;
+pub use sampler::SamplerDesc;
+use shadow::{pick_shadow_format, ShadowPass};
+pub use skybox::CubemapHandle;
+use skybox::SkyboxPass;
+pub use staging_belt::BufferSlice;
+use staging_belt::StagingBelt;
+pub use stereo::stereo_eyes;
+use stereo::Stereo;
 use swapchain::{
-    create_hdr_metadata_if_needed, create_swapchain_bundle, SwapchainBundle, SwapchainConfig,
+    create_hdr_metadata_if_needed, create_swapchain_bundle, PresentPriority, SwapchainBundle,
+    SwapchainConfig,
 };
-pub use swapchain::{HdrFlavor, VkVsyncMode};
+pub use swapchain::{HdrDisplayChange, HdrFlavor, VkVsyncMode};
+pub use upscale::{Upscaler, MIN_RENDER_SCALE};
+pub use validation::{ValidationLayers, ValidationPolicy};
+use viewports::SceneView;
+pub use viewports::ViewportCamera;
 // Re-exported so callers (cubic-app's set_sampler_config plumbing) can build
 // sampler settings without depending on `ash` directly. These two are plain,
 // trivially-constructible enums (unlike e.g. vsync/HDR, which need custom
 // wrapper types for fallback logic), so re-exporting as-is is simplest.
-pub use ash::vk::{Filter, SamplerMipmapMode};
+pub use ash::vk::{
+    Filter, ImageUsageFlags, PolygonMode, PrimitiveTopology, SamplerAddressMode, SamplerMipmapMode,
+};
 use sync::{
     create_command_resources, create_sync_objects, create_timeline_semaphore, AcquireSlot,
-    CommandResources, FrameSync,
+    CommandResources, FrameSync, FRAMES_IN_FLIGHT,
 };
+use text::{TextFont, TextPass, TextVertex};
+use tonemap::TonemapPass;
+pub use tonemap::{DEFAULT_SDR_WHITE_NITS, MAX_SDR_WHITE_NITS, MIN_SDR_WHITE_NITS};
+use upload::TransferUploader;
+use upscale::Fsr1Pass;
+use validation::ValidationState;
 
 /// Offsets into the shared vertex/index buffers (see
 /// `MAX_SHARED_VERTICES`/`MAX_SHARED_INDICES`) rather than owning dedicated
 /// buffers: one `cmd_draw_indexed_indirect_count` call can only bind a
 /// single vertex/index buffer pair, so every mesh that might be drawn
 /// together in one indirect call has to live in the same buffers.
+///
+/// Meshes with a non-standard `VertexLayout` live in the same vertex buffer:
+/// their byte range is rounded to a multiple of their own stride, so
+/// `first_vertex` (in units of that stride) still works as the draw's
+/// vertexOffset. `slot_start`/`slot_len` are what was taken from the
+/// allocator, in `Vertex`-sized units, for free_mesh to hand back.
 struct GpuMesh {
     first_vertex: i32,
     first_index: u32,
     index_count: u32,
     vertex_count: u32,
+    slot_start: u32,
+    slot_len: u32,
+    // Index into VkRenderer::vertex_layouts (0 = VertexLayout::standard()).
+    layout: u32,
+    // Mesh-space bounding sphere for frustum culling (GPU and CPU): xyz
+    // centre, w radius. w < 0 = no bounds known, never culled.
+    bounds: [f32; 4],
 }
 
+/// GpuMesh::bounds of a mesh the cull shader must always keep.
+const UNBOUNDED: [f32; 4] = [0.0, 0.0, 0.0, -1.0];
+
 /// A GPU object retired while it might still be in use, destroyed once the
 /// timeline semaphore reaches `value` (see `VkRenderer::drain_trash`).
 enum GpuResource {
@@ -82,12 +198,37 @@ enum GpuResource {
     ImageView(vk::ImageView),
     Pipeline(vk::Pipeline),
     PipelineLayout(vk::PipelineLayout),
+    // A set from descriptors.persistent, returned to its pool.
+    DescriptorSet(PooledSet),
     MeshSlot {
         first_vertex: u32,
         vertex_count: u32,
         first_index: u32,
         index_count: u32,
     },
+    // A MaterialPool slot, handed back for reuse.
+    MaterialSlot(u32),
+    // A pass's whole set of objects (see RetiredPass).
+    Pass(Box<dyn RetiredPass>),
+    // A replaced swapchain with its views and per-image sync, destroyed in
+    // that order.
+    Swapchain {
+        swapchain: vk::SwapchainKHR,
+        views: Vec<vk::ImageView>,
+        frames: Vec<FrameSync>,
+    },
+}
+
+/// A pass that owns a mix of targets, pipelines and descriptor sets and
+/// frees them all at once: retired as a GpuResource::Pass when it's
+/// rebuilt or turned off, instead of idling the device to destroy it.
+trait RetiredPass {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    );
 }
 
 struct DeferredDrop {
@@ -95,6 +236,34 @@ struct DeferredDrop {
     resource: GpuResource,
 }
 
+/// One draw_mesh* call, waiting for the next render().
+#[derive(Clone, Copy)]
+struct QueuedDraw {
+    mesh: MeshHandle,
+    push: PushData,
+    pipeline: PipelineHandle,
+    material: MaterialHandle,
+    // draw_mesh_with_id's tag for the object-id pass; 0 = none.
+    object: ObjectId,
+    // Outside the frustum: not drawn in the scene, still a shadow caster.
+    // Set by cull_direct_draws; the GPU-culled draws never are.
+    culled: bool,
+}
+
+/// A pipeline registered via `register_pipeline`. The description is kept
+/// so the pipeline can be rebuilt against a new color format (see
+/// `recreate_swapchain`) or freshly hot-reloaded shaders.
+struct NamedPipeline {
+    desc: PipelineDesc,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    // What `layout` was reflected as: its sets and push constant range.
+    reflected: ReflectedLayout,
+    // desc.vertex_layout's index in VkRenderer::vertex_layouts, so draws
+    // can be matched against their mesh's layout without comparing lists.
+    vertex_layout: u32,
+}
+
 // 3) Renderer data model
 pub struct VkRenderer {
     instance: ash::Instance,
@@ -104,6 +273,9 @@ pub struct VkRenderer {
     phys: vk::PhysicalDevice,
     device: ash::Device,
     queue: vk::Queue,
+    // Where presents go: `queue` itself unless the device only presents
+    // from another family (see QueueFamilies).
+    present_queue: vk::Queue,
     // Option so Drop can `.take()` it and drop it explicitly before the
     // device is destroyed (Allocator::drop frees any remaining cached
     // memory blocks via its own device handle).
@@ -113,11 +285,87 @@ pub struct VkRenderer {
     swapchain: vk::SwapchainKHR,
     format: vk::Format,
     extent: vk::Extent2D,
+    // What the last swapchain (re)creation actually got, as opposed to
+    // what `cfg` asked for — reported by present_summary().
+    color_space: vk::ColorSpaceKHR,
+    present_mode: vk::PresentModeKHR,
+    // Usage flags the swapchain images were created with: COLOR_ATTACHMENT
+    // plus whatever of cfg.swapchain_usage the surface/format allowed.
+    // Check this before blitting from or copying into a swapchain image.
+    swapchain_usage: vk::ImageUsageFlags,
+    // IDENTITY unless the surface insisted on the display's rotation, in
+    // which case the swapchain is in the display's natural orientation and
+    // scene and text are turned to match (see swapchain::pre_rotation).
+    // egui-ash-renderer has no rotation, so egui still comes out sideways
+    // there.
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    // Present modes the live swapchain can switch to in place (see
+    // apply_present_mode); just present_mode without
+    // VK_EXT_swapchain_maintenance1.
+    present_modes: Vec<vk::PresentModeKHR>,
+    // Recreations not yet picked up by take_surface_change, coalesced.
+    surface_change: Option<SurfaceChanged>,
 
     images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
+    // HDR tonemap post pass (see tonemap.rs); Some only while the
+    // swapchain colour space is scRGB/HDR10 and the pass built. Scene
+    // pipelines target its FP16 image instead of the swapchain then.
+    tonemap: Option<TonemapPass>,
+    // FSR1 upscale passes (see upscale.rs); Some only while the scene is
+    // scaled, FSR1 is selected and its shaders built.
+    fsr1: Option<Fsr1Pass>,
+    // Post-processing chain ahead of the tonemap (see post.rs); Some only
+    // while effects are enabled, the tonemap pass is up and post.frag built.
+    post: Option<PostStack>,
+    // Object-id target and pipeline behind pick (see picking.rs); Some
+    // while set_picking is on.
+    picking: Option<Picking>,
+    // Two-layer target, pipelines and eyes behind set_stereo (see
+    // stereo.rs); Some while it's on.
+    stereo: Option<Stereo>,
+    // Read-back ring and writer thread behind start_capture (see
+    // capture.rs); Some while a capture runs.
+    capture: Option<Capture>,
+    // Screen-space text overlay (see text.rs): the pipeline (None if text
+    // shaders aren't built), the font atlas once the first draw_text built
+    // it (or failed to), and this frame's quads.
+    text_pass: Option<TextPass>,
+    text_font: Option<TextFont>,
+    text_font_failed: bool,
+    text_vertices: Vec<TextVertex>,
+    // Immediate-mode debug lines (see debug_draw.rs): the LINE_LIST
+    // pipeline (None if its shaders aren't built), this frame's segments,
+    // and how many went over the per-frame cap.
+    debug_line_pipeline: Option<(vk::PipelineLayout, vk::Pipeline)>,
+    debug_lines: Vec<DebugLine>,
+    debug_lines_dropped: usize,
+    // The default opaque scene pipeline (PipelineHandle::DEFAULT).
     pipeline_layout: vk::PipelineLayout,
     pipeline: vk::Pipeline,
+    // What pipeline_layout was reflected as (see record_direct_draws).
+    pipeline_reflected: ReflectedLayout,
+    // Depth-only twin of the default pipeline, used by the optional depth
+    // prepass (set_depth_prepass). Always built — it's one small pipeline
+    // — so toggling the prepass never stalls on pipeline creation.
+    prepass_pipeline_layout: vk::PipelineLayout,
+    prepass_pipeline: vk::Pipeline,
+    depth_prepass: bool,
+    // Pipelines added via register_pipeline(); PipelineHandle(n) is
+    // named_pipelines[n - 1]. All use the default pipeline's descriptor
+    // set layouts, but each its own pipeline layout (see reflect.rs), so
+    // draws rebind the sets when switching to one.
+    named_pipelines: Vec<NamedPipeline>,
+    // Compute subsystem (see compute.rs): ComputePipelineHandle(n) is
+    // compute_pipelines[n]; freed buffers/bindings leave a None so later
+    // handles stay put. pending_compute is drained like pending_draws.
+    compute_pipelines: Vec<compute::ComputePipeline>,
+    gpu_buffers: Vec<Option<compute::GpuBuffer>>,
+    compute_bindings: Vec<Option<compute::ComputeBindings>>,
+    pending_compute: Vec<compute::ComputeOp>,
+    // Queue families sharing the mesh and compute buffers (graphics, plus
+    // the transfer family uploads run on, if separate).
+    mesh_families: Vec<u32>,
 
     cmd_pool: vk::CommandPool,
     cmd_bufs: Vec<vk::CommandBuffer>,
@@ -125,14 +373,27 @@ pub struct VkRenderer {
 
     clear: vk::ClearValue,
     paused: bool,
+    // Some(size to rebuild at) between suspend() and resume(); surface and
+    // swapchain are null meanwhile (see suspend.rs).
+    suspended: Option<RenderSize>,
+    // Renderer::resize's latest size, not yet applied, and when the
+    // swapchain was last recreated for one (see apply_pending_resize).
+    pending_resize: Option<RenderSize>,
+    resized_at: std::time::Instant,
 
     #[allow(dead_code)]
     path: RenderPath,
-    #[cfg(debug_assertions)]
     debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
     acq_slots: Vec<AcquireSlot>,
     acq_index: usize,
     has_hdr_metadata_ext: bool,
+    // HDR10 metadata sent with every HDR10 swapchain: starts as
+    // display_hdr_metadata, replaced by set_hdr_metadata (cubic-app's
+    // [render.hdr_display] calibration). Also sets the tonemap peak.
+    hdr_metadata: HdrMetadata,
+    // What the connected display's EDID reported at startup, or the
+    // generic default if nothing usable was found.
+    display_hdr_metadata: HdrMetadata,
     cfg: RuntimeConfig,
     camera: Camera,
 
@@ -140,45 +401,63 @@ pub struct VkRenderer {
     depth_alloc: Allocation,
     depth_view: vk::ImageView,
     depth_format: vk::Format,
-    // Shared by every mesh (see GpuMesh); bump-allocated, never freed
-    // individually since there's no free_mesh API yet.
+    // Shared by every mesh (see GpuMesh); sub-allocated through vert_alloc
+    // and idx_alloc, ranges handed back by free_mesh.
     shared_vbuf: vk::Buffer,
     shared_vbuf_alloc: Allocation,
     shared_ibuf: vk::Buffer,
     shared_ibuf_alloc: Allocation,
+    // Device addresses of shared_vbuf and shared_ibuf, for vertex pulling.
+    mesh_pool_addresses: [vk::DeviceAddress; 2],
     vert_alloc: RangeAlloc,
     idx_alloc: RangeAlloc,
     meshes: Vec<GpuMesh>,
+    // Every distinct VertexLayout a mesh or pipeline has used, interned so
+    // GpuMesh/NamedPipeline can refer to one by index. [0] is the standard
+    // Vertex layout the default pipeline reads.
+    vertex_layouts: Vec<VertexLayout>,
+    // Batched mesh uploads on the dedicated transfer queue (or the graphics
+    // queue when there isn't one); see upload.rs.
+    uploader: TransferUploader,
+    // upload_transient's ring of per-frame data; see staging_belt.rs.
+    staging_belt: StagingBelt,
     // Draws queued by draw_mesh() for the next render() call; consumed and
     // cleared each time a frame's command buffer is recorded.
-    pending_draws: Vec<(MeshHandle, PushData)>,
+    pending_draws: Vec<QueuedDraw>,
     // GPU resources retired while possibly still in use; reclaimed once the
     // timeline semaphore catches up (see drain_trash).
     trash: Vec<DeferredDrop>,
-    desc_pool: vk::DescriptorPool,
+    // Every pass's descriptor sets but the materials'; see descriptors.rs.
+    descriptors: Descriptors,
     desc_set_layout_camera: vk::DescriptorSetLayout,
     desc_set_layout_material: vk::DescriptorSetLayout,
     // Graphics-side read-only view of the candidates buffer (set = 2); see
     // indirect_compute_desc_set_layout for the compute-side write access.
     desc_set_layout_indirect_graphics: vk::DescriptorSetLayout,
     desc_set_layout_indirect_compute: vk::DescriptorSetLayout,
-    desc_sets: Vec<vk::DescriptorSet>,
-    ubufs: Vec<vk::Buffer>,
-    umems: Vec<Allocation>,
-    ubo_ptrs: Vec<*mut std::ffi::c_void>,
-    ubo_size: vk::DeviceSize,
-    // GPU-driven indirect draw path: per-image candidate/indirect-command/
-    // draw-count buffers + descriptor sets (see resources::IndirectDrawResources).
+    // Set 0 of the scene and shadow passes, over the staging belt (see
+    // create_camera_sets), and this frame's dynamic offsets for them. The
+    // scene's is replaced rather than rewritten (see renew_camera_sets).
+    camera_set: PooledSet,
+    shadow_camera_set: vk::DescriptorSet,
+    frame_uniforms: FrameUniformOffsets,
+    // GPU-driven indirect draw path: per-frame-slot candidate/bounds/
+    // indirect-command/draw-count buffers + descriptor sets (see
+    // resources::IndirectDrawResources).
     indirect_cull_pipeline: vk::Pipeline,
     indirect_cull_pipeline_layout: vk::PipelineLayout,
     candidate_bufs: Vec<vk::Buffer>,
     candidate_allocs: Vec<Allocation>,
     candidate_ptrs: Vec<*mut std::ffi::c_void>,
+    bounds_bufs: Vec<vk::Buffer>,
+    bounds_allocs: Vec<Allocation>,
+    bounds_ptrs: Vec<*mut std::ffi::c_void>,
+    // set_gpu_culling; when off the cull shader expands every candidate.
+    gpu_culling: bool,
     indirect_bufs: Vec<vk::Buffer>,
     indirect_allocs: Vec<Allocation>,
     draw_count_bufs: Vec<vk::Buffer>,
     draw_count_allocs: Vec<Allocation>,
-    indirect_desc_pool: vk::DescriptorPool,
     indirect_compute_desc_sets: Vec<vk::DescriptorSet>,
     indirect_graphics_desc_sets: Vec<vk::DescriptorSet>,
     pipeline_cache: vk::PipelineCache,
@@ -189,22 +468,51 @@ pub struct VkRenderer {
     backoff_frames: u32,
     #[cfg(debug_assertions)]
     shader_dev: Option<ShaderDev>,
-    material_desc_pool: vk::DescriptorPool,
+    // Set 1 allocator (see material.rs) and create_material's materials,
+    // indexed by MaterialHandle; [0] is MaterialHandle::DEFAULT, whose set
+    // material_desc_set caches for the indirect path.
+    material_pool: MaterialPool,
+    materials: Vec<Option<Material>>,
     material_desc_set: vk::DescriptorSet,
     tex_image: vk::Image,
     tex_alloc: Allocation,
     tex_view: vk::ImageView,
     tex_sampler: vk::Sampler,
+    // Every texture sampler (dummy's included), one per distinct desc;
+    // tex_sampler and tex_store's samplers are borrowed from here.
+    samplers: SamplerCache,
     // Bindless texture array bookkeeping for upload_texture(). Index 0 is
-    // permanently the dummy texture above; uploads start at 1.
+    // permanently the dummy texture above; uploads start at 1, up to
+    // tex_caps.capacity.
+    tex_caps: TextureArrayCaps,
     next_tex_index: u32,
     tex_store: Vec<(vk::Image, Allocation, vk::ImageView, vk::Sampler)>,
+    // Pixels of every tex_store entry, in upload order, for device-lost
+    // recovery (see device_lost.rs).
+    tex_sources: Vec<RetainedTexture>,
+    // upload_texture_async textures still on the transfer queue: (uploader
+    // timeline value, bindless index). Registered by
+    // promote_resident_textures once the value is reached, then reported
+    // through resident_textures (take_resident_textures).
+    pending_textures: Vec<(u64, u32)>,
+    resident_textures: Vec<u32>,
+    // create_render_target's targets, indexed by TargetHandle; None once
+    // destroyed. Their images sit in tex_store (see render_target.rs).
+    render_targets: Vec<Option<RenderTarget>>,
+    // set_viewports' views, and this frame's SceneViews for them (written
+    // with the frame uniforms; empty when drawing through the main camera
+    // alone, see viewports.rs).
+    viewports: Vec<ViewportCamera>,
+    viewport_views: Vec<SceneView>,
     // Filter/mipmap/anisotropy settings applied to every texture uploaded
-    // via upload_texture(). Starts at a sensible default (used for the
+    // via upload_texture() (upload_texture_with_sampler brings its own). Starts at a sensible default (used for the
     // dummy texture, created before cubic-app's configure_advanced() can
     // run); set_sampler_config() overrides it with the real cubic.toml
     // values immediately after construction, before any real textures load.
-    sampler_config: SamplerConfig,
+    sampler_config: SamplerDesc,
+    // Blit or CPU mip generation, decided once from TEXTURE_FORMAT's
+    // format features (see pick_mip_gen).
+    mip_gen: MipGen,
 
     // egui overlay support (GPU plumbing only — no egui::Context or input
     // handling here; that lives in cubic-app). Option because it's created
@@ -212,6 +520,62 @@ pub struct VkRenderer {
     egui_renderer: Option<egui_ash_renderer::Renderer>,
     // Staged by queue_egui(), consumed by the next render() call.
     egui_pending: Option<egui_overlay::EguiFrame>,
+    // What egui has uploaded so far, for device-lost recovery.
+    egui_textures: cubic_render::EguiTextureMirror,
+    // The --gpu / new_with_gpu request, so a rebuild after device loss
+    // lands on the same adapter.
+    gpu_request: Option<String>,
+    // set_target_fps's limiter, run after every present (see pace_frame).
+    pacer: FramePacer,
+    // Rolling frame/CPU times behind frame_stats(), recorded by render()
+    // before pacing.
+    frame_stats: FrameStatsTracker,
+    // Validation-layer message policy and counters; the debug messenger's
+    // user data, so boxed (stable address) and destroyed after it.
+    validation: Box<ValidationState>,
+    // Validation checks the instance was created with (see validation.rs).
+    validation_layers: ValidationLayers,
+    // LatencyMode::Low: render() waits for its frame to be displayed (see
+    // wait_for_latency).
+    latency_mode: LatencyMode,
+    // VK_KHR_present_wait, when the device has it: pace_frame waits on the
+    // previous present before the pacer's own sleep. present_id is the id
+    // attached to the last queue_present (0 = none yet).
+    present_wait: Option<ash::khr::present_wait::Device>,
+    present_id: u64,
+    // Object names and pass labels for capture tools (debug builds), and
+    // the debug_marker labels waiting for the next frame.
+    debug_labels: DebugLabels,
+    pending_markers: Vec<String>,
+    // Device-fault queries and per-pass checkpoints for crash reports
+    // (see crash_report.rs).
+    crash_diag: CrashDiagnostics,
+    // VK_EXT_memory_budget is supported (see memory.rs), and when render()
+    // next logs memory_stats.
+    has_memory_budget: bool,
+    memory_log_at: std::time::Instant,
+    // The device's multiview feature is on (see stereo.rs).
+    has_multiview: bool,
+    // set_directional_light / set_shadow_settings; written into the camera
+    // UBO every frame.
+    light: DirectionalLight,
+    shadow_settings: ShadowSettings,
+    // add_light's list, indexed by LightHandle; None = free slot.
+    lights: Vec<Option<Light>>,
+    // The light cluster pass (see light_clusters.rs); None without its
+    // shader, when the scene loops over the whole list.
+    light_clusters: Option<LightClusters>,
+    // Cascaded shadow map and its pipeline. Always present: with shadows
+    // off it holds a 1x1 placeholder map so the scene's descriptor is valid.
+    shadow: ShadowPass,
+    // Skybox pipeline, loaded cubemaps and the one set_environment picked
+    // (see skybox.rs).
+    skybox: SkyboxPass,
+    // Image-based lighting pipelines and the BRDF LUT (see ibl.rs); None
+    // without their shaders, when cubemaps get no IBL maps.
+    ibl: Option<IblPass>,
+    // upload_block_textures' array and material (see block_textures.rs).
+    block_textures: Option<BlockTextures>,
 }
 
 // STRICT TEARDOWN ORDER:
@@ -225,12 +589,8 @@ pub struct VkRenderer {
 // - Destroy surface AFTER device; instance last.
 impl Drop for VkRenderer {
     fn drop(&mut self) {
-        #[cfg(debug_assertions)]
-        {
-            let entry = Entry::linked();
-            if let Some(dbg) = self.debug_messenger {
-                destroy_debug_messenger(&entry, &self.instance, dbg);
-            }
+        if let Some(dbg) = self.debug_messenger {
+            destroy_debug_messenger(&Entry::linked(), &self.instance, dbg);
         }
 
         unsafe {
@@ -261,7 +621,87 @@ impl Drop for VkRenderer {
 
         // Device is fully idle, so every trashed resource is now safe to
         // destroy regardless of its retirement value.
-        self.drain_trash();
+        self.destroy_retired(u64::MAX);
+        self.uploader.destroy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        );
+        if let Some(post) = self.post.take() {
+            post.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+                &mut self.descriptors.persistent,
+            );
+        }
+        // Idle, so every copy is retired; the writer finishes what's left.
+        if let Err(e) = self.end_capture(self.timeline_value) {
+            tracing::warn!("vk: capture stopped: {e:#}");
+        }
+        if let Some(picking) = self.picking.take() {
+            picking.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+            );
+        }
+        self.destroy_stereo();
+        if let Some(mut clusters) = self.light_clusters.take() {
+            clusters.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+            );
+        }
+        if let Some(fsr) = self.fsr1.take() {
+            fsr.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+                &mut self.descriptors.persistent,
+            );
+        }
+        if let Some(tm) = self.tonemap.take() {
+            tm.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+                &mut self.descriptors.persistent,
+            );
+        }
+        if let Some(text) = self.text_pass.take() {
+            text.destroy(&self.device);
+        }
+        if let Some((layout, pipeline)) = self.debug_line_pipeline.take() {
+            unsafe {
+                self.device.destroy_pipeline(pipeline, None);
+                self.device.destroy_pipeline_layout(layout, None);
+            }
+        }
+        self.shadow.destroy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        );
+        self.skybox.destroy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            &mut self.descriptors.persistent,
+        );
+        if let Some(mut ibl) = self.ibl.take() {
+            ibl.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+            );
+        }
+        if let Some(block) = self.block_textures.take() {
+            block.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+            );
+        }
+        self.material_pool.destroy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        );
+        self.staging_belt.destroy(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+        );
 
         unsafe {
             let d = &self.device;
@@ -269,8 +709,17 @@ impl Drop for VkRenderer {
             // 3) PIPELINE & LAYOUTS BEFORE SWAPCHAIN (pipelines can depend on sc format)
             d.destroy_pipeline(self.pipeline, None);
             d.destroy_pipeline_layout(self.pipeline_layout, None);
+            d.destroy_pipeline(self.prepass_pipeline, None);
+            d.destroy_pipeline_layout(self.prepass_pipeline_layout, None);
+            for np in self.named_pipelines.drain(..) {
+                d.destroy_pipeline(np.pipeline, None);
+                d.destroy_pipeline_layout(np.layout, None);
+            }
             d.destroy_pipeline(self.indirect_cull_pipeline, None);
             d.destroy_pipeline_layout(self.indirect_cull_pipeline_layout, None);
+            for cp in self.compute_pipelines.drain(..) {
+                cp.destroy(d);
+            }
 
             // 4) IMAGE VIEWS BEFORE SWAPCHAIN (views are created from sc images)
             for &iv in &self.image_views {
@@ -283,13 +732,15 @@ impl Drop for VkRenderer {
             }
             d.destroy_command_pool(self.cmd_pool, None);
 
-            // 6) DESTROY SWAPCHAIN BEFORE DEVICE
+            // 6) DESTROY SWAPCHAIN BEFORE DEVICE (once the presentation
+            // engine has let go of it, where present fences can tell)
+            self.wait_present_fences();
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None);
 
             // 7) DESTROY PER-FRAME SYNCS (render-finished, in-flight) BEFORE DEVICE
             for f in &self.frames {
-                d.destroy_semaphore(f.render_finished, None);
+                f.destroy(d);
             }
             //    Also destroy acquire-slot syncs (sems + fences)
             for s in &self.acq_slots {
@@ -309,6 +760,11 @@ impl Drop for VkRenderer {
             d.destroy_image_view(self.depth_view, None);
             d.destroy_image(self.depth_image, None);
             let _ = allocator.free(std::mem::take(&mut self.depth_alloc));
+            // Render targets' private depth; their other images are in
+            // tex_store, destroyed with the textures below.
+            for target in self.render_targets.drain(..).flatten() {
+                target.destroy(d, &mut allocator);
+            }
 
             // Destroy the shared vertex/index buffers every upload_mesh call
             // bump-allocates from (meshes themselves own no buffers).
@@ -317,6 +773,9 @@ impl Drop for VkRenderer {
             d.destroy_buffer(self.shared_ibuf, None);
             let _ = allocator.free(std::mem::take(&mut self.shared_vbuf_alloc));
             let _ = allocator.free(std::mem::take(&mut self.shared_ibuf_alloc));
+            for buf in self.gpu_buffers.drain(..).flatten() {
+                buf.destroy(d, &mut allocator);
+            }
 
             // Destroy GPU-driven indirect draw resources
             for &b in &self.candidate_bufs {
@@ -325,6 +784,12 @@ impl Drop for VkRenderer {
             for alloc in self.candidate_allocs.drain(..) {
                 let _ = allocator.free(alloc);
             }
+            for &b in &self.bounds_bufs {
+                d.destroy_buffer(b, None);
+            }
+            for alloc in self.bounds_allocs.drain(..) {
+                let _ = allocator.free(alloc);
+            }
             for &b in &self.indirect_bufs {
                 d.destroy_buffer(b, None);
             }
@@ -337,24 +802,10 @@ impl Drop for VkRenderer {
             for alloc in self.draw_count_allocs.drain(..) {
                 let _ = allocator.free(alloc);
             }
-            d.destroy_descriptor_pool(self.indirect_desc_pool, None);
             d.destroy_descriptor_set_layout(self.desc_set_layout_indirect_compute, None);
             d.destroy_descriptor_set_layout(self.desc_set_layout_indirect_graphics, None);
 
-            // Destroy frame resources (gpu-allocator persistently maps
-            // CpuToGpu allocations, so no explicit unmap is needed)
-            for &b in &self.ubufs {
-                self.device.destroy_buffer(b, None);
-            }
-            for alloc in self.umems.drain(..) {
-                let _ = allocator.free(alloc);
-            }
-            self.ubufs.clear();
-            self.ubo_ptrs.clear();
-            self.ubo_size = 0;
-            if self.desc_pool != vk::DescriptorPool::null() {
-                d.destroy_descriptor_pool(self.desc_pool, None);
-            }
+            self.descriptors.destroy(d);
             if self.desc_set_layout_material != vk::DescriptorSetLayout::null() {
                 d.destroy_descriptor_set_layout(self.desc_set_layout_material, None);
             }
@@ -362,18 +813,14 @@ impl Drop for VkRenderer {
                 d.destroy_descriptor_set_layout(self.desc_set_layout_camera, None);
             }
 
-            // Material descriptor pool (set is freed with pool)
-            d.destroy_descriptor_pool(self.material_desc_pool, None);
-
-            // Texture + sampler
-            d.destroy_sampler(self.tex_sampler, None);
+            // Texture + samplers
+            self.samplers.destroy(d);
             d.destroy_image_view(self.tex_view, None);
             d.destroy_image(self.tex_image, None);
             let _ = allocator.free(std::mem::take(&mut self.tex_alloc));
 
             // Uploaded textures (upload_texture)
-            for (image, alloc, view, sampler) in self.tex_store.drain(..) {
-                d.destroy_sampler(sampler, None);
+            for (image, alloc, view, _) in self.tex_store.drain(..) {
                 d.destroy_image_view(view, None);
                 d.destroy_image(image, None);
                 let _ = allocator.free(alloc);
@@ -382,7 +829,7 @@ impl Drop for VkRenderer {
             // Save and destroy pipeline cache
             let props = self.instance.get_physical_device_properties(self.phys);
             let cache_path = pipeline_cache_path(&props);
-            let _ = save_pipeline_cache(d, self.pipeline_cache, &cache_path);
+            let _ = save_pipeline_cache(d, &props, self.pipeline_cache, &cache_path);
             d.destroy_pipeline_cache(self.pipeline_cache, None);
 
             // Explicitly drop the allocator now, while the device is still
@@ -409,6 +856,7 @@ struct SwapchainInitInput<'a> {
     cfg: SwapchainConfig,
     queue_family: u32,
     has_hdr_meta: bool,
+    hdr_metadata: HdrMetadata,
     pipeline_cache: vk::PipelineCache,
     pipeline_cfg: PipelineConfig,
 }
@@ -417,26 +865,115 @@ struct SwapchainInitInput<'a> {
 struct RuntimeConfig {
     vsync: bool,
     vsync_mode: VkVsyncMode,
+    present_priority: PresentPriority,
     hdr: bool,
     hdr_flavor: HdrFlavor,
     allow_extended_colorspace: bool,
+    // Swapchain image usages beyond COLOR_ATTACHMENT (see
+    // SwapchainConfig::extra_usage).
+    swapchain_usage: vk::ImageUsageFlags,
+    // VK_EXT_full_screen_exclusive was enabled on the device (detected at
+    // device creation, like allow_extended_colorspace at instance creation).
+    full_screen_exclusive_ext: bool,
+    // The app asked for exclusive fullscreen (set_exclusive_fullscreen).
+    exclusive_fullscreen: bool,
+    // VK_EXT_swapchain_maintenance1 was enabled on the device: present
+    // modes switch in place where they can, and presents carry fences.
+    swapchain_maintenance1_ext: bool,
+    // Graphics/present families picked at device creation; swapchain
+    // images are shared between them when they differ.
+    queue_families: QueueFamilies,
+    tonemap: TonemapOperator,
+    // Nits of SDR white on HDR swapchains (see set_sdr_white_nits).
+    sdr_white_nits: f32,
+    // sRGB-encode in the tonemap pass on UNORM SDR swapchains (see
+    // tonemap.rs).
+    srgb_encode: bool,
+    // Scene resolution relative to the swapchain, and how it's upscaled
+    // (see upscale.rs).
+    render_scale: f32,
+    upscaler: Upscaler,
+    // Post-processing effects, and the grading LUT's bindless index and
+    // slice size (see post.rs).
+    post: PostChain,
+    post_lut: Option<(u32, u32)>,
+    // Accessibility filter, last in the post chain (see color_filter.rs).
+    color_filter: ColorFilter,
+    // Aspect ratio and size limits on the scene, and its fixed resolution
+    // if any (see letterbox.rs).
+    letterbox: Letterbox,
+    internal_resolution: Option<InternalResolution>,
 }
 impl RuntimeConfig {
-    /// Build from environment (CUBIC_HDR, CUBIC_HDR_FLAVOR), plus a flag
-    /// detected at instance creation time.
-    fn from_env(allow_extended_colorspace: bool) -> Self {
+    /// Build from environment (CUBIC_HDR, CUBIC_HDR_FLAVOR, CUBIC_TONEMAP,
+    /// CUBIC_SDR_WHITE_NITS, CUBIC_SRGB_ENCODE, CUBIC_RENDER_SCALE,
+    /// CUBIC_UPSCALER, CUBIC_POST, CUBIC_COLOR_FILTER),
+    /// plus a flag detected at instance creation time.
+    fn from_env(
+        allow_extended_colorspace: bool,
+        full_screen_exclusive_ext: bool,
+        swapchain_maintenance1_ext: bool,
+        queue_families: QueueFamilies,
+    ) -> Self {
         let hdr = std::env::var("CUBIC_HDR").ok().as_deref() == Some("1");
         let hdr_flavor = match std::env::var("CUBIC_HDR_FLAVOR").ok().as_deref() {
             Some(s) if s.eq_ignore_ascii_case("hdr10") => HdrFlavor::PreferHdr10,
             _ => HdrFlavor::PreferScrgb,
         };
+        let tonemap = std::env::var("CUBIC_TONEMAP")
+            .ok()
+            .and_then(|s| TonemapOperator::from_name(&s))
+            .unwrap_or_default();
+        let sdr_white_nits = std::env::var("CUBIC_SDR_WHITE_NITS")
+            .ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .filter(|n| n.is_finite())
+            .map_or(DEFAULT_SDR_WHITE_NITS, |n| {
+                n.clamp(MIN_SDR_WHITE_NITS, MAX_SDR_WHITE_NITS)
+            });
+        let srgb_encode = std::env::var("CUBIC_SRGB_ENCODE").ok().as_deref() != Some("0");
+        let render_scale = std::env::var("CUBIC_RENDER_SCALE")
+            .ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .filter(|s| s.is_finite())
+            .map_or(1.0, |s| s.clamp(MIN_RENDER_SCALE, 1.0));
+        let upscaler = std::env::var("CUBIC_UPSCALER")
+            .ok()
+            .and_then(|s| Upscaler::from_name(&s))
+            .unwrap_or_default();
+        let post: Vec<PostEffect> = std::env::var("CUBIC_POST")
+            .map(|s| s.split(',').filter_map(PostEffect::from_name).collect())
+            .unwrap_or_default();
+        let color_filter = std::env::var("CUBIC_COLOR_FILTER")
+            .ok()
+            .and_then(|s| ColorFilter::from_name(&s))
+            .unwrap_or_default();
 
         Self {
             vsync: true,
             vsync_mode: VkVsyncMode::Mailbox,
+            present_priority: PresentPriority::default(),
             hdr,
             hdr_flavor,
             allow_extended_colorspace,
+            // Transfer both ways by default so screenshots (copy out) and
+            // post-process blits (copy in) work without a swapchain rebuild;
+            // both are near-universally supported and free on desktop.
+            swapchain_usage: vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST,
+            full_screen_exclusive_ext,
+            exclusive_fullscreen: false,
+            swapchain_maintenance1_ext,
+            queue_families,
+            tonemap,
+            sdr_white_nits,
+            srgb_encode,
+            render_scale,
+            upscaler,
+            post: PostChain::new(&post),
+            post_lut: None,
+            color_filter,
+            letterbox: Letterbox::default(),
+            internal_resolution: None,
         }
     }
 
@@ -446,9 +983,20 @@ impl RuntimeConfig {
             hint,
             vsync: self.vsync,
             vsync_mode: self.vsync_mode,
+            present_priority: self.present_priority,
             want_hdr: self.hdr,
             allow_extended_colorspace: self.allow_extended_colorspace,
             hdr_flavor: self.hdr_flavor,
+            extra_usage: self.swapchain_usage,
+            full_screen_exclusive: self.full_screen_exclusive_ext.then_some(
+                if self.exclusive_fullscreen {
+                    vk::FullScreenExclusiveEXT::ALLOWED
+                } else {
+                    vk::FullScreenExclusiveEXT::DISALLOWED
+                },
+            ),
+            swapchain_maintenance1: self.swapchain_maintenance1_ext,
+            queue_families: self.queue_families,
         }
     }
 }
@@ -457,7 +1005,7 @@ impl RuntimeConfig {
 type SwapchainInit = (
     SwapchainBundle,
     CommandResources,
-    (vk::PipelineLayout, vk::Pipeline),
+    (vk::PipelineLayout, vk::Pipeline, ReflectedLayout),
     Vec<AcquireSlot>,
     Vec<FrameSync>,
 );
@@ -528,14 +1076,10 @@ fn is_surface_lost(e: vk::Result) -> bool {
     e == vk::Result::ERROR_SURFACE_LOST_KHR
 }
 
-#[inline]
-fn is_device_lost(e: vk::Result) -> bool {
-    e == vk::Result::ERROR_DEVICE_LOST
-}
-
 // 8) Orchestration helpers
 fn make_initial_swapchain_resources(inp: &SwapchainInitInput) -> Result<SwapchainInit> {
     let bundle = create_swapchain_bundle(
+        inp.instance,
         inp.device,
         inp.surf_i,
         inp.swap_d,
@@ -551,10 +1095,11 @@ fn make_initial_swapchain_resources(inp: &SwapchainInitInput) -> Result<Swapchai
         inp.has_hdr_meta,
         bundle.color_space,
         bundle.swapchain,
+        &inp.hdr_metadata,
     );
 
     let image_count = bundle.image_views.len();
-    let cmds = create_command_resources(inp.device, inp.queue_family, image_count)?;
+    let cmds = create_command_resources(inp.device, inp.queue_family, FRAMES_IN_FLIGHT)?;
     let pipe = create_pipeline(
         inp.device,
         inp.pipeline_cache,
@@ -562,8 +1107,10 @@ fn make_initial_swapchain_resources(inp: &SwapchainInitInput) -> Result<Swapchai
             color_format: bundle.format,
             ..inp.pipeline_cfg
         },
+        &PipelineDesc::default(),
     )?;
-    let (acq, frames) = create_sync_objects(inp.device, image_count)?;
+    let (acq, frames) =
+        create_sync_objects(inp.device, image_count, inp.cfg.swapchain_maintenance1)?;
     Ok((bundle, cmds, pipe, acq, frames))
 }
 
@@ -571,14 +1118,15 @@ fn build_renderer(
     window: &dyn HasWindowHandle,
     display: &dyn HasDisplayHandle,
     size: RenderSize,
+    gpu: Option<&str>,
+    layers: ValidationLayers,
+    carried_cfg: Option<RuntimeConfig>,
 ) -> Result<VkRenderer> {
-    // 1) Instance + surface (and record whether colorspace ext exists)
-    #[cfg(debug_assertions)]
-    let (entry, instance, surface_loader, surface, debug_state, have_swapchain_colorspace_ext) =
-        init_instance_and_surface(window, display)?;
-    #[cfg(not(debug_assertions))]
-    let (entry, instance, surface_loader, surface, _debug_state, have_swapchain_colorspace_ext) =
-        init_instance_and_surface(window, display)?;
+    // 1) Instance + surface (and record which optional extensions exist)
+    let validation = Box::new(ValidationState::from_env());
+    let (entry, instance, surface_loader, surface, debug_messenger, inst_features) =
+        init_instance_and_surface(window, display, layers, &validation)?;
+    let have_swapchain_colorspace_ext = inst_features.swapchain_colorspace;
 
     let display_raw = display
         .display_handle()
@@ -586,15 +1134,48 @@ fn build_renderer(
         .as_raw();
     let window_raw = window.window_handle().map_err(|e| anyhow!("{e}"))?.as_raw();
 
-    // 2) Pick device/queue family
-    let (phys, queue_family) = select_device_and_queue(&instance, &surface_loader, surface)?;
+    // 2) Pick device/queue families (+ a separate transfer family if any)
+    let (phys, families) = select_device_and_queue(&instance, &surface_loader, surface, gpu)?;
+    let queue_family = families.graphics;
+    let transfer_family = find_transfer_queue_family(&instance, phys, queue_family);
+    if families.separate_present() {
+        info!(
+            "vk: presenting from queue family {}, separate from graphics family {}",
+            families.present, families.graphics
+        );
+    }
 
     // 3) Create device + choose render path, detect HDR metadata support
-    let (device, queue, path, has_hdr_meta) =
-        decide_path_and_create_device(&entry, &instance, phys, queue_family)?;
+    let (
+        device,
+        queue,
+        present_queue,
+        transfer_queue,
+        path,
+        has_hdr_meta,
+        has_present_wait,
+        has_fse,
+        has_sm1,
+        has_device_fault,
+        has_checkpoints,
+        has_multiview,
+    ) = decide_path_and_create_device(&entry, &instance, phys, families, transfer_family)?;
+    let present_wait =
+        has_present_wait.then(|| ash::khr::present_wait::Device::new(&instance, &device));
+    let uploader = match (transfer_queue, transfer_family) {
+        (Some(tq), Some(tf)) => {
+            info!("vk: mesh uploads on dedicated transfer queue family {tf}");
+            TransferUploader::new(&device, tq, tf)?
+        }
+        _ => TransferUploader::new(&device, queue, queue_family)?,
+    };
+    // Families that touch the shared mesh buffers (CONCURRENT if > 1).
+    let mesh_families: Vec<u32> = std::iter::once(queue_family)
+        .chain(transfer_family)
+        .collect();
     let props = unsafe { instance.get_physical_device_properties(phys) };
     let cache_path = pipeline_cache_path(&props);
-    let pipeline_cache = create_or_load_pipeline_cache(&device, &cache_path)?;
+    let pipeline_cache = create_or_load_pipeline_cache(&device, &props, &cache_path)?;
 
     // 3b) GPU memory sub-allocator, replaces raw vkAllocateMemory/vkFreeMemory
     let mut allocator = Allocator::new(&AllocatorCreateDesc {
@@ -602,7 +1183,10 @@ fn build_renderer(
         device: device.clone(),
         physical_device: phys,
         debug_settings: Default::default(),
-        buffer_device_address: false,
+        // The mesh pools below are read through their device addresses by
+        // vertex-pulling pipelines, so their memory needs the DEVICE_ADDRESS
+        // allocate flag.
+        buffer_device_address: true,
         allocation_sizes: Default::default(),
     })?;
 
@@ -613,8 +1197,18 @@ fn build_renderer(
     // 4) WSI device wrapper
     let swapchain_loader = ash::khr::swapchain::Device::new(&instance, &device);
 
-    // 5) Initial runtime knobs
-    let initial_cfg = RuntimeConfig::from_env(have_swapchain_colorspace_ext);
+    // 5) Initial runtime knobs (or the previous renderer's, when rebuilding
+    // after device loss — the instance is new, so re-check the extension)
+    let initial_cfg = match carried_cfg {
+        Some(c) => RuntimeConfig {
+            allow_extended_colorspace: have_swapchain_colorspace_ext,
+            full_screen_exclusive_ext: has_fse,
+            swapchain_maintenance1_ext: has_sm1,
+            queue_families: families,
+            ..c
+        },
+        None => RuntimeConfig::from_env(have_swapchain_colorspace_ext, has_fse, has_sm1, families),
+    };
     let cfg = initial_cfg.to_swapchain_config(size);
     #[cfg(debug_assertions)]
     let shader_dev = {
@@ -631,6 +1225,8 @@ fn build_renderer(
                     frag_spv: fp,
                     vert_mtime: vm,
                     frag_mtime: fm,
+                    #[cfg(feature = "runtime-shader-compile")]
+                    glsl_sources: shader_compile::watched_glsl_sources(&dir),
                 })
             } else {
                 None
@@ -643,18 +1239,29 @@ fn build_renderer(
     // Create depth buffers
     let depth_format = pick_depth_format(&instance, phys);
     let desc_set_layout_camera = create_camera_desc_set_layout(&device)?;
-    let desc_set_layout_material = create_material_desc_set_layout(&device)?;
+    let tex_caps = query_texture_array_caps(&instance, phys);
+    info!(
+        "vk: bindless texture array: {} slots, update-after-bind {}",
+        tex_caps.capacity,
+        if tex_caps.update_after_bind {
+            "on"
+        } else {
+            "off"
+        }
+    );
+    let desc_set_layout_material = create_material_desc_set_layout(&device, tex_caps)?;
     let desc_set_layout_indirect_compute = create_indirect_compute_desc_set_layout(&device)?;
     let desc_set_layout_indirect_graphics = create_indirect_graphics_desc_set_layout(&device)?;
 
-    // GPU-driven indirect draw: a no-real-culling-yet compute shader that
-    // expands this frame's candidate list into VkDrawIndexedIndirectCommand
-    // entries (see indirect_cull.comp).
+    // GPU-driven indirect draw: a compute shader that frustum-culls this
+    // frame's candidate list against per-mesh bounding spheres and compacts
+    // the survivors into VkDrawIndexedIndirectCommand entries (see
+    // indirect_cull.comp).
     let indirect_cull_pipeline_layout = unsafe {
         let push_range = vk::PushConstantRange {
             stage_flags: vk::ShaderStageFlags::COMPUTE,
             offset: 0,
-            size: std::mem::size_of::<u32>() as u32, // candidate_count
+            size: std::mem::size_of::<CullPush>() as u32,
         };
         let layouts = [desc_set_layout_indirect_compute];
         let ci = vk::PipelineLayoutCreateInfo {
@@ -675,6 +1282,10 @@ fn build_renderer(
         &indirect_cull_words,
     )?;
 
+    // HDR10 metadata from the display's EDID where we can read it; the
+    // generic BT.2020/1000-nit values otherwise.
+    let display_hdr_metadata = detect_display_hdr_metadata().unwrap_or_default();
+
     // 6) Build all swapchain-scoped resources in one place
     let init_inp = SwapchainInitInput {
         device: &device,
@@ -686,6 +1297,7 @@ fn build_renderer(
         cfg,
         queue_family,
         has_hdr_meta,
+        hdr_metadata: display_hdr_metadata,
         pipeline_cache,
         pipeline_cfg: PipelineConfig {
             color_format: vk::Format::UNDEFINED, // filled in from swapchain in make_initial_swapchain_resources
@@ -693,10 +1305,15 @@ fn build_renderer(
             set_layout_camera: desc_set_layout_camera,
             set_layout_material: desc_set_layout_material,
             set_layout_indirect_graphics: desc_set_layout_indirect_graphics,
+            tex_caps,
         },
     };
-    let (sc, cmd, (pipeline_layout, pipeline), acq_slots, frames) =
+    let (sc, cmd, (pipeline_layout, pipeline, pipeline_reflected), acq_slots, frames) =
         make_initial_swapchain_resources(&init_inp)?;
+    // No colour attachment, so the still-UNDEFINED colour format in
+    // init_inp's config doesn't matter here.
+    let (prepass_pipeline_layout, prepass_pipeline) =
+        create_depth_prepass_pipeline(&device, pipeline_cache, &init_inp.pipeline_cfg)?;
 
     let egui_renderer = Some(egui_overlay::build_egui_renderer(
         &instance,
@@ -710,72 +1327,125 @@ fn build_renderer(
     let (depth_image, depth_alloc, depth_view) =
         create_depth_resources(&device, &mut allocator, sc.extent, depth_format)?;
 
-    // Shared vertex/index buffers every upload_mesh call bump-allocates
-    // from (see GpuMesh).
-    let (shared_vbuf, shared_vbuf_alloc) = create_buffer_and_memory(
+    // Shared vertex/index buffers every upload_mesh call sub-allocates
+    // from (see GpuMesh). Bound once per pass as vertex/index buffers, and
+    // also addressable from shaders (see VkRenderer::mesh_pool_addresses).
+    let pool_usage = vk::BufferUsageFlags::TRANSFER_DST
+        | vk::BufferUsageFlags::STORAGE_BUFFER
+        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+    let (shared_vbuf, shared_vbuf_alloc) = create_buffer_and_memory_shared(
         &device,
         &mut allocator,
         MAX_SHARED_VERTICES * std::mem::size_of::<Vertex>() as u64,
-        vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+        vk::BufferUsageFlags::VERTEX_BUFFER | pool_usage,
         MemoryLocation::GpuOnly,
         "shared mesh vertex buffer",
+        &mesh_families,
     )?;
-    let (shared_ibuf, shared_ibuf_alloc) = create_buffer_and_memory(
+    let (shared_ibuf, shared_ibuf_alloc) = create_buffer_and_memory_shared(
         &device,
         &mut allocator,
         MAX_SHARED_INDICES * std::mem::size_of::<u32>() as u64,
-        vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+        vk::BufferUsageFlags::INDEX_BUFFER | pool_usage,
         MemoryLocation::GpuOnly,
         "shared mesh index buffer",
+        &mesh_families,
     )?;
+    let pool_address = |buffer| {
+        let info = vk::BufferDeviceAddressInfo {
+            s_type: vk::StructureType::BUFFER_DEVICE_ADDRESS_INFO,
+            buffer,
+            ..Default::default()
+        };
+        unsafe { device.get_buffer_device_address(&info) }
+    };
+    let mesh_pool_addresses = [pool_address(shared_vbuf), pool_address(shared_ibuf)];
+
+    let staging_belt = StagingBelt::new(&instance, phys, &device, &mut allocator)?;
 
-    // Global material set (swapchain-invariant)
-    let (material_desc_pool, material_desc_set) =
-        create_material_desc_pool_and_set(&device, desc_set_layout_material)?;
+    // Material sets (swapchain-invariant), starting with the default
+    // material's at slot 0.
+    let mut material_pool = MaterialPool::new(&instance, phys, desc_set_layout_material, tex_caps);
+    let default_slot = material_pool.alloc(&device, &mut allocator, &MaterialDesc::default(), 0)?;
+    let material_desc_set = material_pool.set(default_slot);
 
     // Sensible default until cubic-app's configure_advanced() pushes the
     // real cubic.toml values via set_sampler_config() — see the field doc
     // on VkRenderer::sampler_config. Only the dummy texture below ever
     // actually uses this default, since configure_advanced() always runs
     // before any real texture is uploaded.
-    let sampler_config = SamplerConfig {
-        mag_filter: vk::Filter::LINEAR,
-        min_filter: vk::Filter::LINEAR,
-        mipmap_mode: vk::SamplerMipmapMode::LINEAR,
-        max_anisotropy: 0.0,
-        lod_bias: 0.0,
-    };
+    let sampler_config = SamplerDesc::default();
+    let mut samplers = SamplerCache::new(&instance, phys);
 
     // Tiny 2×2 texture and sampler, registered at bindless index 0 (the
     // fallback every draw uses until real texture loading exists).
-    let (tex_image, tex_alloc, tex_view, tex_sampler) = create_dummy_texture_and_sampler(
+    let mip_gen = pick_mip_gen(&instance, phys);
+    let (tex_image, tex_alloc, tex_view) =
+        create_dummy_texture(&device, &mut allocator, queue, cmd.pool, mip_gen)?;
+    let tex_sampler = samplers.get(&device, &sampler_config)?;
+    material_pool.write_texture(&device, 0, tex_view, tex_sampler);
+
+    let mut descriptors = Descriptors::new();
+    let light_clusters = match LightClusters::new(
         &device,
         &mut allocator,
-        queue,
-        cmd.pool,
-        &sampler_config,
+        &mut descriptors.persistent,
+        pipeline_cache,
+        staging_belt.buffer(),
+    ) {
+        Ok(clusters) => Some(clusters),
+        Err(e) => {
+            tracing::warn!("vk: light clusters unavailable, shading every light: {e:#}");
+            None
+        }
+    };
+    let (camera_set, shadow_camera_set) = create_camera_sets(
+        &device,
+        &mut descriptors.persistent,
+        desc_set_layout_camera,
+        staging_belt.buffer(),
+        light_clusters.as_ref().map(LightClusters::buffer_info),
     )?;
-    write_material_descriptors(&device, material_desc_set, 0, tex_view, tex_sampler);
 
-    let (ubufs, umems, ubo_ptrs, ubo_size, desc_pool, desc_sets) = create_frame_uniforms_and_sets(
-        &instance,
+    // Shadow pipeline + placeholder map; set_shadow_settings sizes the real
+    // map once shadows are turned on.
+    let shadow = ShadowPass::new(
         &device,
-        phys,
         &mut allocator,
-        desc_set_layout_camera,
-        sc.image_views.len(),
+        pipeline_cache,
+        &init_inp.pipeline_cfg,
+        pick_shadow_format(&instance, phys),
     )?;
+    let skybox = SkyboxPass::new(&device)?;
+    let ibl = match IblPass::new(
+        &device,
+        &mut allocator,
+        &mut descriptors.persistent,
+        pipeline_cache,
+        queue,
+        cmd.pool,
+    ) {
+        Ok(ibl) => Some(ibl),
+        Err(e) => {
+            tracing::warn!("vk: image-based lighting unavailable: {e:#}");
+            None
+        }
+    };
 
     let indirect = create_indirect_draw_resources(
         &device,
         &mut allocator,
+        &mut descriptors.persistent,
         desc_set_layout_indirect_compute,
         desc_set_layout_indirect_graphics,
-        sc.image_views.len(),
+        FRAMES_IN_FLIGHT,
     )?;
 
     // 7) Assemble VkRenderer
-    let r = VkRenderer {
+    let debug_labels = DebugLabels::new(&instance, &device, inst_features.debug_utils);
+    let crash_diag = CrashDiagnostics::new(&instance, &device, has_device_fault, has_checkpoints);
+    let has_memory_budget = memory::has_memory_budget(&instance, phys);
+    let mut r = VkRenderer {
         instance,
         surface_loader,
         surface,
@@ -783,18 +1453,48 @@ fn build_renderer(
         phys,
         device,
         queue,
+        present_queue,
         allocator: Some(allocator),
 
         swapchain_loader,
         swapchain: sc.swapchain,
         format: sc.format,
         extent: sc.extent,
+        color_space: sc.color_space,
+        present_mode: sc.present_mode,
+        swapchain_usage: sc.usage,
+        pre_transform: sc.pre_transform,
+        present_modes: sc.present_modes,
+        surface_change: None,
 
         images: sc.images,
         image_views: sc.image_views,
+        tonemap: None,
+        fsr1: None,
+        post: None,
+        picking: None,
+        stereo: None,
+        capture: None,
+        text_pass: None,
+        text_font: None,
+        text_font_failed: false,
+        text_vertices: Vec::new(),
+        debug_line_pipeline: None,
+        debug_lines: Vec::new(),
+        debug_lines_dropped: 0,
 
         pipeline,
         pipeline_layout,
+        pipeline_reflected,
+        prepass_pipeline_layout,
+        prepass_pipeline,
+        depth_prepass: false,
+        named_pipelines: Vec::new(),
+        compute_pipelines: Vec::new(),
+        gpu_buffers: Vec::new(),
+        compute_bindings: Vec::new(),
+        pending_compute: Vec::new(),
+        mesh_families,
         cmd_pool: cmd.pool,
         cmd_bufs: cmd.bufs,
 
@@ -805,13 +1505,17 @@ fn build_renderer(
             },
         },
         paused: false,
+        suspended: None,
+        pending_resize: None,
+        resized_at: std::time::Instant::now(),
         path,
 
-        #[cfg(debug_assertions)]
-        debug_messenger: debug_state,
+        debug_messenger,
         acq_slots,
         acq_index: 0,
         has_hdr_metadata_ext: has_hdr_meta,
+        hdr_metadata: display_hdr_metadata,
+        display_hdr_metadata,
         cfg: initial_cfg,
         camera: Camera::default(),
         depth_image,
@@ -822,31 +1526,36 @@ fn build_renderer(
         shared_vbuf_alloc,
         shared_ibuf,
         shared_ibuf_alloc,
+        mesh_pool_addresses,
         vert_alloc: RangeAlloc::new(MAX_SHARED_VERTICES as u32),
         idx_alloc: RangeAlloc::new(MAX_SHARED_INDICES as u32),
         meshes: Vec::new(),
+        vertex_layouts: vec![VertexLayout::standard()],
+        uploader,
+        staging_belt,
         pending_draws: Vec::new(),
         trash: Vec::new(),
-        desc_pool,
+        descriptors,
         desc_set_layout_camera,
         desc_set_layout_material,
         desc_set_layout_indirect_graphics,
         desc_set_layout_indirect_compute,
-        desc_sets,
-        ubufs,
-        umems,
-        ubo_ptrs,
-        ubo_size,
+        camera_set,
+        shadow_camera_set,
+        frame_uniforms: FrameUniformOffsets::default(),
         indirect_cull_pipeline,
         indirect_cull_pipeline_layout,
         candidate_bufs: indirect.candidate_bufs,
         candidate_allocs: indirect.candidate_allocs,
         candidate_ptrs: indirect.candidate_ptrs,
+        bounds_bufs: indirect.bounds_bufs,
+        bounds_allocs: indirect.bounds_allocs,
+        bounds_ptrs: indirect.bounds_ptrs,
+        gpu_culling: true,
         indirect_bufs: indirect.indirect_bufs,
         indirect_allocs: indirect.indirect_allocs,
         draw_count_bufs: indirect.draw_count_bufs,
         draw_count_allocs: indirect.draw_count_allocs,
-        indirect_desc_pool: indirect.desc_pool,
         indirect_compute_desc_sets: indirect.compute_desc_sets,
         indirect_graphics_desc_sets: indirect.graphics_desc_sets,
         pipeline_cache,
@@ -857,29 +1566,129 @@ fn build_renderer(
         backoff_frames: 0,
         #[cfg(debug_assertions)]
         shader_dev,
-        material_desc_pool,
+        material_pool,
+        materials: vec![Some(Material::default_at(default_slot))],
         material_desc_set,
         tex_image,
         tex_alloc,
         tex_view,
         tex_sampler,
+        samplers,
+        tex_caps,
         next_tex_index: 1,
         tex_store: Vec::new(),
+        tex_sources: Vec::new(),
+        pending_textures: Vec::new(),
+        resident_textures: Vec::new(),
+        render_targets: Vec::new(),
+        viewports: Vec::new(),
+        viewport_views: Vec::new(),
         sampler_config,
+        mip_gen,
         egui_renderer,
         egui_pending: None,
+        egui_textures: Default::default(),
+        gpu_request: gpu.map(str::to_owned),
+        pacer: FramePacer::new(),
+        frame_stats: FrameStatsTracker::default(),
+        validation,
+        validation_layers: inst_features.validation,
+        latency_mode: LatencyMode::Throughput,
+        present_wait,
+        present_id: 0,
+        debug_labels,
+        crash_diag,
+        pending_markers: Vec::new(),
+        has_memory_budget,
+        memory_log_at: std::time::Instant::now(),
+        has_multiview,
+        light: DirectionalLight::default(),
+        shadow_settings: ShadowSettings::default(),
+        lights: Vec::new(),
+        light_clusters,
+        shadow,
+        skybox,
+        ibl,
+        block_textures: None,
     };
+    r.write_shadow_descriptors(r.camera_sets());
+
+    // 8) HDR tonemap pass, if the swapchain came up HDR. Needs the
+    // assembled renderer (allocator, extent, colour space); the scene
+    // pipelines built above targeted the swapchain format, so rebuild them
+    // against the FP16 target if the pass is on.
+    r.sync_tonemap_pass();
+    if r.tonemap.is_some() {
+        r.rebuild_scene_pipelines()?;
+    }
+    r.sync_text_pass();
+    r.sync_debug_line_pipeline();
+    r.sync_skybox_pipeline();
+    r.name_objects();
 
     Ok(r)
 }
 
 impl VkRenderer {
+    /// `Renderer::new` with an explicit GPU choice: an index from the
+    /// startup device list, a name substring, or discrete/integrated/cpu.
+    /// None leaves it to CUBIC_GPU, then to device scoring (discrete first).
+    pub fn new_with_gpu(
+        window: &dyn HasWindowHandle,
+        display: &dyn HasDisplayHandle,
+        size: RenderSize,
+        gpu: Option<&str>,
+    ) -> RenderResult<Self> {
+        let layers = ValidationLayers::from_env().unwrap_or_default();
+        Self::new_with_validation(window, display, size, gpu, layers)
+    }
+
+    /// `new_with_gpu` with the validation checks to run, in place of
+    /// CUBIC_VALIDATION and the build's default. Checks whose layer or
+    /// extension isn't installed are skipped with a warning.
+    pub fn new_with_validation(
+        window: &dyn HasWindowHandle,
+        display: &dyn HasDisplayHandle,
+        size: RenderSize,
+        gpu: Option<&str>,
+        validation: ValidationLayers,
+    ) -> RenderResult<Self> {
+        build_renderer(window, display, size, gpu, validation, None).map_err(classify)
+    }
+
     // Set cfg options
     pub fn set_vsync_mode(&mut self, mode: VkVsyncMode) {
-        if self.cfg.vsync_mode as u8 == mode as u8 {
+        if self.cfg.vsync_mode == mode {
             return;
         }
         self.cfg.vsync_mode = mode;
+        self.apply_present_mode();
+    }
+    /// Present modes to try, in order, while vsync is on, ahead of
+    /// set_vsync_mode's own choice and its fallbacks; the first the surface
+    /// supports wins. Repeats are ignored; empty (the default) leaves it to
+    /// the vsync mode alone. Vsync off still means IMMEDIATE first.
+    pub fn set_present_mode_priority(&mut self, modes: &[VkVsyncMode]) {
+        let priority = PresentPriority::new(modes);
+        if self.cfg.present_priority == priority {
+            return;
+        }
+        self.cfg.present_priority = priority;
+        self.apply_present_mode();
+    }
+    /// Exclusive (true) or composited (false) fullscreen, for a window the
+    /// app has made fullscreen. With VK_EXT_full_screen_exclusive (Windows
+    /// drivers) this recreates the swapchain allowing or disallowing
+    /// exclusive mode; elsewhere there's nothing to switch and the window
+    /// system's own fullscreen handling is all there is.
+    pub fn set_exclusive_fullscreen(&mut self, on: bool) {
+        if self.cfg.exclusive_fullscreen == on {
+            return;
+        }
+        self.cfg.exclusive_fullscreen = on;
+        if !self.cfg.full_screen_exclusive_ext {
+            return;
+        }
         let want = RenderSize {
             width: self.extent.width,
             height: self.extent.height,
@@ -909,44 +1718,272 @@ impl VkRenderer {
         let _ = self.recreate_swapchain(want);
     }
 
+    /// Override the HDR10 metadata (primaries, white point, luminance)
+    /// reported to the display, e.g. from a user calibration. Applied to
+    /// the live swapchain right away if it's HDR10; no recreation needed.
+    /// Also moves the tonemap pass's peak to `max_luminance`.
+    pub fn set_hdr_metadata(&mut self, metadata: HdrMetadata) {
+        if self.hdr_metadata == metadata {
+            return;
+        }
+        self.hdr_metadata = metadata;
+        // Suspended: resume's recreate_swapchain applies it.
+        if self.swapchain == vk::SwapchainKHR::null() {
+            return;
+        }
+        create_hdr_metadata_if_needed(
+            &self.instance,
+            &self.device,
+            self.has_hdr_metadata_ext,
+            self.color_space,
+            self.swapchain,
+            &self.hdr_metadata,
+        );
+    }
+
+    /// HDR10 metadata currently in effect.
+    pub fn hdr_metadata(&self) -> HdrMetadata {
+        self.hdr_metadata
+    }
+
+    /// HDR10 metadata detected from the display at startup (or the generic
+    /// default); the base a partial user calibration is layered onto.
+    pub fn display_hdr_metadata(&self) -> HdrMetadata {
+        self.display_hdr_metadata
+    }
+
+    /// Ask for extra swapchain image usages (TRANSFER_SRC/DST, STORAGE,
+    /// SAMPLED) on top of COLOR_ATTACHMENT, recreating the swapchain if the
+    /// request changed. Unsupported bits are dropped with a warning; see
+    /// `swapchain_usage()` for what was granted.
+    pub fn set_swapchain_usage(&mut self, usage: vk::ImageUsageFlags) {
+        let usage = usage & !vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        if self.cfg.swapchain_usage == usage {
+            return;
+        }
+        self.cfg.swapchain_usage = usage;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    /// Usage flags the current swapchain images were created with.
+    pub fn swapchain_usage(&self) -> vk::ImageUsageFlags {
+        self.swapchain_usage
+    }
+
+    /// Maintenance: forget every compiled pipeline the cache holds, in
+    /// memory and on disk (see pipeline_cache_path), e.g. after a driver
+    /// bug that a stale cache entry keeps reproducing. Live pipelines are
+    /// unaffected; anything built afterwards compiles from scratch once.
+    pub fn clear_pipeline_cache(&mut self) -> RenderResult<()> {
+        let props = unsafe { self.instance.get_physical_device_properties(self.phys) };
+        let path = pipeline_cache_path(&props);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("remove {:?}: {e}", path).into()),
+        }
+        let ci = vk::PipelineCacheCreateInfo {
+            s_type: vk::StructureType::PIPELINE_CACHE_CREATE_INFO,
+            ..Default::default()
+        };
+        let empty = unsafe {
+            self.device
+                .create_pipeline_cache(&ci, None)
+                .map_err(|e| vk_error(e, "create_pipeline_cache"))?
+        };
+        // A cache is only read while a pipeline is being created, never by
+        // the pipelines themselves, so the old one can go right away.
+        let old = std::mem::replace(&mut self.pipeline_cache, empty);
+        unsafe { self.device.destroy_pipeline_cache(old, None) };
+        info!("vk: pipeline cache cleared ({:?})", path);
+        Ok(())
+    }
+
+    /// One-line description of the live swapchain (format, colour space,
+    /// present mode, image count, tonemap operator if active, letterbox if
+    /// any, scene size and upscaler if scaled, post effects if any), for the
+    /// debug overlay.
+    pub fn present_summary(&self) -> String {
+        let mut s = format!(
+            "{} / {}, {}, {} images",
+            swapchain::fmt_name(self.format),
+            swapchain::cs_name(self.color_space),
+            swapchain::pm_name(self.present_mode),
+            self.images.len()
+        );
+        match &self.tonemap {
+            Some(tm) if tm.encodes_srgb() => s.push_str(", sRGB encode"),
+            Some(tm) if tm.passthrough() => {}
+            Some(_) => s.push_str(&format!(", tonemap {}", self.cfg.tonemap.name())),
+            None => {}
+        }
+        let scene = self.scene_extent();
+        let output = self.output_area().extent;
+        if self.tonemap.is_some() && output != self.extent {
+            s.push_str(&format!(", letterbox {}x{}", output.width, output.height));
+        }
+        let internal = self
+            .cfg
+            .internal_resolution
+            .filter(|_| self.tonemap.is_some());
+        if internal.is_some() || scene != output {
+            let filter = match internal {
+                Some(internal) => internal.scaling.name(),
+                None if self.fsr1.is_some() => Upscaler::Fsr1.name(),
+                None if self.cfg.upscaler == Upscaler::Nearest => Upscaler::Nearest.name(),
+                None => Upscaler::Bilinear.name(),
+            };
+            s.push_str(&format!(
+                ", scene {}x{} ({filter})",
+                scene.width, scene.height
+            ));
+        }
+        if self.post.is_some() {
+            let mut names: Vec<_> = self.cfg.post.as_slice().iter().map(|e| e.name()).collect();
+            if self.cfg.color_filter != ColorFilter::Off {
+                names.push(self.cfg.color_filter.name());
+            }
+            s.push_str(&format!(", post {}", names.join(" > ")));
+        }
+        s
+    }
+
     pub fn set_camera(&mut self, camera: Camera) {
         self.camera = camera;
     }
 
+    /// The swapchain's size as the window sees it: `extent` turned back
+    /// upright when the swapchain is pre-rotated a quarter turn.
+    pub(crate) fn view_extent(&self) -> vk::Extent2D {
+        if swapchain::transform_swaps_axes(self.pre_transform) {
+            vk::Extent2D {
+                width: self.extent.height,
+                height: self.extent.width,
+            }
+        } else {
+            self.extent
+        }
+    }
+
+    /// Aspect ratio for the camera's projection: the scene's, which is the
+    /// window's unless letterboxed.
+    pub(crate) fn view_aspect(&self) -> f32 {
+        let scene = self.scene_extent();
+        let (w, h) = if swapchain::transform_swaps_axes(self.pre_transform) {
+            (scene.height, scene.width)
+        } else {
+            (scene.width, scene.height)
+        };
+        w as f32 / h.max(1) as f32
+    }
+
+    /// `camera`'s rotation-only view-projection for the swapchain as it is,
+    /// pre-rotation included.
+    pub(crate) fn camera_view_proj(&self, camera: &Camera) -> Mat4 {
+        swapchain::pre_rotation(self.pre_transform)
+            * clip::camera_view_proj(camera, self.view_aspect(), DepthConvention::ReverseZ)
+    }
+
     /// Upload vertex/index data into the shared buffers via bump allocation
     /// and return an opaque handle. All meshes share one vertex buffer and
     /// one index buffer so the entire scene can be drawn with one
     /// cmd_draw_indexed_indirect_count call (GPU-driven indirect path).
-    pub fn upload_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<MeshHandle> {
-        let vc = vertices.len() as u32;
-        let ic = indices.len() as u32;
+    ///
+    /// Doesn't block: the copies are batched onto the transfer queue and
+    /// submitted with the next render(), whose draws wait for them on the
+    /// GPU, so the handle is drawable immediately.
+    pub fn upload_mesh(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> RenderResult<MeshHandle> {
+        Ok(self.upload_mesh_bytes(
+            0,
+            vertices.len() as u32,
+            bytemuck::cast_slice(vertices),
+            indices,
+            clip::bounding_sphere(vertices).unwrap_or(UNBOUNDED),
+        )?)
+    }
 
-        let vstart = self
+    /// `upload_mesh` for vertices in a custom layout (extra normal/tangent/
+    /// skinning attributes, packed formats, ...): `vertex_bytes` is tightly
+    /// packed vertices of `layout.stride` bytes each. The mesh can only be
+    /// drawn with a pipeline registered with an equal
+    /// `PipelineDesc::vertex_layout`, never with the default pipeline
+    /// (unless `layout` is `VertexLayout::standard()`). The renderer can't
+    /// tell which attribute is the position, so the mesh is never frustum
+    /// culled until it gets a `set_mesh_bounds`.
+    pub fn upload_mesh_with_layout(
+        &mut self,
+        layout: &VertexLayout,
+        vertex_bytes: &[u8],
+        indices: &[u32],
+    ) -> RenderResult<MeshHandle> {
+        let layout_index = self.intern_vertex_layout(layout)?;
+        let stride = layout.stride as usize;
+        if !vertex_bytes.len().is_multiple_of(stride) {
+            return Err(anyhow!(
+                "upload_mesh_with_layout: {} bytes isn't a whole number of {stride}-byte vertices",
+                vertex_bytes.len()
+            )
+            .into());
+        }
+        let vc = (vertex_bytes.len() / stride) as u32;
+        Ok(self.upload_mesh_bytes(layout_index, vc, vertex_bytes, indices, UNBOUNDED)?)
+    }
+
+    fn upload_mesh_bytes(
+        &mut self,
+        layout: u32,
+        vc: u32,
+        vertex_bytes: &[u8],
+        indices: &[u32],
+        bounds: [f32; 4],
+    ) -> Result<MeshHandle> {
+        let ic = indices.len() as u32;
+        let unit = std::mem::size_of::<Vertex>() as u64;
+        let stride = self.vertex_layouts[layout as usize].stride as u64;
+
+        // Allocator slots are Vertex-sized; a mesh with another stride also
+        // needs up to stride-1 bytes of slack to start on a stride multiple
+        // (none if every slot boundary already is one).
+        let slack = if unit.is_multiple_of(stride) {
+            0
+        } else {
+            stride - 1
+        };
+        let slot_len = (vc as u64 * stride + slack).div_ceil(unit) as u32;
+        let slot_start = self
             .vert_alloc
-            .alloc(vc)
+            .alloc(slot_len)
             .ok_or_else(|| anyhow!("upload_mesh: shared vertex buffer full"))?;
-        let istart = self
-            .idx_alloc
-            .alloc(ic)
-            .ok_or_else(|| anyhow!("upload_mesh: shared index buffer full"))?;
+        let istart = match self.idx_alloc.alloc(ic) {
+            Some(start) => start,
+            None => {
+                self.vert_alloc.free(slot_start, slot_len);
+                return Err(anyhow!("upload_mesh: shared index buffer full"));
+            }
+        };
 
-        let vbyte_offset = vstart as u64 * std::mem::size_of::<Vertex>() as u64;
+        let vbyte_offset = (slot_start as u64 * unit).next_multiple_of(stride);
         let ibyte_offset = istart as u64 * std::mem::size_of::<u32>() as u64;
 
-        upload_via_staging(
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        self.uploader.queue_buffer_copy(
             &self.device,
-            self.allocator.as_mut().expect("allocator missing"),
-            self.queue,
-            self.cmd_pool,
+            allocator,
             self.shared_vbuf,
             vbyte_offset,
-            bytemuck::cast_slice(vertices),
+            vertex_bytes,
         )?;
-        upload_via_staging(
+        self.uploader.queue_buffer_copy(
             &self.device,
-            self.allocator.as_mut().expect("allocator missing"),
-            self.queue,
-            self.cmd_pool,
+            allocator,
             self.shared_ibuf,
             ibyte_offset,
             bytemuck::cast_slice(indices),
@@ -954,20 +1991,328 @@ impl VkRenderer {
 
         let handle = MeshHandle(self.meshes.len() as u32);
         self.meshes.push(GpuMesh {
-            first_vertex: vstart as i32,
+            first_vertex: (vbyte_offset / stride) as i32,
             first_index: istart,
             index_count: ic,
             vertex_count: vc,
+            slot_start,
+            slot_len,
+            layout,
+            bounds,
         });
         Ok(handle)
     }
 
+    /// Byte range of a live mesh's vertices in the shared vertex buffer.
+    pub(crate) fn mesh_vertex_bytes(&self, handle: MeshHandle) -> Option<(u64, u64)> {
+        let mesh = self.meshes.get(handle.0 as usize)?;
+        if mesh.first_vertex < 0 {
+            return None;
+        }
+        let stride = self.vertex_layouts[mesh.layout as usize].stride as u64;
+        Some((
+            mesh.first_vertex as u64 * stride,
+            mesh.vertex_count as u64 * stride,
+        ))
+    }
+
+    /// Override a mesh's bounding sphere (mesh space: the same space its
+    /// vertex positions are in before PushData::model). Draws whose sphere,
+    /// after the draw's model matrix, lies entirely outside the camera
+    /// frustum are dropped: by the cull pass on the default pipeline, on
+    /// the CPU otherwise.
+    /// upload_mesh already computes a sphere; this is for custom-layout
+    /// meshes, or vertex shaders that displace geometry past the uploaded
+    /// positions. A negative radius opts the mesh out of culling.
+    pub fn set_mesh_bounds(&mut self, handle: MeshHandle, center: [f32; 3], radius: f32) {
+        if let Some(mesh) = self.meshes.get_mut(handle.0 as usize) {
+            mesh.bounds = [center[0], center[1], center[2], radius];
+        }
+    }
+
+    /// Toggle frustum culling (on by default): on the GPU for
+    /// default-pipeline draws, on the CPU for the directly recorded rest
+    /// (counted in frame_stats().culling). Off, every queued draw is drawn;
+    /// useful for checking whether a popping object has bad bounds.
+    pub fn set_gpu_culling(&mut self, on: bool) {
+        self.gpu_culling = on;
+    }
+
+    /// Toggle the depth prepass (off by default): default-pipeline draws
+    /// are rendered depth-only first, then shaded against that depth, so
+    /// each pixel runs the fragment shader about once no matter how many
+    /// voxel faces overlap it. Costs a second vertex pass over the same
+    /// geometry; a win when fragment shading and overdraw dominate, a loss
+    /// on vertex-bound scenes. Registered pipelines aren't prepassed; they
+    /// still depth-test against it.
+    pub fn set_depth_prepass(&mut self, on: bool) {
+        self.depth_prepass = on;
+    }
+
+    /// Index of `layout` in vertex_layouts, adding it (after validating it
+    /// against the device's vertex input limits) if it's new.
+    fn intern_vertex_layout(&mut self, layout: &VertexLayout) -> Result<u32> {
+        if let Some(i) = self.vertex_layouts.iter().position(|l| l == layout) {
+            return Ok(i as u32);
+        }
+        layout.validate()?;
+        let limits = unsafe { self.instance.get_physical_device_properties(self.phys) }.limits;
+        if layout.attributes.len() as u32 > limits.max_vertex_input_attributes {
+            return Err(anyhow!(
+                "vertex layout: {} attributes, device allows {}",
+                layout.attributes.len(),
+                limits.max_vertex_input_attributes
+            ));
+        }
+        if layout.stride > limits.max_vertex_input_binding_stride {
+            return Err(anyhow!(
+                "vertex layout: stride {} over the device's {}",
+                layout.stride,
+                limits.max_vertex_input_binding_stride
+            ));
+        }
+        for a in &layout.attributes {
+            if a.location >= limits.max_vertex_input_attributes {
+                return Err(anyhow!(
+                    "vertex layout: location {} out of range (max {})",
+                    a.location,
+                    limits.max_vertex_input_attributes - 1
+                ));
+            }
+            let format = vk_vertex_format(a.format);
+            let props = unsafe {
+                self.instance
+                    .get_physical_device_format_properties(self.phys, format)
+            };
+            if !props
+                .buffer_features
+                .contains(vk::FormatFeatureFlags::VERTEX_BUFFER)
+            {
+                return Err(anyhow!(
+                    "vertex layout: {:?} ({format:?}) not usable as a vertex attribute on this device",
+                    a.format
+                ));
+            }
+        }
+        self.vertex_layouts.push(layout.clone());
+        Ok(self.vertex_layouts.len() as u32 - 1)
+    }
+
     /// Queue a draw of a previously uploaded mesh for the next render()
     /// call, with the given per-object push constants. Call once per frame
     /// per object; the queue is consumed and cleared when that frame's
     /// command buffer is recorded.
     pub fn draw_mesh(&mut self, handle: MeshHandle, push: PushData) {
-        self.pending_draws.push((handle, push));
+        self.draw_mesh_with_pipeline(handle, push, PipelineHandle::DEFAULT);
+    }
+
+    /// `draw_mesh` with a material (see `create_material`): drawn with the
+    /// material's pipeline and its set 1 bound. An unknown (destroyed)
+    /// material draws like DEFAULT.
+    pub fn draw_mesh_with_material(
+        &mut self,
+        handle: MeshHandle,
+        push: PushData,
+        material: MaterialHandle,
+    ) {
+        let pipeline = self
+            .material_desc(material)
+            .map_or(PipelineHandle::DEFAULT, |d| d.pipeline);
+        self.queue_draw(handle, push, pipeline, material, ObjectId(0));
+    }
+
+    /// `draw_mesh_with_material`, tagged with `id` for pick (see
+    /// set_picking). Draws without an id pick as None.
+    pub fn draw_mesh_with_id(
+        &mut self,
+        handle: MeshHandle,
+        push: PushData,
+        material: MaterialHandle,
+        id: ObjectId,
+    ) {
+        let pipeline = self
+            .material_desc(material)
+            .map_or(PipelineHandle::DEFAULT, |d| d.pipeline);
+        self.queue_draw(handle, push, pipeline, material, id);
+    }
+
+    /// `draw_mesh` with an explicit pipeline (see `register_pipeline`).
+    /// Draws on the default pipeline go through the GPU-driven indirect
+    /// path; everything else is recorded afterwards as direct draws grouped
+    /// by pipeline, in registration order.
+    pub fn draw_mesh_with_pipeline(
+        &mut self,
+        handle: MeshHandle,
+        push: PushData,
+        pipeline: PipelineHandle,
+    ) {
+        self.queue_draw(handle, push, pipeline, MaterialHandle::DEFAULT, ObjectId(0));
+    }
+
+    fn queue_draw(
+        &mut self,
+        handle: MeshHandle,
+        push: PushData,
+        pipeline: PipelineHandle,
+        material: MaterialHandle,
+        object: ObjectId,
+    ) {
+        // A pipeline reading a different vertex layout than the mesh was
+        // uploaded with would draw garbage (or fault); drop the draw.
+        // Unknown handles are left for record time to skip, as before.
+        let mismatch = match (
+            self.meshes.get(handle.0 as usize).map(|m| m.layout),
+            self.vertex_layout_of_pipeline(pipeline),
+        ) {
+            (Some(mesh), Some(pipe)) => mesh != pipe,
+            _ => false,
+        };
+        debug_assert!(
+            !mismatch,
+            "draw of mesh {handle:?} on pipeline {pipeline:?}: vertex layouts differ"
+        );
+        if !mismatch {
+            self.pending_draws.push(QueuedDraw {
+                mesh: handle,
+                push,
+                pipeline,
+                material,
+                object,
+                culled: false,
+            });
+        }
+    }
+
+    fn vertex_layout_of_pipeline(&self, pipeline: PipelineHandle) -> Option<u32> {
+        if pipeline == PipelineHandle::DEFAULT {
+            return Some(0);
+        }
+        self.named_pipelines
+            .get(pipeline.0 as usize - 1)
+            .map(|np| np.vertex_layout)
+    }
+
+    /// Build a graphics pipeline from `desc` (through the shared pipeline
+    /// cache) and return a handle usable with `draw_mesh_with_pipeline`.
+    pub fn register_pipeline(&mut self, desc: PipelineDesc) -> RenderResult<PipelineHandle> {
+        if self.pipeline_by_name(&desc.name).is_some() {
+            return Err(anyhow!(
+                "register_pipeline: a pipeline named {:?} already exists",
+                desc.name
+            )
+            .into());
+        }
+        if desc.polygon_mode != vk::PolygonMode::FILL {
+            let feats = unsafe { self.instance.get_physical_device_features(self.phys) };
+            if feats.fill_mode_non_solid == vk::FALSE {
+                return Err(anyhow!(
+                    "register_pipeline: {:?} needs fillModeNonSolid, unsupported on this device",
+                    desc.name
+                )
+                .into());
+            }
+        }
+        let vertex_layout = self.intern_vertex_layout(&desc.vertex_layout)?;
+        let (layout, pipeline, reflected) = create_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &self.pipeline_config(),
+            &desc,
+        )?;
+        self.named_pipelines.push(NamedPipeline {
+            desc,
+            layout,
+            pipeline,
+            reflected,
+            vertex_layout,
+        });
+        self.name_objects();
+        Ok(PipelineHandle(self.named_pipelines.len() as u32))
+    }
+
+    /// Look a pipeline up by `PipelineDesc::name`. The default pipeline is
+    /// registered as "opaque".
+    pub fn pipeline_by_name(&self, name: &str) -> Option<PipelineHandle> {
+        if name == PipelineDesc::default().name {
+            return Some(PipelineHandle::DEFAULT);
+        }
+        self.named_pipelines
+            .iter()
+            .position(|np| np.desc.name == name)
+            .map(|i| PipelineHandle(i as u32 + 1))
+    }
+
+    /// Swapchain-derived pipeline state shared by every graphics pipeline.
+    pub(crate) fn pipeline_config(&self) -> PipelineConfig {
+        PipelineConfig {
+            color_format: self.scene_color_format(),
+            depth_format: self.depth_format,
+            set_layout_camera: self.desc_set_layout_camera,
+            set_layout_material: self.desc_set_layout_material,
+            set_layout_indirect_graphics: self.desc_set_layout_indirect_graphics,
+            tex_caps: self.tex_caps,
+        }
+    }
+
+    /// Rebuild the default pipeline and every registered one against the
+    /// current scene color format (swapchain or tonemap target), retiring
+    /// the old objects through the trash queue.
+    pub(crate) fn rebuild_scene_pipelines(&mut self) -> Result<()> {
+        let (new_layout, new_pipeline, reflected) = create_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &self.pipeline_config(),
+            &PipelineDesc::default(),
+        )?;
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::Pipeline(self.pipeline),
+        });
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::PipelineLayout(self.pipeline_layout),
+        });
+        self.pipeline_layout = new_layout;
+        self.pipeline = new_pipeline;
+        self.pipeline_reflected = reflected;
+        self.sync_debug_line_pipeline();
+        self.sync_skybox_pipeline();
+        self.rebuild_named_pipelines()
+    }
+
+    /// Rebuild every registered pipeline from its stored description,
+    /// retiring the old objects through the trash queue. Called when the
+    /// color format changes and on shader hot-reload.
+    pub(crate) fn rebuild_named_pipelines(&mut self) -> Result<()> {
+        let cfg = self.pipeline_config();
+        for i in 0..self.named_pipelines.len() {
+            let (layout, pipeline, reflected) = create_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &cfg,
+                &self.named_pipelines[i].desc,
+            )?;
+            let np = &mut self.named_pipelines[i];
+            np.reflected = reflected;
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::Pipeline(std::mem::replace(&mut np.pipeline, pipeline)),
+            });
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::PipelineLayout(std::mem::replace(&mut np.layout, layout)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Device addresses of the shared vertex and index pools, for
+    /// vertex-pulling shaders (see `PipelineDesc::vertex_pulling`). Every
+    /// mesh lives in these two buffers. The camera UBO carries them too, as
+    /// uvec2 pairs in `CameraUbo::mesh_pools`, for a shader that declares
+    /// the block that far; tri.vert stops at `view_proj`, so it doesn't.
+    pub fn mesh_pool_addresses(&self) -> (u64, u64) {
+        (self.mesh_pool_addresses[0], self.mesh_pool_addresses[1])
     }
 
     pub fn free_mesh(&mut self, handle: MeshHandle) {
@@ -975,8 +2320,8 @@ impl VkRenderer {
         self.trash.push(DeferredDrop {
             value: self.timeline_value,
             resource: GpuResource::MeshSlot {
-                first_vertex: mesh.first_vertex as u32,
-                vertex_count: mesh.vertex_count,
+                first_vertex: mesh.slot_start,
+                vertex_count: mesh.slot_len,
                 first_index: mesh.first_index,
                 index_count: mesh.index_count,
             },
@@ -987,6 +2332,10 @@ impl VkRenderer {
             first_index: 0,
             index_count: 0,
             vertex_count: 0,
+            slot_start: 0,
+            slot_len: 0,
+            layout: 0,
+            bounds: UNBOUNDED,
         };
     }
 }
@@ -996,8 +2345,9 @@ impl Renderer for VkRenderer {
         window: &dyn HasWindowHandle,
         display: &dyn HasDisplayHandle,
         size: RenderSize,
-    ) -> Result<Self> {
-        build_renderer(window, display, size)
+    ) -> RenderResult<Self> {
+        let layers = ValidationLayers::from_env().unwrap_or_default();
+        build_renderer(window, display, size, None, layers, None).map_err(classify)
     }
 
     fn set_vsync(&mut self, on: bool) {
@@ -1005,14 +2355,15 @@ impl Renderer for VkRenderer {
             return;
         }
         self.cfg.vsync = on;
-        let want = RenderSize {
-            width: self.extent.width,
-            height: self.extent.height,
-        };
-        let _ = self.recreate_swapchain(want);
+        self.apply_present_mode();
     }
 
-    fn resize(&mut self, size: RenderSize) -> Result<()> {
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()> {
+        // No surface to resize; resume() builds at the latest size.
+        if let Some(pending) = &mut self.suspended {
+            *pending = size;
+            return Ok(());
+        }
         // Handle minimized / 0×0 and pause
         if size.width == 0 || size.height == 0 {
             if !self.paused {
@@ -1031,30 +2382,23 @@ impl Renderer for VkRenderer {
         }
         self.paused = false;
 
-        // Try to recreate the swapchain; if the surface was lost, rebuild it once and retry
-        match self.recreate_swapchain(size) {
-            Ok(()) => Ok(()),
-            Err(e) => {
-                // If we can peel out a vk::Result and it's SURFACE_LOST, rebuild the surface
-                if let Some(vkerr) = e.downcast_ref::<vk::Result>() {
-                    if *vkerr == vk::Result::ERROR_SURFACE_LOST_KHR {
-                        let entry = Entry::linked();
-                        // requires: self.display_raw / self.window_raw fields and recreate_surface() helper
-                        recreate_surface(
-                            &entry,
-                            &self.instance,
-                            &self.surface_loader,
-                            &mut self.surface,
-                            self.display_raw,
-                            self.window_raw,
-                        )?;
-                        // retry swapchain on the new surface
-                        return self.recreate_swapchain(size);
-                    }
-                }
-                Err(e)
-            }
-        }
+        // Recreated by the next render(), at whichever size is latest by
+        // then (see apply_pending_resize).
+        self.pending_resize = Some(size);
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> RenderResult<()> {
+        self.suspend_surface();
+        Ok(())
+    }
+
+    fn resume(&mut self) -> RenderResult<()> {
+        self.resume_surface().map_err(classify)
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
     }
 
     fn set_clear_color(&mut self, rgba: [f32; 4]) {
@@ -1063,15 +2407,98 @@ impl Renderer for VkRenderer {
         };
     }
 
+    fn surface_info(&self) -> SurfaceInfo {
+        SurfaceInfo {
+            format: swapchain::fmt_name(self.format),
+            color_space: swapchain::cs_name(self.color_space),
+            extent: {
+                let view = self.view_extent();
+                RenderSize {
+                    width: view.width,
+                    height: view.height,
+                }
+            },
+            present_mode: match self.present_mode {
+                vk::PresentModeKHR::FIFO => PresentMode::Fifo,
+                vk::PresentModeKHR::FIFO_RELAXED => PresentMode::FifoRelaxed,
+                vk::PresentModeKHR::MAILBOX => PresentMode::Mailbox,
+                vk::PresentModeKHR::IMMEDIATE => PresentMode::Immediate,
+                _ => PresentMode::Other,
+            },
+            hdr: tonemap::is_hdr_color_space(self.color_space),
+            image_count: self.images.len() as u32,
+        }
+    }
+
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged> {
+        self.surface_change.take()
+    }
+
+    fn draw_text(&mut self, pos: [f32; 2], text: &str, size: f32, color: [f32; 4]) {
+        VkRenderer::draw_text(self, pos, text, size, color);
+    }
+
     // STRICT PER-FRAME ORDER:
     // 1) acquire_next_image (waits on acquire semaphore)
     // 2) record this frame's draws into the acquired image's command buffer
     //    (acquire_next_image only returns an image once the GPU is done
     //    with its previous use, so resetting its command buffer here is safe)
     // 3) queue_submit (signals render-finished for THIS image)
-    // 4) queue_present (waits on render-finished)
+    // 4) queue_present on the present queue (waits on render-finished)
     // Each swapchain image has its own FrameSync; do not cross-use semaphores.
-    fn render(&mut self) -> Result<()> {
-        self.render_frame()
+    fn render(&mut self) -> RenderResult<()> {
+        if self.suspended.is_some() {
+            self.pacer.wait();
+            return Ok(());
+        }
+        let cpu_start = std::time::Instant::now();
+        let _frame = tracing::debug_span!("frame", backend = "vk").entered();
+        let last_present = self.present_id;
+        let res = self.render_frame().map_err(classify);
+        if let Err(RenderError::DeviceLost { stage }) = res {
+            self.write_crash_report(stage);
+        }
+        // Text and debug lines are queued per frame; drop what a skipped
+        // frame didn't draw.
+        self.text_vertices.clear();
+        self.debug_lines.clear();
+        self.debug_lines_dropped = 0;
+        if !self.paused {
+            self.frame_stats.record(cpu_start.elapsed());
+        }
+        let pace = tracing::debug_span!("pace").entered();
+        if res.is_ok() && self.present_id != last_present {
+            self.wait_for_latency();
+        }
+        self.pace_frame();
+        drop(pace);
+        self.log_memory_stats_periodically();
+        if let Some(msg) = self.validation.take_fatal() {
+            panic!("Vulkan validation error (panic_on_error): {msg}");
+        }
+        res
+    }
+
+    fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.pacer.set_target_fps(fps);
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            validation: self.validation.counts(),
+            ..self.frame_stats.stats()
+        }
+    }
+
+    fn set_latency_mode(&mut self, mode: LatencyMode) {
+        self.latency_mode = mode;
+    }
+
+    fn set_frame_spike_threshold(&mut self, threshold: Option<std::time::Duration>) {
+        self.frame_stats.set_spike_threshold(threshold);
+    }
+
+    fn record_stream_counts(&mut self, counts: StreamCounts) {
+        self.frame_stats.record_streaming(counts);
     }
 }
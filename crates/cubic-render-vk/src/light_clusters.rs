@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Clustered light culling, so hundreds of point lights (torches down a
+//! cave) cost each fragment only the few that reach it.
+//!
+//! The view frustum is cut into CLUSTER_GRID clusters: screen tiles
+//! across and down, and depth slices spaced evenly in log(view depth)
+//! from the camera's near plane out to CLUSTER_FAR. Each frame the "light
+//! clusters" compute pass (light_cluster.comp, one invocation per cluster)
+//! tests every light in the list against its cluster's view-space box and
+//! writes the indices of those that reach it, up to MAX_CLUSTER_LIGHTS;
+//! tri.frag then finds its own cluster from gl_FragCoord and view depth
+//! and loops over that list alone. Directional lights reach every cluster.
+//! Fragments past CLUSTER_FAR use the last slice, whose box ends there.
+//!
+//! Clusters are for the scene pass through set_camera's camera only: with
+//! fewer than CLUSTER_MIN_LIGHTS lights, with viewports or stereo set, on
+//! a pre-rotated swapchain, in render targets' views, or without the
+//! shader built, tri.frag loops over the whole list as it always did.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_math::{Camera, Mat4};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::DescriptorAllocator;
+use crate::lighting::LightList;
+use crate::pipeline::{create_compute_pipeline, load_spv_file, shader_dir};
+use crate::resources::create_buffer_and_memory;
+use crate::swapchain::pre_rotation;
+use crate::VkRenderer;
+
+/// Tiles across, tiles down, depth slices. Tiles scale with the scene
+/// target, so the grid (and its buffer) never changes.
+pub(crate) const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+
+/// Lights one cluster lists; any more that reach it are dropped. Matches
+/// MAX_CLUSTER_LIGHTS in tri.frag and light_cluster.comp.
+pub(crate) const MAX_CLUSTER_LIGHTS: u32 = 64;
+
+/// View depth the last slice ends at.
+const CLUSTER_FAR: f32 = 512.0;
+
+/// Below this many live lights the per-fragment loop over the whole list
+/// is cheaper than the cluster pass.
+const CLUSTER_MIN_LIGHTS: usize = 16;
+
+fn cluster_count() -> u32 {
+    CLUSTER_GRID.iter().product()
+}
+
+/// Push constants of light_cluster.comp.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct ClusterPush {
+    /// Camera-relative space to view space (rotation only).
+    view: [[f32; 4]; 4],
+    /// Tiles across, down, depth slices; w unused.
+    grid: [u32; 4],
+    /// tan(fovy / 2) * aspect, tan(fovy / 2), near, far.
+    frustum: [f32; 4],
+}
+
+/// The cluster pass: its pipeline, and the buffer it writes and tri.frag
+/// reads (camera set binding 3). Per cluster, a count and then
+/// MAX_CLUSTER_LIGHTS light indices.
+pub(crate) struct LightClusters {
+    buffer: vk::Buffer,
+    alloc: Allocation,
+    set_layout: vk::DescriptorSetLayout,
+    // Binding 0 the light list (the staging belt, dynamic like the camera
+    // set's), binding 1 the clusters.
+    set: vk::DescriptorSet,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl LightClusters {
+    pub(crate) fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        cache: vk::PipelineCache,
+        belt: vk::Buffer,
+    ) -> Result<Self> {
+        // Shader first: a missing .spv is the usual failure, and nothing
+        // needs cleaning up yet.
+        let words = load_spv_file(&shader_dir().join("light_cluster.comp.spv"))?;
+        let mut clusters = Self {
+            buffer: vk::Buffer::null(),
+            alloc: Allocation::default(),
+            set_layout: vk::DescriptorSetLayout::null(),
+            set: vk::DescriptorSet::null(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+        };
+        let result = (|| -> Result<()> {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding {
+                    binding: 0,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    descriptor_count: 1,
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    ..Default::default()
+                },
+                vk::DescriptorSetLayoutBinding {
+                    binding: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    descriptor_count: 1,
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    ..Default::default()
+                },
+            ];
+            let set_layout_ci = vk::DescriptorSetLayoutCreateInfo {
+                s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+                binding_count: bindings.len() as u32,
+                p_bindings: bindings.as_ptr(),
+                ..Default::default()
+            };
+            clusters.set_layout =
+                unsafe { device.create_descriptor_set_layout(&set_layout_ci, None)? };
+            let push_range = vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<ClusterPush>() as u32,
+            };
+            let layout_info = vk::PipelineLayoutCreateInfo {
+                s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+                set_layout_count: 1,
+                p_set_layouts: &clusters.set_layout,
+                push_constant_range_count: 1,
+                p_push_constant_ranges: &push_range,
+                ..Default::default()
+            };
+            clusters.layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+            clusters.pipeline = create_compute_pipeline(device, cache, clusters.layout, &words)?;
+
+            let (buffer, alloc) = create_buffer_and_memory(
+                device,
+                allocator,
+                clusters.buffer_info().range,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                MemoryLocation::GpuOnly,
+                "light clusters",
+            )?;
+            clusters.buffer = buffer;
+            clusters.alloc = alloc;
+
+            clusters.set = descriptors.allocate(device, clusters.set_layout)?.set;
+            let lights_info = vk::DescriptorBufferInfo {
+                buffer: belt,
+                offset: 0,
+                range: std::mem::size_of::<LightList>() as u64,
+            };
+            let clusters_info = clusters.buffer_info();
+            let writes = [
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: clusters.set,
+                    dst_binding: 0,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    p_buffer_info: &lights_info,
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: clusters.set,
+                    dst_binding: 1,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &clusters_info,
+                    ..Default::default()
+                },
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(clusters),
+            Err(e) => {
+                clusters.destroy(device, allocator);
+                Err(e)
+            }
+        }
+    }
+
+    /// The whole cluster buffer, for the camera set's binding 3.
+    pub(crate) fn buffer_info(&self) -> vk::DescriptorBufferInfo {
+        let per_cluster = (1 + MAX_CLUSTER_LIGHTS) as u64 * std::mem::size_of::<u32>() as u64;
+        vk::DescriptorBufferInfo {
+            buffer: self.buffer,
+            offset: 0,
+            range: cluster_count() as u64 * per_cluster,
+        }
+    }
+
+    /// Its descriptor set goes with the persistent pools.
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+        let _ = allocator.free(std::mem::take(&mut self.alloc));
+    }
+}
+
+/// Near and far ends of the slices for `camera`.
+fn cluster_depth_range(camera: &Camera) -> (f32, f32) {
+    let near = camera.near.max(1e-4);
+    (near, CLUSTER_FAR.max(near * 2.0))
+}
+
+impl VkRenderer {
+    /// Whether this frame's scene pass shades from the clusters (see
+    /// module docs).
+    pub(crate) fn light_clusters_active(&self) -> bool {
+        self.light_clusters.is_some()
+            && self.viewports.is_empty()
+            && self.stereo.is_none()
+            && pre_rotation(self.pre_transform) == Mat4::IDENTITY
+            && self.lights.iter().flatten().count() >= CLUSTER_MIN_LIGHTS
+    }
+
+    /// CameraUbo's cluster_grid and cluster_depth for the scene through
+    /// `camera`: what tri.frag needs to find a fragment's cluster.
+    pub(crate) fn light_cluster_params(&self, camera: &Camera) -> ([u32; 4], [f32; 4]) {
+        let [x, y, z] = CLUSTER_GRID;
+        let extent = self.scene_extent();
+        let (near, far) = cluster_depth_range(camera);
+        // slice = z * log(depth / near) / log(far / near)
+        let scale = z as f32 / (far / near).ln();
+        (
+            [x, y, z, self.light_clusters_active() as u32],
+            [
+                extent.width as f32 / x as f32,
+                extent.height as f32 / y as f32,
+                scale,
+                -near.ln() * scale,
+            ],
+        )
+    }
+
+    /// The "light clusters" pass: bin this frame's light list (already in
+    /// the staging belt) into the cluster buffer for the scene pass.
+    pub(crate) fn record_light_clusters(&self, cmd: vk::CommandBuffer) -> Result<()> {
+        let clusters = self
+            .light_clusters
+            .as_ref()
+            .ok_or_else(|| anyhow!("light clusters pass without its pipeline"))?;
+        let camera = &self.camera;
+        let (near, far) = cluster_depth_range(camera);
+        let tan_half = (0.5 * camera.fovy).tan();
+        let [x, y, z] = CLUSTER_GRID;
+        let push = ClusterPush {
+            view: camera.view_matrix_no_translation().to_cols_array_2d(),
+            grid: [x, y, z, 0],
+            frustum: [tan_half * self.view_aspect(), tan_half, near, far],
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, clusters.pipeline);
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                clusters.layout,
+                0,
+                std::slice::from_ref(&clusters.set),
+                &[self.frame_uniforms.lights()],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                clusters.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            // 64 clusters per workgroup, matching local_size.
+            self.device
+                .cmd_dispatch(cmd, cluster_count().div_ceil(64), 1, 1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Scene lighting API: the directional (sun) light and its cascaded shadow
+//! settings, plus the per-frame cascade fit that feeds the camera UBO; and
+//! the light list (add_light/update_light), extra unshadowed point and
+//! directional lights the scene shader sums with Lambert diffuse. With
+//! more than a handful of lights the scene reads them per screen cluster
+//! rather than all of them per fragment (see light_clusters.rs).
+//!
+//! Cascades split the view distance [near, max_distance] with the usual
+//! log/linear blend (split_lambda), and each slice is covered by an
+//! orthographic light-space box fitted to the slice's bounding sphere. A
+//! sphere doesn't change size as the camera turns, and its centre is
+//! snapped to whole shadow-map texels in world space, so shadow edges stay
+//! put instead of shimmering while the camera moves. Everything is built
+//! in the renderer's camera-relative space (see cubic_math::Camera), with
+//! the snapping done in f64 world coordinates.
+
+use anyhow::{anyhow, Result};
+use bytemuck::{Pod, Zeroable};
+use cubic_math::{Camera, DVec3, Mat4, Vec3};
+use cubic_render::{clip, RenderResult};
+
+use crate::VkRenderer;
+
+/// Upper bound on ShadowSettings::cascade_count; the camera UBO and
+/// tri.frag size their cascade arrays to this.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// Most lights add_light accepts at once; the light list buffer holds this
+/// many.
+pub const MAX_LIGHTS: usize = 1024;
+
+/// The scene's single shadow-casting directional light (the sun).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// Direction the light travels (sun to scene), world space; needn't
+    /// be normalized.
+    pub direction: [f32; 3],
+    /// Linear RGB; multiplied by intensity.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Fraction of full brightness every surface gets regardless of the
+    /// light (and of shadows), in [0, 1].
+    pub ambient: f32,
+}
+
+impl Default for DirectionalLight {
+    /// The sun tri.frag hard-coded before there was a lighting API.
+    fn default() -> Self {
+        Self {
+            direction: [-0.5, -1.0, -0.3],
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            ambient: 0.4,
+        }
+    }
+}
+
+/// An entry in the light list. Neither kind casts shadows; only the
+/// DirectionalLight does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Directional {
+        /// Direction the light travels, world space; needn't be normalized.
+        direction: [f32; 3],
+        /// Linear RGB; multiplied by intensity.
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Point {
+        /// World space, f64 like the camera so far-from-origin lights
+        /// don't jitter.
+        position: DVec3,
+        color: [f32; 3],
+        intensity: f32,
+        /// Distance at which the light has faded to nothing.
+        range: f32,
+    },
+}
+
+/// Returned by add_light; dead once passed to remove_light.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightHandle(pub u32);
+
+// One light in the light list (std430, mirrored by tri.frag and
+// light_cluster.comp).
+#[repr(C)]
+#[derive(Clone, Copy, Default, Zeroable, Pod)]
+pub(crate) struct GpuLight {
+    // Point: camera-relative position, w = 1. Directional: unit direction
+    // towards the light, w = 0.
+    pub(crate) position: [f32; 4],
+    // rgb = colour * intensity, w = range (point lights).
+    pub(crate) color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct LightList {
+    // x = number of lights in use.
+    pub(crate) count: [u32; 4],
+    pub(crate) lights: [GpuLight; MAX_LIGHTS],
+}
+
+impl Light {
+    fn to_gpu(self, camera_pos: DVec3) -> GpuLight {
+        match self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => {
+                let to_light = -Vec3::from(direction).try_normalize().unwrap_or(Vec3::NEG_Y);
+                GpuLight {
+                    position: to_light.extend(0.0).to_array(),
+                    color: (Vec3::from(color) * intensity).extend(0.0).to_array(),
+                }
+            }
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => GpuLight {
+                position: (position - camera_pos).as_vec3().extend(1.0).to_array(),
+                color: (Vec3::from(color) * intensity)
+                    .extend(range.max(1e-3))
+                    .to_array(),
+            },
+        }
+    }
+}
+
+/// The light list as the shaders want it this frame: live lights packed
+/// to the front, point lights made camera-relative.
+pub(crate) fn pack_lights(lights: &[Option<Light>], camera_pos: DVec3) -> LightList {
+    let mut list = LightList::zeroed();
+    let mut n = 0;
+    for light in lights.iter().flatten().take(MAX_LIGHTS) {
+        list.lights[n] = light.to_gpu(camera_pos);
+        n += 1;
+    }
+    list.count[0] = n as u32;
+    list
+}
+
+/// Cascaded shadow map parameters for the directional light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Width = height of each cascade's layer, in texels.
+    pub resolution: u32,
+    /// 1..=MAX_SHADOW_CASCADES.
+    pub cascade_count: u32,
+    /// View distance the last cascade ends at; nothing is shadowed beyond.
+    pub max_distance: f32,
+    /// 0 = evenly spaced splits, 1 = logarithmic (more resolution near the
+    /// camera).
+    pub split_lambda: f32,
+    /// Depth bias applied while rendering the shadow map, in the units of
+    /// vkCmdSetDepthBias (constant) and per unit of depth slope.
+    pub depth_bias_constant: f32,
+    pub depth_bias_slope: f32,
+    /// World units the shaded point is pushed along its normal before the
+    /// shadow lookup; hides acne on surfaces facing away from the light.
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 2048,
+            cascade_count: 4,
+            max_distance: 128.0,
+            split_lambda: 0.75,
+            depth_bias_constant: 2.0,
+            depth_bias_slope: 2.5,
+            normal_offset: 0.05,
+        }
+    }
+}
+
+impl ShadowSettings {
+    fn validate(&self) -> Result<()> {
+        if !(1..=MAX_SHADOW_CASCADES as u32).contains(&self.cascade_count) {
+            return Err(anyhow!(
+                "shadow settings: cascade_count {} outside 1..={MAX_SHADOW_CASCADES}",
+                self.cascade_count
+            ));
+        }
+        if self.resolution == 0 {
+            return Err(anyhow!("shadow settings: resolution is 0"));
+        }
+        if self.max_distance.is_nan() || self.max_distance <= 0.0 {
+            return Err(anyhow!(
+                "shadow settings: max_distance must be positive, got {}",
+                self.max_distance
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// One frame's cascade fit. Entries past the cascade count are unused.
+pub(crate) struct Cascades {
+    pub(crate) view_proj: [Mat4; MAX_SHADOW_CASCADES],
+    /// View-depth each cascade ends at.
+    pub(crate) splits: [f32; MAX_SHADOW_CASCADES],
+}
+
+/// Reverse-Z orthographic projection of a light-view box `radius` wide on
+/// each side of the view axis and `depth` deep: depth 1 at the eye, 0 at
+/// the far end, like the scene's projection.
+fn light_projection(radius: f32, depth: f32) -> Mat4 {
+    clip::orthographic_rh_zo_reverse(-radius, radius, -radius, radius, 0.0, depth)
+}
+
+pub(crate) fn compute_cascades(
+    camera: &Camera,
+    aspect: f32,
+    light: &DirectionalLight,
+    settings: &ShadowSettings,
+) -> Cascades {
+    let count = settings.cascade_count.clamp(1, MAX_SHADOW_CASCADES as u32) as usize;
+    let near = camera.near;
+    let far = settings.max_distance.max(near * 2.0);
+    let lambda = settings.split_lambda.clamp(0.0, 1.0);
+
+    let mut splits = [far; MAX_SHADOW_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate().take(count) {
+        let p = (i + 1) as f32 / count as f32;
+        let log = near * (far / near).powf(p);
+        let lin = near + (far - near) * p;
+        *split = lambda * log + (1.0 - lambda) * lin;
+    }
+
+    let dir = Vec3::from(light.direction)
+        .try_normalize()
+        .unwrap_or(Vec3::NEG_Y);
+    let up = if dir.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    // Light-space x/y axes in f64, for snapping world positions that can
+    // be far from the origin.
+    let dir_d = dir.as_dvec3();
+    let right_d = dir_d.cross(up.as_dvec3()).normalize();
+    let up_d = right_d.cross(dir_d);
+
+    // Squared slice radius per unit of view depth, at the slice's corners.
+    let tan_half = (0.5 * camera.fovy).tan();
+    let k2 = tan_half * tan_half * (1.0 + aspect * aspect);
+    let forward = camera.forward();
+
+    let mut view_proj = [Mat4::ZERO; MAX_SHADOW_CASCADES];
+    let mut start = near;
+    for i in 0..count {
+        let (a, b) = (start, splits[i]);
+        start = b;
+        // Smallest sphere through the slice's near and far corners, its
+        // centre on the view axis (clamped to the far face for wide,
+        // shallow slices).
+        let mut centre = 0.5 * (a + b) * (1.0 + k2);
+        let radius = if centre >= b {
+            centre = b;
+            b * k2.sqrt()
+        } else {
+            ((b - centre).powi(2) + b * b * k2).sqrt()
+        };
+        // Quantized so rounding noise never changes the texel size.
+        let radius = (radius * 16.0).ceil() / 16.0;
+        let texel = 2.0 * radius as f64 / settings.resolution as f64;
+
+        let centre_rel = forward * centre;
+        let world = camera.position + centre_rel.as_dvec3();
+        let (x, y) = (world.dot(right_d), world.dot(up_d));
+        let snap = |v: f64| (v / texel).floor() * texel - v;
+        let shift: DVec3 = right_d * snap(x) + up_d * snap(y);
+        let centre_rel = centre_rel + shift.as_vec3();
+
+        // Pulled back past the sphere by the whole shadow distance, so
+        // casters between the sun and the slice (tall terrain, trees)
+        // still land in the map.
+        let depth = 2.0 * radius + far;
+        let eye = centre_rel - dir * (radius + far);
+        let view = clip::look_to_rh(eye, dir, up);
+        view_proj[i] = light_projection(radius, depth) * view;
+    }
+    Cascades { view_proj, splits }
+}
+
+impl VkRenderer {
+    /// Replace the directional light. Takes effect next frame.
+    pub fn set_directional_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+    }
+
+    pub fn directional_light(&self) -> DirectionalLight {
+        self.light
+    }
+
+    /// Apply new shadow settings. Changing the resolution or cascade count
+    /// of enabled shadows reallocates the shadow map, which idles the
+    /// device first; toggling `enabled` or anything else is free.
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) -> RenderResult<()> {
+        settings.validate()?;
+        self.shadow_settings = settings;
+        if settings.enabled {
+            self.resize_shadow_map(settings.resolution, settings.cascade_count)?;
+            self.name_objects();
+        }
+        Ok(())
+    }
+
+    pub fn shadow_settings(&self) -> ShadowSettings {
+        self.shadow_settings
+    }
+
+    /// Add a light to the list, effective next frame. Fails once
+    /// MAX_LIGHTS are live.
+    pub fn add_light(&mut self, light: Light) -> RenderResult<LightHandle> {
+        if let Some(i) = self.lights.iter().position(Option::is_none) {
+            self.lights[i] = Some(light);
+            return Ok(LightHandle(i as u32));
+        }
+        if self.lights.len() >= MAX_LIGHTS {
+            return Err(anyhow!("add_light: all {MAX_LIGHTS} light slots in use").into());
+        }
+        self.lights.push(Some(light));
+        Ok(LightHandle(self.lights.len() as u32 - 1))
+    }
+
+    /// Replace a light (moving it, recolouring it, or switching its kind).
+    pub fn update_light(&mut self, handle: LightHandle, light: Light) -> RenderResult<()> {
+        match self.lights.get_mut(handle.0 as usize) {
+            Some(Some(slot)) => {
+                *slot = light;
+                Ok(())
+            }
+            _ => Err(anyhow!("update_light: {handle:?} is not a live light").into()),
+        }
+    }
+
+    /// Remove a light; its handle may be reused by a later add_light.
+    pub fn remove_light(&mut self, handle: LightHandle) {
+        if let Some(slot) = self.lights.get_mut(handle.0 as usize) {
+            *slot = None;
+        }
+    }
+}
@@ -0,0 +1,598 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Materials: an albedo texture, tint and surface parameters plus the
+//! pipeline they're drawn with, each owning a set = 1 descriptor set.
+//!
+//! Set 1 is the bindless texture array (binding 0), the material's
+//! parameter block (binding 1) and the block texture array (binding 2, see
+//! block_textures.rs). Every material set carries its own copy of both
+//! texture bindings, kept in step by upload_texture and
+//! upload_block_textures, so tex_index keeps working whichever material is
+//! bound. With update-after-bind (see
+//! TextureArrayCaps) those writes are legal while frames are in flight.
+//!
+//! Sets come from a MaterialPool, one per set-1 layout (a "material type";
+//! every pipeline shares one set-1 layout today, so there is one pool). It
+//! grows in chunks of MATERIALS_PER_CHUNK sets, each chunk with its own
+//! host-visible parameter buffer and its sets from the pool's own
+//! DescriptorAllocator (see descriptors.rs), whose pools are sized for the
+//! texture array and update-after-bind where it is; slots are recycled
+//! through the trash queue once the GPU is done with them. Parameters are
+//! immutable after create_material, so a slot's buffer region is only ever
+//! written while nothing can be reading it.
+//!
+//! MaterialHandle::DEFAULT (slot 0) is the neutral material every draw used
+//! before materials existed. Default-pipeline draws with it go through the
+//! GPU-driven indirect path; any other material is bound per draw and
+//! recorded directly (see record_pipeline_draws).
+//!
+//! A material's sampler override lives in its own set's copy of the array:
+//! its albedo entry is rewritten with the override sampler, and put back to
+//! the texture's own when the slot is next handed out.
+//!
+//! ShadingModel::MetallicRoughness materials are drawn with pbr.frag on the
+//! "pbr" pipeline (PipelineDesc::pbr), registered the first time one is
+//! created on the default pipeline. Their normal, metallic-roughness,
+//! occlusion and emissive maps are bindless indices like the albedo, in
+//! glTF's conventions (roughness in g, metalness in b, occlusion in r).
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_render::{PipelineHandle, RenderResult};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::DescriptorAllocator;
+use crate::pipeline::PipelineDesc;
+use crate::resources::{
+    create_buffer_and_memory, write_block_array_descriptor, write_material_descriptors,
+    TextureArrayCaps,
+};
+use crate::sampler::SamplerDesc;
+use crate::{DeferredDrop, GpuResource, VkRenderer};
+
+// Sets (and parameter slots) per chunk, and per descriptor pool.
+const MATERIALS_PER_CHUNK: u32 = 32;
+
+/// Name of the pipeline MetallicRoughness materials are drawn with.
+const PBR_PIPELINE: &str = "pbr";
+
+/// Opaque handle from `create_material`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialHandle(pub u32);
+
+impl MaterialHandle {
+    /// The built-in neutral material: per-draw tex_index and tint only.
+    pub const DEFAULT: MaterialHandle = MaterialHandle(0);
+}
+
+/// How a material's surface is lit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ShadingModel {
+    /// tri.frag: albedo under Lambert diffuse light, roughness and
+    /// metallic unused. What every material had before PBR.
+    #[default]
+    Lambert,
+    /// pbr.frag: glTF-style metallic-roughness, with the texture maps
+    /// below and the environment's IBL maps for ambient light.
+    MetallicRoughness,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialDesc {
+    /// Bindless index from upload_texture. None leaves the texture to each
+    /// draw's PushData::tex_index.
+    pub albedo: Option<u32>,
+    /// Linear RGBA, multiplied with the draw's own tint.
+    pub tint: [f32; 4],
+    pub roughness: f32,
+    pub metallic: f32,
+    pub shading: ShadingModel,
+    /// Bindless indices of the MetallicRoughness maps, like `albedo`.
+    /// Tangent-space normals; roughness in g and metalness in b, scaling
+    /// the factors above; occlusion in r; emissive colour.
+    pub normal: Option<u32>,
+    pub metallic_roughness: Option<u32>,
+    pub occlusion: Option<u32>,
+    pub emissive: Option<u32>,
+    /// Scales the normal map's x and y.
+    pub normal_scale: f32,
+    /// 0 ignores the occlusion map, 1 applies it fully.
+    pub occlusion_strength: f32,
+    /// Linear RGB, multiplied with the emissive map where there is one.
+    pub emissive_factor: [f32; 3],
+    /// DEFAULT draws MetallicRoughness materials on the "pbr" pipeline.
+    pub pipeline: PipelineHandle,
+    /// Sample the albedo with this instead of the sampler it was uploaded
+    /// with. Needs `albedo`.
+    pub sampler: Option<SamplerDesc>,
+    /// Take the albedo from the block texture array (upload_block_textures)
+    /// instead, at the layer each vertex's tex_index names. Overrides
+    /// `albedo`.
+    pub block_array: bool,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            albedo: None,
+            tint: [1.0; 4],
+            roughness: 1.0,
+            metallic: 0.0,
+            shading: ShadingModel::Lambert,
+            normal: None,
+            metallic_roughness: None,
+            occlusion: None,
+            emissive: None,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0; 3],
+            pipeline: PipelineHandle::DEFAULT,
+            sampler: None,
+            block_array: false,
+        }
+    }
+}
+
+// Set 1 binding 1 (std140, mirrored by tri.frag).
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct MaterialParams {
+    tint: [f32; 4],
+    // x = albedo texture index, y = 1 if it overrides the draw's, z = 1 to
+    // sample the block texture array instead.
+    albedo: [u32; 4],
+    // x = roughness, y = metallic, z = occlusion strength, w = normal
+    // scale.
+    surface: [f32; 4],
+    // Normal, metallic-roughness, occlusion and emissive texture indices;
+    // 0 (the dummy) for none.
+    maps: [u32; 4],
+    // rgb = emissive factor.
+    emissive: [f32; 4],
+}
+
+impl From<&MaterialDesc> for MaterialParams {
+    fn from(desc: &MaterialDesc) -> Self {
+        Self {
+            tint: desc.tint,
+            albedo: [
+                desc.albedo.unwrap_or(0),
+                desc.albedo.is_some() as u32,
+                desc.block_array as u32,
+                0,
+            ],
+            surface: [
+                desc.roughness,
+                desc.metallic,
+                desc.occlusion_strength,
+                desc.normal_scale,
+            ],
+            maps: [
+                desc.normal.unwrap_or(0),
+                desc.metallic_roughness.unwrap_or(0),
+                desc.occlusion.unwrap_or(0),
+                desc.emissive.unwrap_or(0),
+            ],
+            emissive: [
+                desc.emissive_factor[0],
+                desc.emissive_factor[1],
+                desc.emissive_factor[2],
+                0.0,
+            ],
+        }
+    }
+}
+
+pub(crate) struct Material {
+    pub(crate) desc: MaterialDesc,
+    slot: u32,
+}
+
+impl Material {
+    /// MaterialHandle::DEFAULT, at the slot build_renderer allocated.
+    pub(crate) fn default_at(slot: u32) -> Self {
+        Self {
+            desc: MaterialDesc::default(),
+            slot,
+        }
+    }
+}
+
+struct MaterialChunk {
+    params: vk::Buffer,
+    alloc: Allocation,
+    sets: Vec<vk::DescriptorSet>,
+}
+
+/// Set-1 descriptor sets for one layout, in chunks. Slot n is set
+/// n % MATERIALS_PER_CHUNK of chunk n / MATERIALS_PER_CHUNK.
+pub(crate) struct MaterialPool {
+    layout: vk::DescriptorSetLayout,
+    // Pools sized (and flagged) for the layout's texture array.
+    descriptors: DescriptorAllocator,
+    // Parameter block stride, padded to minUniformBufferOffsetAlignment.
+    slot_size: vk::DeviceSize,
+    chunks: Vec<MaterialChunk>,
+    free: Vec<u32>,
+    // (slot, bindless index) entries written with a material's sampler
+    // override rather than the texture's own sampler.
+    sampler_overrides: Vec<(u32, u32)>,
+    // Whether binding 2 holds a block texture array, copied into new
+    // chunks' sets like the bindless textures.
+    block_array: bool,
+}
+
+impl MaterialPool {
+    pub(crate) fn new(
+        instance: &ash::Instance,
+        phys: vk::PhysicalDevice,
+        layout: vk::DescriptorSetLayout,
+        tex_caps: TextureArrayCaps,
+    ) -> Self {
+        let limits = unsafe { instance.get_physical_device_properties(phys).limits };
+        let a = limits.min_uniform_buffer_offset_alignment.max(1);
+        let sz = std::mem::size_of::<MaterialParams>() as u64;
+        let flags = if tex_caps.update_after_bind {
+            vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND
+        } else {
+            vk::DescriptorPoolCreateFlags::empty()
+        };
+        // The bindless array plus the block texture array.
+        let ratios = [
+            (
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                tex_caps.capacity + 1,
+            ),
+            (vk::DescriptorType::UNIFORM_BUFFER, 1),
+        ];
+        Self {
+            layout,
+            descriptors: DescriptorAllocator::new(
+                &ratios,
+                flags,
+                MATERIALS_PER_CHUNK,
+                MATERIALS_PER_CHUNK,
+            ),
+            slot_size: sz.div_ceil(a) * a,
+            chunks: Vec::new(),
+            free: Vec::new(),
+            sampler_overrides: Vec::new(),
+            block_array: false,
+        }
+    }
+
+    /// A free slot with `desc`'s parameters written, adding a chunk if
+    /// none is left. `tex_count` is how many bindless entries are live,
+    /// copied into a new chunk's sets from slot 0.
+    pub(crate) fn alloc(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        desc: &MaterialDesc,
+        tex_count: u32,
+    ) -> Result<u32> {
+        if self.free.is_empty() {
+            self.grow(device, allocator, tex_count)?;
+        }
+        let slot = self.free.pop().expect("grow added slots");
+        let chunk = &self.chunks[(slot / MATERIALS_PER_CHUNK) as usize];
+        let offset = (slot % MATERIALS_PER_CHUNK) as u64 * self.slot_size;
+        let params = MaterialParams::from(desc);
+        let src = bytemuck::bytes_of(&params);
+        let dst = chunk
+            .alloc
+            .mapped_ptr()
+            .ok_or_else(|| anyhow!("material parameter buffer not host-mapped"))?
+            .as_ptr() as *mut u8;
+        unsafe {
+            std::ptr::copy_nonoverlapping(src.as_ptr(), dst.add(offset as usize), src.len());
+        }
+        Ok(slot)
+    }
+
+    /// Return a slot; only once the GPU is done with it (see drain_trash).
+    pub(crate) fn free(&mut self, slot: u32) {
+        self.free.push(slot);
+    }
+
+    pub(crate) fn set(&self, slot: u32) -> vk::DescriptorSet {
+        self.chunks[(slot / MATERIALS_PER_CHUNK) as usize].sets
+            [(slot % MATERIALS_PER_CHUNK) as usize]
+    }
+
+    /// Write `slot`'s own entry for bindless `index` with an override
+    /// sampler, remembered until take_sampler_override.
+    pub(crate) fn override_sampler(
+        &mut self,
+        device: &ash::Device,
+        slot: u32,
+        index: u32,
+        view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        write_material_descriptors(device, self.set(slot), index, view, sampler);
+        self.sampler_overrides.push((slot, index));
+    }
+
+    /// The bindless index a previous owner of `slot` overrode, if any; the
+    /// caller writes the texture's own sampler back.
+    pub(crate) fn take_sampler_override(&mut self, slot: u32) -> Option<u32> {
+        let i = self
+            .sampler_overrides
+            .iter()
+            .position(|&(s, _)| s == slot)?;
+        Some(self.sampler_overrides.swap_remove(i).1)
+    }
+
+    /// Register a texture at bindless `index` in every set, live or free
+    /// (free ones may be handed out later).
+    pub(crate) fn write_texture(
+        &self,
+        device: &ash::Device,
+        index: u32,
+        view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        for &set in self.chunks.iter().flat_map(|c| &c.sets) {
+            write_material_descriptors(device, set, index, view, sampler);
+        }
+    }
+
+    /// Point binding 2 of every set, live or free, at the block texture
+    /// array.
+    pub(crate) fn write_block_array(
+        &mut self,
+        device: &ash::Device,
+        view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        for &set in self.chunks.iter().flat_map(|c| &c.sets) {
+            write_block_array_descriptor(device, set, view, sampler);
+        }
+        self.block_array = true;
+    }
+
+    fn grow(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        tex_count: u32,
+    ) -> Result<()> {
+        let (params, alloc) = create_buffer_and_memory(
+            device,
+            allocator,
+            self.slot_size * MATERIALS_PER_CHUNK as u64,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            "material parameters",
+        )?;
+        // Added before anything else can fail, so destroy() cleans up.
+        self.chunks.push(MaterialChunk {
+            params,
+            alloc,
+            sets: Vec::new(),
+        });
+
+        let sets =
+            self.descriptors
+                .allocate_many(device, self.layout, MATERIALS_PER_CHUNK as usize)?;
+
+        let infos: Vec<_> = (0..MATERIALS_PER_CHUNK as u64)
+            .map(|i| vk::DescriptorBufferInfo {
+                buffer: params,
+                offset: i * self.slot_size,
+                range: std::mem::size_of::<MaterialParams>() as u64,
+            })
+            .collect();
+        let writes: Vec<_> = sets
+            .iter()
+            .zip(&infos)
+            .map(|(&set, info)| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 1,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                p_buffer_info: info,
+                ..Default::default()
+            })
+            .collect();
+        // The textures uploaded so far, from the first chunk's slot 0
+        // (empty for the first chunk itself: nothing's uploaded yet).
+        // The block array likewise, once there is one.
+        let mut copies = Vec::new();
+        if let Some(&src) = self.chunks.first().and_then(|c| c.sets.first()) {
+            for &dst in &sets {
+                if tex_count > 0 {
+                    copies.push(vk::CopyDescriptorSet {
+                        s_type: vk::StructureType::COPY_DESCRIPTOR_SET,
+                        src_set: src,
+                        src_binding: 0,
+                        dst_set: dst,
+                        dst_binding: 0,
+                        descriptor_count: tex_count,
+                        ..Default::default()
+                    });
+                }
+                if self.block_array {
+                    copies.push(vk::CopyDescriptorSet {
+                        s_type: vk::StructureType::COPY_DESCRIPTOR_SET,
+                        src_set: src,
+                        src_binding: 2,
+                        dst_set: dst,
+                        dst_binding: 2,
+                        descriptor_count: 1,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        unsafe { device.update_descriptor_sets(&writes, &copies) };
+
+        let base = (self.chunks.len() as u32 - 1) * MATERIALS_PER_CHUNK;
+        // Popped from the back, so the lowest slot goes first.
+        self.free.extend((base..base + MATERIALS_PER_CHUNK).rev());
+        self.chunks.last_mut().expect("pushed above").sets = sets;
+        Ok(())
+    }
+
+    /// Caller must have idled the device. Sets go with their pools.
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        for chunk in self.chunks.drain(..) {
+            unsafe { device.destroy_buffer(chunk.params, None) };
+            let _ = allocator.free(chunk.alloc);
+        }
+        self.descriptors.destroy(device);
+        self.free.clear();
+        self.sampler_overrides.clear();
+        self.block_array = false;
+    }
+}
+
+impl VkRenderer {
+    /// Create a material. Its pipeline must be DEFAULT or registered, its
+    /// textures (if any) indices upload_texture returned, and a sampler
+    /// override needs an albedo to apply to. A MetallicRoughness material
+    /// on DEFAULT is moved to the "pbr" pipeline, registering it if this is
+    /// the first (material_desc reports the pipeline it got).
+    pub fn create_material(&mut self, mut desc: MaterialDesc) -> RenderResult<MaterialHandle> {
+        let textures = [
+            ("albedo", desc.albedo),
+            ("normal", desc.normal),
+            ("metallic-roughness", desc.metallic_roughness),
+            ("occlusion", desc.occlusion),
+            ("emissive", desc.emissive),
+        ];
+        for (name, index) in textures {
+            if let Some(index) = index.filter(|&i| i >= self.next_tex_index) {
+                return Err(
+                    anyhow!("create_material: {name} {index} is not an uploaded texture").into(),
+                );
+            }
+        }
+        if desc.block_array && self.block_textures.is_none() {
+            return Err(
+                anyhow!("create_material: block_array before upload_block_textures").into(),
+            );
+        }
+        if desc.shading == ShadingModel::MetallicRoughness
+            && desc.pipeline == PipelineHandle::DEFAULT
+        {
+            desc.pipeline = match self.pipeline_by_name(PBR_PIPELINE) {
+                Some(pipeline) => pipeline,
+                None => self.register_pipeline(PipelineDesc::pbr(PBR_PIPELINE))?,
+            };
+        }
+        if desc.pipeline != PipelineHandle::DEFAULT
+            && self
+                .named_pipelines
+                .get(desc.pipeline.0 as usize - 1)
+                .is_none()
+        {
+            return Err(anyhow!("create_material: unknown pipeline {:?}", desc.pipeline).into());
+        }
+        let sampler = match (desc.sampler, desc.albedo) {
+            (Some(s), Some(albedo)) => Some((albedo, self.samplers.get(&self.device, &s)?)),
+            (Some(_), None) => {
+                return Err(
+                    anyhow!("create_material: sampler override without an albedo texture").into(),
+                )
+            }
+            (None, _) => None,
+        };
+        let slot = self.material_pool.alloc(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            &desc,
+            self.next_tex_index,
+        )?;
+        if let Some(index) = self.material_pool.take_sampler_override(slot) {
+            let (view, own) = self.texture_binding(index);
+            write_material_descriptors(
+                &self.device,
+                self.material_pool.set(slot),
+                index,
+                view,
+                own,
+            );
+        }
+        if let Some((albedo, sampler)) = sampler {
+            let (view, _) = self.texture_binding(albedo);
+            self.material_pool
+                .override_sampler(&self.device, slot, albedo, view, sampler);
+        }
+        let material = Some(Material { desc, slot });
+        if let Some(i) = self.materials.iter().position(Option::is_none) {
+            self.materials[i] = material;
+            return Ok(MaterialHandle(i as u32));
+        }
+        self.materials.push(material);
+        Ok(MaterialHandle(self.materials.len() as u32 - 1))
+    }
+
+    /// Destroy a material; its set is recycled once in-flight frames are
+    /// done with it. DEFAULT can't be destroyed.
+    pub fn destroy_material(&mut self, handle: MaterialHandle) {
+        if handle == MaterialHandle::DEFAULT {
+            return;
+        }
+        if let Some(m) = self
+            .materials
+            .get_mut(handle.0 as usize)
+            .and_then(Option::take)
+        {
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::MaterialSlot(m.slot),
+            });
+        }
+    }
+
+    pub fn material_desc(&self, handle: MaterialHandle) -> Option<MaterialDesc> {
+        self.materials
+            .get(handle.0 as usize)
+            .and_then(Option::as_ref)
+            .map(|m| m.desc)
+    }
+
+    /// Set 1 for a draw with `material`; unknown handles get DEFAULT's.
+    pub(crate) fn material_set(&self, material: MaterialHandle) -> vk::DescriptorSet {
+        let slot = self
+            .materials
+            .get(material.0 as usize)
+            .and_then(Option::as_ref)
+            .map_or(0, |m| m.slot);
+        self.material_pool.set(slot)
+    }
+
+    /// Recreate materials after device loss at the same handles (see
+    /// device_lost.rs); entry 0 is DEFAULT, already there. Holes are filled
+    /// and freed again so later handles keep their index; so is a material
+    /// that fails to come back (its pipeline, say), with an error logged.
+    pub(crate) fn restore_materials(&mut self, descs: Vec<Option<MaterialDesc>>) {
+        let mut holes = Vec::new();
+        for (i, desc) in descs.into_iter().enumerate().skip(1) {
+            let handle = MaterialHandle(i as u32);
+            let created = match desc {
+                Some(desc) => self.create_material(desc).or_else(|e| {
+                    tracing::error!("vk: material {i} not restored after device loss: {e:#}");
+                    holes.push(handle);
+                    self.create_material(MaterialDesc::default())
+                }),
+                None => {
+                    holes.push(handle);
+                    self.create_material(MaterialDesc::default())
+                }
+            };
+            if let Err(e) = created {
+                tracing::error!("vk: materials from {i} on not restored after device loss: {e:#}");
+                break;
+            }
+        }
+        for h in holes {
+            self.destroy_material(h);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! GPU memory accounting: what the renderer's allocations add up to, by
+//! category, against the per-heap budget the driver reports through
+//! VK_EXT_memory_budget (where available).
+//!
+//! Categories come from the allocation names every create_*_and_memory
+//! call already passes to gpu-allocator, so there's no second bookkeeping
+//! path to keep in sync with frees; a new allocation site only needs a
+//! name `MemoryCategory::of_allocation` knows (anything else is Other).
+//! Swapchain images are the driver's, not ours, so their share is an
+//! estimate from the extent and format. egui's private allocator isn't
+//! counted.
+
+use std::time::{Duration, Instant};
+
+use ash::vk;
+use tracing::{info, warn};
+
+use crate::VkRenderer;
+
+/// How often render() logs a summary (and checks for budget pressure).
+const MEMORY_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Warn once a device-local heap's usage crosses this share of its budget.
+const BUDGET_WARN_FRACTION: f64 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryCategory {
+    Swapchain,
+    Depth,
+    /// Offscreen colour/shadow targets (HDR scene target, shadow map).
+    RenderTarget,
+    Mesh,
+    Texture,
+    Uniform,
+    Staging,
+    Other,
+}
+
+impl MemoryCategory {
+    pub const ALL: [Self; 8] = [
+        Self::Swapchain,
+        Self::Depth,
+        Self::RenderTarget,
+        Self::Mesh,
+        Self::Texture,
+        Self::Uniform,
+        Self::Staging,
+        Self::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Swapchain => "swapchain",
+            Self::Depth => "depth",
+            Self::RenderTarget => "render targets",
+            Self::Mesh => "mesh",
+            Self::Texture => "texture",
+            Self::Uniform => "uniform",
+            Self::Staging => "staging",
+            Self::Other => "other",
+        }
+    }
+
+    /// Category of an allocation by the name it was created with.
+    fn of_allocation(name: &str) -> Self {
+        match name {
+            "depth image" => Self::Depth,
+            "shadow map"
+            | "hdr scene target"
+            | "upscale target"
+            | "render target"
+            | "render target depth"
+            | "object id target"
+            | "stereo target"
+            | "stereo target depth" => Self::RenderTarget,
+            "shared mesh vertex buffer" | "shared mesh index buffer" => Self::Mesh,
+            "uploaded texture"
+            | "uploaded cubemap"
+            | "irradiance map"
+            | "prefiltered environment"
+            | "brdf lut" => Self::Texture,
+            "material parameters" => Self::Uniform,
+            "staging belt" | "texture upload staging" | "transfer upload staging" => Self::Staging,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// One Vulkan memory heap.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapStats {
+    pub device_local: bool,
+    pub size: u64,
+    /// What the driver says this process can use before things start to
+    /// page or fail (VK_EXT_memory_budget); None without the extension.
+    pub budget: Option<u64>,
+    /// This process's current usage of the heap, all allocators included;
+    /// None without the extension.
+    pub usage: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStats {
+    pub heaps: Vec<HeapStats>,
+    /// Live bytes per category, in `MemoryCategory::ALL` order.
+    pub categories: Vec<(MemoryCategory, u64)>,
+    /// Bytes in live allocations, and in the device memory blocks the
+    /// allocator has reserved to hold them.
+    pub allocated: u64,
+    pub reserved: u64,
+}
+
+impl MemoryStats {
+    pub fn category(&self, category: MemoryCategory) -> u64 {
+        self.categories
+            .iter()
+            .find(|(c, _)| *c == category)
+            .map_or(0, |&(_, bytes)| bytes)
+    }
+}
+
+/// VK_EXT_memory_budget support, checked once at startup. Only the
+/// physical-device query is used, which needs the extension supported
+/// rather than enabled.
+pub(crate) fn has_memory_budget(instance: &ash::Instance, phys: vk::PhysicalDevice) -> bool {
+    let Ok(exts) = (unsafe { instance.enumerate_device_extension_properties(phys) }) else {
+        return false;
+    };
+    exts.iter().any(|e| unsafe {
+        std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) == ash::ext::memory_budget::NAME
+    })
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+impl VkRenderer {
+    /// Current GPU memory picture: per-heap budget and usage, and what the
+    /// renderer's own allocations add up to by category. Walks every live
+    /// allocation, so meant for overlays and periodic checks rather than
+    /// every frame.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut props2 = vk::PhysicalDeviceMemoryProperties2 {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_MEMORY_PROPERTIES_2,
+            ..Default::default()
+        };
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT,
+            ..Default::default()
+        };
+        if self.has_memory_budget {
+            props2.p_next = (&mut budget) as *mut _ as *mut _;
+        }
+        unsafe {
+            self.instance
+                .get_physical_device_memory_properties2(self.phys, &mut props2)
+        };
+        let props = props2.memory_properties;
+        let heaps = props.memory_heaps[..props.memory_heap_count as usize]
+            .iter()
+            .enumerate()
+            .map(|(i, heap)| HeapStats {
+                device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                size: heap.size,
+                budget: self.has_memory_budget.then_some(budget.heap_budget[i]),
+                usage: self.has_memory_budget.then_some(budget.heap_usage[i]),
+            })
+            .collect();
+
+        let mut categories: Vec<(MemoryCategory, u64)> =
+            MemoryCategory::ALL.iter().map(|&c| (c, 0)).collect();
+        let mut add = |category: MemoryCategory, bytes: u64| {
+            categories[category as usize].1 += bytes;
+        };
+        let (mut allocated, mut reserved) = (0, 0);
+        if let Some(allocator) = self.allocator.as_ref() {
+            let report = allocator.generate_report();
+            for a in &report.allocations {
+                add(MemoryCategory::of_allocation(&a.name), a.size);
+                allocated += a.size;
+            }
+            reserved = report.blocks.iter().map(|b| b.size).sum();
+        }
+        let bytes_per_pixel = match self.format {
+            vk::Format::R16G16B16A16_SFLOAT => 8,
+            _ => 4,
+        };
+        add(
+            MemoryCategory::Swapchain,
+            self.extent.width as u64
+                * self.extent.height as u64
+                * bytes_per_pixel
+                * self.images.len() as u64,
+        );
+
+        MemoryStats {
+            heaps,
+            categories,
+            allocated,
+            reserved,
+        }
+    }
+
+    /// Called every frame; logs memory_stats once per MEMORY_LOG_INTERVAL,
+    /// and warns when a device-local heap is close to its budget.
+    pub(crate) fn log_memory_stats_periodically(&mut self) {
+        let now = Instant::now();
+        if now < self.memory_log_at {
+            return;
+        }
+        self.memory_log_at = now + MEMORY_LOG_INTERVAL;
+
+        let stats = self.memory_stats();
+        let by_category: Vec<String> = stats
+            .categories
+            .iter()
+            .filter(|&&(_, bytes)| bytes > 0)
+            .map(|&(c, bytes)| format!("{} {:.1}", c.name(), mib(bytes)))
+            .collect();
+        info!(
+            "vk memory: {:.1} MiB allocated in {:.1} MiB of blocks; MiB by category: {}",
+            mib(stats.allocated),
+            mib(stats.reserved),
+            by_category.join(", ")
+        );
+        for (i, heap) in stats.heaps.iter().enumerate() {
+            let (Some(budget), Some(usage)) = (heap.budget, heap.usage) else {
+                continue;
+            };
+            if heap.device_local && usage as f64 > budget as f64 * BUDGET_WARN_FRACTION {
+                warn!(
+                    "vk memory: heap {i} at {:.1} of {:.1} MiB budget",
+                    mib(usage),
+                    mib(budget)
+                );
+            }
+        }
+    }
+}
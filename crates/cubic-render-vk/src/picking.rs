@@ -0,0 +1,443 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Object picking: an R32_UINT id target the size of the scene (the
+//! swapchain's scaled by the render scale), and pick(x, y) to read one
+//! texel of it back.
+//!
+//! While picking is on (set_picking), every frame adds an "object ids" pass
+//! right after the scene. It draws the same queued draws from the same
+//! camera with tri.vert and object_id.frag, writing each draw's ObjectId
+//! (draw_mesh_with_id; 0 for untagged draws) under its own depth test, so
+//! the nearest surface's id wins exactly as its colour does. It's a second
+//! pass rather than a second scene attachment so registered pipelines'
+//! shaders don't have to write ids. The catch is that draws on pipelines
+//! with their own vertex layout aren't in it, and vertex shaders that move
+//! geometry aren't either.
+//!
+//! The graph leaves the target in TRANSFER_SRC_OPTIMAL after each frame.
+//! pick waits for the last submitted frame, copies the texel into a
+//! host-visible buffer and waits for that too. That's a full stall, which
+//! is fine for a click in an editor but not for every frame.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use cubic_render::{PushData, RenderResult};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::DescriptorAllocator;
+use crate::error::vk_error;
+use crate::frame_graph::{Access, FrameGraph, LoadOp, ResourceId};
+use crate::pipeline::{create_pipeline, PipelineConfig, PipelineDesc};
+use crate::resources::{
+    create_buffer_and_memory, create_color_target, depth_attachment_layout, submit_one_time,
+    MAX_INDIRECT_DRAWS,
+};
+use crate::{DeferredDrop, GpuResource, RetiredPass, VkRenderer};
+
+const ID_FORMAT: vk::Format = vk::Format::R32_UINT;
+
+/// What draw_mesh_with_id tags a draw with, and pick reports. 0 is
+/// reserved for "no object".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId(pub u32);
+
+pub(crate) struct Picking {
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    // object_id.frag's block, as reflected: the part of PushData pushed.
+    push_constants: Option<vk::PushConstantRange>,
+    readback: vk::Buffer,
+    readback_alloc: Allocation,
+    // A frame has drawn ids into `image` since it was created, so it's in
+    // TRANSFER_SRC_OPTIMAL with something to read.
+    drawn: bool,
+}
+
+fn create_id_target(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    extent: vk::Extent2D,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    create_color_target(
+        device,
+        allocator,
+        extent,
+        ID_FORMAT,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        "object id target",
+    )
+}
+
+impl Picking {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        cache: vk::PipelineCache,
+        cfg: &PipelineConfig,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let (layout, pipeline, reflected) = create_pipeline(
+            device,
+            cache,
+            &PipelineConfig {
+                color_format: ID_FORMAT,
+                ..*cfg
+            },
+            &PipelineDesc {
+                fragment_shader: "object_id.frag.spv".to_owned(),
+                ..PipelineDesc::opaque("object ids")
+            },
+        )?;
+        let destroy_pipeline = || unsafe {
+            device.destroy_pipeline(pipeline, None);
+            device.destroy_pipeline_layout(layout, None);
+        };
+        let (readback, readback_alloc) = match create_buffer_and_memory(
+            device,
+            allocator,
+            std::mem::size_of::<u32>() as u64,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+            "pick readback",
+        ) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                destroy_pipeline();
+                return Err(e);
+            }
+        };
+        let (image, alloc, view) = match create_id_target(device, allocator, extent) {
+            Ok(target) => target,
+            Err(e) => {
+                destroy_pipeline();
+                unsafe { device.destroy_buffer(readback, None) };
+                let _ = allocator.free(readback_alloc);
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            image,
+            alloc,
+            view,
+            extent,
+            layout,
+            pipeline,
+            push_constants: reflected.push_constants,
+            readback,
+            readback_alloc,
+            drawn: false,
+        })
+    }
+
+    /// New id target at `extent`. Returns the old one, which frames in
+    /// flight may still draw into, for the caller to retire.
+    fn resize(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+    ) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+        let (image, alloc, view) = create_id_target(device, allocator, extent)?;
+        let old = (
+            std::mem::replace(&mut self.image, image),
+            std::mem::replace(&mut self.alloc, alloc),
+            std::mem::replace(&mut self.view, view),
+        );
+        self.extent = extent;
+        self.drawn = false;
+        Ok(old)
+    }
+
+    pub(crate) fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.destroy_buffer(self.readback, None);
+        }
+        let _ = allocator.free(self.alloc);
+        let _ = allocator.free(self.readback_alloc);
+    }
+}
+
+impl RetiredPass for Picking {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        _descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device, allocator);
+    }
+}
+
+impl VkRenderer {
+    /// Turn the object-id pass (see module docs) on or off. Fails if
+    /// object_id.frag isn't built.
+    pub fn set_picking(&mut self, enabled: bool) -> RenderResult<()> {
+        if enabled == self.picking.is_some() {
+            return Ok(());
+        }
+        if let Some(old) = self.picking.take() {
+            self.retire_pass(old);
+            return Ok(());
+        }
+        let extent = self.scene_extent();
+        let config = self.pipeline_config();
+        self.picking = Some(Picking::new(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.pipeline_cache,
+            &config,
+            extent,
+        )?);
+        Ok(())
+    }
+
+    pub fn picking(&self) -> bool {
+        self.picking.is_some()
+    }
+
+    /// The ObjectId drawn at pixel (`x`, `y`) of the swapchain image (0, 0
+    /// at the top left) by the last rendered frame: None over untagged
+    /// draws and the sky, off-screen or on a letterbox bar, and before
+    /// picking's first frame. Blocks until the GPU has finished that frame.
+    /// Errors if picking is off.
+    pub fn pick(&mut self, x: u32, y: u32) -> RenderResult<Option<ObjectId>> {
+        let Some(picking) = self.picking.as_ref() else {
+            return Err(anyhow!("pick: picking is off (see set_picking)").into());
+        };
+        // Swapchain pixel to id texel, for a scene letterboxed or rendered
+        // below output resolution.
+        let area = self.output_area();
+        let (Some(x), Some(y)) = (
+            x.checked_sub(area.offset.x as u32),
+            y.checked_sub(area.offset.y as u32),
+        ) else {
+            return Ok(None);
+        };
+        if !picking.drawn || x >= area.extent.width || y >= area.extent.height {
+            return Ok(None);
+        }
+        let x = (x as u64 * picking.extent.width as u64 / area.extent.width as u64) as u32;
+        let y = (y as u64 * picking.extent.height as u64 / area.extent.height as u64) as u32;
+        if self.timeline_value > 0 {
+            let wait_info = vk::SemaphoreWaitInfo {
+                s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+                semaphore_count: 1,
+                p_semaphores: &self.timeline,
+                p_values: &self.timeline_value,
+                ..Default::default()
+            };
+            unsafe {
+                self.device
+                    .wait_semaphores(&wait_info, u64::MAX)
+                    .map_err(|e| vk_error(e, "wait_semaphores"))?
+            };
+        }
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            },
+            image_extent: vk::Extent3D {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+        };
+        // The copy's write has to be made visible to the host read below.
+        let to_host = vk::MemoryBarrier2 {
+            s_type: vk::StructureType::MEMORY_BARRIER_2,
+            src_stage_mask: vk::PipelineStageFlags2::COPY,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags2::HOST,
+            dst_access_mask: vk::AccessFlags2::HOST_READ,
+            ..Default::default()
+        };
+        let dep = vk::DependencyInfo {
+            s_type: vk::StructureType::DEPENDENCY_INFO,
+            memory_barrier_count: 1,
+            p_memory_barriers: &to_host,
+            ..Default::default()
+        };
+        let device = &self.device;
+        submit_one_time(device, self.queue, self.cmd_pool, |cmd| unsafe {
+            device.cmd_copy_image_to_buffer(
+                cmd,
+                picking.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                picking.readback,
+                std::slice::from_ref(&region),
+            );
+            device.cmd_pipeline_barrier2(cmd, &dep);
+        })?;
+        let bytes = picking
+            .readback_alloc
+            .mapped_slice()
+            .ok_or_else(|| anyhow!("pick: read-back buffer isn't mapped"))?;
+        let id = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Ok((id != 0).then_some(ObjectId(id)))
+    }
+
+    /// Rebuild the id target at the scene's new extent, retiring the old
+    /// one through the trash queue. Called from recreate_swapchain (after
+    /// sync_tonemap_pass, which settles the scene extent); on failure
+    /// picking is turned off.
+    pub(crate) fn sync_picking(&mut self) {
+        let extent = self.scene_extent();
+        let Some(picking) = self.picking.as_mut() else {
+            return;
+        };
+        if picking.extent == extent {
+            return;
+        }
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        match picking.resize(&self.device, allocator, extent) {
+            Ok((image, alloc, view)) => {
+                self.trash.push(DeferredDrop {
+                    value: self.timeline_value,
+                    resource: GpuResource::ImageView(view),
+                });
+                self.trash.push(DeferredDrop {
+                    value: self.timeline_value,
+                    resource: GpuResource::Image { image, alloc },
+                });
+            }
+            Err(e) => {
+                tracing::warn!("picking turned off: id target not rebuilt ({e:#})");
+                if let Some(picking) = self.picking.take() {
+                    self.retire_pass(picking);
+                }
+            }
+        }
+    }
+
+    /// The "object ids" pass, after the scene: clears and redraws `depth`
+    /// (which nothing reads after the scene) alongside the id target;
+    /// `buffers` are what the scene pass reads besides its attachments.
+    pub(crate) fn add_object_id_pass(
+        &self,
+        g: &mut FrameGraph,
+        frame: usize,
+        depth: ResourceId,
+        buffers: &[(ResourceId, Access)],
+    ) {
+        let Some(picking) = self.picking.as_ref() else {
+            return;
+        };
+        // Rewritten whole every frame; only the last pick's copy has to be
+        // done with it first.
+        let ids = g.import_image(
+            "object ids",
+            picking.image,
+            picking.view,
+            vk::ImageAspectFlags::COLOR,
+            Access::stale(vk::PipelineStageFlags2::COPY),
+        );
+        g.export(ids, Some(Access::transfer_read()));
+        let none = vk::ClearValue {
+            color: vk::ClearColorValue { uint32: [0; 4] },
+        };
+        let depth_clear = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 0.0,
+                stencil: 0,
+            },
+        };
+        let depth_layout = depth_attachment_layout(self.depth_format);
+        let mut uses = vec![
+            (ids, Access::color_attachment(LoadOp::Clear(none))),
+            (
+                depth,
+                Access::depth_attachment(LoadOp::Clear(depth_clear), false, depth_layout),
+            ),
+        ];
+        uses.extend_from_slice(buffers);
+        g.add_pass_at("object ids", picking.extent, &uses, move |r, cmd| {
+            r.record_object_ids(cmd, frame);
+            if let Some(picking) = r.picking.as_mut() {
+                picking.drawn = true;
+            }
+            Ok(())
+        });
+    }
+
+    /// Every queued draw on the standard vertex layout, with the id
+    /// pipeline, once per scene view (the id target is the scene's size);
+    /// first_instance is the candidate slot as in the scene, and the
+    /// ObjectId rides in PushData's padding.
+    fn record_object_ids(&self, cmd: vk::CommandBuffer, frame: usize) {
+        let Some(picking) = self.picking.as_ref() else {
+            return;
+        };
+        let sets = self.scene_sets(frame);
+        let end = self.pending_draws.len().min(MAX_INDIRECT_DRAWS as usize);
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, picking.pipeline);
+            self.device.cmd_bind_vertex_buffers(
+                cmd,
+                0,
+                std::slice::from_ref(&self.shared_vbuf),
+                &[0],
+            );
+            self.device
+                .cmd_bind_index_buffer(cmd, self.shared_ibuf, 0, vk::IndexType::UINT32);
+        }
+        for view in self.scene_views() {
+            self.bind_scene_view(cmd, picking.layout, &sets, &view);
+            for (i, draw) in self.pending_draws[..end].iter().enumerate() {
+                if draw.culled {
+                    continue;
+                }
+                let Some(mesh) = self.meshes.get(draw.mesh.0 as usize) else {
+                    continue;
+                };
+                if mesh.index_count == 0 || mesh.layout != 0 {
+                    continue;
+                }
+                let push = PushData {
+                    _pad: [draw.object.0, 0, 0],
+                    ..draw.push
+                };
+                unsafe {
+                    if let Some(range) = picking.push_constants {
+                        let bytes = bytemuck::bytes_of(&push);
+                        self.device.cmd_push_constants(
+                            cmd,
+                            picking.layout,
+                            range.stage_flags,
+                            range.offset,
+                            &bytes[range.offset as usize..(range.offset + range.size) as usize],
+                        );
+                    }
+                    self.device.cmd_draw_indexed(
+                        cmd,
+                        mesh.index_count,
+                        1,
+                        mesh.first_index,
+                        mesh.first_vertex,
+                        i as u32,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,19 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use ash::util::read_spv;
 use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_render::{VertexFormat, VertexLayout};
+
+use crate::reflect::{
+    check_bindings, check_push_constants, check_specialization, merge_layout, reflect,
+    vertex_attributes, ReflectedLayout, ScalarKind, ShaderInterface,
+};
+use crate::resources::{
+    camera_set_bindings, indirect_graphics_set_binding, material_set_bindings, TextureArrayCaps,
+};
+use std::collections::BTreeMap;
 use std::io::Cursor;
 #[cfg(debug_assertions)]
 use std::time::SystemTime;
@@ -14,6 +25,10 @@ pub(crate) struct ShaderDev {
     pub(crate) frag_spv: PathBuf,
     pub(crate) vert_mtime: SystemTime,
     pub(crate) frag_mtime: SystemTime,
+    // GLSL sources recompiled into the .spv files above when edited (see
+    // shader_compile); absent without the runtime-shader-compile feature.
+    #[cfg(feature = "runtime-shader-compile")]
+    pub(crate) glsl_sources: Vec<(PathBuf, SystemTime)>,
 }
 
 pub(crate) fn load_spv_file(path: &Path) -> Result<Vec<u32>> {
@@ -31,43 +46,110 @@ pub(crate) fn shader_dir() -> PathBuf {
     }
 }
 
-fn hex_bytes(b: &[u8]) -> String {
-    let mut s = String::with_capacity(b.len() * 2);
-    for x in b {
-        use std::fmt::Write as _;
-        let _ = write!(&mut s, "{:02x}", x);
+/// Where the pipeline cache for this GPU lives: the platform cache
+/// directory (~/.cache/cubic on Linux, %LOCALAPPDATA%\cubic on Windows,
+/// ~/Library/Caches/cubic on macOS), or CUBIC_CACHE_DIR if set. Only falls
+/// back to the working directory on platforms with no cache directory at
+/// all. One file per vendor/device so switching GPUs doesn't throw the
+/// other's cache away; driver updates are caught by the header instead.
+pub(crate) fn pipeline_cache_path(props: &vk::PhysicalDeviceProperties) -> PathBuf {
+    let dir = match std::env::var_os("CUBIC_CACHE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::cache_dir()
+            .map(|d| d.join("cubic"))
+            .unwrap_or_default(),
+    };
+    dir.join(format!(
+        "vk_pipeline_cache_{:04x}_{:04x}.bin",
+        props.vendor_id, props.device_id
+    ))
+}
+
+const PIPELINE_CACHE_MAGIC: [u8; 8] = *b"CUBICPC1";
+
+/// Prefix of a saved pipeline cache file. Vulkan's own cache header only
+/// lets the driver reject data from another device/driver; this also
+/// catches engine upgrades (whose pipelines would mostly miss anyway) and
+/// truncated writes before the driver ever sees the blob.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Zeroable, Pod)]
+struct PipelineCacheHeader {
+    magic: [u8; 8],
+    // CARGO_PKG_VERSION, zero-padded (truncated if ever longer).
+    engine_version: [u8; 16],
+    vendor_id: u32,
+    device_id: u32,
+    driver_version: u32,
+    // Length of the driver blob that follows.
+    data_len: u32,
+    pipeline_cache_uuid: [u8; vk::UUID_SIZE],
+}
+
+impl PipelineCacheHeader {
+    fn new(props: &vk::PhysicalDeviceProperties, data_len: usize) -> Self {
+        let mut engine_version = [0u8; 16];
+        let v = env!("CARGO_PKG_VERSION").as_bytes();
+        let n = v.len().min(engine_version.len());
+        engine_version[..n].copy_from_slice(&v[..n]);
+        Self {
+            magic: PIPELINE_CACHE_MAGIC,
+            engine_version,
+            vendor_id: props.vendor_id,
+            device_id: props.device_id,
+            driver_version: props.driver_version,
+            data_len: data_len as u32,
+            pipeline_cache_uuid: props.pipeline_cache_uuid,
+        }
     }
-    s
 }
 
-pub(crate) fn pipeline_cache_path(props: &vk::PhysicalDeviceProperties) -> PathBuf {
-    // Keep it simple: local file next to the binary.
-    // You can switch to a platform cache dir later.
-    let uuid = hex_bytes(&props.pipeline_cache_uuid);
-    PathBuf::from(format!(
-        "vk_pipeline_cache_{:04x}_{:04x}_{:08x}_{}.bin",
-        props.vendor_id, props.device_id, props.driver_version, uuid
-    ))
+/// The driver blob in a saved cache file, if its header matches this
+/// engine build and device.
+fn validated_cache_data<'a>(
+    bytes: &'a [u8],
+    props: &vk::PhysicalDeviceProperties,
+) -> Result<&'a [u8], &'static str> {
+    let header_len = std::mem::size_of::<PipelineCacheHeader>();
+    if bytes.len() < header_len {
+        return Err("too short for a header");
+    }
+    let (head, data) = bytes.split_at(header_len);
+    let header: PipelineCacheHeader = bytemuck::pod_read_unaligned(head);
+    if header.magic != PIPELINE_CACHE_MAGIC {
+        return Err("not a cubic pipeline cache");
+    }
+    if header != PipelineCacheHeader::new(props, data.len()) {
+        return Err("written by another engine version, driver or device, or truncated");
+    }
+    Ok(data)
 }
 
 pub(crate) fn create_or_load_pipeline_cache(
     device: &ash::Device,
-    path: &PathBuf,
+    props: &vk::PhysicalDeviceProperties,
+    path: &Path,
 ) -> Result<vk::PipelineCache> {
-    let (p_initial_data, initial_size);
-    let data = fs::read(path).ok();
-    if let Some(ref bytes) = data {
-        p_initial_data = bytes.as_ptr() as *const std::ffi::c_void;
-        initial_size = bytes.len();
-    } else {
-        p_initial_data = std::ptr::null();
-        initial_size = 0;
-    }
+    let file = fs::read(path).ok();
+    let data = match file
+        .as_deref()
+        .map(|bytes| validated_cache_data(bytes, props))
+    {
+        Some(Ok(data)) => data,
+        Some(Err(why)) => {
+            tracing::info!("vk: ignoring pipeline cache {:?}: {why}", path);
+            &[]
+        }
+        None => &[],
+    };
 
     let ci = vk::PipelineCacheCreateInfo {
         s_type: vk::StructureType::PIPELINE_CACHE_CREATE_INFO,
-        initial_data_size: initial_size,
-        p_initial_data,
+        initial_data_size: data.len(),
+        p_initial_data: if data.is_empty() {
+            std::ptr::null()
+        } else {
+            data.as_ptr() as *const std::ffi::c_void
+        },
         ..Default::default()
     };
     let cache = unsafe { device.create_pipeline_cache(&ci, None)? };
@@ -76,10 +158,11 @@ pub(crate) fn create_or_load_pipeline_cache(
 
 pub(crate) fn save_pipeline_cache(
     device: &ash::Device,
+    props: &vk::PhysicalDeviceProperties,
     cache: vk::PipelineCache,
-    path: &PathBuf,
+    path: &Path,
 ) -> Result<()> {
-    let bytes = match unsafe { device.get_pipeline_cache_data(cache) } {
+    let data = match unsafe { device.get_pipeline_cache_data(cache) } {
         Ok(b) => b,
         Err(_) => return Ok(()),
     };
@@ -87,7 +170,15 @@ pub(crate) fn save_pipeline_cache(
     if let Some(parent) = path.parent() {
         let _ = fs::create_dir_all(parent);
     }
-    fs::write(path, &bytes)?;
+    let header = PipelineCacheHeader::new(props, data.len());
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(&header) + data.len());
+    bytes.extend_from_slice(bytemuck::bytes_of(&header));
+    bytes.extend_from_slice(&data);
+    // Write-then-rename, so a crash mid-write leaves the old file intact
+    // rather than a torn one.
+    let tmp = path.with_extension("bin.tmp");
+    fs::write(&tmp, &bytes)?;
+    fs::rename(&tmp, path)?;
     Ok(())
 }
 
@@ -101,13 +192,399 @@ pub(crate) struct PipelineConfig {
     pub(crate) set_layout_camera: vk::DescriptorSetLayout,
     pub(crate) set_layout_material: vk::DescriptorSetLayout,
     pub(crate) set_layout_indirect_graphics: vk::DescriptorSetLayout,
+    /// What the material layout was built with, for checking shaders
+    /// against its bindings (see reflect.rs).
+    pub(crate) tex_caps: TextureArrayCaps,
 }
 
+/// How a pipeline's color output is combined with what's already in the
+/// color attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// No blending; writes replace the attachment.
+    #[default]
+    Opaque,
+    /// Classic `src.a * src + (1 - src.a) * dst`.
+    Alpha,
+    /// `src.a * src + dst` — glow, particles, debug overlays.
+    Additive,
+}
+
+/// A specialization constant's value, for a shader's
+/// `layout(constant_id = N) const` of the same type. Each is 4 bytes, like
+/// SPIR-V's bool, int, uint and float.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecValue {
+    Bool(bool),
+    I32(i32),
+    U32(u32),
+    F32(f32),
+}
+
+impl SpecValue {
+    fn bytes(self) -> [u8; 4] {
+        match self {
+            Self::Bool(b) => vk::Bool32::from(b).to_ne_bytes(),
+            Self::I32(v) => v.to_ne_bytes(),
+            Self::U32(v) => v.to_ne_bytes(),
+            Self::F32(v) => v.to_ne_bytes(),
+        }
+    }
+
+    pub(crate) fn kind(self) -> ScalarKind {
+        match self {
+            Self::Bool(_) => ScalarKind::Bool,
+            Self::I32(_) => ScalarKind::Sint,
+            Self::U32(_) => ScalarKind::Uint,
+            Self::F32(_) => ScalarKind::Float,
+        }
+    }
+}
+
+impl From<bool> for SpecValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<i32> for SpecValue {
+    fn from(v: i32) -> Self {
+        Self::I32(v)
+    }
+}
+
+impl From<u32> for SpecValue {
+    fn from(v: u32) -> Self {
+        Self::U32(v)
+    }
+}
+
+impl From<f32> for SpecValue {
+    fn from(v: f32) -> Self {
+        Self::F32(v)
+    }
+}
+
+/// A specialization map packed for VkSpecializationInfo, which borrows it.
+pub(crate) struct SpecData {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl SpecData {
+    pub(crate) fn new(constants: &BTreeMap<u32, SpecValue>) -> Self {
+        let mut entries = Vec::with_capacity(constants.len());
+        let mut data = Vec::with_capacity(constants.len() * 4);
+        for (&constant_id, value) in constants {
+            entries.push(vk::SpecializationMapEntry {
+                constant_id,
+                offset: data.len() as u32,
+                size: 4,
+            });
+            data.extend_from_slice(&value.bytes());
+        }
+        Self { entries, data }
+    }
+
+    /// For a stage's p_specialization_info; entries for ids a stage
+    /// doesn't declare are ignored, so both stages can share it.
+    pub(crate) fn info(&self) -> vk::SpecializationInfo<'_> {
+        vk::SpecializationInfo {
+            map_entry_count: self.entries.len() as u32,
+            p_map_entries: self.entries.as_ptr(),
+            data_size: self.data.len(),
+            p_data: self.data.as_ptr().cast(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Everything that distinguishes one registered graphics pipeline from
+/// another (see `VkRenderer::register_pipeline`). Swapchain-derived state —
+/// color/depth formats and the descriptor set layouts — comes from
+/// `PipelineConfig` instead, so the same description can be rebuilt as-is
+/// when the swapchain format changes or shaders are hot-reloaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineDesc {
+    /// Lookup key for `VkRenderer::pipeline_by_name`; must be unique.
+    pub name: String,
+    /// SPIR-V file names, resolved against the shader directory
+    /// (assets/shaders/, or CUBIC_SHADER_DIR).
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+    pub topology: vk::PrimitiveTopology,
+    /// `LINE` needs the fillModeNonSolid device feature; registration fails
+    /// on devices without it rather than silently rendering filled.
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_back_faces: bool,
+    pub blend: BlendMode,
+    pub depth_write: bool,
+    /// Vertex buffer layout the vertex shader reads. Only meshes uploaded
+    /// with an equal layout (see `upload_mesh_with_layout`) are drawn with
+    /// this pipeline; `VertexLayout::standard()` is plain `upload_mesh`.
+    /// Every input the shader declares needs an attribute here of the same
+    /// numeric type, or registration fails; attributes it doesn't read are
+    /// skipped.
+    pub vertex_layout: VertexLayout,
+    /// No fixed-function vertex input: the vertex shader fetches its own
+    /// vertices from the shared vertex pool by buffer device address (see
+    /// `VkRenderer::mesh_pool_addresses`), reading vertex `gl_VertexIndex`
+    /// at `vertex_layout.stride` bytes apart. Draws are still indexed and
+    /// still matched to meshes by `vertex_layout`.
+    pub vertex_pulling: bool,
+    /// Specialization constants by constant_id, fixed when the pipeline is
+    /// built: variants of one shader (a feature on or off, a loop bound)
+    /// without a SPIR-V file each. Every id must be declared by one of the
+    /// two shaders, with the value's type. Unset ones keep the shader's
+    /// default.
+    pub specialization: BTreeMap<u32, SpecValue>,
+}
+
+impl PipelineDesc {
+    /// The scene's default opaque pipeline (tri.vert/tri.frag, back-face
+    /// culled, depth write on) that every other preset starts from.
+    pub fn opaque(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            vertex_shader: "tri.vert.spv".to_owned(),
+            fragment_shader: "tri.frag.spv".to_owned(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_back_faces: true,
+            blend: BlendMode::Opaque,
+            depth_write: true,
+            vertex_layout: VertexLayout::standard(),
+            vertex_pulling: false,
+            specialization: BTreeMap::new(),
+        }
+    }
+
+    /// The opaque preset with pbr.frag: metallic-roughness shading for materials that
+    /// ask for it (see `ShadingModel`), which get it by this name.
+    pub fn pbr(name: &str) -> Self {
+        Self {
+            fragment_shader: "pbr.frag.spv".to_owned(),
+            ..Self::opaque(name)
+        }
+    }
+
+    /// Alpha-blended and double-sided; depth-tested but not depth-written,
+    /// so it should be drawn after the opaque geometry it overlaps.
+    pub fn alpha_blend(name: &str) -> Self {
+        Self {
+            cull_back_faces: false,
+            blend: BlendMode::Alpha,
+            depth_write: false,
+            ..Self::opaque(name)
+        }
+    }
+
+    /// Triangle edges only, both faces.
+    pub fn wireframe(name: &str) -> Self {
+        Self {
+            polygon_mode: vk::PolygonMode::LINE,
+            cull_back_faces: false,
+            ..Self::opaque(name)
+        }
+    }
+
+    /// LINE_LIST topology: the mesh's index buffer is read as pairs.
+    pub fn lines(name: &str) -> Self {
+        Self {
+            topology: vk::PrimitiveTopology::LINE_LIST,
+            cull_back_faces: false,
+            ..Self::opaque(name)
+        }
+    }
+}
+
+impl Default for PipelineDesc {
+    fn default() -> Self {
+        Self::opaque("opaque")
+    }
+}
+
+pub(crate) fn vk_vertex_format(format: VertexFormat) -> vk::Format {
+    match format {
+        VertexFormat::Float32 => vk::Format::R32_SFLOAT,
+        VertexFormat::Float32x2 => vk::Format::R32G32_SFLOAT,
+        VertexFormat::Float32x3 => vk::Format::R32G32B32_SFLOAT,
+        VertexFormat::Float32x4 => vk::Format::R32G32B32A32_SFLOAT,
+        VertexFormat::Uint32 => vk::Format::R32_UINT,
+        VertexFormat::Uint32x2 => vk::Format::R32G32_UINT,
+        VertexFormat::Uint32x3 => vk::Format::R32G32B32_UINT,
+        VertexFormat::Uint32x4 => vk::Format::R32G32B32A32_UINT,
+        VertexFormat::Sint32 => vk::Format::R32_SINT,
+        VertexFormat::Unorm8x4 => vk::Format::R8G8B8A8_UNORM,
+        VertexFormat::Snorm8x4 => vk::Format::R8G8B8A8_SNORM,
+        VertexFormat::Uint8x4 => vk::Format::R8G8B8A8_UINT,
+        VertexFormat::Uint16x4 => vk::Format::R16G16B16A16_UINT,
+        VertexFormat::Unorm16x4 => vk::Format::R16G16B16A16_UNORM,
+        VertexFormat::Snorm16x4 => vk::Format::R16G16B16A16_SNORM,
+    }
+}
+
+fn blend_attachment(mode: BlendMode) -> vk::PipelineColorBlendAttachmentState {
+    let color_write_mask = vk::ColorComponentFlags::R
+        | vk::ColorComponentFlags::G
+        | vk::ColorComponentFlags::B
+        | vk::ColorComponentFlags::A;
+    match mode {
+        BlendMode::Opaque => vk::PipelineColorBlendAttachmentState {
+            color_write_mask,
+            blend_enable: vk::FALSE,
+            ..Default::default()
+        },
+        BlendMode::Alpha => vk::PipelineColorBlendAttachmentState {
+            color_write_mask,
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            alpha_blend_op: vk::BlendOp::ADD,
+        },
+        BlendMode::Additive => vk::PipelineColorBlendAttachmentState {
+            color_write_mask,
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ZERO,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE,
+            alpha_blend_op: vk::BlendOp::ADD,
+        },
+    }
+}
+
+/// The whole of `PushData` (96 bytes, well under the 128-byte guaranteed
+/// minimum): the most a scene pipeline's push constant block may span,
+/// since draws push theirs out of PushData (see record_direct_draws).
+pub(crate) fn push_data_range() -> vk::PushConstantRange {
+    vk::PushConstantRange {
+        stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+        offset: 0,
+        size: std::mem::size_of::<cubic_render::PushData>() as u32,
+    }
+}
+
+/// Reflect `words`, the shader file `file`, and check its bindings against
+/// the shared set layouts and its push constants against PushData.
+fn reflect_graphics_shader(
+    words: &[u32],
+    file: &str,
+    cfg: &PipelineConfig,
+) -> Result<ShaderInterface> {
+    let shader = reflect(words).with_context(|| format!("reflecting {file}"))?;
+    let camera = camera_set_bindings();
+    let material = material_set_bindings(cfg.tex_caps);
+    let indirect = [indirect_graphics_set_binding()];
+    check_bindings(&shader, &[&camera, &material, &indirect])
+        .and_then(|()| check_push_constants(&shader, &push_data_range()))
+        .with_context(|| file.to_owned())?;
+    Ok(shader)
+}
+
+/// Build a scene pipeline from `desc`, returning the layout its shaders
+/// were reflected as too: draws need it to bind sets and push constants
+/// (see record_direct_draws).
 pub(crate) fn create_pipeline(
     device: &ash::Device,
     cache: vk::PipelineCache,
     cfg: &PipelineConfig,
+    desc: &PipelineDesc,
+) -> Result<(vk::PipelineLayout, vk::Pipeline, ReflectedLayout)> {
+    build_graphics_pipeline(device, cache, cfg, desc, PipelineKind::Scene)
+}
+
+/// The depth prepass's pipeline (see VkRenderer::set_depth_prepass): the
+/// default pipeline's vertex stage, layout and raster state, but no
+/// fragment shader and no colour attachment, so it only lays down depth.
+/// Same vertex shader as the scene pass so both compute bit-identical
+/// depths, which the scene's GREATER_OR_EQUAL test then passes exactly
+/// for the nearest surface.
+pub(crate) fn create_depth_prepass_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    cfg: &PipelineConfig,
+) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    build_graphics_pipeline(
+        device,
+        cache,
+        cfg,
+        &PipelineDesc::opaque("depth prepass"),
+        PipelineKind::DepthOnly,
+    )
+    .map(|(layout, pipeline, _)| (layout, pipeline))
+}
+
+/// The shadow pass's pipeline (see shadow.rs): like the depth prepass's,
+/// but rendering into `shadow_format`, with dynamic depth bias and no face
+/// culling, so single-sided geometry casts from either side.
+pub(crate) fn create_shadow_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    cfg: &PipelineConfig,
+    shadow_format: vk::Format,
 ) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    let cfg = PipelineConfig {
+        depth_format: shadow_format,
+        ..*cfg
+    };
+    build_graphics_pipeline(
+        device,
+        cache,
+        &cfg,
+        &PipelineDesc::opaque("shadow"),
+        PipelineKind::Shadow,
+    )
+    .map(|(layout, pipeline, _)| (layout, pipeline))
+}
+
+/// The stereo pass's pipeline (see stereo.rs): the default pipeline with
+/// stereo.vert, which picks each eye's view_proj by gl_ViewIndex, built
+/// for a two-view multiview rendering scope.
+pub(crate) fn create_stereo_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    cfg: &PipelineConfig,
+) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    build_graphics_pipeline(
+        device,
+        cache,
+        cfg,
+        &PipelineDesc {
+            vertex_shader: "stereo.vert.spv".to_owned(),
+            ..PipelineDesc::opaque("stereo")
+        },
+        PipelineKind::Stereo,
+    )
+    .map(|(layout, pipeline, _)| (layout, pipeline))
+}
+
+/// Which flavour of pipeline build_graphics_pipeline makes from a
+/// description.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PipelineKind {
+    /// Colour and depth, exactly as described.
+    Scene,
+    /// Vertex stage only, writing depth, no colour attachment.
+    DepthOnly,
+    /// DepthOnly plus dynamic depth bias and no face culling.
+    Shadow,
+    /// Scene, for a multiview scope drawing both views at once.
+    Stereo,
+}
+
+fn build_graphics_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    cfg: &PipelineConfig,
+    desc: &PipelineDesc,
+    kind: PipelineKind,
+) -> Result<(vk::PipelineLayout, vk::Pipeline, ReflectedLayout)> {
+    let depth_only = matches!(kind, PipelineKind::DepthOnly | PipelineKind::Shadow);
     // STRICT: color_attachment_formats MUST match current swapchain image format.
     // On swapchain format change, pipeline must be rebuilt before recording.
 
@@ -115,8 +592,68 @@ pub(crate) fn create_pipeline(
     // assets/shaders/ is the single source of truth (CUBIC_SHADER_DIR can
     // override the directory for dev drops/mods; see shader_dir()).
     let dir = shader_dir();
-    let vs_words = load_spv_file(&dir.join("tri.vert.spv"))?;
-    let fs_words = load_spv_file(&dir.join("tri.frag.spv"))?;
+    let vs_words = load_spv_file(&dir.join(&desc.vertex_shader))?;
+    let fs_words = if depth_only {
+        Vec::new()
+    } else {
+        load_spv_file(&dir.join(&desc.fragment_shader))?
+    };
+
+    // --- Reflect: check both stages against the shared set layouts, and
+    // derive the pipeline layout and vertex attributes from what they
+    // declare and read (see reflect.rs) ---
+    let vs_interface = reflect_graphics_shader(&vs_words, &desc.vertex_shader, cfg)?;
+    let fs_interface = if depth_only {
+        None
+    } else {
+        Some(reflect_graphics_shader(
+            &fs_words,
+            &desc.fragment_shader,
+            cfg,
+        )?)
+    };
+    let reflected = match &fs_interface {
+        Some(fs_interface) => merge_layout(&[&vs_interface, fs_interface]),
+        None => merge_layout(&[&vs_interface]),
+    }
+    .with_context(|| format!("pipeline {:?}", desc.name))?;
+    // A depth-only build drops the fragment stage, and with it any
+    // constants only it declares.
+    let specialization: BTreeMap<u32, SpecValue> = match &fs_interface {
+        Some(fs_interface) => {
+            check_specialization(&[&vs_interface, fs_interface], &desc.specialization)
+                .with_context(|| format!("pipeline {:?}", desc.name))?;
+            desc.specialization.clone()
+        }
+        None => desc
+            .specialization
+            .iter()
+            .filter(|(id, _)| vs_interface.spec_constants.contains_key(id))
+            .map(|(&id, &value)| (id, value))
+            .collect(),
+    };
+    let spec_data = SpecData::new(&specialization);
+    let spec_info = spec_data.info();
+    let p_specialization_info = if specialization.is_empty() {
+        std::ptr::null()
+    } else {
+        &spec_info as *const vk::SpecializationInfo
+    };
+    let va = if desc.vertex_pulling {
+        if let Some(input) = vs_interface.inputs.first() {
+            bail!(
+                "{}: vertex-pulling pipeline {:?}, but the shader reads input {} (location {})",
+                desc.vertex_shader,
+                desc.name,
+                input.name,
+                input.location
+            );
+        }
+        Vec::new()
+    } else {
+        vertex_attributes(&vs_interface, &desc.vertex_layout)
+            .with_context(|| format!("{}: pipeline {:?}", desc.vertex_shader, desc.name))?
+    };
 
     let vs_ci = vk::ShaderModuleCreateInfo {
         s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
@@ -131,7 +668,11 @@ pub(crate) fn create_pipeline(
         ..Default::default()
     };
     let vs = unsafe { device.create_shader_module(&vs_ci, None)? };
-    let fs = unsafe { device.create_shader_module(&fs_ci, None)? };
+    let fs = if depth_only {
+        vk::ShaderModule::null()
+    } else {
+        unsafe { device.create_shader_module(&fs_ci, None)? }
+    };
     let entry = std::ffi::CString::new("main").unwrap();
 
     // --- Shader stage infos ---
@@ -141,6 +682,7 @@ pub(crate) fn create_pipeline(
             stage: vk::ShaderStageFlags::VERTEX,
             module: vs,
             p_name: entry.as_ptr(),
+            p_specialization_info,
             ..Default::default()
         },
         vk::PipelineShaderStageCreateInfo {
@@ -148,68 +690,58 @@ pub(crate) fn create_pipeline(
             stage: vk::ShaderStageFlags::FRAGMENT,
             module: fs,
             p_name: entry.as_ptr(),
+            p_specialization_info,
             ..Default::default()
         },
     ];
+    // Depth-only: vertex stage alone (no fragment shader is valid when
+    // nothing is written but depth).
+    let stages = if depth_only {
+        &stages[..1]
+    } else {
+        &stages[..]
+    };
 
     // --- Fixed-function pipeline states ---
-    // Vertex input layout: binding 0 with Vertex { pos, color }
+    // Vertex input layout: binding 0, desc.vertex_layout's stride, and the
+    // attributes the shader reads (reflected above)
     let vb = vk::VertexInputBindingDescription {
         binding: 0,
-        stride: std::mem::size_of::<super::resources::Vertex>() as u32,
+        stride: desc.vertex_layout.stride,
         input_rate: vk::VertexInputRate::VERTEX,
     };
-    let va = [
-        vk::VertexInputAttributeDescription {
-            location: 0,
-            binding: 0,
-            format: vk::Format::R32G32B32_SFLOAT,
-            offset: 0,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 1,
-            binding: 0,
-            format: vk::Format::R32G32B32_SFLOAT,
-            offset: std::mem::size_of::<[f32; 3]>() as u32,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 2,
-            binding: 0,
-            format: vk::Format::R32G32_SFLOAT,
-            offset: (std::mem::size_of::<[f32; 3]>() * 2) as u32,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 3,
-            binding: 0,
-            format: vk::Format::R32G32B32_SFLOAT,
-            offset: std::mem::offset_of!(super::resources::Vertex, normal) as u32,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 4,
-            binding: 0,
-            format: vk::Format::R32_UINT,
-            offset: std::mem::offset_of!(super::resources::Vertex, tex_index) as u32,
-        },
-    ];
-    let vertex_input = vk::PipelineVertexInputStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
-        vertex_binding_description_count: 1,
-        p_vertex_binding_descriptions: &vb,
-        vertex_attribute_description_count: va.len() as u32,
-        p_vertex_attribute_descriptions: va.as_ptr(),
-        ..Default::default()
+    let vertex_input = if desc.vertex_pulling {
+        vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            ..Default::default()
+        }
+    } else {
+        vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            vertex_binding_description_count: 1,
+            p_vertex_binding_descriptions: &vb,
+            vertex_attribute_description_count: va.len() as u32,
+            p_vertex_attribute_descriptions: va.as_ptr(),
+            ..Default::default()
+        }
     };
-    // Input assembly (triangles)
+    // Input assembly (triangles unless the description says otherwise)
     let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
         s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
-        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        topology: desc.topology,
         ..Default::default()
     };
     // Dynamic state
-    let dyn_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let shadow = kind == PipelineKind::Shadow;
+    let dyn_states = [
+        vk::DynamicState::VIEWPORT,
+        vk::DynamicState::SCISSOR,
+        vk::DynamicState::DEPTH_BIAS,
+    ];
     let dynamic_state = vk::PipelineDynamicStateCreateInfo {
         s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
-        dynamic_state_count: dyn_states.len() as u32,
+        // Depth bias only for shadows (set per ShadowSettings).
+        dynamic_state_count: if shadow { 3 } else { 2 },
         p_dynamic_states: dyn_states.as_ptr(),
         ..Default::default()
     };
@@ -225,9 +757,14 @@ pub(crate) fn create_pipeline(
     // Rasterization
     let raster = vk::PipelineRasterizationStateCreateInfo {
         s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
-        polygon_mode: vk::PolygonMode::FILL,
-        cull_mode: vk::CullModeFlags::BACK,
+        polygon_mode: desc.polygon_mode,
+        cull_mode: if desc.cull_back_faces && !shadow {
+            vk::CullModeFlags::BACK
+        } else {
+            vk::CullModeFlags::NONE
+        },
         front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        depth_bias_enable: shadow as vk::Bool32,
         line_width: 1.0,
         ..Default::default()
     };
@@ -237,43 +774,54 @@ pub(crate) fn create_pipeline(
         rasterization_samples: vk::SampleCountFlags::TYPE_1,
         ..Default::default()
     };
-    // Depth-stencil: enable depth test/write
+    // Depth-stencil: depth test always on; write is per-description (off
+    // for blended pipelines so they don't occlude what's behind them)
     let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
         s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
         depth_test_enable: vk::TRUE,
-        depth_write_enable: vk::TRUE,
+        depth_write_enable: if desc.depth_write {
+            vk::TRUE
+        } else {
+            vk::FALSE
+        },
         depth_compare_op: vk::CompareOp::GREATER_OR_EQUAL, // reverse-z
         ..Default::default()
     };
-    // Color blend (no blending; write all RGBA)
-    let color_blend_att = vk::PipelineColorBlendAttachmentState {
-        color_write_mask: vk::ColorComponentFlags::R
-            | vk::ColorComponentFlags::G
-            | vk::ColorComponentFlags::B
-            | vk::ColorComponentFlags::A,
-        blend_enable: vk::FALSE,
-        ..Default::default()
-    };
+    // Color blend (write all RGBA; blend equation per description)
+    let color_blend_att = blend_attachment(desc.blend);
+    let color_attachment_count = if depth_only { 0 } else { 1 };
     let color_blend = vk::PipelineColorBlendStateCreateInfo {
         s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
-        attachment_count: 1,
+        attachment_count: color_attachment_count,
         p_attachments: &color_blend_att,
         ..Default::default()
     };
 
-    // --- Pipeline layout ---
-    // No push constants: indirect draws can't vary them per-entry, so
-    // per-object data (model/tint/tex_index) comes from the candidates
-    // SSBO (set 2) instead, indexed by gl_InstanceIndex.
+    // --- Pipeline layout, as reflected above ---
+    // Sets: the shared layouts (camera, material, indirect) up to the
+    // highest set a stage uses; unused ones below it are still declared,
+    // so set numbers mean the same in every pipeline. Indirect draws can't
+    // vary push constants per entry, so the default pipeline's per-object
+    // data (model/tint/tex_index) comes from the candidates SSBO (set 2),
+    // indexed by gl_InstanceIndex. Direct draws (see record_direct_draws)
+    // additionally push the part of PushData the shaders' block covers,
+    // for shaders that would rather not bind the SSBO. Layouts differ
+    // between pipelines, so those draws rebind the sets on each switch.
     let layouts = [
         cfg.set_layout_camera,
         cfg.set_layout_material,
         cfg.set_layout_indirect_graphics,
     ];
+    let layouts = &layouts[..reflected.sets.len()];
     let layout_info = vk::PipelineLayoutCreateInfo {
         s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
         set_layout_count: layouts.len() as u32,
         p_set_layouts: layouts.as_ptr(),
+        push_constant_range_count: reflected.push_constants.is_some() as u32,
+        p_push_constant_ranges: reflected
+            .push_constants
+            .as_ref()
+            .map_or(std::ptr::null(), |range| range as *const _),
         ..Default::default()
     };
     let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
@@ -281,7 +829,13 @@ pub(crate) fn create_pipeline(
     // --- Dynamic rendering info (ext / core 1.3 replacement for render passes) ---
     let rendering = vk::PipelineRenderingCreateInfo {
         s_type: vk::StructureType::PIPELINE_RENDERING_CREATE_INFO,
-        color_attachment_count: 1,
+        // Must match the view_mask of the scope it's drawn in.
+        view_mask: if kind == PipelineKind::Stereo {
+            crate::stereo::STEREO_VIEW_MASK
+        } else {
+            0
+        },
+        color_attachment_count,
         p_color_attachment_formats: &cfg.color_format,
         depth_attachment_format: cfg.depth_format,
         ..Default::default()
@@ -313,10 +867,185 @@ pub(crate) fn create_pipeline(
 
     unsafe {
         device.destroy_shader_module(vs, None);
-        device.destroy_shader_module(fs, None);
+        if fs != vk::ShaderModule::null() {
+            device.destroy_shader_module(fs, None);
+        }
     }
 
-    Ok((layout, pipelines[0]))
+    Ok((layout, pipelines[0], reflected))
+}
+
+/// Build a fullscreen-pass pipeline: no vertex input (the vertex shader
+/// derives positions from gl_VertexIndex, see fullscreen.vert), no culling,
+/// no depth test or write, opaque output. `depth_format` is still declared
+/// because these passes run inside a rendering scope that binds the depth
+/// attachment, so egui can be drawn in the same scope afterwards (see
+/// build_egui_renderer); declared and bound formats must match.
+pub(crate) fn create_fullscreen_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    layout: vk::PipelineLayout,
+    shaders: (&str, &str),
+    color_format: vk::Format,
+    depth_format: vk::Format,
+) -> Result<vk::Pipeline> {
+    let vertex_input = vk::PipelineVertexInputStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+        ..Default::default()
+    };
+    create_overlay_pipeline(
+        device,
+        cache,
+        layout,
+        shaders,
+        &vertex_input,
+        BlendMode::Opaque,
+        false,
+        color_format,
+        depth_format,
+    )
+}
+
+/// The fullscreen pipeline's fixed state (no culling, no depth write,
+/// depth format declared) with a caller's vertex input and blending: for
+/// passes drawn on top of the finished image, like the text overlay.
+/// `depth_test` turns on the scene's GREATER_OR_EQUAL test (reverse-Z),
+/// for passes drawn into the scene like the skybox.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_overlay_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    layout: vk::PipelineLayout,
+    shaders: (&str, &str),
+    vertex_input: &vk::PipelineVertexInputStateCreateInfo,
+    blend: BlendMode,
+    depth_test: bool,
+    color_format: vk::Format,
+    depth_format: vk::Format,
+) -> Result<vk::Pipeline> {
+    let dir = shader_dir();
+    let vs_words = load_spv_file(&dir.join(shaders.0))?;
+    let fs_words = load_spv_file(&dir.join(shaders.1))?;
+
+    let vs_ci = vk::ShaderModuleCreateInfo {
+        s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        p_code: vs_words.as_ptr(),
+        code_size: vs_words.len() * 4,
+        ..Default::default()
+    };
+    let fs_ci = vk::ShaderModuleCreateInfo {
+        s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        p_code: fs_words.as_ptr(),
+        code_size: fs_words.len() * 4,
+        ..Default::default()
+    };
+    let vs = unsafe { device.create_shader_module(&vs_ci, None)? };
+    let fs = match unsafe { device.create_shader_module(&fs_ci, None) } {
+        Ok(fs) => fs,
+        Err(e) => {
+            unsafe { device.destroy_shader_module(vs, None) };
+            return Err(e.into());
+        }
+    };
+    let entry = std::ffi::CString::new("main").unwrap();
+
+    let stages = [
+        vk::PipelineShaderStageCreateInfo {
+            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            stage: vk::ShaderStageFlags::VERTEX,
+            module: vs,
+            p_name: entry.as_ptr(),
+            ..Default::default()
+        },
+        vk::PipelineShaderStageCreateInfo {
+            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            stage: vk::ShaderStageFlags::FRAGMENT,
+            module: fs,
+            p_name: entry.as_ptr(),
+            ..Default::default()
+        },
+    ];
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        ..Default::default()
+    };
+    let dyn_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+        dynamic_state_count: dyn_states.len() as u32,
+        p_dynamic_states: dyn_states.as_ptr(),
+        ..Default::default()
+    };
+    let viewport_state = vk::PipelineViewportStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+        viewport_count: 1,
+        scissor_count: 1,
+        ..Default::default()
+    };
+    let raster = vk::PipelineRasterizationStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+        polygon_mode: vk::PolygonMode::FILL,
+        cull_mode: vk::CullModeFlags::NONE,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        line_width: 1.0,
+        ..Default::default()
+    };
+    let multisample = vk::PipelineMultisampleStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+        rasterization_samples: vk::SampleCountFlags::TYPE_1,
+        ..Default::default()
+    };
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+        depth_test_enable: if depth_test { vk::TRUE } else { vk::FALSE },
+        depth_write_enable: vk::FALSE,
+        depth_compare_op: vk::CompareOp::GREATER_OR_EQUAL,
+        ..Default::default()
+    };
+    let color_blend_att = blend_attachment(blend);
+    let color_blend = vk::PipelineColorBlendStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+        attachment_count: 1,
+        p_attachments: &color_blend_att,
+        ..Default::default()
+    };
+    let rendering = vk::PipelineRenderingCreateInfo {
+        s_type: vk::StructureType::PIPELINE_RENDERING_CREATE_INFO,
+        color_attachment_count: 1,
+        p_color_attachment_formats: &color_format,
+        depth_attachment_format: depth_format,
+        ..Default::default()
+    };
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo {
+        s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+        p_next: (&rendering as *const _) as *const _,
+        stage_count: stages.len() as u32,
+        p_stages: stages.as_ptr(),
+        p_vertex_input_state: vertex_input,
+        p_input_assembly_state: &input_assembly,
+        p_viewport_state: &viewport_state,
+        p_rasterization_state: &raster,
+        p_multisample_state: &multisample,
+        p_depth_stencil_state: &depth_stencil,
+        p_color_blend_state: &color_blend,
+        p_dynamic_state: &dynamic_state,
+        layout,
+        ..Default::default()
+    };
+
+    let result = unsafe {
+        device.create_graphics_pipelines(cache, std::slice::from_ref(&pipeline_info), None)
+    };
+    unsafe {
+        device.destroy_shader_module(vs, None);
+        device.destroy_shader_module(fs, None);
+    }
+    let pipelines =
+        result.map_err(|(_, err)| anyhow!("create_graphics_pipelines failed: {:?}", err))?;
+    Ok(pipelines[0])
 }
 
 /// Build a compute pipeline from SPIR-V words and a caller-supplied layout
@@ -329,6 +1058,20 @@ pub(crate) fn create_compute_pipeline(
     layout: vk::PipelineLayout,
     shader_words: &[u32],
 ) -> Result<vk::Pipeline> {
+    create_compute_pipeline_specialized(device, cache, layout, shader_words, &BTreeMap::new())
+}
+
+/// `create_compute_pipeline` with specialization constants, already
+/// checked against the shader (see reflect::check_specialization).
+pub(crate) fn create_compute_pipeline_specialized(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    layout: vk::PipelineLayout,
+    shader_words: &[u32],
+    specialization: &BTreeMap<u32, SpecValue>,
+) -> Result<vk::Pipeline> {
+    let spec_data = SpecData::new(specialization);
+    let spec_info = spec_data.info();
     let module_ci = vk::ShaderModuleCreateInfo {
         s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
         p_code: shader_words.as_ptr(),
@@ -343,6 +1086,11 @@ pub(crate) fn create_compute_pipeline(
         stage: vk::ShaderStageFlags::COMPUTE,
         module,
         p_name: entry.as_ptr(),
+        p_specialization_info: if specialization.is_empty() {
+            std::ptr::null()
+        } else {
+            &spec_info
+        },
         ..Default::default()
     };
 
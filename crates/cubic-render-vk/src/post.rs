@@ -0,0 +1,735 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Post-processing chain between the scene and the tonemap pass.
+//!
+//! The effects set_post_effects lists run in that order over the image the
+//! tonemap pass would otherwise sample (the scene target, or FSR1's
+//! output), each as fullscreen passes of post.frag into FP16 targets that
+//! alternate as source and destination; the tonemap pass then samples the
+//! last one. Everything stays linear HDR until the tonemap, so bloom sees
+//! real highlights.
+//!
+//! - Bloom: a bright pass into a half-size target, a separable Gaussian
+//!   blur through a second one, then added back over the image.
+//! - Vignette: darkens towards the corners.
+//! - Fxaa: edge-directed antialiasing on luma.
+//! - ColorGrade: looks colours up in a LUT texture (set_color_grading_lut);
+//!   passes through until one is set.
+//!
+//! After them all comes the accessibility filter (set_color_filter, see
+//! color_filter.rs), if one is selected.
+//!
+//! Any effect, or a filter, forces the tonemap pass on (in its passthrough mode on an SDR
+//! swapchain), since the chain needs an offscreen scene to read. Effects
+//! run at the resolution of their input, so below 1.0 render scale they
+//! cost less and are upscaled with the scene, unless FSR1 ran first.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::color_filter::ColorFilter;
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::frame_graph::{Access, FrameGraph, LoadOp, ResourceId};
+use crate::pipeline::create_fullscreen_pipeline;
+use crate::resources::create_color_target;
+use crate::tonemap::HDR_TARGET_FORMAT;
+use crate::{DeferredDrop, GpuResource, RetiredPass, VkRenderer};
+use cubic_render::{RenderResult, RenderSize};
+
+/// Brightness (1.0 = paper white) bloom starts picking up, with a soft
+/// knee half as wide below it.
+const BLOOM_THRESHOLD: f32 = 1.0;
+/// How much of the blurred highlights is added back.
+const BLOOM_INTENSITY: f32 = 0.15;
+/// Fraction of brightness the corners lose.
+const VIGNETTE_STRENGTH: f32 = 0.35;
+
+/// One step of the post-processing chain (see module docs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PostEffect {
+    #[default]
+    Bloom,
+    Vignette,
+    Fxaa,
+    ColorGrade,
+}
+
+impl PostEffect {
+    /// CUBIC_POST's names, comma-separated there: bloom, vignette, fxaa,
+    /// color_grade (or lut).
+    pub(crate) fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bloom" => Some(Self::Bloom),
+            "vignette" => Some(Self::Vignette),
+            "fxaa" => Some(Self::Fxaa),
+            "color_grade" | "colour_grade" | "lut" => Some(Self::ColorGrade),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Bloom => "bloom",
+            Self::Vignette => "vignette",
+            Self::Fxaa => "FXAA",
+            Self::ColorGrade => "color grade",
+        }
+    }
+}
+
+/// The enabled effects in order, each at most once; a fixed array so
+/// RuntimeConfig stays Copy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PostChain {
+    effects: [PostEffect; 4],
+    len: usize,
+}
+
+impl PostChain {
+    /// `effects` in order, repeats dropped.
+    pub(crate) fn new(effects: &[PostEffect]) -> Self {
+        let mut chain = Self::default();
+        for &e in effects {
+            if !chain.as_slice().contains(&e) {
+                chain.effects[chain.len] = e;
+                chain.len += 1;
+            }
+        }
+        chain
+    }
+
+    pub(crate) fn as_slice(&self) -> &[PostEffect] {
+        &self.effects[..self.len]
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// post.frag's `mode` values.
+const MODE_BRIGHT: u32 = 0;
+const MODE_BLUR_H: u32 = 1;
+const MODE_BLUR_V: u32 = 2;
+const MODE_BLOOM: u32 = 3;
+const MODE_VIGNETTE: u32 = 4;
+const MODE_FXAA: u32 = 5;
+const MODE_GRADE: u32 = 6;
+const MODE_FILTER: u32 = 7;
+
+/// Push constants for post.frag; layout must match its `Post` block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct PostPush {
+    texel: [f32; 2],
+    mode: u32,
+    lut_size: u32,
+    param: f32,
+    _pad: [f32; 3],
+    // ColorFilter::rows, for MODE_FILTER.
+    filter: [[f32; 4]; 3],
+}
+
+/// Which image a pass reads or writes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// What the chain starts from (scene target or FSR1 output).
+    Input,
+    /// Full-size ping-pong targets.
+    Ping(usize),
+    /// Half-size bloom targets.
+    Bloom(usize),
+}
+
+/// An FP16 target the chain renders into and samples.
+struct PostTarget {
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+}
+
+impl PostTarget {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        name: &str,
+    ) -> Result<Self> {
+        let (image, alloc, view) = create_color_target(
+            device,
+            allocator,
+            extent,
+            HDR_TARGET_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            name,
+        )?;
+        Ok(Self { image, alloc, view })
+    }
+
+    fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(self.alloc);
+    }
+}
+
+/// One fullscreen draw of post.frag.
+struct Step {
+    name: &'static str,
+    mode: u32,
+    param: f32,
+    src: Slot,
+    // Sampled as `bloom`; the source again when the pass doesn't use it.
+    aux: Option<Slot>,
+    dst: Slot,
+    // Size of `src`, for the texel step.
+    src_extent: vk::Extent2D,
+    // Render area: `dst`'s size.
+    extent: vk::Extent2D,
+}
+
+struct PostPass {
+    step: Step,
+    set: PooledSet,
+}
+
+/// The chain's targets, passes and pipeline for one input image.
+pub(crate) struct PostStack {
+    ping: Vec<PostTarget>,
+    bloom: Vec<PostTarget>,
+    passes: Vec<PostPass>,
+    // The slot the tonemap pass samples.
+    output: Slot,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    lut_size: u32,
+    filter: [[f32; 4]; 3],
+}
+
+/// Where the chain reads from and how big that is.
+#[derive(Clone, Copy)]
+struct PostInput {
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+}
+
+/// The grading LUT's view (the dummy texture without one) and slice size
+/// (0 without one).
+struct PostLut {
+    view: vk::ImageView,
+    size: u32,
+}
+
+impl PostStack {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        cache: vk::PipelineCache,
+        chain: &[PostEffect],
+        filter: ColorFilter,
+        input: PostInput,
+        lut: PostLut,
+    ) -> Result<Self> {
+        // Pipeline first, as with the tonemap pass.
+        let set_layout = create_post_set_layout(device)?;
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<PostPush>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+        // No depth: the passes run in scopes of their own, which bind none.
+        let pipeline = match create_fullscreen_pipeline(
+            device,
+            cache,
+            layout,
+            ("fullscreen.vert.spv", "post.frag.spv"),
+            HDR_TARGET_FORMAT,
+            vk::Format::UNDEFINED,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                unsafe {
+                    device.destroy_pipeline_layout(layout, None);
+                    device.destroy_descriptor_set_layout(set_layout, None);
+                }
+                return Err(e);
+            }
+        };
+
+        // LINEAR: bloom's taps and FXAA's edge blend sample between texels.
+        let sampler_ci = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        };
+        let sampler = unsafe { device.create_sampler(&sampler_ci, None)? };
+
+        let mut stack = Self {
+            ping: Vec::new(),
+            bloom: Vec::new(),
+            passes: Vec::new(),
+            output: Slot::Input,
+            sampler,
+            set_layout,
+            layout,
+            pipeline,
+            lut_size: lut.size,
+            filter: filter.rows(),
+        };
+        let built = stack.plan(device, allocator, descriptors, chain, filter, input, &lut);
+        if let Err(e) = built {
+            stack.destroy(device, allocator, descriptors);
+            return Err(e);
+        }
+        Ok(stack)
+    }
+
+    /// Lay out the passes for `chain`, creating targets and descriptor
+    /// sets as they're needed.
+    #[allow(clippy::too_many_arguments)]
+    fn plan(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        chain: &[PostEffect],
+        filter: ColorFilter,
+        input: PostInput,
+        lut: &PostLut,
+    ) -> Result<()> {
+        let full = input.extent;
+        let half = vk::Extent2D {
+            width: full.width.div_ceil(2),
+            height: full.height.div_ceil(2),
+        };
+        let mut cur = Slot::Input;
+        let mut next_ping = 0;
+        // The filter is a last step of its own, after every effect.
+        let filter = (filter != ColorFilter::Off).then_some(None);
+        for effect in chain.iter().copied().map(Some).chain(filter) {
+            let dst = Slot::Ping(next_ping);
+            if self.ping.len() <= next_ping {
+                let name = ["post target a", "post target b"][next_ping];
+                self.ping
+                    .push(PostTarget::new(device, allocator, full, name)?);
+            }
+            next_ping ^= 1;
+            // `cur` -> `dst` at full size; bloom's inner steps override it.
+            let fullscreen = |name, mode, param| Step {
+                name,
+                mode,
+                param,
+                src: cur,
+                aux: None,
+                dst,
+                src_extent: full,
+                extent: full,
+            };
+            let steps = match effect {
+                None => vec![fullscreen("color filter", MODE_FILTER, 0.0)],
+                Some(PostEffect::Bloom) => {
+                    for name in ["bloom target a", "bloom target b"] {
+                        self.bloom
+                            .push(PostTarget::new(device, allocator, half, name)?);
+                    }
+                    let (a, b) = (Slot::Bloom(0), Slot::Bloom(1));
+                    vec![
+                        Step {
+                            dst: a,
+                            extent: half,
+                            ..fullscreen("bloom bright", MODE_BRIGHT, BLOOM_THRESHOLD)
+                        },
+                        Step {
+                            src: a,
+                            dst: b,
+                            src_extent: half,
+                            extent: half,
+                            ..fullscreen("bloom blur h", MODE_BLUR_H, 0.0)
+                        },
+                        Step {
+                            src: b,
+                            dst: a,
+                            src_extent: half,
+                            extent: half,
+                            ..fullscreen("bloom blur v", MODE_BLUR_V, 0.0)
+                        },
+                        Step {
+                            aux: Some(a),
+                            ..fullscreen("bloom", MODE_BLOOM, BLOOM_INTENSITY)
+                        },
+                    ]
+                }
+                Some(PostEffect::Vignette) => {
+                    vec![fullscreen("vignette", MODE_VIGNETTE, VIGNETTE_STRENGTH)]
+                }
+                Some(PostEffect::Fxaa) => vec![fullscreen("fxaa", MODE_FXAA, 0.0)],
+                Some(PostEffect::ColorGrade) => {
+                    vec![fullscreen("color grade", MODE_GRADE, 0.0)]
+                }
+            };
+            for step in steps {
+                let set = descriptors.allocate(device, self.set_layout)?;
+                let src_view = self.view(step.src, input);
+                let aux_view = step.aux.map_or(src_view, |s| self.view(s, input));
+                self.write_set(device, set.set, src_view, aux_view, lut.view);
+                self.passes.push(PostPass { step, set });
+            }
+            cur = dst;
+        }
+        self.output = cur;
+        Ok(())
+    }
+
+    fn view(&self, slot: Slot, input: PostInput) -> vk::ImageView {
+        match slot {
+            Slot::Input => input.view,
+            Slot::Ping(i) => self.ping[i].view,
+            Slot::Bloom(i) => self.bloom[i].view,
+        }
+    }
+
+    fn write_set(
+        &self,
+        device: &ash::Device,
+        set: vk::DescriptorSet,
+        source: vk::ImageView,
+        bloom: vk::ImageView,
+        lut: vk::ImageView,
+    ) {
+        let infos = [source, bloom, lut].map(|view| vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            image_view: view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        });
+        let writes: Vec<_> = infos
+            .iter()
+            .enumerate()
+            .map(|(binding, info)| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: binding as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                p_image_info: info,
+                ..Default::default()
+            })
+            .collect();
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    /// Tear everything down. Caller must have idled the device.
+    pub(crate) fn destroy(
+        self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        for pass in self.passes {
+            descriptors.free(device, pass.set);
+        }
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        for target in self.ping.into_iter().chain(self.bloom) {
+            target.destroy(device, allocator);
+        }
+    }
+
+    /// View of the chain's result, for the tonemap pass to sample.
+    fn output_view(&self, input: PostInput) -> vk::ImageView {
+        self.view(self.output, input)
+    }
+
+    /// Declare the chain's passes, reading `input` (the graph's handle for
+    /// the image PostInput named). Returns the handle of the result.
+    pub(crate) fn add_passes(&self, g: &mut FrameGraph, input: ResourceId) -> ResourceId {
+        // Every target is sampled after it's written, so last frame's final
+        // use of each was a fragment-shader read.
+        let import = |g: &mut FrameGraph, t: &PostTarget, name| {
+            g.import_image(
+                name,
+                t.image,
+                t.view,
+                vk::ImageAspectFlags::COLOR,
+                Access::stale(vk::PipelineStageFlags2::FRAGMENT_SHADER),
+            )
+        };
+        let ping: Vec<_> = self
+            .ping
+            .iter()
+            .zip(["post target a", "post target b"])
+            .map(|(t, name)| import(g, t, name))
+            .collect();
+        let bloom: Vec<_> = self
+            .bloom
+            .iter()
+            .zip(["bloom target a", "bloom target b"])
+            .map(|(t, name)| import(g, t, name))
+            .collect();
+        let id = |slot| match slot {
+            Slot::Input => input,
+            Slot::Ping(i) => ping[i],
+            Slot::Bloom(i) => bloom[i],
+        };
+        for (index, PostPass { step, .. }) in self.passes.iter().enumerate() {
+            let mut uses = vec![
+                (id(step.src), Access::sampled_fragment()),
+                (id(step.dst), Access::color_attachment(LoadOp::DontCare)),
+            ];
+            if let Some(aux) = step.aux {
+                uses.push((id(aux), Access::sampled_fragment()));
+            }
+            g.add_pass_at(step.name, step.extent, &uses, move |r, cmd| {
+                r.record_post_pass(cmd, index);
+                Ok(())
+            });
+        }
+        id(self.output)
+    }
+}
+
+impl RetiredPass for PostStack {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device, allocator, descriptors);
+    }
+}
+
+fn create_post_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+    let bindings = [0, 1, 2].map(|binding| vk::DescriptorSetLayoutBinding {
+        binding,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        descriptor_count: 1,
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        ..Default::default()
+    });
+    let ci = vk::DescriptorSetLayoutCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        binding_count: bindings.len() as u32,
+        p_bindings: bindings.as_ptr(),
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_descriptor_set_layout(&ci, None)? })
+}
+
+impl VkRenderer {
+    /// What the chain reads: FSR1's full-size output when it ran, else the
+    /// tonemap pass's scene target.
+    fn post_input(&self) -> Option<PostInput> {
+        let tm = self.tonemap.as_ref()?;
+        Some(match self.fsr1.as_ref() {
+            Some(fsr) => PostInput {
+                view: fsr.output_target().1,
+                extent: self.output_area().extent,
+            },
+            None => PostInput {
+                view: tm.view,
+                extent: tm.extent,
+            },
+        })
+    }
+
+    /// Rebuild the chain over the current tonemap target (or FSR1 output)
+    /// and point the tonemap pass at its result, or back at its input when
+    /// there's no chain; the old chain is retired through the trash queue.
+    /// Called from sync_tonemap_pass, with a tonemap pass no frame has used
+    /// yet, and after a LUT change once the tonemap pass has a fresh set,
+    /// since pointing it elsewhere rewrites its set. A chain that fails to build
+    /// (e.g. post.frag.spv not compiled) is logged and skipped.
+    pub(crate) fn sync_post_stack(&mut self) {
+        if let Some(old) = self.post.take() {
+            self.retire_pass(old);
+        }
+        let Some(input) = self.post_input() else {
+            return;
+        };
+        let tm = self.tonemap.as_ref().expect("post input without tonemap");
+        let chain = self.cfg.post;
+        let filter = self.cfg.color_filter;
+        if chain.is_empty() && filter == ColorFilter::Off {
+            tm.set_source(&self.device, input.view);
+            return;
+        }
+        // A LUT index the bindless array doesn't hold (yet: device-lost
+        // recovery re-uploads textures after the first sync) grades nothing.
+        let lut = match self.cfg.post_lut {
+            Some((index, size)) if (1..=self.tex_store.len() as u32).contains(&index) => PostLut {
+                view: self.texture_binding(index).0,
+                size,
+            },
+            _ => PostLut {
+                view: self.tex_view,
+                size: 0,
+            },
+        };
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        match PostStack::new(
+            &self.device,
+            allocator,
+            &mut self.descriptors.persistent,
+            self.pipeline_cache,
+            chain.as_slice(),
+            filter,
+            input,
+            lut,
+        ) {
+            Ok(stack) => {
+                tm.set_source(&self.device, stack.output_view(input));
+                self.post = Some(stack);
+            }
+            Err(e) => {
+                tm.set_source(&self.device, input.view);
+                tracing::warn!("post-processing unavailable ({e:#}); tonemapping the scene as is");
+            }
+        }
+    }
+
+    /// Run `effects` in order between the scene and the tonemap pass
+    /// (repeats dropped; empty turns post-processing off). Recreates the
+    /// swapchain if the list changes, since an effect may need the
+    /// offscreen scene target the tonemap pass brings.
+    pub fn set_post_effects(&mut self, effects: &[PostEffect]) {
+        let chain = PostChain::new(effects);
+        if self.cfg.post == chain {
+            return;
+        }
+        self.cfg.post = chain;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.cfg.post.as_slice()
+    }
+
+    /// Grade with the upload_texture texture at `index` (None for no
+    /// grading): a strip of N slices N×N texels, N² wide and N high, red
+    /// across each slice, green down, blue from slice to slice; an
+    /// identity LUT maps each texel to its own coordinates. Only used
+    /// while PostEffect::ColorGrade is in the chain. Rebuilding the chain
+    /// retires the old one, and the tonemap pass moves to a fresh set.
+    pub fn set_color_grading_lut(&mut self, index: Option<u32>) -> RenderResult<()> {
+        let lut = match index {
+            None => None,
+            Some(index) => {
+                let Some(tex) = index
+                    .checked_sub(1)
+                    .and_then(|i| self.tex_sources.get(i as usize))
+                else {
+                    return Err(anyhow!("color grading LUT: no texture at index {index}").into());
+                };
+                if tex.height < 2 || tex.width != tex.height * tex.height {
+                    return Err(anyhow!(
+                        "color grading LUT must be N²×N texels, got {}x{}",
+                        tex.width,
+                        tex.height
+                    )
+                    .into());
+                }
+                Some((index, tex.height))
+            }
+        };
+        if self.cfg.post_lut == lut {
+            return Ok(());
+        }
+        self.cfg.post_lut = lut;
+        if self.post.is_some() {
+            let tm = self.tonemap.as_mut().expect("post chain without tonemap");
+            let old = tm.renew_set(&self.device, &mut self.descriptors.persistent)?;
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::DescriptorSet(old),
+            });
+            self.sync_post_stack();
+        }
+        Ok(())
+    }
+
+    /// The "post" passes' recording: one fullscreen draw into the graph's
+    /// rendering scope on the pass's target.
+    pub(crate) fn record_post_pass(&self, cmd: vk::CommandBuffer, index: usize) {
+        let Some(post) = self.post.as_ref() else {
+            return;
+        };
+        let PostPass { step: pass, set } = &post.passes[index];
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: pass.extent,
+        };
+        let vp = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: pass.extent.width as f32,
+            height: pass.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let push = PostPush {
+            texel: [
+                1.0 / pass.src_extent.width as f32,
+                1.0 / pass.src_extent.height as f32,
+            ],
+            mode: pass.mode,
+            lut_size: post.lut_size,
+            param: pass.param,
+            _pad: [0.0; 3],
+            filter: post.filter,
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, post.pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&render_area));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                post.layout,
+                0,
+                std::slice::from_ref(&set.set),
+                &[],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                post.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            self.device.cmd_draw(cmd, 3, 1, 0, 0);
+        }
+    }
+}
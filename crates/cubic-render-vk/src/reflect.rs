@@ -0,0 +1,983 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! SPIR-V reflection (rspirv): what a shader module expects of its
+//! pipeline, read from the module instead of restated by hand next to it.
+//!
+//! Graphics pipelines use it in build_graphics_pipeline:
+//! - pipeline layout: merge_layout merges both stages' bindings and push
+//!   constant blocks. The pipeline layout takes the renderer's set layouts
+//!   up to the highest set a stage uses, and the merged push constant
+//!   range, so draws push only as much of PushData as the shaders read.
+//! - descriptor bindings: every `layout(set, binding)` both stages use
+//!   must be in the renderer's shared set layouts (camera, material,
+//!   indirect; see resources.rs) with a matching type, a big enough count
+//!   and the stage in its stage flags. Those are what the scene's
+//!   descriptor sets are allocated from, so there are no others to use.
+//! - push constants: the shaders' block must fit within PushData
+//!   (push_data_range), which is where draws push it from.
+//! - vertex input: the attribute descriptions are the vertex shader's
+//!   inputs, each taken from the description's VertexLayout at the same
+//!   location, which must exist with the same numeric type. Attributes the
+//!   shader doesn't read are left out.
+//! - specialization constants: every constant_id the description sets must
+//!   be declared by one of the shaders, with the value's type. Compute
+//!   pipelines check these too.
+//!
+//! A mismatch fails the pipeline with the shader's name and the variable's,
+//! rather than leaving it to the validation layer (when it's on) or to
+//! garbage on screen.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, bail, Context, Result};
+use ash::vk;
+use cubic_render::{VertexFormat, VertexLayout};
+use rspirv::dr::{Instruction, Module, Operand};
+use rspirv::spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass, Word};
+
+use crate::pipeline::{vk_vertex_format, SpecValue};
+
+/// The type of a vertex input or a specialization constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScalarKind {
+    Float,
+    Sint,
+    Uint,
+    Bool,
+    /// 64-bit or otherwise nothing a VertexFormat or SpecValue provides.
+    Other,
+}
+
+/// One `layout(set, binding)` a shader declares.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ShaderBinding {
+    pub(crate) set: u32,
+    pub(crate) binding: u32,
+    /// Never a _DYNAMIC type: the shader can't tell; see compatible_types.
+    pub(crate) descriptor_type: vk::DescriptorType,
+    /// Array length; 0 for a runtime-sized (bindless) array.
+    pub(crate) count: u32,
+    pub(crate) name: String,
+}
+
+/// One vertex shader input location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ShaderInput {
+    pub(crate) location: u32,
+    pub(crate) kind: ScalarKind,
+    pub(crate) name: String,
+}
+
+/// What one shader module's entry point uses.
+#[derive(Clone, Debug)]
+pub(crate) struct ShaderInterface {
+    pub(crate) stage: vk::ShaderStageFlags,
+    pub(crate) bindings: Vec<ShaderBinding>,
+    /// Bytes of push constants the block spans from offset 0; 0 = none.
+    pub(crate) push_constant_size: u32,
+    /// Vertex stage only; empty for the others.
+    pub(crate) inputs: Vec<ShaderInput>,
+    /// Specialization constants by constant_id.
+    pub(crate) spec_constants: BTreeMap<u32, ScalarKind>,
+}
+
+/// The pipeline layout a set of shader stages declares between them (see
+/// merge_layout).
+#[derive(Clone, Default)]
+pub(crate) struct ReflectedLayout {
+    /// Bindings by set index, in binding order; a set no stage uses is
+    /// empty. Counts are the shaders' (0 for a runtime-sized array).
+    pub(crate) sets: Vec<Vec<vk::DescriptorSetLayoutBinding<'static>>>,
+    /// From offset 0 to the end of the largest block, visible to every
+    /// stage that has one; None when none does.
+    pub(crate) push_constants: Option<vk::PushConstantRange>,
+}
+
+/// Decorations on one id (or on one struct member).
+#[derive(Clone, Copy, Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    offset: Option<u32>,
+    array_stride: Option<u32>,
+    matrix_stride: Option<u32>,
+    spec_id: Option<u32>,
+    builtin: bool,
+    buffer_block: bool,
+}
+
+impl Decorations {
+    fn apply(&mut self, decoration: Decoration, value: Option<u32>) {
+        match decoration {
+            Decoration::DescriptorSet => self.set = value,
+            Decoration::Binding => self.binding = value,
+            Decoration::Location => self.location = value,
+            Decoration::Offset => self.offset = value,
+            Decoration::ArrayStride => self.array_stride = value,
+            Decoration::MatrixStride => self.matrix_stride = value,
+            Decoration::SpecId => self.spec_id = value,
+            Decoration::BuiltIn => self.builtin = true,
+            Decoration::BufferBlock => self.buffer_block = true,
+            _ => {}
+        }
+    }
+}
+
+/// The module's ids, indexed for the lookups below.
+struct Ids<'a> {
+    defs: HashMap<Word, &'a Instruction>,
+    names: HashMap<Word, &'a str>,
+    decorations: HashMap<Word, Decorations>,
+    members: HashMap<(Word, u32), Decorations>,
+}
+
+fn literal(op: Option<&Operand>) -> Option<u32> {
+    match op? {
+        Operand::LiteralInt32(v) => Some(*v),
+        Operand::LiteralInt64(v) => u32::try_from(*v).ok(),
+        _ => None,
+    }
+}
+
+fn id_ref(op: Option<&Operand>) -> Option<Word> {
+    match op? {
+        Operand::IdRef(id) => Some(*id),
+        _ => None,
+    }
+}
+
+impl<'a> Ids<'a> {
+    fn new(module: &'a Module) -> Self {
+        let defs = module
+            .types_global_values
+            .iter()
+            .filter_map(|inst| inst.result_id.map(|id| (id, inst)))
+            .collect();
+        let names = module
+            .debug_names
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Name)
+            .filter_map(
+                |inst| match (&inst.operands[..], id_ref(inst.operands.first())) {
+                    ([_, Operand::LiteralString(name)], Some(id)) => Some((id, name.as_str())),
+                    _ => None,
+                },
+            )
+            .collect();
+        let mut decorations: HashMap<Word, Decorations> = HashMap::new();
+        let mut members: HashMap<(Word, u32), Decorations> = HashMap::new();
+        for inst in &module.annotations {
+            let ops = &inst.operands;
+            match inst.class.opcode {
+                Op::Decorate => {
+                    if let (Some(id), Some(Operand::Decoration(d))) =
+                        (id_ref(ops.first()), ops.get(1))
+                    {
+                        decorations
+                            .entry(id)
+                            .or_default()
+                            .apply(*d, literal(ops.get(2)));
+                    }
+                }
+                Op::MemberDecorate => {
+                    if let (Some(id), Some(member), Some(Operand::Decoration(d))) =
+                        (id_ref(ops.first()), literal(ops.get(1)), ops.get(2))
+                    {
+                        members
+                            .entry((id, member))
+                            .or_default()
+                            .apply(*d, literal(ops.get(3)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            defs,
+            names,
+            decorations,
+            members,
+        }
+    }
+
+    fn def(&self, id: Word) -> Result<&'a Instruction> {
+        self.defs
+            .get(&id)
+            .copied()
+            .ok_or_else(|| anyhow!("id %{id} has no definition"))
+    }
+
+    fn decorations(&self, id: Word) -> Decorations {
+        self.decorations.get(&id).copied().unwrap_or_default()
+    }
+
+    /// A variable's name for errors: its own, else its type's (an
+    /// anonymous `uniform Camera { .. };`), else its id.
+    fn name(&self, id: Word, ty: Word) -> String {
+        let named = |id| self.names.get(&id).filter(|name| !name.is_empty());
+        let elem = self.unarray(ty).map_or(ty, |(elem, _)| elem);
+        match named(id).or_else(|| named(elem)) {
+            Some(name) => (*name).to_owned(),
+            None => format!("%{id}"),
+        }
+    }
+
+    /// A type's `idx`th id operand.
+    fn operand(&self, ty: &Instruction, idx: usize) -> Result<Word> {
+        id_ref(ty.operands.get(idx)).ok_or_else(|| anyhow!("malformed {:?}", ty.class.opcode))
+    }
+
+    /// The value of an OpConstant (an array length); a spec constant's
+    /// default.
+    fn constant(&self, id: Word) -> Result<u32> {
+        let inst = self.def(id)?;
+        match inst.class.opcode {
+            Op::Constant | Op::SpecConstant => literal(inst.operands.first())
+                .ok_or_else(|| anyhow!("constant %{id} isn't a 32-bit integer")),
+            op => bail!("array length %{id} is an {op:?}, not a constant"),
+        }
+    }
+
+    /// `ty` with one level of array stripped, and its length (0 for a
+    /// runtime array, 1 for no array).
+    fn unarray(&self, ty: Word) -> Result<(Word, u32)> {
+        let inst = self.def(ty)?;
+        match inst.class.opcode {
+            Op::TypeArray => Ok((
+                self.operand(inst, 0)?,
+                self.constant(self.operand(inst, 1)?)?,
+            )),
+            Op::TypeRuntimeArray => Ok((self.operand(inst, 0)?, 0)),
+            _ => Ok((ty, 1)),
+        }
+    }
+
+    fn descriptor_type(&self, storage: StorageClass, ty: Word) -> Result<vk::DescriptorType> {
+        let inst = self.def(ty)?;
+        let decorations = self.decorations(ty);
+        Ok(match (storage, inst.class.opcode) {
+            (StorageClass::StorageBuffer, _) => vk::DescriptorType::STORAGE_BUFFER,
+            (StorageClass::Uniform, _) if decorations.buffer_block => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (StorageClass::Uniform, _) => vk::DescriptorType::UNIFORM_BUFFER,
+            (_, Op::TypeSampledImage) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (_, Op::TypeSampler) => vk::DescriptorType::SAMPLER,
+            (_, Op::TypeAccelerationStructureKHR) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            (_, Op::TypeImage) => {
+                // OpTypeImage: sampled type, Dim, depth, arrayed, MS, sampled.
+                let sampled = literal(inst.operands.get(5)) == Some(1);
+                match (inst.operands.get(1), sampled) {
+                    (Some(Operand::Dim(Dim::DimSubpassData)), _) => {
+                        vk::DescriptorType::INPUT_ATTACHMENT
+                    }
+                    (Some(Operand::Dim(Dim::DimBuffer)), true) => {
+                        vk::DescriptorType::UNIFORM_TEXEL_BUFFER
+                    }
+                    (Some(Operand::Dim(Dim::DimBuffer)), false) => {
+                        vk::DescriptorType::STORAGE_TEXEL_BUFFER
+                    }
+                    (_, true) => vk::DescriptorType::SAMPLED_IMAGE,
+                    (_, false) => vk::DescriptorType::STORAGE_IMAGE,
+                }
+            }
+            (_, op) => bail!("a {storage:?} {op:?} isn't a descriptor"),
+        })
+    }
+
+    /// Bytes a block member of type `ty` occupies, using its MatrixStride
+    /// (`matrix_stride`) and the types' ArrayStride and member Offsets.
+    fn size_of(&self, ty: Word, matrix_stride: Option<u32>) -> Result<u32> {
+        let inst = self.def(ty)?;
+        Ok(match inst.class.opcode {
+            Op::TypeInt | Op::TypeFloat => literal(inst.operands.first()).unwrap_or(32) / 8,
+            Op::TypeBool => 4,
+            Op::TypeVector => {
+                let count = literal(inst.operands.get(1)).unwrap_or(0);
+                self.size_of(self.operand(inst, 0)?, None)? * count
+            }
+            Op::TypeMatrix => {
+                let columns = literal(inst.operands.get(1)).unwrap_or(0);
+                let column = self.operand(inst, 0)?;
+                match matrix_stride {
+                    Some(stride) => stride * columns,
+                    None => self.size_of(column, None)? * columns,
+                }
+            }
+            Op::TypeArray => {
+                let (elem, len) = self.unarray(ty)?;
+                let stride = match self.decorations(ty).array_stride {
+                    Some(stride) => stride,
+                    None => self.size_of(elem, matrix_stride)?,
+                };
+                stride * len
+            }
+            Op::TypeStruct => {
+                let mut end = 0;
+                for (i, op) in inst.operands.iter().enumerate() {
+                    let member = id_ref(Some(op)).context("malformed OpTypeStruct")?;
+                    let decorations = self
+                        .members
+                        .get(&(ty, i as u32))
+                        .copied()
+                        .unwrap_or_default();
+                    let size = self.size_of(member, decorations.matrix_stride)?;
+                    end = end.max(decorations.offset.unwrap_or(end) + size);
+                }
+                end
+            }
+            op => bail!("{op:?} has no size in a block"),
+        })
+    }
+
+    /// A specialization constant's type: OpSpecConstantTrue/False, or an
+    /// OpSpecConstant's 32-bit scalar.
+    fn spec_kind(&self, inst: &Instruction) -> Result<ScalarKind> {
+        match inst.class.opcode {
+            Op::SpecConstantTrue | Op::SpecConstantFalse => Ok(ScalarKind::Bool),
+            _ => {
+                let ty = inst.result_type.context("OpSpecConstant without a type")?;
+                Ok(self.input_kind(ty)?.0)
+            }
+        }
+    }
+
+    /// The numeric type of a vertex input's type, and the locations it
+    /// takes (a matrix or array takes one per column or element).
+    fn input_kind(&self, ty: Word) -> Result<(ScalarKind, u32)> {
+        let inst = self.def(ty)?;
+        Ok(match inst.class.opcode {
+            Op::TypeFloat if literal(inst.operands.first()) == Some(32) => (ScalarKind::Float, 1),
+            Op::TypeInt if literal(inst.operands.first()) == Some(32) => {
+                match literal(inst.operands.get(1)) {
+                    Some(0) => (ScalarKind::Uint, 1),
+                    _ => (ScalarKind::Sint, 1),
+                }
+            }
+            Op::TypeVector => self.input_kind(self.operand(inst, 0)?)?,
+            Op::TypeMatrix => {
+                let (kind, _) = self.input_kind(self.operand(inst, 0)?)?;
+                (kind, literal(inst.operands.get(1)).unwrap_or(1))
+            }
+            Op::TypeArray => {
+                let (elem, len) = self.unarray(ty)?;
+                let (kind, per) = self.input_kind(elem)?;
+                (kind, per * len)
+            }
+            _ => (ScalarKind::Other, 1),
+        })
+    }
+}
+
+/// Reflect the module's first entry point.
+pub(crate) fn reflect(words: &[u32]) -> Result<ShaderInterface> {
+    let module = rspirv::dr::load_words(words).map_err(|e| anyhow!("parsing SPIR-V: {e}"))?;
+    let entry = module
+        .entry_points
+        .first()
+        .ok_or_else(|| anyhow!("no entry point"))?;
+    let stage = match entry.operands.first() {
+        Some(Operand::ExecutionModel(ExecutionModel::Vertex)) => vk::ShaderStageFlags::VERTEX,
+        Some(Operand::ExecutionModel(ExecutionModel::Fragment)) => vk::ShaderStageFlags::FRAGMENT,
+        Some(Operand::ExecutionModel(ExecutionModel::GLCompute)) => vk::ShaderStageFlags::COMPUTE,
+        other => bail!("unsupported execution model {other:?}"),
+    };
+    // From SPIR-V 1.4 the entry point lists every global it uses, so
+    // declared-but-unused resources aren't held against the layout.
+    let version = module.header.as_ref().map_or((1, 0), |h| h.version());
+    let interface: Option<Vec<Word>> = (version >= (1, 4)).then(|| {
+        entry
+            .operands
+            .iter()
+            .skip(3)
+            .filter_map(|op| id_ref(Some(op)))
+            .collect()
+    });
+
+    let ids = Ids::new(&module);
+    let mut shader = ShaderInterface {
+        stage,
+        bindings: Vec::new(),
+        push_constant_size: 0,
+        inputs: Vec::new(),
+        spec_constants: BTreeMap::new(),
+    };
+    for inst in &module.types_global_values {
+        let spec_id = inst
+            .result_id
+            .and_then(|id| ids.decorations(id).spec_id)
+            .filter(|_| {
+                matches!(
+                    inst.class.opcode,
+                    Op::SpecConstant | Op::SpecConstantTrue | Op::SpecConstantFalse
+                )
+            });
+        if let Some(spec_id) = spec_id {
+            shader.spec_constants.insert(spec_id, ids.spec_kind(inst)?);
+        }
+    }
+    for var in &module.types_global_values {
+        if var.class.opcode != Op::Variable {
+            continue;
+        }
+        let (Some(id), Some(ptr)) = (var.result_id, var.result_type) else {
+            continue;
+        };
+        if interface.as_ref().is_some_and(|used| !used.contains(&id)) {
+            continue;
+        }
+        let Some(Operand::StorageClass(storage)) = var.operands.first().cloned() else {
+            continue;
+        };
+        // OpTypePointer: storage class, pointee.
+        let pointee = ids.operand(ids.def(ptr)?, 1)?;
+        let decorations = ids.decorations(id);
+        let name = ids.name(id, pointee);
+        match storage {
+            StorageClass::Uniform | StorageClass::UniformConstant | StorageClass::StorageBuffer => {
+                let (Some(set), Some(binding)) = (decorations.set, decorations.binding) else {
+                    continue;
+                };
+                let (elem, count) = ids.unarray(pointee)?;
+                let descriptor_type = ids
+                    .descriptor_type(storage, elem)
+                    .with_context(|| format!("{name} (set {set}, binding {binding})"))?;
+                shader.bindings.push(ShaderBinding {
+                    set,
+                    binding,
+                    descriptor_type,
+                    count,
+                    name,
+                });
+            }
+            StorageClass::PushConstant => {
+                shader.push_constant_size = ids
+                    .size_of(pointee, None)
+                    .with_context(|| format!("push constant block {name}"))?;
+            }
+            StorageClass::Input if stage == vk::ShaderStageFlags::VERTEX => {
+                if decorations.builtin {
+                    continue;
+                }
+                let Some(location) = decorations.location else {
+                    continue;
+                };
+                let (kind, locations) = ids.input_kind(pointee)?;
+                shader.inputs.extend((0..locations).map(|i| ShaderInput {
+                    location: location + i,
+                    kind,
+                    name: name.clone(),
+                }));
+            }
+            _ => {}
+        }
+    }
+    shader.inputs.sort_by_key(|input| input.location);
+    Ok(shader)
+}
+
+/// Merge the bindings and push constant blocks of `shaders`, the stages of
+/// one pipeline. A binding more than one stage declares gets every such
+/// stage's flag and the largest count, and must have the same type in each.
+pub(crate) fn merge_layout(shaders: &[&ShaderInterface]) -> Result<ReflectedLayout> {
+    let mut layout = ReflectedLayout::default();
+    for shader in shaders {
+        for b in &shader.bindings {
+            let set = b.set as usize;
+            if layout.sets.len() <= set {
+                layout.sets.resize_with(set + 1, Vec::new);
+            }
+            let bindings = &mut layout.sets[set];
+            match bindings.iter_mut().find(|d| d.binding == b.binding) {
+                Some(merged) => {
+                    if merged.descriptor_type != b.descriptor_type {
+                        bail!(
+                            "{} (set {}, binding {}) is a {:?} in the {:?} stage but a {:?} in {:?}",
+                            b.name,
+                            b.set,
+                            b.binding,
+                            b.descriptor_type,
+                            shader.stage,
+                            merged.descriptor_type,
+                            merged.stage_flags
+                        );
+                    }
+                    merged.stage_flags |= shader.stage;
+                    // A runtime-sized array in either stage stays one.
+                    merged.descriptor_count = match (merged.descriptor_count, b.count) {
+                        (0, _) | (_, 0) => 0,
+                        (a, b) => a.max(b),
+                    };
+                }
+                None => bindings.push(vk::DescriptorSetLayoutBinding {
+                    binding: b.binding,
+                    descriptor_type: b.descriptor_type,
+                    descriptor_count: b.count,
+                    stage_flags: shader.stage,
+                    ..Default::default()
+                }),
+            }
+        }
+        if shader.push_constant_size > 0 {
+            let range = layout
+                .push_constants
+                .get_or_insert(vk::PushConstantRange::default());
+            range.stage_flags |= shader.stage;
+            range.size = range.size.max(shader.push_constant_size);
+        }
+    }
+    for bindings in &mut layout.sets {
+        bindings.sort_by_key(|d| d.binding);
+    }
+    Ok(layout)
+}
+
+/// The numeric type a vertex shader reads `format` as.
+pub(crate) fn format_kind(format: VertexFormat) -> ScalarKind {
+    match format {
+        VertexFormat::Float32
+        | VertexFormat::Float32x2
+        | VertexFormat::Float32x3
+        | VertexFormat::Float32x4
+        | VertexFormat::Unorm8x4
+        | VertexFormat::Snorm8x4
+        | VertexFormat::Unorm16x4
+        | VertexFormat::Snorm16x4 => ScalarKind::Float,
+        VertexFormat::Uint32
+        | VertexFormat::Uint32x2
+        | VertexFormat::Uint32x3
+        | VertexFormat::Uint32x4
+        | VertexFormat::Uint8x4
+        | VertexFormat::Uint16x4 => ScalarKind::Uint,
+        VertexFormat::Sint32 => ScalarKind::Sint,
+    }
+}
+
+/// Check `constants` against the specialization constants `shaders`
+/// declare between them: each id must be declared, as the value's type.
+pub(crate) fn check_specialization(
+    shaders: &[&ShaderInterface],
+    constants: &BTreeMap<u32, SpecValue>,
+) -> Result<()> {
+    for (&id, value) in constants {
+        let declared = shaders
+            .iter()
+            .find_map(|shader| shader.spec_constants.get(&id))
+            .ok_or_else(|| anyhow!("no shader declares constant_id {id}"))?;
+        if value.kind() != *declared {
+            bail!("constant_id {id} is a {declared:?} in the shader but was given {value:?}");
+        }
+    }
+    Ok(())
+}
+
+/// Whether a shader's `reflected` type is what a layout's `declared`
+/// binding holds. Dynamic offsets are the layout's business only.
+fn compatible_types(reflected: vk::DescriptorType, declared: vk::DescriptorType) -> bool {
+    let base = |ty| match ty {
+        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => vk::DescriptorType::UNIFORM_BUFFER,
+        vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => vk::DescriptorType::STORAGE_BUFFER,
+        ty => ty,
+    };
+    base(reflected) == base(declared)
+}
+
+/// Check `shader`'s bindings against `sets`, the bindings of each set
+/// layout in pipeline layout order.
+pub(crate) fn check_bindings(
+    shader: &ShaderInterface,
+    sets: &[&[vk::DescriptorSetLayoutBinding]],
+) -> Result<()> {
+    for b in &shader.bindings {
+        let declared = sets
+            .get(b.set as usize)
+            .and_then(|set| set.iter().find(|d| d.binding == b.binding))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} (set {}, binding {}) isn't in the pipeline layout",
+                    b.name,
+                    b.set,
+                    b.binding
+                )
+            })?;
+        if !compatible_types(b.descriptor_type, declared.descriptor_type) {
+            bail!(
+                "{} (set {}, binding {}) is a {:?} but the layout has a {:?}",
+                b.name,
+                b.set,
+                b.binding,
+                b.descriptor_type,
+                declared.descriptor_type
+            );
+        }
+        if b.count > declared.descriptor_count {
+            bail!(
+                "{} (set {}, binding {}) has {} descriptors but the layout {}",
+                b.name,
+                b.set,
+                b.binding,
+                b.count,
+                declared.descriptor_count
+            );
+        }
+        if !declared.stage_flags.contains(shader.stage) {
+            bail!(
+                "{} (set {}, binding {}) isn't visible to the {:?} stage (layout: {:?})",
+                b.name,
+                b.set,
+                b.binding,
+                shader.stage,
+                declared.stage_flags
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check `shader`'s push constant block fits `range`.
+pub(crate) fn check_push_constants(
+    shader: &ShaderInterface,
+    range: &vk::PushConstantRange,
+) -> Result<()> {
+    if shader.push_constant_size == 0 {
+        return Ok(());
+    }
+    if !range.stage_flags.contains(shader.stage) {
+        bail!(
+            "push constants aren't visible to the {:?} stage",
+            shader.stage
+        );
+    }
+    if shader.push_constant_size > range.offset + range.size {
+        bail!(
+            "push constant block is {} bytes; the layout has {}",
+            shader.push_constant_size,
+            range.offset + range.size
+        );
+    }
+    Ok(())
+}
+
+/// The vertex attribute descriptions for `shader`'s inputs, from the
+/// attributes of `layout` at the same locations (binding 0).
+pub(crate) fn vertex_attributes(
+    shader: &ShaderInterface,
+    layout: &VertexLayout,
+) -> Result<Vec<vk::VertexInputAttributeDescription>> {
+    shader
+        .inputs
+        .iter()
+        .map(|input| {
+            let attr = layout
+                .attributes
+                .iter()
+                .find(|a| a.location == input.location)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "input {} (location {}) has no attribute in the vertex layout",
+                        input.name,
+                        input.location
+                    )
+                })?;
+            if format_kind(attr.format) != input.kind {
+                bail!(
+                    "input {} (location {}) reads {:?} but the vertex layout has {:?}",
+                    input.name,
+                    input.location,
+                    input.kind,
+                    attr.format
+                );
+            }
+            Ok(vk::VertexInputAttributeDescription {
+                location: input.location,
+                binding: 0,
+                format: vk_vertex_format(attr.format),
+                offset: attr.offset,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::pipeline::{load_spv_file, push_data_range};
+    use crate::resources::{
+        camera_set_bindings, indirect_graphics_set_binding, material_set_bindings, CullPush,
+        TextureArrayCaps,
+    };
+
+    /// Reflect the committed `assets/shaders/<file>`.
+    fn reflect_committed(file: &str) -> ShaderInterface {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../assets/shaders")
+            .join(file);
+        let words = load_spv_file(&path).unwrap();
+        reflect(&words).unwrap()
+    }
+
+    fn sorted_bindings(shader: &ShaderInterface) -> Vec<(u32, u32, vk::DescriptorType, u32)> {
+        let mut bindings: Vec<_> = shader
+            .bindings
+            .iter()
+            .map(|b| (b.set, b.binding, b.descriptor_type, b.count))
+            .collect();
+        bindings.sort();
+        bindings
+    }
+
+    fn shader(stage: vk::ShaderStageFlags, bindings: &[ShaderBinding]) -> ShaderInterface {
+        ShaderInterface {
+            stage,
+            bindings: bindings.to_vec(),
+            push_constant_size: 0,
+            inputs: Vec::new(),
+            spec_constants: BTreeMap::new(),
+        }
+    }
+
+    fn shader_binding(set: u32, binding: u32, ty: vk::DescriptorType) -> ShaderBinding {
+        ShaderBinding {
+            set,
+            binding,
+            descriptor_type: ty,
+            count: 1,
+            name: format!("b{set}_{binding}"),
+        }
+    }
+
+    #[test]
+    fn tri_vert_reads_the_camera_candidates_and_standard_vertex() {
+        let vs = reflect_committed("tri.vert.spv");
+        assert_eq!(vs.stage, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(
+            sorted_bindings(&vs),
+            [
+                (0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+                (2, 0, vk::DescriptorType::STORAGE_BUFFER, 1),
+            ]
+        );
+        assert_eq!(vs.push_constant_size, 0);
+        let inputs: Vec<_> = vs.inputs.iter().map(|i| (i.location, i.kind)).collect();
+        assert_eq!(
+            inputs,
+            [
+                (0, ScalarKind::Float),
+                (1, ScalarKind::Float),
+                (2, ScalarKind::Float),
+                (3, ScalarKind::Float),
+                (4, ScalarKind::Uint),
+            ]
+        );
+
+        let layout = VertexLayout::standard();
+        let attributes = vertex_attributes(&vs, &layout).unwrap();
+        assert_eq!(attributes.len(), layout.attributes.len());
+        for (vk_attr, attr) in attributes.iter().zip(&layout.attributes) {
+            assert_eq!(vk_attr.location, attr.location);
+            assert_eq!(vk_attr.binding, 0);
+            assert_eq!(vk_attr.format, vk_vertex_format(attr.format));
+            assert_eq!(vk_attr.offset, attr.offset);
+        }
+        // A layout missing a location the shader reads, or with the wrong
+        // numeric type there, is refused.
+        let mut missing = VertexLayout::standard();
+        missing.attributes.retain(|a| a.location != 3);
+        assert!(vertex_attributes(&vs, &missing).is_err());
+        let mut float_index = VertexLayout::standard();
+        float_index.attributes[4].format = VertexFormat::Float32;
+        assert!(vertex_attributes(&vs, &float_index).is_err());
+    }
+
+    #[test]
+    fn tri_frag_reads_the_scene_and_material_sets() {
+        let fs = reflect_committed("tri.frag.spv");
+        assert_eq!(fs.stage, vk::ShaderStageFlags::FRAGMENT);
+        assert!(fs.inputs.is_empty());
+        assert_eq!(
+            sorted_bindings(&fs),
+            [
+                // Camera, shadow map, light list, clusters, irradiance.
+                (0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+                (0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                (0, 2, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (0, 3, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (0, 4, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                // Bindless array, material block, block texture array.
+                (1, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 0),
+                (1, 1, vk::DescriptorType::UNIFORM_BUFFER, 1),
+                (1, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn tri_stages_merge_into_the_scene_sets() {
+        let vs = reflect_committed("tri.vert.spv");
+        let fs = reflect_committed("tri.frag.spv");
+        let layout = merge_layout(&[&vs, &fs]).unwrap();
+        assert_eq!(layout.sets.len(), 3);
+        assert!(layout.push_constants.is_none());
+        let camera = layout.sets[0].iter().find(|b| b.binding == 0).unwrap();
+        assert_eq!(camera.descriptor_type, vk::DescriptorType::UNIFORM_BUFFER);
+        assert!(camera.stage_flags.contains(vk::ShaderStageFlags::VERTEX));
+        let textures = layout.sets[1].iter().find(|b| b.binding == 0).unwrap();
+        assert_eq!(textures.descriptor_count, 0);
+        assert_eq!(textures.stage_flags, vk::ShaderStageFlags::FRAGMENT);
+        let candidates = &layout.sets[2];
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(
+            candidates[0].descriptor_type,
+            vk::DescriptorType::STORAGE_BUFFER
+        );
+        assert_eq!(candidates[0].stage_flags, vk::ShaderStageFlags::VERTEX);
+
+        // Both stages fit the layouts the scene's sets are allocated from.
+        let caps = TextureArrayCaps {
+            capacity: 1024,
+            update_after_bind: false,
+        };
+        let camera = camera_set_bindings();
+        let material = material_set_bindings(caps);
+        let indirect = [indirect_graphics_set_binding()];
+        let sets: [&[vk::DescriptorSetLayoutBinding]; 3] = [&camera, &material, &indirect];
+        for stage in [&vs, &fs] {
+            check_bindings(stage, &sets).unwrap();
+            check_push_constants(stage, &push_data_range()).unwrap();
+        }
+    }
+
+    #[test]
+    fn indirect_cull_reads_its_buffers_and_push_block() {
+        let cs = reflect_committed("indirect_cull.comp.spv");
+        assert_eq!(cs.stage, vk::ShaderStageFlags::COMPUTE);
+        assert_eq!(
+            sorted_bindings(&cs),
+            [
+                (0, 0, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (0, 1, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (0, 2, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (0, 3, vk::DescriptorType::STORAGE_BUFFER, 1),
+            ]
+        );
+        let layout = merge_layout(&[&cs]).unwrap();
+        let push = layout.push_constants.unwrap();
+        assert_eq!(push.stage_flags, vk::ShaderStageFlags::COMPUTE);
+        assert_eq!(push.offset, 0);
+        assert_eq!(push.size, std::mem::size_of::<CullPush>() as u32);
+
+        let noop = reflect_committed("noop.comp.spv");
+        assert!(noop.bindings.is_empty());
+        assert_eq!(noop.push_constant_size, 0);
+        let layout = merge_layout(&[&noop]).unwrap();
+        assert!(layout.sets.is_empty() && layout.push_constants.is_none());
+    }
+
+    #[test]
+    fn merge_unions_stages_and_push_blocks() {
+        let mut vs = shader(
+            vk::ShaderStageFlags::VERTEX,
+            &[
+                shader_binding(0, 0, vk::DescriptorType::UNIFORM_BUFFER),
+                shader_binding(2, 0, vk::DescriptorType::STORAGE_BUFFER),
+            ],
+        );
+        vs.push_constant_size = 64;
+        let mut fs = shader(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[
+                shader_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+                shader_binding(0, 0, vk::DescriptorType::UNIFORM_BUFFER),
+            ],
+        );
+        fs.push_constant_size = 88;
+        fs.bindings[0].count = 4;
+        let layout = merge_layout(&[&vs, &fs]).unwrap();
+
+        let both = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+        let sets: Vec<Vec<_>> = layout
+            .sets
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|b| {
+                        (
+                            b.binding,
+                            b.descriptor_type,
+                            b.descriptor_count,
+                            b.stage_flags,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                vec![
+                    (0, vk::DescriptorType::UNIFORM_BUFFER, 1, both),
+                    (
+                        1,
+                        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        4,
+                        vk::ShaderStageFlags::FRAGMENT
+                    ),
+                ],
+                vec![],
+                vec![(
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    1,
+                    vk::ShaderStageFlags::VERTEX
+                )],
+            ]
+        );
+        let push = layout.push_constants.unwrap();
+        assert_eq!((push.stage_flags, push.offset, push.size), (both, 0, 88));
+    }
+
+    #[test]
+    fn merge_refuses_a_binding_typed_differently_per_stage() {
+        let vs = shader(
+            vk::ShaderStageFlags::VERTEX,
+            &[shader_binding(0, 0, vk::DescriptorType::UNIFORM_BUFFER)],
+        );
+        let fs = shader(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[shader_binding(0, 0, vk::DescriptorType::STORAGE_BUFFER)],
+        );
+        assert!(merge_layout(&[&vs, &fs]).is_err());
+    }
+
+    #[test]
+    fn bindings_must_be_in_the_layout_for_the_stage() {
+        let camera = camera_set_bindings();
+        let sets: [&[vk::DescriptorSetLayoutBinding]; 1] = [&camera];
+        // The shadow map is fragment-only.
+        let vs = shader(
+            vk::ShaderStageFlags::VERTEX,
+            &[shader_binding(
+                0,
+                1,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            )],
+        );
+        assert!(check_bindings(&vs, &sets).is_err());
+        // No set 1 in this layout.
+        let fs = shader(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[shader_binding(1, 0, vk::DescriptorType::UNIFORM_BUFFER)],
+        );
+        assert!(check_bindings(&fs, &sets).is_err());
+        // The camera block is dynamic in the layout, which the shader
+        // can't tell apart.
+        let ok = shader(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[shader_binding(0, 0, vk::DescriptorType::UNIFORM_BUFFER)],
+        );
+        check_bindings(&ok, &sets).unwrap();
+    }
+}
@@ -0,0 +1,533 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Offscreen render targets (render-to-texture): FP16 colour images with
+//! their own depth, which the scene is drawn into from a second camera and
+//! which materials then sample like any uploaded texture. Mirrors,
+//! minimaps, portal views.
+//!
+//! A target's colour, and its depth with RenderTargetDesc::sample_depth,
+//! is registered in the bindless texture array, so its index goes wherever
+//! an upload_texture index would. While a target has a camera
+//! (set_render_target_camera), the frame graph gives it a pass of its own
+//! ahead of the scene: the sky, then every queued draw (the main camera's
+//! culling doesn't apply) into the cleared target. The graph moves it
+//! between attachment and sampled layouts around that pass and the passes
+//! that read it, so materials always see it SHADER_READ_ONLY. A target
+//! without a camera keeps its last image, black to begin with.
+//!
+//! Target views have no shadows, since the cascades follow the main
+//! camera, and a target's own texture must not be drawn into it. Targets
+//! need the scene pipelines' FP16 format, so the first one turns the
+//! tonemap pass on, as post-processing does; if that pass can't be built,
+//! targets are never drawn. They don't survive device loss: their indices
+//! come back as black textures and their handles are dead.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use cubic_math::{Camera, Mat4};
+use cubic_render::{clip, DepthConvention, RenderResult, RenderSize};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::frame_graph::{Access, FrameGraph, LoadOp, ResourceId};
+use crate::resources::{
+    clear_to_shader_read, create_color_target, create_depth_target, depth_aspect_mask,
+    depth_attachment_layout, CameraUbo, RetainedTexture,
+};
+use crate::sampler::SamplerDesc;
+use crate::tonemap::HDR_TARGET_FORMAT;
+use crate::VkRenderer;
+
+/// Size and options of a create_render_target target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderTargetDesc {
+    pub width: u32,
+    pub height: u32,
+    /// Register the depth image as a texture too (see
+    /// render_target_depth_texture). Reverse-Z: 1 at the near plane, 0 at
+    /// infinity.
+    pub sample_depth: bool,
+}
+
+/// A target from create_render_target, valid until destroy_render_target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetHandle(pub u32);
+
+pub(crate) struct RenderTarget {
+    extent: vk::Extent2D,
+    // tex_store owns the colour image, as it does an uploaded texture's.
+    color_image: vk::Image,
+    color_view: vk::ImageView,
+    color_index: u32,
+    // Depth formats are pure depth (pick_depth_format), so the attachment
+    // view samples too. depth_alloc is Some while the target owns the
+    // image; a sampled one belongs to tex_store at depth_index instead.
+    depth_image: vk::Image,
+    depth_view: vk::ImageView,
+    depth_alloc: Option<Allocation>,
+    depth_index: Option<u32>,
+    camera: Option<Camera>,
+    // This frame's CameraUbo for `camera` in the staging belt.
+    camera_offset: u32,
+}
+
+impl RenderTarget {
+    fn owns_texture(&self, index: u32) -> bool {
+        self.color_index == index || self.depth_index == Some(index)
+    }
+
+    pub(crate) fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        if let Some(alloc) = self.depth_alloc {
+            unsafe {
+                device.destroy_image_view(self.depth_view, None);
+                device.destroy_image(self.depth_image, None);
+            }
+            let _ = allocator.free(alloc);
+        }
+    }
+}
+
+fn destroy_image(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    (image, alloc, view): (vk::Image, Allocation, vk::ImageView),
+) {
+    unsafe {
+        device.destroy_image_view(view, None);
+        device.destroy_image(image, None);
+    }
+    let _ = allocator.free(alloc);
+}
+
+/// The view-projection a target's camera sees the scene through: model
+/// matrices are relative to the main camera, so shift by the offset
+/// between the two before `view`'s rotation and projection.
+pub(crate) fn target_view_proj(main: &Camera, view: &Camera, extent: vk::Extent2D) -> Mat4 {
+    let aspect = extent.width as f32 / extent.height as f32;
+    let offset = (main.position - view.position).as_vec3();
+    clip::camera_view_proj(view, aspect, DepthConvention::ReverseZ) * Mat4::from_translation(offset)
+}
+
+impl VkRenderer {
+    /// Create a render target (see module docs) and register its images
+    /// as textures: one slot, or two with `desc.sample_depth`. Nothing is
+    /// drawn into it until set_render_target_camera gives it a camera.
+    pub fn create_render_target(&mut self, desc: RenderTargetDesc) -> RenderResult<TargetHandle> {
+        if desc.width == 0 || desc.height == 0 {
+            return Err(anyhow!(
+                "create_render_target: empty {}x{} target",
+                desc.width,
+                desc.height
+            )
+            .into());
+        }
+        let capacity = self.tex_caps.capacity;
+        if self.next_tex_index + 1 + desc.sample_depth as u32 > capacity {
+            return Err(anyhow!(
+                "create_render_target: bindless texture array full ({capacity} textures)"
+            )
+            .into());
+        }
+        if desc.sample_depth {
+            let props = unsafe {
+                self.instance
+                    .get_physical_device_format_properties(self.phys, self.depth_format)
+            };
+            if !props
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
+            {
+                return Err(anyhow!(
+                    "create_render_target: {:?} depth can't be sampled on this device",
+                    self.depth_format
+                )
+                .into());
+            }
+        }
+        let color_sampler =
+            SamplerDesc::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        // Depth formats don't promise linear filtering.
+        let depth_sampler = SamplerDesc {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            ..color_sampler
+        };
+        let color_vk_sampler = self.samplers.get(&self.device, &color_sampler)?;
+        let depth_vk_sampler = self.samplers.get(&self.device, &depth_sampler)?;
+
+        let extent = vk::Extent2D {
+            width: desc.width,
+            height: desc.height,
+        };
+        let transfer = vk::ImageUsageFlags::TRANSFER_DST;
+        let depth_usage = if desc.sample_depth {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | transfer
+        } else {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        };
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        let color = create_color_target(
+            &self.device,
+            allocator,
+            extent,
+            HDR_TARGET_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | transfer,
+            "render target",
+        )?;
+        let depth = match create_depth_target(
+            &self.device,
+            allocator,
+            extent,
+            self.depth_format,
+            depth_usage,
+            "render target depth",
+        ) {
+            Ok(depth) => depth,
+            Err(e) => {
+                destroy_image(&self.device, allocator, color);
+                return Err(e.into());
+            }
+        };
+        // Black, and far depth, until the first time it's drawn.
+        let black = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        };
+        let far = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 0.0,
+                stencil: 0,
+            },
+        };
+        let cleared = clear_to_shader_read(
+            &self.device,
+            self.queue,
+            self.cmd_pool,
+            color.0,
+            vk::ImageAspectFlags::COLOR,
+            black,
+        )
+        .and_then(|()| {
+            if !desc.sample_depth {
+                return Ok(());
+            }
+            clear_to_shader_read(
+                &self.device,
+                self.queue,
+                self.cmd_pool,
+                depth.0,
+                depth_aspect_mask(self.depth_format),
+                far,
+            )
+        });
+        if let Err(e) = cleared {
+            destroy_image(&self.device, allocator, color);
+            destroy_image(&self.device, allocator, depth);
+            return Err(e.into());
+        }
+
+        let (color_image, _, color_view) = color;
+        let color_index = self.register_target_image(color, color_vk_sampler, color_sampler);
+        let (depth_image, depth_alloc, depth_view) = depth;
+        let (depth_alloc, depth_index) = if desc.sample_depth {
+            let index = self.register_target_image(
+                (depth_image, depth_alloc, depth_view),
+                depth_vk_sampler,
+                depth_sampler,
+            );
+            (None, Some(index))
+        } else {
+            (Some(depth_alloc), None)
+        };
+        let had_targets = self.has_render_targets();
+        self.render_targets.push(Some(RenderTarget {
+            extent,
+            color_image,
+            color_view,
+            color_index,
+            depth_image,
+            depth_view,
+            depth_alloc,
+            depth_index,
+            camera: None,
+            camera_offset: 0,
+        }));
+        if !had_targets && self.tonemap.is_none() {
+            let want = RenderSize {
+                width: self.extent.width,
+                height: self.extent.height,
+            };
+            let _ = self.recreate_swapchain(want);
+        }
+        Ok(TargetHandle(self.render_targets.len() as u32 - 1))
+    }
+
+    /// Hand a target's image to tex_store at the next bindless index, with
+    /// a black 1x1 stand-in for device-lost replay (see module docs).
+    fn register_target_image(
+        &mut self,
+        (image, alloc, view): (vk::Image, Allocation, vk::ImageView),
+        vk_sampler: vk::Sampler,
+        sampler: SamplerDesc,
+    ) -> u32 {
+        let index = self.next_tex_index;
+        self.material_pool
+            .write_texture(&self.device, index, view, vk_sampler);
+        self.tex_store.push((image, alloc, view, vk_sampler));
+        self.tex_sources.push(RetainedTexture {
+            pixels: vec![0, 0, 0, 255],
+            width: 1,
+            height: 1,
+            sampler,
+        });
+        self.next_tex_index += 1;
+        index
+    }
+
+    /// Free a target. Its texture indices stay registered, as 1x1 black
+    /// textures, since materials may still name them. Its images are
+    /// retired through the trash queue; replace_texture waits for the
+    /// frames in flight to rewrite the indices.
+    pub fn destroy_render_target(&mut self, handle: TargetHandle) -> RenderResult<()> {
+        if self.render_target(handle).is_none() {
+            return Err(anyhow!("destroy_render_target: no target {}", handle.0).into());
+        }
+        let Some(target) = self.render_targets[handle.0 as usize].take() else {
+            return Ok(());
+        };
+        let (color_index, depth_index) = (target.color_index, target.depth_index);
+        if let Some(alloc) = target.depth_alloc {
+            self.retire_image(target.depth_image, alloc, target.depth_view);
+        }
+        for index in std::iter::once(color_index).chain(depth_index) {
+            self.replace_texture(index, &[0, 0, 0, 255], 1, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Draw the scene into `handle` from `camera` every frame from now on,
+    /// at the target's own aspect ratio; None stops drawing it, keeping the
+    /// last image. Positions are world positions, as for set_camera.
+    pub fn set_render_target_camera(
+        &mut self,
+        handle: TargetHandle,
+        camera: Option<Camera>,
+    ) -> RenderResult<()> {
+        let target = self
+            .render_targets
+            .get_mut(handle.0 as usize)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| anyhow!("set_render_target_camera: no target {}", handle.0))?;
+        target.camera = camera;
+        Ok(())
+    }
+
+    /// Bindless index of a target's colour, for MaterialDesc or
+    /// PushData::tex_index.
+    pub fn render_target_texture(&self, handle: TargetHandle) -> Option<u32> {
+        self.render_target(handle).map(|t| t.color_index)
+    }
+
+    /// Bindless index of a target's depth; None unless it was created with
+    /// RenderTargetDesc::sample_depth.
+    pub fn render_target_depth_texture(&self, handle: TargetHandle) -> Option<u32> {
+        self.render_target(handle).and_then(|t| t.depth_index)
+    }
+
+    fn render_target(&self, handle: TargetHandle) -> Option<&RenderTarget> {
+        self.render_targets.get(handle.0 as usize)?.as_ref()
+    }
+
+    pub(crate) fn has_render_targets(&self) -> bool {
+        self.render_targets.iter().any(Option::is_some)
+    }
+
+    /// Whether bindless `index` is a render target's image, which only the
+    /// target itself may replace.
+    pub(crate) fn is_render_target_texture(&self, index: u32) -> bool {
+        self.render_targets
+            .iter()
+            .flatten()
+            .any(|t| t.owns_texture(index))
+    }
+
+    /// Every target camera's CameraUbo: `main_ubo` (the scene's) with the
+    /// view swapped for the target's, and shadows off.
+    pub(crate) fn write_render_target_uniforms(
+        &mut self,
+        main: &Camera,
+        main_ubo: &CameraUbo,
+    ) -> Result<()> {
+        for i in 0..self.render_targets.len() {
+            let Some((view, extent)) = self.render_targets[i]
+                .as_ref()
+                .and_then(|t| Some((t.camera?, t.extent)))
+            else {
+                continue;
+            };
+            let mut light_dir = main_ubo.light_dir;
+            light_dir[3] = 0.0;
+            let ubo = CameraUbo {
+                view_proj: target_view_proj(main, &view, extent).to_cols_array_2d(),
+                view_forward: view.forward().extend(0.0).to_array(),
+                light_dir,
+                // The light clusters are the scene view's.
+                cluster_grid: [0; 4],
+                ..*main_ubo
+            };
+            let offset = self.upload_uniform(&ubo)?;
+            if let Some(target) = self.render_targets[i].as_mut() {
+                target.camera_offset = offset;
+            }
+        }
+        Ok(())
+    }
+
+    /// Import every target into the graph and add a pass for each one
+    /// with a camera, reading `shared` (the scene pass's buffers and
+    /// shadow map) and every other target. Returns the targets' sampled
+    /// images, for the scene pass to declare as read.
+    pub(crate) fn add_render_target_passes(
+        &self,
+        g: &mut FrameGraph,
+        frame: usize,
+        shared: &[(ResourceId, Access)],
+    ) -> Vec<ResourceId> {
+        let mut sampled = Vec::new();
+        if self.tonemap.is_none() {
+            return sampled;
+        }
+        let depth_aspect = depth_aspect_mask(self.depth_format);
+        let depth_layout = depth_attachment_layout(self.depth_format);
+        let mut passes = Vec::new();
+        for (index, target) in self.render_targets.iter().enumerate() {
+            let Some(target) = target else {
+                continue;
+            };
+            let color = g.import_image(
+                "render target",
+                target.color_image,
+                target.color_view,
+                vk::ImageAspectFlags::COLOR,
+                Access::sampled_fragment(),
+            );
+            g.export(color, Some(Access::sampled_fragment()));
+            sampled.push(color);
+            let depth = if target.depth_index.is_some() {
+                let depth = g.import_image(
+                    "render target depth",
+                    target.depth_image,
+                    target.depth_view,
+                    depth_aspect,
+                    Access::sampled_fragment(),
+                );
+                g.export(depth, Some(Access::sampled_fragment()));
+                sampled.push(depth);
+                depth
+            } else {
+                g.import_image(
+                    "render target depth",
+                    target.depth_image,
+                    target.depth_view,
+                    depth_aspect,
+                    Access::depth_attachment(LoadOp::DontCare, false, depth_layout),
+                )
+            };
+            if target.camera.is_some() {
+                let store_depth = target.depth_index.is_some();
+                passes.push((index, target.extent, color, depth, store_depth));
+            }
+        }
+
+        let clear = self.clear;
+        let depth_clear = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 0.0,
+                stencil: 0,
+            },
+        };
+        for (index, extent, color, depth, store_depth) in passes {
+            let mut uses = vec![
+                (color, Access::color_attachment(LoadOp::Clear(clear))),
+                (
+                    depth,
+                    Access::depth_attachment(LoadOp::Clear(depth_clear), store_depth, depth_layout),
+                ),
+            ];
+            uses.extend_from_slice(shared);
+            uses.extend(
+                sampled
+                    .iter()
+                    .filter(|&&id| id != color && id != depth)
+                    .map(|&id| (id, Access::sampled_fragment())),
+            );
+            g.add_pass_at("render target", extent, &uses, move |r, cmd| {
+                r.record_render_target(cmd, frame, index);
+                Ok(())
+            });
+        }
+        sampled
+    }
+
+    /// The "render target" pass for render_targets[index]: sky, then every
+    /// queued draw directly, through the target camera's uniforms.
+    fn record_render_target(&self, cmd: vk::CommandBuffer, frame: usize, index: usize) {
+        let Some(target) = self.render_targets.get(index).and_then(Option::as_ref) else {
+            return;
+        };
+        let Some(camera) = target.camera else {
+            return;
+        };
+        let extent = target.extent;
+        let aspect = extent.width as f32 / extent.height as f32;
+        self.record_sky(
+            cmd,
+            clip::camera_view_proj(&camera, aspect, DepthConvention::ReverseZ),
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+        );
+        // Flipped like the scene's viewport.
+        let vp = vk::Viewport {
+            x: 0.0,
+            y: extent.height as f32,
+            width: extent.width as f32,
+            height: -(extent.height as f32),
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let sc = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let sets = self.scene_sets(frame);
+        let offsets = self.frame_uniforms.with_camera(target.camera_offset);
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&sc));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &sets,
+                &offsets,
+            );
+            self.device.cmd_bind_vertex_buffers(
+                cmd,
+                0,
+                std::slice::from_ref(&self.shared_vbuf),
+                &[0],
+            );
+            self.device
+                .cmd_bind_index_buffer(cmd, self.shared_ibuf, 0, vk::IndexType::UINT32);
+        }
+        self.record_direct_draws(cmd, 0, false, &sets, &offsets);
+    }
+}
@@ -3,18 +3,17 @@
 use anyhow::{anyhow, Context, Result};
 use ash::vk;
 use bytemuck::{Pod, Zeroable};
-use cubic_math::Camera;
+use cubic_math::{Camera, Vec3};
+use cubic_render::RenderResult;
 use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
 use gpu_allocator::MemoryLocation;
 
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::error::alloc_error;
+use crate::lighting::{compute_cascades, pack_lights, LightList, MAX_SHADOW_CASCADES};
+use crate::sampler::SamplerDesc;
 use crate::VkRenderer;
 
-// Vertex and PushData now live in cubic-render (the shared trait crate) so
-// that cubic-world's mesher can produce them without depending on Vulkan.
-// Re-export them here so existing internal paths (super::resources::Vertex
-// etc.) continue to work inside this crate.
-pub use cubic_render::Vertex;
-
 /// Fixed capacity of the shared mesh vertex/index buffers all `upload_mesh`
 /// calls bump-allocate from. There's no mesh-freeing API yet (matching
 /// `VkRenderer::meshes`, which also only ever grows), so this is sized
@@ -25,11 +24,91 @@ pub(crate) const MAX_SHARED_INDICES: u64 = 12582912;
 /// Max draws the indirect-cull compute shader can emit in one dispatch.
 pub(crate) const MAX_INDIRECT_DRAWS: u32 = 32768;
 
-/// Upper bound on the bindless texture array's descriptor count. Most
-/// slots can stay unwritten (the layout binding is PARTIALLY_BOUND); this
-/// just caps how many distinct textures can ever be registered at once.
+/// Upper bound on the bindless texture array's descriptor count without
+/// update-after-bind support. Most slots can stay unwritten (the layout
+/// binding is PARTIALLY_BOUND); this just caps how many distinct textures
+/// can ever be registered at once.
 pub(crate) const MAX_TEXTURES: u32 = 256;
 
+/// The same cap where the device can update the array after binding (see
+/// TextureArrayCaps); device limits can lower it further. Every material
+/// set carries a copy of the array, so this isn't the device's limit.
+pub(crate) const MAX_BINDLESS_TEXTURES: u32 = 4096;
+
+/// How the bindless texture array (set 1, binding 0) is built, decided
+/// once per device by `query_texture_array_caps`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TextureArrayCaps {
+    /// Descriptor count of the array; upload_texture's limit.
+    pub(crate) capacity: u32,
+    /// UPDATE_AFTER_BIND + UPDATE_UNUSED_WHILE_PENDING on the binding (and
+    /// UPDATE_AFTER_BIND on its layout and pools): upload_texture can then
+    /// write new slots while in-flight frames still have the set bound.
+    pub(crate) update_after_bind: bool,
+}
+
+/// Update-after-bind needs descriptorBindingSampledImageUpdateAfterBind
+/// and descriptorBindingUpdateUnusedWhilePending (both optional parts of
+/// descriptor indexing); decide_path_and_create_device enables them when
+/// this says so. Without them the array stays at MAX_TEXTURES.
+pub(crate) fn query_texture_array_caps(
+    instance: &ash::Instance,
+    phys: vk::PhysicalDevice,
+) -> TextureArrayCaps {
+    let mut feats12 = vk::PhysicalDeviceVulkan12Features {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_2_FEATURES,
+        ..Default::default()
+    };
+    let mut feats = vk::PhysicalDeviceFeatures2 {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+        p_next: (&mut feats12) as *mut _ as *mut _,
+        ..Default::default()
+    };
+    unsafe { instance.get_physical_device_features2(phys, &mut feats) };
+    let supported = feats12.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+        && feats12.descriptor_binding_update_unused_while_pending == vk::TRUE;
+    if !supported {
+        return TextureArrayCaps {
+            capacity: MAX_TEXTURES,
+            update_after_bind: false,
+        };
+    }
+
+    let mut props12 = vk::PhysicalDeviceVulkan12Properties {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_2_PROPERTIES,
+        ..Default::default()
+    };
+    let mut props = vk::PhysicalDeviceProperties2 {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_PROPERTIES_2,
+        p_next: (&mut props12) as *mut _ as *mut _,
+        ..Default::default()
+    };
+    unsafe { instance.get_physical_device_properties2(phys, &mut props) };
+    // A combined image sampler counts against both the sampler and the
+    // sampled-image limits; leave a few for set 0's shadow map and
+    // whatever registered pipelines' shaders add.
+    let limit = [
+        props12.max_per_stage_descriptor_update_after_bind_samplers,
+        props12.max_per_stage_descriptor_update_after_bind_sampled_images,
+        props12.max_descriptor_set_update_after_bind_samplers,
+        props12.max_descriptor_set_update_after_bind_sampled_images,
+    ]
+    .into_iter()
+    .min()
+    .unwrap_or(0)
+    .saturating_sub(8);
+    if limit < MAX_TEXTURES {
+        return TextureArrayCaps {
+            capacity: MAX_TEXTURES,
+            update_after_bind: false,
+        };
+    }
+    TextureArrayCaps {
+        capacity: limit.min(MAX_BINDLESS_TEXTURES),
+        update_after_bind: true,
+    }
+}
+
 pub(crate) struct RangeAlloc {
     free: Vec<(u32, u32)>, // (start, len), kept sorted by start
 }
@@ -79,35 +158,131 @@ impl RangeAlloc {
 
 // Convention: this holds the combined view*proj matrix only; the model
 // transform is supplied separately via PushData and applied in the vertex
-// shader, so this is not a true "MVP" matrix.
+// shader, so this is not a true "MVP" matrix. The rest is the directional
+// light and its shadow cascades (see lighting.rs), read by tri.frag; std140
+// layout, every member a vec4 or mat4 so no padding rules come into play.
 #[repr(C)]
 #[derive(Clone, Copy, Default, Zeroable, Pod)]
 pub(crate) struct CameraUbo {
     pub(crate) view_proj: [[f32; 4]; 4],
+    // Camera-relative space -> cascade clip space.
+    pub(crate) cascade_view_proj: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES],
+    // View depth each cascade ends at.
+    pub(crate) cascade_splits: [f32; 4],
+    // xyz = camera forward (for view depth), w unused.
+    pub(crate) view_forward: [f32; 4],
+    // xyz = unit direction towards the light, w = 1 with shadows on.
+    pub(crate) light_dir: [f32; 4],
+    // rgb = colour * intensity, w = ambient.
+    pub(crate) light_color: [f32; 4],
+    // x = cascade count, y = normal offset, z = 1 / map resolution.
+    pub(crate) shadow_params: [f32; 4],
+    // Shared vertex pool address (lo, hi), then the index pool's; what a
+    // vertex-pulling shader reads with GL_EXT_buffer_reference_uvec2.
+    pub(crate) mesh_pools: [u32; 4],
+    // Light cluster grid: tiles across, down, depth slices; w = 1 when the
+    // scene shades from the clusters (see light_clusters.rs).
+    pub(crate) cluster_grid: [u32; 4],
+    // Tile width and height in pixels, then the depth slice's scale and
+    // bias on log(view depth).
+    pub(crate) cluster_depth: [f32; 4],
+    // x = 1 with the environment's IBL maps bound, y = the prefiltered
+    // map's last level (see ibl.rs).
+    pub(crate) ibl_params: [f32; 4],
+}
+
+// One cascade's view_proj: what tri.vert sees through the shadow pass's
+// camera set.
+const SHADOW_VIEW_PROJ_SIZE: u64 = std::mem::size_of::<[[f32; 4]; 4]>() as u64;
+
+/// Where this frame's uniform blocks landed in the staging belt: the
+/// dynamic offsets the camera sets are bound with.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FrameUniformOffsets {
+    camera: u32,
+    lights: u32,
+    cascades: [u32; MAX_SHADOW_CASCADES],
+}
+
+impl FrameUniformOffsets {
+    /// For the main camera set: binding 0, then binding 2.
+    pub(crate) fn main(&self) -> [u32; 2] {
+        [self.camera, self.lights]
+    }
+
+    /// The light list on its own, for the light cluster pass.
+    pub(crate) fn lights(&self) -> u32 {
+        self.lights
+    }
+
+    /// For the main camera set with another camera block at `camera` (a
+    /// render target's view), lights as for the scene.
+    pub(crate) fn with_camera(&self, camera: u32) -> [u32; 2] {
+        [camera, self.lights]
+    }
+
+    /// For the shadow camera set, drawing `cascade`.
+    pub(crate) fn shadow(&self, cascade: usize) -> [u32; 2] {
+        [self.cascades[cascade], self.lights]
+    }
 }
 
 impl VkRenderer {
-    pub(crate) fn update_camera_ubo_for_image(
-        &self,
-        image_index: usize,
+    /// Write this frame's camera, light list and cascade blocks (and each
+    /// render target's camera block) to the staging belt and remember
+    /// their offsets for binding.
+    pub(crate) fn write_frame_uniforms(
+        &mut self,
         camera: &Camera,
         aspect: f32,
     ) -> anyhow::Result<()> {
-        let view_proj = camera.projection_matrix(aspect) * camera.view_matrix_no_translation();
+        let view_proj = self.camera_view_proj(camera);
+        let light = &self.light;
+        let shadows = &self.shadow_settings;
+        let cascades = compute_cascades(camera, aspect, light, shadows);
+        let to_light = -Vec3::from(light.direction)
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Y);
+        let color = Vec3::from(light.color) * light.intensity;
+        let (cluster_grid, cluster_depth) = self.light_cluster_params(camera);
         let data = CameraUbo {
             view_proj: view_proj.to_cols_array_2d(),
+            cascade_view_proj: cascades.view_proj.map(|m| m.to_cols_array_2d()),
+            cascade_splits: cascades.splits,
+            view_forward: camera.forward().extend(0.0).to_array(),
+            light_dir: to_light
+                .extend(if self.shadows_active() { 1.0 } else { 0.0 })
+                .to_array(),
+            light_color: color.extend(light.ambient).to_array(),
+            shadow_params: [
+                shadows.cascade_count as f32,
+                shadows.normal_offset,
+                1.0 / shadows.resolution as f32,
+                0.0,
+            ],
+            mesh_pools: {
+                let [v, i] = self.mesh_pool_addresses;
+                [v as u32, (v >> 32) as u32, i as u32, (i >> 32) as u32]
+            },
+            cluster_grid,
+            cluster_depth,
+            ibl_params: self.ibl_params(),
         };
 
-        let dst = self.ubo_ptrs[image_index];
-        if dst.is_null() {
-            return Err(anyhow::anyhow!("UBO memory not mapped"));
-        }
-        let src = bytemuck::bytes_of(&data);
-
-        unsafe {
-            std::ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len());
+        let lights = pack_lights(&self.lights, camera.position);
+        let mut offsets = FrameUniformOffsets {
+            camera: self.upload_uniform(&data)?,
+            lights: self.upload_uniform(&lights)?,
+            cascades: [0; MAX_SHADOW_CASCADES],
+        };
+        // The shadow pass's per-cascade view_proj.
+        for (offset, m) in offsets.cascades.iter_mut().zip(&cascades.view_proj) {
+            *offset = self.upload_uniform(&m.to_cols_array_2d())?;
         }
-        Ok(())
+        self.frame_uniforms = offsets;
+        self.write_viewport_uniforms(camera, &data)?;
+        self.write_stereo_uniforms(camera, &data)?;
+        self.write_render_target_uniforms(camera, &data)
     }
 }
 
@@ -128,29 +303,65 @@ pub(crate) struct DrawCandidate {
     pub(crate) tex_index: u32,
 }
 
-/// Sampler settings derived from `cubic.toml`'s `[render]` texture_filter /
-/// mipmap_mode / anisotropy / lod_bias, applied to every texture the
-/// sampler-creation helpers below build.
-pub(crate) struct SamplerConfig {
-    pub mag_filter: vk::Filter,
-    pub min_filter: vk::Filter,
-    pub mipmap_mode: vk::SamplerMipmapMode,
-    /// 0.0 = disabled, otherwise clamped to the device's
-    /// `max_sampler_anisotropy` limit by the caller before storing.
-    pub max_anisotropy: f32,
-    /// Offset applied to the computed mip level before sampling; positive
-    /// values bias toward blurrier/lower-resolution mips, negative toward
-    /// sharper/higher-resolution ones.
-    pub lod_bias: f32,
+/// Push constants of indirect_cull.comp. `candidate_count` has to stay at
+/// offset 0: shader builds predating culling declare only that field.
+/// `planes` are world-space-relative-to-camera (the same space as
+/// DrawCandidate::model's output), normalized, pointing inwards: left,
+/// right, bottom, top, near. No far plane, the projection has none.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct CullPush {
+    pub(crate) candidate_count: u32,
+    /// 0 = expand every candidate (culling off or no frustum yet).
+    pub(crate) cull_enabled: u32,
+    pub(crate) _pad: [u32; 2],
+    pub(crate) planes: [[f32; 4]; 5],
+}
+
+/// Texture format every upload_texture() image uses (RGBA8 input, sampled
+/// with hardware sRGB decode).
+pub(crate) const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// How mip levels 1.. of an uploaded texture get filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MipGen {
+    /// GPU blit chain (generate_mip_chain) — the normal path.
+    Blit,
+    /// CPU box filter, every level uploaded from staging. For devices whose
+    /// TEXTURE_FORMAT lacks BLIT_SRC/BLIT_DST or linear filtering. Not a
+    /// compute-shader fallback: sRGB formats almost never support
+    /// STORAGE_IMAGE, so a compute downsample would need a UNORM
+    /// mutable-format alias and hand-rolled sRGB encode/decode, all for
+    /// level data that's one-time and tiny next to level 0. The cost is a
+    /// third more staging and one CPU pass per upload, on the rare device
+    /// that lands here (pick_mip_gen warns when it does).
+    Cpu,
+}
+
+/// Pick the mip generation path for TEXTURE_FORMAT on this device.
+pub(crate) fn pick_mip_gen(instance: &ash::Instance, phys: vk::PhysicalDevice) -> MipGen {
+    let props = unsafe { instance.get_physical_device_format_properties(phys, TEXTURE_FORMAT) };
+    let needed = vk::FormatFeatureFlags::BLIT_SRC
+        | vk::FormatFeatureFlags::BLIT_DST
+        | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+    if props.optimal_tiling_features.contains(needed) {
+        MipGen::Blit
+    } else {
+        tracing::warn!("vk: texture format can't be linearly blitted; generating mips on the CPU");
+        MipGen::Cpu
+    }
 }
 
 impl VkRenderer {
     /// Push cubic.toml's texture_filter/mipmap_mode/anisotropy/lod_bias
-    /// settings into the renderer. Only affects textures uploaded *after*
-    /// this call — the dummy texture created in `build_renderer` already
-    /// has its sampler baked in. `anisotropy` is clamped to the device's
+    /// settings into the renderer: the SamplerDesc upload_texture uses.
+    /// Only affects textures uploaded *after* this call — the dummy texture
+    /// created in `build_renderer` already has its sampler baked in. Wrap
+    /// modes are left as they are (repeat). `anisotropy` is clamped to the device's
     /// actual `max_sampler_anisotropy` limit (0.0 disables anisotropic
-    /// filtering regardless of the device limit).
+    /// filtering regardless of the device limit), and ignored entirely on
+    /// devices where samplerAnisotropy wasn't enabled (see
+    /// decide_path_and_create_device).
     pub fn set_sampler_config(
         &mut self,
         mag_filter: vk::Filter,
@@ -159,80 +370,264 @@ impl VkRenderer {
         anisotropy: f32,
         lod_bias: f32,
     ) {
-        let limits = unsafe {
-            self.instance
-                .get_physical_device_properties(self.phys)
-                .limits
-        };
-        let max_anisotropy = anisotropy.clamp(0.0, limits.max_sampler_anisotropy);
-        self.sampler_config = SamplerConfig {
+        if anisotropy > 0.0 && !self.samplers.anisotropy_supported() {
+            tracing::warn!(
+                "vk: max_anisotropy={anisotropy} ignored: samplerAnisotropy unsupported"
+            );
+        }
+        self.sampler_config = self.samplers.clamp(SamplerDesc {
             mag_filter,
             min_filter,
             mipmap_mode,
-            max_anisotropy,
+            max_anisotropy: anisotropy,
             lod_bias,
-        };
+            ..self.sampler_config
+        });
+    }
+
+    /// How many textures the bindless array holds (dummy included): 256,
+    /// or up to 4096 on devices with update-after-bind descriptor indexing.
+    pub fn texture_capacity(&self) -> u32 {
+        self.tex_caps.capacity
     }
 
     /// Upload an RGBA8 texture and register it into the bindless descriptor
     /// array, returning its index (see `PushData::tex_index`). Index 0 is
     /// permanently the dummy texture created in `build_renderer`; this
-    /// starts handing out indices at 1.
-    pub fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<u32> {
-        if self.next_tex_index >= MAX_TEXTURES {
+    /// starts handing out indices at 1. Sampled with cubic.toml's settings
+    /// (see set_sampler_config).
+    pub fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> RenderResult<u32> {
+        self.upload_texture_with_sampler(pixels, width, height, self.sampler_config)
+    }
+
+    /// upload_texture with its own sampling, e.g. nearest/clamp for UI
+    /// sprites or pixel art. Materials using the texture can still override
+    /// it (MaterialDesc::sampler).
+    pub fn upload_texture_with_sampler(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        sampler: SamplerDesc,
+    ) -> RenderResult<u32> {
+        let capacity = self.tex_caps.capacity;
+        if self.next_tex_index >= capacity {
             return Err(anyhow!(
-                "upload_texture: bindless texture array full (MAX_TEXTURES = {MAX_TEXTURES})"
-            ));
+                "upload_texture: bindless texture array full ({capacity} textures)"
+            )
+            .into());
         }
 
-        let (image, alloc, view, sampler) = create_texture_and_sampler(
+        let vk_sampler = self.samplers.get(&self.device, &sampler)?;
+        let (image, alloc, view) = create_texture(
             &self.device,
             self.allocator.as_mut().expect("allocator missing"),
             self.queue,
             self.cmd_pool,
             pixels,
             vk::Extent2D { width, height },
-            &self.sampler_config,
+            self.mip_gen,
         )?;
 
         let index = self.next_tex_index;
-        write_material_descriptors(&self.device, self.material_desc_set, index, view, sampler);
-
-        self.tex_store.push((image, alloc, view, sampler));
+        self.material_pool
+            .write_texture(&self.device, index, view, vk_sampler);
+
+        self.tex_store.push((image, alloc, view, vk_sampler));
+        self.tex_sources.push(RetainedTexture {
+            pixels: pixels.to_vec(),
+            width,
+            height,
+            sampler,
+        });
         self.next_tex_index += 1;
 
         Ok(index)
     }
+
+    /// Swap the pixels behind an upload_texture index for new ones (any
+    /// size), keeping its sampler: asset hot-reload, where draws and
+    /// materials already hold the index. The old image is retired through
+    /// the trash queue, but the bindless entry is rewritten in place, so
+    /// this waits for the frames in flight (see wait_frames_in_flight).
+    pub fn replace_texture(
+        &mut self,
+        index: u32,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<()> {
+        let slot = (index as usize)
+            .checked_sub(1)
+            .filter(|&i| i < self.tex_store.len())
+            .ok_or_else(|| anyhow!("replace_texture: {index} isn't an uploaded texture"))?;
+        if self.pending_textures.iter().any(|&(_, i)| i == index) {
+            return Err(anyhow!("replace_texture: {index} is still uploading").into());
+        }
+        if self.is_render_target_texture(index) {
+            return Err(anyhow!("replace_texture: {index} is a render target").into());
+        }
+        let sampler = self.tex_sources[slot].sampler;
+        let vk_sampler = self.samplers.get(&self.device, &sampler)?;
+        self.wait_frames_in_flight()?;
+        let (image, alloc, view) = create_texture(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.queue,
+            self.cmd_pool,
+            pixels,
+            vk::Extent2D { width, height },
+            self.mip_gen,
+        )?;
+        self.material_pool
+            .write_texture(&self.device, index, view, vk_sampler);
+
+        let (old_image, old_alloc, old_view, _) =
+            std::mem::replace(&mut self.tex_store[slot], (image, alloc, view, vk_sampler));
+        self.retire_image(old_image, old_alloc, old_view);
+        self.tex_sources[slot] = RetainedTexture {
+            pixels: pixels.to_vec(),
+            width,
+            height,
+            sampler,
+        };
+        Ok(())
+    }
+
+    /// upload_texture without the wait: the pixels go up on the transfer
+    /// queue with the next render(), mips built on the CPU right here, and
+    /// the returned index is only registered in the bindless array once
+    /// the GPU has the whole texture. Until the index comes back from
+    /// take_resident_textures, nothing may draw with it (or a material
+    /// use it); it's reserved, not yet readable. Sampled like
+    /// upload_texture.
+    pub fn upload_texture_async(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<u32> {
+        let capacity = self.tex_caps.capacity;
+        if self.next_tex_index >= capacity {
+            return Err(anyhow!(
+                "upload_texture_async: bindless texture array full ({capacity} textures)"
+            )
+            .into());
+        }
+        let sampler = self.sampler_config;
+        let vk_sampler = self.samplers.get(&self.device, &sampler)?;
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        let (image, alloc, view, levels) = create_texture_for_transfer(
+            &self.device,
+            allocator,
+            pixels,
+            vk::Extent2D { width, height },
+            &self.mesh_families,
+        )?;
+        let device = &self.device;
+        let queued = self
+            .uploader
+            .queue_staged(device, allocator, &levels.data, |cmd, staging| {
+                record_texture_upload(device, cmd, staging, image, &levels)
+            });
+        let value = match queued {
+            Ok(value) => value,
+            Err(e) => {
+                unsafe {
+                    self.device.destroy_image_view(view, None);
+                    self.device.destroy_image(image, None);
+                }
+                let _ = allocator.free(alloc);
+                return Err(e.into());
+            }
+        };
+
+        let index = self.next_tex_index;
+        self.tex_store.push((image, alloc, view, vk_sampler));
+        self.tex_sources.push(RetainedTexture {
+            pixels: pixels.to_vec(),
+            width,
+            height,
+            sampler,
+        });
+        self.pending_textures.push((value, index));
+        self.next_tex_index += 1;
+        Ok(index)
+    }
+
+    /// Register every upload_texture_async texture whose transfer has
+    /// finished. Called each frame from render(); non-blocking.
+    pub(crate) fn promote_resident_textures(&mut self) {
+        if self.pending_textures.is_empty() {
+            return;
+        }
+        let done = self.uploader.completed_value(&self.device);
+        self.pending_textures.retain(|&(value, index)| {
+            if value > done {
+                return true;
+            }
+            let (_, _, view, sampler) = &self.tex_store[index as usize - 1];
+            self.material_pool
+                .write_texture(&self.device, index, *view, *sampler);
+            self.resident_textures.push(index);
+            false
+        });
+    }
+
+    /// upload_texture_async indices that became usable since the last
+    /// call, in no particular order. Poll once per frame.
+    pub fn take_resident_textures(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.resident_textures)
+    }
+
+    /// View and sampler registered at bindless `index` (0 = the dummy).
+    pub(crate) fn texture_binding(&self, index: u32) -> (vk::ImageView, vk::Sampler) {
+        match index {
+            0 => (self.tex_view, self.tex_sampler),
+            i => {
+                let (_, _, view, sampler) = &self.tex_store[i as usize - 1];
+                (*view, *sampler)
+            }
+        }
+    }
+}
+
+/// CPU copy of an upload_texture call, replayed in order by device-lost
+/// recovery so every bindless index comes back pointing at the same
+/// texture. Costs one RGBA8 copy per texture in system memory.
+pub(crate) struct RetainedTexture {
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) sampler: SamplerDesc,
 }
 
-struct ImageAllocInfo {
+struct ImageAllocInfo<'a> {
     extent: vk::Extent2D,
     mip_levels: u32,
     format: vk::Format,
     usage: vk::ImageUsageFlags,
     tiling: vk::ImageTiling,
+    // Six cube-compatible array layers instead of one plain 2D image.
+    cube: bool,
+    // Array layers: 1 for a plain 2D image, 6 for a cube.
+    layers: u32,
+    // CONCURRENT across these if more than one, as for
+    // create_buffer_and_memory_shared.
+    queue_families: &'a [u32],
 }
 
-struct LayoutTransition {
-    image: vk::Image,
-    sub: vk::ImageSubresourceRange,
-    src_stage: vk::PipelineStageFlags2,
-    src_access: vk::AccessFlags2,
-    old_layout: vk::ImageLayout,
-    dst_stage: vk::PipelineStageFlags2,
-    dst_access: vk::AccessFlags2,
-    new_layout: vk::ImageLayout,
+pub(crate) struct LayoutTransition {
+    pub(crate) image: vk::Image,
+    pub(crate) sub: vk::ImageSubresourceRange,
+    pub(crate) src_stage: vk::PipelineStageFlags2,
+    pub(crate) src_access: vk::AccessFlags2,
+    pub(crate) old_layout: vk::ImageLayout,
+    pub(crate) dst_stage: vk::PipelineStageFlags2,
+    pub(crate) dst_access: vk::AccessFlags2,
+    pub(crate) new_layout: vk::ImageLayout,
 }
 
-pub(crate) type FrameUniforms = (
-    Vec<vk::Buffer>,
-    Vec<Allocation>,
-    Vec<*mut std::ffi::c_void>,
-    vk::DeviceSize,
-    vk::DescriptorPool,
-    Vec<vk::DescriptorSet>,
-);
-
 fn has_stencil(format: vk::Format) -> bool {
     matches!(
         format,
@@ -305,6 +700,26 @@ pub(crate) fn create_depth_resources(
     allocator: &mut Allocator,
     extent: vk::Extent2D,
     depth_format: vk::Format,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    create_depth_target(
+        device,
+        allocator,
+        extent,
+        depth_format,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        "depth image",
+    )
+}
+
+/// create_depth_resources with extra usage (SAMPLED for a depth texture)
+/// and its own allocation name.
+pub(crate) fn create_depth_target(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    extent: vk::Extent2D,
+    depth_format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    name: &str,
 ) -> Result<(vk::Image, Allocation, vk::ImageView)> {
     let img_ci = vk::ImageCreateInfo {
         s_type: vk::StructureType::IMAGE_CREATE_INFO,
@@ -319,7 +734,7 @@ pub(crate) fn create_depth_resources(
         array_layers: 1,
         samples: vk::SampleCountFlags::TYPE_1,
         tiling: vk::ImageTiling::OPTIMAL,
-        usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        usage,
         sharing_mode: vk::SharingMode::EXCLUSIVE,
         ..Default::default()
     };
@@ -333,12 +748,13 @@ pub(crate) fn create_depth_resources(
     let mem_req = unsafe { device.get_image_memory_requirements(image) };
     let allocation = allocator
         .allocate(&AllocationCreateDesc {
-            name: "depth image",
+            name,
             requirements: mem_req,
             location: MemoryLocation::GpuOnly,
             linear: false,
             allocation_scheme: AllocationScheme::DedicatedImage(image),
         })
+        .map_err(alloc_error)
         .with_context(|| format!("allocate (depth) size={}", mem_req.size))?;
 
     unsafe { device.bind_image_memory(image, allocation.memory(), allocation.offset()) }
@@ -348,6 +764,73 @@ pub(crate) fn create_depth_resources(
     Ok((image, allocation, depth_view))
 }
 
+/// A single-mip color render target (offscreen scene target for post
+/// passes), dedicated GpuOnly allocation like the depth image.
+pub(crate) fn create_color_target(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    name: &str,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    let (image, alloc) = create_image_and_memory(
+        device,
+        allocator,
+        &ImageAllocInfo {
+            extent,
+            mip_levels: 1,
+            format,
+            usage,
+            tiling: vk::ImageTiling::OPTIMAL,
+            cube: false,
+            layers: 1,
+            queue_families: &[],
+        },
+        name,
+    )?;
+    match make_image_view_2d_color(device, image, format, 0, 1) {
+        Ok(view) => Ok((image, alloc, view)),
+        Err(e) => {
+            unsafe { device.destroy_image(image, None) };
+            let _ = allocator.free(alloc);
+            Err(e)
+        }
+    }
+}
+
+/// An image compute shaders write and later passes sample: six
+/// cube-compatible layers with `cube`, else one. Views are the caller's,
+/// since it wants one per level to write and one over all of them to read.
+pub(crate) fn create_storage_image(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    size: u32,
+    mip_levels: u32,
+    format: vk::Format,
+    cube: bool,
+    name: &str,
+) -> Result<(vk::Image, Allocation)> {
+    create_image_and_memory(
+        device,
+        allocator,
+        &ImageAllocInfo {
+            extent: vk::Extent2D {
+                width: size,
+                height: size,
+            },
+            mip_levels,
+            format,
+            usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            tiling: vk::ImageTiling::OPTIMAL,
+            cube,
+            layers: if cube { 6 } else { 1 },
+            queue_families: &[],
+        },
+        name,
+    )
+}
+
 // Buffers are sub-allocated (GpuAllocatorManaged) rather than given a
 // dedicated VkDeviceMemory each: many short-lived/small buffers (UBOs,
 // staging, mesh data) would otherwise burn through the driver's discrete
@@ -360,11 +843,38 @@ pub(crate) fn create_buffer_and_memory(
     location: MemoryLocation,
     name: &str,
 ) -> Result<(vk::Buffer, Allocation)> {
+    create_buffer_and_memory_shared(device, allocator, size, usage, location, name, &[])
+}
+
+/// `create_buffer_and_memory` for buffers touched by more than one queue
+/// family (e.g. written on the transfer queue, read on graphics): CONCURRENT
+/// sharing across `queue_families` spares every upload a queue-family
+/// ownership transfer. Fewer than two families means EXCLUSIVE.
+pub(crate) fn create_buffer_and_memory_shared(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    location: MemoryLocation,
+    name: &str,
+    queue_families: &[u32],
+) -> Result<(vk::Buffer, Allocation)> {
+    let concurrent = queue_families.len() > 1;
     let bci = vk::BufferCreateInfo {
         s_type: vk::StructureType::BUFFER_CREATE_INFO,
         size,
         usage,
-        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        sharing_mode: if concurrent {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        },
+        queue_family_index_count: if concurrent {
+            queue_families.len() as u32
+        } else {
+            0
+        },
+        p_queue_family_indices: queue_families.as_ptr(),
         ..Default::default()
     };
     let buf = unsafe { device.create_buffer(&bci, None) }
@@ -379,6 +889,7 @@ pub(crate) fn create_buffer_and_memory(
             linear: true,
             allocation_scheme: AllocationScheme::GpuAllocatorManaged,
         })
+        .map_err(alloc_error)
         .with_context(|| format!("allocate buffer name={name} usage={usage:?} size={size}"))?;
 
     unsafe { device.bind_buffer_memory(buf, allocation.memory(), allocation.offset()) }
@@ -387,69 +898,171 @@ pub(crate) fn create_buffer_and_memory(
     Ok((buf, allocation))
 }
 
-fn create_host_visible_ubo(
-    device: &ash::Device,
-    allocator: &mut Allocator,
-    size: vk::DeviceSize,
-) -> Result<(vk::Buffer, Allocation)> {
-    create_buffer_and_memory(
-        device,
-        allocator,
-        size,
-        vk::BufferUsageFlags::UNIFORM_BUFFER,
-        MemoryLocation::CpuToGpu,
-        "camera ubo",
-    )
+/// The camera set's bindings (set 0): the layout's, and what graphics
+/// shaders are checked against (see reflect.rs).
+pub(crate) fn camera_set_bindings() -> [vk::DescriptorSetLayoutBinding<'static>; 7] {
+    [
+        // Camera + lighting (CameraUbo); tri.frag reads the lighting half.
+        // This and the light list are dynamic: see create_camera_sets.
+        vk::DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        // Shadow map (see shadow.rs).
+        vk::DescriptorSetLayoutBinding {
+            binding: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        // Light list (LightList, see lighting.rs).
+        vk::DescriptorSetLayoutBinding {
+            binding: 2,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        // Per-cluster light lists (see light_clusters.rs).
+        vk::DescriptorSetLayoutBinding {
+            binding: 3,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        // Environment irradiance, prefiltered specular and BRDF LUT (see
+        // ibl.rs).
+        vk::DescriptorSetLayoutBinding {
+            binding: 4,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        vk::DescriptorSetLayoutBinding {
+            binding: 5,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        vk::DescriptorSetLayoutBinding {
+            binding: 6,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+    ]
 }
 
 pub(crate) fn create_camera_desc_set_layout(
     device: &ash::Device,
 ) -> Result<vk::DescriptorSetLayout> {
-    let binding = vk::DescriptorSetLayoutBinding {
-        binding: 0,
-        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-        descriptor_count: 1,
-        stage_flags: vk::ShaderStageFlags::VERTEX,
+    let bindings = camera_set_bindings();
+    // PARTIALLY_BOUND on the clusters and the IBL maps: only some sets
+    // point at them (and only with an environment set), and tri.frag
+    // reads them only through a camera block that says so.
+    let binding_flags = [
+        vk::DescriptorBindingFlags::empty(),
+        vk::DescriptorBindingFlags::empty(),
+        vk::DescriptorBindingFlags::empty(),
+        vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+        vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+        vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+        vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+    ];
+    let mut binding_flags_ci = vk::DescriptorSetLayoutBindingFlagsCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
+        binding_count: binding_flags.len() as u32,
+        p_binding_flags: binding_flags.as_ptr(),
         ..Default::default()
     };
     let ci = vk::DescriptorSetLayoutCreateInfo {
         s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
-        binding_count: 1,
-        p_bindings: &binding,
+        p_next: (&mut binding_flags_ci) as *mut _ as *mut std::ffi::c_void,
+        binding_count: bindings.len() as u32,
+        p_bindings: bindings.as_ptr(),
         ..Default::default()
     };
     Ok(unsafe { device.create_descriptor_set_layout(&ci, None)? })
 }
 
-/// Bindless texture array: set = 1, binding = 0 (convention; set index is
-/// decided by pipeline layout order). A single global set of MAX_TEXTURES
-/// combined image samplers, indexed in the shader via a push constant
-/// rather than rebinding a different descriptor set per draw.
+/// The material set's bindings (set 1); see create_material_desc_set_layout.
+pub(crate) fn material_set_bindings(
+    caps: TextureArrayCaps,
+) -> [vk::DescriptorSetLayoutBinding<'static>; 3] {
+    [
+        vk::DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: caps.capacity,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        vk::DescriptorSetLayoutBinding {
+            binding: 1,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        vk::DescriptorSetLayoutBinding {
+            binding: 2,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+    ]
+}
+
+/// Material set: set = 1 (convention; set index is decided by pipeline
+/// layout order). Binding 0 is the bindless texture array of
+/// `caps.capacity` combined image samplers, indexed in the shader via the
+/// draw's tex_index rather than rebinding a different descriptor set per
+/// texture; binding 1 the material's parameter block (see material.rs);
+/// binding 2 the block texture array (see block_textures.rs), whose
+/// layers the same tex_index picks for materials that sample it.
 pub(crate) fn create_material_desc_set_layout(
     device: &ash::Device,
+    caps: TextureArrayCaps,
 ) -> Result<vk::DescriptorSetLayout> {
-    let binding = vk::DescriptorSetLayoutBinding {
-        binding: 0,
-        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-        descriptor_count: MAX_TEXTURES,
-        stage_flags: vk::ShaderStageFlags::FRAGMENT,
-        ..Default::default()
-    };
+    let bindings = material_set_bindings(caps);
     // PARTIALLY_BOUND: slots we never write (i.e. almost all of them,
     // until more textures are loaded) don't need to hold valid descriptors
     // as long as the shader never indexes them.
-    let binding_flags = vk::DescriptorBindingFlags::PARTIALLY_BOUND;
+    let mut array_flags = vk::DescriptorBindingFlags::PARTIALLY_BOUND;
+    let mut layout_flags = vk::DescriptorSetLayoutCreateFlags::empty();
+    if caps.update_after_bind {
+        array_flags |= vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING;
+        layout_flags |= vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL;
+    }
+    // The block array is unwritten until upload_block_textures, and read
+    // only by materials that say so; written like the bindless array.
+    let binding_flags = [
+        array_flags,
+        vk::DescriptorBindingFlags::empty(),
+        array_flags,
+    ];
     let mut binding_flags_ci = vk::DescriptorSetLayoutBindingFlagsCreateInfo {
         s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
-        binding_count: 1,
-        p_binding_flags: &binding_flags,
+        binding_count: binding_flags.len() as u32,
+        p_binding_flags: binding_flags.as_ptr(),
         ..Default::default()
     };
     let ci = vk::DescriptorSetLayoutCreateInfo {
         s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
         p_next: (&mut binding_flags_ci) as *mut _ as *mut std::ffi::c_void,
-        binding_count: 1,
-        p_bindings: &binding,
+        flags: layout_flags,
+        binding_count: bindings.len() as u32,
+        p_bindings: bindings.as_ptr(),
         ..Default::default()
     };
     Ok(unsafe { device.create_descriptor_set_layout(&ci, None)? })
@@ -461,8 +1074,14 @@ fn create_image_and_memory(
     info: &ImageAllocInfo,
     name: &str,
 ) -> Result<(vk::Image, Allocation)> {
+    let concurrent = info.queue_families.len() > 1;
     let ci = vk::ImageCreateInfo {
         s_type: vk::StructureType::IMAGE_CREATE_INFO,
+        flags: if info.cube {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        },
         image_type: vk::ImageType::TYPE_2D,
         format: info.format,
         extent: vk::Extent3D {
@@ -471,11 +1090,21 @@ fn create_image_and_memory(
             depth: 1,
         },
         mip_levels: info.mip_levels,
-        array_layers: 1,
+        array_layers: info.layers,
         samples: vk::SampleCountFlags::TYPE_1,
         tiling: info.tiling,
         usage: info.usage,
-        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        sharing_mode: if concurrent {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        },
+        queue_family_index_count: if concurrent {
+            info.queue_families.len() as u32
+        } else {
+            0
+        },
+        p_queue_family_indices: info.queue_families.as_ptr(),
         ..Default::default()
     };
     let image = unsafe { device.create_image(&ci, None) }.with_context(|| {
@@ -494,6 +1123,7 @@ fn create_image_and_memory(
             linear: false,
             allocation_scheme: AllocationScheme::DedicatedImage(image),
         })
+        .map_err(alloc_error)
         .with_context(|| format!("allocate (image) name={name} size={}", req.size))?;
     unsafe { device.bind_image_memory(image, allocation.memory(), allocation.offset()) }?;
     Ok((image, allocation))
@@ -526,7 +1156,11 @@ fn make_image_view_2d_color(
 }
 
 // sync2 layout transition (generic helper)
-fn transition_image_layout2(device: &ash::Device, cmd: vk::CommandBuffer, t: &LayoutTransition) {
+pub(crate) fn transition_image_layout2(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    t: &LayoutTransition,
+) {
     let b = vk::ImageMemoryBarrier2 {
         s_type: vk::StructureType::IMAGE_MEMORY_BARRIER_2,
         src_stage_mask: t.src_stage,
@@ -750,21 +1384,104 @@ fn generate_mip_chain(
     transition_mip_dst_to_shader_read(device, cmd, image, mip_levels - 1);
 }
 
-fn copy_buffer_to_image(
+/// CPU mip chain for MipGen::Cpu: 2×2 box filter per level, averaged in
+/// linear light (the data is sRGB-encoded, and averaging encoded values
+/// darkens every downsample). Odd edges clamp, matching what a LINEAR blit
+/// does. Returns every level packed back to back (level 0 = `pixels`
+/// verbatim) plus each level's (byte offset, extent) for the copy regions —
+/// offsets stay 4-byte aligned since texels are 4 bytes.
+fn downsample_mips_srgb(
+    pixels: &[u8],
+    extent: vk::Extent2D,
+    mip_levels: u32,
+) -> (Vec<u8>, Vec<(vk::DeviceSize, vk::Extent2D)>) {
+    let to_linear: Vec<f32> = (0..256)
+        .map(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+        .collect();
+    let to_srgb = |l: f32| -> u8 {
+        let c = if l <= 0.0031308 {
+            l * 12.92
+        } else {
+            1.055 * l.powf(1.0 / 2.4) - 0.055
+        };
+        (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+    };
+
+    let mut data = pixels.to_vec();
+    let mut levels = vec![(0, extent)];
+    let (mut w, mut h) = (extent.width as usize, extent.height as usize);
+    let mut src_off = 0usize;
+    for _ in 1..mip_levels {
+        let (nw, nh) = ((w / 2).max(1), (h / 2).max(1));
+        let dst_off = data.len();
+        for y in 0..nh {
+            for x in 0..nw {
+                let taps = [
+                    (2 * x, 2 * y),
+                    ((2 * x + 1).min(w - 1), 2 * y),
+                    (2 * x, (2 * y + 1).min(h - 1)),
+                    ((2 * x + 1).min(w - 1), (2 * y + 1).min(h - 1)),
+                ];
+                for c in 0..4 {
+                    let sum: f32 = taps
+                        .iter()
+                        .map(|&(tx, ty)| {
+                            let v = data[src_off + (ty * w + tx) * 4 + c];
+                            // Alpha is linear already.
+                            if c == 3 {
+                                v as f32 / 255.0
+                            } else {
+                                to_linear[v as usize]
+                            }
+                        })
+                        .sum();
+                    let avg = sum / 4.0;
+                    data.push(if c == 3 {
+                        (avg * 255.0 + 0.5) as u8
+                    } else {
+                        to_srgb(avg)
+                    });
+                }
+            }
+        }
+        levels.push((
+            dst_off as vk::DeviceSize,
+            vk::Extent2D {
+                width: nw as u32,
+                height: nh as u32,
+            },
+        ));
+        src_off = dst_off;
+        w = nw;
+        h = nh;
+    }
+    (data, levels)
+}
+
+fn copy_buffer_to_image(
     device: &ash::Device,
     cmd: vk::CommandBuffer,
     buffer: vk::Buffer,
     image: vk::Image,
     extent: vk::Extent2D,
+    mip: u32,
+    buffer_offset: vk::DeviceSize,
 ) {
     let sub = vk::ImageSubresourceLayers {
         aspect_mask: vk::ImageAspectFlags::COLOR,
-        mip_level: 0,
+        mip_level: mip,
         base_array_layer: 0,
         layer_count: 1,
     };
     let region = vk::BufferImageCopy {
-        buffer_offset: 0,
+        buffer_offset,
         buffer_row_length: 0,   // tightly packed
         buffer_image_height: 0, // tightly packed
         image_subresource: sub,
@@ -786,65 +1503,6 @@ fn copy_buffer_to_image(
     };
 }
 
-pub(crate) fn create_sampler(
-    device: &ash::Device,
-    mip_levels: u32,
-    mag_filter: vk::Filter,
-    min_filter: vk::Filter,
-    mipmap_mode: vk::SamplerMipmapMode,
-    anisotropy: f32,
-    lod_bias: f32,
-) -> Result<vk::Sampler> {
-    let ci = vk::SamplerCreateInfo {
-        s_type: vk::StructureType::SAMPLER_CREATE_INFO,
-        mag_filter,
-        min_filter,
-        mipmap_mode,
-        address_mode_u: vk::SamplerAddressMode::REPEAT,
-        address_mode_v: vk::SamplerAddressMode::REPEAT,
-        address_mode_w: vk::SamplerAddressMode::REPEAT,
-        anisotropy_enable: if anisotropy > 0.0 {
-            vk::TRUE
-        } else {
-            vk::FALSE
-        },
-        max_anisotropy: anisotropy,
-        min_lod: 0.0,
-        max_lod: mip_levels as f32,
-        mip_lod_bias: lod_bias,
-        ..Default::default()
-    };
-    Ok(unsafe { device.create_sampler(&ci, None)? })
-}
-
-pub(crate) fn create_material_desc_pool_and_set(
-    device: &ash::Device,
-    set_layout: vk::DescriptorSetLayout,
-) -> Result<(vk::DescriptorPool, vk::DescriptorSet)> {
-    let pool_sizes = [vk::DescriptorPoolSize {
-        ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-        descriptor_count: MAX_TEXTURES,
-    }];
-    let pool_ci = vk::DescriptorPoolCreateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
-        max_sets: 1,
-        pool_size_count: pool_sizes.len() as u32,
-        p_pool_sizes: pool_sizes.as_ptr(),
-        ..Default::default()
-    };
-    let pool = unsafe { device.create_descriptor_pool(&pool_ci, None)? };
-
-    let alloc = vk::DescriptorSetAllocateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
-        descriptor_pool: pool,
-        descriptor_set_count: 1,
-        p_set_layouts: &set_layout,
-        ..Default::default()
-    };
-    let set = unsafe { device.allocate_descriptor_sets(&alloc)?[0] };
-    Ok((pool, set))
-}
-
 /// Register a texture into the bindless array at `index` (see
 /// `PushData::tex_index`).
 pub(crate) fn write_material_descriptors(
@@ -872,22 +1530,46 @@ pub(crate) fn write_material_descriptors(
     unsafe { device.update_descriptor_sets(std::slice::from_ref(&write), &[]) };
 }
 
+/// Point set 1's block texture array (binding 2) at `view`.
+pub(crate) fn write_block_array_descriptor(
+    device: &ash::Device,
+    set: vk::DescriptorSet,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+) {
+    let image_info = vk::DescriptorImageInfo {
+        sampler,
+        image_view: view,
+        image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+    let write = vk::WriteDescriptorSet {
+        s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+        dst_set: set,
+        dst_binding: 2,
+        descriptor_count: 1,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        p_image_info: &image_info,
+        ..Default::default()
+    };
+    unsafe { device.update_descriptor_sets(std::slice::from_ref(&write), &[]) };
+}
+
 /// 2x2 checkerboard RGBA, registered at bindless index 0 as the fallback
-/// texture. Delegates to `create_texture_and_sampler` so it goes through the
+/// texture. Delegates to `create_texture` so it goes through the
 /// exact same mip-chain generation as every other texture (a 2x2 source
 /// image gets mip_levels = 2: the 2x2 base plus a 1x1 level) rather than a
 /// second, independently-maintained copy of that logic.
-pub(crate) fn create_dummy_texture_and_sampler(
+pub(crate) fn create_dummy_texture(
     device: &ash::Device,
     allocator: &mut Allocator,
     queue: vk::Queue,
     cmd_pool: vk::CommandPool,
-    sampler_config: &SamplerConfig,
-) -> Result<(vk::Image, Allocation, vk::ImageView, vk::Sampler)> {
+    mip_gen: MipGen,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
     let pixels: [u8; 16] = [
         255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255,
     ];
-    create_texture_and_sampler(
+    create_texture(
         device,
         allocator,
         queue,
@@ -897,26 +1579,26 @@ pub(crate) fn create_dummy_texture_and_sampler(
             width: 2,
             height: 2,
         },
-        sampler_config,
+        mip_gen,
     )
 }
 
-/// Same staging/transition/copy/view/sampler pattern as
-/// `create_dummy_texture_and_sampler`, parameterized over caller-supplied
-/// RGBA8 pixel data instead of the hardcoded 2x2 checkerboard. Registering
-/// the result into the bindless descriptor array (`write_material_descriptors`)
-/// is the caller's job since that needs the live `material_desc_set` and the
-/// next free index, both of which live on `VkRenderer`.
-pub(crate) fn create_texture_and_sampler(
+/// Staging/transition/copy/view for caller-supplied RGBA8 pixel data (the
+/// dummy texture is just a 2x2 call of this). Picking a sampler (from the
+/// SamplerCache) and registering the result into the bindless descriptor
+/// array (`MaterialPool::write_texture`) is the caller's job since those
+/// need the live material sets and the next free index, both of which live
+/// on `VkRenderer`.
+pub(crate) fn create_texture(
     device: &ash::Device,
     allocator: &mut Allocator,
     queue: vk::Queue,
     cmd_pool: vk::CommandPool,
     pixels: &[u8],
     extent: vk::Extent2D,
-    sampler_config: &SamplerConfig,
-) -> Result<(vk::Image, Allocation, vk::ImageView, vk::Sampler)> {
-    let mip_levels = (extent.width.max(extent.height) as f32).log2().floor() as u32 + 1;
+    mip_gen: MipGen,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    let mip_levels = mip_count(extent);
 
     // Create device-local image. TRANSFER_SRC is needed in addition to
     // TRANSFER_DST because the mip chain is generated by blitting each level
@@ -924,16 +1606,28 @@ pub(crate) fn create_texture_and_sampler(
     let info = ImageAllocInfo {
         extent,
         mip_levels,
-        format: vk::Format::R8G8B8A8_SRGB,
+        format: TEXTURE_FORMAT,
         usage: vk::ImageUsageFlags::TRANSFER_DST
             | vk::ImageUsageFlags::TRANSFER_SRC
             | vk::ImageUsageFlags::SAMPLED,
         tiling: vk::ImageTiling::OPTIMAL,
+        cube: false,
+        layers: 1,
+        queue_families: &[],
     };
     let (image, memory) = create_image_and_memory(device, allocator, &info, "uploaded texture")?;
 
-    // Create staging buffer and copy pixels into it
-    let size = pixels.len() as vk::DeviceSize;
+    // Create staging buffer and copy pixels into it — level 0 only for the
+    // blit path, every level back to back for the CPU path.
+    let cpu_levels = match mip_gen {
+        MipGen::Blit => None,
+        MipGen::Cpu => Some(downsample_mips_srgb(pixels, extent, mip_levels)),
+    };
+    let upload: &[u8] = match &cpu_levels {
+        Some((data, _)) => data,
+        None => pixels,
+    };
+    let size = upload.len() as vk::DeviceSize;
     let (staging, mut staging_alloc) = create_buffer_and_memory(
         device,
         allocator,
@@ -946,90 +1640,396 @@ pub(crate) fn create_texture_and_sampler(
         let mapped = staging_alloc
             .mapped_slice_mut()
             .ok_or_else(|| anyhow!("texture upload staging allocation not host-mapped"))?;
-        mapped[..pixels.len()].copy_from_slice(pixels);
+        mapped[..upload.len()].copy_from_slice(upload);
     }
 
     // One-time command buffer to do the transitions + copy
-    let ai = vk::CommandBufferAllocateInfo {
-        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
-        command_pool: cmd_pool,
-        level: vk::CommandBufferLevel::PRIMARY,
-        command_buffer_count: 1,
-        ..Default::default()
-    };
-    let cmd = unsafe { device.allocate_command_buffers(&ai)?[0] };
-    let bi = vk::CommandBufferBeginInfo {
-        s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
-        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-        ..Default::default()
-    };
-    unsafe { device.begin_command_buffer(cmd, &bi)? };
+    submit_one_time(device, queue, cmd_pool, |cmd| {
+        transition_color_to_transfer_dst(device, cmd, image, mip_levels);
+        match &cpu_levels {
+            None => {
+                copy_buffer_to_image(device, cmd, staging, image, extent, 0, 0);
+                generate_mip_chain(device, cmd, image, extent.width, extent.height, mip_levels);
+            }
+            Some((_, levels)) => {
+                for (mip, &(offset, level_extent)) in levels.iter().enumerate() {
+                    copy_buffer_to_image(
+                        device,
+                        cmd,
+                        staging,
+                        image,
+                        level_extent,
+                        mip as u32,
+                        offset,
+                    );
+                    transition_mip_dst_to_shader_read(device, cmd, image, mip as u32);
+                }
+            }
+        }
+    })?;
+    unsafe { device.destroy_buffer(staging, None) };
+    allocator.free(staging_alloc)?;
 
-    transition_color_to_transfer_dst(device, cmd, image, mip_levels);
-    copy_buffer_to_image(device, cmd, staging, image, extent);
-    generate_mip_chain(device, cmd, image, extent.width, extent.height, mip_levels);
+    let view = make_image_view_2d_color(device, image, TEXTURE_FORMAT, 0, mip_levels)?;
 
-    unsafe { device.end_command_buffer(cmd)? };
-    let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
-    let si = vk::SubmitInfo {
-        s_type: vk::StructureType::SUBMIT_INFO,
-        command_buffer_count: 1,
-        p_command_buffers: &cmd,
-        ..Default::default()
+    Ok((image, memory, view))
+}
+
+/// Upload a cubemap: `faces` holds the +X, -X, +Y, -Y, +Z, -Z faces back to
+/// back, `size` x `size` RGBA8 sRGB texels each. One mip level: a skybox
+/// is sampled at roughly a texel per pixel, never minified far.
+pub(crate) fn create_cubemap(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    queue: vk::Queue,
+    cmd_pool: vk::CommandPool,
+    faces: &[u8],
+    size: u32,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    let info = ImageAllocInfo {
+        extent: vk::Extent2D {
+            width: size,
+            height: size,
+        },
+        mip_levels: 1,
+        format: TEXTURE_FORMAT,
+        usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        tiling: vk::ImageTiling::OPTIMAL,
+        cube: true,
+        layers: 6,
+        queue_families: &[],
     };
-    unsafe {
-        device.queue_submit(queue, std::slice::from_ref(&si), fence)?;
-        device.wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)?;
-        device.destroy_fence(fence, None);
-        device.free_command_buffers(cmd_pool, std::slice::from_ref(&cmd));
-        device.destroy_buffer(staging, None);
-    }
-    allocator.free(staging_alloc)?;
+    let (image, memory) = create_image_and_memory(device, allocator, &info, "uploaded cubemap")?;
 
-    let view = make_image_view_2d_color(device, image, vk::Format::R8G8B8A8_SRGB, 0, mip_levels)?;
-    let sampler = create_sampler(
+    let (staging, mut staging_alloc) = create_buffer_and_memory(
         device,
-        mip_levels,
-        sampler_config.mag_filter,
-        sampler_config.min_filter,
-        sampler_config.mipmap_mode,
-        sampler_config.max_anisotropy,
-        sampler_config.lod_bias,
+        allocator,
+        faces.len() as vk::DeviceSize,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        MemoryLocation::CpuToGpu,
+        "texture upload staging",
     )?;
+    {
+        let mapped = staging_alloc
+            .mapped_slice_mut()
+            .ok_or_else(|| anyhow!("cubemap upload staging allocation not host-mapped"))?;
+        mapped[..faces.len()].copy_from_slice(faces);
+    }
+
+    let sub = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 6,
+    };
+    submit_one_time(device, queue, cmd_pool, |cmd| {
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::TOP_OF_PIPE,
+                src_access: vk::AccessFlags2::empty(),
+                old_layout: vk::ImageLayout::UNDEFINED,
+                dst_stage: vk::PipelineStageFlags2::TRANSFER,
+                dst_access: vk::AccessFlags2::TRANSFER_WRITE,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            },
+        );
+        // Tightly packed layers follow each other in the buffer, so one
+        // region covers all six faces.
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 6,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+        };
+        unsafe {
+            device.cmd_copy_buffer_to_image(
+                cmd,
+                staging,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+            )
+        };
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::TRANSFER,
+                src_access: vk::AccessFlags2::TRANSFER_WRITE,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access: vk::AccessFlags2::SHADER_READ,
+                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            },
+        );
+    })?;
+    unsafe { device.destroy_buffer(staging, None) };
+    allocator.free(staging_alloc)?;
 
-    Ok((image, memory, view, sampler))
+    let ci = vk::ImageViewCreateInfo {
+        s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+        image,
+        view_type: vk::ImageViewType::CUBE,
+        format: TEXTURE_FORMAT,
+        components: vk::ComponentMapping::default(),
+        subresource_range: sub,
+        ..Default::default()
+    };
+    let view = unsafe { device.create_image_view(&ci, None)? };
+
+    Ok((image, memory, view))
 }
 
-/// One-shot staging upload: host->staging, then staging->dst (device-local)
-/// at `dst_offset`. Uses the graphics queue and a one-time command buffer;
-/// waits until done.
-pub(crate) fn upload_via_staging(
+/// Upload a 2D texture array: `layers` holds `count` layers back to back,
+/// `size` x `size` RGBA8 sRGB texels each. Full mip chain per layer, built
+/// on the CPU (downsample_mips_srgb) so no layer ever blits from another.
+pub(crate) fn create_texture_array(
     device: &ash::Device,
     allocator: &mut Allocator,
     queue: vk::Queue,
     cmd_pool: vk::CommandPool,
-    dst: vk::Buffer,
-    dst_offset: vk::DeviceSize,
-    src_data: &[u8],
-) -> Result<()> {
-    // 1) Create CPU-to-GPU staging buffer
-    let size = src_data.len() as vk::DeviceSize;
+    layers: &[u8],
+    size: u32,
+    count: u32,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    let extent = vk::Extent2D {
+        width: size,
+        height: size,
+    };
+    let mip_levels = mip_count(extent);
+    let info = ImageAllocInfo {
+        extent,
+        mip_levels,
+        format: TEXTURE_FORMAT,
+        usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        tiling: vk::ImageTiling::OPTIMAL,
+        cube: false,
+        layers: count,
+        queue_families: &[],
+    };
+    let (image, memory) = create_image_and_memory(device, allocator, &info, "texture array")?;
+
+    // Every layer's levels back to back, one copy region per level.
+    let layer_len = size as usize * size as usize * 4;
+    let mut data = Vec::new();
+    let mut regions = Vec::new();
+    for (layer, pixels) in layers.chunks_exact(layer_len).enumerate() {
+        let (levels_data, levels) = downsample_mips_srgb(pixels, extent, mip_levels);
+        let base = data.len() as vk::DeviceSize;
+        for (mip, &(offset, level_extent)) in levels.iter().enumerate() {
+            regions.push(vk::BufferImageCopy {
+                buffer_offset: base + offset,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: mip as u32,
+                    base_array_layer: layer as u32,
+                    layer_count: 1,
+                },
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: vk::Extent3D {
+                    width: level_extent.width,
+                    height: level_extent.height,
+                    depth: 1,
+                },
+            });
+        }
+        data.extend_from_slice(&levels_data);
+    }
+
     let (staging, mut staging_alloc) = create_buffer_and_memory(
         device,
         allocator,
-        size,
+        data.len() as vk::DeviceSize,
         vk::BufferUsageFlags::TRANSFER_SRC,
         MemoryLocation::CpuToGpu,
-        "upload staging",
+        "texture upload staging",
     )?;
+    {
+        let mapped = staging_alloc
+            .mapped_slice_mut()
+            .ok_or_else(|| anyhow!("texture array staging allocation not host-mapped"))?;
+        mapped[..data.len()].copy_from_slice(&data);
+    }
+
+    let sub = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: mip_levels,
+        base_array_layer: 0,
+        layer_count: count,
+    };
+    submit_one_time(device, queue, cmd_pool, |cmd| {
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::TOP_OF_PIPE,
+                src_access: vk::AccessFlags2::empty(),
+                old_layout: vk::ImageLayout::UNDEFINED,
+                dst_stage: vk::PipelineStageFlags2::TRANSFER,
+                dst_access: vk::AccessFlags2::TRANSFER_WRITE,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            },
+        );
+        unsafe {
+            device.cmd_copy_buffer_to_image(
+                cmd,
+                staging,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            )
+        };
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::TRANSFER,
+                src_access: vk::AccessFlags2::TRANSFER_WRITE,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access: vk::AccessFlags2::SHADER_READ,
+                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            },
+        );
+    })?;
+    unsafe { device.destroy_buffer(staging, None) };
+    allocator.free(staging_alloc)?;
+
+    let ci = vk::ImageViewCreateInfo {
+        s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+        image,
+        view_type: vk::ImageViewType::TYPE_2D_ARRAY,
+        format: TEXTURE_FORMAT,
+        components: vk::ComponentMapping::default(),
+        subresource_range: sub,
+        ..Default::default()
+    };
+    let view = unsafe { device.create_image_view(&ci, None)? };
+
+    Ok((image, memory, view))
+}
+
+/// Record `record` into a fresh primary command buffer, submit it and
+/// wait for it to finish. For uploads outside the frame loop.
+/// Full mip chain length for a texture of `extent`.
+fn mip_count(extent: vk::Extent2D) -> u32 {
+    (extent.width.max(extent.height) as f32).log2().floor() as u32 + 1
+}
+
+/// create_texture for the transfer queue (upload_texture_async): the mip
+/// chain is built on the CPU, since a transfer-only queue can't blit, and
+/// the image is CONCURRENT across `queue_families` so the graphics queue
+/// can sample what the transfer queue wrote without an ownership transfer.
+/// Returns the image and view plus the packed levels for
+/// record_texture_upload.
+pub(crate) fn create_texture_for_transfer(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    pixels: &[u8],
+    extent: vk::Extent2D,
+    queue_families: &[u32],
+) -> Result<(vk::Image, Allocation, vk::ImageView, TextureLevels)> {
+    let mip_levels = mip_count(extent);
+    let info = ImageAllocInfo {
+        extent,
+        mip_levels,
+        format: TEXTURE_FORMAT,
+        usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        tiling: vk::ImageTiling::OPTIMAL,
+        cube: false,
+        layers: 1,
+        queue_families,
+    };
+    let (image, memory) = create_image_and_memory(device, allocator, &info, "uploaded texture")?;
+    match make_image_view_2d_color(device, image, TEXTURE_FORMAT, 0, mip_levels) {
+        Ok(view) => {
+            let (data, levels) = downsample_mips_srgb(pixels, extent, mip_levels);
+            Ok((image, memory, view, TextureLevels { data, levels }))
+        }
+        Err(e) => {
+            unsafe { device.destroy_image(image, None) };
+            let _ = allocator.free(memory);
+            Err(e)
+        }
+    }
+}
 
-    // Map + copy into staging
-    let mapped = staging_alloc
-        .mapped_slice_mut()
-        .ok_or_else(|| anyhow!("upload staging allocation not host-mapped"))?;
-    mapped[..src_data.len()].copy_from_slice(src_data);
+/// A texture's whole mip chain back to back, with each level's offset
+/// into `data` and extent.
+pub(crate) struct TextureLevels {
+    pub(crate) data: Vec<u8>,
+    levels: Vec<(vk::DeviceSize, vk::Extent2D)>,
+}
+
+/// Copy `levels` (staged in `staging`) into every mip of `image`, leaving
+/// it SHADER_READ_ONLY. Only transfer-stage work, so it records on any
+/// queue; the reader has to wait on the submit's semaphore, which is what
+/// makes the writes visible (the final barrier has no destination scope).
+pub(crate) fn record_texture_upload(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    staging: vk::Buffer,
+    image: vk::Image,
+    levels: &TextureLevels,
+) {
+    let mip_levels = levels.levels.len() as u32;
+    transition_color_to_transfer_dst(device, cmd, image, mip_levels);
+    for (mip, &(offset, extent)) in levels.levels.iter().enumerate() {
+        copy_buffer_to_image(device, cmd, staging, image, extent, mip as u32, offset);
+    }
+    transition_image_layout2(
+        device,
+        cmd,
+        &LayoutTransition {
+            image,
+            sub: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: mip_levels,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            src_stage: vk::PipelineStageFlags2::TRANSFER,
+            src_access: vk::AccessFlags2::TRANSFER_WRITE,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            dst_stage: vk::PipelineStageFlags2::NONE,
+            dst_access: vk::AccessFlags2::NONE,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+    );
+}
 
-    // 2) One-time copy staging -> dst
+pub(crate) fn submit_one_time(
+    device: &ash::Device,
+    queue: vk::Queue,
+    cmd_pool: vk::CommandPool,
+    record: impl FnOnce(vk::CommandBuffer),
+) -> Result<()> {
     let ai = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         command_pool: cmd_pool,
@@ -1044,109 +2044,181 @@ pub(crate) fn upload_via_staging(
         ..Default::default()
     };
     unsafe { device.begin_command_buffer(cmd, &bi)? };
-    let region = vk::BufferCopy {
-        src_offset: 0,
-        dst_offset,
-        size,
-    };
-    unsafe { device.cmd_copy_buffer(cmd, staging, dst, std::slice::from_ref(&region)) };
+    record(cmd);
     unsafe { device.end_command_buffer(cmd)? };
 
-    // 3) Submit + wait
+    let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
     let si = vk::SubmitInfo {
         s_type: vk::StructureType::SUBMIT_INFO,
         command_buffer_count: 1,
         p_command_buffers: &cmd,
         ..Default::default()
     };
-    let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
-    unsafe { device.queue_submit(queue, std::slice::from_ref(&si), fence)? };
-    unsafe { device.wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)? };
-    unsafe { device.destroy_fence(fence, None) };
-
-    // 4) Cleanup
-    unsafe { device.free_command_buffers(cmd_pool, std::slice::from_ref(&cmd)) };
-    unsafe { device.destroy_buffer(staging, None) };
-    allocator.free(staging_alloc)?;
+    unsafe {
+        device.queue_submit(queue, std::slice::from_ref(&si), fence)?;
+        device.wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)?;
+        device.destroy_fence(fence, None);
+        device.free_command_buffers(cmd_pool, std::slice::from_ref(&cmd));
+    }
     Ok(())
 }
 
-pub(crate) fn create_frame_uniforms_and_sets(
-    instance: &ash::Instance,
+/// Clear a new render target (TRANSFER_DST usage) and leave it in
+/// SHADER_READ_ONLY_OPTIMAL, so it samples as `clear` until something is
+/// drawn into it. `aspect` picks a colour or a depth clear.
+pub(crate) fn clear_to_shader_read(
     device: &ash::Device,
-    phys: vk::PhysicalDevice,
-    allocator: &mut Allocator,
-    set_layout: vk::DescriptorSetLayout,
-    image_count: usize,
-) -> Result<FrameUniforms> {
-    let limits = unsafe { instance.get_physical_device_properties(phys).limits };
-    let a = limits.min_uniform_buffer_offset_alignment.max(1);
-    let sz = std::mem::size_of::<CameraUbo>() as u64;
-    let ubo_size = sz.div_ceil(a) * a;
-
-    let mut ubufs = Vec::with_capacity(image_count);
-    let mut uallocs = Vec::with_capacity(image_count);
-    let mut ubo_ptrs = Vec::with_capacity(image_count);
-
-    for _ in 0..image_count {
-        let (b, alloc) = create_host_visible_ubo(device, allocator, ubo_size)?;
-        let ptr = alloc
-            .mapped_ptr()
-            .ok_or_else(|| anyhow!("UBO allocation not host-mapped"))?
-            .as_ptr();
-        ubufs.push(b);
-        uallocs.push(alloc);
-        ubo_ptrs.push(ptr);
-    }
-
-    let pool_sizes = [vk::DescriptorPoolSize {
-        ty: vk::DescriptorType::UNIFORM_BUFFER,
-        descriptor_count: image_count as u32,
-    }];
-    let pool_ci = vk::DescriptorPoolCreateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
-        max_sets: image_count as u32,
-        pool_size_count: 1,
-        p_pool_sizes: pool_sizes.as_ptr(),
-        ..Default::default()
+    queue: vk::Queue,
+    cmd_pool: vk::CommandPool,
+    image: vk::Image,
+    aspect: vk::ImageAspectFlags,
+    clear: vk::ClearValue,
+) -> Result<()> {
+    let sub = vk::ImageSubresourceRange {
+        aspect_mask: aspect,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
     };
-    let pool = unsafe { device.create_descriptor_pool(&pool_ci, None)? };
-
-    let layouts = vec![set_layout; image_count];
-    let alloc = vk::DescriptorSetAllocateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
-        descriptor_pool: pool,
-        descriptor_set_count: image_count as u32,
-        p_set_layouts: layouts.as_ptr(),
-        ..Default::default()
+    submit_one_time(device, queue, cmd_pool, |cmd| {
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::TOP_OF_PIPE,
+                src_access: vk::AccessFlags2::empty(),
+                old_layout: vk::ImageLayout::UNDEFINED,
+                dst_stage: vk::PipelineStageFlags2::CLEAR,
+                dst_access: vk::AccessFlags2::TRANSFER_WRITE,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            },
+        );
+        unsafe {
+            if aspect.contains(vk::ImageAspectFlags::COLOR) {
+                device.cmd_clear_color_image(
+                    cmd,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &clear.color,
+                    std::slice::from_ref(&sub),
+                );
+            } else {
+                device.cmd_clear_depth_stencil_image(
+                    cmd,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &clear.depth_stencil,
+                    std::slice::from_ref(&sub),
+                );
+            }
+        }
+        transition_image_layout2(
+            device,
+            cmd,
+            &LayoutTransition {
+                image,
+                sub,
+                src_stage: vk::PipelineStageFlags2::CLEAR,
+                src_access: vk::AccessFlags2::TRANSFER_WRITE,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_access: vk::AccessFlags2::SHADER_SAMPLED_READ,
+                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            },
+        );
+    })
+}
+
+/// The camera sets: `main` for the scene passes, and `shadow` for the
+/// shadow pass, whose binding 0 is one cascade's view_proj where tri.vert
+/// expects the CameraUbo. Bindings 0 and 2 are dynamic views of the staging
+/// belt's buffer (`belt`), pointed at each frame's blocks by dynamic offset
+/// (see FrameUniformOffsets), so they're written once for the renderer's
+/// life; so is the main set's binding 3, the light clusters (`clusters`,
+/// if there are any). The shadow set leaves bindings 1 (the shadow map)
+/// and 3 unwritten: its pipeline has no fragment stage.
+pub(crate) fn create_camera_sets(
+    device: &ash::Device,
+    descriptors: &mut DescriptorAllocator,
+    set_layout: vk::DescriptorSetLayout,
+    belt: vk::Buffer,
+    clusters: Option<vk::DescriptorBufferInfo>,
+) -> Result<(PooledSet, vk::DescriptorSet)> {
+    let main = create_scene_camera_set(device, descriptors, set_layout, belt, clusters)?;
+    let shadow = descriptors.allocate(device, set_layout)?.set;
+
+    let info = |range| vk::DescriptorBufferInfo {
+        buffer: belt,
+        offset: 0,
+        range,
     };
-    let sets = unsafe { device.allocate_descriptor_sets(&alloc)? };
+    let cascade_info = info(SHADOW_VIEW_PROJ_SIZE);
+    let lights_info = info(std::mem::size_of::<LightList>() as u64);
+    let writes = [
+        camera_buffer_write(shadow, 0, UBO, &cascade_info),
+        camera_buffer_write(shadow, 2, SSBO, &lights_info),
+    ];
+    unsafe { device.update_descriptor_sets(&writes, &[]) };
 
-    let mut writes = Vec::with_capacity(image_count);
-    let mut infos: Vec<vk::DescriptorBufferInfo> = Vec::with_capacity(image_count);
-    for i in 0..image_count {
-        infos.push(vk::DescriptorBufferInfo {
-            buffer: ubufs[i],
-            offset: 0,
-            range: ubo_size,
-        });
-        writes.push(vk::WriteDescriptorSet {
-            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
-            dst_set: sets[i],
-            dst_binding: 0,
-            descriptor_count: 1,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            p_buffer_info: &infos[i],
-            ..Default::default()
-        });
+    Ok((main, shadow))
+}
+
+/// create_camera_sets' `main` set alone, with its buffer bindings written;
+/// VkRenderer::renew_camera_sets allocates a replacement with it.
+pub(crate) fn create_scene_camera_set(
+    device: &ash::Device,
+    descriptors: &mut DescriptorAllocator,
+    set_layout: vk::DescriptorSetLayout,
+    belt: vk::Buffer,
+    clusters: Option<vk::DescriptorBufferInfo>,
+) -> Result<PooledSet> {
+    let main = descriptors.allocate(device, set_layout)?;
+    let info = |range| vk::DescriptorBufferInfo {
+        buffer: belt,
+        offset: 0,
+        range,
+    };
+    let camera_info = info(std::mem::size_of::<CameraUbo>() as u64);
+    let lights_info = info(std::mem::size_of::<LightList>() as u64);
+    let mut writes = vec![
+        camera_buffer_write(main.set, 0, UBO, &camera_info),
+        camera_buffer_write(main.set, 2, SSBO, &lights_info),
+    ];
+    if let Some(clusters) = clusters.as_ref() {
+        let storage = vk::DescriptorType::STORAGE_BUFFER;
+        writes.push(camera_buffer_write(main.set, 3, storage, clusters));
     }
     unsafe { device.update_descriptor_sets(&writes, &[]) };
+    Ok(main)
+}
+
+// The camera sets' per-frame blocks: dynamic views of the staging belt.
+const UBO: vk::DescriptorType = vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC;
+const SSBO: vk::DescriptorType = vk::DescriptorType::STORAGE_BUFFER_DYNAMIC;
 
-    Ok((ubufs, uallocs, ubo_ptrs, ubo_size, pool, sets))
+fn camera_buffer_write(
+    set: vk::DescriptorSet,
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    info: &vk::DescriptorBufferInfo,
+) -> vk::WriteDescriptorSet<'_> {
+    vk::WriteDescriptorSet {
+        s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+        dst_set: set,
+        dst_binding: binding,
+        descriptor_count: 1,
+        descriptor_type,
+        p_buffer_info: info,
+        ..Default::default()
+    }
 }
 
 /// Descriptor set layout for the indirect-cull compute shader: read-only
-/// candidates, write-only indirect commands, read-write atomic draw count.
+/// candidates, write-only indirect commands, read-write atomic draw count,
+/// read-only per-candidate bounding spheres.
 pub(crate) fn create_indirect_compute_desc_set_layout(
     device: &ash::Device,
 ) -> Result<vk::DescriptorSetLayout> {
@@ -1172,6 +2244,13 @@ pub(crate) fn create_indirect_compute_desc_set_layout(
             stage_flags: vk::ShaderStageFlags::COMPUTE,
             ..Default::default()
         },
+        vk::DescriptorSetLayoutBinding {
+            binding: 3,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
     ];
     let ci = vk::DescriptorSetLayoutCreateInfo {
         s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
@@ -1182,19 +2261,25 @@ pub(crate) fn create_indirect_compute_desc_set_layout(
     Ok(unsafe { device.create_descriptor_set_layout(&ci, None)? })
 }
 
+/// The indirect-draw set's one binding (set 2); see
+/// create_indirect_graphics_desc_set_layout.
+pub(crate) fn indirect_graphics_set_binding() -> vk::DescriptorSetLayoutBinding<'static> {
+    vk::DescriptorSetLayoutBinding {
+        binding: 0,
+        descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+        descriptor_count: 1,
+        stage_flags: vk::ShaderStageFlags::VERTEX,
+        ..Default::default()
+    }
+}
+
 /// Descriptor set layout for the graphics pipeline's read-only view of the
 /// same candidates buffer the compute shader populated, indexed by
 /// gl_InstanceIndex in the vertex shader (set = 2, binding = 0).
 pub(crate) fn create_indirect_graphics_desc_set_layout(
     device: &ash::Device,
 ) -> Result<vk::DescriptorSetLayout> {
-    let binding = vk::DescriptorSetLayoutBinding {
-        binding: 0,
-        descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-        descriptor_count: 1,
-        stage_flags: vk::ShaderStageFlags::VERTEX,
-        ..Default::default()
-    };
+    let binding = indirect_graphics_set_binding();
     let ci = vk::DescriptorSetLayoutCreateInfo {
         s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
         binding_count: 1,
@@ -1208,42 +2293,49 @@ pub(crate) struct IndirectDrawResources {
     pub(crate) candidate_bufs: Vec<vk::Buffer>,
     pub(crate) candidate_allocs: Vec<Allocation>,
     pub(crate) candidate_ptrs: Vec<*mut std::ffi::c_void>,
+    pub(crate) bounds_bufs: Vec<vk::Buffer>,
+    pub(crate) bounds_allocs: Vec<Allocation>,
+    pub(crate) bounds_ptrs: Vec<*mut std::ffi::c_void>,
     pub(crate) indirect_bufs: Vec<vk::Buffer>,
     pub(crate) indirect_allocs: Vec<Allocation>,
     pub(crate) draw_count_bufs: Vec<vk::Buffer>,
     pub(crate) draw_count_allocs: Vec<Allocation>,
-    pub(crate) desc_pool: vk::DescriptorPool,
     pub(crate) compute_desc_sets: Vec<vk::DescriptorSet>,
     pub(crate) graphics_desc_sets: Vec<vk::DescriptorSet>,
 }
 
-/// Per-swapchain-image buffers + descriptor sets for the GPU-driven
-/// indirect draw path. candidate_bufs are host-visible and persistently
-/// mapped (CPU writes this frame's draw candidates directly, like the
-/// camera UBOs); indirect_bufs/draw_count_bufs are GPU-only, written by
+/// Per-frame-slot buffers + descriptor sets for the GPU-driven indirect
+/// draw path (see sync::FRAMES_IN_FLIGHT). candidate_bufs and bounds_bufs are host-visible
+/// and persistently mapped (CPU writes this frame's draw candidates and
+/// their bounding spheres directly, like the staging belt); indirect_bufs/draw_count_bufs are GPU-only, written by
 /// the indirect-cull compute dispatch and consumed by
 /// cmd_draw_indexed_indirect_count later in the same command buffer.
 pub(crate) fn create_indirect_draw_resources(
     device: &ash::Device,
     allocator: &mut Allocator,
+    descriptors: &mut DescriptorAllocator,
     compute_set_layout: vk::DescriptorSetLayout,
     graphics_set_layout: vk::DescriptorSetLayout,
-    image_count: usize,
+    frames: usize,
 ) -> Result<IndirectDrawResources> {
     let candidates_size = MAX_INDIRECT_DRAWS as u64 * std::mem::size_of::<DrawCandidate>() as u64;
     let indirect_size =
         MAX_INDIRECT_DRAWS as u64 * std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u64;
     let count_size = std::mem::size_of::<u32>() as u64;
-
-    let mut candidate_bufs = Vec::with_capacity(image_count);
-    let mut candidate_allocs = Vec::with_capacity(image_count);
-    let mut candidate_ptrs = Vec::with_capacity(image_count);
-    let mut indirect_bufs = Vec::with_capacity(image_count);
-    let mut indirect_allocs = Vec::with_capacity(image_count);
-    let mut draw_count_bufs = Vec::with_capacity(image_count);
-    let mut draw_count_allocs = Vec::with_capacity(image_count);
-
-    for _ in 0..image_count {
+    let bounds_size = MAX_INDIRECT_DRAWS as u64 * std::mem::size_of::<[f32; 4]>() as u64;
+
+    let mut candidate_bufs = Vec::with_capacity(frames);
+    let mut candidate_allocs = Vec::with_capacity(frames);
+    let mut candidate_ptrs = Vec::with_capacity(frames);
+    let mut bounds_bufs = Vec::with_capacity(frames);
+    let mut bounds_allocs = Vec::with_capacity(frames);
+    let mut bounds_ptrs = Vec::with_capacity(frames);
+    let mut indirect_bufs = Vec::with_capacity(frames);
+    let mut indirect_allocs = Vec::with_capacity(frames);
+    let mut draw_count_bufs = Vec::with_capacity(frames);
+    let mut draw_count_allocs = Vec::with_capacity(frames);
+
+    for _ in 0..frames {
         let (cbuf, calloc) = create_buffer_and_memory(
             device,
             allocator,
@@ -1260,6 +2352,22 @@ pub(crate) fn create_indirect_draw_resources(
         candidate_allocs.push(calloc);
         candidate_ptrs.push(cptr);
 
+        let (bbuf, balloc) = create_buffer_and_memory(
+            device,
+            allocator,
+            bounds_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::CpuToGpu,
+            "indirect cull bounds",
+        )?;
+        let bptr = balloc
+            .mapped_ptr()
+            .ok_or_else(|| anyhow!("cull bounds buffer not host-mapped"))?
+            .as_ptr();
+        bounds_bufs.push(bbuf);
+        bounds_allocs.push(balloc);
+        bounds_ptrs.push(bptr);
+
         let (ibuf, ialloc) = create_buffer_and_memory(
             device,
             allocator,
@@ -1285,45 +2393,16 @@ pub(crate) fn create_indirect_draw_resources(
         draw_count_allocs.push(dalloc);
     }
 
-    // One compute set (3 storage buffers) + one graphics set (1 storage
+    // One compute set (4 storage buffers) + one graphics set (1 storage
     // buffer) per image.
-    let pool_sizes = [vk::DescriptorPoolSize {
-        ty: vk::DescriptorType::STORAGE_BUFFER,
-        descriptor_count: (image_count * 4) as u32,
-    }];
-    let pool_ci = vk::DescriptorPoolCreateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
-        max_sets: (image_count * 2) as u32,
-        pool_size_count: 1,
-        p_pool_sizes: pool_sizes.as_ptr(),
-        ..Default::default()
-    };
-    let desc_pool = unsafe { device.create_descriptor_pool(&pool_ci, None)? };
-
-    let compute_layouts = vec![compute_set_layout; image_count];
-    let compute_alloc_info = vk::DescriptorSetAllocateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
-        descriptor_pool: desc_pool,
-        descriptor_set_count: image_count as u32,
-        p_set_layouts: compute_layouts.as_ptr(),
-        ..Default::default()
-    };
-    let compute_desc_sets = unsafe { device.allocate_descriptor_sets(&compute_alloc_info)? };
-
-    let graphics_layouts = vec![graphics_set_layout; image_count];
-    let graphics_alloc_info = vk::DescriptorSetAllocateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
-        descriptor_pool: desc_pool,
-        descriptor_set_count: image_count as u32,
-        p_set_layouts: graphics_layouts.as_ptr(),
-        ..Default::default()
-    };
-    let graphics_desc_sets = unsafe { device.allocate_descriptor_sets(&graphics_alloc_info)? };
-
-    let mut cand_infos = Vec::with_capacity(image_count);
-    let mut indirect_infos = Vec::with_capacity(image_count);
-    let mut count_infos = Vec::with_capacity(image_count);
-    for i in 0..image_count {
+    let compute_desc_sets = descriptors.allocate_many(device, compute_set_layout, frames)?;
+    let graphics_desc_sets = descriptors.allocate_many(device, graphics_set_layout, frames)?;
+
+    let mut cand_infos = Vec::with_capacity(frames);
+    let mut indirect_infos = Vec::with_capacity(frames);
+    let mut count_infos = Vec::with_capacity(frames);
+    let mut bounds_infos = Vec::with_capacity(frames);
+    for i in 0..frames {
         cand_infos.push(vk::DescriptorBufferInfo {
             buffer: candidate_bufs[i],
             offset: 0,
@@ -1339,10 +2418,15 @@ pub(crate) fn create_indirect_draw_resources(
             offset: 0,
             range: count_size,
         });
+        bounds_infos.push(vk::DescriptorBufferInfo {
+            buffer: bounds_bufs[i],
+            offset: 0,
+            range: bounds_size,
+        });
     }
 
-    let mut writes = Vec::with_capacity(image_count * 4);
-    for i in 0..image_count {
+    let mut writes = Vec::with_capacity(frames * 5);
+    for i in 0..frames {
         writes.push(vk::WriteDescriptorSet {
             s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
             dst_set: compute_desc_sets[i],
@@ -1370,6 +2454,15 @@ pub(crate) fn create_indirect_draw_resources(
             p_buffer_info: &count_infos[i],
             ..Default::default()
         });
+        writes.push(vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: compute_desc_sets[i],
+            dst_binding: 3,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            p_buffer_info: &bounds_infos[i],
+            ..Default::default()
+        });
         writes.push(vk::WriteDescriptorSet {
             s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
             dst_set: graphics_desc_sets[i],
@@ -1386,12 +2479,67 @@ pub(crate) fn create_indirect_draw_resources(
         candidate_bufs,
         candidate_allocs,
         candidate_ptrs,
+        bounds_bufs,
+        bounds_allocs,
+        bounds_ptrs,
         indirect_bufs,
         indirect_allocs,
         draw_count_bufs,
         draw_count_allocs,
-        desc_pool,
         compute_desc_sets,
         graphics_desc_sets,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    #[test]
+    fn levels_pack_back_to_back_halving_to_one() {
+        let pixels = vec![0u8; 5 * 3 * 4];
+        let (data, levels) = downsample_mips_srgb(&pixels, extent(5, 3), 3);
+        assert_eq!(
+            levels,
+            vec![(0, extent(5, 3)), (60, extent(2, 1)), (68, extent(1, 1))]
+        );
+        assert_eq!(data.len(), 72);
+    }
+
+    #[test]
+    fn colour_averages_in_linear_light_and_alpha_as_is() {
+        // Two black texels, two white: half the light is sRGB 188, not the
+        // 128 averaging encoded values gives. Alpha 255 and 0 averages to
+        // 128 either way.
+        #[rustfmt::skip]
+        let pixels = [
+            0, 0, 0, 255,    255, 255, 255, 0,
+            255, 255, 255, 255,  0, 0, 0, 0,
+        ];
+        let (data, _) = downsample_mips_srgb(&pixels, extent(2, 2), 2);
+        assert_eq!(data[16..], [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn flat_colour_survives_every_level() {
+        let pixels = [40u8, 100, 220, 77].repeat(8 * 8);
+        let (data, levels) = downsample_mips_srgb(&pixels, extent(8, 8), 4);
+        assert_eq!(levels.len(), 4);
+        for texel in data.chunks_exact(4) {
+            assert_eq!(texel, [40, 100, 220, 77]);
+        }
+    }
+
+    #[test]
+    fn odd_edges_clamp_instead_of_wrapping() {
+        // 3x1 down to 1x1 reads texels 0 and 1 only; the white third
+        // texel must not bleed in.
+        let pixels = [0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255];
+        let (data, _) = downsample_mips_srgb(&pixels, extent(3, 1), 2);
+        assert_eq!(data[12..], [0, 0, 0, 255]);
+    }
+}
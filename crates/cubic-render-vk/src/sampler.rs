@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Texture samplers: SamplerDesc (filtering, wrap modes, LOD bias,
+//! anisotropy) and the cache that turns one into a vk::Sampler.
+//!
+//! Textures and materials name the sampling they want by value; identical
+//! descs share one sampler, so a thousand textures with cubic.toml's
+//! defaults still cost one. Samplers live as long as the renderer (the
+//! handful of distinct descs a game uses isn't worth refcounting) and are
+//! destroyed with it.
+
+use anyhow::Result;
+use ash::vk;
+
+/// How a texture is sampled. `Default` is trilinear, repeating, no bias or
+/// anisotropy; upload_texture uses cubic.toml's settings instead (see
+/// set_sampler_config).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    /// Offset applied to the computed mip level before sampling; positive
+    /// values bias toward blurrier/lower-resolution mips, negative toward
+    /// sharper/higher-resolution ones.
+    pub lod_bias: f32,
+    /// 0.0 = disabled, otherwise clamped to the device's
+    /// `max_sampler_anisotropy` limit (and always 0.0 on devices without the
+    /// samplerAnisotropy feature).
+    pub max_anisotropy: f32,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            lod_bias: 0.0,
+            max_anisotropy: 0.0,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Same wrap mode on every axis.
+    pub fn with_address_mode(self, mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            address_mode_u: mode,
+            address_mode_v: mode,
+            address_mode_w: mode,
+            ..self
+        }
+    }
+}
+
+/// One sampler per distinct (clamped) SamplerDesc, looked up linearly like
+/// the vertex layouts in intern_vertex_layout: there are only ever a few.
+pub(crate) struct SamplerCache {
+    // The device's anisotropy ceiling; 0.0 without samplerAnisotropy.
+    max_anisotropy: f32,
+    entries: Vec<(SamplerDesc, vk::Sampler)>,
+}
+
+impl SamplerCache {
+    pub(crate) fn new(instance: &ash::Instance, phys: vk::PhysicalDevice) -> Self {
+        let supported = unsafe {
+            instance
+                .get_physical_device_features(phys)
+                .sampler_anisotropy
+        } == vk::TRUE;
+        let max_anisotropy = if supported {
+            unsafe {
+                instance
+                    .get_physical_device_properties(phys)
+                    .limits
+                    .max_sampler_anisotropy
+            }
+        } else {
+            0.0
+        };
+        Self {
+            max_anisotropy,
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn anisotropy_supported(&self) -> bool {
+        self.max_anisotropy > 0.0
+    }
+
+    /// `desc` with its anisotropy brought within what the device allows.
+    pub(crate) fn clamp(&self, desc: SamplerDesc) -> SamplerDesc {
+        SamplerDesc {
+            max_anisotropy: desc.max_anisotropy.clamp(0.0, self.max_anisotropy),
+            ..desc
+        }
+    }
+
+    pub(crate) fn get(&mut self, device: &ash::Device, desc: &SamplerDesc) -> Result<vk::Sampler> {
+        let desc = self.clamp(*desc);
+        if let Some(&(_, sampler)) = self.entries.iter().find(|(d, _)| *d == desc) {
+            return Ok(sampler);
+        }
+        let ci = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: desc.mag_filter,
+            min_filter: desc.min_filter,
+            mipmap_mode: desc.mipmap_mode,
+            address_mode_u: desc.address_mode_u,
+            address_mode_v: desc.address_mode_v,
+            address_mode_w: desc.address_mode_w,
+            anisotropy_enable: if desc.max_anisotropy > 0.0 {
+                vk::TRUE
+            } else {
+                vk::FALSE
+            },
+            max_anisotropy: desc.max_anisotropy,
+            min_lod: 0.0,
+            // Shared across textures, so no per-texture mip count here; the
+            // view's level count is the real limit.
+            max_lod: vk::LOD_CLAMP_NONE,
+            mip_lod_bias: desc.lod_bias,
+            ..Default::default()
+        };
+        let sampler = unsafe { device.create_sampler(&ci, None)? };
+        self.entries.push((desc, sampler));
+        Ok(sampler)
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        for (_, sampler) in self.entries.drain(..) {
+            unsafe { device.destroy_sampler(sampler, None) };
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Runtime GLSL → SPIR-V compilation for debug hot-reload (feature
+//! "runtime-shader-compile"). Recompiled modules are written back next to
+//! their source as `<name>.spv`, so the existing .spv mtime watch in
+//! `hot_reload_shaders_if_changed` picks them up exactly as if
+//! tools/shader_make.sh had been run by hand.
+
+use anyhow::{anyhow, Context, Result};
+use cubic_render::RenderError;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// GLSL stages hot-reload knows how to recompile. Compute shaders are left
+/// out: nothing rebuilds compute pipelines on reload yet.
+const STAGES: [(&str, shaderc::ShaderKind); 2] = [
+    ("vert", shaderc::ShaderKind::Vertex),
+    ("frag", shaderc::ShaderKind::Fragment),
+];
+
+fn stage_of(path: &Path) -> Option<shaderc::ShaderKind> {
+    let ext = path.extension()?.to_str()?;
+    STAGES.iter().find(|(e, _)| *e == ext).map(|(_, k)| *k)
+}
+
+/// Every recompilable GLSL file in `dir`, with its current mtime.
+pub(crate) fn watched_glsl_sources(dir: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| stage_of(p).is_some())
+        .filter_map(|p| {
+            let mtime = std::fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+            Some((p, mtime))
+        })
+        .collect()
+}
+
+fn compile_glsl_file(compiler: &shaderc::Compiler, src: &Path) -> Result<Vec<u32>> {
+    let kind = stage_of(src).ok_or_else(|| anyhow!("not a GLSL stage: {:?}", src))?;
+    let source = std::fs::read_to_string(src).with_context(|| format!("read {:?}", src))?;
+    let mut options =
+        shaderc::CompileOptions::new().ok_or_else(|| anyhow!("shaderc: no compile options"))?;
+    // Same target/optimization as tools/shader_make.sh.
+    options.set_target_env(
+        shaderc::TargetEnv::Vulkan,
+        shaderc::EnvVersion::Vulkan1_2 as u32,
+    );
+    options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+
+    let name = src.file_name().and_then(|n| n.to_str()).unwrap_or("shader");
+    let artifact = compiler
+        .compile_into_spirv(&source, kind, name, "main", Some(&options))
+        .map_err(|e| RenderError::ShaderCompile {
+            shader: name.to_owned(),
+            log: e.to_string(),
+        })?;
+    if artifact.get_num_warnings() > 0 {
+        tracing::warn!("shaderc: {}", artifact.get_warning_messages());
+    }
+    Ok(artifact.as_binary().to_vec())
+}
+
+/// Recompile every source whose mtime moved past the recorded one and write
+/// the result to `<source>.spv`. Compile errors are logged and leave the
+/// previous .spv (and so the running pipeline) untouched — a typo mid-edit
+/// must never take the renderer down.
+pub(crate) fn recompile_changed(sources: &mut [(PathBuf, SystemTime)]) {
+    let mut compiler = None;
+    for (path, seen) in sources.iter_mut() {
+        let Ok(mtime) = std::fs::metadata(&*path).and_then(|m| m.modified()) else {
+            continue;
+        };
+        if mtime <= *seen {
+            continue;
+        }
+        // Record first so a failing file isn't recompiled every frame.
+        *seen = mtime;
+
+        if compiler.is_none() {
+            compiler = shaderc::Compiler::new();
+            if compiler.is_none() {
+                tracing::error!("shaderc: failed to create compiler; GLSL reload disabled");
+                return;
+            }
+        }
+        let Some(c) = compiler.as_ref() else {
+            return;
+        };
+
+        let mut out = path.clone().into_os_string();
+        out.push(".spv");
+        match compile_glsl_file(c, path) {
+            Ok(words) => match std::fs::write(&out, bytemuck::cast_slice::<u32, u8>(&words)) {
+                Ok(()) => tracing::info!("shaderc: recompiled {:?}", path),
+                Err(e) => tracing::error!("shaderc: write {:?}: {e}", out),
+            },
+            Err(e) => tracing::error!("shaderc: {:?} failed to compile:\n{e:#}", path),
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Cascaded shadow map for the directional light (see lighting.rs for the
+//! API and the cascade fit).
+//!
+//! One depth-only 2D array image, a layer per cascade, rendered each frame
+//! by the graph's "shadows" pass: per cascade, the default pipeline's draws
+//! are drawn with the shadow pipeline (tri.vert, no fragment stage, depth
+//! bias) through a camera set whose view_proj is that cascade's light
+//! matrix. The scene's fragment shader then samples it through a compare
+//! sampler (set 0, binding 1) with a small PCF kernel.
+//!
+//! Only default-pipeline draws cast shadows: registered pipelines can have
+//! any vertex layout, which the shared shadow pipeline can't read. They
+//! still receive shadows.
+//!
+//! With shadows off the image shrinks to a 1x1 placeholder, kept only so
+//! the scene's set 0 has something valid at binding 1.
+
+use anyhow::{Context, Result};
+use ash::vk;
+use cubic_render::PipelineHandle;
+use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::DescriptorAllocator;
+use crate::error::alloc_error;
+use crate::pipeline::{create_shadow_pipeline, PipelineConfig};
+use crate::resources::MAX_INDIRECT_DRAWS;
+#[cfg(debug_assertions)]
+use crate::{DeferredDrop, GpuResource};
+use crate::{RetiredPass, VkRenderer};
+
+pub(crate) struct ShadowMap {
+    pub(crate) image: vk::Image,
+    alloc: Allocation,
+    /// Every layer, as sampled by the scene.
+    pub(crate) array_view: vk::ImageView,
+    /// One per layer, as rendered into by the shadow pass.
+    layer_views: Vec<vk::ImageView>,
+    resolution: u32,
+}
+
+impl ShadowMap {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        format: vk::Format,
+        resolution: u32,
+        layers: u32,
+    ) -> Result<Self> {
+        let ci = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: vk::Extent3D {
+                width: resolution,
+                height: resolution,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: layers,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let image = unsafe { device.create_image(&ci, None) }
+            .with_context(|| format!("create_image shadow map {resolution}² x {layers}"))?;
+        let req = unsafe { device.get_image_memory_requirements(image) };
+        let alloc = match allocator.allocate(&AllocationCreateDesc {
+            name: "shadow map",
+            requirements: req,
+            location: MemoryLocation::GpuOnly,
+            linear: false,
+            allocation_scheme: AllocationScheme::DedicatedImage(image),
+        }) {
+            Ok(a) => a,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(alloc_error(e)).context("allocate shadow map");
+            }
+        };
+        let mut map = Self {
+            image,
+            alloc,
+            array_view: vk::ImageView::null(),
+            layer_views: Vec::with_capacity(layers as usize),
+            resolution,
+        };
+        // From here on destroy() can clean up whatever got created.
+        let result = (|| -> Result<()> {
+            unsafe { device.bind_image_memory(image, map.alloc.memory(), map.alloc.offset())? };
+            let view = |view_type, base_array_layer, layer_count| {
+                let ci = vk::ImageViewCreateInfo {
+                    s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+                    image,
+                    view_type,
+                    format,
+                    subresource_range: vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::DEPTH,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer,
+                        layer_count,
+                    },
+                    ..Default::default()
+                };
+                unsafe { device.create_image_view(&ci, None) }
+            };
+            map.array_view = view(vk::ImageViewType::TYPE_2D_ARRAY, 0, layers)?;
+            for layer in 0..layers {
+                map.layer_views
+                    .push(view(vk::ImageViewType::TYPE_2D, layer, 1)?);
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(map),
+            Err(e) => {
+                map.destroy(device, allocator);
+                Err(e)
+            }
+        }
+    }
+
+    fn layers(&self) -> u32 {
+        self.layer_views.len() as u32
+    }
+
+    /// Caller must have idled the device (or retired every use).
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            for v in self.layer_views.drain(..) {
+                device.destroy_image_view(v, None);
+            }
+            if self.array_view != vk::ImageView::null() {
+                device.destroy_image_view(self.array_view, None);
+            }
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(std::mem::take(&mut self.alloc));
+    }
+}
+
+impl RetiredPass for ShadowMap {
+    fn destroy_retired(
+        mut self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        _descriptors: &mut DescriptorAllocator,
+    ) {
+        self.destroy(device, allocator);
+    }
+}
+
+/// Everything the shadow pass owns besides its camera set (that lives with
+/// the main one, see create_camera_sets).
+pub(crate) struct ShadowPass {
+    pub(crate) map: ShadowMap,
+    format: vk::Format,
+    /// Depth-compare sampler the scene reads the map through.
+    sampler: vk::Sampler,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+/// Shadow map format: pure depth, renderable and sampleable. Prefers
+/// formats that can be filtered, so the compare sampler gets hardware 2x2
+/// PCF per tap. Returns the format and whether LINEAR filtering works.
+pub(crate) fn pick_shadow_format(
+    instance: &ash::Instance,
+    phys: vk::PhysicalDevice,
+) -> (vk::Format, bool) {
+    let needed =
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+    let candidates = [vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+    let features = |fmt| unsafe {
+        instance
+            .get_physical_device_format_properties(phys, fmt)
+            .optimal_tiling_features
+    };
+    let linear = candidates.iter().find(|&&fmt| {
+        features(fmt).contains(needed | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+    });
+    if let Some(&fmt) = linear {
+        return (fmt, true);
+    }
+    // D16_UNORM with DEPTH_STENCIL_ATTACHMENT and SAMPLED_IMAGE is
+    // required by the spec.
+    let fmt = candidates
+        .into_iter()
+        .find(|&fmt| features(fmt).contains(needed))
+        .unwrap_or(vk::Format::D16_UNORM);
+    (fmt, false)
+}
+
+impl ShadowPass {
+    pub(crate) fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        cache: vk::PipelineCache,
+        cfg: &PipelineConfig,
+        (format, linear): (vk::Format, bool),
+    ) -> Result<Self> {
+        let (layout, pipeline) = create_shadow_pipeline(device, cache, cfg, format)?;
+        let filter = if linear {
+            vk::Filter::LINEAR
+        } else {
+            vk::Filter::NEAREST
+        };
+        // Reverse-Z: a point is lit when it's at least as close to the
+        // light as the nearest caster, i.e. its depth >= the stored one.
+        // Outside the map the border (depth 0, the far plane) lights it.
+        let sampler_ci = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            compare_enable: vk::TRUE,
+            compare_op: vk::CompareOp::GREATER_OR_EQUAL,
+            border_color: vk::BorderColor::FLOAT_OPAQUE_BLACK,
+            ..Default::default()
+        };
+        let sampler = match unsafe { device.create_sampler(&sampler_ci, None) } {
+            Ok(s) => s,
+            Err(e) => {
+                unsafe {
+                    device.destroy_pipeline(pipeline, None);
+                    device.destroy_pipeline_layout(layout, None);
+                }
+                return Err(e.into());
+            }
+        };
+        let map = match ShadowMap::new(device, allocator, format, 1, 1) {
+            Ok(m) => m,
+            Err(e) => {
+                unsafe {
+                    device.destroy_sampler(sampler, None);
+                    device.destroy_pipeline(pipeline, None);
+                    device.destroy_pipeline_layout(layout, None);
+                }
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            map,
+            format,
+            sampler,
+            layout,
+            pipeline,
+        })
+    }
+
+    /// Tear everything down. Caller must have idled the device.
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        self.map.destroy(device, allocator);
+    }
+}
+
+impl VkRenderer {
+    /// Point binding 1 (the shadow map) of `sets` (camera_sets, or a new
+    /// stereo set alone) at the current map. Only call while the sets
+    /// aren't in use: right after creating them (see renew_camera_sets).
+    pub(crate) fn write_shadow_descriptors(
+        &self,
+        sets: impl IntoIterator<Item = vk::DescriptorSet>,
+    ) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler: self.shadow.sampler,
+            image_view: self.shadow.map.array_view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let writes: Vec<vk::WriteDescriptorSet> = sets
+            .into_iter()
+            .map(|set| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 1,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                p_image_info: &image_info,
+                ..Default::default()
+            })
+            .collect();
+        unsafe { self.device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    /// Reallocate the shadow map if its size or layer count differs. The
+    /// old map may still be read by frames in flight, so it's retired
+    /// through the trash queue, and the camera sets pointing at it are
+    /// replaced rather than rewritten (see renew_camera_sets).
+    pub(crate) fn resize_shadow_map(&mut self, resolution: u32, layers: u32) -> Result<()> {
+        if self.shadow.map.resolution == resolution && self.shadow.map.layers() == layers {
+            return Ok(());
+        }
+        let map = ShadowMap::new(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.shadow.format,
+            resolution,
+            layers,
+        )?;
+        let old = std::mem::replace(&mut self.shadow.map, map);
+        self.retire_pass(old);
+        self.renew_camera_sets()
+    }
+
+    /// Rebuild the shadow pipeline (shader hot-reload: it runs tri.vert),
+    /// retiring the old one through the trash queue.
+    #[cfg(debug_assertions)]
+    pub(crate) fn rebuild_shadow_pipeline(&mut self) -> Result<()> {
+        let (layout, pipeline) = create_shadow_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &self.pipeline_config(),
+            self.shadow.format,
+        )?;
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::Pipeline(std::mem::replace(&mut self.shadow.pipeline, pipeline)),
+        });
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::PipelineLayout(std::mem::replace(
+                &mut self.shadow.layout,
+                layout,
+            )),
+        });
+        Ok(())
+    }
+
+    /// Whether this frame renders shadows: enabled, and the map has been
+    /// sized for it (set_shadow_settings failing to allocate leaves the
+    /// placeholder in place).
+    pub(crate) fn shadows_active(&self) -> bool {
+        let s = &self.shadow_settings;
+        s.enabled
+            && self.shadow.map.resolution == s.resolution
+            && self.shadow.map.layers() == s.cascade_count
+    }
+
+    /// The graph's "shadows" pass: clear and render each cascade's layer.
+    /// Runs outside any graph-opened rendering scope (it opens one per
+    /// layer itself); default-pipeline draws only, recorded directly
+    /// rather than through the camera-culled indirect buffer, since
+    /// casters outside the view still throw shadows into it.
+    pub(crate) fn record_shadow_pass(&self, cmd: vk::CommandBuffer, frame: usize) {
+        let map = &self.shadow.map;
+        let size = map.resolution;
+        let extent = vk::Extent2D {
+            width: size,
+            height: size,
+        };
+        // Flipped like the scene's viewport, so the same y convention
+        // holds when tri.frag turns a light-space position into a UV.
+        let vp = vk::Viewport {
+            x: 0.0,
+            y: size as f32,
+            width: size as f32,
+            height: -(size as f32),
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let sc = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        // Every default-pipeline draw, whatever its material: they lead
+        // the sorted queue.
+        let draws = self
+            .pending_draws
+            .iter()
+            .take(MAX_INDIRECT_DRAWS as usize)
+            .take_while(|d| d.pipeline == PipelineHandle::DEFAULT)
+            .count();
+        let s = &self.shadow_settings;
+        for (cascade, &view) in map.layer_views.iter().enumerate() {
+            let depth = vk::RenderingAttachmentInfo {
+                s_type: vk::StructureType::RENDERING_ATTACHMENT_INFO,
+                image_view: view,
+                image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                clear_value: vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue {
+                        depth: 0.0,
+                        stencil: 0,
+                    },
+                },
+                ..Default::default()
+            };
+            let rendering_info = vk::RenderingInfo {
+                s_type: vk::StructureType::RENDERING_INFO,
+                render_area: sc,
+                layer_count: 1,
+                p_depth_attachment: &depth,
+                ..Default::default()
+            };
+            let sets = [
+                self.shadow_camera_set,
+                self.material_desc_set,
+                self.indirect_graphics_desc_sets[frame],
+            ];
+            unsafe {
+                self.device.cmd_begin_rendering(cmd, &rendering_info);
+                self.device.cmd_bind_pipeline(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.shadow.pipeline,
+                );
+                self.device
+                    .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+                self.device
+                    .cmd_set_scissor(cmd, 0, std::slice::from_ref(&sc));
+                // Reverse-Z: "further from the light" is smaller depth,
+                // so the bias pushing casters back is negative.
+                self.device.cmd_set_depth_bias(
+                    cmd,
+                    -s.depth_bias_constant,
+                    0.0,
+                    -s.depth_bias_slope,
+                );
+                self.device.cmd_bind_descriptor_sets(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.shadow.layout,
+                    0,
+                    &sets,
+                    &self.frame_uniforms.shadow(cascade),
+                );
+                self.device.cmd_bind_vertex_buffers(
+                    cmd,
+                    0,
+                    std::slice::from_ref(&self.shared_vbuf),
+                    &[0],
+                );
+                self.device
+                    .cmd_bind_index_buffer(cmd, self.shared_ibuf, 0, vk::IndexType::UINT32);
+                for (i, draw) in self.pending_draws[..draws].iter().enumerate() {
+                    let Some(mesh) = self.meshes.get(draw.mesh.0 as usize) else {
+                        continue;
+                    };
+                    if mesh.index_count == 0 {
+                        continue;
+                    }
+                    // first_instance = candidate slot, as in the scene.
+                    self.device.cmd_draw_indexed(
+                        cmd,
+                        mesh.index_count,
+                        1,
+                        mesh.first_index,
+                        mesh.first_vertex,
+                        i as u32,
+                    );
+                }
+                self.device.cmd_end_rendering(cmd);
+            }
+        }
+    }
+}
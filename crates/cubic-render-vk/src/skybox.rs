@@ -0,0 +1,387 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Skybox: a cubemap drawn behind the scene instead of the clear colour.
+//!
+//! Cubemaps are uploaded from six faces (load_cubemap) or converted on the
+//! CPU from an equirectangular panorama (load_cubemap_equirect), and
+//! set_environment picks the one to draw. They live outside the bindless
+//! array, which only holds 2D textures: each gets its own descriptor set
+//! (one samplerCube) from the renderer's persistent descriptor allocator.
+//!
+//! The sky is one fullscreen triangle at the far plane (z = 0 with
+//! reverse-Z) drawn first in the scene pass with a GREATER_OR_EQUAL test
+//! and no depth write. skybox.vert turns each corner back into a view
+//! direction through the inverse of the camera's rotation-only view_proj,
+//! so the sky turns with the camera but never moves. With the depth prepass
+//! on, only pixels nothing covers pass the test; without it the sky fills
+//! the screen and the scene's draws cover it. Without skybox shaders built,
+//! load_cubemap still works and set_environment falls back to the clear
+//! colour.
+//!
+//! Each cubemap also gets its image-based lighting maps as it's uploaded,
+//! and set_environment binds those of the one it picks for the scene to
+//! light with; see ibl.rs.
+
+use std::f32::consts::{PI, TAU};
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_math::{Mat4, Vec3};
+use cubic_render::RenderResult;
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::ibl::IblMaps;
+use crate::pipeline::{create_overlay_pipeline, BlendMode};
+use crate::resources::create_cubemap;
+use crate::sampler::SamplerDesc;
+use crate::{DeferredDrop, GpuResource, VkRenderer};
+
+/// Opaque handle to a cubemap uploaded with `VkRenderer::load_cubemap` or
+/// `load_cubemap_equirect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CubemapHandle(pub u32);
+
+/// Push constants for skybox.vert; layout must match its `Sky` block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct SkyPush {
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+struct Cubemap {
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+    set: PooledSet,
+    // The faces as uploaded, for device-lost recovery (like tex_sources).
+    faces: Vec<u8>,
+    size: u32,
+    // None without the IBL pass, or if generating them failed.
+    ibl: Option<IblMaps>,
+}
+
+/// Skybox pipeline, its set layout, and every cubemap loaded so far.
+pub(crate) struct SkyboxPass {
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    // Built against the scene colour format; None until sync_skybox_pipeline
+    // succeeds (skybox shaders compiled).
+    pub(crate) pipeline: Option<vk::Pipeline>,
+    cubemaps: Vec<Cubemap>,
+    pub(crate) environment: Option<CubemapHandle>,
+    /// The cubemap whose IBL maps the camera sets hold (see
+    /// sync_environment_lighting).
+    pub(crate) ibl_bound: Option<CubemapHandle>,
+}
+
+impl SkyboxPass {
+    pub(crate) fn new(device: &ash::Device) -> Result<Self> {
+        let binding = vk::DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        };
+        let set_layout_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            binding_count: 1,
+            p_bindings: &binding,
+            ..Default::default()
+        };
+        let set_layout = unsafe { device.create_descriptor_set_layout(&set_layout_info, None)? };
+
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<SkyPush>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        Ok(Self {
+            set_layout,
+            layout,
+            pipeline: None,
+            cubemaps: Vec::new(),
+            environment: None,
+            ibl_bound: None,
+        })
+    }
+
+    pub(crate) fn ibl_maps(&self, cubemap: CubemapHandle) -> Option<&IblMaps> {
+        self.cubemaps.get(cubemap.0 as usize)?.ibl.as_ref()
+    }
+
+    /// The uploaded faces of every cubemap, in handle order, for
+    /// device-lost recovery to load again.
+    pub(crate) fn take_sources(&mut self) -> Vec<(Vec<u8>, u32)> {
+        self.cubemaps
+            .iter_mut()
+            .map(|c| (std::mem::take(&mut c.faces), c.size))
+            .collect()
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        unsafe {
+            for c in self.cubemaps.drain(..) {
+                descriptors.free(device, c.set);
+                device.destroy_image_view(c.view, None);
+                device.destroy_image(c.image, None);
+                let _ = allocator.free(c.alloc);
+                if let Some(maps) = c.ibl {
+                    maps.destroy(device, allocator);
+                }
+            }
+            if let Some(pipeline) = self.pipeline.take() {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+/// World-space direction through texel (u, v) of cube face `face`, both in
+/// -1..1 with v down, following Vulkan's face selection table.
+fn cube_face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+}
+
+/// Resample an equirectangular RGBA8 panorama into six `size`² faces
+/// (bilinear, wrapping horizontally). The panorama's centre column looks
+/// down -Z, its top row straight up.
+fn equirect_to_cube(pixels: &[u8], width: u32, height: u32, size: u32) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let texel = |x: usize, y: usize, c: usize| pixels[(y * w + x) * 4 + c] as f32;
+    let mut out = Vec::with_capacity(size as usize * size as usize * 4 * 6);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let dir = cube_face_direction(face, u, v).normalize();
+                let s = 0.5 + dir.x.atan2(-dir.z) / TAU;
+                let t = dir.y.clamp(-1.0, 1.0).acos() / PI;
+
+                let fx = s * w as f32 - 0.5;
+                let fy = (t * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (ax, ay) = (fx - x0, fy - y0);
+                let x0 = (x0 as isize).rem_euclid(w as isize) as usize;
+                let x1 = (x0 + 1) % w;
+                let y0 = y0 as usize;
+                let y1 = (y0 + 1).min(h - 1);
+                for c in 0..4 {
+                    let top = texel(x0, y0, c) * (1.0 - ax) + texel(x1, y0, c) * ax;
+                    let bottom = texel(x0, y1, c) * (1.0 - ax) + texel(x1, y1, c) * ax;
+                    out.push((top * (1.0 - ay) + bottom * ay).round() as u8);
+                }
+            }
+        }
+    }
+    out
+}
+
+impl VkRenderer {
+    /// Upload a cubemap from six square RGBA8 sRGB faces, `size` texels on
+    /// a side, in Vulkan's +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn load_cubemap(&mut self, faces: [&[u8]; 6], size: u32) -> RenderResult<CubemapHandle> {
+        let face_len = size as usize * size as usize * 4;
+        if size == 0 || faces.iter().any(|f| f.len() != face_len) {
+            return Err(anyhow!(
+                "load_cubemap: every face must be {size}x{size} RGBA8 ({face_len} bytes)"
+            )
+            .into());
+        }
+        Ok(self.upload_cubemap(faces.concat(), size)?)
+    }
+
+    /// Upload a cubemap converted from an equirectangular (2:1 lat-long)
+    /// RGBA8 sRGB panorama, with faces a quarter of its width on a side.
+    pub fn load_cubemap_equirect(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<CubemapHandle> {
+        if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+            return Err(
+                anyhow!("load_cubemap_equirect: expected {width}x{height} RGBA8 pixels").into(),
+            );
+        }
+        let size = (width / 4).max(1);
+        Ok(self.upload_cubemap(equirect_to_cube(pixels, width, height, size), size)?)
+    }
+
+    pub(crate) fn upload_cubemap(&mut self, faces: Vec<u8>, size: u32) -> Result<CubemapHandle> {
+        // Clamped, so the seams between faces don't pick up the far edge.
+        let sampler = self.samplers.get(
+            &self.device,
+            &SamplerDesc::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        )?;
+        let (image, alloc, view) = create_cubemap(
+            &self.device,
+            self.allocator.as_mut().expect("allocator missing"),
+            self.queue,
+            self.cmd_pool,
+            &faces,
+            size,
+        )?;
+        let set = self
+            .descriptors
+            .persistent
+            .allocate(&self.device, self.skybox.set_layout)?;
+        let image_info = vk::DescriptorImageInfo {
+            sampler,
+            image_view: view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let write = vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: set.set,
+            dst_binding: 0,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+        unsafe { self.device.update_descriptor_sets(&[write], &[]) };
+
+        let handle = CubemapHandle(self.skybox.cubemaps.len() as u32);
+        let ibl = self.build_ibl_maps(view).unwrap_or_else(|e| {
+            tracing::warn!("cubemap {} has no IBL maps: {e:#}", handle.0);
+            None
+        });
+        self.skybox.cubemaps.push(Cubemap {
+            image,
+            alloc,
+            view,
+            set,
+            faces,
+            size,
+            ibl,
+        });
+        Ok(handle)
+    }
+
+    /// Draw `cubemap` behind the scene from the next frame on, in place of
+    /// the clear colour, and light the scene with its IBL maps. Switching
+    /// to a cubemap whose maps aren't bound yet idles the device.
+    pub fn set_environment(&mut self, cubemap: CubemapHandle) {
+        self.skybox.environment = Some(cubemap);
+        self.sync_environment_lighting();
+    }
+
+    /// Back to the plain clear colour.
+    pub fn clear_environment(&mut self) {
+        self.skybox.environment = None;
+    }
+
+    /// (Re)build the skybox pipeline against the current scene target,
+    /// retiring the old one. Called at startup and with the scene pipelines
+    /// (see rebuild_scene_pipelines); on failure the sky isn't drawn.
+    pub(crate) fn sync_skybox_pipeline(&mut self) {
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            ..Default::default()
+        };
+        let built = create_overlay_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            self.skybox.layout,
+            ("skybox.vert.spv", "skybox.frag.spv"),
+            &vertex_input,
+            BlendMode::Opaque,
+            true,
+            self.scene_color_format(),
+            self.depth_format,
+        );
+        let new = match built {
+            Ok(p) => Some(p),
+            Err(e) => {
+                tracing::warn!("skybox disabled: {e:#}");
+                None
+            }
+        };
+        if let Some(old) = std::mem::replace(&mut self.skybox.pipeline, new) {
+            self.trash.push(DeferredDrop {
+                value: self.timeline_value,
+                resource: GpuResource::Pipeline(old),
+            });
+        }
+    }
+
+    /// Draw the environment through a rotation-only `view_proj` over
+    /// `area`, first thing in a scene view or a render target. No-op
+    /// without one set or without a pipeline.
+    pub(crate) fn record_sky(&self, cmd: vk::CommandBuffer, view_proj: Mat4, area: vk::Rect2D) {
+        let (Some(pipeline), Some(env)) = (self.skybox.pipeline, self.skybox.environment) else {
+            return;
+        };
+        let Some(cubemap) = self.skybox.cubemaps.get(env.0 as usize) else {
+            return;
+        };
+        let push = SkyPush {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+        };
+        // Same flipped viewport as record_indirect_draws, so NDC means
+        // what the camera's projection says it does.
+        let vp = vk::Viewport {
+            x: area.offset.x as f32,
+            y: (area.offset.y + area.extent.height as i32) as f32,
+            width: area.extent.width as f32,
+            height: -(area.extent.height as f32),
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        self.debug_labels.begin(cmd, "skybox");
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&area));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.skybox.layout,
+                0,
+                std::slice::from_ref(&cubemap.set.set),
+                &[],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                self.skybox.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            self.device.cmd_draw(cmd, 3, 1, 0, 0);
+        }
+        self.debug_labels.end(cmd);
+    }
+}
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Staging belt: a persistently mapped ring buffer for per-frame dynamic
+//! data (instance buffers, UI vertices, small uniform blocks).
+//!
+//! `upload_transient` copies bytes into the ring and hands back a
+//! BufferSlice the current frame's commands can read directly; nothing is
+//! copied again on the GPU. Every slice handed out since the last submit is
+//! retired with that submit's timeline value (see render_frame), and the
+//! ring only reuses the space once the timeline has passed it. If the ring
+//! is full, upload_transient waits for the oldest frame in flight to
+//! finish rather than fail; only a single upload bigger than the whole ring
+//! is an error.
+//!
+//! The renderer's own per-frame uniform blocks (camera, lights, shadow
+//! cascades) come from the belt too, through upload_uniform: the camera
+//! sets hold DYNAMIC_UNIFORM_BUFFER descriptors over the whole buffer and
+//! are bound with each block's offset, so one buffer and one set serve
+//! every frame in flight, and per-draw blocks would only add offsets.
+//!
+//! Offsets are kept as ever-increasing byte counts (physical offset = count
+//! modulo the ring size), so "used" is just head - tail, and an allocation
+//! that would straddle the end skips ahead to the start of the next lap.
+
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::Pod;
+use cubic_render::RenderResult;
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::error::vk_error;
+use crate::resources::create_buffer_and_memory;
+use crate::VkRenderer;
+
+/// Ring size. Plenty for a frame or three of UI and instance data.
+const STAGING_BELT_SIZE: vk::DeviceSize = 8 * 1024 * 1024;
+
+/// A range of the staging belt written by `upload_transient`. Valid for
+/// commands recorded for the next rendered frame only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferSlice {
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+}
+
+impl BufferSlice {
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Aligned for any use the buffer allows (uniform, storage, vertex,
+    /// index, indirect).
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+}
+
+pub(crate) struct StagingBelt {
+    buffer: vk::Buffer,
+    alloc: Allocation,
+    alignment: vk::DeviceSize,
+    // Byte counts since creation; see the module docs.
+    head: u64,
+    tail: u64,
+    // (head when the frame was submitted, that submit's timeline value),
+    // oldest first.
+    in_flight: VecDeque<(u64, u64)>,
+}
+
+impl StagingBelt {
+    pub(crate) fn new(
+        instance: &ash::Instance,
+        phys: vk::PhysicalDevice,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+    ) -> Result<Self> {
+        let limits = unsafe { instance.get_physical_device_properties(phys).limits };
+        let alignment = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment)
+            .max(16);
+        let (buffer, alloc) = create_buffer_and_memory(
+            device,
+            allocator,
+            STAGING_BELT_SIZE,
+            vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::INDEX_BUFFER
+                | vk::BufferUsageFlags::UNIFORM_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            "staging belt",
+        )?;
+        if alloc.mapped_ptr().is_none() {
+            unsafe { device.destroy_buffer(buffer, None) };
+            let _ = allocator.free(alloc);
+            return Err(anyhow!("staging belt allocation not host-mapped"));
+        }
+        Ok(Self {
+            buffer,
+            alloc,
+            alignment,
+            head: 0,
+            tail: 0,
+            in_flight: VecDeque::new(),
+        })
+    }
+
+    /// Give back every frame the timeline has passed.
+    fn reclaim(&mut self, signaled: u64) {
+        while let Some(&(end, value)) = self.in_flight.front() {
+            if value > signaled {
+                break;
+            }
+            self.tail = end;
+            self.in_flight.pop_front();
+        }
+    }
+
+    /// Physical offset of `len` free bytes, or None if the ring is full.
+    fn alloc(&mut self, len: u64) -> Option<u64> {
+        let mut start = self.head.next_multiple_of(self.alignment);
+        if start % STAGING_BELT_SIZE + len > STAGING_BELT_SIZE {
+            start = start.next_multiple_of(STAGING_BELT_SIZE);
+        }
+        if start + len - self.tail > STAGING_BELT_SIZE {
+            return None;
+        }
+        self.head = start + len;
+        Some(start % STAGING_BELT_SIZE)
+    }
+
+    pub(crate) fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Retire everything allocated since the last call with the frame
+    /// submit that signals `value`.
+    pub(crate) fn finish_frame(&mut self, value: u64) {
+        let last = self.in_flight.back().map_or(self.tail, |&(end, _)| end);
+        if self.head > last {
+            self.in_flight.push_back((self.head, value));
+        }
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe { device.destroy_buffer(self.buffer, None) };
+        let _ = allocator.free(std::mem::take(&mut self.alloc));
+    }
+}
+
+impl VkRenderer {
+    /// Copy `data` into the staging belt for this frame's commands to read
+    /// (see BufferSlice). Blocks only if the belt is full of frames still
+    /// in flight.
+    pub fn upload_transient(&mut self, data: &[u8]) -> RenderResult<BufferSlice> {
+        let len = data.len() as u64;
+        if len > STAGING_BELT_SIZE {
+            return Err(anyhow!(
+                "upload_transient: {len} bytes is more than the {STAGING_BELT_SIZE}-byte staging belt"
+            )
+            .into());
+        }
+        let belt = &mut self.staging_belt;
+        let signaled = unsafe {
+            self.device
+                .get_semaphore_counter_value(self.timeline)
+                .map_err(|e| vk_error(e, "get_semaphore_counter_value"))?
+        };
+        belt.reclaim(signaled);
+        let offset = loop {
+            if let Some(offset) = belt.alloc(len) {
+                break offset;
+            }
+            // Full: wait for the oldest frame in flight and take its space.
+            let Some(&(_, value)) = belt.in_flight.front() else {
+                return Err(anyhow!(
+                    "upload_transient: staging belt full with this frame's uploads alone"
+                )
+                .into());
+            };
+            let wait_info = vk::SemaphoreWaitInfo {
+                s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+                semaphore_count: 1,
+                p_semaphores: &self.timeline,
+                p_values: &value,
+                ..Default::default()
+            };
+            unsafe {
+                self.device
+                    .wait_semaphores(&wait_info, u64::MAX)
+                    .map_err(|e| vk_error(e, "wait_semaphores"))?
+            };
+            belt.reclaim(value);
+        };
+        let dst = belt
+            .alloc
+            .mapped_ptr()
+            .expect("checked in StagingBelt::new")
+            .as_ptr() as *mut u8;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), dst.add(offset as usize), data.len());
+        }
+        Ok(BufferSlice {
+            buffer: belt.buffer,
+            offset,
+            size: len,
+        })
+    }
+
+    /// upload_transient for one uniform block, returning the dynamic
+    /// offset to bind it at through a descriptor over the belt's buffer.
+    pub(crate) fn upload_uniform<T: Pod>(&mut self, block: &T) -> Result<u32> {
+        let slice = self.upload_transient(bytemuck::bytes_of(block))?;
+        Ok(slice.offset as u32)
+    }
+}
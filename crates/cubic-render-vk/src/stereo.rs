@@ -0,0 +1,751 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Stereo rendering through multiview (VK_KHR_multiview, core in 1.1):
+//! groundwork for VR output, and a way to exercise layered rendering.
+//!
+//! With set_stereo on, the scene is drawn once into a two-layer colour and
+//! depth target ("stereo scene"), the rendering scope's view mask sending
+//! every draw to both layers and stereo.vert picking each eye's view_proj
+//! by gl_ViewIndex. A "stereo composite" pass then copies the layers side
+//! by side into the scene target (left eye on the left), and everything
+//! after the scene (post, tonemap, text, egui) runs as usual. Each eye is
+//! half the scene's width, so the composite is 1:1.
+//!
+//! Eye cameras work like a render target's (see render_target.rs): model
+//! matrices stay relative to set_camera's camera, each eye's block shifts
+//! by the offset between the two. The eyes share the main camera's
+//! shadows and lights, and both are lit as the left one sees it.
+//!
+//! It's groundwork, so only the default pipeline's indirect draws are in
+//! it: no sky, registered pipelines, direct draws, debug lines or object
+//! ids, and GPU culling keeps every draw. It replaces the depth prepass
+//! and scene passes (and set_viewports' views) while on, and is skipped on
+//! a pre-rotated swapchain.
+
+use anyhow::{Context, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_math::{Camera, DVec3, Mat4, Vec3};
+use cubic_render::{RenderError, RenderResult};
+use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::error::alloc_error;
+use crate::frame_graph::{Access, FrameGraph, LoadOp, ResourceId};
+use crate::light_clusters::LightClusters;
+use crate::lighting::LightList;
+use crate::pipeline::{create_fullscreen_pipeline, create_stereo_pipeline, PipelineConfig};
+use crate::render_target::target_view_proj;
+use crate::resources::{
+    create_scene_camera_set, depth_aspect_mask, depth_attachment_layout, CameraUbo,
+    MAX_INDIRECT_DRAWS,
+};
+use crate::swapchain::pre_rotation;
+use crate::tonemap::HDR_TARGET_FORMAT;
+use crate::{DeferredDrop, GpuResource, RetiredPass, VkRenderer};
+
+/// Both eyes, layer 0 the left.
+pub(crate) const STEREO_VIEW_MASK: u32 = 0b11;
+
+/// Two eyes for `camera`, `separation` apart (world units; 0.064 is a
+/// typical interpupillary distance in metres) along its right vector.
+pub fn stereo_eyes(camera: &Camera, separation: f32) -> [Camera; 2] {
+    let right = camera
+        .forward()
+        .cross(Vec3::Y)
+        .try_normalize()
+        .unwrap_or(Vec3::X);
+    let half = DVec3::from(right * (separation * 0.5));
+    [
+        Camera {
+            position: camera.position - half,
+            ..*camera
+        },
+        Camera {
+            position: camera.position + half,
+            ..*camera
+        },
+    ]
+}
+
+/// Push constants for stereo_composite.frag.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct CompositePush {
+    layer: u32,
+}
+
+/// One two-layer attachment of the stereo target. Null handles (and no
+/// allocation) until created.
+struct Layers {
+    image: vk::Image,
+    alloc: Option<Allocation>,
+    /// Both layers, as the multiview scope renders and the composite
+    /// samples them.
+    view: vk::ImageView,
+}
+
+impl Layers {
+    fn none() -> Self {
+        Self {
+            image: vk::Image::null(),
+            alloc: None,
+            view: vk::ImageView::null(),
+        }
+    }
+
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect: vk::ImageAspectFlags,
+        name: &'static str,
+    ) -> Result<Self> {
+        let ci = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 2,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let image = unsafe { device.create_image(&ci, None) }
+            .with_context(|| format!("create_image {name} {extent:?} x 2"))?;
+        let mut layers = Self {
+            image,
+            alloc: None,
+            view: vk::ImageView::null(),
+        };
+        let result = (|| -> Result<()> {
+            let req = unsafe { device.get_image_memory_requirements(image) };
+            let alloc = allocator
+                .allocate(&AllocationCreateDesc {
+                    name,
+                    requirements: req,
+                    location: MemoryLocation::GpuOnly,
+                    linear: false,
+                    allocation_scheme: AllocationScheme::DedicatedImage(image),
+                })
+                .map_err(alloc_error)
+                .with_context(|| format!("allocate {name}"))?;
+            let alloc = layers.alloc.insert(alloc);
+            unsafe { device.bind_image_memory(image, alloc.memory(), alloc.offset())? };
+            let view_ci = vk::ImageViewCreateInfo {
+                s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+                image,
+                view_type: vk::ImageViewType::TYPE_2D_ARRAY,
+                format,
+                subresource_range: vk::ImageSubresourceRange {
+                    aspect_mask: aspect,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 2,
+                },
+                ..Default::default()
+            };
+            layers.view = unsafe { device.create_image_view(&view_ci, None)? };
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(layers),
+            Err(e) => {
+                layers.destroy(device, allocator);
+                Err(e)
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        if let Some(alloc) = self.alloc.take() {
+            let _ = allocator.free(alloc);
+        }
+        *self = Self::none();
+    }
+}
+
+pub(crate) struct Stereo {
+    eyes: [Camera; 2],
+    /// One eye's size: half the scene's width, all of its height.
+    extent: vk::Extent2D,
+    /// What the composite writes: the scene colour format it was built
+    /// for.
+    composite_format: vk::Format,
+    color: Layers,
+    depth: Layers,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    /// The scene's camera set layout, with binding 0 covering both eyes'
+    /// blocks; None until allocated.
+    camera_set: Option<PooledSet>,
+    /// This frame's eye blocks, and the view_projs in them.
+    camera_offset: u32,
+    view_projs: [Mat4; 2],
+    sampler: vk::Sampler,
+    composite_set_layout: vk::DescriptorSetLayout,
+    composite_set: Option<PooledSet>,
+    composite_layout: vk::PipelineLayout,
+    composite_pipeline: vk::Pipeline,
+}
+
+impl Stereo {
+    /// Caller must have idled the device (or retired every use).
+    fn destroy(
+        mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        for set in [self.camera_set, self.composite_set].into_iter().flatten() {
+            descriptors.free(device, set);
+        }
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_pipeline(self.composite_pipeline, None);
+            device.destroy_pipeline_layout(self.composite_layout, None);
+            device.destroy_descriptor_set_layout(self.composite_set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        self.color.destroy(device, allocator);
+        self.depth.destroy(device, allocator);
+    }
+}
+
+impl RetiredPass for Stereo {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device, allocator, descriptors);
+    }
+}
+
+impl VkRenderer {
+    /// Render the scene for two eyes (see module docs), or go back to one
+    /// view with None. Changing the eyes while on is cheap (per-frame head
+    /// tracking); turning it on builds the target and pipelines and fails
+    /// without multiview support or stereo.vert / stereo_composite.frag
+    /// built. See stereo_eyes for a pair from one camera.
+    pub fn set_stereo(&mut self, eyes: Option<[Camera; 2]>) -> RenderResult<()> {
+        let Some(eyes) = eyes else {
+            if let Some(old) = self.stereo.take() {
+                self.retire_pass(old);
+            }
+            return Ok(());
+        };
+        if let Some(stereo) = self.stereo.as_mut() {
+            stereo.eyes = eyes;
+            return Ok(());
+        }
+        if !self.has_multiview {
+            return Err(RenderError::unsupported(
+                "stereo: the device has no multiview",
+            ));
+        }
+        self.stereo = Some(self.build_stereo(eyes)?);
+        // Only the new set: frames in flight have the main one bound.
+        self.write_shadow_descriptors(self.stereo_camera_set());
+        self.write_environment_descriptors(self.stereo_camera_set());
+        Ok(())
+    }
+
+    pub fn stereo(&self) -> Option<[Camera; 2]> {
+        self.stereo.as_ref().map(|s| s.eyes)
+    }
+
+    /// The target, pipelines and sets for `eyes` at the current scene
+    /// extent and colour format.
+    fn build_stereo(&mut self, eyes: [Camera; 2]) -> Result<Stereo> {
+        let scene = self.scene_extent();
+        let extent = vk::Extent2D {
+            width: (scene.width / 2).max(1),
+            height: scene.height,
+        };
+        let cfg = PipelineConfig {
+            color_format: HDR_TARGET_FORMAT,
+            ..self.pipeline_config()
+        };
+        let composite_format = self.scene_color_format();
+        let camera_set_layout = self.desc_set_layout_camera;
+        let cache = self.pipeline_cache;
+        let belt = self.staging_belt.buffer();
+        let device = &self.device;
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        let descriptors = &mut self.descriptors.persistent;
+        let mut stereo = Stereo {
+            eyes,
+            extent,
+            composite_format,
+            color: Layers::none(),
+            depth: Layers::none(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            camera_set: None,
+            camera_offset: 0,
+            view_projs: [Mat4::IDENTITY; 2],
+            sampler: vk::Sampler::null(),
+            composite_set_layout: vk::DescriptorSetLayout::null(),
+            composite_set: None,
+            composite_layout: vk::PipelineLayout::null(),
+            composite_pipeline: vk::Pipeline::null(),
+        };
+        // From here on destroy() can clean up whatever got created.
+        let result = (|| -> Result<()> {
+            (stereo.layout, stereo.pipeline) = create_stereo_pipeline(device, cache, &cfg)?;
+
+            let binding = vk::DescriptorSetLayoutBinding {
+                binding: 0,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                ..Default::default()
+            };
+            let set_layout_ci = vk::DescriptorSetLayoutCreateInfo {
+                s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+                binding_count: 1,
+                p_bindings: &binding,
+                ..Default::default()
+            };
+            stereo.composite_set_layout =
+                unsafe { device.create_descriptor_set_layout(&set_layout_ci, None)? };
+            let push_range = vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<CompositePush>() as u32,
+            };
+            let layout_ci = vk::PipelineLayoutCreateInfo {
+                s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+                set_layout_count: 1,
+                p_set_layouts: &stereo.composite_set_layout,
+                push_constant_range_count: 1,
+                p_push_constant_ranges: &push_range,
+                ..Default::default()
+            };
+            stereo.composite_layout = unsafe { device.create_pipeline_layout(&layout_ci, None)? };
+            stereo.composite_pipeline = create_fullscreen_pipeline(
+                device,
+                cache,
+                stereo.composite_layout,
+                ("fullscreen.vert.spv", "stereo_composite.frag.spv"),
+                composite_format,
+                cfg.depth_format,
+            )?;
+
+            stereo.color = Layers::new(
+                device,
+                allocator,
+                extent,
+                HDR_TARGET_FORMAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                vk::ImageAspectFlags::COLOR,
+                "stereo target",
+            )?;
+            stereo.depth = Layers::new(
+                device,
+                allocator,
+                extent,
+                cfg.depth_format,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                depth_aspect_mask(cfg.depth_format),
+                "stereo target depth",
+            )?;
+            // Sampled 1:1.
+            let sampler_ci = vk::SamplerCreateInfo {
+                s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+                mag_filter: vk::Filter::NEAREST,
+                min_filter: vk::Filter::NEAREST,
+                mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                ..Default::default()
+            };
+            stereo.sampler = unsafe { device.create_sampler(&sampler_ci, None)? };
+
+            // Bindings 1 (the shadow map) and 4 to 6 (IBL) are written by
+            // write_shadow_descriptors and write_environment_descriptors,
+            // like the scene's set.
+            let camera_set = *stereo
+                .camera_set
+                .insert(descriptors.allocate(device, camera_set_layout)?);
+            let composite_set = *stereo
+                .composite_set
+                .insert(descriptors.allocate(device, stereo.composite_set_layout)?);
+            let eyes_info = vk::DescriptorBufferInfo {
+                buffer: belt,
+                offset: 0,
+                range: std::mem::size_of::<[CameraUbo; 2]>() as u64,
+            };
+            let lights_info = vk::DescriptorBufferInfo {
+                buffer: belt,
+                offset: 0,
+                range: std::mem::size_of::<LightList>() as u64,
+            };
+            let image_info = vk::DescriptorImageInfo {
+                sampler: stereo.sampler,
+                image_view: stereo.color.view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            };
+            let buffer_write = |binding, descriptor_type, info: &vk::DescriptorBufferInfo| {
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: camera_set.set,
+                    dst_binding: binding,
+                    descriptor_count: 1,
+                    descriptor_type,
+                    p_buffer_info: info,
+                    ..Default::default()
+                }
+            };
+            // Binding 3 (the light clusters) stays unwritten: the stereo
+            // camera blocks never turn clustered lighting on.
+            let writes = [
+                buffer_write(0, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, &eyes_info),
+                buffer_write(2, vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, &lights_info),
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: composite_set.set,
+                    dst_binding: 0,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    p_image_info: &image_info,
+                    ..Default::default()
+                },
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(stereo),
+            Err(e) => {
+                stereo.destroy(device, allocator, descriptors);
+                Err(e)
+            }
+        }
+    }
+
+    /// Drop the stereo target; the device must be idle.
+    pub(crate) fn destroy_stereo(&mut self) {
+        if let Some(stereo) = self.stereo.take() {
+            stereo.destroy(
+                &self.device,
+                self.allocator.as_mut().expect("allocator missing"),
+                &mut self.descriptors.persistent,
+            );
+        }
+    }
+
+    /// The stereo camera set, for write_shadow_descriptors and
+    /// write_environment_descriptors.
+    pub(crate) fn stereo_camera_set(&self) -> Option<vk::DescriptorSet> {
+        self.stereo.as_ref()?.camera_set.map(|s| s.set)
+    }
+
+    /// The camera set and the stereo one, if any: every set those two
+    /// write.
+    pub(crate) fn camera_sets(&self) -> impl Iterator<Item = vk::DescriptorSet> {
+        std::iter::once(self.camera_set.set).chain(self.stereo_camera_set())
+    }
+
+    /// Point the camera sets at the shadow map and environment maps as
+    /// they are now. Frames in flight may still have the sets bound, so
+    /// rather than rewriting them this allocates a fresh scene set and
+    /// rebuilds stereo (which owns its own), retiring the old ones through
+    /// the trash queue.
+    pub(crate) fn renew_camera_sets(&mut self) -> Result<()> {
+        let set = create_scene_camera_set(
+            &self.device,
+            &mut self.descriptors.persistent,
+            self.desc_set_layout_camera,
+            self.staging_belt.buffer(),
+            self.light_clusters.as_ref().map(LightClusters::buffer_info),
+        )?;
+        let old = std::mem::replace(&mut self.camera_set, set);
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value,
+            resource: GpuResource::DescriptorSet(old),
+        });
+        if let Some(old) = self.stereo.take() {
+            let eyes = old.eyes;
+            self.retire_pass(old);
+            match self.build_stereo(eyes) {
+                Ok(stereo) => self.stereo = Some(stereo),
+                Err(e) => tracing::warn!("stereo turned off: target not rebuilt ({e:#})"),
+            }
+        }
+        self.write_shadow_descriptors(self.camera_sets());
+        self.write_environment_descriptors(self.camera_sets());
+        Ok(())
+    }
+
+    /// Rebuild the stereo target for the scene's new extent or colour
+    /// format, retiring the old one through the trash queue. Called from
+    /// recreate_swapchain (after sync_tonemap_pass); on failure stereo is
+    /// turned off.
+    pub(crate) fn sync_stereo(&mut self) {
+        let Some(stereo) = self.stereo.as_ref() else {
+            return;
+        };
+        let scene = self.scene_extent();
+        if stereo.extent.width == (scene.width / 2).max(1)
+            && stereo.extent.height == scene.height
+            && stereo.composite_format == self.scene_color_format()
+        {
+            return;
+        }
+        let eyes = stereo.eyes;
+        if let Some(old) = self.stereo.take() {
+            self.retire_pass(old);
+        }
+        match self.build_stereo(eyes) {
+            Ok(stereo) => {
+                self.stereo = Some(stereo);
+                self.write_shadow_descriptors(self.stereo_camera_set());
+                self.write_environment_descriptors(self.stereo_camera_set());
+            }
+            Err(e) => tracing::warn!("stereo turned off: target not rebuilt ({e:#})"),
+        }
+    }
+
+    /// Both eyes' CameraUbo, back to back: `main_ubo` (the scene's) with
+    /// the view swapped for the eye's.
+    pub(crate) fn write_stereo_uniforms(
+        &mut self,
+        main: &Camera,
+        main_ubo: &CameraUbo,
+    ) -> Result<()> {
+        let Some((eyes, extent)) = self.stereo.as_ref().map(|s| (s.eyes, s.extent)) else {
+            return Ok(());
+        };
+        let view_projs = eyes.map(|eye| target_view_proj(main, &eye, extent));
+        let ubos: [CameraUbo; 2] = std::array::from_fn(|i| CameraUbo {
+            view_proj: view_projs[i].to_cols_array_2d(),
+            view_forward: eyes[i].forward().extend(0.0).to_array(),
+            ..*main_ubo
+        });
+        let offset = self.upload_uniform(&ubos)?;
+        if let Some(stereo) = self.stereo.as_mut() {
+            stereo.camera_offset = offset;
+            stereo.view_projs = view_projs;
+        }
+        Ok(())
+    }
+
+    /// The eyes' view_projs while the stereo passes run this frame.
+    pub(crate) fn stereo_view_projs(&self) -> Option<[Mat4; 2]> {
+        let stereo = self.stereo.as_ref()?;
+        (pre_rotation(self.pre_transform) == Mat4::IDENTITY).then_some(stereo.view_projs)
+    }
+
+    /// The "stereo scene" and "stereo composite" passes, in place of the
+    /// scene pass while stereo_view_projs is Some: `scene_target` and
+    /// `depth` are the scene's, `shared` what it reads besides them.
+    pub(crate) fn add_stereo_passes(
+        &self,
+        g: &mut FrameGraph,
+        frame: usize,
+        scene_target: ResourceId,
+        depth: ResourceId,
+        shared: &[(ResourceId, Access)],
+    ) {
+        let Some(stereo) = self.stereo.as_ref() else {
+            return;
+        };
+        let depth_layout = depth_attachment_layout(self.depth_format);
+        // Last read by the previous frame's composite.
+        let color = g.import_image(
+            "stereo target",
+            stereo.color.image,
+            stereo.color.view,
+            vk::ImageAspectFlags::COLOR,
+            Access::sampled_fragment(),
+        );
+        let eye_depth = g.import_image(
+            "stereo depth",
+            stereo.depth.image,
+            stereo.depth.view,
+            depth_aspect_mask(self.depth_format),
+            Access::depth_attachment(LoadOp::DontCare, false, depth_layout),
+        );
+        let depth_clear = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 0.0,
+                stencil: 0,
+            },
+        };
+        let mut uses = vec![
+            (color, Access::color_attachment(LoadOp::Clear(self.clear))),
+            (
+                eye_depth,
+                Access::depth_attachment(LoadOp::Clear(depth_clear), false, depth_layout),
+            ),
+        ];
+        uses.extend_from_slice(shared);
+        g.add_multiview_pass(
+            "stereo scene",
+            stereo.extent,
+            STEREO_VIEW_MASK,
+            &uses,
+            move |r, cmd| {
+                r.record_stereo_scene(cmd, frame);
+                Ok(())
+            },
+        );
+        // Depth is only bound for the pipeline's sake (see
+        // create_fullscreen_pipeline).
+        g.add_pass_at(
+            "stereo composite",
+            self.scene_extent(),
+            &[
+                (scene_target, Access::color_attachment(LoadOp::DontCare)),
+                (
+                    depth,
+                    Access::depth_attachment(LoadOp::DontCare, false, depth_layout),
+                ),
+                (color, Access::sampled_fragment()),
+            ],
+            |r, cmd| {
+                r.record_stereo_composite(cmd);
+                Ok(())
+            },
+        );
+    }
+
+    /// The default pipeline's indirect draws, as record_indirect_draws,
+    /// through both eyes at once.
+    fn record_stereo_scene(&self, cmd: vk::CommandBuffer, frame: usize) {
+        let Some(stereo) = self.stereo.as_ref() else {
+            return;
+        };
+        let Some(camera_set) = stereo.camera_set else {
+            return;
+        };
+        let extent = stereo.extent;
+        // Flipped like the scene's viewport.
+        let vp = vk::Viewport {
+            x: 0.0,
+            y: extent.height as f32,
+            width: extent.width as f32,
+            height: -(extent.height as f32),
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let sc = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let sets = [
+            camera_set.set,
+            self.material_desc_set,
+            self.indirect_graphics_desc_sets[frame],
+        ];
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, stereo.pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&sc));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                stereo.layout,
+                0,
+                &sets,
+                &self.frame_uniforms.with_camera(stereo.camera_offset),
+            );
+            self.device.cmd_bind_vertex_buffers(
+                cmd,
+                0,
+                std::slice::from_ref(&self.shared_vbuf),
+                &[0],
+            );
+            self.device
+                .cmd_bind_index_buffer(cmd, self.shared_ibuf, 0, vk::IndexType::UINT32);
+            self.device.cmd_draw_indexed_indirect_count(
+                cmd,
+                self.indirect_bufs[frame],
+                0,
+                self.draw_count_bufs[frame],
+                0,
+                MAX_INDIRECT_DRAWS,
+                std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+            );
+        }
+    }
+
+    /// Each eye's layer into its half of the scene target.
+    fn record_stereo_composite(&self, cmd: vk::CommandBuffer) {
+        let Some(stereo) = self.stereo.as_ref() else {
+            return;
+        };
+        let Some(set) = stereo.composite_set else {
+            return;
+        };
+        let extent = stereo.extent;
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                stereo.composite_pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                stereo.composite_layout,
+                0,
+                std::slice::from_ref(&set.set),
+                &[],
+            );
+        }
+        for layer in 0..2 {
+            let x = layer * extent.width;
+            // Not flipped: fullscreen.vert's UVs already run top-down.
+            let vp = vk::Viewport {
+                x: x as f32,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            let sc = vk::Rect2D {
+                offset: vk::Offset2D { x: x as i32, y: 0 },
+                extent,
+            };
+            unsafe {
+                self.device
+                    .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+                self.device
+                    .cmd_set_scissor(cmd, 0, std::slice::from_ref(&sc));
+                self.device.cmd_push_constants(
+                    cmd,
+                    stereo.composite_layout,
+                    vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&CompositePush { layer }),
+                );
+                self.device.cmd_draw(cmd, 3, 1, 0, 0);
+            }
+        }
+    }
+}
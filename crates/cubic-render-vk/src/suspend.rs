@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Renderer::suspend/resume: giving the surface back while the platform
+//! has taken the window away (Android's pause, a Wayland session change),
+//! and rebuilding on the same raw handles afterwards.
+//!
+//! Suspend releases only what's tied to the surface: the swapchain, its
+//! image views and per-image sync, then the VkSurfaceKHR itself. The
+//! device, pipelines, uploaded meshes/textures and offscreen targets all
+//! stay, so resume is a surface plus a recreate_swapchain, not a rebuild.
+//! While suspended `surface` and `swapchain` are null; recreate_swapchain
+//! and anything else that would touch them checks for that.
+
+use anyhow::{Context, Result};
+use ash::vk;
+use ash::Entry;
+use tracing::info;
+
+use crate::VkRenderer;
+
+impl VkRenderer {
+    pub(crate) fn suspend_surface(&mut self) {
+        if self.suspended.is_some() {
+            return;
+        }
+        let extent = self.extent;
+        self.suspended = Some(
+            self.pending_resize
+                .take()
+                .unwrap_or(cubic_render::RenderSize {
+                    width: extent.width,
+                    height: extent.height,
+                }),
+        );
+        self.paused = true;
+
+        // Same order as recreate_swapchain's teardown: GPU idle, then the
+        // presentation engine done with the images, then views and
+        // per-image sync before the swapchain they belong to.
+        unsafe { self.device.device_wait_idle().ok() };
+        self.wait_present_fences();
+        // Swapchains retired by a recreation belong to the surface too.
+        self.destroy_retired(u64::MAX);
+        for iv in self.image_views.drain(..) {
+            unsafe { self.device.destroy_image_view(iv, None) };
+        }
+        for f in self.frames.drain(..) {
+            f.destroy(&self.device);
+        }
+        self.images.clear();
+        unsafe {
+            self.swapchain_loader
+                .destroy_swapchain(self.swapchain, None);
+            self.surface_loader.destroy_surface(self.surface, None);
+        }
+        self.swapchain = vk::SwapchainKHR::null();
+        self.surface = vk::SurfaceKHR::null();
+        info!("vk: suspended, surface released");
+    }
+
+    pub(crate) fn resume_surface(&mut self) -> Result<()> {
+        let Some(size) = self.suspended else {
+            return Ok(());
+        };
+        let entry = Entry::linked();
+        self.surface = unsafe {
+            ash_window::create_surface(
+                &entry,
+                &self.instance,
+                self.display_raw,
+                self.window_raw,
+                None,
+            )
+        }
+        .context("resume: ash_window::create_surface")?;
+        self.suspended = None;
+        if size.width == 0 || size.height == 0 {
+            // Stays paused until a real size arrives through resize().
+            info!("vk: resumed at zero size, waiting for a resize");
+            return Ok(());
+        }
+        self.paused = false;
+        self.recreate_swapchain(size)?;
+        info!("vk: resumed at {}x{}", size.width, size.height);
+        Ok(())
+    }
+}
@@ -1,21 +1,72 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use ash::khr::{surface, swapchain};
 use ash::vk;
-use cubic_render::RenderSize;
+use ash::Entry;
+use cubic_math::Mat4;
+use cubic_render::{RenderError, RenderSize, Renderer, SurfaceChanged};
 
-use crate::pipeline::{create_pipeline, PipelineConfig};
-use crate::resources::{
-    create_depth_resources, create_frame_uniforms_and_sets, create_indirect_draw_resources,
-};
+use crate::device::QueueFamilies;
+use crate::error::classify;
+use crate::hdr_metadata::HdrMetadata;
+use crate::instance::recreate_surface;
+use crate::resources::create_depth_resources;
 use crate::sync::FrameSync;
+use crate::tonemap::is_hdr_color_space;
 use crate::{DeferredDrop, GpuResource, VkRenderer};
 
-#[derive(Clone, Copy, Debug)]
+/// How often a window being dragged to a new size gets a new swapchain.
+/// Sizes arriving faster are skipped, the frames between them presenting
+/// the old swapchain for the compositor to scale or crop.
+const LIVE_RESIZE_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum VkVsyncMode {
-    Fifo,    // Target monitor refresh rate
+    Fifo, // Target monitor refresh rate
+    #[default]
     Mailbox, // Smart Vsync, fps uncapped
+    FifoRelaxed, // Adaptive: FIFO, but a late frame tears instead of waiting
+    Immediate, // No vsync at all, tears
+}
+
+impl VkVsyncMode {
+    fn present_mode(self) -> vk::PresentModeKHR {
+        match self {
+            VkVsyncMode::Fifo => vk::PresentModeKHR::FIFO,
+            VkVsyncMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            VkVsyncMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+            VkVsyncMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+        }
+    }
+}
+
+/// A user-ordered present-mode preference (set_present_mode_priority),
+/// each mode at most once. Fixed-size so SwapchainConfig stays Copy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PresentPriority {
+    modes: [VkVsyncMode; 4],
+    len: usize,
+}
+
+impl PresentPriority {
+    /// `modes` in order, later repeats dropped.
+    pub(crate) fn new(modes: &[VkVsyncMode]) -> Self {
+        let mut out = Self::default();
+        for &m in modes {
+            if !out.as_slice().contains(&m) {
+                out.modes[out.len] = m;
+                out.len += 1;
+            }
+        }
+        out
+    }
+
+    pub(crate) fn as_slice(&self) -> &[VkVsyncMode] {
+        &self.modes[..self.len]
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -24,14 +75,42 @@ pub enum HdrFlavor {
     PreferHdr10, // HDR10 first, then scRGB
 }
 
+/// What recheck_display_hdr did after the window changed display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HdrDisplayChange {
+    /// The surface stopped offering an HDR format (an SDR display): the
+    /// swapchain was recreated in SDR.
+    FellBackToSdr,
+    /// HDR formats are on offer again: the swapchain is HDR again.
+    Restored,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct SwapchainConfig {
     pub(crate) hint: RenderSize,
     pub(crate) vsync: bool,
     pub(crate) vsync_mode: VkVsyncMode,
+    /// Tried in order before `vsync_mode`'s own fallbacks when vsync is on;
+    /// empty leaves it to `vsync_mode`.
+    pub(crate) present_priority: PresentPriority,
     pub(crate) want_hdr: bool,
     pub(crate) allow_extended_colorspace: bool,
     pub(crate) hdr_flavor: HdrFlavor,
+    /// Image usages wanted on top of COLOR_ATTACHMENT (which is always
+    /// requested). Treated as a wish list: create_swapchain_bundle drops
+    /// whatever the surface or the picked format can't do, and reports what
+    /// was actually granted in `SwapchainBundle::usage`.
+    pub(crate) extra_usage: vk::ImageUsageFlags,
+    /// VK_EXT_full_screen_exclusive's mode for the swapchain, or None
+    /// without the extension (nothing chained; the driver decides).
+    pub(crate) full_screen_exclusive: Option<vk::FullScreenExclusiveEXT>,
+    /// VK_EXT_swapchain_maintenance1 is enabled: the swapchain is created
+    /// able to switch between compatible present modes in place, and
+    /// presents carry fences (FrameSync::present_fence).
+    pub(crate) swapchain_maintenance1: bool,
+    /// Graphics and present families; images are CONCURRENT between them
+    /// when they differ, so neither side needs ownership transfers.
+    pub(crate) queue_families: QueueFamilies,
 }
 
 pub(crate) struct SwapchainBundle {
@@ -41,10 +120,17 @@ pub(crate) struct SwapchainBundle {
     pub(crate) images: Vec<vk::Image>,
     pub(crate) image_views: Vec<vk::ImageView>,
     pub(crate) color_space: vk::ColorSpaceKHR,
+    pub(crate) present_mode: vk::PresentModeKHR,
+    pub(crate) usage: vk::ImageUsageFlags,
+    pub(crate) pre_transform: vk::SurfaceTransformFlagsKHR,
+    /// Present modes the swapchain can switch to without recreation,
+    /// `present_mode` included; just that one without
+    /// VK_EXT_swapchain_maintenance1.
+    pub(crate) present_modes: Vec<vk::PresentModeKHR>,
 }
 
 #[inline]
-fn fmt_name(f: ash::vk::Format) -> &'static str {
+pub(crate) fn fmt_name(f: ash::vk::Format) -> &'static str {
     match f {
         ash::vk::Format::B8G8R8A8_UNORM => "B8G8R8A8_UNORM",
         ash::vk::Format::B8G8R8A8_SRGB => "B8G8R8A8_SRGB",
@@ -58,7 +144,7 @@ fn fmt_name(f: ash::vk::Format) -> &'static str {
 }
 
 #[inline]
-fn cs_name(cs: ash::vk::ColorSpaceKHR) -> &'static str {
+pub(crate) fn cs_name(cs: ash::vk::ColorSpaceKHR) -> &'static str {
     match cs {
         ash::vk::ColorSpaceKHR::SRGB_NONLINEAR => "SRGB_NONLINEAR",
         ash::vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT => "DISPLAY_P3_NONLINEAR",
@@ -70,7 +156,7 @@ fn cs_name(cs: ash::vk::ColorSpaceKHR) -> &'static str {
 }
 
 #[inline]
-fn pm_name(m: ash::vk::PresentModeKHR) -> &'static str {
+pub(crate) fn pm_name(m: ash::vk::PresentModeKHR) -> &'static str {
     match m {
         ash::vk::PresentModeKHR::FIFO => "FIFO",
         ash::vk::PresentModeKHR::MAILBOX => "MAILBOX",
@@ -85,32 +171,55 @@ fn choose_present_mode(
     modes: &[vk::PresentModeKHR],
     vsync: bool,
     mode: VkVsyncMode,
+    priority: PresentPriority,
 ) -> vk::PresentModeKHR {
+    let immediate = [
+        vk::PresentModeKHR::IMMEDIATE,
+        vk::PresentModeKHR::MAILBOX,
+        vk::PresentModeKHR::FIFO,
+    ];
     if !vsync {
-        return [
-            vk::PresentModeKHR::IMMEDIATE,
-            vk::PresentModeKHR::MAILBOX,
-            vk::PresentModeKHR::FIFO,
-        ]
-        .into_iter()
-        .find(|m| modes.contains(m))
-        .unwrap_or(vk::PresentModeKHR::FIFO);
-    }
-
-    match mode {
-        VkVsyncMode::Mailbox => [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO]
+        return immediate
             .into_iter()
             .find(|m| modes.contains(m))
-            .unwrap_or(vk::PresentModeKHR::FIFO),
-        VkVsyncMode::Fifo => [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX]
-            .into_iter()
-            .find(|m| modes.contains(m))
-            .unwrap_or(vk::PresentModeKHR::FIFO),
+            .unwrap_or(vk::PresentModeKHR::FIFO);
     }
+
+    let fallbacks: &[vk::PresentModeKHR] = match mode {
+        VkVsyncMode::Mailbox => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+        VkVsyncMode::Fifo => &[vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX],
+        VkVsyncMode::FifoRelaxed => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+        VkVsyncMode::Immediate => &immediate,
+    };
+
+    // FIFO is the one mode every surface must support, so it ends the list
+    // whatever the user put in it.
+    priority
+        .as_slice()
+        .iter()
+        .map(|m| m.present_mode())
+        .chain(fallbacks.iter().copied())
+        .find(|m| modes.contains(m))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
 }
 
 #[inline]
-fn extent_from_caps(caps: &vk::SurfaceCapabilitiesKHR, want: RenderSize) -> vk::Extent2D {
+/// `want` is the window's size; with a quarter-turn `pre_transform` the
+/// swapchain is that on its side. A fixed current_extent is already in the
+/// display's natural orientation.
+fn extent_from_caps(
+    caps: &vk::SurfaceCapabilitiesKHR,
+    want: RenderSize,
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+) -> vk::Extent2D {
+    let want = if transform_swaps_axes(pre_transform) {
+        RenderSize {
+            width: want.height,
+            height: want.width,
+        }
+    } else {
+        want
+    };
     if caps.current_extent.width != u32::MAX {
         caps.current_extent
     } else {
@@ -125,6 +234,77 @@ fn extent_from_caps(caps: &vk::SurfaceCapabilitiesKHR, want: RenderSize) -> vk::
     }
 }
 
+/// Prefer IDENTITY where the surface allows it: the compositor then turns
+/// the image for a rotated display (at the cost of a blit there).
+/// Otherwise (some Wayland compositors, embedded and mobile panels) the
+/// swapchain takes the display's current transform and the renderer applies
+/// that rotation itself: pre_rotation in the camera's projection, and
+/// pre_rotate_pixel for the text overlay.
+fn choose_pre_transform(caps: &vk::SurfaceCapabilitiesKHR) -> vk::SurfaceTransformFlagsKHR {
+    if caps
+        .supported_transforms
+        .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+    {
+        return vk::SurfaceTransformFlagsKHR::IDENTITY;
+    }
+    use vk::SurfaceTransformFlagsKHR as T;
+    let t = caps.current_transform;
+    let mirrored = T::HORIZONTAL_MIRROR
+        | T::HORIZONTAL_MIRROR_ROTATE_90
+        | T::HORIZONTAL_MIRROR_ROTATE_180
+        | T::HORIZONTAL_MIRROR_ROTATE_270;
+    if mirrored.contains(t) {
+        tracing::warn!("surface transform {t:?} is mirrored; only its rotation is compensated");
+    }
+    t
+}
+
+/// Clockwise quarter turns in a surface transform (a mirrored one's
+/// rotation part).
+fn rotation_of(t: vk::SurfaceTransformFlagsKHR) -> u32 {
+    use vk::SurfaceTransformFlagsKHR as T;
+    match t {
+        T::ROTATE_90 | T::HORIZONTAL_MIRROR_ROTATE_90 => 1,
+        T::ROTATE_180 | T::HORIZONTAL_MIRROR_ROTATE_180 => 2,
+        T::ROTATE_270 | T::HORIZONTAL_MIRROR_ROTATE_270 => 3,
+        _ => 0,
+    }
+}
+
+/// Whether `t` turns the image a quarter turn, making the swapchain's
+/// width the window's height and vice versa.
+pub(crate) fn transform_swaps_axes(t: vk::SurfaceTransformFlagsKHR) -> bool {
+    rotation_of(t) % 2 == 1
+}
+
+/// Clip-space rotation applying `t` to the scene: multiplied in front of
+/// the camera's projection (built for the window's aspect, not the
+/// swapchain's). The transforms turn clockwise; under the scene's flipped
+/// (+Y up) viewport that's a negative angle about Z.
+pub(crate) fn pre_rotation(t: vk::SurfaceTransformFlagsKHR) -> Mat4 {
+    match rotation_of(t) {
+        0 => Mat4::IDENTITY,
+        turns => Mat4::from_rotation_z(-(turns as f32) * std::f32::consts::FRAC_PI_2),
+    }
+}
+
+/// pre_rotation for +Y-down pixel positions: `p` in a `window`-sized
+/// image, turned into the swapchain image.
+pub(crate) fn pre_rotate_pixel(
+    t: vk::SurfaceTransformFlagsKHR,
+    window: vk::Extent2D,
+    p: [f32; 2],
+) -> [f32; 2] {
+    let (w, h) = (window.width as f32, window.height as f32);
+    let [x, y] = p;
+    match rotation_of(t) {
+        1 => [h - y, x],
+        2 => [w - x, h - y],
+        3 => [y, w - x],
+        _ => p,
+    }
+}
+
 fn pick_surface_format(
     formats: &[vk::SurfaceFormatKHR],
     want_hdr: bool,
@@ -149,18 +329,22 @@ fn pick_surface_format(
                 .iter()
                 .copied()
                 .find(|f| {
-                    (f.color_space == vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
-                        || f.color_space == vk::ColorSpaceKHR::EXTENDED_SRGB_NONLINEAR_EXT)
+                    f.color_space == vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
                         && f.format == vk::Format::R16G16B16A16_SFLOAT
                 })
                 .map(|f| (f, "scrgb_fp16"))
         };
 
-        return match flavor {
+        // Only the spaces tonemap.rs has a final encode for: anything
+        // else would get the scene as linear BT.709, so no HDR at all
+        // looks better.
+        let hdr = match flavor {
             HdrFlavor::PreferScrgb => try_scrgb().or_else(try_hdr10),
             HdrFlavor::PreferHdr10 => try_hdr10().or_else(try_scrgb),
+        };
+        if let Some(picked) = hdr {
+            return picked;
         }
-        .unwrap_or_else(|| (formats[0], "driver_default_hdr"));
     }
 
     // SDR fallbacks
@@ -219,6 +403,7 @@ pub(crate) fn create_hdr_metadata_if_needed(
     has_hdr_meta: bool,
     color_space: vk::ColorSpaceKHR,
     swapchain: vk::SwapchainKHR,
+    metadata: &HdrMetadata,
 ) {
     // Fast bailouts: no extension, or not an HDR10 PQ surface
     if !has_hdr_meta || color_space != vk::ColorSpaceKHR::HDR10_ST2084_EXT {
@@ -227,29 +412,126 @@ pub(crate) fn create_hdr_metadata_if_needed(
 
     let hdr = ash::ext::hdr_metadata::Device::new(instance, device);
 
-    // Basic BT.2020 primaries + D65 white and typical luminance values.
-    // Adjust later if you want per-display calibration or content-driven values.
-    let metadata = vk::HdrMetadataEXT {
-        s_type: vk::StructureType::HDR_METADATA_EXT,
-        display_primary_red: vk::XYColorEXT { x: 0.708, y: 0.292 },
-        display_primary_green: vk::XYColorEXT { x: 0.170, y: 0.797 },
-        display_primary_blue: vk::XYColorEXT { x: 0.131, y: 0.046 },
-        white_point: vk::XYColorEXT {
-            x: 0.3127,
-            y: 0.3290,
-        },
-        max_luminance: 1000.0,
-        min_luminance: 0.001,
-        max_content_light_level: 1000.0,
-        max_frame_average_light_level: 400.0,
-        ..Default::default()
-    };
+    // Display-derived (EDID), user-calibrated, or the generic BT.2020
+    // 1000-nit fallback; see hdr_metadata.rs for the precedence.
+    let metadata = metadata.to_vk();
 
     // Apply to the current swapchain. Safe to reapply on recreate.
     unsafe { hdr.set_hdr_metadata(&[swapchain], std::slice::from_ref(&metadata)) };
 }
 
+/// Resolve the swapchain image usage: COLOR_ATTACHMENT plus whichever of
+/// `extra` both the surface (supported_usage_flags) and the chosen format
+/// (optimal-tiling features) support. The surface caps alone aren't enough:
+/// STORAGE in particular is commonly advertised by the surface but missing
+/// from the sRGB formats we usually end up picking, and creating the
+/// swapchain with it anyway is a validation error (and a driver crash on
+/// some stacks).
+fn resolve_image_usage(
+    instance: &ash::Instance,
+    phys: vk::PhysicalDevice,
+    caps: &vk::SurfaceCapabilitiesKHR,
+    format: vk::Format,
+    extra: vk::ImageUsageFlags,
+) -> vk::ImageUsageFlags {
+    let feats = unsafe { instance.get_physical_device_format_properties(phys, format) }
+        .optimal_tiling_features;
+    let format_allows = [
+        (
+            vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::FormatFeatureFlags::TRANSFER_SRC,
+        ),
+        (
+            vk::ImageUsageFlags::TRANSFER_DST,
+            vk::FormatFeatureFlags::TRANSFER_DST,
+        ),
+        (
+            vk::ImageUsageFlags::STORAGE,
+            vk::FormatFeatureFlags::STORAGE_IMAGE,
+        ),
+        (
+            vk::ImageUsageFlags::SAMPLED,
+            vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        ),
+    ];
+
+    let mut granted = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+    for (usage, feature) in format_allows {
+        if extra.contains(usage)
+            && caps.supported_usage_flags.contains(usage)
+            && feats.contains(feature)
+        {
+            granted |= usage;
+        }
+    }
+    let dropped = extra & !granted;
+    if !dropped.is_empty() {
+        tracing::warn!(
+            "swapchain usage {:?} not supported for {} on this surface; continuing without it",
+            dropped,
+            fmt_name(format)
+        );
+    }
+    granted
+}
+
+/// The present modes a swapchain created with `mode` can switch to in place
+/// (VK_EXT_surface_maintenance1's compatibility query), `mode` first and
+/// limited to `supported`. Just `mode` if the query fails.
+fn compatible_present_modes(
+    instance: &ash::Instance,
+    phys: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+    mode: vk::PresentModeKHR,
+    supported: &[vk::PresentModeKHR],
+) -> Vec<vk::PresentModeKHR> {
+    let caps2 = ash::khr::get_surface_capabilities2::Instance::new(&ash::Entry::linked(), instance);
+    let mut mode_info = vk::SurfacePresentModeEXT {
+        s_type: vk::StructureType::SURFACE_PRESENT_MODE_EXT,
+        present_mode: mode,
+        ..Default::default()
+    };
+    let surface_info = vk::PhysicalDeviceSurfaceInfo2KHR {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_SURFACE_INFO_2_KHR,
+        p_next: (&mut mode_info) as *mut _ as *const _,
+        surface,
+        ..Default::default()
+    };
+    // Two calls: the count, then the modes.
+    let query = |compat: &mut vk::SurfacePresentModeCompatibilityEXT| {
+        let mut caps = vk::SurfaceCapabilities2KHR {
+            s_type: vk::StructureType::SURFACE_CAPABILITIES_2_KHR,
+            p_next: compat as *mut _ as *mut _,
+            ..Default::default()
+        };
+        unsafe { caps2.get_physical_device_surface_capabilities2(phys, &surface_info, &mut caps) }
+    };
+    let mut compat = vk::SurfacePresentModeCompatibilityEXT {
+        s_type: vk::StructureType::SURFACE_PRESENT_MODE_COMPATIBILITY_EXT,
+        ..Default::default()
+    };
+    if query(&mut compat).is_err() {
+        return vec![mode];
+    }
+    let mut modes = vec![vk::PresentModeKHR::default(); compat.present_mode_count as usize];
+    compat.p_present_modes = modes.as_mut_ptr();
+    if query(&mut compat).is_err() {
+        return vec![mode];
+    }
+    modes.truncate(compat.present_mode_count as usize);
+
+    let mut out = vec![mode];
+    out.extend(
+        modes
+            .into_iter()
+            .filter(|m| *m != mode && supported.contains(m)),
+    );
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create_swapchain_bundle(
+    instance: &ash::Instance,
     device: &ash::Device,
     surf_i: &surface::Instance,
     swap_d: &swapchain::Device,
@@ -281,9 +563,10 @@ pub(crate) fn create_swapchain_bundle(
         cfg.hdr_flavor,
     );
     // Prefer MAILBOX if vsync==true && mode==Mailbox (& available), else FIFO fallback
-    let present_mode = choose_present_mode(&modes, cfg.vsync, cfg.vsync_mode);
+    let present_mode = choose_present_mode(&modes, cfg.vsync, cfg.vsync_mode, cfg.present_priority);
+    let pre_transform = choose_pre_transform(&caps);
     // Resolve desired extent respecting min/max if current_extent is UINT_MAX (free-size)
-    let extent = extent_from_caps(&caps, cfg.hint);
+    let extent = extent_from_caps(&caps, cfg.hint, pre_transform);
 
     tracing::info!(
         "reason: {}, format: {} / {}, present_mode: {}, vsync={}, mode={:?}, extent: {}x{}, images(min={} → picked={})",
@@ -299,8 +582,17 @@ pub(crate) fn create_swapchain_bundle(
         else { (caps.min_image_count + 1).min(caps.max_image_count) }
     );
 
+    // Modes this swapchain will be able to switch to in place later
+    // (VkRenderer::apply_present_mode).
+    let present_modes = if cfg.swapchain_maintenance1 {
+        compatible_present_modes(instance, phys, surface, present_mode, &modes)
+    } else {
+        vec![present_mode]
+    };
+
     // --- Decide image count ---
-    let want_images = if present_mode == vk::PresentModeKHR::MAILBOX {
+    // (MAILBOX wants a third image, including when it's only switched to.)
+    let want_images = if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
         (caps.min_image_count + 1).max(3)
     } else {
         caps.min_image_count + 1
@@ -311,17 +603,6 @@ pub(crate) fn create_swapchain_bundle(
         want_images.min(caps.max_image_count)
     };
 
-    // --- Surface transform ---
-    // Prefer IDENTITY if supported (common), otherwise use current to avoid extra blits.
-    let pre_transform = if caps
-        .supported_transforms
-        .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
-    {
-        vk::SurfaceTransformFlagsKHR::IDENTITY
-    } else {
-        caps.current_transform
-    };
-
     // PIck supported alpha flag
     let composite_alpha = [
         vk::CompositeAlphaFlagsKHR::OPAQUE,
@@ -334,19 +615,64 @@ pub(crate) fn create_swapchain_bundle(
     .find(|f| caps.supported_composite_alpha.contains(*f))
     .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE);
 
+    // --- Image usage ---
+    // COLOR_ATTACHMENT for the scene/egui passes, plus whatever extra usage
+    // the config asks for (blits, readback, compute post) and the surface +
+    // format can actually provide.
+    let usage = resolve_image_usage(instance, phys, &caps, surf_format.format, cfg.extra_usage);
+
     // --- Swapchain create info ---
-    // IMPORTANT: image_usage must match how you use the images; here we only render to them.
-    // If you later add post-processing blits/reads, include TRANSFER_DST/SRC as needed.
+    // IMPORTANT: image_usage must match how you use the images; anything
+    // that blits/copies/stores into them must check `usage` first.
+    // ALLOWED lets the driver take the display exclusively once the window
+    // covers it; DISALLOWED keeps a fullscreen window composited (true
+    // borderless). Only the mode is given, so no Win32 monitor info is
+    // needed and no acquire/release calls are made.
+    let mut fse_info = cfg.full_screen_exclusive.map(|full_screen_exclusive| {
+        vk::SurfaceFullScreenExclusiveInfoEXT {
+            s_type: vk::StructureType::SURFACE_FULL_SCREEN_EXCLUSIVE_INFO_EXT,
+            full_screen_exclusive,
+            ..Default::default()
+        }
+    });
+    let fse_ptr = fse_info
+        .as_mut()
+        .map_or(std::ptr::null(), |i| i as *mut _ as *const std::ffi::c_void);
+    // Declaring every switchable mode up front is what lets a present pick
+    // any of them (vk::SwapchainPresentModeInfoEXT, see render()).
+    let mut modes_info = vk::SwapchainPresentModesCreateInfoEXT {
+        s_type: vk::StructureType::SWAPCHAIN_PRESENT_MODES_CREATE_INFO_EXT,
+        p_next: fse_ptr,
+        present_mode_count: present_modes.len() as u32,
+        p_present_modes: present_modes.as_ptr(),
+        ..Default::default()
+    };
+    // A separate present family gets the images CONCURRENT rather than
+    // with ownership transfers: no extra barriers or semaphores per frame,
+    // at what's at worst a small compression cost on the images.
+    let sharing_families = [cfg.queue_families.graphics, cfg.queue_families.present];
+    let (sharing_mode, sharing_count) = if cfg.queue_families.separate_present() {
+        (vk::SharingMode::CONCURRENT, sharing_families.len() as u32)
+    } else {
+        (vk::SharingMode::EXCLUSIVE, 0)
+    };
     let swap_info = vk::SwapchainCreateInfoKHR {
         s_type: vk::StructureType::SWAPCHAIN_CREATE_INFO_KHR,
+        p_next: if cfg.swapchain_maintenance1 {
+            (&mut modes_info) as *mut _ as *const _
+        } else {
+            fse_ptr
+        },
         surface,
         min_image_count: min_count,
         image_format: surf_format.format,
         image_color_space: surf_format.color_space,
         image_extent: extent,
         image_array_layers: 1, // non-stereo
-        image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
-        image_sharing_mode: vk::SharingMode::EXCLUSIVE, // single graphics queue family
+        image_usage: usage,
+        image_sharing_mode: sharing_mode,
+        queue_family_index_count: sharing_count,
+        p_queue_family_indices: sharing_families.as_ptr(),
         pre_transform,
         composite_alpha,
         present_mode,
@@ -375,119 +701,147 @@ pub(crate) fn create_swapchain_bundle(
         images,
         image_views: views,
         color_space: surf_format.color_space,
+        present_mode,
+        usage,
+        pre_transform,
+        present_modes,
     })
 }
 
 impl VkRenderer {
-    // STRICT ORDER (recreate):
-    // 1) Wait all in-flight image fences + acquire fences (no work using old sc)
-    // 2) device_wait_idle() to avoid destroying in-use views/pipelines
-    // 3) Destroy per-image views + per-image sync tied to OLD swapchain
-    // 4) Create NEW swapchain + images + views
-    // 5) Recreate per-image sync objects
-    // 6) Recreate pipeline ONLY if format changed
-    // 7) Resize command buffers if image count changed
-    // (No re-record step here: render() records each frame's command
-    // buffer fresh for whichever image it just acquired.)
-    // Any deviation can cause sporadic DEVICE_LOST or image-in-use errors.
-    pub(crate) fn recreate_swapchain(&mut self, size: RenderSize) -> Result<()> {
-        // Guard min size window
-        if size.width == 0 || size.height == 0 {
-            return Ok(());
+    /// Wait (a bounded while) for every present fence: once they're all
+    /// signaled the presentation engine holds nothing from the current
+    /// swapchain. No-op without VK_EXT_swapchain_maintenance1.
+    pub(crate) fn wait_present_fences(&self) {
+        let fences: Vec<vk::Fence> = self
+            .frames
+            .iter()
+            .map(|f| f.present_fence)
+            .filter(|&f| f != vk::Fence::null())
+            .collect();
+        if fences.is_empty() {
+            return;
         }
-
-        // 1) Wait for GPU to reach the last signaled timeline value (flush all prior work)
-        if self.timeline_value > 0 {
-            let wait_info = vk::SemaphoreWaitInfo {
-                s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
-                flags: vk::SemaphoreWaitFlags::empty(),
-                semaphore_count: 1,
-                p_semaphores: &self.timeline,
-                p_values: &self.timeline_value,
-                ..Default::default()
-            };
-            unsafe { self.device.wait_semaphores(&wait_info, u64::MAX).ok() };
+        // A present the engine rejected may never signal; don't hang on it.
+        let res = unsafe { self.device.wait_for_fences(&fences, true, 1_000_000_000) };
+        if let Err(e) = res {
+            tracing::warn!("vk: present fences not signaled ({e:?}); destroying anyway");
         }
+    }
 
-        // 2) device_wait_idle() to avoid destroying in-use views/pipelines
-        unsafe { self.device.device_wait_idle().ok() };
-
-        // 3) Destroy per-image views + per-image sync tied to OLD swapchain
-        for &iv in &self.image_views {
-            unsafe { self.device.destroy_image_view(iv, None) };
-        }
-        for f in &self.frames {
-            unsafe { self.device.destroy_semaphore(f.render_finished, None) };
-        }
-        self.frames.clear();
-
-        // 3b) Retire per-image UBOs + descriptor pool tied to OLD swapchain.
-        // gpu-allocator persistently maps CpuToGpu allocations, so no
-        // explicit unmap is needed. device_wait_idle() above already makes
-        // these safe to destroy immediately, but route them through the
-        // trash queue anyway for consistency with the rest of the renderer.
-        for (buffer, alloc) in self.ubufs.drain(..).zip(self.umems.drain(..)) {
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::Buffer { buffer, alloc },
-            });
-        }
-        self.ubo_ptrs.clear();
-        self.ubo_size = 0;
-
-        if self.desc_pool != vk::DescriptorPool::null() {
-            unsafe { self.device.destroy_descriptor_pool(self.desc_pool, None) };
-            self.desc_pool = vk::DescriptorPool::null();
+    /// Re-pick the present mode after a vsync setting changed. When the
+    /// live swapchain was created able to switch to the new pick
+    /// (VK_EXT_swapchain_maintenance1), the next present just names it: no
+    /// recreation, no hitch. Otherwise the swapchain is recreated.
+    pub(crate) fn apply_present_mode(&mut self) {
+        if self.cfg.swapchain_maintenance1_ext {
+            let modes = unsafe {
+                self.surface_loader
+                    .get_physical_device_surface_present_modes(self.phys, self.surface)
+            }
+            .unwrap_or_default();
+            let mode = choose_present_mode(
+                &modes,
+                self.cfg.vsync,
+                self.cfg.vsync_mode,
+                self.cfg.present_priority,
+            );
+            if self.present_modes.contains(&mode) {
+                if mode != self.present_mode {
+                    tracing::info!(
+                        "vk: present mode {} -> {} in place",
+                        pm_name(self.present_mode),
+                        pm_name(mode)
+                    );
+                    let previous = self.surface_info();
+                    self.present_mode = mode;
+                    let previous = self.surface_change.map_or(previous, |c| c.previous);
+                    self.surface_change = Some(SurfaceChanged {
+                        previous,
+                        current: self.surface_info(),
+                    });
+                }
+                return;
+            }
         }
-        self.desc_sets.clear();
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
 
-        // 3c) Retire per-image indirect draw buffers.
-        for (buffer, alloc) in self
-            .candidate_bufs
-            .drain(..)
-            .zip(self.candidate_allocs.drain(..))
+    /// Apply Renderer::resize's latest size, if any, before render()
+    /// acquires: every size that arrived since the last frame collapses into
+    /// one recreation, and while a drag keeps sending sizes, into at most
+    /// one per LIVE_RESIZE_INTERVAL, so the content keeps animating instead
+    /// of the frame loop spending itself on sizes already gone. `force` (the
+    /// old swapchain is out of date and can't be presented) skips the
+    /// interval and recreates even with nothing pending. On a lost surface,
+    /// rebuilds it once and retries.
+    pub(crate) fn apply_pending_resize(&mut self, force: bool) -> Result<()> {
+        if !force
+            && (self.pending_resize.is_none() || self.resized_at.elapsed() < LIVE_RESIZE_INTERVAL)
         {
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::Buffer { buffer, alloc },
-            });
-        }
-        self.candidate_ptrs.clear();
-        for (buffer, alloc) in self
-            .indirect_bufs
-            .drain(..)
-            .zip(self.indirect_allocs.drain(..))
-        {
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::Buffer { buffer, alloc },
-            });
+            return Ok(());
         }
-        for (buffer, alloc) in self
-            .draw_count_bufs
-            .drain(..)
-            .zip(self.draw_count_allocs.drain(..))
-        {
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::Buffer { buffer, alloc },
-            });
+        let size = self.pending_resize.take().unwrap_or(RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        });
+        self.resized_at = Instant::now();
+        match self.recreate_swapchain(size).map_err(classify) {
+            Err(RenderError::SurfaceLost) => {
+                let entry = Entry::linked();
+                recreate_surface(
+                    &entry,
+                    &self.instance,
+                    &self.surface_loader,
+                    &mut self.surface,
+                    self.display_raw,
+                    self.window_raw,
+                )?;
+                self.recreate_swapchain(size)
+            }
+            res => Ok(res?),
         }
-        if self.indirect_desc_pool != vk::DescriptorPool::null() {
-            unsafe {
-                self.device
-                    .destroy_descriptor_pool(self.indirect_desc_pool, None)
-            };
-            self.indirect_desc_pool = vk::DescriptorPool::null();
+    }
+
+    // STRICT ORDER (recreate):
+    // 1) Wait for the presentation engine to let go of the old images
+    //    (present fences; the GPU itself isn't waited for)
+    // 2) Create NEW swapchain + images + views
+    // 3) Retire everything tied to the OLD swapchain through the trash
+    //    queue: views, per-image sync, the swapchain itself, depth, the
+    //    passes sized to it
+    // 4) Recreate per-image sync (and the HDR tonemap pass)
+    // 5) Recreate scene pipelines ONLY if the scene color format changed
+    // Command buffers, indirect-draw buffers and their sets belong to the
+    // frame slots, not the images, so they carry over untouched.
+    // (No re-record step here: render() records each frame's command
+    // buffer fresh for whichever image it just acquired.)
+    // Nothing old is destroyed until the timeline says the frames that
+    // used it are done, so recreation never idles the device.
+    // Any deviation can cause sporadic DEVICE_LOST or image-in-use errors.
+    pub(crate) fn recreate_swapchain(&mut self, size: RenderSize) -> Result<()> {
+        // Guard min size window, and suspend (no surface; see suspend.rs)
+        if size.width == 0 || size.height == 0 || self.surface == vk::SurfaceKHR::null() {
+            return Ok(());
         }
-        self.indirect_compute_desc_sets.clear();
-        self.indirect_graphics_desc_sets.clear();
+        let _span =
+            tracing::debug_span!("recreate", width = size.width, height = size.height).entered();
+        let previous = self.surface_info();
 
-        // 4a) cfg for new swapchain (hdr/vsync/flavor/extent)
+        // 1) With present fences, wait for the presentation engine to let
+        // go of the old images and their render-finished semaphores.
+        self.wait_present_fences();
+
+        // 2a) cfg for new swapchain (hdr/vsync/flavor/extent)
         let cfg = self.cfg.to_swapchain_config(size);
 
-        // 4b) create NEW swapchain + images + views
+        // 2b) create NEW swapchain + images + views
         let bundle = create_swapchain_bundle(
+            &self.instance,
             &self.device,
             &self.surface_loader,
             &self.swapchain_loader,
@@ -496,10 +850,6 @@ impl VkRenderer {
             self.swapchain,
             cfg,
         )?;
-        unsafe {
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None)
-        };
         let SwapchainBundle {
             swapchain,
             format,
@@ -507,26 +857,51 @@ impl VkRenderer {
             images,
             image_views,
             color_space,
+            present_mode,
+            usage,
+            pre_transform,
+            present_modes,
         } = bundle;
 
-        // 4c) HDR metadata
+        // 2c) HDR metadata
         create_hdr_metadata_if_needed(
             &self.instance,
             &self.device,
             self.has_hdr_metadata_ext,
             color_space,
             swapchain,
+            &self.hdr_metadata,
         );
 
-        // 4d) Swap in new data
+        // 3a) Retire the OLD swapchain with its views and per-image sync.
+        // The last frame may still be drawing into one of its images, and
+        // its present still waiting on a render-finished semaphore: they go
+        // once the first frame on the new swapchain is done, which the
+        // queue runs after those presents.
+        self.trash.push(DeferredDrop {
+            value: self.timeline_value + 1,
+            resource: GpuResource::Swapchain {
+                swapchain: self.swapchain,
+                views: std::mem::take(&mut self.image_views),
+                frames: std::mem::take(&mut self.frames),
+            },
+        });
+
+        // 3b) Swap in new data
         let old_format = self.format;
+        let old_scene_format = self.scene_color_format();
         self.swapchain = swapchain;
         self.format = format;
         self.extent = extent;
         self.images = images;
         self.image_views = image_views;
+        self.color_space = color_space;
+        self.present_mode = present_mode;
+        self.swapchain_usage = usage;
+        self.pre_transform = pre_transform;
+        self.present_modes = present_modes;
 
-        // 4e) Recreate depth resources for the NEW extent (using same depth format)
+        // 3c) Recreate depth resources for the NEW extent (using same depth format)
         if self.depth_view != vk::ImageView::null() {
             self.trash.push(DeferredDrop {
                 value: self.timeline_value,
@@ -562,76 +937,42 @@ impl VkRenderer {
         self.depth_alloc = dalloc;
         self.depth_view = dview;
 
-        // 5) Recreate per-image UBOs + descriptor sets
-        let (ubufs, umems, ubo_ptrs, ubo_size, desc_pool, desc_sets) =
-            create_frame_uniforms_and_sets(
-                &self.instance,
+        // 4a) Recreate per-image sync
+        for _ in 0..self.images.len() {
+            self.frames.push(FrameSync::new(
                 &self.device,
-                self.phys,
-                self.allocator.as_mut().expect("allocator missing"),
-                self.desc_set_layout_camera,
-                self.images.len(),
-            )?;
-        self.ubufs = ubufs;
-        self.umems = umems;
-        self.ubo_ptrs = ubo_ptrs;
-        self.ubo_size = ubo_size;
-        self.desc_pool = desc_pool;
-        self.desc_sets = desc_sets;
-
-        // 5b) Recreate per-image indirect draw resources.
-        let indirect = create_indirect_draw_resources(
-            &self.device,
-            self.allocator.as_mut().expect("allocator missing"),
-            self.desc_set_layout_indirect_compute,
-            self.desc_set_layout_indirect_graphics,
-            self.images.len(),
-        )?;
-        self.candidate_bufs = indirect.candidate_bufs;
-        self.candidate_allocs = indirect.candidate_allocs;
-        self.candidate_ptrs = indirect.candidate_ptrs;
-        self.indirect_bufs = indirect.indirect_bufs;
-        self.indirect_allocs = indirect.indirect_allocs;
-        self.draw_count_bufs = indirect.draw_count_bufs;
-        self.draw_count_allocs = indirect.draw_count_allocs;
-        self.indirect_desc_pool = indirect.desc_pool;
-        self.indirect_compute_desc_sets = indirect.compute_desc_sets;
-        self.indirect_graphics_desc_sets = indirect.graphics_desc_sets;
-
-        // 5c) Recreate per-image sync
-        let image_count = self.images.len();
-        let sem_info = vk::SemaphoreCreateInfo::default();
-        for _ in 0..image_count {
-            let rf = unsafe { self.device.create_semaphore(&sem_info, None)? };
-            self.frames.push(FrameSync {
-                render_finished: rf,
-            });
+                self.cfg.swapchain_maintenance1_ext,
+            )?);
         }
 
-        // 6) Recreate pipeline only if COLOR format changed
-        if self.format != old_format {
-            let (new_layout, new_pipeline) = create_pipeline(
-                &self.device,
-                self.pipeline_cache,
-                &PipelineConfig {
-                    color_format: self.format,
-                    depth_format: self.depth_format,
-                    set_layout_camera: self.desc_set_layout_camera,
-                    set_layout_material: self.desc_set_layout_material,
-                    set_layout_indirect_graphics: self.desc_set_layout_indirect_graphics,
-                },
-            )?;
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::Pipeline(self.pipeline),
-            });
-            self.trash.push(DeferredDrop {
-                value: self.timeline_value,
-                resource: GpuResource::PipelineLayout(self.pipeline_layout),
-            });
-            self.pipeline_layout = new_layout;
-            self.pipeline = new_pipeline;
+        // 4b) HDR tonemap pass: rebuilt for the new extent/format, or
+        // dropped if the new colour space is SDR; the old passes are
+        // retired.
+        self.sync_tonemap_pass();
+        self.sync_text_pass();
+        self.sync_picking();
+        self.sync_stereo();
+        self.sync_capture();
 
+        // 5) Recreate scene pipelines only if the format they render into
+        // changed (swapchain format, or toggling the tonemap target)
+        if self.scene_color_format() != old_scene_format {
+            self.rebuild_scene_pipelines()?;
+        }
+        if self.format != old_format {
+            // egui's renderer replaces its pipeline in place, so this (rare:
+            // HDR toggled, or the display changed) is the one case that
+            // waits for the frames in flight.
+            if self.timeline_value > 0 {
+                let wait_info = vk::SemaphoreWaitInfo {
+                    s_type: vk::StructureType::SEMAPHORE_WAIT_INFO,
+                    semaphore_count: 1,
+                    p_semaphores: &self.timeline,
+                    p_values: &self.timeline_value,
+                    ..Default::default()
+                };
+                unsafe { self.device.wait_semaphores(&wait_info, u64::MAX).ok() };
+            }
             // The egui pipeline is built against a fixed color format too
             // (see build_renderer); left stale here, cmd_begin_rendering's
             // new-format attachment wouldn't match it and every egui draw
@@ -651,24 +992,68 @@ impl VkRenderer {
             }
         }
 
-        // 7) Resize CBs if image count changed
-        if self.cmd_bufs.len() != self.images.len() {
-            unsafe {
-                self.device
-                    .free_command_buffers(self.cmd_pool, &self.cmd_bufs)
-            };
-            let alloc_info = vk::CommandBufferAllocateInfo {
-                s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
-                command_pool: self.cmd_pool,
-                level: vk::CommandBufferLevel::PRIMARY,
-                command_buffer_count: self.images.len() as u32,
-                ..Default::default()
-            };
-            self.cmd_bufs = unsafe { self.device.allocate_command_buffers(&alloc_info)? };
-        }
+        self.name_objects();
 
-        self.acq_index = 0;
+        // 6) Tell the app (see Renderer::take_surface_change), keeping the
+        // oldest `previous` if it hasn't looked since the last recreation.
+        let previous = self.surface_change.map_or(previous, |c| c.previous);
+        self.surface_change = Some(SurfaceChanged {
+            previous,
+            current: self.surface_info(),
+        });
 
         Ok(())
     }
+
+    /// Re-query the surface's formats, which follow the display the window
+    /// is on, and recreate the swapchain if HDR is asked for and
+    /// pick_surface_format would now choose differently: SDR on a display
+    /// without HDR, HDR again once back on one. For the app to call when
+    /// the window moves to another monitor. Some when HDR output went off
+    /// or came back, for the app to tell the user.
+    pub fn recheck_display_hdr(&mut self) -> Option<HdrDisplayChange> {
+        if !self.cfg.hdr || self.surface == vk::SurfaceKHR::null() {
+            return None;
+        }
+        let formats = match unsafe {
+            self.surface_loader
+                .get_physical_device_surface_formats(self.phys, self.surface)
+        } {
+            Ok(formats) if !formats.is_empty() => formats,
+            Ok(_) => return None,
+            Err(e) => {
+                tracing::warn!("recheck_display_hdr: surface formats: {e:?}");
+                return None;
+            }
+        };
+        let (picked, reason) = pick_surface_format(
+            &formats,
+            true,
+            self.cfg.allow_extended_colorspace,
+            self.cfg.hdr_flavor,
+        );
+        if picked.format == self.format && picked.color_space == self.color_space {
+            return None;
+        }
+
+        tracing::info!(
+            "display changed: surface format now {} / {} ({reason}); recreating the swapchain",
+            fmt_name(picked.format),
+            cs_name(picked.color_space)
+        );
+        let was_hdr = is_hdr_color_space(self.color_space);
+        let size = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        if let Err(e) = self.recreate_swapchain(size) {
+            tracing::warn!("recheck_display_hdr: recreate_swapchain: {e:#}");
+            return None;
+        }
+        match (was_hdr, is_hdr_color_space(self.color_space)) {
+            (true, false) => Some(HdrDisplayChange::FellBackToSdr),
+            (false, true) => Some(HdrDisplayChange::Restored),
+            _ => None,
+        }
+    }
 }
@@ -3,8 +3,48 @@
 use anyhow::Result;
 use ash::vk;
 
+/// Frames the CPU can record ahead of the GPU. Each has an acquire slot,
+/// and render_frame waits for a slot's last submit before reusing it, so
+/// the command buffer and indirect-draw buffers kept per slot never depend
+/// on the swapchain's image count and survive its recreation.
+pub(crate) const FRAMES_IN_FLIGHT: usize = 2;
+
 pub(crate) struct FrameSync {
     pub(crate) render_finished: vk::Semaphore,
+    /// Signaled once the presentation engine is done with this image's
+    /// last present, render_finished included (VK_EXT_swapchain_maintenance1's
+    /// present fence); null without the extension. Created signaled.
+    pub(crate) present_fence: vk::Fence,
+}
+
+impl FrameSync {
+    pub(crate) fn new(device: &ash::Device, present_fence: bool) -> Result<Self> {
+        let render_finished =
+            unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)? };
+        let present_fence = if present_fence {
+            let info = vk::FenceCreateInfo {
+                s_type: vk::StructureType::FENCE_CREATE_INFO,
+                flags: vk::FenceCreateFlags::SIGNALED,
+                ..Default::default()
+            };
+            unsafe { device.create_fence(&info, None)? }
+        } else {
+            vk::Fence::null()
+        };
+        Ok(Self {
+            render_finished,
+            present_fence,
+        })
+    }
+
+    pub(crate) fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_semaphore(self.render_finished, None);
+            if self.present_fence != vk::Fence::null() {
+                device.destroy_fence(self.present_fence, None);
+            }
+        }
+    }
 }
 
 pub(crate) struct AcquireSlot {
@@ -59,12 +99,11 @@ pub(crate) fn create_timeline_semaphore(
 }
 
 /// Generic sync2 execution/memory barrier, with no buffer or image tied to
-/// it. Suitable for compute<->graphics ordering around dispatches that
-/// don't (yet) read/write a specific resource; barriers scoped to an
-/// actual buffer or image should use VkBufferMemoryBarrier2 /
-/// VkImageMemoryBarrier2 instead once there's a real resource to name.
-#[allow(dead_code)]
-fn memory_barrier2(
+/// it. What the compute subsystem uses between its ops: a global barrier
+/// costs drivers no more than a list of per-buffer ones, and needs no
+/// bookkeeping of which op touched which buffer. Barriers between passes
+/// come from the frame graph instead.
+pub(crate) fn memory_barrier2(
     device: &ash::Device,
     cmd: vk::CommandBuffer,
     src_stage: vk::PipelineStageFlags2,
@@ -89,47 +128,19 @@ fn memory_barrier2(
     unsafe { device.cmd_pipeline_barrier2(cmd, &dep) };
 }
 
-/// Ensures compute shader writes are visible to subsequent vertex/fragment
-/// shader reads (e.g. a compute pass writing chunk/culling data that the
-/// graphics pipeline then reads).
-#[allow(dead_code)]
-pub(crate) fn barrier_compute_to_graphics(device: &ash::Device, cmd: vk::CommandBuffer) {
-    memory_barrier2(
-        device,
-        cmd,
-        vk::PipelineStageFlags2::COMPUTE_SHADER,
-        vk::AccessFlags2::SHADER_WRITE,
-        vk::PipelineStageFlags2::VERTEX_SHADER | vk::PipelineStageFlags2::FRAGMENT_SHADER,
-        vk::AccessFlags2::SHADER_READ,
-    );
-}
-
-/// Ensures vertex/fragment shader writes are visible to a subsequent
-/// compute dispatch's reads (the reverse direction of
-/// `barrier_compute_to_graphics`).
-#[allow(dead_code)]
-pub(crate) fn barrier_graphics_to_compute(device: &ash::Device, cmd: vk::CommandBuffer) {
-    memory_barrier2(
-        device,
-        cmd,
-        vk::PipelineStageFlags2::VERTEX_SHADER | vk::PipelineStageFlags2::FRAGMENT_SHADER,
-        vk::AccessFlags2::SHADER_WRITE,
-        vk::PipelineStageFlags2::COMPUTE_SHADER,
-        vk::AccessFlags2::SHADER_READ,
-    );
-}
-
 pub(crate) fn create_sync_objects(
     device: &ash::Device,
     image_count: usize,
+    present_fences: bool,
 ) -> Result<(Vec<AcquireSlot>, Vec<FrameSync>)> {
-    let mut acq_slots = Vec::with_capacity(2);
+    let mut acq_slots = Vec::with_capacity(FRAMES_IN_FLIGHT);
     let mut frames = Vec::with_capacity(image_count);
 
     let sem_ci = vk::SemaphoreCreateInfo::default();
 
-    // Two acquire slots (binary semaphores), tracked by timeline values
-    for _ in 0..2 {
+    // One acquire slot (binary semaphore) per frame in flight, tracked by
+    // timeline values
+    for _ in 0..FRAMES_IN_FLIGHT {
         let sem = unsafe { device.create_semaphore(&sem_ci, None)? };
         acq_slots.push(AcquireSlot {
             sem,
@@ -137,12 +148,9 @@ pub(crate) fn create_sync_objects(
         });
     }
 
-    // Per-image present wait semaphores (binary)
+    // Per-image present wait semaphores (binary), plus present fences
     for _ in 0..image_count {
-        let rf = unsafe { device.create_semaphore(&sem_ci, None)? };
-        frames.push(FrameSync {
-            render_finished: rf,
-        });
+        frames.push(FrameSync::new(device, present_fences)?);
     }
     Ok((acq_slots, frames))
 }
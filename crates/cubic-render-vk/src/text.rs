@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Screen-space text (FPS counters, debug readouts) drawn by the renderer
+//! itself, without going through egui.
+//!
+//! Glyphs come from a bitmap font atlas: printable ASCII rasterized once
+//! with fontdue at ATLAS_PX from a TTF (egui's bundled Hack monospace
+//! unless set_font supplies another) and uploaded into the bindless texture
+//! array like any other texture, coverage in alpha. The atlas is built on
+//! the first draw_text, so an app that never draws text never spends a
+//! bindless slot on it. draw_text lays strings out into quads, scaled from
+//! the atlas size (sharp up to about ATLAS_PX; the mip chain covers smaller
+//! sizes), and the frame graph's "text" pass uploads the frame's quads
+//! through the staging belt and draws them in one call with an
+//! alpha-blended pipeline (text.vert/text.frag).
+//!
+//! The pass runs after tonemap, straight onto the swapchain image, so the
+//! pipeline follows the swapchain format like the tonemap pass does and
+//! text.frag applies the output encoding itself. Without text shaders
+//! built the pass is skipped with a warning and draw_text does nothing.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cubic_render::RenderResult;
+use gpu_allocator::vulkan::Allocator;
+
+use crate::descriptors::DescriptorAllocator;
+use crate::pipeline::{create_overlay_pipeline, BlendMode};
+use crate::sampler::SamplerDesc;
+use crate::swapchain::pre_rotate_pixel;
+use crate::tonemap::{output_encoding, OutputEncode};
+use crate::{RetiredPass, VkRenderer};
+
+/// Pixel size glyphs are rasterized at.
+const ATLAS_PX: f32 = 32.0;
+const ATLAS_WIDTH: usize = 512;
+/// Empty texels around each glyph, so mips and linear filtering don't
+/// bleed neighbours in.
+const GLYPH_PADDING: usize = 2;
+/// Printable ASCII; anything else is drawn as '?'.
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+
+// Vertex layout of text.vert.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct TextVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Push constants for text.vert/text.frag; layout must match their `Text`
+/// block (text.vert stops before `gamut`).
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct TextPush {
+    screen_size: [f32; 2],
+    tex_index: u32,
+    encoding: u32,
+    paper_white_nits: f32,
+    _pad: [f32; 3],
+    gamut: [[f32; 4]; 3],
+}
+
+/// One glyph's atlas rectangle and metrics, in atlas pixels.
+#[derive(Clone, Copy)]
+struct Glyph {
+    uv: [f32; 4],
+    size: [f32; 2],
+    // Left edge from the pen, bottom edge above the baseline.
+    offset: [f32; 2],
+    advance: f32,
+}
+
+/// A rasterized font: glyph table plus the atlas's bindless index.
+pub(crate) struct TextFont {
+    glyphs: Vec<Glyph>,
+    ascent: f32,
+    line_height: f32,
+    tex_index: u32,
+}
+
+impl TextFont {
+    fn glyph(&self, c: char) -> &Glyph {
+        let c = if (FIRST_CHAR..=LAST_CHAR).contains(&c) {
+            c
+        } else {
+            '?'
+        };
+        &self.glyphs[(c as u32 - FIRST_CHAR as u32) as usize]
+    }
+
+    /// Append `text`'s quads, two triangles each; `pos` is the top-left
+    /// corner of the first line and `size` the font's pixel size.
+    fn layout(
+        &self,
+        out: &mut Vec<TextVertex>,
+        pos: [f32; 2],
+        text: &str,
+        size: f32,
+        color: [f32; 4],
+    ) {
+        let scale = size / ATLAS_PX;
+        let mut pen = [pos[0], pos[1] + self.ascent * scale];
+        for c in text.chars() {
+            if c == '\n' {
+                pen = [pos[0], pen[1] + self.line_height * scale];
+                continue;
+            }
+            let g = self.glyph(c);
+            if g.size[0] > 0.0 && g.size[1] > 0.0 {
+                let x0 = pen[0] + g.offset[0] * scale;
+                let y1 = pen[1] - g.offset[1] * scale;
+                let x1 = x0 + g.size[0] * scale;
+                let y0 = y1 - g.size[1] * scale;
+                let [u0, v0, u1, v1] = g.uv;
+                let corners = [
+                    ([x0, y0], [u0, v0]),
+                    ([x1, y0], [u1, v0]),
+                    ([x1, y1], [u1, v1]),
+                    ([x0, y1], [u0, v1]),
+                ];
+                out.extend([0, 1, 2, 0, 2, 3].map(|i| TextVertex {
+                    pos: corners[i].0,
+                    uv: corners[i].1,
+                    color,
+                }));
+            }
+            pen[0] += g.advance * scale;
+        }
+    }
+}
+
+/// CPU side of a font: the glyph table and RGBA8 atlas pixels (white,
+/// coverage in alpha), before upload.
+struct RasterizedFont {
+    glyphs: Vec<Glyph>,
+    ascent: f32,
+    line_height: f32,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+fn rasterize_font(ttf: &[u8]) -> Result<RasterizedFont> {
+    let settings = fontdue::FontSettings {
+        scale: ATLAS_PX,
+        ..Default::default()
+    };
+    let font = fontdue::Font::from_bytes(ttf, settings)
+        .map_err(|e| anyhow!("text: font not loaded: {e}"))?;
+    let line = font
+        .horizontal_line_metrics(ATLAS_PX)
+        .ok_or_else(|| anyhow!("text: font has no horizontal line metrics"))?;
+    let rasters: Vec<_> = (FIRST_CHAR..=LAST_CHAR)
+        .map(|c| font.rasterize(c, ATLAS_PX))
+        .collect();
+
+    // Shelf packing: left to right, a new row when one is full.
+    let (mut x, mut y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+    let mut places = Vec::with_capacity(rasters.len());
+    for (m, _) in &rasters {
+        if x + m.width + GLYPH_PADDING > ATLAS_WIDTH {
+            x = GLYPH_PADDING;
+            y += row_height + GLYPH_PADDING;
+            row_height = 0;
+        }
+        places.push((x, y));
+        x += m.width + GLYPH_PADDING;
+        row_height = row_height.max(m.height);
+    }
+    let height = (y + row_height + GLYPH_PADDING).next_power_of_two();
+
+    let mut pixels = vec![0u8; ATLAS_WIDTH * height * 4];
+    for texel in pixels.chunks_exact_mut(4) {
+        texel[..3].fill(255);
+    }
+    let (w, h) = (ATLAS_WIDTH as f32, height as f32);
+    let mut glyphs = Vec::with_capacity(rasters.len());
+    for ((m, bitmap), &(gx, gy)) in rasters.iter().zip(&places) {
+        for row in 0..m.height {
+            for col in 0..m.width {
+                pixels[((gy + row) * ATLAS_WIDTH + gx + col) * 4 + 3] = bitmap[row * m.width + col];
+            }
+        }
+        glyphs.push(Glyph {
+            uv: [
+                gx as f32 / w,
+                gy as f32 / h,
+                (gx + m.width) as f32 / w,
+                (gy + m.height) as f32 / h,
+            ],
+            size: [m.width as f32, m.height as f32],
+            offset: [m.xmin as f32, m.ymin as f32],
+            advance: m.advance_width,
+        });
+    }
+    Ok(RasterizedFont {
+        glyphs,
+        ascent: line.ascent,
+        line_height: line.new_line_size,
+        pixels,
+        width: ATLAS_WIDTH as u32,
+        height: height as u32,
+    })
+}
+
+/// The text pipeline, built against the swapchain format.
+pub(crate) struct TextPass {
+    layout: vk::PipelineLayout,
+    pub(crate) pipeline: vk::Pipeline,
+}
+
+impl TextPass {
+    fn new(
+        device: &ash::Device,
+        cache: vk::PipelineCache,
+        material_set_layout: vk::DescriptorSetLayout,
+        swapchain_format: vk::Format,
+        depth_format: vk::Format,
+    ) -> Result<Self> {
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<TextPush>() as u32,
+        };
+        // The bindless array is the material layout's binding 0; set 0 here.
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &material_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        let binding = vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<TextVertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        };
+        let attributes = [
+            (vk::Format::R32G32_SFLOAT, 0),
+            (vk::Format::R32G32_SFLOAT, 8),
+            (vk::Format::R32G32B32A32_SFLOAT, 16),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(
+            |(location, (format, offset))| vk::VertexInputAttributeDescription {
+                location: location as u32,
+                binding: 0,
+                format,
+                offset,
+            },
+        )
+        .collect::<Vec<_>>();
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            vertex_binding_description_count: 1,
+            p_vertex_binding_descriptions: &binding,
+            vertex_attribute_description_count: attributes.len() as u32,
+            p_vertex_attribute_descriptions: attributes.as_ptr(),
+            ..Default::default()
+        };
+        let pipeline = match create_overlay_pipeline(
+            device,
+            cache,
+            layout,
+            ("text.vert.spv", "text.frag.spv"),
+            &vertex_input,
+            BlendMode::Alpha,
+            false,
+            swapchain_format,
+            depth_format,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                unsafe { device.destroy_pipeline_layout(layout, None) };
+                return Err(e);
+            }
+        };
+        Ok(Self { layout, pipeline })
+    }
+
+    /// Caller must have idled the device.
+    pub(crate) fn destroy(self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+impl RetiredPass for TextPass {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        _allocator: &mut Allocator,
+        _descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device);
+    }
+}
+
+impl VkRenderer {
+    /// Queue `text` for the next frame, top-left corner of its first line at
+    /// `pos` (pixels from the window's top-left), `size` pixels tall, in
+    /// linear RGBA `color`. '\n' starts a new line; characters outside
+    /// printable ASCII show as '?'. Drawn over the scene, under egui.
+    pub fn draw_text(&mut self, pos: [f32; 2], text: &str, size: f32, color: [f32; 4]) {
+        if self.text_pass.is_none() || text.is_empty() {
+            return;
+        }
+        if self.text_font.is_none() && !self.text_font_failed {
+            let ttf = egui::FontDefinitions::default()
+                .font_data
+                .get("Hack")
+                .map(|data| data.font.clone());
+            let loaded = match ttf {
+                Some(ttf) => self.load_font(&ttf),
+                None => Err(anyhow!("text: egui's default monospace font is missing")),
+            };
+            if let Err(e) = loaded {
+                tracing::warn!("text overlay unavailable: {e:#}");
+                self.text_font_failed = true;
+            }
+        }
+        if let Some(font) = &self.text_font {
+            font.layout(&mut self.text_vertices, pos, text, size, color);
+        }
+    }
+
+    /// Replace draw_text's font with a TTF/OTF. Each call uploads a new
+    /// atlas into its own bindless slot (textures are never freed), so this
+    /// is for picking a font at startup, not switching per frame.
+    pub fn set_font(&mut self, ttf: &[u8]) -> RenderResult<()> {
+        self.load_font(ttf)?;
+        self.text_font_failed = false;
+        Ok(())
+    }
+
+    fn load_font(&mut self, ttf: &[u8]) -> Result<()> {
+        let font = rasterize_font(ttf)?;
+        let sampler =
+            SamplerDesc::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        let tex_index =
+            self.upload_texture_with_sampler(&font.pixels, font.width, font.height, sampler)?;
+        self.text_font = Some(TextFont {
+            glyphs: font.glyphs,
+            ascent: font.ascent,
+            line_height: font.line_height,
+            tex_index,
+        });
+        Ok(())
+    }
+
+    /// Whether the "text" pass has anything to draw this frame.
+    pub(crate) fn text_pending(&self) -> bool {
+        self.text_pass.is_some() && !self.text_vertices.is_empty()
+    }
+
+    /// Rebuild the text pipeline for the current swapchain format, retiring
+    /// the old one through the trash queue. Called with sync_tonemap_pass;
+    /// a failure (text shaders not compiled) is logged and leaves draw_text
+    /// a no-op.
+    pub(crate) fn sync_text_pass(&mut self) {
+        if let Some(old) = self.text_pass.take() {
+            self.retire_pass(old);
+        }
+        match TextPass::new(
+            &self.device,
+            self.pipeline_cache,
+            self.desc_set_layout_material,
+            self.format,
+            self.depth_format,
+        ) {
+            Ok(pass) => self.text_pass = Some(pass),
+            Err(e) => tracing::warn!("text overlay unavailable ({e:#}); draw_text disabled"),
+        }
+    }
+
+    /// Draw the frame's queued text as the frame graph's "text" pass: the
+    /// graph has opened a rendering scope on the swapchain image (loaded)
+    /// with depth bound, which the pipeline was built against.
+    pub(crate) fn record_text(&mut self, cmd: vk::CommandBuffer) -> Result<()> {
+        let mut vertices = std::mem::take(&mut self.text_vertices);
+        if self.pre_transform != vk::SurfaceTransformFlagsKHR::IDENTITY {
+            let window = self.view_extent();
+            for v in &mut vertices {
+                v.pos = pre_rotate_pixel(self.pre_transform, window, v.pos);
+            }
+        }
+        let (Some(pass), Some(font)) = (self.text_pass.as_ref(), self.text_font.as_ref()) else {
+            return Ok(());
+        };
+        let (layout, pipeline, tex_index) = (pass.layout, pass.pipeline, font.tex_index);
+        let slice = self.upload_transient(bytemuck::cast_slice(&vertices))?;
+
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+        let vp = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.extent.width as f32,
+            height: self.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        // No encode needed is the colour as is, for *_SRGB swapchains.
+        let encode = output_encoding(self.format, self.color_space, self.cfg.srgb_encode)
+            .unwrap_or(OutputEncode::PASSTHROUGH);
+        let push = TextPush {
+            screen_size: [self.extent.width as f32, self.extent.height as f32],
+            tex_index,
+            encoding: encode.encoding,
+            paper_white_nits: self.cfg.sdr_white_nits,
+            _pad: [0.0; 3],
+            gamut: encode.gamut,
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&render_area));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                std::slice::from_ref(&self.material_desc_set),
+                &[],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            self.device
+                .cmd_bind_vertex_buffers(cmd, 0, &[slice.buffer()], &[slice.offset()]);
+            self.device.cmd_draw(cmd, vertices.len() as u32, 1, 0, 0);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,543 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! HDR tonemapping post pass.
+//!
+//! When the swapchain lands on an HDR colour space (scRGB FP16 or HDR10 PQ,
+//! see pick_surface_format) the scene is rendered into an intermediate FP16
+//! target instead of the swapchain image. A fullscreen pass then runs the
+//! selected tonemap operator over it and ends on the colour space's final
+//! encode (see OutputEncode): the gamut matrix from the scene's BT.709 into
+//! the space's primaries, then its transfer function (linear scRGB scaling
+//! or PQ), writing to the swapchain; egui is drawn on top afterwards.
+//!
+//! SDR swapchains skip all of this and render straight to the swapchain
+//! image as before, so the pass costs nothing unless HDR is actually on.
+//! The one exception is an SDR swapchain with a UNORM format (the last
+//! fallback in pick_surface_format): the hardware doesn't sRGB-encode on
+//! write there, so the same pass runs in a plain sRGB-encode mode, no
+//! curve, to make it look like the *_SRGB formats. `set_srgb_encode(false)`
+//! turns that off and writes linear values as before.
+//!
+//! A render scale below 1.0 (see upscale.rs) also needs the intermediate
+//! target, now smaller than the swapchain: the pass then samples it with
+//! the upscaler's filter (or samples FSR1's full-size output), and on an
+//! *_SRGB swapchain runs in a passthrough mode that only copies. The same
+//! goes for an SDR swapchain with post-processing on (see post.rs), which
+//! needs the scene offscreen to read it.
+
+use anyhow::Result;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::color_filter::ColorFilter;
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::pipeline::create_fullscreen_pipeline;
+use crate::resources::create_color_target;
+use crate::upscale::{scaled_extent, Upscaler};
+use crate::{RetiredPass, VkRenderer};
+use cubic_render::{RenderSize, TonemapOperator};
+
+/// Format of the intermediate scene target while the pass is active.
+/// FP16 keeps values above 1.0 (and below 0.0, for scRGB's wide gamut) that
+/// the tonemap curve needs to see.
+pub(crate) const HDR_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Default brightness the scene's 1.0 (SDR white, "paper white") maps to
+/// on HDR swapchains; see set_sdr_white_nits. The peak the curve's shoulder
+/// lands on is the HDR metadata's max_luminance (see hdr_metadata.rs), so
+/// the curve and what the display is told agree.
+pub const DEFAULT_SDR_WHITE_NITS: f32 = 200.0;
+/// Range set_sdr_white_nits clamps to: scRGB's own 1.0 up to about where
+/// SDR white stops looking like white and starts looking like a lamp.
+pub const MIN_SDR_WHITE_NITS: f32 = 80.0;
+pub const MAX_SDR_WHITE_NITS: f32 = 1000.0;
+
+/// Push constants for tonemap.frag; layout must match its `Tonemap` block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct TonemapPush {
+    gamut: [[f32; 4]; 3],
+    operator: u32,
+    encoding: u32,
+    paper_white_nits: f32,
+    peak_nits: f32,
+}
+
+const ENCODING_SCRGB_LINEAR: u32 = 0;
+const ENCODING_PQ: u32 = 1;
+const ENCODING_SRGB: u32 = 2;
+const ENCODING_PASSTHROUGH: u32 = 3;
+
+/// BT.709 to BT.2020 primaries, both D65; columns, as GLSL's mat3 takes
+/// them.
+const BT709_TO_BT2020: [[f32; 4]; 3] = [
+    [0.6274, 0.0691, 0.0164, 0.0],
+    [0.3293, 0.9195, 0.0880, 0.0],
+    [0.0433, 0.0114, 0.8956, 0.0],
+];
+const IDENTITY: [[f32; 4]; 3] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// The final encode from the scene's linear BT.709 to what a swapchain
+/// colour space expects: a gamut matrix into its primaries, then its
+/// transfer function. tonemap.frag and text.frag take both as push
+/// constants, so the shaders don't need to know which space it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct OutputEncode {
+    /// Columns, each padded to a vec4 as a push-constant mat3's are.
+    pub(crate) gamut: [[f32; 4]; 3],
+    /// Which transfer function the shaders apply (ENCODING_*).
+    pub(crate) encoding: u32,
+}
+
+impl OutputEncode {
+    /// No matrix, no curve: the values are written as they are, for the
+    /// hardware's *_SRGB encode on write.
+    pub(crate) const PASSTHROUGH: Self = Self {
+        gamut: IDENTITY,
+        encoding: ENCODING_PASSTHROUGH,
+    };
+    /// scRGB: BT.709 primaries, linear, 1.0 = 80 nits.
+    const SCRGB_LINEAR: Self = Self {
+        gamut: IDENTITY,
+        encoding: ENCODING_SCRGB_LINEAR,
+    };
+    /// HDR10: BT.2020 primaries, SMPTE ST 2084 (PQ) in absolute nits.
+    const PQ: Self = Self {
+        gamut: BT709_TO_BT2020,
+        encoding: ENCODING_PQ,
+    };
+    /// SDR on a UNORM format: BT.709 primaries, the sRGB curve.
+    const SRGB: Self = Self {
+        gamut: IDENTITY,
+        encoding: ENCODING_SRGB,
+    };
+}
+
+/// Whether `color_space` is one of the HDR spaces the pass encodes for;
+/// pick_surface_format picks no other.
+pub(crate) fn is_hdr_color_space(color_space: vk::ColorSpaceKHR) -> bool {
+    matches!(
+        color_space,
+        vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT | vk::ColorSpaceKHR::HDR10_ST2084_EXT
+    )
+}
+
+/// The final encode the swapchain's colour space needs, or None when the
+/// scene can render to it directly: SDR with an *_SRGB format, or with a
+/// UNORM one when `srgb_encode` is off.
+pub(crate) fn output_encoding(
+    format: vk::Format,
+    color_space: vk::ColorSpaceKHR,
+    srgb_encode: bool,
+) -> Option<OutputEncode> {
+    match color_space {
+        vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => Some(OutputEncode::SCRGB_LINEAR),
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT => Some(OutputEncode::PQ),
+        vk::ColorSpaceKHR::SRGB_NONLINEAR if srgb_encode && !is_srgb_format(format) => {
+            Some(OutputEncode::SRGB)
+        }
+        _ => None,
+    }
+}
+
+/// Formats the hardware sRGB-encodes on write.
+fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+pub(crate) struct TonemapPass {
+    pub(crate) image: vk::Image,
+    alloc: Allocation,
+    pub(crate) view: vk::ImageView,
+    // Size of `image`: the swapchain extent times the render scale.
+    pub(crate) extent: vk::Extent2D,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    desc_set: PooledSet,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    encode: OutputEncode,
+}
+
+impl TonemapPass {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        cache: vk::PipelineCache,
+        extent: vk::Extent2D,
+        filter: vk::Filter,
+        swapchain_format: vk::Format,
+        depth_format: vk::Format,
+        encode: OutputEncode,
+    ) -> Result<Self> {
+        // Pipeline first: it's the part that fails in practice (shaders
+        // not built), and nothing else needs cleaning up if it does.
+        let set_layout = create_tonemap_set_layout(device)?;
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<TonemapPush>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+        let pipeline = match create_fullscreen_pipeline(
+            device,
+            cache,
+            layout,
+            ("fullscreen.vert.spv", "tonemap.frag.spv"),
+            swapchain_format,
+            depth_format,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                unsafe {
+                    device.destroy_pipeline_layout(layout, None);
+                    device.destroy_descriptor_set_layout(set_layout, None);
+                }
+                return Err(e);
+            }
+        };
+
+        let (image, alloc, view) = create_color_target(
+            device,
+            allocator,
+            extent,
+            HDR_TARGET_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            "hdr scene target",
+        )?;
+
+        // NEAREST when sampled 1:1 with the output, the upscaler's filter
+        // otherwise; CLAMP keeps the oversized triangle's out-of-range UVs
+        // (clipped anyway) harmless.
+        let sampler_ci = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        };
+        let sampler = unsafe { device.create_sampler(&sampler_ci, None)? };
+
+        let desc_set = descriptors.allocate(device, set_layout)?;
+
+        let pass = Self {
+            image,
+            alloc,
+            view,
+            extent,
+            sampler,
+            set_layout,
+            desc_set,
+            layout,
+            pipeline,
+            encode,
+        };
+        pass.set_source(device, view);
+        Ok(pass)
+    }
+
+    /// Point the pass at the image it reads: its own scene target, or an
+    /// upscaler's output. Only while the pass isn't in flight; see
+    /// renew_set otherwise.
+    pub(crate) fn set_source(&self, device: &ash::Device, view: vk::ImageView) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            image_view: view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let write = vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: self.desc_set.set,
+            dst_binding: 0,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+        unsafe { device.update_descriptor_sets(std::slice::from_ref(&write), &[]) };
+    }
+
+    /// Swap in a fresh descriptor set for set_source to write, returning
+    /// the old one to be retired: frames in flight may still have it bound.
+    pub(crate) fn renew_set(
+        &mut self,
+        device: &ash::Device,
+        descriptors: &mut DescriptorAllocator,
+    ) -> Result<PooledSet> {
+        let set = descriptors.allocate(device, self.set_layout)?;
+        Ok(std::mem::replace(&mut self.desc_set, set))
+    }
+
+    /// Tear everything down. Caller must have idled the device.
+    pub(crate) fn destroy(
+        self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        descriptors.free(device, self.desc_set);
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(self.alloc);
+    }
+
+    /// Running as a plain sRGB encode for a UNORM swapchain (no curve).
+    pub(crate) fn encodes_srgb(&self) -> bool {
+        self.encode.encoding == ENCODING_SRGB
+    }
+
+    /// Only there to upscale a scaled scene or to carry post-processing; no
+    /// curve, no encode.
+    pub(crate) fn passthrough(&self) -> bool {
+        self.encode.encoding == ENCODING_PASSTHROUGH
+    }
+}
+
+impl RetiredPass for TonemapPass {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device, allocator, descriptors);
+    }
+}
+
+fn create_tonemap_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+    let binding = vk::DescriptorSetLayoutBinding {
+        binding: 0,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        descriptor_count: 1,
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        ..Default::default()
+    };
+    let ci = vk::DescriptorSetLayoutCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        binding_count: 1,
+        p_bindings: &binding,
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_descriptor_set_layout(&ci, None)? })
+}
+
+impl VkRenderer {
+    /// Format the scene pipelines render into: the FP16 target while the
+    /// tonemap pass is active, the swapchain format otherwise.
+    pub(crate) fn scene_color_format(&self) -> vk::Format {
+        if self.tonemap.is_some() {
+            HDR_TARGET_FORMAT
+        } else {
+            self.format
+        }
+    }
+
+    /// Bring the tonemap pass in line with the current swapchain: rebuilt at
+    /// the new extent/format when the colour space is HDR (or the format
+    /// needs an sRGB encode, or the scene is rendered scaled, letterboxed,
+    /// at a fixed resolution or post-processed, or render targets exist),
+    /// dropped when it isn't. The FSR1 upscaler and the
+    /// post-processing chain are rebuilt along with it, the old passes
+    /// retired through the trash queue. Called from recreate_swapchain
+    /// and once at startup. A
+    /// pass that fails to build (e.g. tonemap.frag.spv not compiled yet) is
+    /// logged and skipped — the scene then renders to the swapchain
+    /// directly, exactly as it would in SDR.
+    pub(crate) fn sync_tonemap_pass(&mut self) {
+        let output = self.output_area().extent;
+        let internal = self.internal_extent();
+        let scene_extent = internal.unwrap_or_else(|| scaled_extent(output, self.cfg.render_scale));
+        let scaled = scene_extent != output;
+        let offscreen = scaled
+            || output != self.extent
+            || !self.cfg.post.is_empty()
+            || self.cfg.color_filter != ColorFilter::Off
+            || self.has_render_targets();
+        if let Some(old) = self.post.take() {
+            self.retire_pass(old);
+        }
+        if let Some(old) = self.fsr1.take() {
+            self.retire_pass(old);
+        }
+        if let Some(old) = self.tonemap.take() {
+            self.retire_pass(old);
+        }
+        let Some(encode) = output_encoding(self.format, self.color_space, self.cfg.srgb_encode)
+            .or(offscreen.then_some(OutputEncode::PASSTHROUGH))
+        else {
+            return;
+        };
+        // FSR1 falling back to bilinear (see sync_fsr1_pass) wants LINEAR
+        // too; its own output is sampled 1:1, where the two agree.
+        // A fixed internal resolution is always scaled up NEAREST.
+        let filter = if scaled && internal.is_none() && self.cfg.upscaler != Upscaler::Nearest {
+            vk::Filter::LINEAR
+        } else {
+            vk::Filter::NEAREST
+        };
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        let descriptors = &mut self.descriptors.persistent;
+        match TonemapPass::new(
+            &self.device,
+            allocator,
+            descriptors,
+            self.pipeline_cache,
+            scene_extent,
+            filter,
+            self.format,
+            self.depth_format,
+            encode,
+        ) {
+            Ok(pass) => self.tonemap = Some(pass),
+            // Nothing else can encode for an HDR colour space: the display
+            // gets linear BT.709 where it expects scRGB or PQ.
+            Err(e) if is_hdr_color_space(self.color_space) => tracing::error!(
+                "tonemap pass unavailable ({e:#}); {} output is unencoded and will look wrong",
+                crate::swapchain::cs_name(self.color_space)
+            ),
+            Err(e) => tracing::warn!(
+                "tonemap pass unavailable ({e:#}); rendering to the {} / {} swapchain unencoded",
+                crate::swapchain::fmt_name(self.format),
+                crate::swapchain::cs_name(self.color_space)
+            ),
+        }
+        if scaled
+            && internal.is_none()
+            && self.tonemap.is_some()
+            && self.cfg.upscaler == Upscaler::Fsr1
+        {
+            self.sync_fsr1_pass();
+        }
+        self.sync_post_stack();
+    }
+
+    /// Select the tonemap curve. Takes effect on the next frame; no
+    /// swapchain or pipeline rebuild involved.
+    pub fn set_tonemap_operator(&mut self, op: TonemapOperator) {
+        self.cfg.tonemap = op;
+    }
+
+    pub fn tonemap_operator(&self) -> TonemapOperator {
+        self.cfg.tonemap
+    }
+
+    /// How bright SDR white (scene and text colour 1.0) is on scRGB and
+    /// HDR10 swapchains, in nits, clamped to MIN_SDR_WHITE_NITS..=
+    /// MAX_SDR_WHITE_NITS. No effect on SDR output. Takes effect on the
+    /// next frame, like the tonemap operator.
+    pub fn set_sdr_white_nits(&mut self, nits: f32) {
+        self.cfg.sdr_white_nits = if nits.is_finite() {
+            nits.clamp(MIN_SDR_WHITE_NITS, MAX_SDR_WHITE_NITS)
+        } else {
+            DEFAULT_SDR_WHITE_NITS
+        };
+    }
+
+    pub fn sdr_white_nits(&self) -> f32 {
+        self.cfg.sdr_white_nits
+    }
+
+    /// Whether a UNORM SDR swapchain gets its output sRGB-encoded by the
+    /// tonemap pass (default on). Off writes linear values straight to it,
+    /// which looks too dark next to an *_SRGB swapchain. Recreates the
+    /// swapchain if it changes.
+    pub fn set_srgb_encode(&mut self, on: bool) {
+        if self.cfg.srgb_encode == on {
+            return;
+        }
+        self.cfg.srgb_encode = on;
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    /// True while the scene goes through the tonemap pass (HDR colour space,
+    /// a UNORM swapchain being sRGB-encoded, a scaled scene or post effects,
+    /// and the pass built successfully).
+    pub fn tonemap_active(&self) -> bool {
+        self.tonemap.is_some()
+    }
+
+    /// Draw the fullscreen tonemap of the FP16 target (or of FSR1's
+    /// upscale of it), as the frame graph's
+    /// "tonemap" pass: the graph has already made the target sampleable
+    /// and opened a rendering scope on the swapchain image (with depth
+    /// bound, which the pipeline was built against).
+    pub(crate) fn record_tonemap(&self, cmd: vk::CommandBuffer) {
+        let Some(tm) = self.tonemap.as_ref() else {
+            return;
+        };
+        // The letterboxed part of the swapchain, or all of it.
+        let render_area = self.output_area();
+        // Not flipped, unlike the scene viewport: fullscreen.vert already
+        // maps UV (0,0) to the top-left in Vulkan's +Y-down clip space.
+        let vp = vk::Viewport {
+            x: render_area.offset.x as f32,
+            y: render_area.offset.y as f32,
+            width: render_area.extent.width as f32,
+            height: render_area.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let push = TonemapPush {
+            gamut: tm.encode.gamut,
+            operator: self.cfg.tonemap as u32,
+            encoding: tm.encode.encoding,
+            paper_white_nits: self.cfg.sdr_white_nits,
+            peak_nits: self.hdr_metadata.max_luminance.max(self.cfg.sdr_white_nits),
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, tm.pipeline);
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&vp));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&render_area));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                tm.layout,
+                0,
+                std::slice::from_ref(&tm.desc_set.set),
+                &[],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                tm.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            self.device.cmd_draw(cmd, 3, 1, 0, 0);
+        }
+    }
+}
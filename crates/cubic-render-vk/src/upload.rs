@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Batched, non-blocking buffer uploads on a dedicated transfer queue.
+//!
+//! `queue_buffer_copy` stages data and records the copy into the current
+//! batch; `flush` submits the batch once per frame, signalling the
+//! uploader's own timeline semaphore, and the frame's graphics submit waits
+//! on that value at VERTEX_INPUT. Staging memory is reclaimed once the
+//! timeline passes its batch, so nothing on the CPU ever blocks on a fence.
+//!
+//! Without a dedicated transfer family the same scheme runs on the graphics
+//! queue (still batched and fence-free, just not overlapping). Most
+//! textures don't go through here: their mip chains are blitted, which
+//! needs a graphics-capable queue (see create_texture). upload_texture_async
+//! builds its chain on the CPU instead and queues plain copies with
+//! `queue_staged`.
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use gpu_allocator::vulkan::{Allocation, Allocator};
+use gpu_allocator::MemoryLocation;
+
+use crate::resources::create_buffer_and_memory;
+use crate::semaphore_submit_info_signal;
+use crate::sync::create_timeline_semaphore;
+
+struct InFlightBatch {
+    value: u64,
+    cmd: vk::CommandBuffer,
+    staging: Vec<(vk::Buffer, Allocation)>,
+}
+
+pub(crate) struct TransferUploader {
+    queue: vk::Queue,
+    pool: vk::CommandPool,
+    timeline: vk::Semaphore,
+    // Last value submitted; the graphics submit waits for it.
+    submitted: u64,
+    // Open batch: command buffer being recorded + its staging buffers.
+    recording: Option<vk::CommandBuffer>,
+    batch_staging: Vec<(vk::Buffer, Allocation)>,
+    in_flight: Vec<InFlightBatch>,
+}
+
+impl TransferUploader {
+    pub(crate) fn new(device: &ash::Device, queue: vk::Queue, family: u32) -> Result<Self> {
+        let pool_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            queue_family_index: family,
+            flags: vk::CommandPoolCreateFlags::TRANSIENT,
+            ..Default::default()
+        };
+        let pool = unsafe { device.create_command_pool(&pool_info, None)? };
+        let timeline = create_timeline_semaphore(device, 0)?;
+        Ok(Self {
+            queue,
+            pool,
+            timeline,
+            submitted: 0,
+            recording: None,
+            batch_staging: Vec::new(),
+            in_flight: Vec::new(),
+        })
+    }
+
+    pub(crate) fn timeline(&self) -> vk::Semaphore {
+        self.timeline
+    }
+
+    /// Timeline value covering every upload flushed so far (0 = none yet).
+    pub(crate) fn submitted_value(&self) -> u64 {
+        self.submitted
+    }
+
+    /// Timeline value the transfer queue has reached: every batch up to it
+    /// has landed.
+    pub(crate) fn completed_value(&self, device: &ash::Device) -> u64 {
+        unsafe { device.get_semaphore_counter_value(self.timeline) }.unwrap_or(0)
+    }
+
+    /// Stage `data` and record a copy into `dst` at `dst_offset`. The data
+    /// only becomes visible to frames submitted after the next `flush`.
+    pub(crate) fn queue_buffer_copy(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        dst: vk::Buffer,
+        dst_offset: vk::DeviceSize,
+        data: &[u8],
+    ) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let size = data.len() as vk::DeviceSize;
+        self.queue_staged(device, allocator, data, |cmd, staging| {
+            let region = vk::BufferCopy {
+                src_offset: 0,
+                dst_offset,
+                size,
+            };
+            unsafe { device.cmd_copy_buffer(cmd, staging, dst, std::slice::from_ref(&region)) };
+        })?;
+        Ok(())
+    }
+
+    /// Stage `data` and let `record` copy out of the staging buffer into
+    /// the current batch. Returns the timeline value the batch will signal
+    /// once flushed and done.
+    pub(crate) fn queue_staged(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        data: &[u8],
+        record: impl FnOnce(vk::CommandBuffer, vk::Buffer),
+    ) -> Result<u64> {
+        let size = data.len() as vk::DeviceSize;
+        let (staging, mut staging_alloc) = create_buffer_and_memory(
+            device,
+            allocator,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            "transfer upload staging",
+        )?;
+        match staging_alloc.mapped_slice_mut() {
+            Some(mapped) => mapped[..data.len()].copy_from_slice(data),
+            None => {
+                unsafe { device.destroy_buffer(staging, None) };
+                let _ = allocator.free(staging_alloc);
+                return Err(anyhow!("transfer staging allocation not host-mapped"));
+            }
+        }
+
+        let cmd = match self.begin_batch(device) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                unsafe { device.destroy_buffer(staging, None) };
+                let _ = allocator.free(staging_alloc);
+                return Err(e);
+            }
+        };
+        record(cmd, staging);
+        self.batch_staging.push((staging, staging_alloc));
+        Ok(self.submitted + 1)
+    }
+
+    fn begin_batch(&mut self, device: &ash::Device) -> Result<vk::CommandBuffer> {
+        if let Some(cmd) = self.recording {
+            return Ok(cmd);
+        }
+        let ai = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            command_pool: self.pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        };
+        let cmd = unsafe { device.allocate_command_buffers(&ai)?[0] };
+        let bi = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+        unsafe { device.begin_command_buffer(cmd, &bi)? };
+        self.recording = Some(cmd);
+        Ok(cmd)
+    }
+
+    /// Submit the open batch, if any. Called once per frame before the
+    /// graphics submit, which then waits on `submitted_value()`.
+    pub(crate) fn flush(&mut self, device: &ash::Device) -> Result<()> {
+        let Some(cmd) = self.recording.take() else {
+            return Ok(());
+        };
+        unsafe { device.end_command_buffer(cmd)? };
+
+        let value = self.submitted + 1;
+        let signal =
+            semaphore_submit_info_signal(self.timeline, value, vk::PipelineStageFlags2::TRANSFER);
+        let cmd_info = vk::CommandBufferSubmitInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_SUBMIT_INFO,
+            command_buffer: cmd,
+            ..Default::default()
+        };
+        let submit = vk::SubmitInfo2 {
+            s_type: vk::StructureType::SUBMIT_INFO_2,
+            command_buffer_info_count: 1,
+            p_command_buffer_infos: &cmd_info,
+            signal_semaphore_info_count: 1,
+            p_signal_semaphore_infos: &signal,
+            ..Default::default()
+        };
+        unsafe {
+            device.queue_submit2(self.queue, std::slice::from_ref(&submit), vk::Fence::null())?
+        };
+        self.submitted = value;
+        self.in_flight.push(InFlightBatch {
+            value,
+            cmd,
+            staging: std::mem::take(&mut self.batch_staging),
+        });
+        Ok(())
+    }
+
+    /// Free staging buffers and command buffers of batches the transfer
+    /// timeline has passed. Non-blocking.
+    pub(crate) fn reclaim(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+        let done = self.completed_value(device);
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            if self.in_flight[i].value > done {
+                i += 1;
+                continue;
+            }
+            let batch = self.in_flight.swap_remove(i);
+            unsafe {
+                device.free_command_buffers(self.pool, std::slice::from_ref(&batch.cmd));
+            }
+            for (buffer, alloc) in batch.staging {
+                unsafe { device.destroy_buffer(buffer, None) };
+                let _ = allocator.free(alloc);
+            }
+        }
+    }
+
+    /// Tear everything down. Caller must have idled the device.
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        if let Some(cmd) = self.recording.take() {
+            unsafe { device.free_command_buffers(self.pool, std::slice::from_ref(&cmd)) };
+        }
+        for (buffer, alloc) in self.batch_staging.drain(..) {
+            unsafe { device.destroy_buffer(buffer, None) };
+            let _ = allocator.free(alloc);
+        }
+        for batch in self.in_flight.drain(..) {
+            for (buffer, alloc) in batch.staging {
+                unsafe { device.destroy_buffer(buffer, None) };
+                let _ = allocator.free(alloc);
+            }
+        }
+        unsafe {
+            // Destroying the pool frees any command buffers still allocated.
+            device.destroy_command_pool(self.pool, None);
+            device.destroy_semaphore(self.timeline, None);
+        }
+    }
+}
@@ -0,0 +1,469 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Render scale and upscaling.
+//!
+//! With a render scale below 1.0 the scene (depth prepass, sky, draws,
+//! debug lines) renders into the tonemap pass's intermediate target at
+//! scale × the swapchain extent, and gets brought back up to full size on
+//! the way to the swapchain, so fill-rate-bound devices can trade
+//! sharpness for frame time. Text and egui still draw at full resolution
+//! on top. The upscale filter is selectable:
+//!
+//! - Nearest: the tonemap pass samples the small target with NEAREST.
+//!   Blocky, but free and exact at 0.5.
+//! - Bilinear: the same with LINEAR. Soft; the default.
+//! - Fsr1: two compute passes after the scene, modelled on AMD's FSR 1.0:
+//!   an edge-adaptive Lanczos-ish upscale to full size (fsr1_easu.comp)
+//!   and a contrast-adaptive sharpen (fsr1_rcas.comp). The tonemap pass
+//!   then samples their output 1:1. Noticeably crisper than bilinear at
+//!   0.5–0.75 for two full-resolution dispatches. Without the shaders
+//!   built it falls back to bilinear.
+//!
+//! The scale is fixed until set_render_scale changes it, which rebuilds the
+//! swapchain-sized targets like any other swapchain recreation.
+
+use anyhow::Result;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::vulkan::{Allocation, Allocator};
+
+use crate::descriptors::{DescriptorAllocator, PooledSet};
+use crate::pipeline::{create_compute_pipeline, load_spv_file, shader_dir};
+use crate::resources::create_color_target;
+use crate::tonemap::HDR_TARGET_FORMAT;
+use crate::{RetiredPass, VkRenderer};
+use cubic_render::RenderSize;
+
+/// Smallest render scale set_render_scale accepts.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+/// RCAS strength in stops below full: 0.0 is the sharpest, each +1.0
+/// halves it. 0.2 is FSR's usual default.
+const FSR1_SHARPNESS: f32 = 0.2;
+
+/// How a scene rendered below output resolution is brought back up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Upscaler {
+    Nearest,
+    #[default]
+    Bilinear,
+    /// Edge-adaptive upscale + sharpen compute passes (see module docs).
+    Fsr1,
+}
+
+impl Upscaler {
+    pub(crate) fn from_name(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Some(Self::Nearest),
+            "bilinear" | "linear" => Some(Self::Bilinear),
+            "fsr1" | "fsr" => Some(Self::Fsr1),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Bilinear => "bilinear",
+            Self::Fsr1 => "FSR1",
+        }
+    }
+}
+
+/// `extent` times `scale`, rounded, at least 1x1.
+pub(crate) fn scaled_extent(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+    if scale >= 1.0 {
+        return extent;
+    }
+    let dim = |d: u32| ((d as f32 * scale).round() as u32).clamp(1, d.max(1));
+    vk::Extent2D {
+        width: dim(extent.width),
+        height: dim(extent.height),
+    }
+}
+
+/// Push constants for both fsr1_*.comp; layout must match their `Fsr1`
+/// block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct Fsr1Push {
+    in_size: [f32; 2],
+    out_size: [f32; 2],
+    sharpness: f32,
+}
+
+/// A full-size FP16 storage target.
+struct Fsr1Target {
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+}
+
+impl Fsr1Target {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        name: &str,
+    ) -> Result<Self> {
+        let (image, alloc, view) = create_color_target(
+            device,
+            allocator,
+            extent,
+            HDR_TARGET_FORMAT,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            name,
+        )?;
+        Ok(Self { image, alloc, view })
+    }
+
+    fn destroy(self, device: &ash::Device, allocator: &mut Allocator) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        let _ = allocator.free(self.alloc);
+    }
+}
+
+/// The FSR1 compute passes and their full-size targets. The EASU pass
+/// reads the scene target into `easu`; RCAS reads that into `output`,
+/// which the tonemap pass samples.
+pub(crate) struct Fsr1Pass {
+    easu: Fsr1Target,
+    output: Fsr1Target,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    // [EASU, RCAS]: each one source sampler and one storage destination.
+    desc_sets: [PooledSet; 2],
+    layout: vk::PipelineLayout,
+    easu_pipeline: vk::Pipeline,
+    rcas_pipeline: vk::Pipeline,
+    in_extent: vk::Extent2D,
+    out_extent: vk::Extent2D,
+}
+
+impl Fsr1Pass {
+    fn new(
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+        cache: vk::PipelineCache,
+        scene_view: vk::ImageView,
+        in_extent: vk::Extent2D,
+        out_extent: vk::Extent2D,
+    ) -> Result<Self> {
+        // Shaders first, as with the tonemap pass: missing .spv files are
+        // the usual failure, and nothing needs cleaning up yet.
+        let easu_words = load_spv_file(&shader_dir().join("fsr1_easu.comp.spv"))?;
+        let rcas_words = load_spv_file(&shader_dir().join("fsr1_rcas.comp.spv"))?;
+
+        let bindings = [
+            vk::DescriptorSetLayoutBinding {
+                binding: 0,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                ..Default::default()
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                ..Default::default()
+            },
+        ];
+        let set_layout_ci = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            binding_count: bindings.len() as u32,
+            p_bindings: bindings.as_ptr(),
+            ..Default::default()
+        };
+        let set_layout = unsafe { device.create_descriptor_set_layout(&set_layout_ci, None)? };
+        let push_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: std::mem::size_of::<Fsr1Push>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: 1,
+            p_set_layouts: &set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_range,
+            ..Default::default()
+        };
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+        let pipelines =
+            create_compute_pipeline(device, cache, layout, &easu_words).and_then(|easu| {
+                match create_compute_pipeline(device, cache, layout, &rcas_words) {
+                    Ok(rcas) => Ok((easu, rcas)),
+                    Err(e) => {
+                        unsafe { device.destroy_pipeline(easu, None) };
+                        Err(e)
+                    }
+                }
+            });
+        let (easu_pipeline, rcas_pipeline) = match pipelines {
+            Ok(p) => p,
+            Err(e) => {
+                unsafe {
+                    device.destroy_pipeline_layout(layout, None);
+                    device.destroy_descriptor_set_layout(set_layout, None);
+                }
+                return Err(e);
+            }
+        };
+
+        let easu = Fsr1Target::new(device, allocator, out_extent, "upscale target")?;
+        let output = Fsr1Target::new(device, allocator, out_extent, "upscale target")?;
+
+        // Every tap lands on a texel centre (EASU) or is a texelFetch
+        // (RCAS), so the filter doesn't matter; CLAMP handles the borders.
+        let sampler_ci = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        };
+        let sampler = unsafe { device.create_sampler(&sampler_ci, None)? };
+
+        let desc_sets = [
+            descriptors.allocate(device, set_layout)?,
+            descriptors.allocate(device, set_layout)?,
+        ];
+
+        for (set, src, dst) in [
+            (desc_sets[0].set, scene_view, easu.view),
+            (desc_sets[1].set, easu.view, output.view),
+        ] {
+            let src_info = vk::DescriptorImageInfo {
+                sampler,
+                image_view: src,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            };
+            let dst_info = vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: dst,
+                image_layout: vk::ImageLayout::GENERAL,
+            };
+            let writes = [
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: set,
+                    dst_binding: 0,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    p_image_info: &src_info,
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: set,
+                    dst_binding: 1,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                    p_image_info: &dst_info,
+                    ..Default::default()
+                },
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+
+        Ok(Self {
+            easu,
+            output,
+            sampler,
+            set_layout,
+            desc_sets,
+            layout,
+            easu_pipeline,
+            rcas_pipeline,
+            in_extent,
+            out_extent,
+        })
+    }
+
+    /// Tear everything down. Caller must have idled the device.
+    pub(crate) fn destroy(
+        self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        for set in self.desc_sets {
+            descriptors.free(device, set);
+        }
+        unsafe {
+            device.destroy_pipeline(self.easu_pipeline, None);
+            device.destroy_pipeline(self.rcas_pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        self.easu.destroy(device, allocator);
+        self.output.destroy(device, allocator);
+    }
+
+    /// (image, view) of the EASU result, for the frame graph.
+    pub(crate) fn easu_target(&self) -> (vk::Image, vk::ImageView) {
+        (self.easu.image, self.easu.view)
+    }
+
+    /// (image, view) of the sharpened result the tonemap pass samples.
+    pub(crate) fn output_target(&self) -> (vk::Image, vk::ImageView) {
+        (self.output.image, self.output.view)
+    }
+}
+
+impl RetiredPass for Fsr1Pass {
+    fn destroy_retired(
+        self: Box<Self>,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        descriptors: &mut DescriptorAllocator,
+    ) {
+        (*self).destroy(device, allocator, descriptors);
+    }
+}
+
+impl VkRenderer {
+    /// Size the scene renders at: the tonemap target's, which is the
+    /// swapchain extent (letterboxed, see letterbox.rs) scaled by the
+    /// render scale, or the fixed internal resolution (or the swapchain
+    /// extent itself, without the pass).
+    pub(crate) fn scene_extent(&self) -> vk::Extent2D {
+        self.tonemap.as_ref().map_or(self.extent, |tm| tm.extent)
+    }
+
+    /// Build the FSR1 passes over the current tonemap target and point the
+    /// tonemap pass at their output. Called from sync_tonemap_pass, on its
+    /// new tonemap pass, when the scene is scaled and FSR1 selected; on
+    /// failure the tonemap pass keeps sampling the scene target, i.e.
+    /// bilinear.
+    pub(crate) fn sync_fsr1_pass(&mut self) {
+        let Some(tm) = self.tonemap.as_ref() else {
+            return;
+        };
+        let output = self.output_area().extent;
+        let allocator = self.allocator.as_mut().expect("allocator missing");
+        match Fsr1Pass::new(
+            &self.device,
+            allocator,
+            &mut self.descriptors.persistent,
+            self.pipeline_cache,
+            tm.view,
+            tm.extent,
+            output,
+        ) {
+            Ok(pass) => {
+                tm.set_source(&self.device, pass.output.view);
+                self.fsr1 = Some(pass);
+            }
+            Err(e) => tracing::warn!("FSR1 upscaler unavailable ({e:#}); upscaling bilinear"),
+        }
+    }
+
+    /// Render the scene at `scale` × the window's resolution (clamped to
+    /// MIN_RENDER_SCALE..=1.0) and upscale it with the selected Upscaler.
+    /// Recreates the swapchain if it changes.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        let scale = if scale.is_finite() {
+            scale.clamp(MIN_RENDER_SCALE, 1.0)
+        } else {
+            1.0
+        };
+        if self.cfg.render_scale == scale {
+            return;
+        }
+        self.cfg.render_scale = scale;
+        self.rebuild_scaled_targets();
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.cfg.render_scale
+    }
+
+    /// Select the filter a scaled scene is upscaled with. Rebuilds the
+    /// swapchain-sized targets if it changes while the scene is scaled.
+    pub fn set_upscaler(&mut self, upscaler: Upscaler) {
+        if self.cfg.upscaler == upscaler {
+            return;
+        }
+        self.cfg.upscaler = upscaler;
+        if self.cfg.render_scale < 1.0 {
+            self.rebuild_scaled_targets();
+        }
+    }
+
+    pub fn upscaler(&self) -> Upscaler {
+        self.cfg.upscaler
+    }
+
+    fn rebuild_scaled_targets(&mut self) {
+        let want = RenderSize {
+            width: self.extent.width,
+            height: self.extent.height,
+        };
+        let _ = self.recreate_swapchain(want);
+    }
+
+    /// The FSR1 "easu" pass: scene target -> full-size EASU target.
+    pub(crate) fn record_fsr1_easu(&self, cmd: vk::CommandBuffer) {
+        if let Some(fsr) = self.fsr1.as_ref() {
+            self.dispatch_fsr1(cmd, fsr, fsr.easu_pipeline, fsr.desc_sets[0].set);
+        }
+    }
+
+    /// The FSR1 "rcas" pass: EASU target -> sharpened output.
+    pub(crate) fn record_fsr1_rcas(&self, cmd: vk::CommandBuffer) {
+        if let Some(fsr) = self.fsr1.as_ref() {
+            self.dispatch_fsr1(cmd, fsr, fsr.rcas_pipeline, fsr.desc_sets[1].set);
+        }
+    }
+
+    fn dispatch_fsr1(
+        &self,
+        cmd: vk::CommandBuffer,
+        fsr: &Fsr1Pass,
+        pipeline: vk::Pipeline,
+        set: vk::DescriptorSet,
+    ) {
+        let push = Fsr1Push {
+            in_size: [fsr.in_extent.width as f32, fsr.in_extent.height as f32],
+            out_size: [fsr.out_extent.width as f32, fsr.out_extent.height as f32],
+            sharpness: FSR1_SHARPNESS,
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, pipeline);
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                fsr.layout,
+                0,
+                std::slice::from_ref(&set),
+                &[],
+            );
+            self.device.cmd_push_constants(
+                cmd,
+                fsr.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&push),
+            );
+            // 8x8 workgroups, matching local_size in both shaders.
+            self.device.cmd_dispatch(
+                cmd,
+                fsr.out_extent.width.div_ceil(8),
+                fsr.out_extent.height.div_ceil(8),
+                1,
+            );
+        }
+    }
+}
@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Which parts of the Khronos validation layer run (ValidationLayers), and
+//! what happens to their messages.
+//!
+//! The layer is on by default in debug builds and off in release builds.
+//! CUBIC_VALIDATION (or the app's own setting, via
+//! VkRenderer::new_with_validation) overrides that either way, so a
+//! release build can be diagnosed on a user's machine: see
+//! ValidationLayers::parse for the syntax. The layer ships with the Vulkan
+//! SDK, not with drivers; whatever isn't installed is skipped with a
+//! warning (see instance.rs).
+//!
+//! Every message goes to tracing under the `vulkan` target at its own
+//! severity (ERROR -> error, WARNING -> warn, INFO -> info, VERBOSE ->
+//! trace), tagged with its VUID name and number, so RUST_LOG filters them
+//! like anything else. On top of that:
+//!
+//! - suppression: messages whose id name (e.g.
+//!   "VUID-vkCmdDraw-None-02699") or id number (decimal or 0x hex) is on
+//!   the list are counted and dropped, for known driver/layer false
+//!   positives;
+//! - counters: errors, warnings and suppressed messages since startup,
+//!   surfaced in frame_stats() so a test run can assert on them;
+//! - panic_on_error: for CI. The callback can't unwind into the driver, so
+//!   it keeps the first error and render() panics with it once the frame
+//!   is done.
+//!
+//! CUBIC_VALIDATION_SUPPRESS (comma-separated ids) and
+//! CUBIC_VALIDATION_PANIC=1 apply on top of whatever the app sets.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use ash::vk;
+use cubic_render::ValidationCounts;
+
+use crate::VkRenderer;
+
+/// Which validation checks run. Fixed for the instance's lifetime, so it
+/// is chosen when the renderer is built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidationLayers {
+    /// VK_LAYER_KHRONOS_validation, with the messenger below.
+    pub enabled: bool,
+    /// GPU-assisted validation: shader instrumentation catching
+    /// out-of-bounds descriptor and buffer access. Slow.
+    pub gpu_assisted: bool,
+    /// Best-practices warnings: legal usage that is likely a mistake or
+    /// slow on some vendor.
+    pub best_practices: bool,
+}
+
+impl Default for ValidationLayers {
+    /// The layer alone in debug builds; nothing in release builds.
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            ..Self::OFF
+        }
+    }
+}
+
+impl ValidationLayers {
+    pub const OFF: Self = Self {
+        enabled: false,
+        gpu_assisted: false,
+        best_practices: false,
+    };
+
+    /// A comma-separated list: `off` (or `0`), `on` (or `1`) for the layer
+    /// alone, `gpu` and `best` for GPU-assisted and best-practices checks
+    /// (each implying `on`), `all` for everything. None if a word isn't
+    /// one of these.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut layers = Self::OFF;
+        for word in s.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            match word.to_ascii_lowercase().as_str() {
+                "off" | "0" => layers = Self::OFF,
+                "on" | "1" => layers.enabled = true,
+                "gpu" => {
+                    layers.enabled = true;
+                    layers.gpu_assisted = true;
+                }
+                "best" => {
+                    layers.enabled = true;
+                    layers.best_practices = true;
+                }
+                "all" => {
+                    layers = Self {
+                        enabled: true,
+                        gpu_assisted: true,
+                        best_practices: true,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(layers)
+    }
+
+    /// CUBIC_VALIDATION, if it's set to something parse accepts.
+    pub fn from_env() -> Option<Self> {
+        let var = std::env::var("CUBIC_VALIDATION").ok()?;
+        if var.trim().is_empty() {
+            return None;
+        }
+        let layers = Self::parse(&var);
+        if layers.is_none() {
+            tracing::warn!(
+                "CUBIC_VALIDATION={var:?} not understood (expected off, on, gpu, best or all); \
+                 ignoring it"
+            );
+        }
+        layers
+    }
+}
+
+/// How validation messages are filtered and escalated; see the module
+/// docs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// Message id names or numbers to drop.
+    pub suppress: Vec<String>,
+    /// Panic (after the frame) on the first validation error.
+    pub panic_on_error: bool,
+}
+
+impl ValidationPolicy {
+    fn from_env() -> Self {
+        let suppress = std::env::var("CUBIC_VALIDATION_SUPPRESS")
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            suppress,
+            panic_on_error: std::env::var("CUBIC_VALIDATION_PANIC").ok().as_deref() == Some("1"),
+        }
+    }
+
+    fn matches(&self, name: &str, number: i32) -> bool {
+        self.suppress.iter().any(|id| {
+            id == name
+                || id.parse::<i32>().is_ok_and(|n| n == number)
+                || id
+                    .strip_prefix("0x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .is_some_and(|n| n as i32 == number)
+        })
+    }
+}
+
+/// Shared with the debug messenger callback (its user data), which can
+/// run on driver threads: hence the atomics and locks.
+pub(crate) struct ValidationState {
+    env: ValidationPolicy,
+    policy: Mutex<ValidationPolicy>,
+    errors: AtomicU64,
+    warnings: AtomicU64,
+    suppressed: AtomicU64,
+    // First error seen under panic_on_error, until render() takes it.
+    fatal: Mutex<Option<String>>,
+}
+
+impl ValidationState {
+    pub(crate) fn from_env() -> Self {
+        let env = ValidationPolicy::from_env();
+        Self {
+            policy: Mutex::new(env.clone()),
+            env,
+            errors: AtomicU64::new(0),
+            warnings: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            fatal: Mutex::new(None),
+        }
+    }
+
+    /// Replace the app's part of the policy; the environment's stays.
+    pub(crate) fn set_policy(&self, mut policy: ValidationPolicy) {
+        policy.suppress.extend(self.env.suppress.iter().cloned());
+        policy.panic_on_error |= self.env.panic_on_error;
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    pub(crate) fn policy(&self) -> ValidationPolicy {
+        self.policy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Take over another state's policy and counts, e.g. the lost device's
+    /// after a rebuild (see device_lost.rs).
+    pub(crate) fn inherit(&self, old: &ValidationState) {
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner()) = old.policy();
+        for (new, old) in [
+            (&self.errors, &old.errors),
+            (&self.warnings, &old.warnings),
+            (&self.suppressed, &old.suppressed),
+        ] {
+            new.fetch_add(old.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn counts(&self) -> ValidationCounts {
+        ValidationCounts {
+            errors: self.errors.load(Ordering::Relaxed),
+            warnings: self.warnings.load(Ordering::Relaxed),
+            suppressed: self.suppressed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The error panic_on_error is holding, if any.
+    pub(crate) fn take_fatal(&self) -> Option<String> {
+        self.fatal.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    fn handle(
+        &self,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        types: vk::DebugUtilsMessageTypeFlagsEXT,
+        name: &str,
+        number: i32,
+        message: &str,
+    ) {
+        let policy = self.policy.lock().unwrap_or_else(|e| e.into_inner());
+        if policy.matches(name, number) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let panic_on_error = policy.panic_on_error;
+        drop(policy);
+        if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            tracing::error!(target: "vulkan", id = name, number, ?types, "{message}");
+            if panic_on_error {
+                self.fatal
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert_with(|| format!("{name}: {message}"));
+            }
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            self.warnings.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(target: "vulkan", id = name, number, ?types, "{message}");
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            tracing::info!(target: "vulkan", id = name, number, ?types, "{message}");
+        } else {
+            tracing::trace!(target: "vulkan", id = name, number, ?types, "{message}");
+        }
+    }
+}
+
+impl VkRenderer {
+    /// Set which validation messages are dropped and whether an error
+    /// panics. CUBIC_VALIDATION_SUPPRESS/CUBIC_VALIDATION_PANIC still apply
+    /// on top. Does nothing visible without the validation layer (see
+    /// validation_layers).
+    pub fn set_validation_policy(&mut self, policy: ValidationPolicy) {
+        self.validation.set_policy(policy);
+    }
+
+    pub fn validation_policy(&self) -> ValidationPolicy {
+        self.validation.policy()
+    }
+
+    /// The validation checks actually running: those asked for, less any
+    /// this system doesn't have installed.
+    pub fn validation_layers(&self) -> ValidationLayers {
+        self.validation_layers
+    }
+}
+
+/// The debug messenger's callback; `user` is the renderer's
+/// ValidationState (see create_debug_messenger).
+pub(crate) unsafe extern "system" fn debug_callback(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    user: *mut std::ffi::c_void,
+) -> vk::Bool32 {
+    if data.is_null() || user.is_null() {
+        return vk::FALSE;
+    }
+    // SAFETY: the layer hands us valid callback data for the duration of
+    // the call, and `user` is the ValidationState the renderer keeps
+    // alive (boxed) until after the messenger is destroyed.
+    let (data, state) = unsafe { (&*data, &*(user as *const ValidationState)) };
+    let cstr = |p: *const std::ffi::c_char| {
+        if p.is_null() {
+            std::borrow::Cow::Borrowed("")
+        } else {
+            unsafe { std::ffi::CStr::from_ptr(p) }.to_string_lossy()
+        }
+    };
+    let name = cstr(data.p_message_id_name);
+    let message = cstr(data.p_message);
+    state.handle(severity, types, &name, data.message_id_number, &message);
+    vk::FALSE
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Split screen and multi-view: set_viewports draws the scene once per
+//! ViewportCamera, each into its own rectangle of the frame through its
+//! own camera (local multiplayer, an editor's top/side/perspective views).
+//!
+//! Each viewport gets a camera block of its own, built like a render
+//! target's (see render_target.rs): model matrices stay relative to
+//! set_camera's camera, so the block shifts by the offset between the two.
+//! The scene pass (and the depth prepass and object-id pass with it) then
+//! repeats sky, indirect and direct draws per viewport, with the
+//! viewport's rectangle as viewport and scissor; everything after the
+//! scene (post, tonemap, text, egui) is over the whole frame as before.
+//!
+//! set_camera's camera is still the one shadow cascades, light selection
+//! and draw sorting follow, so a viewport looking from anywhere else gets
+//! no shadows. With more than one viewport the GPU cull pass keeps every
+//! draw, since it tests one frustum; the CPU test of direct draws keeps a
+//! draw if any viewport sees it. Viewports are ignored on a pre-rotated
+//! swapchain.
+
+use anyhow::Result;
+use ash::vk;
+use cubic_math::{Camera, Mat4};
+use cubic_render::{clip, DepthConvention};
+
+use crate::render_target::target_view_proj;
+use crate::resources::CameraUbo;
+use crate::swapchain::pre_rotation;
+use crate::VkRenderer;
+
+/// One set_viewports view: a camera and where on screen it's drawn.
+#[derive(Clone, Copy, Debug)]
+pub struct ViewportCamera {
+    pub camera: Camera,
+    /// x, y, width, height as fractions of the frame, (0, 0) at the top
+    /// left: [0.0, 0.5, 1.0, 0.5] is the bottom half.
+    pub rect: [f32; 4],
+}
+
+/// One rectangle of the scene pass and the camera it's drawn with, for
+/// this frame.
+#[derive(Clone, Copy)]
+pub(crate) struct SceneView {
+    /// In scene-target pixels.
+    pub(crate) area: vk::Rect2D,
+    /// What the view's camera block holds.
+    pub(crate) view_proj: Mat4,
+    /// Rotation only, for the sky.
+    pub(crate) sky_view_proj: Mat4,
+    /// Dynamic offsets of the camera set.
+    pub(crate) offsets: [u32; 2],
+}
+
+impl SceneView {
+    /// `area` as a viewport, flipped like every scene viewport so NDC
+    /// means what the projection says it does.
+    pub(crate) fn viewport(&self) -> vk::Viewport {
+        vk::Viewport {
+            x: self.area.offset.x as f32,
+            y: (self.area.offset.y + self.area.extent.height as i32) as f32,
+            width: self.area.extent.width as f32,
+            height: -(self.area.extent.height as f32),
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+/// `rect` (fractions) in pixels of `extent`; None if it covers none.
+fn pixel_area(rect: [f32; 4], extent: vk::Extent2D) -> Option<vk::Rect2D> {
+    let [x, y, w, h] = rect.map(|v| {
+        if v.is_finite() {
+            v.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    });
+    let (fw, fh) = (extent.width as f32, extent.height as f32);
+    let x0 = (x * fw).round() as u32;
+    let y0 = (y * fh).round() as u32;
+    let x1 = (((x + w) * fw).round() as u32).min(extent.width);
+    let y1 = (((y + h) * fh).round() as u32).min(extent.height);
+    (x1 > x0 && y1 > y0).then_some(vk::Rect2D {
+        offset: vk::Offset2D {
+            x: x0 as i32,
+            y: y0 as i32,
+        },
+        extent: vk::Extent2D {
+            width: x1 - x0,
+            height: y1 - y0,
+        },
+    })
+}
+
+/// Looking from the same place the same way, so the cascades fit it.
+fn same_view(a: &Camera, b: &Camera) -> bool {
+    a.position == b.position && a.yaw == b.yaw && a.pitch == b.pitch
+}
+
+impl VkRenderer {
+    /// Draw the scene once per viewport (see module docs), in order; an
+    /// empty slice goes back to one full-frame view through set_camera's
+    /// camera. Rectangles are clamped to the frame; empty ones are dropped.
+    pub fn set_viewports(&mut self, viewports: &[ViewportCamera]) {
+        self.viewports = viewports.to_vec();
+    }
+
+    pub fn viewports(&self) -> &[ViewportCamera] {
+        &self.viewports
+    }
+
+    /// Every viewport's CameraUbo: `main_ubo` (the scene's) with the view
+    /// swapped for the viewport's, shadows off unless it looks from
+    /// `main`; and the frame's SceneViews to go with them.
+    pub(crate) fn write_viewport_uniforms(
+        &mut self,
+        main: &Camera,
+        main_ubo: &CameraUbo,
+    ) -> Result<()> {
+        self.viewport_views.clear();
+        if pre_rotation(self.pre_transform) != Mat4::IDENTITY {
+            return Ok(());
+        }
+        let extent = self.scene_extent();
+        for i in 0..self.viewports.len() {
+            let ViewportCamera { camera, rect } = self.viewports[i];
+            let Some(area) = pixel_area(rect, extent) else {
+                continue;
+            };
+            let view_proj = target_view_proj(main, &camera, area.extent);
+            let mut light_dir = main_ubo.light_dir;
+            if !same_view(main, &camera) {
+                light_dir[3] = 0.0;
+            }
+            let ubo = CameraUbo {
+                view_proj: view_proj.to_cols_array_2d(),
+                view_forward: camera.forward().extend(0.0).to_array(),
+                light_dir,
+                ..*main_ubo
+            };
+            let offset = self.upload_uniform(&ubo)?;
+            let aspect = area.extent.width as f32 / area.extent.height as f32;
+            self.viewport_views.push(SceneView {
+                area,
+                view_proj,
+                sky_view_proj: clip::camera_view_proj(&camera, aspect, DepthConvention::ReverseZ),
+                offsets: self.frame_uniforms.with_camera(offset),
+            });
+        }
+        Ok(())
+    }
+
+    /// The views the scene is drawn through this frame: the viewports', or
+    /// else set_camera's camera over the whole scene target.
+    pub(crate) fn scene_views(&self) -> Vec<SceneView> {
+        if !self.viewport_views.is_empty() {
+            return self.viewport_views.clone();
+        }
+        let view_proj = self.camera_view_proj(&self.camera);
+        vec![SceneView {
+            area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.scene_extent(),
+            },
+            view_proj,
+            sky_view_proj: view_proj,
+            offsets: self.frame_uniforms.main(),
+        }]
+    }
+
+    /// Set `view`'s viewport and scissor and bind its camera block with
+    /// the other sets, for a pipeline on the scene layout.
+    pub(crate) fn bind_scene_view(
+        &self,
+        cmd: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        sets: &[vk::DescriptorSet],
+        view: &SceneView,
+    ) {
+        unsafe {
+            self.device
+                .cmd_set_viewport(cmd, 0, std::slice::from_ref(&view.viewport()));
+            self.device
+                .cmd_set_scissor(cmd, 0, std::slice::from_ref(&view.area));
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                sets,
+                &view.offsets,
+            );
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Egui overlay for the wgpu backend — the counterpart of cubic-render-vk's
+//! and cubic-render-gl's egui_overlay.rs, painting with egui-wgpu. Same
+//! contract: cubic-app hands over a tessellated frame via queue_egui and
+//! it's drawn over the scene by the next render().
+
+use egui_wgpu::wgpu;
+
+use crate::WgpuRenderer;
+
+pub(crate) struct EguiFrame {
+    pub(crate) textures_delta: egui::TexturesDelta,
+    pub(crate) paint_jobs: Vec<egui::ClippedPrimitive>,
+    pub(crate) screen_width: u32,
+    pub(crate) screen_height: u32,
+    pub(crate) pixels_per_point: f32,
+}
+
+pub(crate) fn build_egui_renderer(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> egui_wgpu::Renderer {
+    // No depth or MSAA: the overlay gets its own pass over the resolved
+    // swapchain image.
+    egui_wgpu::Renderer::new(device, format, egui_wgpu::RendererOptions::default())
+}
+
+impl WgpuRenderer {
+    /// Record a staged egui frame (see Renderer::queue_egui) into `encoder`
+    /// as a pass loading `target`. Called from render() after the scene
+    /// pass. Returns the command buffers egui-wgpu wants submitted before
+    /// `encoder`'s (its buffer uploads); empty if nothing is staged.
+    pub(crate) fn paint_egui(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let Some(frame) = self.egui_pending.take() else {
+            return Vec::new();
+        };
+        for (id, delta) in &frame.textures_delta.set {
+            self.egui_renderer
+                .update_texture(&self.device, &self.queue, *id, delta);
+        }
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [frame.screen_width, frame.screen_height],
+            pixels_per_point: frame.pixels_per_point,
+        };
+        let uploads = self.egui_renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            encoder,
+            &frame.paint_jobs,
+            &screen,
+        );
+        {
+            let mut pass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("egui"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                })
+                .forget_lifetime();
+            self.egui_renderer
+                .render(&mut pass, &frame.paint_jobs, &screen);
+        }
+        // After recording, so this frame can still draw with them; wgpu
+        // keeps the textures alive until the GPU is done with them.
+        for id in &frame.textures_delta.free {
+            self.egui_renderer.free_texture(id);
+        }
+        uploads
+    }
+}
@@ -0,0 +1,800 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Renderer on wgpu, for platforms where raw Vulkan or EGL is awkward
+//! (macOS through Metal, later WebGPU). Selected with `--backend wgpu`.
+//!
+//! Between the GL and Vulkan backends in scope: meshes, the camera and the
+//! egui overlay, on whatever native API wgpu picks, but no textures,
+//! shadows, HDR or GPU-driven culling yet (draws are frustum-culled on
+//! the CPU instead). Uses the same cubic-render types as the others
+//! (Vertex, PushData, MeshHandle, Camera), and the same conventions:
+//! right-handed, reverse-Z with an infinite far plane (clear depth 0,
+//! GREATER_OR_EQUAL), model matrices camera-relative. wgpu's clip space
+//! is y-up with depth in [0, 1], which is what cubic-render's clip module
+//! already produces for Vulkan's flipped viewport, so the matrices go
+//! through untouched.
+
+mod egui_overlay;
+
+use anyhow::{anyhow, Context, Result};
+use cubic_math::{Camera, Mat4};
+use cubic_render::clip::{self, Frustum};
+use cubic_render::{
+    draw_depth, CullCounts, DepthConvention, DrawSortKey, FramePacer, FrameStats,
+    FrameStatsTracker, LatencyMode, MemoryKind, MeshHandle, PresentMode, PushData, RenderError,
+    RenderResult, RenderSize, Renderer, StreamCounts, SurfaceChanged, SurfaceInfo, Vertex,
+};
+use egui_wgpu::wgpu;
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use wgpu::util::DeviceExt as _;
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Per-draw data in the draws storage buffer (see mesh.wgsl Draw).
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct GpuDraw {
+    model: [[f32; 4]; 4],
+    tint: [f32; 4],
+}
+
+struct Mesh {
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    index_count: u32,
+    // Mesh-space bounding sphere for frustum culling; None for no
+    // vertices.
+    bounds: Option<[f32; 4]>,
+}
+
+pub struct WgpuRenderer {
+    // Dropped in declaration order: everything made from the device goes
+    // before it, and the surface before the instance.
+    egui_renderer: egui_wgpu::Renderer,
+    egui_pending: Option<egui_overlay::EguiFrame>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    camera_buffer: wgpu::Buffer,
+    // Grown (never shrunk) to the largest frame's draw count; the bind
+    // group is rebuilt with it.
+    draw_buffer: wgpu::Buffer,
+    draw_capacity: u64,
+    bind_group: wgpu::BindGroup,
+    depth: wgpu::TextureView,
+    // Slot per MeshHandle; None once freed, reused by the next upload.
+    meshes: Vec<Option<Mesh>>,
+    draws: Vec<(MeshHandle, GpuDraw)>,
+    camera: Camera,
+    // None between suspend() and resume(), which makes a new one from the
+    // raw handles below.
+    surface: Option<wgpu::Surface<'static>>,
+    config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
+    queue: wgpu::Queue,
+    device: wgpu::Device,
+    adapter_info: wgpu::AdapterInfo,
+    instance: wgpu::Instance,
+    display_raw: RawDisplayHandle,
+    window_raw: RawWindowHandle,
+    size: RenderSize,
+    clear: [f32; 4],
+    vsync: bool,
+    pacer: FramePacer,
+    frame_stats: FrameStatsTracker,
+    latency_mode: LatencyMode,
+    // Resizes and reconfigurations not yet picked up by
+    // take_surface_change, coalesced.
+    surface_change: Option<SurfaceChanged>,
+}
+
+impl WgpuRenderer {
+    /// The present mode `vsync` asks for, from what the surface offers:
+    /// FIFO always exists; without vsync, immediate, else mailbox, else
+    /// FIFO anyway.
+    fn pick_present_mode(&self, vsync: bool) -> wgpu::PresentMode {
+        if vsync {
+            return wgpu::PresentMode::Fifo;
+        }
+        [wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox]
+            .into_iter()
+            .find(|m| self.present_modes.contains(m))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    /// Apply size, vsync and latency mode to the surface (and the depth
+    /// buffer), recording the change for take_surface_change. Skipped at
+    /// zero size, where wgpu can't configure a surface; render() pauses
+    /// there anyway.
+    fn configure_surface(&mut self) {
+        let Some(surface) = &self.surface else {
+            return;
+        };
+        if self.size.width == 0 || self.size.height == 0 {
+            return;
+        }
+        let _span = tracing::debug_span!(
+            "recreate",
+            width = self.size.width,
+            height = self.size.height
+        )
+        .entered();
+        let previous = self
+            .surface_change
+            .map_or(self.surface_info(), |c| c.previous);
+        self.config.width = self.size.width;
+        self.config.height = self.size.height;
+        self.config.present_mode = self.pick_present_mode(self.vsync);
+        // Low latency: the CPU may only run one frame ahead, the wgpu
+        // counterpart of Vulkan's present wait.
+        self.config.desired_maximum_frame_latency = match self.latency_mode {
+            LatencyMode::Throughput => 2,
+            LatencyMode::Low => 1,
+        };
+        surface.configure(&self.device, &self.config);
+        self.depth = create_depth(&self.device, self.size);
+        self.surface_change = Some(SurfaceChanged {
+            previous,
+            current: self.surface_info(),
+        });
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    /// One vertex and one index buffer per mesh; no shared arena or
+    /// batching yet, unlike the Vulkan backend.
+    pub fn upload_mesh(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> RenderResult<MeshHandle> {
+        let mesh = Mesh {
+            vertices: self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("mesh vertices"),
+                    contents: bytemuck::cast_slice(vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            indices: self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("mesh indices"),
+                    contents: bytemuck::cast_slice(indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+            index_count: indices.len() as u32,
+            bounds: clip::bounding_sphere(vertices),
+        };
+        let index = match self.meshes.iter().position(Option::is_none) {
+            Some(i) => {
+                self.meshes[i] = Some(mesh);
+                i
+            }
+            None => {
+                self.meshes.push(Some(mesh));
+                self.meshes.len() - 1
+            }
+        };
+        Ok(MeshHandle(index as u32))
+    }
+
+    /// Queue a draw for the next render(). `push.tex_index` is ignored
+    /// until the backend has textures; unknown or freed handles are
+    /// skipped there, as on the other backends.
+    pub fn draw_mesh(&mut self, handle: MeshHandle, push: PushData) {
+        self.draws.push((
+            handle,
+            GpuDraw {
+                model: push.model,
+                tint: push.tint,
+            },
+        ));
+    }
+
+    /// Not implemented yet: every texture is index 0, which the shader
+    /// doesn't sample (see mesh.wgsl), as on GL.
+    pub fn upload_texture_async(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<u32> {
+        self.upload_texture(pixels, width, height)
+    }
+
+    /// One-line description of the presentation setup, for the debug
+    /// overlay.
+    pub fn present_summary(&self) -> String {
+        format!(
+            "wgpu on {:?} ({}), {:?} {:?}, max {} frame(s) ahead",
+            self.adapter_info.backend,
+            self.adapter_info.name,
+            self.config.format,
+            self.config.present_mode,
+            self.config.desired_maximum_frame_latency
+        )
+    }
+
+    /// Take the queued draws, minus those whose bounds are outside
+    /// `frustum`, counting them for frame_stats.
+    fn cull_draws(&mut self, frustum: &Frustum) -> Vec<(MeshHandle, GpuDraw)> {
+        let mut draws = std::mem::take(&mut self.draws);
+        let mut counts = CullCounts::default();
+        draws.retain(|(handle, draw)| {
+            let Some(Some(Mesh {
+                bounds: Some(bounds),
+                ..
+            })) = self.meshes.get(handle.0 as usize)
+            else {
+                return true;
+            };
+            let visible = frustum.contains_bounds(&Mat4::from_cols_array_2d(&draw.model), *bounds);
+            counts.tested += 1;
+            counts.culled += !visible as u32;
+            visible
+        });
+        self.frame_stats.record_culling(counts);
+        draws
+    }
+
+    /// Front to back (see DrawSortKey); with one opaque pipeline there's
+    /// nothing to group by.
+    fn sort_draws(&self, draws: &mut [(MeshHandle, GpuDraw)]) {
+        let forward = self.camera.forward();
+        draws.sort_by_cached_key(|(handle, draw)| {
+            let bounds = match self.meshes.get(handle.0 as usize) {
+                Some(Some(Mesh {
+                    bounds: Some(bounds),
+                    ..
+                })) => *bounds,
+                _ => [0.0, 0.0, 0.0, -1.0],
+            };
+            let model = Mat4::from_cols_array_2d(&draw.model);
+            DrawSortKey::opaque(0, 0, draw_depth(forward, &model, bounds))
+        });
+    }
+
+    /// Make room for `count` draws in the draws buffer.
+    fn reserve_draws(&mut self, count: usize) {
+        let needed = (count.max(1) * std::mem::size_of::<GpuDraw>()) as u64;
+        if needed <= self.draw_capacity {
+            return;
+        }
+        let capacity = needed.next_power_of_two();
+        self.draw_buffer = create_draw_buffer(&self.device, capacity);
+        self.draw_capacity = capacity;
+        self.bind_group = create_bind_group(
+            &self.device,
+            &self.bind_group_layout,
+            &self.camera_buffer,
+            &self.draw_buffer,
+        );
+    }
+
+    /// Record the scene pass: clear, then every queued draw whose mesh is
+    /// still live and inside the camera frustum.
+    fn record_scene(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let aspect = self.size.width as f32 / self.size.height.max(1) as f32;
+        let convention = DepthConvention::ReverseZ;
+        let view_proj: Mat4 = clip::camera_view_proj(&self.camera, aspect, convention);
+        let mut draws = self.cull_draws(&Frustum::from_view_proj(&view_proj, convention));
+        self.sort_draws(&mut draws);
+        self.reserve_draws(draws.len());
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::bytes_of(&view_proj.to_cols_array_2d()),
+        );
+        let gpu_draws: Vec<GpuDraw> = draws.iter().map(|&(_, d)| d).collect();
+        if !gpu_draws.is_empty() {
+            self.queue
+                .write_buffer(&self.draw_buffer, 0, bytemuck::cast_slice(&gpu_draws));
+        }
+
+        let [r, g, b, a] = self.clear.map(f64::from);
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("scene"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        for (i, (handle, _)) in draws.iter().enumerate() {
+            let Some(Some(mesh)) = self.meshes.get(handle.0 as usize) else {
+                continue;
+            };
+            pass.set_vertex_buffer(0, mesh.vertices.slice(..));
+            pass.set_index_buffer(mesh.indices.slice(..), wgpu::IndexFormat::Uint32);
+            let i = i as u32;
+            pass.draw_indexed(0..mesh.index_count, 0, i..i + 1);
+        }
+    }
+}
+
+impl Renderer for WgpuRenderer {
+    fn new(
+        window: &dyn HasWindowHandle,
+        display_handle: &dyn HasDisplayHandle,
+        size: RenderSize,
+    ) -> RenderResult<Self> {
+        let wh = window.window_handle().map_err(|e| anyhow!("{e}"))?.as_raw();
+        let dh = display_handle
+            .display_handle()
+            .map_err(|e| anyhow!("{e}"))?
+            .as_raw();
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        // SAFETY: as with the other backends, the app keeps the window
+        // alive for as long as the renderer (see cubic-app's Backend).
+        let surface = unsafe { create_surface(&instance, dh, wh) }?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        }))
+        .context("no wgpu adapter can present to this window")?;
+        let adapter_info = adapter.get_info();
+        tracing::info!(
+            "wgpu adapter: {} ({:?}, {:?})",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.device_type
+        );
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("cubic"),
+            ..Default::default()
+        }))
+        .context("request_device")?;
+
+        let caps = surface.get_capabilities(&adapter);
+        // sRGB, as the other backends: shaders write linear colour.
+        let format = caps
+            .formats
+            .iter()
+            .copied()
+            .find(wgpu::TextureFormat::is_srgb)
+            .or_else(|| caps.formats.first().copied())
+            .ok_or_else(|| anyhow!("surface offers no formats"))?;
+        if !format.is_srgb() {
+            tracing::warn!("wgpu: no sRGB surface format, using {format:?}; colours will be dark");
+        }
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: caps
+                .alpha_modes
+                .first()
+                .copied()
+                .unwrap_or(wgpu::CompositeAlphaMode::Auto),
+            view_formats: Vec::new(),
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("scene"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = create_pipeline(&device, &bind_group_layout, format);
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("camera"),
+            size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let draw_capacity = 256 * std::mem::size_of::<GpuDraw>() as u64;
+        let draw_buffer = create_draw_buffer(&device, draw_capacity);
+        let bind_group =
+            create_bind_group(&device, &bind_group_layout, &camera_buffer, &draw_buffer);
+        let egui_renderer = egui_overlay::build_egui_renderer(&device, format);
+
+        let mut renderer = Self {
+            egui_renderer,
+            egui_pending: None,
+            pipeline,
+            bind_group_layout,
+            camera_buffer,
+            draw_buffer,
+            draw_capacity,
+            bind_group,
+            depth: create_depth(&device, size),
+            meshes: Vec::new(),
+            draws: Vec::new(),
+            camera: Camera::default(),
+            surface: Some(surface),
+            config,
+            present_modes: caps.present_modes,
+            queue,
+            device,
+            adapter_info,
+            instance,
+            display_raw: dh,
+            window_raw: wh,
+            size,
+            clear: [0.02, 0.02, 0.04, 1.0],
+            vsync: true,
+            pacer: FramePacer::new(),
+            frame_stats: FrameStatsTracker::default(),
+            latency_mode: LatencyMode::Throughput,
+            surface_change: None,
+        };
+        renderer.configure_surface();
+        // Creation isn't a change anyone has to react to.
+        renderer.surface_change = None;
+        Ok(renderer)
+    }
+
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()> {
+        self.size = size;
+        self.configure_surface();
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> RenderResult<()> {
+        // wgpu holds on to the surface's internals until the GPU is done
+        // with the last frame, so it can simply go.
+        if self.surface.take().is_some() {
+            tracing::info!("wgpu: suspended, surface released");
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) -> RenderResult<()> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+        // SAFETY: the handles new() was given; see there.
+        let surface = unsafe { create_surface(&self.instance, self.display_raw, self.window_raw) }?;
+        self.surface = Some(surface);
+        self.configure_surface();
+        tracing::info!("wgpu: resumed at {}x{}", self.size.width, self.size.height);
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    fn render(&mut self) -> RenderResult<()> {
+        let Some(surface) = &self.surface else {
+            self.draws.clear();
+            return Ok(());
+        };
+        if self.size.width == 0 || self.size.height == 0 {
+            self.draws.clear();
+            return Ok(());
+        }
+        let cpu_start = std::time::Instant::now();
+        let _frame = tracing::debug_span!("frame", backend = "wgpu").entered();
+
+        let acquire = tracing::debug_span!("acquire").entered();
+        let frame = match surface.get_current_texture() {
+            Ok(frame) => frame,
+            // The window changed under the surface: reconfigure, and draw
+            // next time.
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure_surface();
+                self.draws.clear();
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                tracing::warn!("wgpu: timed out acquiring a surface texture; frame skipped");
+                self.draws.clear();
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                return Err(RenderError::OutOfMemory {
+                    kind: MemoryKind::Device,
+                });
+            }
+            Err(e) => return Err(anyhow!("get_current_texture: {e}").into()),
+        };
+        drop(acquire);
+
+        let record = tracing::debug_span!("record").entered();
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("frame"),
+            });
+        self.record_scene(&mut encoder, &view);
+        let egui_uploads = self.paint_egui(&mut encoder, &view);
+        drop(record);
+        tracing::debug_span!("submit").in_scope(|| {
+            self.queue
+                .submit(egui_uploads.into_iter().chain([encoder.finish()]))
+        });
+        let suboptimal = frame.suboptimal;
+        tracing::debug_span!("present").in_scope(|| frame.present());
+        if suboptimal {
+            self.configure_surface();
+        }
+
+        self.frame_stats.record(cpu_start.elapsed());
+        tracing::debug_span!("pace").in_scope(|| self.pacer.wait());
+        Ok(())
+    }
+
+    fn set_clear_color(&mut self, rgba: [f32; 4]) {
+        self.clear = rgba;
+    }
+
+    fn surface_info(&self) -> SurfaceInfo {
+        SurfaceInfo {
+            format: format_name(self.config.format),
+            color_space: "SRGB_NONLINEAR",
+            extent: self.size,
+            present_mode: match self.config.present_mode {
+                wgpu::PresentMode::Fifo => PresentMode::Fifo,
+                wgpu::PresentMode::FifoRelaxed => PresentMode::FifoRelaxed,
+                wgpu::PresentMode::Mailbox => PresentMode::Mailbox,
+                wgpu::PresentMode::Immediate => PresentMode::Immediate,
+                _ => PresentMode::Other,
+            },
+            hdr: false,
+            // wgpu doesn't say; frame latency plus the one on screen.
+            image_count: self.config.desired_maximum_frame_latency + 1,
+        }
+    }
+
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged> {
+        self.surface_change.take()
+    }
+
+    fn set_vsync(&mut self, on: bool) {
+        if on != self.vsync {
+            self.vsync = on;
+            self.configure_surface();
+        }
+    }
+
+    fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.pacer.set_target_fps(fps);
+    }
+
+    fn set_latency_mode(&mut self, mode: LatencyMode) {
+        if mode != self.latency_mode {
+            self.latency_mode = mode;
+            self.configure_surface();
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        self.frame_stats.stats()
+    }
+
+    fn set_frame_spike_threshold(&mut self, threshold: Option<std::time::Duration>) {
+        self.frame_stats.set_spike_threshold(threshold);
+    }
+
+    fn record_stream_counts(&mut self, counts: StreamCounts) {
+        self.frame_stats.record_streaming(counts);
+    }
+
+    fn free_mesh(&mut self, handle: MeshHandle) {
+        // wgpu keeps the buffers alive until in-flight frames are done
+        // with them.
+        if let Some(slot) = self.meshes.get_mut(handle.0 as usize) {
+            *slot = None;
+        }
+    }
+
+    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> RenderResult<u32> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(anyhow!(
+                "{} bytes for a {width}x{height} RGBA8 texture",
+                pixels.len()
+            )
+            .into());
+        }
+        Ok(0)
+    }
+
+    fn queue_egui(
+        &mut self,
+        textures_delta: egui::TexturesDelta,
+        paint_jobs: Vec<egui::ClippedPrimitive>,
+        screen_width: u32,
+        screen_height: u32,
+        pixels_per_point: f32,
+    ) {
+        self.egui_pending = Some(egui_overlay::EguiFrame {
+            textures_delta,
+            paint_jobs,
+            screen_width,
+            screen_height,
+            pixels_per_point,
+        });
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("mesh.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("mesh.wgsl").into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("scene"),
+        bind_group_layouts: &[bind_group_layout],
+        ..Default::default()
+    });
+    // cubic_render::Vertex: pos, color, uv, normal, tex_index.
+    let attributes = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x3,
+        4 => Uint32,
+    ];
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("scene"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Vertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &attributes,
+            }],
+        },
+        primitive: wgpu::PrimitiveState {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            // Reverse-Z, as on Vulkan (see DepthConvention).
+            depth_compare: wgpu::CompareFunction::GreaterEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multisample: Default::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn create_draw_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("draws"),
+        size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &wgpu::Buffer,
+    draws: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("scene"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: draws.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_depth(device: &wgpu::Device, size: RenderSize) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// A surface on the app's window from its raw handles.
+///
+/// # Safety
+/// The window and display behind the handles must outlive the surface.
+unsafe fn create_surface(
+    instance: &wgpu::Instance,
+    display: RawDisplayHandle,
+    window: RawWindowHandle,
+) -> Result<wgpu::Surface<'static>> {
+    // SAFETY: the caller's guarantee.
+    unsafe {
+        instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+            raw_display_handle: display,
+            raw_window_handle: window,
+        })
+    }
+    .context("create_surface")
+}
+
+/// SurfaceInfo wants a static name; the formats a surface realistically
+/// offers, else "OTHER" (present_summary has the real one).
+fn format_name(format: wgpu::TextureFormat) -> &'static str {
+    match format {
+        wgpu::TextureFormat::Bgra8UnormSrgb => "BGRA8_SRGB",
+        wgpu::TextureFormat::Bgra8Unorm => "BGRA8_UNORM",
+        wgpu::TextureFormat::Rgba8UnormSrgb => "RGBA8_SRGB",
+        wgpu::TextureFormat::Rgba8Unorm => "RGBA8_UNORM",
+        wgpu::TextureFormat::Rgba16Float => "RGBA16_SFLOAT",
+        wgpu::TextureFormat::Rgb10a2Unorm => "A2B10G10R10_UNORM",
+        _ => "OTHER",
+    }
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: CEPL-1.0
+// The wgpu backend's scene shader: vertex colour times the draw's tint,
+// with a fixed directional light. Untextured until the backend has a
+// texture path (see WgpuRenderer::upload_texture).
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+};
+
+// Mirrors lib.rs GpuDraw (cubic_render::PushData minus tex_index).
+struct Draw {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> draws: array<Draw>;
+
+struct VertexIn {
+    @location(0) pos: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) normal: vec3<f32>,
+    @location(4) tex_index: u32,
+};
+
+struct VertexOut {
+    @builtin(position) clip: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+// The draw's index comes in as the instance (see render: one instance,
+// first_instance = draw index).
+@vertex
+fn vs_main(v: VertexIn, @builtin(instance_index) draw: u32) -> VertexOut {
+    let d = draws[draw];
+    var out: VertexOut;
+    out.clip = camera.view_proj * d.model * vec4<f32>(v.pos, 1.0);
+    out.color = v.color * d.tint.rgb;
+    // Assumes uniform scale, as tri.vert does.
+    out.normal = (d.model * vec4<f32>(v.normal, 0.0)).xyz;
+    return out;
+}
+
+const LIGHT_DIR: vec3<f32> = vec3<f32>(0.36, 0.8, 0.48);
+const AMBIENT: f32 = 0.35;
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let diffuse = max(dot(normalize(in.normal), LIGHT_DIR), 0.0);
+    return vec4<f32>(in.color * (AMBIENT + (1.0 - AMBIENT) * diffuse), 1.0);
+}
@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Projection and view builders, and frustum planes from their product,
+//! in the conventions every backend draws with: right-handed view space
+//! looking down -Z, +Y up, clip-space depth in [0, 1] (ZO). The `reverse`
+//! builders put the near plane at depth 1 (see DepthConvention, which picks
+//! between the two families).
+//!
+//! Matrices are written out rather than taken from glam so the depth
+//! mapping is visible where it's defined.
+
+use cubic_math::camera::rh::view::look_to_mat4;
+use cubic_math::{Camera, Mat4, Vec3, Vec4};
+
+use crate::{DepthConvention, Vertex};
+
+/// Perspective with an infinite far plane, depth 1 at `near` falling to 0
+/// at infinity. `fovy` is vertical, in radians.
+pub fn perspective_rh_zo_reverse_infinite(fovy: f32, aspect: f32, near: f32) -> Mat4 {
+    let f = 1.0 / (0.5 * fovy).tan();
+    Mat4::from_cols(
+        Vec4::new(f / aspect, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, f, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, 0.0, -1.0),
+        Vec4::new(0.0, 0.0, near, 0.0),
+    )
+}
+
+/// Perspective with an infinite far plane, depth 0 at `near` rising to 1
+/// at infinity.
+pub fn perspective_rh_zo_infinite(fovy: f32, aspect: f32, near: f32) -> Mat4 {
+    let f = 1.0 / (0.5 * fovy).tan();
+    Mat4::from_cols(
+        Vec4::new(f / aspect, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, f, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, -1.0, -1.0),
+        Vec4::new(0.0, 0.0, -near, 0.0),
+    )
+}
+
+/// Orthographic box from `left..right`, `bottom..top` and `near..far` view
+/// distance, depth 1 at `near` and 0 at `far`.
+pub fn orthographic_rh_zo_reverse(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Mat4 {
+    // Standard ZO with the planes swapped runs depth the other way.
+    orthographic_rh_zo(left, right, bottom, top, far, near)
+}
+
+/// Orthographic box, depth 0 at `near` and 1 at `far`.
+pub fn orthographic_rh_zo(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Mat4 {
+    let (w, h, d) = (right - left, top - bottom, far - near);
+    Mat4::from_cols(
+        Vec4::new(2.0 / w, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 2.0 / h, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, -1.0 / d, 0.0),
+        Vec4::new(-(right + left) / w, -(top + bottom) / h, -near / d, 1.0),
+    )
+}
+
+/// View matrix for an eye at `eye` looking along `dir`, a unit vector.
+pub fn look_to_rh(eye: Vec3, dir: Vec3, up: Vec3) -> Mat4 {
+    look_to_mat4(eye, dir, up)
+}
+
+/// View matrix for an eye at `eye` looking at `target`.
+pub fn look_at_rh(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+    look_to_mat4(eye, (target - eye).normalize(), up)
+}
+
+/// `camera`'s rotation-only view-projection, for camera-relative model
+/// matrices (see Camera::view_matrix_no_translation).
+pub fn camera_view_proj(camera: &Camera, aspect: f32, convention: DepthConvention) -> Mat4 {
+    convention.camera_projection(camera, aspect) * camera.view_matrix_no_translation()
+}
+
+/// Bounding sphere (centre of the AABB, radius to the farthest vertex) of
+/// a mesh's vertices, as xyz centre and w radius; None for no vertices.
+/// Not the minimal sphere, but within a few percent of it for the boxy
+/// meshes cubic draws, and one pass cheaper.
+pub fn bounding_sphere(vertices: &[Vertex]) -> Option<[f32; 4]> {
+    let first = Vec3::from(vertices.first()?.pos);
+    let (min, max) = vertices.iter().fold((first, first), |(min, max), v| {
+        (min.min(v.pos.into()), max.max(v.pos.into()))
+    });
+    let c = 0.5 * (min + max);
+    let r2 = vertices
+        .iter()
+        .map(|v| Vec3::from(v.pos).distance_squared(c))
+        .fold(0.0f32, f32::max);
+    Some([c.x, c.y, c.z, r2.sqrt()])
+}
+
+/// Inward-facing, normalized planes of a view-projection's frustum, in
+/// whatever space it takes its points from (camera-relative world space
+/// for camera_view_proj).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near, far; `xyz . p + w >= 0` inside. An
+    /// infinite far plane is stored as (0, 0, 0, 1), which never culls.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Gribb/Hartmann: planes from the rows of `view_proj`, whose depth
+    /// runs as `convention` says. ZO depth makes the near/far planes
+    /// z >= 0 and z <= w; reverse-Z swaps which is which.
+    pub fn from_view_proj(view_proj: &Mat4, convention: DepthConvention) -> Self {
+        let m = view_proj;
+        let r = [m.row(0), m.row(1), m.row(2), m.row(3)];
+        let (near, far) = match convention {
+            DepthConvention::ReverseZ => (r[3] - r[2], r[2]),
+            DepthConvention::Standard => (r[2], r[3] - r[2]),
+        };
+        let planes = [
+            r[3] + r[0],
+            r[3] - r[0],
+            r[3] + r[1],
+            r[3] - r[1],
+            near,
+            far,
+        ]
+        .map(|p| {
+            let len = p.truncate().length();
+            // Only an infinite far plane has no normal.
+            if len > 1.0e-6 {
+                p / len
+            } else {
+                Vec4::W
+            }
+        });
+        Self { planes }
+    }
+
+    /// False only if the box `min..max` is entirely outside.
+    pub fn contains_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|p| {
+            // The corner furthest along the plane's normal.
+            let v = Vec3::select(p.truncate().cmpge(Vec3::ZERO), max, min);
+            p.truncate().dot(v) + p.w >= 0.0
+        })
+    }
+
+    /// False only if the sphere is entirely outside.
+    pub fn contains_sphere(&self, centre: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.truncate().dot(centre) + p.w >= -radius)
+    }
+
+    /// contains_sphere for a mesh-space `bounds` (as bounding_sphere
+    /// returns; a negative radius is never culled) drawn with `model`. The
+    /// same test as Vulkan's indirect_cull.comp: the largest axis scale
+    /// bounds the sphere under non-uniform scale.
+    pub fn contains_bounds(&self, model: &Mat4, bounds: [f32; 4]) -> bool {
+        if bounds[3] < 0.0 {
+            return true;
+        }
+        let centre = model.transform_point3(Vec3::new(bounds[0], bounds[1], bounds[2]));
+        let scale = model
+            .x_axis
+            .truncate()
+            .length()
+            .max(model.y_axis.truncate().length())
+            .max(model.z_axis.truncate().length());
+        self.contains_sphere(centre, bounds[3] * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ndc(m: Mat4, p: Vec3) -> Vec3 {
+        let c = m * p.extend(1.0);
+        c.truncate() / c.w
+    }
+
+    #[test]
+    fn orthographic_depth_runs_both_ways() {
+        let p = Vec3::new(1.0, -1.0, -2.0);
+        let ortho = orthographic_rh_zo(-2.0, 2.0, -1.0, 1.0, 2.0, 10.0);
+        let reverse = orthographic_rh_zo_reverse(-2.0, 2.0, -1.0, 1.0, 2.0, 10.0);
+        assert!(ndc(ortho, p).abs_diff_eq(Vec3::new(0.5, -1.0, 0.0), 1.0e-6));
+        assert!(ndc(reverse, p).abs_diff_eq(Vec3::new(0.5, -1.0, 1.0), 1.0e-6));
+        let far = Vec3::new(0.0, 0.0, -10.0);
+        assert!((ndc(ortho, far).z - 1.0).abs() < 1.0e-6);
+        assert!(ndc(reverse, far).z.abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn bounds_follow_the_model_matrix() {
+        let vertex = |pos| Vertex {
+            pos,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let bounds = bounding_sphere(&[vertex([0.0; 3]), vertex([2.0, 2.0, 0.0])]).unwrap();
+        assert_eq!(bounds, [1.0, 1.0, 0.0, 2.0f32.sqrt()]);
+        assert_eq!(bounding_sphere(&[]), None);
+
+        let camera = Camera::default();
+        let convention = DepthConvention::ReverseZ;
+        let frustum =
+            Frustum::from_view_proj(&camera_view_proj(&camera, 1.0, convention), convention);
+        let at = |x: f32, scale: f32| {
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(scale),
+                cubic_math::Quat::IDENTITY,
+                Vec3::new(x, 0.0, -10.0),
+            )
+        };
+        assert!(frustum.contains_bounds(&at(0.0, 1.0), bounds));
+        assert!(!frustum.contains_bounds(&at(8.0, 1.0), bounds));
+        // Scaled up far enough to reach back into view.
+        assert!(frustum.contains_bounds(&at(8.0, 10.0), bounds));
+        assert!(frustum.contains_bounds(&at(8.0, 1.0), [0.0, 0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn look_at_faces_the_target() {
+        let view = look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let p = view.transform_point3(Vec3::ZERO);
+        assert!(p.abs_diff_eq(Vec3::new(0.0, 0.0, -5.0), 1.0e-6));
+    }
+
+    #[test]
+    fn frustum_culls_the_same_under_either_convention() {
+        let camera = Camera::default();
+        for convention in [DepthConvention::ReverseZ, DepthConvention::Standard] {
+            let frustum =
+                Frustum::from_view_proj(&camera_view_proj(&camera, 1.0, convention), convention);
+            // In front, far off, behind, behind the near plane, off to the side.
+            assert!(frustum.contains_sphere(Vec3::new(0.0, 0.0, -5.0), 0.5));
+            assert!(frustum.contains_sphere(Vec3::new(0.0, 0.0, -1.0e6), 0.5));
+            assert!(!frustum.contains_sphere(Vec3::new(0.0, 0.0, 5.0), 0.5));
+            assert!(!frustum.contains_aabb(Vec3::splat(-0.01), Vec3::splat(0.01)));
+            let side = Vec3::new(50.0, 0.0, 0.0);
+            let (min, max) = (Vec3::new(-1.0, -1.0, -6.0), Vec3::new(1.0, 1.0, -5.0));
+            assert!(!frustum.contains_aabb(min + side, max + side));
+            assert!(frustum.contains_aabb(min, max));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Which way depth runs, for anyone building their own projection.
+//!
+//! Every backend uses a right-handed view space (camera looking down -Z),
+//! clip-space depth in [0, 1] and an infinite far plane. What differs by
+//! convention is the direction: reverse-Z, the default, puts the near
+//! plane at depth 1 and infinity at 0, which spreads float depth precision
+//! evenly over distance; standard depth runs the other way. A matrix built
+//! for one convention draws nothing, or everything, under the other's
+//! depth test, so take it from `Renderer::depth_convention` rather than
+//! assuming.
+//!
+//! GL gets [0, 1] depth from glClipControl. Without it (pre-4.5 drivers
+//! lacking ARB_clip_control) the same matrices still draw correctly, but
+//! GL's [-1, 1] range squeezes them into the upper half of the depth
+//! buffer and reverse-Z loses its precision advantage.
+
+use cubic_math::{Camera, Mat4};
+
+use crate::clip;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DepthConvention {
+    /// Near plane at 1, infinity at 0: clear to 0, GREATER_OR_EQUAL.
+    #[default]
+    ReverseZ,
+    /// Near plane at 0, infinity at 1: clear to 1, LESS_OR_EQUAL.
+    Standard,
+}
+
+impl DepthConvention {
+    /// CUBIC_DEPTH's names: reverse (or reverse-z), standard (or forward).
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "reverse" | "reverse-z" | "reversez" => Some(Self::ReverseZ),
+            "standard" | "forward" => Some(Self::Standard),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ReverseZ => "reverse-Z",
+            Self::Standard => "standard",
+        }
+    }
+
+    /// The depth buffer's clear value: the far end of the range.
+    pub fn clear_depth(self) -> f32 {
+        match self {
+            Self::ReverseZ => 0.0,
+            Self::Standard => 1.0,
+        }
+    }
+
+    /// Whether a fragment at depth `new` is in front of what's stored at
+    /// `old`, as the depth test decides it (ties pass).
+    pub fn passes(self, new: f32, old: f32) -> bool {
+        match self {
+            Self::ReverseZ => new >= old,
+            Self::Standard => new <= old,
+        }
+    }
+
+    /// Right-handed, [0, 1] depth, infinite far plane perspective for this
+    /// convention. `fovy` is vertical, in radians.
+    pub fn perspective(self, fovy: f32, aspect: f32, near: f32) -> Mat4 {
+        match self {
+            Self::ReverseZ => clip::perspective_rh_zo_reverse_infinite(fovy, aspect, near),
+            Self::Standard => clip::perspective_rh_zo_infinite(fovy, aspect, near),
+        }
+    }
+
+    /// `camera`'s projection for this convention.
+    pub fn camera_projection(self, camera: &Camera, aspect: f32) -> Mat4 {
+        self.perspective(camera.fovy, aspect, camera.near)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cubic_math::Vec4;
+
+    fn depth_at(convention: DepthConvention, distance: f32) -> f32 {
+        let clip = convention.perspective(1.0, 1.5, 0.1) * Vec4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn near_and_far_land_on_the_right_ends() {
+        for convention in [DepthConvention::ReverseZ, DepthConvention::Standard] {
+            let near = depth_at(convention, 0.1);
+            let far = depth_at(convention, 1.0e7);
+            assert!((far - convention.clear_depth()).abs() < 1.0e-6);
+            assert!((near - (1.0 - convention.clear_depth())).abs() < 1.0e-6);
+            assert!(convention.passes(depth_at(convention, 5.0), depth_at(convention, 6.0)));
+            assert!(!convention.passes(depth_at(convention, 6.0), depth_at(convention, 5.0)));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! The order backends record a frame's draws in, as one u64 per draw:
+//! sorting by DrawSortKey puts every opaque draw first, grouped by
+//! pipeline and then material so each is bound once, front to back within
+//! a group so early depth testing rejects what's hidden; then every
+//! transparent draw, back to front across all pipelines, since blending is
+//! only right in that order. Sort stably: equal keys keep submission order.
+//!
+//! Bits, high to low: transparent (1); then for opaque draws pipeline (16),
+//! material (24) and depth (23), and for transparent ones inverted depth
+//! (32). Handles past their field's range saturate, which only costs
+//! extra binds. Opaque depth is the f32's top bits, so its precision is
+//! relative (one part in 32k), which is plenty for ordering.
+
+use cubic_math::{Mat4, Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DrawSortKey(pub u64);
+
+const TRANSPARENT: u64 = 1 << 63;
+
+impl DrawSortKey {
+    /// An opaque draw on `pipeline` with `material` (0 for none), `depth`
+    /// along the view direction from the eye.
+    pub fn opaque(pipeline: u32, material: u32, depth: f32) -> Self {
+        let pipeline = pipeline.min(0xffff) as u64;
+        let material = material.min(0xff_ffff) as u64;
+        Self(pipeline << 47 | material << 23 | (depth_bits(depth) >> 8) as u64)
+    }
+
+    /// A blended draw `depth` from the eye: after every opaque one, the
+    /// farthest first.
+    pub fn transparent(depth: f32) -> Self {
+        Self(TRANSPARENT | (!depth_bits(depth) as u64) << 32 >> 1)
+    }
+
+    pub fn is_transparent(self) -> bool {
+        self.0 & TRANSPARENT != 0
+    }
+}
+
+/// Order-preserving bits of a depth; anything behind the eye (or NaN)
+/// counts as at the eye.
+fn depth_bits(depth: f32) -> u32 {
+    if depth > 0.0 {
+        depth.to_bits()
+    } else {
+        0
+    }
+}
+
+/// Depth of a draw for DrawSortKey: the view-direction distance to its
+/// bounding sphere's centre (`bounds` as clip::bounding_sphere returns;
+/// the model origin for unbounded meshes) under `model`, with model
+/// matrices camera-relative as everywhere in cubic.
+pub fn draw_depth(forward: Vec3, model: &Mat4, bounds: [f32; 4]) -> f32 {
+    let centre = if bounds[3] < 0.0 {
+        Vec3::ZERO
+    } else {
+        Vec3::new(bounds[0], bounds[1], bounds[2])
+    };
+    forward.dot(model.transform_point3(centre))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_groups_then_transparent_back_to_front() {
+        let mut keys = [
+            ("glass near", DrawSortKey::transparent(2.0)),
+            ("b far", DrawSortKey::opaque(1, 0, 50.0)),
+            ("a far", DrawSortKey::opaque(0, 3, 40.0)),
+            ("glass far", DrawSortKey::transparent(30.0)),
+            ("b near", DrawSortKey::opaque(1, 0, 1.0)),
+            ("a near", DrawSortKey::opaque(0, 3, 0.5)),
+            ("default", DrawSortKey::opaque(0, 0, 100.0)),
+            ("behind", DrawSortKey::transparent(-5.0)),
+        ];
+        keys.sort_by_key(|&(_, k)| k);
+        let order: Vec<_> = keys.iter().map(|&(name, _)| name).collect();
+        assert_eq!(
+            order,
+            [
+                "default",
+                "a near",
+                "a far",
+                "b near",
+                "b far",
+                "glass far",
+                "glass near",
+                "behind"
+            ]
+        );
+        assert!(keys[..5].iter().all(|(_, k)| !k.is_transparent()));
+        assert!(keys[5..].iter().all(|(_, k)| k.is_transparent()));
+    }
+
+    #[test]
+    fn depth_is_along_the_view_direction() {
+        let model = Mat4::from_translation(Vec3::new(3.0, 0.0, -10.0));
+        let forward = Vec3::NEG_Z;
+        assert_eq!(draw_depth(forward, &model, [0.0, 0.0, 2.0, 1.0]), 8.0);
+        assert_eq!(draw_depth(forward, &model, [0.0, 0.0, 2.0, -1.0]), 10.0);
+    }
+}
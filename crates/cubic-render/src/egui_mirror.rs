@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! CPU copy of every texture egui currently has on a backend, kept in step
+//! with each frame's TexturesDelta. egui only ever sends deltas (the font
+//! atlas goes up once, then grows by patches), so a fresh egui renderer —
+//! after a Vulkan device loss, or a switch to another backend — can only
+//! get its textures back from a copy like this.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// See the module docs.
+#[derive(Default)]
+pub struct EguiTextureMirror {
+    textures: HashMap<egui::TextureId, egui::epaint::ImageDelta>,
+}
+
+impl EguiTextureMirror {
+    /// Follow one frame's texture updates, including frees.
+    pub fn apply(&mut self, delta: &egui::TexturesDelta) {
+        for (id, d) in &delta.set {
+            let Some([x, y]) = d.pos else {
+                self.textures.insert(*id, d.clone());
+                continue;
+            };
+            // Partial update: patch the rows into the retained full image.
+            let Some(full) = self.textures.get_mut(id) else {
+                continue;
+            };
+            let egui::ImageData::Color(src) = &d.image;
+            let egui::ImageData::Color(dst) = &mut full.image;
+            let dst = Arc::make_mut(dst);
+            let (w, sw) = (dst.size[0], src.size[0]);
+            for row in 0..src.size[1] {
+                let o = (y + row) * w + x;
+                let s = row * sw;
+                if let Some(out) = dst.pixels.get_mut(o..o + sw) {
+                    out.copy_from_slice(&src.pixels[s..s + sw]);
+                }
+            }
+            full.options = d.options;
+        }
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Every texture as a whole-image update, for handing to a renderer
+    /// that has none of them.
+    pub fn full_set(&self) -> Vec<(egui::TextureId, egui::epaint::ImageDelta)> {
+        self.textures
+            .iter()
+            .map(|(id, d)| (*id, d.clone()))
+            .collect()
+    }
+}
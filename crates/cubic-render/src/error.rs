@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! The error renderer backends return from their public APIs. The variants
+//! are the failures a caller can do something about: rebuild the surface,
+//! rebuild the renderer, free memory, fix a shader, fall back to another
+//! backend. Everything else is `Other`, carrying the backend's anyhow chain
+//! for the log.
+//!
+//! Backends raise the typed variants where they see the cause (a
+//! VK_ERROR_DEVICE_LOST, an allocator out of memory, a shader that won't
+//! compile) and may wrap them in anyhow context on the way up: converting
+//! an anyhow::Error back (`?` or `RenderError::from`) finds a RenderError
+//! anywhere in its chain.
+
+use std::fmt;
+
+/// Which memory ran out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryKind {
+    /// System memory the driver allocates from.
+    Host,
+    /// GPU memory (VRAM, or the shared pool on integrated GPUs).
+    Device,
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    /// The window surface is gone (VK_ERROR_SURFACE_LOST_KHR, wgpu's
+    /// SurfaceError::Lost). The device and every resource are fine:
+    /// `suspend()` and `resume()` build a new surface on the window.
+    SurfaceLost,
+    /// The GPU device was lost: a driver reset, a hang, a GPU removed.
+    /// Nothing created on it is usable and calling again won't help;
+    /// rebuild the renderer (VkRenderer::recover_from_device_lost).
+    DeviceLost {
+        /// Which call reported it: "wait", "acquire", "submit", "present",
+        /// ...
+        stage: &'static str,
+    },
+    /// An allocation failed. Freeing resources (fewer chunks resident,
+    /// smaller render targets) and retrying may succeed.
+    OutOfMemory { kind: MemoryKind },
+    /// A shader failed to compile or link; `log` is the compiler's output.
+    ShaderCompile { shader: String, log: String },
+    /// The device, driver or surface lacks something the call needs.
+    /// Another backend, or the call without that feature, may work.
+    Unsupported { what: String },
+    /// Anything else; not recoverable beyond logging it.
+    Other(anyhow::Error),
+}
+
+/// Result of a renderer backend's public API.
+pub type RenderResult<T> = std::result::Result<T, RenderError>;
+
+impl RenderError {
+    /// `Unsupported` for `what`.
+    pub fn unsupported(what: impl Into<String>) -> Self {
+        Self::Unsupported { what: what.into() }
+    }
+
+    /// Whether the renderer has to be rebuilt before it can draw again.
+    pub fn is_device_lost(&self) -> bool {
+        matches!(self, Self::DeviceLost { .. })
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SurfaceLost => write!(f, "window surface lost"),
+            Self::DeviceLost { stage } => write!(f, "device lost during {stage}"),
+            Self::OutOfMemory {
+                kind: MemoryKind::Host,
+            } => write!(f, "out of host memory"),
+            Self::OutOfMemory {
+                kind: MemoryKind::Device,
+            } => write!(f, "out of device memory"),
+            Self::ShaderCompile { shader, log } => write!(f, "{shader}: {log}"),
+            Self::Unsupported { what } => write!(f, "unsupported: {what}"),
+            // `{:#}` so the context chain survives a plain `{e}`.
+            Self::Other(e) => write!(f, "{e:#}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<anyhow::Error> for RenderError {
+    fn from(e: anyhow::Error) -> Self {
+        if e.downcast_ref::<RenderError>().is_none() {
+            return Self::Other(e);
+        }
+        // Through any context, which the typed variant doesn't need.
+        e.downcast::<RenderError>().unwrap_or_else(Self::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn typed_errors_survive_anyhow_context() {
+        let e = anyhow::Error::from(RenderError::DeviceLost { stage: "submit" })
+            .context("rendering frame 12");
+        assert!(matches!(
+            RenderError::from(e),
+            RenderError::DeviceLost { stage: "submit" }
+        ));
+
+        let e = Err::<(), _>(RenderError::OutOfMemory {
+            kind: MemoryKind::Device,
+        })
+        .context("uploading mesh")
+        .unwrap_err();
+        assert!(matches!(
+            RenderError::from(e),
+            RenderError::OutOfMemory {
+                kind: MemoryKind::Device
+            }
+        ));
+    }
+
+    #[test]
+    fn untyped_errors_become_other_with_their_chain() {
+        let e = RenderError::from(anyhow!("bad index").context("replace_texture"));
+        assert!(matches!(e, RenderError::Other(_)));
+        assert_eq!(e.to_string(), "replace_texture: bad index");
+    }
+}
@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Rolling frame-time history behind `Renderer::frame_stats`. Each backend
+//! records once per render(): the time since the previous frame (what
+//! stutter shows up in) and the CPU time render() itself took. Stats are
+//! over the last `window` frames; a frame longer than the spike threshold
+//! also logs a `frame time spike` warning, with the window's average for
+//! scale, so hitches show up in the log next to whatever caused them.
+//!
+//! Backends that frustum-cull their draw list on the CPU also report the
+//! last frame's counts here (see CullCounts), and the app hands in its
+//! voxel streaming's (see StreamCounts) so they sit next to the frame
+//! times they cost.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames kept by FrameStatsTracker::default: about two seconds at 120 fps.
+pub const FRAME_STATS_WINDOW: usize = 240;
+
+/// Summary of one series of frame times, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTimeStats {
+    pub avg_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+}
+
+impl FrameTimeStats {
+    /// Summarize `samples` (milliseconds); all zero if there are none.
+    pub fn of(samples: impl IntoIterator<Item = f32>) -> Self {
+        let mut sorted: Vec<f32> = samples.into_iter().collect();
+        if sorted.is_empty() {
+            return Self::default();
+        }
+        sorted.sort_by(f32::total_cmp);
+        // Nearest rank: the smallest sample at or above p of the window.
+        let pct = |p: f32| sorted[((p * sorted.len() as f32).ceil() as usize).max(1) - 1];
+        Self {
+            avg_ms: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p95_ms: pct(0.95),
+            p99_ms: pct(0.99),
+            max_ms: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Frame-time statistics over the renderer's rolling window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Frames the stats cover (less than the window until it fills).
+    pub frames: usize,
+    /// Time from one render() to the next: the frame time a player sees.
+    pub frame: FrameTimeStats,
+    /// CPU time spent inside render() (recording, submit, present; not the
+    /// target-fps pacer's wait).
+    pub cpu: FrameTimeStats,
+    /// GPU time per frame, for backends that measure it; None until then.
+    pub gpu: Option<FrameTimeStats>,
+    /// Validation-layer messages since startup (not just the window), for
+    /// backends running under one; zero otherwise.
+    pub validation: ValidationCounts,
+    /// The last frame's CPU frustum culling.
+    pub culling: CullCounts,
+    /// Voxel world streaming as of the last Renderer::record_stream_counts.
+    pub streaming: StreamCounts,
+}
+
+/// Draws a backend frustum-tested on the CPU in one frame, and how many of
+/// them it dropped. Draws culled elsewhere (Vulkan's GPU cull pass) or
+/// never tested (no bounds, culling off) aren't counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullCounts {
+    pub tested: u32,
+    pub culled: u32,
+}
+
+/// Where a streamed voxel world stands: what's loaded, and the work queued
+/// behind it. All zero for apps that don't stream one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamCounts {
+    /// Chunks whose voxel data is loaded.
+    pub resident: u32,
+    /// Chunks being generated and meshed on the job pool, plus chunks
+    /// waiting to be remeshed.
+    pub meshing: u32,
+    /// Finished meshes waiting for their turn in the per-frame upload
+    /// budget.
+    pub uploads_pending: u32,
+}
+
+/// Validation messages a backend's debug callback has seen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    pub errors: u64,
+    pub warnings: u64,
+    /// Messages dropped by the suppression list, either severity.
+    pub suppressed: u64,
+}
+
+#[derive(Debug)]
+pub struct FrameStatsTracker {
+    window: usize,
+    frame_ms: VecDeque<f32>,
+    cpu_ms: VecDeque<f32>,
+    last: Option<Instant>,
+    spike_threshold: Option<Duration>,
+    culling: CullCounts,
+    streaming: StreamCounts,
+}
+
+impl Default for FrameStatsTracker {
+    fn default() -> Self {
+        Self::new(FRAME_STATS_WINDOW)
+    }
+}
+
+impl FrameStatsTracker {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            window,
+            frame_ms: VecDeque::with_capacity(window),
+            cpu_ms: VecDeque::with_capacity(window),
+            last: None,
+            spike_threshold: None,
+            culling: CullCounts::default(),
+            streaming: StreamCounts::default(),
+        }
+    }
+
+    /// Frames longer than `threshold` log a spike warning; None (the
+    /// default) or zero turns that off.
+    pub fn set_spike_threshold(&mut self, threshold: Option<Duration>) {
+        self.spike_threshold = threshold.filter(|t| !t.is_zero());
+    }
+
+    /// Record a frame that took `cpu` inside render(). Call once per
+    /// render(), before any pacing wait; the frame time is measured from
+    /// the previous call.
+    pub fn record(&mut self, cpu: Duration) {
+        let now = Instant::now();
+        let frame = self.last.map(|last| now - last);
+        self.last = Some(now);
+        push_capped(&mut self.cpu_ms, self.window, ms(cpu));
+        let Some(frame) = frame else {
+            return;
+        };
+        push_capped(&mut self.frame_ms, self.window, ms(frame));
+        if let Some(threshold) = self.spike_threshold {
+            if frame > threshold {
+                let avg = FrameTimeStats::of(self.frame_ms.iter().copied()).avg_ms;
+                tracing::warn!(
+                    frame_ms = ms(frame),
+                    cpu_ms = ms(cpu),
+                    avg_ms = avg,
+                    threshold_ms = ms(threshold),
+                    "frame time spike"
+                );
+            }
+        }
+    }
+
+    /// The frame's culling counts; replaces the previous frame's.
+    pub fn record_culling(&mut self, counts: CullCounts) {
+        self.culling = counts;
+    }
+
+    /// The app's streaming counts; replaces the previous ones. Kept across
+    /// reset(), since they describe the world rather than the window.
+    pub fn record_streaming(&mut self, counts: StreamCounts) {
+        self.streaming = counts;
+    }
+
+    /// Forget the history, e.g. after a load screen or device loss whose
+    /// frames aren't representative.
+    pub fn reset(&mut self) {
+        self.frame_ms.clear();
+        self.cpu_ms.clear();
+        self.last = None;
+        self.culling = CullCounts::default();
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            frames: self.frame_ms.len(),
+            frame: FrameTimeStats::of(self.frame_ms.iter().copied()),
+            cpu: FrameTimeStats::of(self.cpu_ms.iter().copied()),
+            gpu: None,
+            validation: ValidationCounts::default(),
+            culling: self.culling,
+            streaming: self.streaming,
+        }
+    }
+}
+
+fn ms(d: Duration) -> f32 {
+    d.as_secs_f32() * 1000.0
+}
+
+fn push_capped(samples: &mut VecDeque<f32>, cap: usize, v: f32) {
+    if samples.len() == cap {
+        samples.pop_front();
+    }
+    samples.push_back(v);
+}
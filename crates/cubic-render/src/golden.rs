@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Image comparison for golden-image tests (`cubic golden`): a rendered
+//! frame against a stored one, both RGBA8, top row first. Alpha is ignored;
+//! what a swapchain leaves there differs by backend and driver.
+//!
+//! Two measures, since neither catches everything alone. The per-channel
+//! delta counts pixels that moved by more than the tolerance, which finds
+//! a missing chunk or a wrong colour however small. SSIM (structural
+//! similarity, over luma in 8x8 windows) scores the image as a whole and
+//! shrugs off the dithering and filtering differences between drivers that
+//! would trip a strict delta everywhere.
+
+use anyhow::{bail, Result};
+
+/// SSIM window size, in pixels.
+const SSIM_WINDOW: usize = 8;
+/// The usual SSIM stabilisers, (0.01 L)^2 and (0.03 L)^2 for L = 255.
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// How far a frame may stray from its golden and still pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoldenTolerance {
+    /// A channel differing by more than this makes the pixel a mismatch.
+    pub channel_delta: u8,
+    /// Fraction of pixels allowed to mismatch, 0..=1.
+    pub max_mismatched: f64,
+    /// Lowest SSIM accepted; 1.0 means identical.
+    pub min_ssim: f64,
+}
+
+impl Default for GoldenTolerance {
+    /// Loose enough for two drivers' rasterisation and filtering to agree,
+    /// tight enough that a missing chunk or a shading change fails.
+    fn default() -> Self {
+        Self {
+            channel_delta: 8,
+            max_mismatched: 0.005,
+            min_ssim: 0.98,
+        }
+    }
+}
+
+/// How two images differ; see compare_rgba8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageDiff {
+    /// Largest difference in any colour channel of any pixel.
+    pub max_channel_delta: u8,
+    /// Pixels with a channel differing by more than the tolerance's
+    /// channel_delta.
+    pub mismatched: usize,
+    /// Pixels compared.
+    pub pixels: usize,
+    /// Mean SSIM over the luma windows, -1..=1.
+    pub ssim: f64,
+}
+
+impl ImageDiff {
+    /// Fraction of pixels that mismatched.
+    pub fn mismatched_fraction(&self) -> f64 {
+        if self.pixels == 0 {
+            0.0
+        } else {
+            self.mismatched as f64 / self.pixels as f64
+        }
+    }
+
+    pub fn passes(&self, tolerance: &GoldenTolerance) -> bool {
+        self.mismatched_fraction() <= tolerance.max_mismatched && self.ssim >= tolerance.min_ssim
+    }
+}
+
+/// Compare `actual` to `golden`, both `width` x `height` RGBA8, counting a
+/// pixel as mismatched past `channel_delta`. Errs if either buffer isn't
+/// that size.
+pub fn compare_rgba8(
+    actual: &[u8],
+    golden: &[u8],
+    width: u32,
+    height: u32,
+    channel_delta: u8,
+) -> Result<ImageDiff> {
+    let (w, h) = (width as usize, height as usize);
+    let len = w * h * 4;
+    if actual.len() != len || golden.len() != len {
+        bail!(
+            "expected {width}x{height} RGBA8 ({len} bytes), got {} and {}",
+            actual.len(),
+            golden.len()
+        );
+    }
+
+    let mut max_channel_delta = 0;
+    let mut mismatched = 0;
+    for (a, g) in actual.chunks_exact(4).zip(golden.chunks_exact(4)) {
+        let delta = (0..3).map(|c| a[c].abs_diff(g[c])).max().unwrap_or(0);
+        max_channel_delta = max_channel_delta.max(delta);
+        if delta > channel_delta {
+            mismatched += 1;
+        }
+    }
+
+    Ok(ImageDiff {
+        max_channel_delta,
+        mismatched,
+        pixels: w * h,
+        ssim: ssim(&luma(actual), &luma(golden), w, h),
+    })
+}
+
+/// A visualisation of compare_rgba8's mismatches: the golden dimmed to a
+/// quarter, with every mismatched pixel in red. RGBA8, like the inputs.
+pub fn diff_image_rgba8(actual: &[u8], golden: &[u8], channel_delta: u8) -> Vec<u8> {
+    actual
+        .chunks_exact(4)
+        .zip(golden.chunks_exact(4))
+        .flat_map(|(a, g)| {
+            let delta = (0..3).map(|c| a[c].abs_diff(g[c])).max().unwrap_or(0);
+            if delta > channel_delta {
+                [255, 0, 0, 255]
+            } else {
+                [g[0] / 4, g[1] / 4, g[2] / 4, 255]
+            }
+        })
+        .collect()
+}
+
+/// Rec. 709 luma of each pixel.
+fn luma(rgba: &[u8]) -> Vec<f64> {
+    rgba.chunks_exact(4)
+        .map(|p| 0.2126 * p[0] as f64 + 0.7152 * p[1] as f64 + 0.0722 * p[2] as f64)
+        .collect()
+}
+
+/// Mean SSIM over non-overlapping SSIM_WINDOW windows; an image smaller
+/// than one window is a single window of its own size.
+fn ssim(a: &[f64], b: &[f64], w: usize, h: usize) -> f64 {
+    if w == 0 || h == 0 {
+        return 1.0;
+    }
+    let (win_w, win_h) = (SSIM_WINDOW.min(w), SSIM_WINDOW.min(h));
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in (0..=h - win_h).step_by(win_h) {
+        for x0 in (0..=w - win_w).step_by(win_w) {
+            let pixels =
+                || (y0..y0 + win_h).flat_map(move |y| (x0..x0 + win_w).map(move |x| y * w + x));
+            let n = (win_w * win_h) as f64;
+            let mean_a = pixels().map(|i| a[i]).sum::<f64>() / n;
+            let mean_b = pixels().map(|i| b[i]).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut cov) = (0.0, 0.0, 0.0);
+            for i in pixels() {
+                let (da, db) = (a[i] - mean_a, b[i] - mean_b);
+                var_a += da * da;
+                var_b += db * db;
+                cov += da * db;
+            }
+            let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+            total += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * cov + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 32x32 gradient with a checker on top, so SSIM has structure to
+    /// compare.
+    fn scene() -> Vec<u8> {
+        (0..32 * 32)
+            .flat_map(|i| {
+                let (x, y) = (i % 32, i / 32);
+                let check = if (x / 4 + y / 4) % 2 == 0 { 60 } else { 0 };
+                [(x * 6 + check) as u8, (y * 6) as u8, 128, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_images_pass() {
+        let img = scene();
+        let diff = compare_rgba8(&img, &img, 32, 32, 0).unwrap();
+        assert_eq!(diff.max_channel_delta, 0);
+        assert_eq!(diff.mismatched, 0);
+        assert!((diff.ssim - 1.0).abs() < 1e-9);
+        assert!(diff.passes(&GoldenTolerance::default()));
+    }
+
+    #[test]
+    fn small_noise_passes_and_a_missing_block_fails() {
+        let golden = scene();
+        let tolerance = GoldenTolerance::default();
+
+        // Alpha is ignored; a +-2 wobble is within the channel delta.
+        let mut noisy = golden.clone();
+        for (i, p) in noisy.chunks_exact_mut(4).enumerate() {
+            p[0] = p[0].saturating_add((i % 3) as u8);
+            p[3] = 0;
+        }
+        let diff = compare_rgba8(&noisy, &golden, 32, 32, tolerance.channel_delta).unwrap();
+        assert_eq!(diff.max_channel_delta, 2);
+        assert!(diff.passes(&tolerance), "{diff:?}");
+
+        // A flat 8x8 hole where the checker was.
+        let mut holed = golden.clone();
+        for y in 8..16 {
+            for x in 8..16 {
+                holed[(y * 32 + x) * 4..][..3].copy_from_slice(&[0, 0, 0]);
+            }
+        }
+        let diff = compare_rgba8(&holed, &golden, 32, 32, tolerance.channel_delta).unwrap();
+        assert_eq!(diff.mismatched, 64);
+        assert!(diff.ssim < 1.0);
+        assert!(!diff.passes(&tolerance), "{diff:?}");
+
+        let marked = diff_image_rgba8(&holed, &golden, tolerance.channel_delta);
+        assert_eq!(&marked[(8 * 32 + 8) * 4..][..4], &[255, 0, 0, 255]);
+        assert_ne!(&marked[..4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn size_mismatch_is_an_error() {
+        let img = scene();
+        assert!(compare_rgba8(&img, &img[..img.len() - 4], 32, 32, 0).is_err());
+        assert!(compare_rgba8(&img, &img, 16, 16, 0).is_err());
+    }
+}
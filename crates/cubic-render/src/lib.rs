@@ -1,6 +1,5 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
-use anyhow::Result;
 use bytemuck::{Pod, Zeroable};
 // Re-exported so downstream crates (cubic-app) can name egui's paint-job
 // types in their own renderer-backend abstractions without taking a direct
@@ -8,6 +7,29 @@ use bytemuck::{Pod, Zeroable};
 pub use egui;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
+pub mod clip;
+mod depth;
+mod draw_sort;
+mod egui_mirror;
+mod error;
+mod frame_stats;
+mod golden;
+mod null;
+mod pacer;
+mod vertex_layout;
+pub use depth::DepthConvention;
+pub use draw_sort::{draw_depth, DrawSortKey};
+pub use egui_mirror::EguiTextureMirror;
+pub use error::{MemoryKind, RenderError, RenderResult};
+pub use frame_stats::{
+    CullCounts, FrameStats, FrameStatsTracker, FrameTimeStats, StreamCounts, ValidationCounts,
+    FRAME_STATS_WINDOW,
+};
+pub use golden::{compare_rgba8, diff_image_rgba8, GoldenTolerance, ImageDiff};
+pub use null::{NullRenderer, NullStats};
+pub use pacer::FramePacer;
+pub use vertex_layout::{VertexAttribute, VertexFormat, VertexLayout};
+
 // ---------------------------------------------------------------------------
 // Shared mesh types — defined here so both the renderer backend (cubic-render-vk)
 // and the world/meshing system (cubic-world) can depend on cubic-render
@@ -44,29 +66,230 @@ pub struct PushData {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MeshHandle(pub u32);
 
+/// Opaque handle to a graphics pipeline registered with a renderer backend
+/// (different shaders, blend state, topology, ...). `DEFAULT` is always the
+/// backend's built-in opaque scene pipeline; registered pipelines start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PipelineHandle(pub u32);
+
+impl PipelineHandle {
+    pub const DEFAULT: Self = Self(0);
+}
+
 // ---------------------------------------------------------------------------
 
-#[derive(Clone, Copy, Debug)]
+/// A surface size in physical pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RenderSize {
     pub width: u32,
     pub height: u32,
 }
 
+/// The window a renderer draws into: its physical size and DPI scale
+/// (physical pixels per logical pixel). Renderers size their surfaces in
+/// physical pixels; UI and text are laid out in logical ones and scaled by
+/// `scale_factor` to stay the same apparent size on every display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowInfo {
+    pub size: RenderSize,
+    pub scale_factor: f32,
+}
+
+impl WindowInfo {
+    /// The size in logical pixels (egui's points).
+    pub fn logical_size(&self) -> [f32; 2] {
+        [
+            self.size.width as f32 / self.scale_factor,
+            self.size.height as f32 / self.scale_factor,
+        ]
+    }
+
+    /// A length in logical pixels, in physical ones.
+    pub fn to_physical(&self, logical: f32) -> f32 {
+        logical * self.scale_factor
+    }
+}
+
+/// How finished frames reach the screen (Vulkan's VkPresentModeKHR; GL
+/// maps its swap interval onto Fifo/Immediate).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Vsync: one image per vblank, the queue blocks when full.
+    Fifo,
+    /// Vsync, but a late frame is shown immediately (may tear).
+    FifoRelaxed,
+    /// Vsync without blocking: the newest finished image replaces the
+    /// queued one.
+    Mailbox,
+    /// No vsync; may tear.
+    Immediate,
+    /// Anything the backend can't name here.
+    Other,
+}
+
+/// Whether render() may run ahead of the display (see
+/// `Renderer::set_latency_mode`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LatencyMode {
+    /// The CPU queues frames as far ahead as the swapchain allows: best
+    /// throughput.
+    #[default]
+    Throughput,
+    /// render() returns only once its frame has reached the display (or,
+    /// where that can't be observed, once the GPU has finished it), so the
+    /// next frame's input and simulation start as late, and as fresh, as
+    /// possible. CPU and GPU stop overlapping, which costs frame rate.
+    Low,
+}
+
+/// Curve applied to scene values on their way to the display, by the
+/// backends' tonemap passes (Vulkan's tonemap.frag, GL's hdr.rs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TonemapOperator {
+    /// Narkowicz's ACES filmic fit: soft shoulder, slight contrast boost.
+    #[default]
+    Aces,
+    /// `x / (1 + x)`: gentler, desaturates highlights less than ACES.
+    Reinhard,
+    /// No curve; anything above the display peak clips.
+    Clamp,
+}
+
+impl TonemapOperator {
+    /// CUBIC_TONEMAP's names: aces, reinhard, clamp (or none).
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "aces" => Some(Self::Aces),
+            "reinhard" => Some(Self::Reinhard),
+            "clamp" | "none" => Some(Self::Clamp),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Aces => "ACES",
+            Self::Reinhard => "Reinhard",
+            Self::Clamp => "clamp",
+        }
+    }
+}
+
+/// What a backend's presentation surface actually ended up as, which may
+/// differ from what was asked for (no HDR format, no mailbox, ...). See
+/// `Renderer::surface_info`.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceInfo {
+    /// Backend-specific format name, e.g. "B8G8R8A8_SRGB".
+    pub format: &'static str,
+    /// Backend-specific colour space name, e.g. "SRGB_NONLINEAR".
+    pub color_space: &'static str,
+    pub extent: RenderSize,
+    pub present_mode: PresentMode,
+    /// Output is in an HDR colour space (scRGB or HDR10/PQ).
+    pub hdr: bool,
+    pub image_count: u32,
+}
+
+/// The surface was recreated (resize, vsync/HDR toggle, ...), as drained by
+/// `Renderer::take_surface_change`. Anything sized or formatted after the
+/// surface — post-process targets, UI scaling — should be rebuilt from
+/// `current`.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceChanged {
+    pub previous: SurfaceInfo,
+    pub current: SurfaceInfo,
+}
+
+impl SurfaceChanged {
+    pub fn extent_changed(&self) -> bool {
+        self.previous.extent != self.current.extent
+    }
+
+    /// Format, colour space or HDR state differ.
+    pub fn format_changed(&self) -> bool {
+        self.previous.format != self.current.format
+            || self.previous.color_space != self.current.color_space
+            || self.previous.hdr != self.current.hdr
+    }
+}
+
 pub trait Renderer {
     fn new(
         window: &dyn HasWindowHandle,
         display: &dyn HasDisplayHandle,
         size: RenderSize,
-    ) -> Result<Self>
+    ) -> RenderResult<Self>
     where
         Self: Sized;
 
-    fn resize(&mut self, size: RenderSize) -> Result<()>;
-    fn render(&mut self) -> Result<()>;
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()>;
+    fn render(&mut self) -> RenderResult<()>;
+    /// Release the window surface and everything sized to it (swapchain,
+    /// GL window surface), keeping the device and every uploaded resource.
+    /// For when the platform takes the surface away: Android's pause, a
+    /// Wayland session change. Until resume(), render() draws nothing and
+    /// resize() only records the size. Calling it twice is harmless.
+    fn suspend(&mut self) -> RenderResult<()> {
+        Ok(())
+    }
+    /// Rebuild what suspend() released, on the window handles the renderer
+    /// was created with, at the latest resize()d size. No-op when not
+    /// suspended.
+    fn resume(&mut self) -> RenderResult<()> {
+        Ok(())
+    }
+    /// Whether suspend() is in effect.
+    fn is_suspended(&self) -> bool {
+        false
+    }
     fn set_clear_color(&mut self, rgba: [f32; 4]);
+    /// The window's DPI scale (see WindowInfo), at startup and whenever it
+    /// changes. draw_text still takes physical pixels; this is for
+    /// backends picking glyph and UI raster sizes to match the display.
+    fn set_scale_factor(&mut self, _scale_factor: f32) {}
+    /// Format, colour space, size and present mode of the live swapchain
+    /// (or default framebuffer). Changes on resize and on anything that
+    /// recreates the swapchain, so query it rather than caching it.
+    fn surface_info(&self) -> SurfaceInfo;
+    /// The surface recreation(s) since the last call, if any. Several
+    /// between two calls come back as one change, from the oldest
+    /// `previous` to the newest `current`; poll once per frame.
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged> {
+        None
+    }
     fn set_vsync(&mut self, _on: bool) {}
+    /// Cap the frame rate inside render() (see FramePacer); None uncaps.
+    /// Independent of vsync: with both on, whichever is slower wins.
+    fn set_target_fps(&mut self, _fps: Option<u32>) {}
+    /// Trade throughput for input latency (see LatencyMode).
+    fn set_latency_mode(&mut self, _mode: LatencyMode) {}
+    /// The depth range and test the renderer draws with. Projections
+    /// handed to it must be built for this (see DepthConvention).
+    fn depth_convention(&self) -> DepthConvention {
+        DepthConvention::ReverseZ
+    }
+    /// Switch depth conventions. Returns false, keeping the current one,
+    /// where the backend can't do `convention`.
+    fn set_depth_convention(&mut self, convention: DepthConvention) -> bool {
+        convention == self.depth_convention()
+    }
+    /// Average, p95/p99 and max frame times over the last
+    /// FRAME_STATS_WINDOW frames (see FrameStatsTracker).
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats::default()
+    }
+    /// Log a `frame time spike` warning for any frame slower than
+    /// `threshold`; None turns it off.
+    fn set_frame_spike_threshold(&mut self, _threshold: Option<std::time::Duration>) {}
+    /// The app's voxel streaming counts, reported back in frame_stats.
+    fn record_stream_counts(&mut self, _counts: StreamCounts) {}
     fn free_mesh(&mut self, _handle: MeshHandle) {} // default no-op
-    fn upload_texture(&mut self, _pixels: &[u8], _width: u32, _height: u32) -> Result<u32> {
+    /// Queue screen-space text for the next frame: `pos` is the top-left
+    /// of the first line in pixels, `size` the font's pixel height, `color`
+    /// linear RGBA. Backends without a text overlay ignore it.
+    fn draw_text(&mut self, _pos: [f32; 2], _text: &str, _size: f32, _color: [f32; 4]) {}
+    fn upload_texture(&mut self, _pixels: &[u8], _width: u32, _height: u32) -> RenderResult<u32> {
         Ok(0) // default no-op
     }
     fn queue_egui(
@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! A Renderer that draws nothing, for tests and headless runs: no GPU, no
+//! window system, no surface. It keeps the state the other backends would
+//! (size, clear colour, vsync, latency mode, depth convention, pacing,
+//! frame stats, surface changes) and counts what it was asked to do, so app logic around the
+//! renderer (resize, pause on zero size, frame capping, config
+//! application) can be checked against `NullStats` instead of pixels.
+//!
+//! Meshes and textures get real, distinct handles; a zero-area size or
+//! suspend() pauses render() exactly as it does on GL/Vulkan.
+
+use anyhow::{anyhow, bail, Result};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+
+use crate::{
+    DepthConvention, FramePacer, FrameStats, FrameStatsTracker, LatencyMode, MeshHandle,
+    PresentMode, PushData, RenderResult, RenderSize, Renderer, StreamCounts, SurfaceChanged,
+    SurfaceInfo, Vertex,
+};
+
+/// What a NullRenderer has been asked to do. Totals since creation unless
+/// the field says otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullStats {
+    /// render() calls that produced a frame (not paused).
+    pub frames: u64,
+    /// render() calls skipped because the size was zero or the renderer
+    /// was suspended.
+    pub paused_frames: u64,
+    pub resizes: u64,
+    /// Meshes uploaded and not freed.
+    pub live_meshes: u32,
+    pub textures: u32,
+    /// draw_mesh calls in the last frame.
+    pub draws_last_frame: u32,
+    /// draw_text calls in the last frame.
+    pub texts_last_frame: u32,
+    /// Frames that had an egui overlay queued.
+    pub egui_frames: u64,
+}
+
+#[derive(Debug)]
+pub struct NullRenderer {
+    size: RenderSize,
+    clear: [f32; 4],
+    vsync: bool,
+    latency_mode: LatencyMode,
+    depth_convention: DepthConvention,
+    pacer: FramePacer,
+    frame_stats: FrameStatsTracker,
+    surface_change: Option<SurfaceChanged>,
+    // Slot per handle ever handed out; false once freed.
+    meshes: Vec<bool>,
+    draws: u32,
+    texts: u32,
+    egui_queued: bool,
+    suspended: bool,
+    // upload_texture_async indices not yet reported by
+    // take_resident_textures.
+    resident: Vec<u32>,
+    stats: NullStats,
+}
+
+impl NullRenderer {
+    /// The same as `Renderer::new`, minus the handles it would ignore.
+    pub fn headless(size: RenderSize) -> Self {
+        Self {
+            size,
+            clear: [0.0, 0.0, 0.0, 1.0],
+            vsync: true,
+            latency_mode: LatencyMode::default(),
+            depth_convention: DepthConvention::default(),
+            pacer: FramePacer::new(),
+            frame_stats: FrameStatsTracker::default(),
+            surface_change: None,
+            meshes: Vec::new(),
+            draws: 0,
+            texts: 0,
+            egui_queued: false,
+            suspended: false,
+            resident: Vec::new(),
+            stats: NullStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> NullStats {
+        self.stats
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    pub fn target_fps_frame_time(&self) -> Option<std::time::Duration> {
+        self.pacer.frame_time()
+    }
+
+    /// Mirrors the other backends' upload_mesh: a fresh handle per call.
+    pub fn upload_mesh(&mut self, _verts: &[Vertex], _idxs: &[u32]) -> RenderResult<MeshHandle> {
+        self.meshes.push(true);
+        self.stats.live_meshes += 1;
+        Ok(MeshHandle(self.meshes.len() as u32 - 1))
+    }
+
+    /// Counts the draw; drawing a freed or unknown handle is an error the
+    /// real backends would hit too, so it fails loudly here.
+    pub fn draw_mesh(&mut self, handle: MeshHandle, _push: PushData) {
+        assert!(
+            self.meshes.get(handle.0 as usize) == Some(&true),
+            "draw_mesh on dead mesh handle {handle:?}"
+        );
+        self.draws += 1;
+    }
+
+    /// Like upload_texture, but the index is only reported resident by the
+    /// next take_resident_textures, as on Vulkan.
+    pub fn upload_texture_async(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<u32> {
+        let index = self.upload_texture(pixels, width, height)?;
+        self.resident.push(index);
+        Ok(index)
+    }
+
+    pub fn take_resident_textures(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.resident)
+    }
+
+    pub fn replace_texture(
+        &mut self,
+        index: u32,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> RenderResult<()> {
+        if index >= self.stats.textures {
+            return Err(anyhow!("replace_texture: no texture {index}").into());
+        }
+        Ok(check_rgba8(pixels, width, height)?)
+    }
+
+    pub fn present_summary(&self) -> String {
+        format!(
+            "null renderer, {}x{}, vsync {}",
+            self.size.width,
+            self.size.height,
+            if self.vsync { "on" } else { "off" }
+        )
+    }
+}
+
+impl Renderer for NullRenderer {
+    fn new(
+        _window: &dyn HasWindowHandle,
+        _display: &dyn HasDisplayHandle,
+        size: RenderSize,
+    ) -> RenderResult<Self> {
+        Ok(Self::headless(size))
+    }
+
+    fn resize(&mut self, size: RenderSize) -> RenderResult<()> {
+        let previous = self
+            .surface_change
+            .map_or(self.surface_info(), |c| c.previous);
+        self.size = size;
+        self.stats.resizes += 1;
+        self.surface_change = Some(SurfaceChanged {
+            previous,
+            current: self.surface_info(),
+        });
+        Ok(())
+    }
+
+    fn render(&mut self) -> RenderResult<()> {
+        let draws = std::mem::take(&mut self.draws);
+        let texts = std::mem::take(&mut self.texts);
+        let egui = std::mem::take(&mut self.egui_queued);
+        if self.suspended || self.size.width == 0 || self.size.height == 0 {
+            self.stats.paused_frames += 1;
+            return Ok(());
+        }
+        let cpu_start = std::time::Instant::now();
+        self.stats.frames += 1;
+        self.stats.draws_last_frame = draws;
+        self.stats.texts_last_frame = texts;
+        self.stats.egui_frames += u64::from(egui);
+        self.frame_stats.record(cpu_start.elapsed());
+        self.pacer.wait();
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> RenderResult<()> {
+        self.suspended = true;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> RenderResult<()> {
+        self.suspended = false;
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    fn set_clear_color(&mut self, rgba: [f32; 4]) {
+        self.clear = rgba;
+    }
+
+    fn surface_info(&self) -> SurfaceInfo {
+        SurfaceInfo {
+            format: "NONE",
+            color_space: "NONE",
+            extent: self.size,
+            present_mode: if self.vsync {
+                PresentMode::Fifo
+            } else {
+                PresentMode::Immediate
+            },
+            hdr: false,
+            image_count: 1,
+        }
+    }
+
+    fn take_surface_change(&mut self) -> Option<SurfaceChanged> {
+        self.surface_change.take()
+    }
+
+    fn set_vsync(&mut self, on: bool) {
+        self.vsync = on;
+    }
+
+    fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.pacer.set_target_fps(fps);
+    }
+
+    fn set_latency_mode(&mut self, mode: LatencyMode) {
+        self.latency_mode = mode;
+    }
+
+    fn depth_convention(&self) -> DepthConvention {
+        self.depth_convention
+    }
+
+    /// Both conventions; there's no depth buffer to get wrong.
+    fn set_depth_convention(&mut self, convention: DepthConvention) -> bool {
+        self.depth_convention = convention;
+        true
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        self.frame_stats.stats()
+    }
+
+    fn set_frame_spike_threshold(&mut self, threshold: Option<std::time::Duration>) {
+        self.frame_stats.set_spike_threshold(threshold);
+    }
+
+    fn record_stream_counts(&mut self, counts: StreamCounts) {
+        self.frame_stats.record_streaming(counts);
+    }
+
+    fn free_mesh(&mut self, handle: MeshHandle) {
+        if let Some(live) = self.meshes.get_mut(handle.0 as usize) {
+            if std::mem::take(live) {
+                self.stats.live_meshes -= 1;
+            }
+        }
+    }
+
+    fn draw_text(&mut self, _pos: [f32; 2], _text: &str, _size: f32, _color: [f32; 4]) {
+        self.texts += 1;
+    }
+
+    fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> RenderResult<u32> {
+        check_rgba8(pixels, width, height)?;
+        self.stats.textures += 1;
+        Ok(self.stats.textures - 1)
+    }
+
+    fn queue_egui(
+        &mut self,
+        _textures_delta: egui::TexturesDelta,
+        _paint_jobs: Vec<egui::ClippedPrimitive>,
+        _screen_width: u32,
+        _screen_height: u32,
+        _pixels_per_point: f32,
+    ) {
+        self.egui_queued = true;
+    }
+}
+
+fn check_rgba8(pixels: &[u8], width: u32, height: u32) -> Result<()> {
+    if pixels.len() != width as usize * height as usize * 4 {
+        bail!(
+            "{} bytes for a {width}x{height} RGBA8 texture",
+            pixels.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    const SIZE: RenderSize = RenderSize {
+        width: 640,
+        height: 480,
+    };
+
+    #[test]
+    fn zero_size_pauses_render() {
+        let mut r = NullRenderer::headless(SIZE);
+        r.render().unwrap();
+        r.resize(RenderSize {
+            width: 0,
+            height: 0,
+        })
+        .unwrap();
+        r.render().unwrap();
+        r.resize(SIZE).unwrap();
+        r.render().unwrap();
+        let stats = r.stats();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.paused_frames, 1);
+        assert_eq!(stats.resizes, 2);
+    }
+
+    #[test]
+    fn suspend_skips_frames_until_resume() {
+        let mut r = NullRenderer::headless(SIZE);
+        r.suspend().unwrap();
+        r.suspend().unwrap();
+        assert!(r.is_suspended());
+        r.render().unwrap();
+        r.resume().unwrap();
+        r.render().unwrap();
+        assert!(!r.is_suspended());
+        assert_eq!(r.stats().frames, 1);
+        assert_eq!(r.stats().paused_frames, 1);
+    }
+
+    #[test]
+    fn surface_changes_coalesce() {
+        let mut r = NullRenderer::headless(SIZE);
+        r.resize(RenderSize {
+            width: 800,
+            height: 600,
+        })
+        .unwrap();
+        r.resize(RenderSize {
+            width: 1024,
+            height: 768,
+        })
+        .unwrap();
+        let change = r.take_surface_change().unwrap();
+        assert_eq!(change.previous.extent, SIZE);
+        assert_eq!(change.current.extent.width, 1024);
+        assert!(r.take_surface_change().is_none());
+    }
+
+    #[test]
+    fn draws_count_per_frame() {
+        let mut r = NullRenderer::headless(SIZE);
+        let mesh = r.upload_mesh(&[], &[]).unwrap();
+        r.draw_mesh(mesh, PushData::zeroed());
+        r.draw_mesh(mesh, PushData::zeroed());
+        r.draw_text([0.0, 0.0], "hi", 16.0, [1.0; 4]);
+        r.render().unwrap();
+        assert_eq!(r.stats().draws_last_frame, 2);
+        assert_eq!(r.stats().texts_last_frame, 1);
+        r.render().unwrap();
+        assert_eq!(r.stats().draws_last_frame, 0);
+        r.free_mesh(mesh);
+        r.free_mesh(mesh);
+        assert_eq!(r.stats().live_meshes, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "dead mesh handle")]
+    fn drawing_a_freed_mesh_panics() {
+        let mut r = NullRenderer::headless(SIZE);
+        let mesh = r.upload_mesh(&[], &[]).unwrap();
+        r.free_mesh(mesh);
+        r.draw_mesh(mesh, PushData::zeroed());
+    }
+
+    #[test]
+    fn settings_are_observable() {
+        let mut r = NullRenderer::headless(SIZE);
+        r.set_vsync(false);
+        r.set_clear_color([0.1, 0.2, 0.3, 1.0]);
+        r.set_latency_mode(LatencyMode::Low);
+        r.set_target_fps(Some(50));
+        assert!(r.set_depth_convention(DepthConvention::Standard));
+        assert!(!r.vsync());
+        assert_eq!(r.surface_info().present_mode, PresentMode::Immediate);
+        assert_eq!(r.clear_color(), [0.1, 0.2, 0.3, 1.0]);
+        assert_eq!(r.latency_mode(), LatencyMode::Low);
+        assert_eq!(r.depth_convention(), DepthConvention::Standard);
+        assert_eq!(
+            r.target_fps_frame_time(),
+            Some(std::time::Duration::from_millis(20))
+        );
+    }
+}
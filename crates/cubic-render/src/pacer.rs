@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! CPU frame pacer behind the backends' `Renderer::set_target_fps`. Each
+//! frame owns a fixed time slot; `wait()` blocks until the current slot
+//! ends. OS sleeps overshoot by up to a scheduler tick, so the last stretch
+//! is a spin instead: sleep until SPIN_MARGIN before the deadline, then
+//! busy-wait the rest. Costs at most SPIN_MARGIN of one core per frame, and
+//! keeps frame times flat where a sleep-only limiter jitters by a
+//! millisecond or more.
+
+use std::time::{Duration, Instant};
+
+/// How far ahead of the deadline sleeping stops and spinning starts.
+/// Comfortably above typical Linux/Windows timer slack (~50 us / ~1 ms).
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+#[derive(Debug, Default)]
+pub struct FramePacer {
+    // None = uncapped; wait() returns immediately.
+    frame: Option<Duration>,
+    // End of the current frame's slot; None until the first paced frame.
+    deadline: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cap to `fps` frames per second; None or Some(0) uncaps. Changing
+    /// the target restarts the cadence from the next frame rather than
+    /// carrying over a deadline computed for the old rate.
+    pub fn set_target_fps(&mut self, fps: Option<u32>) {
+        let frame = fps
+            .filter(|&f| f > 0)
+            .map(|f| Duration::from_nanos(1_000_000_000 / f as u64));
+        if frame != self.frame {
+            self.frame = frame;
+            self.deadline = None;
+        }
+    }
+
+    /// Duration of one frame slot at the current target, if capped.
+    pub fn frame_time(&self) -> Option<Duration> {
+        self.frame
+    }
+
+    /// Block until the current frame's slot ends, then open the next one.
+    /// Call once per frame, after present.
+    pub fn wait(&mut self) {
+        let Some(frame) = self.frame else {
+            return;
+        };
+        let Some(deadline) = self.deadline else {
+            self.deadline = Some(Instant::now() + frame);
+            return;
+        };
+
+        let now = Instant::now();
+        if deadline > now {
+            let remaining = deadline - now;
+            if remaining > SPIN_MARGIN {
+                std::thread::sleep(remaining - SPIN_MARGIN);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        // Keep the cadence (next slot starts where this one ended) unless a
+        // hitch left us more than a whole frame behind — then restart from
+        // now instead of rushing several frames out back to back.
+        let next = deadline + frame;
+        let now = Instant::now();
+        self.deadline = Some(if next < now { now + frame } else { next });
+    }
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Backend-agnostic description of a vertex buffer's memory layout, so
+//! meshes aren't limited to the built-in `Vertex` (pos/color/uv/normal/
+//! tex_index). A pipeline is built for one layout and only draws meshes
+//! uploaded with that same layout; `VertexLayout::standard()` is `Vertex`'s.
+
+use anyhow::{anyhow, Result};
+use std::mem::offset_of;
+
+use crate::Vertex;
+
+/// Per-attribute data type, as it sits in the vertex buffer. Normalized
+/// variants arrive in the shader as floats in [0, 1] / [-1, 1].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    Float32,
+    Float32x2,
+    Float32x3,
+    Float32x4,
+    Uint32,
+    Uint32x2,
+    Uint32x3,
+    Uint32x4,
+    Sint32,
+    /// Colours, blend weights.
+    Unorm8x4,
+    /// Packed normals/tangents.
+    Snorm8x4,
+    /// Joint indices for up to 256 bones.
+    Uint8x4,
+    /// Joint indices beyond 256 bones.
+    Uint16x4,
+    Unorm16x4,
+    Snorm16x4,
+}
+
+impl VertexFormat {
+    /// Size in bytes.
+    pub fn size(self) -> u32 {
+        match self {
+            Self::Float32 | Self::Uint32 | Self::Sint32 => 4,
+            Self::Unorm8x4 | Self::Snorm8x4 | Self::Uint8x4 => 4,
+            Self::Float32x2 | Self::Uint32x2 => 8,
+            Self::Uint16x4 | Self::Unorm16x4 | Self::Snorm16x4 => 8,
+            Self::Float32x3 | Self::Uint32x3 => 12,
+            Self::Float32x4 | Self::Uint32x4 => 16,
+        }
+    }
+}
+
+/// One shader input: `layout(location = N)` reads `format` at byte
+/// `offset` within each vertex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub offset: u32,
+    pub format: VertexFormat,
+}
+
+/// Stride plus attribute list of a single interleaved vertex buffer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub stride: u32,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    /// The built-in `Vertex`: pos (0), color (1), uv (2), normal (3),
+    /// tex_index (4). What `upload_mesh` and the default pipeline use.
+    pub fn standard() -> Self {
+        let attr = |location, offset: usize, format| VertexAttribute {
+            location,
+            offset: offset as u32,
+            format,
+        };
+        Self {
+            stride: std::mem::size_of::<Vertex>() as u32,
+            attributes: vec![
+                attr(0, offset_of!(Vertex, pos), VertexFormat::Float32x3),
+                attr(1, offset_of!(Vertex, color), VertexFormat::Float32x3),
+                attr(2, offset_of!(Vertex, uv), VertexFormat::Float32x2),
+                attr(3, offset_of!(Vertex, normal), VertexFormat::Float32x3),
+                attr(4, offset_of!(Vertex, tex_index), VertexFormat::Uint32),
+            ],
+        }
+    }
+
+    /// Builder for packed layouts: attributes laid out back to back in
+    /// call order, stride = their total size.
+    pub fn packed(formats: &[(u32, VertexFormat)]) -> Self {
+        let mut offset = 0;
+        let attributes = formats
+            .iter()
+            .map(|&(location, format)| {
+                let a = VertexAttribute {
+                    location,
+                    offset,
+                    format,
+                };
+                offset += format.size();
+                a
+            })
+            .collect();
+        Self {
+            stride: offset,
+            attributes,
+        }
+    }
+
+    /// Reject layouts no backend could build a pipeline from: zero stride,
+    /// no attributes, an attribute running past the stride, or two
+    /// attributes on one location. Device limits (attribute count, max
+    /// stride) are the backend's to check.
+    pub fn validate(&self) -> Result<()> {
+        if self.stride == 0 {
+            return Err(anyhow!("vertex layout: stride is 0"));
+        }
+        if self.attributes.is_empty() {
+            return Err(anyhow!("vertex layout: no attributes"));
+        }
+        for (i, a) in self.attributes.iter().enumerate() {
+            if a.offset + a.format.size() > self.stride {
+                return Err(anyhow!(
+                    "vertex layout: location {} ({:?} at offset {}) overruns stride {}",
+                    a.location,
+                    a.format,
+                    a.offset,
+                    self.stride
+                ));
+            }
+            if self.attributes[..i]
+                .iter()
+                .any(|b| b.location == a.location)
+            {
+                return Err(anyhow!("vertex layout: location {} used twice", a.location));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for VertexLayout {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
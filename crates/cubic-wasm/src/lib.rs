@@ -335,6 +335,51 @@ impl WasmInstance {
             },
         )?;
 
+        linker.func_wrap(
+            IMPORT_PHYSICS_MODULE,
+            "raycast",
+            |mut caller: wasmtime::Caller<'_, HostState>,
+             ox: f64,
+             oy: f64,
+             oz: f64,
+             dx: f32,
+             dy: f32,
+             dz: f32,
+             max_dist: f32,
+             out_ptr: i32|
+             -> i32 {
+                let hit = with_chunk_query(|q| {
+                    q.and_then(|q| {
+                        cubic_world::raycast(
+                            q,
+                            cubic_math::DVec3::new(ox, oy, oz),
+                            cubic_math::Vec3::new(dx, dy, dz).as_dvec3(),
+                            max_dist as f64,
+                        )
+                    })
+                });
+                let Some(hit) = hit else {
+                    return 0;
+                };
+                let mem = caller
+                    .get_export("memory")
+                    .and_then(|e| e.into_memory())
+                    .expect("guest has no memory export");
+                let data = mem.data_mut(&mut caller);
+                let base = out_ptr as usize;
+                let corner = hit.pos.to_world_origin();
+                data[base..base + 8].copy_from_slice(&corner.x.to_le_bytes());
+                data[base + 8..base + 16].copy_from_slice(&corner.y.to_le_bytes());
+                data[base + 16..base + 24].copy_from_slice(&corner.z.to_le_bytes());
+                for (i, n) in hit.normal.iter().enumerate() {
+                    let at = base + 24 + i * 4;
+                    data[at..at + 4].copy_from_slice(&n.to_le_bytes());
+                }
+                data[base + 36..base + 40].copy_from_slice(&hit.block.0.to_le_bytes());
+                40i32
+            },
+        )?;
+
         linker.func_wrap(
             IMPORT_PHYSICS_MODULE,
             "request-set-block",
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Block-face texture registry: every face texture the block registry
+//! names, packed as the layers of one texture array so a chunk mesh draws
+//! without switching textures per block type.
+//!
+//! Layers are all `size` x `size`; faces of any other size are resampled
+//! nearest-neighbour on insert (block textures are pixel art, and blending
+//! would blur it). Layer 0 is reserved for faces with no texture or one
+//! that failed to load, a 2x2 checker like the renderer's own fallback.
+//!
+//! The mesher needs nothing different for it: build the
+//! `BlockFaceTextures` table from `layer` instead of bindless indices and
+//! every vertex's `tex_index` names its layer, with the UVs it already
+//! emits (in blocks, repeating across greedy-merged quads).
+
+use std::collections::HashMap;
+
+/// Packed RGBA8 layers and the path each came from.
+pub struct BlockTextureArray {
+    size: u32,
+    /// Layer after layer, `size * size * 4` bytes each.
+    pixels: Vec<u8>,
+    layers: HashMap<String, u32>,
+}
+
+impl BlockTextureArray {
+    /// An array of `size` x `size` layers holding just the fallback.
+    pub fn new(size: u32) -> Self {
+        let size = size.max(1);
+        let half = (size / 2).max(1);
+        let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let light = (x < half) == (y < half);
+                let v = if light { 255 } else { 0 };
+                pixels.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        Self {
+            size,
+            pixels,
+            layers: HashMap::new(),
+        }
+    }
+
+    /// Add `path`'s RGBA8 pixels as a new layer, returning it. A path
+    /// already added keeps its first layer; pixels that don't match
+    /// `width` x `height` get the fallback.
+    pub fn insert(&mut self, path: &str, pixels: &[u8], width: u32, height: u32) -> u32 {
+        if let Some(&layer) = self.layers.get(path) {
+            return layer;
+        }
+        if width == 0 || height == 0 || pixels.len() != (width * height * 4) as usize {
+            return 0;
+        }
+        let layer = self.layer_count();
+        for y in 0..self.size {
+            let sy = (y * height / self.size) as usize;
+            for x in 0..self.size {
+                let sx = (x * width / self.size) as usize;
+                let i = (sy * width as usize + sx) * 4;
+                self.pixels.extend_from_slice(&pixels[i..i + 4]);
+            }
+        }
+        self.layers.insert(path.to_string(), layer);
+        layer
+    }
+
+    /// `path`'s layer, or the fallback's (0) if it was never added.
+    pub fn layer(&self, path: &str) -> u32 {
+        self.layers.get(path).copied().unwrap_or(0)
+    }
+
+    /// Side length of every layer, in texels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Layers including the fallback.
+    pub fn layer_count(&self) -> u32 {
+        (self.pixels.len() / (self.size * self.size * 4) as usize) as u32
+    }
+
+    /// Every layer back to back, ready for the renderer's upload.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Vec<u8> {
+        rgba.repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn starts_with_the_fallback_layer() {
+        let array = BlockTextureArray::new(4);
+        assert_eq!(array.layer_count(), 1);
+        assert_eq!(array.pixels().len(), 4 * 4 * 4);
+        assert_eq!(array.layer("missing.png"), 0);
+        // Checker: top-left light, top-right dark.
+        assert_eq!(&array.pixels()[..4], &[255, 255, 255, 255]);
+        assert_eq!(&array.pixels()[3 * 4..4 * 4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn insert_dedups_by_path() {
+        let mut array = BlockTextureArray::new(2);
+        let red = solid(2, 2, [255, 0, 0, 255]);
+        let green = solid(2, 2, [0, 255, 0, 255]);
+        assert_eq!(array.insert("stone.png", &red, 2, 2), 1);
+        assert_eq!(array.insert("grass.png", &green, 2, 2), 2);
+        assert_eq!(array.insert("stone.png", &green, 2, 2), 1);
+        assert_eq!(array.layer_count(), 3);
+        assert_eq!(array.layer("grass.png"), 2);
+        // Bad pixel data falls back rather than adding a layer.
+        assert_eq!(array.insert("bad.png", &red, 3, 3), 0);
+        assert_eq!(array.layer_count(), 3);
+    }
+
+    #[test]
+    fn resamples_to_the_layer_size() {
+        let mut array = BlockTextureArray::new(4);
+        // 2x1: left red, right blue.
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+        let layer = array.insert("wide.png", &pixels, 2, 1);
+        let layer_len = 4 * 4 * 4;
+        let texels = &array.pixels()[layer as usize * layer_len..][..layer_len];
+        for row in texels.chunks_exact(4 * 4) {
+            assert_eq!(&row[..8], &[255, 0, 0, 255, 255, 0, 0, 255]);
+            assert_eq!(&row[8..], &[0, 0, 255, 255, 0, 0, 255, 255]);
+        }
+    }
+}
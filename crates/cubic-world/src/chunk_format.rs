@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Versioned whole-chunk serialization, the payload of a region file's
+//! full-chunk diffs (see region.rs).
+//!
+//! # Layout
+//! ```text
+//! [format version: u8]    currently 1
+//! [compression: u8]       0 = none, 1 = LZ4, 2 = zstd
+//! [body_len: u32]         uncompressed body size
+//! [body ...]              compressed as the byte above says
+//! ```
+//! The body is the chunk's palette, cut down to air and the block types
+//! actually present, then its voxels in storage order as runs of one palette index:
+//! ```text
+//! [palette_len: varint][palette: palette_len × varint block id]
+//! [run_count: varint][runs: run_count × (length: varint, index: varint)]
+//! ```
+//! Varints are LEB128. Natural terrain is long runs of air, stone and
+//! dirt, so the runs alone usually land well under a kilobyte before any
+//! compression; LZ4 or zstd then squeeze the repeats between runs.
+//!
+//! zstd needs the crate's "zstd" feature. Without it, encoding with
+//! `ChunkCompression::Zstd` falls back to LZ4 and decoding a zstd chunk is
+//! an error.
+
+use crate::{BlockTypeId, Chunk, CHUNK_VOLUME};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Current chunk format version, written first in every payload.
+pub const CHUNK_FORMAT_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 6;
+
+/// Most palette entries a chunk can encode: air's slot plus one per voxel.
+const MAX_PALETTE_LEN: usize = CHUNK_VOLUME + 1;
+
+/// Largest body a chunk can encode to: a full palette, every voxel its own
+/// run, every varint at its 5-byte worst, plus the two counts.
+/// A header claiming more is corrupt, and is refused before the
+/// decompressors allocate `body_len` bytes for it.
+const MAX_BODY_LEN: usize = 5 * (2 + MAX_PALETTE_LEN + 2 * CHUNK_VOLUME);
+
+/// How an encoded chunk's body is compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChunkCompression {
+    /// Palette and runs only.
+    None,
+    /// Fast to write, the default.
+    #[default]
+    Lz4,
+    /// Smaller and slower; needs the "zstd" feature.
+    Zstd,
+}
+
+impl ChunkCompression {
+    fn tag(self) -> u8 {
+        match self {
+            ChunkCompression::None => 0,
+            ChunkCompression::Lz4 => 1,
+            ChunkCompression::Zstd => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        Ok(match tag {
+            0 => ChunkCompression::None,
+            1 => ChunkCompression::Lz4,
+            2 => ChunkCompression::Zstd,
+            t => bail!("unknown chunk compression {t}"),
+        })
+    }
+}
+
+/// Serialize `chunk` (block ids only; instance data isn't persisted yet).
+pub fn encode_chunk(chunk: &Chunk, compression: ChunkCompression) -> Vec<u8> {
+    // Palette entries in first-use order, so unused ones drop out; slot 0
+    // stays air whether or not any is left.
+    let mut remap: HashMap<u16, u32> = HashMap::from([(0, 0)]);
+    let mut palette = vec![chunk.palette[0].0];
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &index in chunk.data.iter() {
+        let compact = *remap.entry(index).or_insert_with(|| {
+            palette.push(chunk.palette[index as usize].0);
+            palette.len() as u32 - 1
+        });
+        match runs.last_mut() {
+            Some((len, run)) if *run == compact => *len += 1,
+            _ => runs.push((1, compact)),
+        }
+    }
+
+    let mut body = Vec::new();
+    write_varint(&mut body, palette.len() as u32);
+    for id in palette {
+        write_varint(&mut body, id);
+    }
+    write_varint(&mut body, runs.len() as u32);
+    for (len, index) in runs {
+        write_varint(&mut body, len);
+        write_varint(&mut body, index);
+    }
+
+    let compression = match compression {
+        ChunkCompression::Zstd if !cfg!(feature = "zstd") => ChunkCompression::Lz4,
+        c => c,
+    };
+    let packed = match compression {
+        ChunkCompression::None => body.clone(),
+        ChunkCompression::Lz4 => lz4_flex::compress(&body),
+        ChunkCompression::Zstd => zstd_compress(&body),
+    };
+    let mut out = Vec::with_capacity(HEADER_LEN + packed.len());
+    out.push(CHUNK_FORMAT_VERSION);
+    out.push(compression.tag());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&packed);
+    out
+}
+
+/// Parse a payload `encode_chunk` wrote, of any version up to this one.
+pub fn decode_chunk(bytes: &[u8]) -> Result<Chunk> {
+    if bytes.len() < HEADER_LEN {
+        bail!("chunk payload too short");
+    }
+    let version = bytes[0];
+    if version == 0 || version > CHUNK_FORMAT_VERSION {
+        bail!("unsupported chunk format version {version}");
+    }
+    let compression = ChunkCompression::from_tag(bytes[1])?;
+    let body_len = u32::from_le_bytes(bytes[2..6].try_into().unwrap()) as usize;
+    if body_len > MAX_BODY_LEN {
+        bail!("chunk body length {body_len} exceeds the {MAX_BODY_LEN}-byte maximum");
+    }
+    let packed = &bytes[HEADER_LEN..];
+    let body = match compression {
+        ChunkCompression::None => packed.to_vec(),
+        ChunkCompression::Lz4 => {
+            lz4_flex::decompress(packed, body_len).context("lz4 decompress failed")?
+        }
+        ChunkCompression::Zstd => zstd_decompress(packed, body_len)?,
+    };
+    if body.len() != body_len {
+        bail!("chunk body is {} bytes, header says {body_len}", body.len());
+    }
+
+    let mut cur = 0usize;
+    let palette_len = read_varint(&body, &mut cur)? as usize;
+    if palette_len == 0 || palette_len > MAX_PALETTE_LEN {
+        bail!("bad chunk palette length {palette_len}");
+    }
+    let palette: Vec<BlockTypeId> = (0..palette_len)
+        .map(|_| read_varint(&body, &mut cur).map(BlockTypeId))
+        .collect::<Result<_>>()?;
+
+    let run_count = read_varint(&body, &mut cur)? as usize;
+    let mut data = Box::new([0u16; CHUNK_VOLUME]);
+    let mut filled = 0usize;
+    for _ in 0..run_count {
+        let len = read_varint(&body, &mut cur)? as usize;
+        let index = read_varint(&body, &mut cur)? as usize;
+        if index >= palette_len {
+            bail!("chunk run index {index} outside palette of {palette_len}");
+        }
+        if len > CHUNK_VOLUME - filled {
+            bail!("chunk runs overflow {CHUNK_VOLUME} voxels");
+        }
+        data[filled..filled + len].fill(index as u16);
+        filled += len;
+    }
+    if filled != CHUNK_VOLUME {
+        bail!("chunk runs cover {filled} of {CHUNK_VOLUME} voxels");
+    }
+
+    Ok(Chunk {
+        palette,
+        data,
+        instance_data: HashMap::new(),
+    })
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(buf: &[u8], cur: &mut usize) -> Result<u32> {
+    let mut v = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *buf.get(*cur).context("chunk body truncated")?;
+        *cur += 1;
+        v |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    bail!("chunk varint too long")
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_compress(body: &[u8]) -> Vec<u8> {
+    // Level 3 is zstd's own default: most of the ratio, still quick.
+    zstd::bulk::compress(body, 3).expect("zstd compress into a Vec")
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_body: &[u8]) -> Vec<u8> {
+    unreachable!("encode_chunk falls back to LZ4 without the zstd feature")
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(packed: &[u8], body_len: usize) -> Result<Vec<u8>> {
+    zstd::bulk::decompress(packed, body_len).context("zstd decompress failed")
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_packed: &[u8], _body_len: usize) -> Result<Vec<u8>> {
+    bail!("zstd-compressed chunk, but cubic-world was built without the \"zstd\" feature")
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChunkLocalPos;
+
+    fn layered_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..32u8 {
+            for z in 0..32u8 {
+                for y in 0..8u8 {
+                    chunk.set(ChunkLocalPos::new(x, y, z), BlockTypeId(1));
+                }
+                chunk.set(ChunkLocalPos::new(x, 8, z), BlockTypeId(2));
+            }
+        }
+        chunk.set(ChunkLocalPos::new(3, 20, 7), BlockTypeId(300));
+        chunk
+    }
+
+    fn same_blocks(a: &Chunk, b: &Chunk) -> bool {
+        (0..CHUNK_VOLUME).all(|i| a.palette[a.data[i] as usize] == b.palette[b.data[i] as usize])
+    }
+
+    #[test]
+    fn roundtrips_with_every_codec() {
+        let chunk = layered_chunk();
+        for compression in [
+            ChunkCompression::None,
+            ChunkCompression::Lz4,
+            ChunkCompression::Zstd,
+        ] {
+            let bytes = encode_chunk(&chunk, compression);
+            assert_eq!(bytes[0], CHUNK_FORMAT_VERSION);
+            let back = decode_chunk(&bytes).unwrap();
+            assert!(same_blocks(&chunk, &back), "{compression:?}");
+        }
+    }
+
+    #[test]
+    fn unused_palette_entries_are_dropped() {
+        let mut chunk = layered_chunk();
+        // Overwrite the lone block: its id stays in the live palette.
+        chunk.set(ChunkLocalPos::new(3, 20, 7), BlockTypeId(0));
+        assert!(chunk.palette.contains(&BlockTypeId(300)));
+        let back = decode_chunk(&encode_chunk(&chunk, ChunkCompression::None)).unwrap();
+        assert!(!back.palette.contains(&BlockTypeId(300)));
+        assert!(same_blocks(&chunk, &back));
+    }
+
+    #[test]
+    fn runs_keep_uniform_chunks_tiny() {
+        let bytes = encode_chunk(&Chunk::new(), ChunkCompression::None);
+        // Header, one palette entry, one run of 32768.
+        assert!(bytes.len() < 16, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn rejects_newer_versions_and_bad_runs() {
+        let mut bytes = encode_chunk(&layered_chunk(), ChunkCompression::None);
+        bytes[0] = CHUNK_FORMAT_VERSION + 1;
+        assert!(decode_chunk(&bytes).is_err());
+
+        // One run short of a full chunk.
+        let mut body = Vec::new();
+        write_varint(&mut body, 1);
+        write_varint(&mut body, 0);
+        write_varint(&mut body, 1);
+        write_varint(&mut body, CHUNK_VOLUME as u32 - 1);
+        write_varint(&mut body, 0);
+        let mut short = vec![CHUNK_FORMAT_VERSION, 0];
+        short.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        short.extend_from_slice(&body);
+        assert!(decode_chunk(&short).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_body_len_before_decompressing() {
+        let mut bytes = encode_chunk(&layered_chunk(), ChunkCompression::Lz4);
+        bytes[2..6].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = decode_chunk(&bytes)
+            .err()
+            .expect("oversized body_len accepted");
+        assert!(err.to_string().contains("exceeds"), "{err}");
+
+        // The bounds still admit the worst real chunk: every voxel distinct,
+        // so air's slot sits unused ahead of 32768 other entries.
+        let mut chunk = Chunk::new();
+        for x in 0..32u8 {
+            for y in 0..32u8 {
+                for z in 0..32u8 {
+                    let id = 1 + x as u32 * 1024 + y as u32 * 32 + z as u32;
+                    chunk.set(ChunkLocalPos::new(x, y, z), BlockTypeId(id));
+                }
+            }
+        }
+        let bytes = encode_chunk(&chunk, ChunkCompression::None);
+        assert!(bytes.len() - HEADER_LEN <= MAX_BODY_LEN);
+        assert!(same_blocks(&chunk, &decode_chunk(&bytes).unwrap()));
+    }
+}
@@ -1,7 +1,9 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
+pub mod block_textures;
+pub use block_textures::BlockTextureArray;
 pub mod mesher;
-pub use mesher::{mesh_chunk, BlockFaceTextures};
+pub use mesher::{mesh_chunk, mesh_chunk_culled, mesh_chunk_lod, BlockFaceTextures};
 pub mod generator;
 pub use generator::WorldGenerator;
 pub mod stream;
@@ -9,7 +11,11 @@ pub use stream::{world_pos_to_chunk, StreamDelta, WorldStream};
 pub mod stream_pool;
 pub use stream_pool::AsyncWorldStream;
 pub mod physics;
-pub use physics::{sweep_aabb, world_to_chunk_local, ChunkQuery, SweepResult};
+pub use physics::{raycast, sweep_aabb, world_to_chunk_local, BlockHit, ChunkQuery, SweepResult};
+pub mod terrain;
+pub use terrain::{Heightmap, Terrain, TerrainConfig, TerrainTile};
+pub mod chunk_format;
+pub use chunk_format::{decode_chunk, encode_chunk, ChunkCompression, CHUNK_FORMAT_VERSION};
 pub mod region;
 pub use region::{
     apply_diff, diff_from_chunks, region_path, ChunkDiff, CpdEntry, RegionCache, RegionFile,
@@ -208,6 +214,7 @@ pub enum BlockData {
 /// so the chunk always carries the minimal set of block types actually present.
 /// `instance_data` is a sparse side-channel for per-voxel metadata that
 /// most blocks will never need (signs, containers, etc.).
+#[derive(Clone)]
 pub struct Chunk {
     /// Block types present in this chunk. Index 0 is always air.
     pub palette: Vec<BlockTypeId>,
@@ -277,6 +284,57 @@ impl ChunkPos {
     }
 }
 
+/// Signed world-space voxel coordinate: one unit per VOXEL_SIZE. i64 so
+/// every voxel of every i32 chunk index has one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl BlockPos {
+    /// The voxel containing world-space position `p` (metres).
+    pub fn from_world(p: DVec3) -> Self {
+        let v = VOXEL_SIZE as f64;
+        Self {
+            x: (p.x / v).floor() as i64,
+            y: (p.y / v).floor() as i64,
+            z: (p.z / v).floor() as i64,
+        }
+    }
+
+    /// World-space position of this voxel's min corner in metres.
+    pub fn to_world_origin(self) -> DVec3 {
+        let v = VOXEL_SIZE as f64;
+        DVec3::new(self.x as f64 * v, self.y as f64 * v, self.z as f64 * v)
+    }
+
+    /// The chunk holding this voxel, and the voxel's position within it.
+    pub fn to_chunk_local(self) -> (ChunkPos, ChunkLocalPos) {
+        let cs = CHUNK_SIZE as i64;
+        let chunk = ChunkPos {
+            x: self.x.div_euclid(cs) as i32,
+            y: self.y.div_euclid(cs) as i32,
+            z: self.z.div_euclid(cs) as i32,
+        };
+        let local = ChunkLocalPos::new(
+            self.x.rem_euclid(cs) as u8,
+            self.y.rem_euclid(cs) as u8,
+            self.z.rem_euclid(cs) as u8,
+        );
+        (chunk, local)
+    }
+
+    pub fn offset(self, dx: i64, dy: i64, dz: i64) -> Self {
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            z: self.z + dz,
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -36,7 +36,8 @@ const CRACK_EPS: f32 = VOXEL_SIZE * 0.001;
 // ---------------------------------------------------------------------------
 
 /// Pre-built per-block face texture index table, indexed by `BlockTypeId.0`.
-/// Entry `6*id+dir` gives the bindless texture array index for that face.
+/// Entry `6*id+dir` gives the bindless texture array index for that face,
+/// or its layer when built from a `BlockTextureArray`.
 /// Dir order matches the mesher: 0=-X 1=+X 2=-Y 3=+Y 4=-Z 5=+Z
 pub struct BlockFaceTextures {
     /// Flat array: [block0_neg_x, block0_pos_x, block0_neg_y, block0_pos_y, block0_neg_z, block0_pos_z, block1_neg_x, ...]
@@ -84,6 +85,90 @@ pub fn mesh_chunk(
     chunk: &Chunk,
     neighbors: [Option<&Chunk>; 6],
     face_textures: &BlockFaceTextures,
+) -> (Vec<Vertex>, Vec<u32>) {
+    mesh_chunk_impl(chunk, neighbors, face_textures, true)
+}
+
+/// Naive face-culling variant of `mesh_chunk`: same visibility rules and
+/// vertex layout, but one quad per visible voxel face, no merging. Several
+/// times the geometry, but every quad is exactly one voxel, so there are
+/// no T-junctions and per-voxel attributes stay per-voxel — the reference
+/// to diff greedy output against when chasing a meshing artifact.
+pub fn mesh_chunk_culled(
+    chunk: &Chunk,
+    neighbors: [Option<&Chunk>; 6],
+    face_textures: &BlockFaceTextures,
+) -> (Vec<Vertex>, Vec<u32>) {
+    mesh_chunk_impl(chunk, neighbors, face_textures, false)
+}
+
+/// Coarse mesh for a distant chunk: every `step`³ cell of voxels is meshed
+/// as one solid block (when at least half its voxels are opaque, taking the
+/// block type of the cell's topmost opaque voxel, the one seen from above)
+/// or as air. Greedy merging then works on cells instead of voxels, so the
+/// mesh shrinks roughly with `step`². Meshed without neighbours: boundary
+/// faces stay, which also covers the cracks where chunks of different steps
+/// meet.
+///
+/// `step` is rounded up to a power of two and capped at CHUNK_SIZE; a step
+/// of 1 is `mesh_chunk` with no neighbours.
+pub fn mesh_chunk_lod(
+    chunk: &Chunk,
+    step: usize,
+    face_textures: &BlockFaceTextures,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let step = step.max(1).next_power_of_two().min(CS);
+    if step == 1 {
+        return mesh_chunk_impl(chunk, [None; 6], face_textures, true);
+    }
+    mesh_chunk_impl(&downsample(chunk, step), [None; 6], face_textures, true)
+}
+
+/// `chunk` at a `step`-voxel resolution, still CHUNK_SIZE voxels across
+/// (see `mesh_chunk_lod`).
+fn downsample(chunk: &Chunk, step: usize) -> Chunk {
+    let mut coarse = Chunk::new();
+    for cy in (0..CS).step_by(step) {
+        for cz in (0..CS).step_by(step) {
+            for cx in (0..CS).step_by(step) {
+                let mut opaque = 0;
+                let mut top = AIR;
+                // Top layer first, so the first opaque voxel found is the
+                // cell's topmost.
+                for y in (cy..cy + step).rev() {
+                    for z in cz..cz + step {
+                        for x in cx..cx + step {
+                            let id = chunk.get(ChunkLocalPos::new(x as u8, y as u8, z as u8));
+                            if is_opaque(id) {
+                                opaque += 1;
+                                if top == AIR {
+                                    top = id;
+                                }
+                            }
+                        }
+                    }
+                }
+                if opaque * 2 < step * step * step {
+                    continue;
+                }
+                for y in cy..cy + step {
+                    for z in cz..cz + step {
+                        for x in cx..cx + step {
+                            coarse.set(ChunkLocalPos::new(x as u8, y as u8, z as u8), top);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    coarse
+}
+
+fn mesh_chunk_impl(
+    chunk: &Chunk,
+    neighbors: [Option<&Chunk>; 6],
+    face_textures: &BlockFaceTextures,
+    greedy: bool,
 ) -> (Vec<Vertex>, Vec<u32>) {
     let mut verts: Vec<Vertex> = Vec::new();
     let mut idxs: Vec<u32> = Vec::new();
@@ -145,9 +230,9 @@ pub fn mesh_chunk(
                     }
                     let block = mask[i0].unwrap();
 
-                    // Extend in the u direction.
+                    // Extend in the u direction (culled mode keeps 1×1).
                     let mut w = 1;
-                    while u0 + w < CS {
+                    while greedy && u0 + w < CS {
                         let i = (u0 + w) * CS + v0;
                         if consumed[i] || mask[i] != Some(block) {
                             break;
@@ -157,7 +242,7 @@ pub fn mesh_chunk(
 
                     // Extend in the v direction, keeping width w fixed.
                     let mut h = 1;
-                    'grow_v: while v0 + h < CS {
+                    'grow_v: while greedy && v0 + h < CS {
                         for du in 0..w {
                             let i = (u0 + du) * CS + (v0 + h);
                             if consumed[i] || mask[i] != Some(block) {
@@ -441,4 +526,103 @@ mod tests {
         assert_eq!(plus_y_verts.len(), 4, "+Y face should be one merged quad");
         let _ = idxs; // silence unused warning
     }
+
+    /// Total face area covered by a mesh's quads, in voxel faces (each quad
+    /// is two triangles sharing vertices 0 and 2, see mesh_chunk_impl).
+    fn quad_area(verts: &[Vertex]) -> f32 {
+        verts
+            .chunks_exact(4)
+            .map(|q| {
+                let d = |a: [f32; 3], b: [f32; 3]| {
+                    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+                };
+                // Undo CRACK_EPS inflation on both edges before measuring.
+                let w = d(q[0].pos, q[1].pos) - 2.0 * CRACK_EPS;
+                let h = d(q[1].pos, q[2].pos) - 2.0 * CRACK_EPS;
+                w * h / (VOXEL_SIZE * VOXEL_SIZE)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn culled_emits_one_quad_per_face() {
+        let mut reg = BlockRegistry::new();
+        let c = solid_chunk(&mut reg);
+        let (v, i) = mesh_chunk_culled(&c, [None; 6], &BlockFaceTextures::new());
+        assert_eq!(v.len(), 6 * CHUNK_SIZE * CHUNK_SIZE * 4);
+        assert_eq!(i.len(), 6 * CHUNK_SIZE * CHUNK_SIZE * 6);
+    }
+
+    #[test]
+    fn greedy_and_culled_cover_same_area() {
+        let mut reg = BlockRegistry::new();
+        let stone = reg.register("stone");
+        let dirt = reg.register("dirt");
+        let mut chunk = Chunk::new();
+        // An L of mixed block types so merging actually kicks in but can't
+        // swallow everything into one quad.
+        for x in 0..6u8 {
+            chunk.set(ChunkLocalPos::new(x, 0, 0), stone);
+        }
+        for z in 1..4u8 {
+            chunk.set(ChunkLocalPos::new(0, 0, z), dirt);
+        }
+        let tex = BlockFaceTextures::new();
+        let (greedy, _) = mesh_chunk(&chunk, [None; 6], &tex);
+        let (culled, _) = mesh_chunk_culled(&chunk, [None; 6], &tex);
+        assert!(greedy.len() < culled.len());
+        assert!((quad_area(&greedy) - quad_area(&culled)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn lod_drops_sparse_cells_and_fills_dense_ones() {
+        let mut reg = BlockRegistry::new();
+        let stone = reg.register("stone");
+        let mut chunk = Chunk::new();
+        // One lone voxel in the first 2³ cell, half of the next one filled.
+        chunk.set(ChunkLocalPos::new(0, 0, 0), stone);
+        for x in 4..6u8 {
+            for z in 0..2u8 {
+                chunk.set(ChunkLocalPos::new(x, 0, z), stone);
+            }
+        }
+        let (v, i) = mesh_chunk_lod(&chunk, 2, &BlockFaceTextures::new());
+        assert_eq!(v.len(), 24, "one 2x2x2 block: 6 faces × 4 verts");
+        assert_eq!(i.len(), 36);
+        let coarse = downsample(&chunk, 2);
+        assert_eq!(coarse.get(ChunkLocalPos::new(0, 0, 0)), AIR);
+        assert_eq!(coarse.get(ChunkLocalPos::new(5, 1, 1)), stone);
+    }
+
+    #[test]
+    fn lod_cell_takes_topmost_block() {
+        let mut reg = BlockRegistry::new();
+        let dirt = reg.register("dirt");
+        let grass = reg.register("grass");
+        let mut chunk = Chunk::new();
+        for x in 0..4u8 {
+            for z in 0..4u8 {
+                for y in 0..3u8 {
+                    chunk.set(ChunkLocalPos::new(x, y, z), dirt);
+                }
+                chunk.set(ChunkLocalPos::new(x, 3, z), grass);
+            }
+        }
+        let coarse = downsample(&chunk, 4);
+        assert_eq!(coarse.get(ChunkLocalPos::new(0, 0, 0)), grass);
+        assert_eq!(coarse.get(ChunkLocalPos::new(3, 3, 3)), grass);
+    }
+
+    #[test]
+    fn lod_step_one_matches_full_detail() {
+        let mut reg = BlockRegistry::new();
+        let c = solid_chunk(&mut reg);
+        let tex = BlockFaceTextures::new();
+        let (full, _) = mesh_chunk(&c, [None; 6], &tex);
+        let (lod, _) = mesh_chunk_lod(&c, 1, &tex);
+        assert_eq!(full.len(), lod.len());
+        // A solid chunk is a cube at any step.
+        let (coarse, _) = mesh_chunk_lod(&c, 8, &tex);
+        assert_eq!(coarse.len(), full.len());
+    }
 }
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
 
-use crate::{BlockTypeId, Chunk, ChunkLocalPos, ChunkPos, CHUNK_SIZE, VOXEL_SIZE};
+use crate::{BlockPos, BlockTypeId, Chunk, ChunkLocalPos, ChunkPos, VOXEL_SIZE};
 use cubic_math::{DVec3, Vec3};
 use std::collections::HashMap;
 
@@ -17,23 +17,15 @@ pub trait ChunkQuery {
 }
 
 /// Which chunk a world-space position falls in, and its position within
-/// that chunk in voxel-local coordinates — shared by `get_block_at` (read)
-/// and `AsyncWorldStream::set_block_at` (write, see stream_pool.rs) so both
-/// use the exact same world->chunk mapping. f64 input so the chunk index
-/// stays exact at any distance from the origin — the local voxel index
-/// (0..CHUNK_SIZE) only ever needs a few bits of precision, but the chunk
-/// index itself must not drift.
+/// that chunk in voxel-local coordinates — the containing BlockPos's
+/// `to_chunk_local`, so `get_block_at` (read) and
+/// `AsyncWorldStream::set_block` (write, see stream_pool.rs) use the exact
+/// same world->chunk mapping. f64 input so the chunk index stays exact at
+/// any distance from the origin — the local voxel index (0..CHUNK_SIZE)
+/// only ever needs a few bits of precision, but the chunk index itself
+/// must not drift.
 pub fn world_to_chunk_local(wx: f64, wy: f64, wz: f64) -> (ChunkPos, ChunkLocalPos) {
-    let s = CHUNK_SIZE as f64 * VOXEL_SIZE as f64;
-    let cp = ChunkPos {
-        x: (wx / s).floor() as i32,
-        y: (wy / s).floor() as i32,
-        z: (wz / s).floor() as i32,
-    };
-    let lx = ((wx / VOXEL_SIZE as f64).floor() as i64).rem_euclid(CHUNK_SIZE as i64) as u8;
-    let ly = ((wy / VOXEL_SIZE as f64).floor() as i64).rem_euclid(CHUNK_SIZE as i64) as u8;
-    let lz = ((wz / VOXEL_SIZE as f64).floor() as i64).rem_euclid(CHUNK_SIZE as i64) as u8;
-    (cp, ChunkLocalPos::new(lx, ly, lz))
+    BlockPos::from_world(DVec3::new(wx, wy, wz)).to_chunk_local()
 }
 
 impl ChunkQuery for HashMap<ChunkPos, Chunk> {
@@ -166,6 +158,102 @@ pub fn sweep_aabb(
     }
 }
 
+// ---------------------------------------------------------------------------
+// Voxel raycast
+// ---------------------------------------------------------------------------
+
+/// The first solid voxel along a ray (see `raycast`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockHit {
+    pub pos: BlockPos,
+    pub block: BlockTypeId,
+    /// Outward normal of the face the ray entered `pos` through; zero when
+    /// the ray started inside it.
+    pub normal: [i32; 3],
+    /// Metres along the ray to that face.
+    pub distance: f64,
+}
+
+impl BlockHit {
+    /// The voxel in front of the hit face: where placing against it puts
+    /// a block. `pos` itself when the ray started inside it.
+    pub fn adjacent(&self) -> BlockPos {
+        let [x, y, z] = self.normal;
+        self.pos.offset(x as i64, y as i64, z as i64)
+    }
+}
+
+/// Walk the voxels a ray from `origin` along `dir` (any length, not zero)
+/// passes through, in order, and return the first solid one within
+/// `max_dist` metres. Voxel-exact (Amanatides & Woo's grid traversal), so
+/// unlike a fixed-step march it can't skip a corner it only clips. Unloaded
+/// chunks read as air, as everywhere else in ChunkQuery.
+pub fn raycast(
+    world: &dyn ChunkQuery,
+    origin: DVec3,
+    dir: DVec3,
+    max_dist: f64,
+) -> Option<BlockHit> {
+    let len = dir.length();
+    // Also rejects NaN.
+    if !(len > 0.0 && max_dist >= 0.0) {
+        return None;
+    }
+    let dir = dir / len;
+    let voxel = VOXEL_SIZE as f64;
+    let mut pos = BlockPos::from_world(origin);
+
+    // Per axis: which way the ray steps, the ray distance between two
+    // voxel boundaries, and the distance to the first one.
+    let axis = |o: f64, d: f64, cell: i64| -> (i64, f64, f64) {
+        if d > 0.0 {
+            (1, voxel / d, ((cell + 1) as f64 * voxel - o) / d)
+        } else if d < 0.0 {
+            (-1, -voxel / d, (cell as f64 * voxel - o) / d)
+        } else {
+            (0, f64::INFINITY, f64::INFINITY)
+        }
+    };
+    let (step_x, delta_x, mut next_x) = axis(origin.x, dir.x, pos.x);
+    let (step_y, delta_y, mut next_y) = axis(origin.y, dir.y, pos.y);
+    let (step_z, delta_z, mut next_z) = axis(origin.z, dir.z, pos.z);
+
+    let mut normal = [0; 3];
+    let mut distance = 0.0;
+    loop {
+        let center = pos.to_world_origin() + DVec3::splat(voxel * 0.5);
+        let block = world.get_block_at(center.x, center.y, center.z);
+        if block != BlockTypeId(0) {
+            return Some(BlockHit {
+                pos,
+                block,
+                normal,
+                distance,
+            });
+        }
+        // Cross whichever boundary the ray reaches first.
+        if next_x <= next_y && next_x <= next_z {
+            distance = next_x;
+            pos.x += step_x;
+            next_x += delta_x;
+            normal = [-step_x as i32, 0, 0];
+        } else if next_y <= next_z {
+            distance = next_y;
+            pos.y += step_y;
+            next_y += delta_y;
+            normal = [0, -step_y as i32, 0];
+        } else {
+            distance = next_z;
+            pos.z += step_z;
+            next_z += delta_z;
+            normal = [0, 0, -step_z as i32];
+        }
+        if distance > max_dist {
+            return None;
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -243,4 +331,54 @@ mod tests {
         assert!((result.pos.x - 1.0).abs() < 1e-4);
         assert!((result.pos.z - 2.0).abs() < 1e-4);
     }
+
+    #[test]
+    fn raycast_hits_ground_face() {
+        let ground = FlatGround { ground_y: 10.0 };
+        let origin = DVec3::new(0.25, 12.0, 0.25);
+        let hit =
+            raycast(&ground, origin, DVec3::new(0.0, -3.0, 0.0), 5.0).expect("ground is 2m below");
+        // The top voxel below y = 10 spans [9.5, 10).
+        assert_eq!(hit.pos, BlockPos { x: 0, y: 19, z: 0 });
+        assert_eq!(hit.normal, [0, 1, 0]);
+        assert!((hit.distance - 2.0).abs() < 1e-9);
+        assert_eq!(hit.adjacent(), BlockPos { x: 0, y: 20, z: 0 });
+        assert_eq!(hit.block, BlockTypeId(1));
+    }
+
+    #[test]
+    fn raycast_stops_at_max_dist() {
+        let ground = FlatGround { ground_y: 10.0 };
+        let origin = DVec3::new(0.25, 12.0, 0.25);
+        assert!(raycast(&ground, origin, DVec3::NEG_Y, 1.5).is_none());
+        assert!(raycast(&ground, origin, DVec3::Y, 100.0).is_none());
+        assert!(raycast(&ground, origin, DVec3::ZERO, 100.0).is_none());
+    }
+
+    #[test]
+    fn raycast_diagonal_places_against_air() {
+        let ground = FlatGround { ground_y: 10.0 };
+        let origin = DVec3::new(0.1, 10.3, -0.2);
+        let dir = DVec3::new(1.0, -0.7, 0.4);
+        let hit = raycast(&ground, origin, dir, 10.0).expect("heading into the ground");
+        let solid = |p: BlockPos| {
+            let c = p.to_world_origin() + DVec3::splat(VOXEL_SIZE as f64 * 0.5);
+            ground.is_solid(c.x, c.y, c.z)
+        };
+        assert!(solid(hit.pos));
+        assert!(!solid(hit.adjacent()));
+        assert_eq!(hit.normal.iter().map(|n| n.abs()).sum::<i32>(), 1);
+        // The hit point lies on the entered face.
+        let p = origin + dir.normalize() * hit.distance;
+        assert!((p.y - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn raycast_from_inside_solid_hits_at_origin() {
+        let ground = FlatGround { ground_y: 10.0 };
+        let hit = raycast(&ground, DVec3::new(1.0, 4.0, 1.0), DVec3::X, 3.0).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.normal, [0, 0, 0]);
+        assert_eq!(hit.adjacent(), hit.pos);
+    }
 }
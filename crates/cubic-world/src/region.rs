@@ -24,10 +24,19 @@
 //! A tombstone has `data_len == 0` with a non-sentinel `chunk_y`;
 //! compaction removes them.
 //!
+//! # Chunk payloads
+//! Each chunk's data is a `ChunkDiff` against the generator's output:
+//! `[tag: u8]` then a sparse list of changed voxels (tag 0) or the whole
+//! chunk in chunk_format's versioned encoding (tag 2), then a CPD count.
+//! Tag 1, an LZ4 dump of the raw palette and index array, is what files
+//! written before the chunk format hold; it's still read, and converted to
+//! the chunk format on the way in.
+//!
 //! # Compaction
 //! Writes a fresh file to `<path>.tmp`, then atomically renames it over the
 //! original. Tombstoned entries and orphaned data blobs are dropped.
 
+use crate::chunk_format::{decode_chunk, encode_chunk, ChunkCompression};
 use crate::{BlockTypeId, Chunk, ChunkLocalPos, CHUNK_VOLUME};
 use anyhow::{bail, Context, Result};
 use std::collections::HashMap;
@@ -55,7 +64,10 @@ const Y_ENTRY_SIZE: usize = 10;
 const Y_SENTINEL: i16 = i16::MIN;
 
 const DIFF_TAG_SPARSE: u8 = 0;
-const DIFF_TAG_FULL: u8 = 1;
+/// Legacy full chunk: raw palette and indices, LZ4. Read only.
+const DIFF_TAG_FULL_LZ4: u8 = 1;
+/// Full chunk in chunk_format's encoding.
+const DIFF_TAG_CHUNK: u8 = 2;
 
 /// Soft warning threshold: log if a single chunk's saved data exceeds this.
 const CHUNK_SIZE_WARN_BYTES: usize = 1024 * 1024; // 1 MiB
@@ -87,7 +99,7 @@ pub enum ChunkDiff {
         entries: Vec<SparseDiffEntry>,
         cpd: Vec<CpdEntry>,
     },
-    /// At or above `threshold` — full chunk LZ4-compressed.
+    /// At or above `threshold` — the full chunk, as `encode_chunk` bytes.
     Full {
         compressed: Vec<u8>,
         cpd: Vec<CpdEntry>,
@@ -118,8 +130,14 @@ pub fn decode_local_pos(pos: u16) -> (u8, u8, u8) {
 // ---------------------------------------------------------------------------
 
 /// Compute the delta between a generator baseline and the current chunk state.
-/// Returns `None` if the chunk is still virgin (no changes).
-pub fn diff_from_chunks(original: &Chunk, modified: &Chunk, threshold: usize) -> Option<ChunkDiff> {
+/// Returns `None` if the chunk is still virgin (no changes). A full diff's
+/// chunk is compressed with `compression`.
+pub fn diff_from_chunks(
+    original: &Chunk,
+    modified: &Chunk,
+    threshold: usize,
+    compression: ChunkCompression,
+) -> Option<ChunkDiff> {
     let mut entries: Vec<SparseDiffEntry> = Vec::new();
 
     for y in 0u8..32 {
@@ -144,7 +162,7 @@ pub fn diff_from_chunks(original: &Chunk, modified: &Chunk, threshold: usize) ->
 
     if entries.len() >= threshold {
         Some(ChunkDiff::Full {
-            compressed: compress_chunk(modified),
+            compressed: encode_chunk(modified, compression),
             cpd: vec![],
         })
     } else {
@@ -164,34 +182,22 @@ pub fn apply_diff(chunk: &mut Chunk, diff: &ChunkDiff) {
                 chunk.set(ChunkLocalPos::new(x, y, z), BlockTypeId(e.block_id));
             }
         }
-        ChunkDiff::Full { compressed, .. } => match decompress_chunk(compressed) {
+        ChunkDiff::Full { compressed, .. } => match decode_chunk(compressed) {
             Ok(c) => *chunk = c,
             Err(e) => {
-                tracing::error!("failed to decompress full chunk diff: {e:#} — chunk unchanged")
+                tracing::error!("failed to decode full chunk diff: {e:#} — chunk unchanged")
             }
         },
     }
 }
 
 // ---------------------------------------------------------------------------
-// LZ4 full-chunk serialization
+// Legacy LZ4 full-chunk payloads
 // ---------------------------------------------------------------------------
 
-/// `[palette_len: u32][palette: palette_len × u32][data: CHUNK_VOLUME × u16]` then LZ4.
-fn compress_chunk(chunk: &Chunk) -> Vec<u8> {
-    let palette_len = chunk.palette.len() as u32;
-    let mut raw = Vec::with_capacity(4 + chunk.palette.len() * 4 + CHUNK_VOLUME * 2);
-    raw.extend_from_slice(&palette_len.to_le_bytes());
-    for id in &chunk.palette {
-        raw.extend_from_slice(&id.0.to_le_bytes());
-    }
-    for &idx in chunk.data.iter() {
-        raw.extend_from_slice(&idx.to_le_bytes());
-    }
-    lz4_flex::compress_prepend_size(&raw)
-}
-
-fn decompress_chunk(compressed: &[u8]) -> Result<Chunk> {
+/// `[palette_len: u32][palette: palette_len × u32][data: CHUNK_VOLUME × u16]`
+/// then LZ4, as DIFF_TAG_FULL_LZ4 payloads were written.
+fn decompress_legacy_chunk(compressed: &[u8]) -> Result<Chunk> {
     let raw = lz4_flex::decompress_size_prepended(compressed).context("lz4 decompress failed")?;
 
     let min_len = 4;
@@ -244,7 +250,7 @@ fn serialize_diff(diff: &ChunkDiff) -> Vec<u8> {
             }
         }
         ChunkDiff::Full { compressed, .. } => {
-            out.push(DIFF_TAG_FULL);
+            out.push(DIFF_TAG_CHUNK);
             out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
             out.extend_from_slice(compressed);
         }
@@ -289,7 +295,7 @@ fn deserialize_diff(data: &[u8]) -> Result<ChunkDiff> {
                 cpd: vec![],
             })
         }
-        DIFF_TAG_FULL => {
+        DIFF_TAG_FULL_LZ4 | DIFF_TAG_CHUNK => {
             let len = read_u32(data, cur)? as usize;
             cur += 4;
             if cur + len > data.len() {
@@ -298,8 +304,16 @@ fn deserialize_diff(data: &[u8]) -> Result<ChunkDiff> {
                     data.len() - cur
                 );
             }
+            let payload = &data[cur..cur + len];
+            let compressed = if tag == DIFF_TAG_FULL_LZ4 {
+                // Re-encoded here so everything past this point sees one format;
+                // the next save of the chunk writes it back as DIFF_TAG_CHUNK.
+                encode_chunk(&decompress_legacy_chunk(payload)?, ChunkCompression::Lz4)
+            } else {
+                payload.to_vec()
+            };
             Ok(ChunkDiff::Full {
-                compressed: data[cur..cur + len].to_vec(),
+                compressed,
                 cpd: vec![],
             })
         }
@@ -822,4 +836,54 @@ mod tests {
             "tombstoned chunk should report false"
         );
     }
+
+    fn edited_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0u8..32 {
+            for z in 0u8..32 {
+                chunk.set(ChunkLocalPos::new(x, 0, z), BlockTypeId(7));
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn full_diff_roundtrips_through_the_chunk_format() {
+        let dir = TempDir::new("full");
+        let mut rf = RegionFile::open(&dir.region_path()).unwrap();
+        let chunk = edited_chunk();
+        let diff = diff_from_chunks(&Chunk::new(), &chunk, 1, ChunkCompression::Lz4).unwrap();
+        assert!(matches!(diff, ChunkDiff::Full { .. }));
+        rf.write_chunk(0, 0, 0, &diff).unwrap();
+
+        let mut loaded = Chunk::new();
+        apply_diff(&mut loaded, &rf.read_chunk(0, 0, 0).unwrap().unwrap());
+        assert_eq!(loaded.get(ChunkLocalPos::new(5, 0, 9)), BlockTypeId(7));
+        assert_eq!(loaded.get(ChunkLocalPos::new(5, 1, 9)), BlockTypeId(0));
+    }
+
+    #[test]
+    fn legacy_lz4_full_diffs_still_load() {
+        let chunk = edited_chunk();
+        let mut raw = Vec::new();
+        raw.extend_from_slice(&(chunk.palette.len() as u32).to_le_bytes());
+        for id in &chunk.palette {
+            raw.extend_from_slice(&id.0.to_le_bytes());
+        }
+        for &idx in chunk.data.iter() {
+            raw.extend_from_slice(&idx.to_le_bytes());
+        }
+        let legacy = lz4_flex::compress_prepend_size(&raw);
+        let mut payload = vec![DIFF_TAG_FULL_LZ4];
+        payload.extend_from_slice(&(legacy.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&legacy);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+
+        let diff = deserialize_diff(&payload).unwrap();
+        let mut loaded = Chunk::new();
+        apply_diff(&mut loaded, &diff);
+        assert_eq!(loaded.get(ChunkLocalPos::new(31, 0, 31)), BlockTypeId(7));
+        // Written back in the current format.
+        assert_eq!(serialize_diff(&diff)[0], DIFF_TAG_CHUNK);
+    }
 }
@@ -1,17 +1,19 @@
 // SPDX-License-Identifier: CEPL-1.0
 #![deny(unsafe_op_in_unsafe_fn)]
 
-use crate::physics::{world_to_chunk_local, ChunkQuery};
+use crate::physics::{raycast, BlockHit, ChunkQuery};
 use crate::region::{apply_diff, diff_from_chunks, RegionCache};
 use crate::{
-    mesh_chunk, BlockFaceTextures, BlockTypeId, Chunk, ChunkPos, StreamDelta, WorldGenerator,
-    WorldStream, CHUNK_SIZE,
+    mesh_chunk, mesh_chunk_lod, BlockFaceTextures, BlockPos, BlockTypeId, Chunk, ChunkCompression,
+    ChunkPos, StreamDelta, WorldGenerator, WorldStream, CHUNK_SIZE,
 };
-use cubic_render::Vertex;
+use cubic_jobs::{JobPool, Priority};
+use cubic_math::DVec3;
+use cubic_render::{StreamCounts, Vertex};
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 
 // ---------------------------------------------------------------------------
 // Internal channel types
@@ -23,6 +25,7 @@ struct WorkItem {
     generator: Arc<dyn WorldGenerator>,
     face_textures: Arc<BlockFaceTextures>,
     region_cache: Option<Arc<Mutex<RegionCache>>>,
+    lod_step: usize,
 }
 
 struct WorkResult {
@@ -30,6 +33,155 @@ struct WorkResult {
     chunk: Option<Chunk>, // None = air, Some = has geometry
     vertices: Vec<Vertex>,
     indices: Vec<u32>,
+    lod_step: usize,
+}
+
+/// Coarsest step `lod_step` hands out: a 32-voxel chunk meshed as 4³ cells.
+const MAX_LOD_STEP: usize = 8;
+
+/// Mesh step (see `mesh_chunk_lod`) for the chunk at `pos` with the camera
+/// in `center`: full detail out to `lod_distance` chunks across (x/z), then
+/// each band twice as wide as the last at twice the step. 0 turns LOD off.
+fn lod_step(center: ChunkPos, pos: ChunkPos, lod_distance: i32) -> usize {
+    if lod_distance <= 0 {
+        return 1;
+    }
+    let distance = (pos.x - center.x).abs().max((pos.z - center.z).abs());
+    let mut step = 1;
+    let mut limit = lod_distance;
+    while distance > limit && step < MAX_LOD_STEP {
+        step *= 2;
+        limit = limit.saturating_mul(2);
+    }
+    step
+}
+
+fn is_all_air(chunk: &Chunk) -> bool {
+    chunk
+        .data
+        .iter()
+        .all(|&i| chunk.palette[i as usize] == BlockTypeId(0))
+}
+
+/// One chunk-generation job: generate, reapply any saved diff, mesh
+/// without neighbours (the boundary remesh stitches those in later), at
+/// the job's LOD step.
+fn generate_chunk(work: WorkItem) -> WorkResult {
+    let mut chunk = work.generator.generate(work.pos, work.seed);
+
+    if let Some(cache) = &work.region_cache {
+        if let Ok(mut cache) = cache.lock() {
+            match cache.read_chunk(work.pos.x, work.pos.y, work.pos.z) {
+                Ok(Some(diff)) => apply_diff(&mut chunk, &diff),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("failed to read diff for {:?}: {e:#}", work.pos),
+            }
+        }
+    }
+
+    let (vertices, indices) = mesh_chunk_lod(&chunk, work.lod_step, &work.face_textures);
+    // A coarse mesh can come out empty for a chunk of scattered voxels,
+    // which still needs its data for when the camera comes closer.
+    let empty = vertices.is_empty() && (work.lod_step == 1 || is_all_air(&chunk));
+    if empty {
+        // No geometry — pure air or fully buried solid. Neighbors don't
+        // need to know since this chunk contributes no faces. A future
+        // "dirty chunk" system will handle the fully-buried case when
+        // block removal is added.
+        WorkResult {
+            pos: work.pos,
+            chunk: None,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            lod_step: work.lod_step,
+        }
+    } else {
+        WorkResult {
+            pos: work.pos,
+            chunk: Some(chunk),
+            vertices,
+            indices,
+            lod_step: work.lod_step,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Background saving
+// ---------------------------------------------------------------------------
+
+/// Chunk snapshots waiting to be written, and the positions a save job is
+/// running for. A position has at most one job, which picks up whatever
+/// snapshot was queued behind it before finishing, so a position's saves
+/// land in the order they were made.
+#[derive(Default)]
+struct SaveState {
+    pending: HashMap<ChunkPos, Chunk>,
+    active: HashSet<ChunkPos>,
+}
+
+#[derive(Default)]
+struct SaveQueue {
+    state: Mutex<SaveState>,
+    // Signalled whenever a position's job finishes.
+    idle: Condvar,
+}
+
+impl SaveQueue {
+    fn lock(&self) -> MutexGuard<'_, SaveState> {
+        // Nothing panics while holding the lock; a poisoned one is still
+        // consistent.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Block until nothing is being saved for `pos`.
+    fn wait_for(&self, pos: ChunkPos) {
+        let mut state = self.lock();
+        while state.active.contains(&pos) {
+            state = self.idle.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Block until every queued save is on disk.
+    fn wait_all(&self) {
+        let mut state = self.lock();
+        while !state.active.is_empty() {
+            state = self.idle.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Everything a save job needs besides the chunk.
+#[derive(Clone)]
+struct SaveTarget {
+    cache: Arc<Mutex<RegionCache>>,
+    generator: Arc<dyn WorldGenerator>,
+    seed: u64,
+    diff_threshold: usize,
+    compression: ChunkCompression,
+}
+
+/// Diff `chunk` against the generator's output for `pos` and write it, or
+/// drop the saved diff if the chunk is back to what the generator makes.
+fn save_chunk(target: &SaveTarget, pos: ChunkPos, chunk: &Chunk) {
+    let baseline = target.generator.generate(pos, target.seed);
+    let diff = diff_from_chunks(&baseline, chunk, target.diff_threshold, target.compression);
+    let Ok(mut cache) = target.cache.lock() else {
+        return;
+    };
+    match diff {
+        Some(diff) => {
+            if let Err(e) = cache.write_chunk(pos.x, pos.y, pos.z, &diff) {
+                tracing::error!("failed to save chunk {pos:?}: {e:#}");
+            }
+        }
+        None => {
+            // Chunk reverted to virgin -- remove any previously saved diff
+            if let Err(e) = cache.remove_chunk(pos.x, pos.y, pos.z) {
+                tracing::warn!("failed to tombstone chunk {pos:?}: {e:#}");
+            }
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -46,15 +198,26 @@ impl ChunkQuery for ChunkQueryView<'_> {
     }
 }
 
-/// Wraps `WorldStream` with a background worker pool so chunk generation
-/// never blocks the main thread. Call `update` each frame exactly like
+/// Wraps `WorldStream` with jobs on a `JobPool` so chunk generation never
+/// blocks the main thread. Call `update` each frame exactly like
 /// `WorldStream::update`; completed chunks trickle in via the result channel
 /// and appear in `StreamDelta::loaded` once ready.
+///
+/// With `set_lod_distance`, chunks farther out are meshed coarser (see
+/// `mesh_chunk_lod`) and remeshed through `remesh_queue` as the camera's
+/// movement changes their step. `ready_meshes` is kept nearest-last, so
+/// popping from the end uploads the chunks around the camera first.
+///
+/// Edited chunks are saved on the pool too, at low priority: when they
+/// unload and when `flush_dirty` snapshots them. A chunk with a save still
+/// running isn't reloaded until it finishes, so it always comes back with
+/// its edits.
 pub struct AsyncWorldStream {
     inner: WorldStream,
     in_flight: HashSet<ChunkPos>,
     discard: HashSet<ChunkPos>,
-    work_tx: Sender<WorkItem>,
+    jobs: Arc<JobPool>,
+    result_tx: Sender<WorkResult>,
     result_rx: Receiver<WorkResult>,
     pub ready_meshes: Vec<(ChunkPos, Vec<Vertex>, Vec<u32>)>,
     pub remesh_queue: Vec<ChunkPos>,
@@ -66,110 +229,63 @@ pub struct AsyncWorldStream {
     // or fully buried solid. Not inserted into inner.chunks. Solid entries will
     // need revisiting when block removal exists (dirty chunk system).
     known_empty: HashSet<ChunkPos>,
-    _workers: Vec<JoinHandle<()>>,
     dirty: HashSet<ChunkPos>,
     region_cache: Option<Arc<Mutex<RegionCache>>>,
     generator: Option<Arc<dyn WorldGenerator>>,
     seed: u64,
     diff_threshold: usize,
+    save_compression: ChunkCompression,
+    saves: Arc<SaveQueue>,
+    // Chunk distance past which meshes get coarser; 0 = full detail
+    // everywhere.
+    lod_distance: i32,
+    // The LOD step each loaded chunk is (or is queued to be) meshed at.
+    mesh_steps: HashMap<ChunkPos, usize>,
 }
 
 impl AsyncWorldStream {
-    pub fn new(
-        radius_xz: i32,
-        radius_y: i32,
-        on_worker_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
-    ) -> Self {
-        let worker_count = thread::available_parallelism()
-            .map_or(4, |n| n.get())
-            .saturating_sub(1)
-            .max(1);
-
-        let (work_tx, work_rx) = mpsc::channel::<WorkItem>();
+    /// Chunks are generated and meshed on `jobs`, whose workers must have
+    /// had any per-thread generator setup done already (see
+    /// JobPool::new's `on_worker_start`).
+    pub fn new(radius_xz: i32, radius_y: i32, jobs: Arc<JobPool>) -> Self {
         let (result_tx, result_rx) = mpsc::channel::<WorkResult>();
 
-        // Wrap the receiver so multiple workers can pull from it
-        let work_rx = Arc::new(Mutex::new(work_rx));
-
-        let workers = (0..worker_count)
-            .enumerate()
-            .map(|(i, _)| {
-                let work_rx = Arc::clone(&work_rx);
-                let result_tx = result_tx.clone();
-                let cb = on_worker_start.clone(); // clone the Option<Arc<...>>
-                thread::spawn(move || {
-                    if let Some(ref f) = cb {
-                        f(i);
-                    }
-                    loop {
-                        let item = {
-                            let rx = work_rx.lock().unwrap();
-                            rx.recv()
-                        };
-                        match item {
-                            Ok(work) => {
-                                let mut chunk = work.generator.generate(work.pos, work.seed);
-
-                                if let Some(cache) = &work.region_cache {
-                                    if let Ok(mut cache) = cache.lock() {
-                                        match cache.read_chunk(work.pos.x, work.pos.y, work.pos.z) {
-                                            Ok(Some(diff)) => apply_diff(&mut chunk, &diff),
-                                            Ok(None) => {}
-                                            Err(e) => tracing::warn!(
-                                                "failed to read diff for {:?}: {e:#}",
-                                                work.pos
-                                            ),
-                                        }
-                                    }
-                                }
-
-                                let (vertices, indices) =
-                                    mesh_chunk(&chunk, [None; 6], &work.face_textures);
-                                if vertices.is_empty() {
-                                    // No geometry — pure air or fully buried solid.
-                                    // Neighbors don't need to know since this chunk
-                                    // contributes no faces. A future "dirty chunk"
-                                    // system will handle the fully-buried case when
-                                    // block removal is added.
-                                    let _ = result_tx.send(WorkResult {
-                                        pos: work.pos,
-                                        chunk: None,
-                                        vertices: Vec::new(),
-                                        indices: Vec::new(),
-                                    });
-                                } else {
-                                    let _ = result_tx.send(WorkResult {
-                                        pos: work.pos,
-                                        chunk: Some(chunk),
-                                        vertices,
-                                        indices,
-                                    });
-                                }
-                            }
-                            // Channel closed — main thread dropped the sender, time to exit
-                            Err(_) => break,
-                        }
-                    }
-                })
-            })
-            .collect();
-
         Self {
             inner: WorldStream::new(radius_xz, radius_y),
             in_flight: HashSet::new(),
             discard: HashSet::new(),
-            work_tx,
+            jobs,
+            result_tx,
             result_rx,
             ready_meshes: Vec::new(),
             remesh_queue: Vec::new(),
             remeshed_with: HashMap::new(),
             known_empty: HashSet::new(),
-            _workers: workers,
             dirty: HashSet::new(),
             region_cache: None,
             generator: None,
             seed: 0,
             diff_threshold: 512,
+            save_compression: ChunkCompression::default(),
+            saves: Arc::new(SaveQueue::default()),
+            lod_distance: 0,
+            mesh_steps: HashMap::new(),
+        }
+    }
+
+    /// Mesh chunks more than `chunks` away (x/z) coarser, the step doubling
+    /// with each band twice as wide as the last; 0 (the default) keeps
+    /// full detail everywhere. Loaded chunks pick it up on the next update.
+    pub fn set_lod_distance(&mut self, chunks: i32) {
+        self.lod_distance = chunks.max(0);
+    }
+
+    /// Loaded chunks and the work queued behind them, for frame_stats.
+    pub fn stream_counts(&self) -> StreamCounts {
+        StreamCounts {
+            resident: self.inner.chunks.len() as u32,
+            meshing: (self.in_flight.len() + self.remesh_queue.len()) as u32,
+            uploads_pending: self.ready_meshes.len() as u32,
         }
     }
 
@@ -186,6 +302,62 @@ impl AsyncWorldStream {
         self.diff_threshold = diff_threshold;
     }
 
+    /// How chunks saved whole (see `diff_from_chunks`) are compressed from
+    /// now on. LZ4 by default.
+    pub fn set_save_compression(&mut self, compression: ChunkCompression) {
+        self.save_compression = compression;
+    }
+
+    /// Loaded chunks edited since they were last queued for saving.
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Block until every queued save has been written. Call after
+    /// `flush_dirty` before quitting or suspending.
+    pub fn wait_for_saves(&self) {
+        self.saves.wait_all();
+    }
+
+    fn save_target(&self) -> Option<SaveTarget> {
+        Some(SaveTarget {
+            cache: Arc::clone(self.region_cache.as_ref()?),
+            generator: Arc::clone(self.generator.as_ref()?),
+            seed: self.seed,
+            diff_threshold: self.diff_threshold,
+            compression: self.save_compression,
+        })
+    }
+
+    /// Save `chunk` for `pos` on the job pool. Behind a save already
+    /// running for `pos`, it replaces any snapshot still waiting there.
+    fn queue_save(&self, pos: ChunkPos, chunk: Chunk) {
+        let Some(target) = self.save_target() else {
+            return;
+        };
+        let mut state = self.saves.lock();
+        state.pending.insert(pos, chunk);
+        if !state.active.insert(pos) {
+            return;
+        }
+        drop(state);
+        let saves = Arc::clone(&self.saves);
+        self.jobs.spawn(Priority::Low, "chunk save", move || loop {
+            let chunk = {
+                let mut state = saves.lock();
+                match state.pending.remove(&pos) {
+                    Some(chunk) => chunk,
+                    None => {
+                        state.active.remove(&pos);
+                        saves.idle.notify_all();
+                        return;
+                    }
+                }
+            };
+            save_chunk(&target, pos, &chunk);
+        });
+    }
+
     /// Same contract as `WorldStream::update` but generation is async.
     /// Loaded chunks in the returned delta are only the ones that completed
     /// this frame — newly dispatched ones will appear in future frames.
@@ -215,6 +387,7 @@ impl AsyncWorldStream {
                 }
             };
             self.inner.chunks.insert(result.pos, chunk);
+            self.mesh_steps.insert(result.pos, result.lod_step);
             if !result.vertices.is_empty() {
                 self.ready_meshes
                     .push((result.pos, result.vertices, result.indices));
@@ -222,13 +395,17 @@ impl AsyncWorldStream {
             // Queue self and all loaded neighbors for boundary remesh — but only
             // if their neighbor set actually changed since they were last
             // remeshed, so a chunk isn't re-remeshed for a neighbor it already
-            // accounted for.
-            if needs_remesh(&self.remeshed_with, &self.inner.chunks, result.pos) {
+            // accounted for. Coarse meshes ignore their neighbors, so only
+            // full-detail ones are stitched.
+            if result.lod_step == 1
+                && needs_remesh(&self.remeshed_with, &self.inner.chunks, result.pos)
+            {
                 self.remesh_queue.push(result.pos);
             }
             for neighbor_pos in six_neighbors(result.pos) {
                 if self.inner.chunks.contains_key(&neighbor_pos)
                     && !self.known_empty.contains(&neighbor_pos)
+                    && self.mesh_steps.get(&neighbor_pos) == Some(&1)
                     && needs_remesh(&self.remeshed_with, &self.inner.chunks, neighbor_pos)
                 {
                     self.remesh_queue.push(neighbor_pos);
@@ -237,7 +414,20 @@ impl AsyncWorldStream {
             loaded.push(result.pos);
         }
 
+        // --- Remesh chunks whose LOD step changed as the camera moved ---
+        if self.lod_distance > 0 {
+            for (&pos, step) in &mut self.mesh_steps {
+                let wanted = lod_step(center, pos, self.lod_distance);
+                if *step != wanted {
+                    *step = wanted;
+                    self.remesh_queue.push(pos);
+                }
+            }
+        }
+
         // --- Compute desired set and find what needs loading ---
+        // Chunks still being saved would load without their latest edits.
+        let saving = self.saves.lock().active.clone();
         for x in (center.x - rxz)..=(center.x + rxz) {
             for y in (center.y - ry)..=(center.y + ry) {
                 for z in (center.z - rxz)..=(center.z + rxz) {
@@ -245,6 +435,7 @@ impl AsyncWorldStream {
                     if self.inner.chunks.contains_key(&pos)
                         || self.in_flight.contains(&pos)
                         || self.known_empty.contains(&pos)
+                        || saving.contains(&pos)
                     {
                         continue;
                     }
@@ -252,7 +443,7 @@ impl AsyncWorldStream {
                         // The generator's heuristic is coarse (e.g. "this
                         // chunk's Y range is entirely above the highest
                         // possible terrain height") and knows nothing about
-                        // player edits — see set_block_at's doc comment,
+                        // player edits — see set_block's doc comment,
                         // which can materialize exactly this kind of chunk
                         // on demand when a diff *does* exist. Record it as
                         // known_empty so every later frame hits the cheap
@@ -262,14 +453,20 @@ impl AsyncWorldStream {
                         continue;
                     }
                     self.in_flight.insert(pos);
-                    // best-effort send — if the channel is full we'll retry next frame
-                    let _ = self.work_tx.send(WorkItem {
+                    let work = WorkItem {
                         pos,
                         seed,
                         generator: Arc::clone(generator),
                         face_textures: Arc::clone(face_textures),
                         region_cache: self.region_cache.clone(),
-                    });
+                        lod_step: lod_step(center, pos, self.lod_distance),
+                    };
+                    let result_tx = self.result_tx.clone();
+                    self.jobs
+                        .spawn(Priority::Normal, "chunk generate", move || {
+                            // The stream may be gone (world reload) by now.
+                            let _ = result_tx.send(generate_chunk(work));
+                        });
                 }
             }
         }
@@ -301,36 +498,12 @@ impl AsyncWorldStream {
 
         // Apply unloads
         for pos in &to_unload {
-            if self.dirty.contains(pos) {
-                if let (Some(chunk), Some(cache), Some(gen)) = (
-                    self.inner.chunks.get(pos),
-                    &self.region_cache,
-                    &self.generator,
-                ) {
-                    let baseline = gen.generate(*pos, self.seed);
-                    match diff_from_chunks(&baseline, chunk, self.diff_threshold) {
-                        Some(diff) => {
-                            if let Ok(mut cache) = cache.lock() {
-                                if let Err(e) = cache.write_chunk(pos.x, pos.y, pos.z, &diff) {
-                                    tracing::error!("failed to save chunk {pos:?}: {e:#}");
-                                }
-                            }
-                        }
-                        None => {
-                            // Chunk reverted to virgin -- remove any previously saved diff
-                            if let Ok(mut cache) = cache.lock() {
-                                if let Err(e) = cache.remove_chunk(pos.x, pos.y, pos.z) {
-                                    tracing::warn!("failed to tombstone chunk {pos:?}: {e:#}");
-                                }
-                            }
-                        }
-                    }
-                }
-                self.dirty.remove(pos);
+            let chunk = self.inner.chunks.remove(pos);
+            if let (true, Some(chunk)) = (self.dirty.remove(pos), chunk) {
+                self.queue_save(*pos, chunk);
             }
-
-            self.inner.chunks.remove(pos);
             self.remeshed_with.remove(pos);
+            self.mesh_steps.remove(pos);
             // Clear this position's bit in each neighbor's recorded mask so
             // they get re-queued if a new chunk later fills this slot.
             for (i, neighbor_pos) in six_neighbors(*pos).iter().enumerate() {
@@ -349,6 +522,16 @@ impl AsyncWorldStream {
                 && (pos.z - center.z).abs() <= rxz
         });
 
+        // Meshes of chunks unloaded before their upload came round would
+        // otherwise be uploaded for chunks no longer there. The rest go
+        // nearest-last, for callers popping them off the end.
+        if !to_unload.is_empty() {
+            self.ready_meshes
+                .retain(|(pos, ..)| self.inner.chunks.contains_key(pos));
+        }
+        self.ready_meshes
+            .sort_by_key(|(pos, ..)| Reverse(chunk_distance_sq(center, *pos)));
+
         StreamDelta {
             loaded,
             unloaded: to_unload,
@@ -381,11 +564,49 @@ impl AsyncWorldStream {
         &self.inner.chunks
     }
 
+    /// Mesh `positions` against their current neighbors, in parallel on
+    /// the job pool, blocking until all are done, each at its LOD step
+    /// (coarse meshes ignore neighbors). Positions that aren't loaded are
+    /// left out of the result.
+    pub fn remesh(
+        &self,
+        positions: &[ChunkPos],
+        face_textures: &BlockFaceTextures,
+    ) -> Vec<(ChunkPos, Vec<Vertex>, Vec<u32>)> {
+        let mut meshes: Vec<_> = positions
+            .iter()
+            .filter_map(|&pos| {
+                let chunk = self.inner.chunks.get(&pos)?;
+                let neighbors = self.inner.neighbors(pos);
+                let step = self.mesh_steps.get(&pos).copied().unwrap_or(1);
+                Some((pos, chunk, neighbors, step, Vec::new(), Vec::new()))
+            })
+            .collect();
+        self.jobs.scope(|s| {
+            for (_, chunk, neighbors, step, vertices, indices) in &mut meshes {
+                s.spawn(Priority::High, "chunk remesh", move || {
+                    (*vertices, *indices) = if *step > 1 {
+                        mesh_chunk_lod(chunk, *step, face_textures)
+                    } else {
+                        mesh_chunk(chunk, *neighbors, face_textures)
+                    };
+                });
+            }
+        });
+        meshes
+            .into_iter()
+            .map(|(pos, _, _, _, vertices, indices)| (pos, vertices, indices))
+            .collect()
+    }
+
     /// Record which neighbors were present the last time `pos` was remeshed,
     /// so future arrivals that don't change its neighbor set won't re-queue it.
+    /// Also drops any first mesh of `pos` still waiting in `ready_meshes`,
+    /// which the remesh supersedes.
     pub fn mark_remeshed(&mut self, pos: ChunkPos) {
         let mask = neighbor_mask(&self.inner.chunks, pos);
         self.remeshed_with.insert(pos, mask);
+        self.ready_meshes.retain(|(p, ..)| *p != pos);
     }
 
     pub fn query_view(&self) -> ChunkQueryView<'_> {
@@ -394,6 +615,19 @@ impl AsyncWorldStream {
         }
     }
 
+    /// First solid voxel within `max_dist` metres along the ray, against
+    /// the loaded chunks (see `physics::raycast`). Break `hit.pos`, place
+    /// at `hit.adjacent()`, both through `set_block`.
+    pub fn raycast(&self, origin: DVec3, dir: DVec3, max_dist: f64) -> Option<BlockHit> {
+        raycast(&self.query_view(), origin, dir, max_dist)
+    }
+
+    /// `set_block` for the voxel containing world-space position
+    /// `(wx, wy, wz)`.
+    pub fn set_block_at(&mut self, wx: f64, wy: f64, wz: f64, id: BlockTypeId) -> bool {
+        self.set_block(BlockPos::from_world(DVec3::new(wx, wy, wz)), id)
+    }
+
     /// Edit a single voxel in the currently loaded world (break/place),
     /// queuing this chunk — and any face-adjacent neighbor whose mesh culls
     /// faces against this voxel — for remesh via the same `remesh_queue`
@@ -417,6 +651,9 @@ impl AsyncWorldStream {
     /// - Simply never requested yet, e.g. the streaming loop hasn't reached
     ///   it this frame.
     ///
+    /// A save still running for the chunk (it was edited, then unloaded)
+    /// is waited for first, so its diff is read back complete.
+    ///
     /// All three are safe to materialize synchronously here: a block edit
     /// is only ever raycast-bounded to within reach of the player, so it's
     /// always near the currently active area, and this pays the same
@@ -425,8 +662,8 @@ impl AsyncWorldStream {
     /// `in_flight` — a worker is already generating this exact chunk, and
     /// generating a second copy here would race its result, silently
     /// overwriting this edit when the worker's result lands.
-    pub fn set_block_at(&mut self, wx: f64, wy: f64, wz: f64, id: BlockTypeId) -> bool {
-        let (cp, lp) = world_to_chunk_local(wx, wy, wz);
+    pub fn set_block(&mut self, pos: BlockPos, id: BlockTypeId) -> bool {
+        let (cp, lp) = pos.to_chunk_local();
         if !self.inner.chunks.contains_key(&cp) {
             if self.in_flight.contains(&cp) {
                 return false;
@@ -434,6 +671,7 @@ impl AsyncWorldStream {
             let Some(generator) = &self.generator else {
                 return false;
             };
+            self.saves.wait_for(cp);
             let mut chunk = generator.generate(cp, self.seed);
             if let Some(cache) = &self.region_cache {
                 if let Ok(mut cache) = cache.lock() {
@@ -444,6 +682,9 @@ impl AsyncWorldStream {
             }
             self.known_empty.remove(&cp);
             self.inner.chunks.insert(cp, chunk);
+            // Edits are within reach of the camera; the next update
+            // corrects the step if not.
+            self.mesh_steps.insert(cp, 1);
         }
         let chunk = self
             .inner
@@ -457,33 +698,16 @@ impl AsyncWorldStream {
         true
     }
 
-    /// Save all dirty loaded chunks to disk without unloading them.
-    /// Called by the autosave timer and on clean quit.
+    /// Queue every dirty loaded chunk for a background save without
+    /// unloading it, and mark them clean. Called by the autosave policy and
+    /// on clean quit, which then waits with `wait_for_saves`.
     pub fn flush_dirty(&mut self) {
-        let (Some(cache), Some(gen)) = (&self.region_cache, &self.generator) else {
+        if self.save_target().is_none() {
             return;
-        };
-        let dirty_positions: Vec<ChunkPos> = self.dirty.iter().copied().collect();
-        for pos in dirty_positions {
-            let Some(chunk) = self.inner.chunks.get(&pos) else {
-                self.dirty.remove(&pos);
-                continue;
-            };
-            let baseline = gen.generate(pos, self.seed);
-            match diff_from_chunks(&baseline, chunk, self.diff_threshold) {
-                Some(diff) => {
-                    if let Ok(mut cache) = cache.lock() {
-                        if let Err(e) = cache.write_chunk(pos.x, pos.y, pos.z, &diff) {
-                            tracing::error!("autosave failed for {pos:?}: {e:#}");
-                        }
-                    }
-                }
-                None => {
-                    if let Ok(mut cache) = cache.lock() {
-                        let _ = cache.remove_chunk(pos.x, pos.y, pos.z);
-                    }
-                    self.dirty.remove(&pos);
-                }
+        }
+        for pos in std::mem::take(&mut self.dirty) {
+            if let Some(chunk) = self.inner.chunks.get(&pos) {
+                self.queue_save(pos, chunk.clone());
             }
         }
     }
@@ -493,7 +717,7 @@ impl AsyncWorldStream {
 /// `lp` also affects — i.e. `lp` sits on one of the 6 chunk faces, so the
 /// neighbor's mesh (which culls its own faces against this chunk's
 /// occupancy) needs to be recomputed too. Pure and separately testable from
-/// `set_block_at`, which just needs live chunk data to exercise otherwise.
+/// `set_block`, which just needs live chunk data to exercise otherwise.
 fn boundary_neighbors(cp: ChunkPos, lp: crate::ChunkLocalPos) -> Vec<ChunkPos> {
     let max = (CHUNK_SIZE - 1) as u8;
     let mut out = Vec::new();
@@ -518,6 +742,11 @@ fn boundary_neighbors(cp: ChunkPos, lp: crate::ChunkLocalPos) -> Vec<ChunkPos> {
     out
 }
 
+fn chunk_distance_sq(a: ChunkPos, b: ChunkPos) -> i64 {
+    let d = |a: i32, b: i32| (a as i64 - b as i64).pow(2);
+    d(a.x, b.x) + d(a.y, b.y) + d(a.z, b.z)
+}
+
 fn neighbor_mask(chunks: &std::collections::HashMap<ChunkPos, Chunk>, pos: ChunkPos) -> u8 {
     let mut mask = 0u8;
     for (i, neighbor_pos) in six_neighbors(pos).iter().enumerate() {
@@ -572,6 +801,10 @@ mod tests {
     use super::*;
     use crate::ChunkLocalPos;
 
+    fn test_jobs() -> Arc<JobPool> {
+        Arc::new(JobPool::new("test-stream", 1, None))
+    }
+
     #[test]
     fn boundary_neighbors_empty_for_interior_voxel() {
         let cp = ChunkPos { x: 0, y: 0, z: 0 };
@@ -599,12 +832,25 @@ mod tests {
         assert!(out.contains(&ChunkPos { x: 0, y: 0, z: -1 }));
     }
 
+    #[test]
+    fn lod_step_bands_double() {
+        let center = ChunkPos { x: 0, y: 0, z: 0 };
+        let at = |x: i32, z: i32| lod_step(center, ChunkPos { x, y: 9, z }, 4);
+        assert_eq!(at(4, -4), 1);
+        assert_eq!(at(5, 0), 2);
+        assert_eq!(at(0, -8), 2);
+        assert_eq!(at(9, 3), 4);
+        assert_eq!(at(17, 0), 8);
+        assert_eq!(at(1000, 0), MAX_LOD_STEP);
+        assert_eq!(lod_step(center, ChunkPos { x: 50, y: 0, z: 0 }, 0), 1);
+    }
+
     /// Always generates an all-air chunk and always reports itself as
     /// "definitely air" — stands in for both the `known_empty` case (a
     /// chunk streamed once, found empty, and discarded) and the
     /// never-generated-at-all case (a chunk `update()`'s request loop
     /// skipped outright because `is_definitely_air` said not to bother —
-    /// see `set_block_at`'s doc comment).
+    /// see `set_block`'s doc comment).
     struct AirGenerator;
     impl WorldGenerator for AirGenerator {
         fn generate(&self, _pos: ChunkPos, _seed: u64) -> Chunk {
@@ -622,7 +868,7 @@ mod tests {
         // air) and was therefore never stored — only recorded in
         // known_empty. Placing a block into it (e.g. against the surface
         // chunk's top face) must succeed, not silently no-op.
-        let mut stream = AsyncWorldStream::new(1, 1, None);
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
         stream.generator = Some(Arc::new(AirGenerator) as Arc<dyn WorldGenerator>);
         let pos = ChunkPos { x: 0, y: 1, z: 0 };
         stream.known_empty.insert(pos);
@@ -652,7 +898,7 @@ mod tests {
         // is_definitely_air short-circuits it out of update()'s request
         // loop entirely (e.g. building straight up past the generator's
         // "highest possible terrain" bound). Must still succeed.
-        let mut stream = AsyncWorldStream::new(1, 1, None);
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
         stream.generator = Some(Arc::new(AirGenerator) as Arc<dyn WorldGenerator>);
         let pos = ChunkPos { x: 0, y: 3, z: 0 };
 
@@ -670,12 +916,35 @@ mod tests {
         assert_eq!(chunk.get(ChunkLocalPos::new(2, 2, 2)), stone);
     }
 
+    #[test]
+    fn set_block_then_raycast_finds_it() {
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
+        stream.generator = Some(Arc::new(AirGenerator) as Arc<dyn WorldGenerator>);
+        // Local x = 0: the -X neighbour culls against it too.
+        let pos = BlockPos { x: 32, y: 3, z: 5 };
+        assert!(stream.set_block(pos, BlockTypeId(7)));
+
+        let origin = pos.to_world_origin() + DVec3::new(0.25, 4.0, 0.25);
+        let hit = stream
+            .raycast(origin, DVec3::NEG_Y, 8.0)
+            .expect("block below");
+        assert_eq!(hit.pos, pos);
+        assert_eq!(hit.block, BlockTypeId(7));
+        assert_eq!(hit.adjacent(), pos.offset(0, 1, 0));
+        let chunk = ChunkPos { x: 1, y: 0, z: 0 };
+        assert!(stream.remesh_queue.contains(&chunk));
+        assert!(stream.remesh_queue.contains(&ChunkPos { x: 0, ..chunk }));
+
+        assert!(stream.set_block(pos, BlockTypeId(0)));
+        assert!(stream.raycast(origin, DVec3::NEG_Y, 8.0).is_none());
+    }
+
     #[test]
     fn set_block_at_noop_while_chunk_in_flight() {
         // A worker is already generating this exact chunk. Materializing a
         // second copy here would race the worker's own result landing,
         // which would silently overwrite this edit — must stay a no-op.
-        let mut stream = AsyncWorldStream::new(1, 1, None);
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
         stream.generator = Some(Arc::new(AirGenerator) as Arc<dyn WorldGenerator>);
         let pos = ChunkPos { x: 0, y: 0, z: 0 };
         stream.in_flight.insert(pos);
@@ -727,7 +996,7 @@ mod tests {
         };
         cache.write_chunk(pos.x, pos.y, pos.z, &diff).unwrap();
 
-        let mut stream = AsyncWorldStream::new(1, 1, None);
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
         let generator = Arc::new(AirGenerator) as Arc<dyn WorldGenerator>;
         stream.set_persistence(Arc::new(Mutex::new(cache)), Arc::clone(&generator), 0, 512);
 
@@ -749,7 +1018,7 @@ mod tests {
         let dir = TempDir::new("no_diff");
         let cache = RegionCache::new(dir.0.clone(), 4);
 
-        let mut stream = AsyncWorldStream::new(1, 1, None);
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
         let generator = Arc::new(AirGenerator) as Arc<dyn WorldGenerator>;
         stream.set_persistence(Arc::new(Mutex::new(cache)), Arc::clone(&generator), 0, 512);
 
@@ -759,4 +1028,74 @@ mod tests {
         assert!(stream.known_empty.contains(&pos));
         assert!(!stream.in_flight.contains(&pos));
     }
+
+    #[test]
+    fn saves_run_in_the_background_and_hold_back_reloads() {
+        let dir = TempDir::new("save");
+        let cache = Arc::new(Mutex::new(RegionCache::new(dir.0.clone(), 4)));
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
+        let generator = Arc::new(AirGenerator) as Arc<dyn WorldGenerator>;
+        stream.set_persistence(Arc::clone(&cache), Arc::clone(&generator), 0, 1);
+        let pos = ChunkPos { x: 0, y: 0, z: 0 };
+
+        assert!(stream.set_block(BlockPos { x: 2, y: 2, z: 2 }, BlockTypeId(1)));
+        assert_eq!(stream.dirty_count(), 1);
+        stream.flush_dirty();
+        assert_eq!(stream.dirty_count(), 0);
+        stream.wait_for_saves();
+        let diff = cache.lock().unwrap().read_chunk(0, 0, 0).unwrap();
+        let mut chunk = Chunk::new();
+        apply_diff(&mut chunk, &diff.expect("saved"));
+        assert_eq!(chunk.get(ChunkLocalPos::new(2, 2, 2)), BlockTypeId(1));
+
+        // While a save is marked running the chunk isn't requested again.
+        stream.inner.chunks.clear();
+        stream.saves.lock().active.insert(pos);
+        stream.update(pos, &generator, 0, &Arc::new(BlockFaceTextures::new()));
+        assert!(!stream.in_flight.contains(&pos));
+        stream.saves.lock().active.clear();
+    }
+
+    #[test]
+    fn update_evicts_pending_meshes_and_orders_nearest_last() {
+        let mut stream = AsyncWorldStream::new(1, 1, test_jobs());
+        let generator = Arc::new(AirGenerator) as Arc<dyn WorldGenerator>;
+        let near = ChunkPos { x: 0, y: 0, z: 0 };
+        let mid = ChunkPos { x: 1, y: 0, z: 1 };
+        let far = ChunkPos { x: 5, y: 0, z: 0 };
+        for pos in [near, mid, far] {
+            stream.inner.chunks.insert(pos, Chunk::new());
+            stream.ready_meshes.push((pos, Vec::new(), Vec::new()));
+        }
+
+        let delta = stream.update(near, &generator, 0, &Arc::new(BlockFaceTextures::new()));
+
+        assert_eq!(delta.unloaded, vec![far]);
+        let order: Vec<ChunkPos> = stream.ready_meshes.iter().map(|m| m.0).collect();
+        assert_eq!(order, vec![mid, near]);
+        let counts = stream.stream_counts();
+        assert_eq!(counts.resident, 2);
+        assert_eq!(counts.uploads_pending, 2);
+        assert_eq!(counts.meshing, 0);
+    }
+
+    #[test]
+    fn update_requeues_chunks_whose_lod_step_changed() {
+        let mut stream = AsyncWorldStream::new(2, 1, test_jobs());
+        stream.set_lod_distance(1);
+        let generator = Arc::new(AirGenerator) as Arc<dyn WorldGenerator>;
+        let pos = ChunkPos { x: 2, y: 0, z: 0 };
+        stream.inner.chunks.insert(pos, Chunk::new());
+        stream.mesh_steps.insert(pos, 1);
+        let textures = Arc::new(BlockFaceTextures::new());
+
+        stream.update(ChunkPos { x: 0, y: 0, z: 0 }, &generator, 0, &textures);
+        assert_eq!(stream.mesh_steps[&pos], 2);
+        assert_eq!(stream.remesh_queue, vec![pos]);
+
+        // Already at the right step: not queued again.
+        stream.remesh_queue.clear();
+        stream.update(ChunkPos { x: 0, y: 0, z: 0 }, &generator, 0, &textures);
+        assert!(stream.remesh_queue.is_empty());
+    }
 }
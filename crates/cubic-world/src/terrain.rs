@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: CEPL-1.0
+#![deny(unsafe_op_in_unsafe_fn)]
+//! Heightmap terrain: large outdoor ground as a grid of tiles, each meshed
+//! at a level of detail picked by its distance from the camera. The
+//! complement to the voxel world for scenes where blocks would be far too
+//! many.
+//!
+//! A `Heightmap` is a grid of heights, loaded from 8-bit greyscale (e.g.
+//! `cubic_assets::load_image`'s pixels) or 16-bit raw. `Terrain` cuts it
+//! into tiles of `TerrainConfig::tile_cells` cells a side; LOD n samples
+//! every 2^n-th height, so each level has a quarter of the triangles of
+//! the one before. Tiles at different LODs don't share their edge
+//! vertices, so every tile hangs a skirt (its edge repeated
+//! `skirt_depth` straight down) to cover the cracks between them.
+//!
+//! Like `mesh_chunk`, meshing is backend-agnostic `Vertex` lists: positions
+//! relative to the tile's origin (draw it at `tile_origin`, camera-relative
+//! like a chunk), heightmap normals, and UVs repeating every
+//! `TerrainConfig::uv_scale` metres for tiling ground textures, sampled
+//! from `tex_index`. Any material draws it, e.g. a metallic-roughness one
+//! with a normal map for the ground's detail.
+
+use anyhow::{bail, Result};
+use cubic_math::{DVec3, Vec3};
+use cubic_render::Vertex;
+
+/// A grid of heights in metres, `width` samples along x and `depth`
+/// along z.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    width: u32,
+    depth: u32,
+    /// Row by row, x varying fastest.
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// `heights` row by row, x varying fastest. Needs at least 2x2.
+    pub fn new(width: u32, depth: u32, heights: Vec<f32>) -> Result<Self> {
+        if width < 2 || depth < 2 {
+            bail!("heightmap: {width}x{depth} is smaller than 2x2");
+        }
+        if heights.len() != width as usize * depth as usize {
+            bail!("heightmap: {} heights for {width}x{depth}", heights.len());
+        }
+        Ok(Self {
+            width,
+            depth,
+            heights,
+        })
+    }
+
+    /// The red channel of RGBA8 pixels, 0..=255 onto 0..=max_height.
+    pub fn from_rgba8(pixels: &[u8], width: u32, depth: u32, max_height: f32) -> Result<Self> {
+        if pixels.len() != width as usize * depth as usize * 4 {
+            bail!("heightmap: expected {width}x{depth} RGBA8 pixels");
+        }
+        let heights = pixels
+            .chunks_exact(4)
+            .map(|px| px[0] as f32 / 255.0 * max_height)
+            .collect();
+        Self::new(width, depth, heights)
+    }
+
+    /// Little-endian 16-bit raw, the usual terrain tools' export,
+    /// 0..=65535 onto 0..=max_height.
+    pub fn from_r16(bytes: &[u8], width: u32, depth: u32, max_height: f32) -> Result<Self> {
+        if bytes.len() != width as usize * depth as usize * 2 {
+            bail!("heightmap: expected {width}x{depth} 16-bit samples");
+        }
+        let heights = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0 * max_height)
+            .collect();
+        Self::new(width, depth, heights)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Height of sample (x, z), clamped to the map's edge.
+    pub fn sample(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let z = z.clamp(0, self.depth as i64 - 1) as usize;
+        self.heights[x + z * self.width as usize]
+    }
+
+    /// Bilinear height between samples, in sample coordinates.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as i64, z0 as i64);
+        let top = lerp(self.sample(x0, z0), self.sample(x0 + 1, z0), fx);
+        let bottom = lerp(self.sample(x0, z0 + 1), self.sample(x0 + 1, z0 + 1), fx);
+        lerp(top, bottom, fz)
+    }
+
+    /// Surface normal at sample (x, z) from central differences, samples
+    /// `cell_size` metres apart.
+    fn normal(&self, x: i64, z: i64, cell_size: f32) -> [f32; 3] {
+        let dx = self.sample(x - 1, z) - self.sample(x + 1, z);
+        let dz = self.sample(x, z - 1) - self.sample(x, z + 1);
+        Vec3::new(dx, 2.0 * cell_size, dz)
+            .normalize_or_zero()
+            .to_array()
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainConfig {
+    /// Metres between neighbouring heights.
+    pub cell_size: f32,
+    /// Cells along a tile's side at LOD 0: a power of two, and at least
+    /// 2^(lod_levels - 1) so the coarsest LOD still has a cell.
+    pub tile_cells: u32,
+    /// LODs 0 to lod_levels - 1.
+    pub lod_levels: u32,
+    /// Tiles nearer the camera than this (horizontally, to their nearest
+    /// point) are LOD 0; each further LOD's band reaches twice as far.
+    pub lod_distance: f32,
+    /// How far skirts hang below the tile's edge; more than the largest
+    /// height step between neighbouring LODs hides every crack.
+    pub skirt_depth: f32,
+    /// Metres per repeat of the ground texture.
+    pub uv_scale: f32,
+    /// Bindless texture index baked into the vertices.
+    pub tex_index: u32,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.0,
+            tile_cells: 64,
+            lod_levels: 4,
+            lod_distance: 64.0,
+            skirt_depth: 2.0,
+            uv_scale: 4.0,
+            tex_index: 0,
+        }
+    }
+}
+
+/// One tile at one LOD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TerrainTile {
+    pub x: u32,
+    pub z: u32,
+    pub lod: u32,
+}
+
+/// A heightmap cut into tiles, and the LOD each was last meshed at.
+pub struct Terrain {
+    heightmap: Heightmap,
+    config: TerrainConfig,
+    tiles_x: u32,
+    tiles_z: u32,
+    /// Per tile, x varying fastest; None until the first update.
+    lods: Vec<Option<u32>>,
+}
+
+impl Terrain {
+    pub fn new(heightmap: Heightmap, config: TerrainConfig) -> Result<Self> {
+        if !config.tile_cells.is_power_of_two() {
+            bail!(
+                "terrain: tile_cells {} is not a power of two",
+                config.tile_cells
+            );
+        }
+        if config.lod_levels == 0 || config.lod_levels > 1 + config.tile_cells.ilog2() {
+            bail!(
+                "terrain: {} LODs don't fit tiles of {} cells",
+                config.lod_levels,
+                config.tile_cells
+            );
+        }
+        if config.cell_size <= 0.0 || config.uv_scale <= 0.0 {
+            bail!("terrain: cell_size and uv_scale must be positive");
+        }
+        let tiles_x = (heightmap.width - 1).div_ceil(config.tile_cells);
+        let tiles_z = (heightmap.depth - 1).div_ceil(config.tile_cells);
+        Ok(Self {
+            heightmap,
+            config,
+            tiles_x,
+            tiles_z,
+            lods: vec![None; (tiles_x * tiles_z) as usize],
+        })
+    }
+
+    pub fn heightmap(&self) -> &Heightmap {
+        &self.heightmap
+    }
+
+    pub fn config(&self) -> &TerrainConfig {
+        &self.config
+    }
+
+    /// Tiles along x and along z.
+    pub fn tiles(&self) -> (u32, u32) {
+        (self.tiles_x, self.tiles_z)
+    }
+
+    /// Metres along a tile's side.
+    fn tile_size(&self) -> f64 {
+        self.config.tile_cells as f64 * self.config.cell_size as f64
+    }
+
+    /// Terrain-space position of tile (x, z)'s corner, where its mesh's
+    /// origin is.
+    pub fn tile_origin(&self, x: u32, z: u32) -> DVec3 {
+        let s = self.tile_size();
+        DVec3::new(x as f64 * s, 0.0, z as f64 * s)
+    }
+
+    /// Height of the ground at terrain-space (x, z), between samples as
+    /// LOD 0 draws it.
+    pub fn height_at(&self, x: f64, z: f64) -> f32 {
+        let cell = self.config.cell_size as f64;
+        self.heightmap
+            .height_at((x / cell) as f32, (z / cell) as f32)
+    }
+
+    /// The LOD for a tile whose nearest point is `distance` metres away.
+    pub fn lod_for_distance(&self, distance: f32) -> u32 {
+        let mut lod = 0;
+        let mut band = self.config.lod_distance;
+        while distance >= band && lod + 1 < self.config.lod_levels {
+            lod += 1;
+            band *= 2.0;
+        }
+        lod
+    }
+
+    /// Pick every tile's LOD for a camera at terrain-space `camera`,
+    /// returning the tiles whose LOD changed (all of them the first time):
+    /// the ones to mesh_tile and upload again, freeing their old mesh.
+    pub fn update(&mut self, camera: DVec3) -> Vec<TerrainTile> {
+        let size = self.tile_size();
+        let mut changed = Vec::new();
+        for z in 0..self.tiles_z {
+            for x in 0..self.tiles_x {
+                let origin = self.tile_origin(x, z);
+                let dx = (origin.x - camera.x)
+                    .max(camera.x - origin.x - size)
+                    .max(0.0);
+                let dz = (origin.z - camera.z)
+                    .max(camera.z - origin.z - size)
+                    .max(0.0);
+                let lod = self.lod_for_distance(dx.hypot(dz) as f32);
+                let slot = &mut self.lods[(x + z * self.tiles_x) as usize];
+                if *slot != Some(lod) {
+                    *slot = Some(lod);
+                    changed.push(TerrainTile { x, z, lod });
+                }
+            }
+        }
+        changed
+    }
+
+    /// The LOD tile (x, z) was given by the last update.
+    pub fn tile_lod(&self, x: u32, z: u32) -> Option<u32> {
+        if x >= self.tiles_x || z >= self.tiles_z {
+            return None;
+        }
+        self.lods[(x + z * self.tiles_x) as usize]
+    }
+
+    /// `tile`'s mesh at its LOD, skirt included, relative to its
+    /// tile_origin. Cells past the heightmap's far edge collapse onto it.
+    pub fn mesh_tile(&self, tile: TerrainTile) -> (Vec<Vertex>, Vec<u32>) {
+        let cfg = &self.config;
+        let step = 1u32 << tile.lod.min(cfg.lod_levels - 1);
+        let n = cfg.tile_cells / step;
+        let (x0, z0) = (tile.x * cfg.tile_cells, tile.z * cfg.tile_cells);
+        let last = (self.heightmap.width - 1, self.heightmap.depth - 1);
+
+        let row = n + 1;
+        let mut verts = Vec::with_capacity((row * row + 4 * row) as usize);
+        for j in 0..=n {
+            for i in 0..=n {
+                let sx = (x0 + i * step).min(last.0);
+                let sz = (z0 + j * step).min(last.1);
+                let (lx, lz) = ((sx - x0) as f32, (sz - z0) as f32);
+                let (wx, wz) = (sx as f32 * cfg.cell_size, sz as f32 * cfg.cell_size);
+                verts.push(Vertex {
+                    pos: [
+                        lx * cfg.cell_size,
+                        self.heightmap.sample(sx as i64, sz as i64),
+                        lz * cfg.cell_size,
+                    ],
+                    color: [1.0; 3],
+                    uv: [wx / cfg.uv_scale, wz / cfg.uv_scale],
+                    normal: self.heightmap.normal(sx as i64, sz as i64, cfg.cell_size),
+                    tex_index: cfg.tex_index,
+                });
+            }
+        }
+
+        let mut idxs = Vec::with_capacity((n * n * 6 + 4 * n * 6) as usize);
+        let at = |i: u32, j: u32| i + j * row;
+        for j in 0..n {
+            for i in 0..n {
+                let quad = [at(i, j), at(i, j + 1), at(i + 1, j + 1), at(i + 1, j)];
+                push_quad(&verts, &mut idxs, quad, Vec3::Y);
+            }
+        }
+
+        // Skirts: each edge's vertices again, dropped, joined to the edge.
+        let edges: [(Vec<u32>, Vec3); 4] = [
+            ((0..=n).map(|i| at(i, 0)).collect(), Vec3::NEG_Z),
+            ((0..=n).map(|i| at(i, n)).collect(), Vec3::Z),
+            ((0..=n).map(|j| at(0, j)).collect(), Vec3::NEG_X),
+            ((0..=n).map(|j| at(n, j)).collect(), Vec3::X),
+        ];
+        for (edge, outward) in edges {
+            let base = verts.len() as u32;
+            for &v in &edge {
+                let mut skirt = verts[v as usize];
+                skirt.pos[1] -= cfg.skirt_depth;
+                verts.push(skirt);
+            }
+            for k in 0..n {
+                let quad = [
+                    edge[k as usize],
+                    edge[k as usize + 1],
+                    base + k + 1,
+                    base + k,
+                ];
+                push_quad(&verts, &mut idxs, quad, outward);
+            }
+        }
+        (verts, idxs)
+    }
+}
+
+/// Two triangles over `quad` (its corners in order round it), wound
+/// counter-clockwise seen from `outward` like the voxel mesher's faces.
+fn push_quad(verts: &[Vertex], idxs: &mut Vec<u32>, quad: [u32; 4], outward: Vec3) {
+    let p = |i: u32| Vec3::from(verts[i as usize].pos);
+    let facing = (p(quad[1]) - p(quad[0]))
+        .cross(p(quad[2]) - p(quad[0]))
+        .dot(outward);
+    let [a, b, c, d] = if facing >= 0.0 {
+        quad
+    } else {
+        [quad[0], quad[3], quad[2], quad[1]]
+    };
+    idxs.extend_from_slice(&[a, b, c, a, c, d]);
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(size: u32) -> Heightmap {
+        Heightmap::new(size, size, vec![0.0; (size * size) as usize]).unwrap()
+    }
+
+    fn config(tile_cells: u32, lod_levels: u32) -> TerrainConfig {
+        TerrainConfig {
+            tile_cells,
+            lod_levels,
+            lod_distance: 10.0,
+            ..TerrainConfig::default()
+        }
+    }
+
+    #[test]
+    fn heightmap_scales_and_interpolates() {
+        let pixels = [0, 0, 0, 255, 255, 0, 0, 255, 0, 0, 0, 255, 255, 0, 0, 255];
+        let map = Heightmap::from_rgba8(&pixels, 2, 2, 10.0).unwrap();
+        assert_eq!(map.sample(1, 0), 10.0);
+        assert_eq!(map.sample(5, -3), 10.0); // clamped to (1, 0)
+        assert!((map.height_at(0.5, 0.5) - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn heightmap_rejects_wrong_sizes() {
+        assert!(Heightmap::new(1, 4, vec![0.0; 4]).is_err());
+        assert!(Heightmap::new(3, 3, vec![0.0; 8]).is_err());
+        assert!(Heightmap::from_r16(&[0; 7], 2, 2, 1.0).is_err());
+    }
+
+    #[test]
+    fn lod_bands_double() {
+        let terrain = Terrain::new(flat(33), config(8, 3)).unwrap();
+        assert_eq!(terrain.lod_for_distance(0.0), 0);
+        assert_eq!(terrain.lod_for_distance(9.9), 0);
+        assert_eq!(terrain.lod_for_distance(10.0), 1);
+        assert_eq!(terrain.lod_for_distance(19.9), 1);
+        assert_eq!(terrain.lod_for_distance(25.0), 2);
+        // Clamped to the coarsest LOD.
+        assert_eq!(terrain.lod_for_distance(1e6), 2);
+    }
+
+    #[test]
+    fn config_validated() {
+        assert!(Terrain::new(flat(33), config(6, 1)).is_err());
+        assert!(Terrain::new(flat(33), config(4, 4)).is_err());
+        assert!(Terrain::new(flat(33), config(4, 3)).is_ok());
+    }
+
+    #[test]
+    fn mesh_counts_per_lod() {
+        let terrain = Terrain::new(flat(9), config(8, 4)).unwrap();
+        for (lod, n) in [(0, 8u32), (1, 4), (3, 1)] {
+            let (verts, idxs) = terrain.mesh_tile(TerrainTile { x: 0, z: 0, lod });
+            // Grid plus four skirt strips.
+            assert_eq!(verts.len() as u32, (n + 1) * (n + 1) + 4 * (n + 1));
+            assert_eq!(idxs.len() as u32, n * n * 6 + 4 * n * 6);
+        }
+    }
+
+    #[test]
+    fn mesh_faces_up_and_skirts_hang() {
+        let terrain = Terrain::new(flat(9), config(8, 1)).unwrap();
+        let (verts, idxs) = terrain.mesh_tile(TerrainTile { x: 0, z: 0, lod: 0 });
+        let grid = 8 * 8 * 6;
+        for tri in idxs[..grid].chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(verts[tri[k] as usize].pos));
+            assert!((b - a).cross(c - a).y > 0.0);
+        }
+        let lowest = verts.iter().map(|v| v.pos[1]).fold(f32::MAX, f32::min);
+        assert_eq!(lowest, -terrain.config().skirt_depth);
+    }
+
+    #[test]
+    fn update_reports_only_changes() {
+        // 4x4 tiles of 8 cells, 1 m each.
+        let mut terrain = Terrain::new(flat(33), config(8, 3)).unwrap();
+        let first = terrain.update(DVec3::new(4.0, 0.0, 4.0));
+        assert_eq!(first.len(), 16);
+        assert_eq!(terrain.tile_lod(0, 0), Some(0));
+        assert_eq!(terrain.tile_lod(3, 3), Some(2));
+        assert!(terrain.update(DVec3::new(3.5, 0.0, 3.5)).is_empty());
+        let moved = terrain.update(DVec3::new(28.0, 0.0, 28.0));
+        assert!(moved.contains(&TerrainTile { x: 3, z: 3, lod: 0 }));
+        assert!(moved.contains(&TerrainTile { x: 0, z: 0, lod: 2 }));
+    }
+}
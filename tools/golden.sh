@@ -0,0 +1,41 @@
+#!/usr/bin/env bash
+# Render the golden scenes and compare them with tests/golden (see
+# crates/cubic-app/src/golden.rs), for Vulkan and GL or the backends named.
+# --update writes the frames over the goldens instead.
+#
+# CI's golden job runs this under Xvfb on Mesa's software rasterizers
+# (lavapipe, llvmpipe), and the committed goldens are theirs. A GPU draws
+# differently enough to fail, so regenerate goldens with that job (run it
+# by hand with `update` ticked), not locally.
+#
+# Usage: tools/golden.sh [--update] [vk|gl ...]
+set -euo pipefail
+
+# Goldens only compare at the size they were made at.
+WIDTH=640
+HEIGHT=360
+
+root=$(git rev-parse --show-toplevel)
+cd "$root"
+
+update=()
+backends=()
+for arg in "$@"; do
+  case "$arg" in
+    --update) update=(--update) ;;
+    *) backends+=("$arg") ;;
+  esac
+done
+[ ${#backends[@]} -gt 0 ] || backends=(vk gl)
+
+# The scenes are of the bench world, which the game generates.
+cargo build --release -p cubic-game --target wasm32-unknown-unknown
+mkdir -p games/cubic-game
+cp target/wasm32-unknown-unknown/release/cubic_game.wasm games/cubic-game/game.wasm
+
+status=0
+for backend in "${backends[@]}"; do
+  cargo run --release --bin cubic-app -- golden --backend "$backend" \
+    --width "$WIDTH" --height "$HEIGHT" "${update[@]}" || status=1
+done
+exit $status
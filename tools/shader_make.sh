@@ -8,4 +8,24 @@ TARGET_ENV="--target-env=vulkan1.2"
 
 $GLSLC "$SRC_DIR/tri.vert" -o "$OUT_DIR/tri.vert.spv" $TARGET_ENV -O
 $GLSLC "$SRC_DIR/tri.frag" -o "$OUT_DIR/tri.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/pbr.frag" -o "$OUT_DIR/pbr.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/fullscreen.vert" -o "$OUT_DIR/fullscreen.vert.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/tonemap.frag" -o "$OUT_DIR/tonemap.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/post.frag" -o "$OUT_DIR/post.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/object_id.frag" -o "$OUT_DIR/object_id.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/stereo.vert" -o "$OUT_DIR/stereo.vert.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/stereo_composite.frag" -o "$OUT_DIR/stereo_composite.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/text.vert" -o "$OUT_DIR/text.vert.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/text.frag" -o "$OUT_DIR/text.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/debug_line.vert" -o "$OUT_DIR/debug_line.vert.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/debug_line.frag" -o "$OUT_DIR/debug_line.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/skybox.vert" -o "$OUT_DIR/skybox.vert.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/skybox.frag" -o "$OUT_DIR/skybox.frag.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/indirect_cull.comp" -o "$OUT_DIR/indirect_cull.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/light_cluster.comp" -o "$OUT_DIR/light_cluster.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/ibl_irradiance.comp" -o "$OUT_DIR/ibl_irradiance.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/ibl_prefilter.comp" -o "$OUT_DIR/ibl_prefilter.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/ibl_brdf.comp" -o "$OUT_DIR/ibl_brdf.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/fsr1_easu.comp" -o "$OUT_DIR/fsr1_easu.comp.spv" $TARGET_ENV -O
+$GLSLC "$SRC_DIR/fsr1_rcas.comp" -o "$OUT_DIR/fsr1_rcas.comp.spv" $TARGET_ENV -O
 echo "Shaders built to $OUT_DIR"